@@ -282,15 +282,23 @@ pub fn instructions(Instructions { prefixes, normal }: Instructions) -> TokenStr
         build_sizes(&v.ident.to_string(), &composed_map);
     }
 
-    let bytesize = base_sizes.into_iter().map(|(id, (base_size, num_fields))| {
-        let fields: Vec<_> = (0..num_fields).map(build_ident('f')).collect();
-        let var = make_variant(&id, fields.iter());
-
-        // include the base instruction size, then the bytesize of each field
-        let sizes = std::iter::once(quote! { #base_size }).chain(fields.into_iter().map(|f| quote! { #f.bytesize() }));
-        // join with pluses to add
-        quote! { Instruction::#var => #(#sizes)+* }
-    });
+    let (bytesize, tokens): (Vec<_>, Vec<_>) = base_sizes
+        .into_iter()
+        .map(|(id, (base_size, num_fields))| {
+            let fields: Vec<_> = (0..num_fields).map(build_ident('f')).collect();
+            let var = make_variant(&id, fields.iter());
+
+            // include the base instruction size, then the bytesize of each field
+            let sizes = std::iter::once(quote! { #base_size }).chain(fields.iter().map(|f| quote! { #f.bytesize() }));
+            // join with pluses to add
+            let bytesize = quote! { Instruction::#var => #(#sizes)+* };
+
+            // the fields get bound by reference here, since we're matching on &mut self
+            let tokens = quote! { Instruction::#var => { #( tokens.extend(#fields.token_mut()); )* } };
+
+            (bytesize, tokens)
+        })
+        .unzip();
 
     let mut build_writes = |prefix: &Variant, input_map: &FieldsMap| {
         let prefix_name = prefix.ident.to_string();
@@ -353,6 +361,10 @@ pub fn instructions(Instructions { prefixes, normal }: Instructions) -> TokenStr
             fn parse(from: &[u8], offset: &mut usize) -> scroll::Result<Self>;
             fn write(self, into: &mut scroll_buffer::DynamicBuffer, offset: &mut usize) -> scroll::Result<()>;
             fn bytesize(&self) -> usize;
+
+            fn token_mut(&mut self) -> Option<&mut Token> {
+                None
+            }
         }
 
         #(
@@ -385,6 +397,10 @@ pub fn instructions(Instructions { prefixes, normal }: Instructions) -> TokenStr
             fn bytesize(&self) -> usize {
                 4
             }
+
+            fn token_mut(&mut self) -> Option<&mut Token> {
+                Some(self)
+            }
         }
 
         // the Switch instruction is the only usage of this
@@ -422,6 +438,15 @@ pub fn instructions(Instructions { prefixes, normal }: Instructions) -> TokenStr
                     #(#bytesize),*
                 }
             }
+
+            /// The metadata tokens that this instruction refers to, including those of its prefixes.
+            pub fn tokens_mut(&mut self) -> Vec<&mut Token> {
+                let mut tokens = vec![];
+                match self {
+                    #(#tokens),*
+                }
+                tokens
+            }
         }
 
         impl scroll::ctx::TryFromCtx<'_> for Instruction {
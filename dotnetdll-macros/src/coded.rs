@@ -77,13 +77,14 @@ pub fn coded_index(CodedIndex { name, tables }: CodedIndex) -> proc_macro2::Toke
                     from.gread_with::<u32>(offset, scroll::LE)?
                 };
 
-                if coded == 0 {
-                    return Ok((#name::Null, *offset));
-                }
-
                 let mask = (1 << #log) - 1;
                 let index = (coded >> #log) as usize;
 
+                // row indices are 1-based, so index 0 is null whatever table the tag names
+                if index == 0 {
+                    return Ok((#name::Null, *offset));
+                }
+
                 let val = match (coded & mask) as usize {
                     #(#from_match_arms,)*
                     bad_tag => throw!("bad {} coded index tag {}", stringify!(#name), bad_tag)
@@ -165,6 +165,22 @@ pub fn r_instructions(Instructions(is): Instructions) -> TokenStream {
             }
         });
 
+    let visit_arms = is.iter().zip(names.iter()).filter(|(i, _)| !i.fields.is_empty()).map(
+        |(Instruction { flags, name, .. }, (_, field_names))| {
+            let pattern = if flags.is_empty() {
+                quote! { (#(#field_names),*) }
+            } else {
+                quote! { { #(#field_names,)* .. } }
+            };
+
+            quote! {
+                Instruction::#name #pattern => {
+                    #(InstructionOperand::visit(#field_names, visitor);)*
+                }
+            }
+        },
+    );
+
     let constructors = is
         .iter()
         .zip(names.iter())
@@ -211,6 +227,14 @@ pub fn r_instructions(Instructions(is): Instructions) -> TokenStream {
 
         impl Instruction {
             #(#constructors)*
+
+            /// Calls `visitor` on every type, method, field and signature operand of the instruction.
+            pub fn visit_operands(&mut self, visitor: &mut impl OperandVisitor) {
+                match self {
+                    #(#visit_arms),*
+                    _ => {}
+                }
+            }
         }
     }
 }
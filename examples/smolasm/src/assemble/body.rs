@@ -0,0 +1,767 @@
+use dotnetdll::prelude::*;
+use std::collections::HashMap;
+
+use super::types::{bits, unsigned, Core, Generics};
+use super::Assembler;
+use crate::ast::{self, Spanned};
+use crate::error::{Error, Result, Span};
+
+use body::{DataSection, Exception, ExceptionKind};
+use debug::{LocalScope, LocalVariableName, MethodDebugInformation};
+use Instruction as I;
+
+// the state of a method body while it is being lowered
+#[derive(Default)]
+struct Body {
+    labels: HashMap<String, usize>,
+    arguments: Vec<Option<String>>,
+    locals: Vec<LocalVariable>,
+    local_names: Vec<LocalVariableName>,
+    initialize_locals: bool,
+    maximum_stack_size: Option<usize>,
+    instructions: Vec<Instruction>,
+    exceptions: Vec<Exception>,
+    prefixes: Vec<Spanned<ast::Prefix>>,
+    present: bool,
+}
+impl Body {
+    fn label(&self, name: &ast::Name) -> Result<usize> {
+        self.labels
+            .get(&name.value)
+            .copied()
+            .ok_or_else(|| Error::semantic(name.span, format!("label {} is not defined", name.value)))
+    }
+
+    fn no_prefixes(&self) -> Result<()> {
+        match self.prefixes.first() {
+            Some(p) => Err(Error::semantic(p.span, "a prefix must be followed by an instruction")),
+            None => Ok(()),
+        }
+    }
+}
+
+fn conversion(name: &str) -> Option<ConversionType> {
+    use ConversionType::*;
+    Some(match name {
+        "i1" => Int8,
+        "u1" => UInt8,
+        "i2" => Int16,
+        "u2" => UInt16,
+        "i4" => Int32,
+        "u4" => UInt32,
+        "i8" => Int64,
+        "u8" => UInt64,
+        "i" => IntPtr,
+        "u" => UIntPtr,
+        _ => return None,
+    })
+}
+
+fn load_type(name: &str) -> Option<LoadType> {
+    use LoadType::*;
+    Some(match name {
+        "i1" => Int8,
+        "u1" => UInt8,
+        "i2" => Int16,
+        "u2" => UInt16,
+        "i4" => Int32,
+        "u4" => UInt32,
+        // there is no separate unsigned 64-bit load, but the alias is accepted like ilasm does
+        "i8" | "u8" => Int64,
+        "r4" => Float32,
+        "r8" => Float64,
+        "i" => IntPtr,
+        "ref" => Object,
+        _ => return None,
+    })
+}
+
+fn store_type(name: &str) -> Option<StoreType> {
+    use StoreType::*;
+    Some(match name {
+        "i1" => Int8,
+        "i2" => Int16,
+        "i4" => Int32,
+        "i8" => Int64,
+        "r4" => Float32,
+        "r8" => Float64,
+        "i" => IntPtr,
+        "ref" => Object,
+        _ => return None,
+    })
+}
+
+// instructions without operands
+fn simple(mnemonic: &str) -> Option<Instruction> {
+    use NumberSign::{Signed, Unsigned};
+
+    // the variants of a signed operation, which may end in `.un`
+    let (base, sign) = match mnemonic.strip_suffix(".un") {
+        Some(b) => (b, Unsigned),
+        None => (mnemonic, Signed),
+    };
+    if let Some(t) = base.strip_prefix("conv.ovf.") {
+        return conversion(t).map(|c| I::ConvertOverflow(c, sign));
+    }
+    let signed = match base {
+        "add.ovf" => Some(I::AddOverflow(sign)),
+        "sub.ovf" => Some(I::SubtractOverflow(sign)),
+        "mul.ovf" => Some(I::MultiplyOverflow(sign)),
+        "div" => Some(I::Divide(sign)),
+        "rem" => Some(I::Remainder(sign)),
+        "shr" => Some(I::ShiftRight(sign)),
+        "cgt" => Some(I::CompareGreater(sign)),
+        "clt" => Some(I::CompareLess(sign)),
+        _ => None,
+    };
+    if signed.is_some() {
+        return signed;
+    }
+
+    if let Some((kind, t)) = mnemonic.split_once('.') {
+        let typed = match kind {
+            "conv" if t == "r4" => Some(I::ConvertFloat32),
+            "conv" if t == "r8" => Some(I::ConvertFloat64),
+            "conv" if t == "r.un" => Some(I::ConvertUnsignedToFloat),
+            "conv" => conversion(t).map(I::Convert),
+            "ldind" => load_type(t).map(I::load_indirect),
+            "stind" => store_type(t).map(I::store_indirect),
+            "ldelem" => load_type(t).map(I::load_element_primitive),
+            "stelem" => store_type(t).map(I::store_element_primitive),
+            _ => None,
+        };
+        if typed.is_some() {
+            return typed;
+        }
+    }
+
+    Some(match mnemonic {
+        "nop" => I::NoOperation,
+        "break" => I::Breakpoint,
+        "ldarg.0" => I::LoadArgument(0),
+        "ldarg.1" => I::LoadArgument(1),
+        "ldarg.2" => I::LoadArgument(2),
+        "ldarg.3" => I::LoadArgument(3),
+        "ldloc.0" => I::LoadLocal(0),
+        "ldloc.1" => I::LoadLocal(1),
+        "ldloc.2" => I::LoadLocal(2),
+        "ldloc.3" => I::LoadLocal(3),
+        "stloc.0" => I::StoreLocal(0),
+        "stloc.1" => I::StoreLocal(1),
+        "stloc.2" => I::StoreLocal(2),
+        "stloc.3" => I::StoreLocal(3),
+        "ldnull" => I::LoadNull,
+        "ldc.i4.m1" | "ldc.i4.M1" => I::LoadConstantInt32(-1),
+        "ldc.i4.0" => I::LoadConstantInt32(0),
+        "ldc.i4.1" => I::LoadConstantInt32(1),
+        "ldc.i4.2" => I::LoadConstantInt32(2),
+        "ldc.i4.3" => I::LoadConstantInt32(3),
+        "ldc.i4.4" => I::LoadConstantInt32(4),
+        "ldc.i4.5" => I::LoadConstantInt32(5),
+        "ldc.i4.6" => I::LoadConstantInt32(6),
+        "ldc.i4.7" => I::LoadConstantInt32(7),
+        "ldc.i4.8" => I::LoadConstantInt32(8),
+        "dup" => I::Duplicate,
+        "pop" => I::Pop,
+        "ret" => I::Return,
+        "add" => I::Add,
+        "sub" => I::Subtract,
+        "mul" => I::Multiply,
+        "and" => I::And,
+        "or" => I::Or,
+        "xor" => I::Xor,
+        "shl" => I::ShiftLeft,
+        "neg" => I::Negate,
+        "not" => I::Not,
+        "throw" => I::Throw,
+        "rethrow" => I::Rethrow,
+        "ldlen" => I::LoadLength,
+        "ckfinite" => I::CheckFinite,
+        "endfinally" | "endfault" => I::EndFinally,
+        "endfilter" => I::EndFilter,
+        "localloc" => I::LocalMemoryAllocate,
+        "arglist" => I::ArgumentList,
+        "ceq" => I::CompareEqual,
+        "cpblk" => I::copy_memory_block(),
+        "initblk" => I::initialize_memory_block(),
+        "refanytype" => I::ReadTypedReferenceType,
+        _ => return None,
+    })
+}
+
+fn alignment(value: &ast::Int) -> Result<Alignment> {
+    match value.value {
+        1 => Ok(Alignment::Byte),
+        2 => Ok(Alignment::Double),
+        4 => Ok(Alignment::Quad),
+        _ => Err(Error::semantic(value.span, "alignments must be 1, 2 or 4")),
+    }
+}
+
+// the `unaligned.` and `volatile.` slots of an instruction
+fn memory_flags(i: &mut Instruction) -> Option<(Option<&mut Option<Alignment>>, &mut bool)> {
+    match i {
+        I::CopyMemoryBlock { unaligned, volatile }
+        | I::InitializeMemoryBlock { unaligned, volatile }
+        | I::LoadIndirect {
+            unaligned, volatile, ..
+        }
+        | I::StoreIndirect {
+            unaligned, volatile, ..
+        }
+        | I::LoadField {
+            unaligned, volatile, ..
+        }
+        | I::StoreField {
+            unaligned, volatile, ..
+        }
+        | I::LoadObject {
+            unaligned, volatile, ..
+        }
+        | I::StoreObject {
+            unaligned, volatile, ..
+        } => Some((Some(unaligned), volatile)),
+        I::LoadStaticField { volatile, .. } | I::StoreStaticField { volatile, .. } => Some((None, volatile)),
+        _ => None,
+    }
+}
+
+// the check of a `no.` prefix with the given bit
+fn check_flag(i: &mut Instruction, bit: i128) -> Option<&mut bool> {
+    match (bit, i) {
+        (
+            1,
+            I::CastClass { skip_type_check, .. }
+            | I::UnboxIntoAddress { skip_type_check, .. }
+            | I::LoadElementAddress { skip_type_check, .. }
+            | I::StoreElement { skip_type_check, .. }
+            | I::StoreElementPrimitive { skip_type_check, .. },
+        ) => Some(skip_type_check),
+        (
+            2,
+            I::LoadElement { skip_range_check, .. }
+            | I::LoadElementPrimitive { skip_range_check, .. }
+            | I::LoadElementAddress { skip_range_check, .. }
+            | I::StoreElement { skip_range_check, .. }
+            | I::StoreElementPrimitive { skip_range_check, .. },
+        ) => Some(skip_range_check),
+        (
+            4,
+            I::CallVirtual { skip_null_check, .. }
+            | I::LoadVirtualMethodPointer { skip_null_check, .. }
+            | I::LoadElement { skip_null_check, .. }
+            | I::LoadElementPrimitive { skip_null_check, .. }
+            | I::LoadElementAddress { skip_null_check, .. }
+            | I::StoreElement { skip_null_check, .. }
+            | I::StoreElementPrimitive { skip_null_check, .. },
+        ) => Some(skip_null_check),
+        _ => None,
+    }
+}
+
+fn apply_prefix(
+    instruction: Instruction,
+    prefix: Spanned<ast::Prefix>,
+    constraint: Option<MethodType>,
+) -> Result<Instruction> {
+    let invalid = || Error::semantic(prefix.span, "this prefix cannot be applied to the instruction after it");
+
+    let mut instruction = instruction;
+    match prefix.value {
+        ast::Prefix::Tail => {
+            return match instruction {
+                I::Call {
+                    tail_call: false,
+                    param0,
+                } => Ok(I::Call {
+                    tail_call: true,
+                    param0,
+                }),
+                I::CallIndirect {
+                    tail_call: false,
+                    param0,
+                } => Ok(I::CallIndirect {
+                    tail_call: true,
+                    param0,
+                }),
+                I::CallVirtual {
+                    skip_null_check: false,
+                    param0,
+                } => Ok(I::CallVirtualTail(param0)),
+                _ => Err(invalid()),
+            }
+        }
+        ast::Prefix::Constrained(_) => {
+            let constraint = constraint.unwrap();
+            return match instruction {
+                I::Call {
+                    tail_call: false,
+                    param0,
+                } => Ok(I::CallConstrained(constraint, param0)),
+                I::CallVirtual {
+                    skip_null_check: false,
+                    param0,
+                } => Ok(I::CallVirtualConstrained(constraint, param0)),
+                _ => Err(invalid()),
+            };
+        }
+        ast::Prefix::Readonly => {
+            return match instruction {
+                I::LoadElementAddress {
+                    skip_type_check: false,
+                    skip_range_check: false,
+                    skip_null_check: false,
+                    param0,
+                } => Ok(I::LoadElementAddressReadonly(param0)),
+                _ => Err(invalid()),
+            }
+        }
+        ast::Prefix::Unaligned(a) => match memory_flags(&mut instruction) {
+            Some((Some(unaligned @ None), _)) => *unaligned = Some(alignment(&a)?),
+            _ => return Err(invalid()),
+        },
+        ast::Prefix::Volatile => match memory_flags(&mut instruction) {
+            Some((_, volatile @ false)) => *volatile = true,
+            _ => return Err(invalid()),
+        },
+        ast::Prefix::No(mask) => {
+            if !(1..=7).contains(&mask.value) {
+                return Err(Error::semantic(
+                    mask.span,
+                    "expected a combination of typecheck (1), rangecheck (2) and nullcheck (4)",
+                ));
+            }
+            // fields have separate instructions that skip the null check, without any other flags
+            if mask.value == 4 {
+                match instruction {
+                    I::LoadField {
+                        unaligned: None,
+                        volatile: false,
+                        param0,
+                    } => return Ok(I::LoadFieldSkipNullCheck(param0)),
+                    I::StoreField {
+                        unaligned: None,
+                        volatile: false,
+                        param0,
+                    } => return Ok(I::StoreFieldSkipNullCheck(param0)),
+                    _ => {}
+                }
+            }
+            for bit in [1, 2, 4] {
+                if mask.value & bit == 0 {
+                    continue;
+                }
+                match check_flag(&mut instruction, bit) {
+                    Some(flag @ false) => *flag = true,
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+    }
+    Ok(instruction)
+}
+
+impl Assembler {
+    fn local(&mut self, local: &ast::Local, g: Generics) -> Result<LocalVariable> {
+        let mut lowered = self.lower_type::<MethodType>(&local.local_type, g)?;
+        let span = lowered.span;
+        let custom_modifiers = std::mem::take(&mut lowered.modifiers);
+        Ok(match lowered.core {
+            Core::Type(var_type) => LocalVariable::Variable {
+                custom_modifiers,
+                pinned: lowered.pinned,
+                by_ref: lowered.by_ref,
+                var_type,
+            },
+            Core::TypedRef if custom_modifiers.is_empty() && !lowered.by_ref && !lowered.pinned => {
+                LocalVariable::TypedReference
+            }
+            Core::TypedRef => return Err(Error::semantic(span, "typedref locals cannot be modified")),
+            Core::Void => return Err(Error::semantic(span, "locals cannot be void")),
+        })
+    }
+
+    // collects the labels and locals of a body, which can be used before they are declared
+    fn scan(&mut self, body: &mut Body, items: &[ast::MethodItem], count: &mut usize, g: Generics) -> Result<()> {
+        for item in items {
+            match item {
+                ast::MethodItem::MaxStack(n) => {
+                    let size = unsigned(n, "stack sizes")?;
+                    if body.maximum_stack_size.replace(size).is_some() {
+                        return Err(Error::semantic(n.span, ".maxstack can only be declared once"));
+                    }
+                }
+                ast::MethodItem::Locals { init, variables, .. } => {
+                    body.initialize_locals |= init;
+                    for v in variables {
+                        let index = body.locals.len();
+                        if let Some(slot) = &v.slot {
+                            if slot.value != index as i128 {
+                                return Err(Error::semantic(
+                                    slot.span,
+                                    format!("expected local slot {}, since slots must be declared in order", index),
+                                ));
+                            }
+                        }
+                        if let Some(name) = &v.name {
+                            if body.local_names.iter().any(|l| l.name == name.value) {
+                                return Err(Error::semantic(
+                                    name.span,
+                                    format!("local {} is already declared", name.value),
+                                ));
+                            }
+                            body.local_names.push(LocalVariableName::new(index, name.value.clone()));
+                        }
+                        let local = self.local(v, g)?;
+                        body.locals.push(local);
+                    }
+                }
+                ast::MethodItem::Try(t) => {
+                    let regions = std::iter::once(&t.region).chain(t.handlers.iter().flat_map(|h| {
+                        let filter = match &h.kind {
+                            ast::HandlerKind::Filter(ast::Filter::Block(r)) => Some(r),
+                            _ => None,
+                        };
+                        filter.into_iter().chain([&h.region])
+                    }));
+                    for r in regions {
+                        if let ast::Region::Block(items, _) = r {
+                            self.scan(body, items, count, g)?;
+                        }
+                    }
+                }
+                ast::MethodItem::Label(l) => {
+                    if body.labels.insert(l.value.clone(), *count).is_some() {
+                        return Err(Error::semantic(l.span, format!("label {} is already defined", l.value)));
+                    }
+                }
+                ast::MethodItem::Instruction(_) => *count += 1,
+                ast::MethodItem::Prefix(_) => {}
+                _ => continue,
+            }
+            body.present = true;
+        }
+        Ok(())
+    }
+
+    fn variable(&self, body: &Body, instruction: &ast::Instruction, v: &ast::VariableRef) -> Result<u16> {
+        let mnemonic = instruction.mnemonic.value.as_str();
+        let (base, short) = match mnemonic.strip_suffix(".s") {
+            Some(b) => (b, true),
+            None => (mnemonic, false),
+        };
+        let (what, count) = if base.ends_with("arg") || base.ends_with("arga") {
+            ("argument", body.arguments.len())
+        } else {
+            ("local", body.locals.len())
+        };
+
+        let (index, span) = match v {
+            ast::VariableRef::Index(i) => (i.value, i.span),
+            ast::VariableRef::Name(n) => {
+                let found = if what == "argument" {
+                    body.arguments
+                        .iter()
+                        .position(|a| a.as_deref() == Some(n.value.as_str()))
+                } else {
+                    body.local_names.iter().find(|l| l.name == n.value).map(|l| l.index)
+                };
+                match found {
+                    Some(i) => (i as i128, n.span),
+                    None => {
+                        return Err(Error::semantic(
+                            n.span,
+                            format!("there is no {} named {}", what, n.value),
+                        ))
+                    }
+                }
+            }
+        };
+
+        if !(0..count as i128).contains(&index) {
+            return Err(Error::semantic(span, format!("{} {} does not exist", what, index)));
+        }
+        let max = if short { u8::MAX.into() } else { u16::MAX };
+        match u16::try_from(index) {
+            Ok(i) if i <= max => Ok(i),
+            _ => Err(Error::semantic(
+                span,
+                format!("{} does not fit into the operand of {}", index, mnemonic),
+            )),
+        }
+    }
+
+    #[allow(clippy::too_many_lines, clippy::cast_possible_truncation)]
+    fn instruction(&mut self, body: &Body, i: &ast::Instruction, g: Generics) -> Result<Instruction> {
+        use ast::Operand;
+        use NumberSign::{Signed, Unsigned};
+
+        let mnemonic = i.mnemonic.value.as_str();
+        let base = mnemonic.strip_suffix(".s").unwrap_or(mnemonic);
+        Ok(match &i.operand {
+            Operand::None => simple(mnemonic).ok_or_else(|| {
+                Error::semantic(
+                    i.mnemonic.span,
+                    format!("{} is not an instruction that takes no operand", mnemonic),
+                )
+            })?,
+            Operand::Variable(v) => {
+                let index = self.variable(body, i, v)?;
+                match base {
+                    "ldarg" => I::LoadArgument(index),
+                    "ldarga" => I::LoadArgumentAddress(index),
+                    "starg" => I::StoreArgument(index),
+                    "ldloc" => I::LoadLocal(index),
+                    "ldloca" => I::LoadLocalAddress(index),
+                    _ => I::StoreLocal(index),
+                }
+            }
+            Operand::Int(n) => match mnemonic {
+                "ldc.i4" => I::LoadConstantInt32(bits(n.value, 32, n.span)? as u32 as i32),
+                "ldc.i4.s" => I::LoadConstantInt32(i32::from(bits(n.value, 8, n.span)? as u8 as i8)),
+                _ => I::LoadConstantInt64(bits(n.value, 64, n.span)? as i64),
+            },
+            Operand::Float(f) => {
+                let is_float32 = mnemonic == "ldc.r4";
+                let value = match &f.value {
+                    ast::Float::Literal(v) => *v,
+                    #[allow(clippy::cast_precision_loss)]
+                    ast::Float::Integer(v) => *v as f64,
+                    ast::Float::Bits(kind, value) => {
+                        let expected = if is_float32 { "float32" } else { "float64" };
+                        if kind.value != expected {
+                            return Err(Error::semantic(kind.span, format!("expected {}(...)", expected)));
+                        }
+                        if is_float32 {
+                            f64::from(f32::from_bits(bits(value.value, 32, value.span)? as u32))
+                        } else {
+                            f64::from_bits(bits(value.value, 64, value.span)?)
+                        }
+                    }
+                    ast::Float::Bytes(b) => match (is_float32, b.len()) {
+                        (true, 4) => f64::from(f32::from_le_bytes(b[..].try_into().unwrap())),
+                        (false, 8) => f64::from_le_bytes(b[..].try_into().unwrap()),
+                        _ => {
+                            return Err(Error::semantic(
+                                f.span,
+                                format!("expected {} bytes", if is_float32 { 4 } else { 8 }),
+                            ))
+                        }
+                    },
+                };
+                if is_float32 {
+                    I::LoadConstantFloat32(value as f32)
+                } else {
+                    I::LoadConstantFloat64(value)
+                }
+            }
+            Operand::Branch(l) => {
+                let target = body.label(l)?;
+                match base {
+                    "br" => I::Branch(target),
+                    "leave" => I::Leave(target),
+                    "brfalse" | "brzero" | "brnull" => I::BranchFalsy(target),
+                    "brtrue" | "brinst" => I::BranchTruthy(target),
+                    "beq" => I::BranchEqual(target),
+                    "bne.un" => I::BranchNotEqual(target),
+                    "bge" => I::BranchGreaterOrEqual(Signed, target),
+                    "bge.un" => I::BranchGreaterOrEqual(Unsigned, target),
+                    "bgt" => I::BranchGreater(Signed, target),
+                    "bgt.un" => I::BranchGreater(Unsigned, target),
+                    "ble" => I::BranchLessOrEqual(Signed, target),
+                    "ble.un" => I::BranchLessOrEqual(Unsigned, target),
+                    "blt" => I::BranchLess(Signed, target),
+                    _ => I::BranchLess(Unsigned, target),
+                }
+            }
+            Operand::Method(m) if mnemonic == "newobj" => I::NewObject(self.user_method(m, g)?),
+            Operand::Method(m) => {
+                let method = self.method_ref(m, g)?;
+                match mnemonic {
+                    "call" => I::call(method),
+                    "callvirt" => I::call_virtual(method),
+                    "jmp" => I::Jump(method),
+                    "ldftn" => I::LoadMethodPointer(method),
+                    _ => I::load_virtual_method_pointer(method),
+                }
+            }
+            Operand::Field(f) => {
+                let field = self.field_ref(f, g)?;
+                match mnemonic {
+                    "ldfld" => I::load_field(field),
+                    "ldflda" => I::LoadFieldAddress(field),
+                    "stfld" => I::store_field(field),
+                    "ldsfld" => I::load_static_field(field),
+                    "ldsflda" => I::LoadStaticFieldAddress(field),
+                    _ => I::store_static_field(field),
+                }
+            }
+            Operand::Type(t) => {
+                let t: MethodType = self.type_spec(t, g)?;
+                match mnemonic {
+                    "box" => I::BoxValue(t),
+                    "castclass" => I::cast_class(t),
+                    "cpobj" => I::CopyObject(t),
+                    "initobj" => I::InitializeForObject(t),
+                    "isinst" => I::IsInstance(t),
+                    "ldelem" => I::load_element(t),
+                    "ldelema" => I::load_element_address(t),
+                    "ldobj" => I::load_object(t),
+                    "mkrefany" => I::MakeTypedReference(t),
+                    "newarr" => I::NewArray(t),
+                    "refanyval" => I::ReadTypedReferenceValue(t),
+                    "sizeof" => I::Sizeof(t),
+                    "stelem" => I::store_element(t),
+                    "stobj" => I::store_object(t),
+                    "unbox" => I::unbox_into_address(t),
+                    _ => I::UnboxIntoValue(t),
+                }
+            }
+            Operand::String(s) => I::LoadString(s.clone()),
+            Operand::Signature(sig) => I::call_indirect(self.standalone_signature(
+                &sig.call_conv,
+                &sig.return_type,
+                &sig.parameters,
+                i.span,
+                g,
+            )?),
+            Operand::Token(ast::Token::Method(m)) => I::LoadTokenMethod(self.method_ref(m, g)?),
+            Operand::Token(ast::Token::Field(f)) => I::LoadTokenField(self.field_ref(f, g)?),
+            Operand::Token(ast::Token::Type(t)) => I::LoadTokenType(self.type_spec(t, g)?),
+            Operand::Switch(labels) => I::Switch(labels.iter().map(|l| body.label(l)).collect::<Result<_>>()?),
+        })
+    }
+
+    // the offset and length of a protected region or handler
+    fn region(&mut self, body: &mut Body, region: &ast::Region, g: Generics) -> Result<(usize, usize)> {
+        match region {
+            ast::Region::Labels(start, end) => {
+                let (s, e) = (body.label(start)?, body.label(end)?);
+                if e < s {
+                    return Err(Error::semantic(
+                        Span::new(start.span.start, end.span.end),
+                        "the end of a region must come after its start",
+                    ));
+                }
+                Ok((s, e - s))
+            }
+            ast::Region::Block(items, _) => {
+                let start = body.instructions.len();
+                self.emit(body, items, true, g)?;
+                Ok((start, body.instructions.len() - start))
+            }
+        }
+    }
+
+    fn emit(&mut self, body: &mut Body, items: &[ast::MethodItem], nested: bool, g: Generics) -> Result<()> {
+        for item in items {
+            match item {
+                ast::MethodItem::Instruction(i) => {
+                    let mut instruction = self.instruction(body, i, g)?;
+                    for p in std::mem::take(&mut body.prefixes) {
+                        let constraint = match &p.value {
+                            ast::Prefix::Constrained(t) => Some(self.type_spec(t, g)?),
+                            _ => None,
+                        };
+                        instruction = apply_prefix(instruction, p, constraint)?;
+                    }
+                    body.instructions.push(instruction);
+                }
+                ast::MethodItem::Prefix(p) => body.prefixes.push(p.clone()),
+                ast::MethodItem::Label(_) => body.no_prefixes()?,
+                ast::MethodItem::Try(t) => {
+                    body.no_prefixes()?;
+                    let (try_offset, try_length) = self.region(body, &t.region, g)?;
+                    let mut handlers = vec![];
+                    for h in &t.handlers {
+                        let kind = match &h.kind {
+                            ast::HandlerKind::Catch(t) => ExceptionKind::TypedException(self.type_spec(t, g)?),
+                            ast::HandlerKind::Filter(ast::Filter::Label(l)) => {
+                                ExceptionKind::Filter { offset: body.label(l)? }
+                            }
+                            ast::HandlerKind::Filter(ast::Filter::Block(r)) => ExceptionKind::Filter {
+                                offset: self.region(body, r, g)?.0,
+                            },
+                            ast::HandlerKind::Finally => ExceptionKind::Finally,
+                            ast::HandlerKind::Fault => ExceptionKind::Fault,
+                        };
+                        let (handler_offset, handler_length) = self.region(body, &h.region, g)?;
+                        handlers.push(Exception {
+                            kind,
+                            try_offset,
+                            try_length,
+                            handler_offset,
+                            handler_length,
+                        });
+                    }
+                    // handlers of the blocks inside come first, as they must be listed innermost first
+                    body.exceptions.extend(handlers);
+                }
+                ast::MethodItem::MaxStack(_) | ast::MethodItem::Locals { .. } => {}
+                ast::MethodItem::EntryPoint(span) | ast::MethodItem::Param(_, span) if nested => {
+                    return Err(Error::semantic(*span, "this directive is not allowed inside a block"));
+                }
+                ast::MethodItem::Attribute(ast::Attribute::Custom(ast::Custom { span, .. }))
+                | ast::MethodItem::Attribute(ast::Attribute::PermissionSet(ast::PermissionSet { span, .. }))
+                | ast::MethodItem::Override(ast::Override { span, .. })
+                    if nested =>
+                {
+                    return Err(Error::semantic(*span, "this directive is not allowed inside a block"));
+                }
+                _ => {}
+            }
+        }
+        body.no_prefixes()
+    }
+
+    /// Lowers the body of a method, if it has one.
+    pub(super) fn method_body(
+        &mut self,
+        decl: &ast::MethodDecl,
+        index: MethodIndex,
+        g: Generics,
+    ) -> Result<Option<body::Method>> {
+        let method = &self.res[index];
+        let mut arguments = vec![];
+        if method.signature.instance && !method.signature.explicit_this {
+            arguments.push(None);
+        }
+        arguments.extend((0..method.signature.parameters.len()).map(|i| {
+            method
+                .parameter_metadata
+                .get(i)
+                .and_then(|m| m.as_ref()?.name.as_ref())
+                .map(|n| n.to_string())
+        }));
+
+        let mut body = Body {
+            arguments,
+            ..Body::default()
+        };
+        self.scan(&mut body, &decl.items, &mut 0, g)?;
+        if !body.present {
+            return Ok(None);
+        }
+        self.emit(&mut body, &decl.items, false, g)?;
+
+        let mut result = body::Method::new(body.instructions);
+        result.header.initialize_locals = body.initialize_locals;
+        result.header.local_variables = body.locals;
+        if let Some(size) = body.maximum_stack_size {
+            result.header.maximum_stack_size = size;
+        }
+        if !body.exceptions.is_empty() {
+            result
+                .data_sections
+                .push(DataSection::ExceptionHandlers(body.exceptions));
+        }
+        // local names only exist in debug information
+        if !body.local_names.is_empty() {
+            result.debug = Some(MethodDebugInformation {
+                scopes: vec![LocalScope::new(0, result.instructions.len(), body.local_names)],
+                ..MethodDebugInformation::default()
+            });
+        }
+        Ok(Some(result))
+    }
+}
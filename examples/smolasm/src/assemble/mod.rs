@@ -0,0 +1,1558 @@
+use dotnetdll::prelude::*;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::ast;
+use crate::error::{Error, Result, Span};
+use types::{bits, constant, marshal, unsigned, Generics};
+
+mod body;
+mod types;
+
+// the name types are looked up by, e.g. `System.Collections.Generic.Dictionary`2/Enumerator`
+fn type_key(namespace: Option<&str>, names: &[String]) -> String {
+    let nested = names.join("/");
+    match namespace {
+        Some(ns) => format!("{}.{}", ns, nested),
+        None => nested,
+    }
+}
+
+fn join_namespace(outer: Option<&str>, inner: Option<&str>) -> Option<String> {
+    match (outer, inner) {
+        (Some(o), Some(i)) => Some(format!("{}.{}", o, i)),
+        (o, i) => o.or(i).map(str::to_string),
+    }
+}
+
+fn member_access(flags: &[ast::Name]) -> Result<Option<MemberAccessibility>> {
+    let mut access = None;
+    for f in flags {
+        use Accessibility::*;
+        let a = match f.value.as_str() {
+            "privatescope" => MemberAccessibility::CompilerControlled,
+            "private" => MemberAccessibility::Access(Private),
+            "famandassem" => MemberAccessibility::Access(FamilyANDAssembly),
+            "assembly" => MemberAccessibility::Access(Assembly),
+            "family" => MemberAccessibility::Access(Family),
+            "famorassem" => MemberAccessibility::Access(FamilyORAssembly),
+            "public" => MemberAccessibility::Access(Public),
+            _ => continue,
+        };
+        if access.replace(a).is_some() {
+            return Err(Error::semantic(f.span, "conflicting accessibility flags"));
+        }
+    }
+    Ok(access)
+}
+
+fn version(v: &[ast::Int; 4]) -> Result<Version> {
+    let part = |i: &ast::Int| {
+        u16::try_from(i.value).map_err(|_| Error::semantic(i.span, "version numbers must fit into 16 bits"))
+    };
+    Ok(Version {
+        major: part(&v[0])?,
+        minor: part(&v[1])?,
+        build: part(&v[2])?,
+        revision: part(&v[3])?,
+    })
+}
+
+fn type_flags(flags: &[ast::Name], nested: bool) -> Result<TypeFlags> {
+    let mut result = TypeFlags {
+        accessibility: if nested {
+            TypeAccessibility::Nested(Accessibility::Private)
+        } else {
+            TypeAccessibility::NotPublic
+        },
+        ..TypeFlags::default()
+    };
+
+    for f in flags {
+        let word = f.value.as_str();
+        if let Some(access) = word.strip_prefix("nested ") {
+            if !nested {
+                return Err(Error::semantic(f.span, "only nested types can have nested visibility"));
+            }
+            use Accessibility::*;
+            result.accessibility = TypeAccessibility::Nested(match access {
+                "public" => Public,
+                "private" => Private,
+                "family" => Family,
+                "assembly" => Assembly,
+                "famandassem" => FamilyANDAssembly,
+                _ => FamilyORAssembly,
+            });
+            continue;
+        }
+
+        match word {
+            "public" | "private" if nested => {
+                return Err(Error::semantic(
+                    f.span,
+                    format!("nested types must be declared `nested {}`", word),
+                ))
+            }
+            "public" => result.accessibility = TypeAccessibility::Public,
+            "private" => result.accessibility = TypeAccessibility::NotPublic,
+            "interface" => result.kind = Kind::Interface,
+            "auto" => result.layout = Layout::Automatic,
+            "sequential" => result.layout = Layout::Sequential(None),
+            "explicit" => result.layout = Layout::Explicit(None),
+            "ansi" => result.string_formatting = StringFormatting::ANSI,
+            "unicode" => result.string_formatting = StringFormatting::Unicode,
+            "autochar" => result.string_formatting = StringFormatting::Automatic,
+            "abstract" => result.abstract_type = true,
+            "sealed" => result.sealed = true,
+            "specialname" => result.special_name = true,
+            "rtspecialname" => result.runtime_special_name = true,
+            "import" => result.imported = true,
+            "serializable" => result.serializable = true,
+            _ => result.before_field_init = true,
+        }
+    }
+
+    Ok(result)
+}
+
+fn security_action(action: &ast::SecurityAction) -> Result<u16> {
+    Ok(match action {
+        ast::SecurityAction::Named(n) => match n.value.as_str() {
+            "request" => 1,
+            "demand" => 2,
+            "assert" => 3,
+            "deny" => 4,
+            "permitonly" => 5,
+            "linkcheck" => 6,
+            "inheritcheck" => 7,
+            "reqmin" => 8,
+            "reqopt" => 9,
+            "reqrefuse" => 10,
+            "prejitgrant" => 11,
+            "prejitdeny" => 12,
+            "noncasdemand" => 13,
+            "noncaslinkdemand" => 14,
+            _ => 15,
+        },
+        #[allow(clippy::cast_possible_truncation)]
+        ast::SecurityAction::Value(i) => bits(i.value, 16, i.span)? as u16,
+    })
+}
+
+fn data(decl: &ast::DataDecl) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    for item in &decl.items {
+        match item {
+            ast::DataItem::Bytes(b) => buf.extend_from_slice(b),
+            ast::DataItem::Value {
+                data_type,
+                value,
+                count,
+            } => {
+                let count = count.as_ref().map_or(Ok(1), |c| unsigned(c, "repetition counts"))?;
+                let span = value.as_ref().map_or(data_type.span, |v| v.span);
+                let value = value.as_ref().map(|v| &v.value);
+
+                #[allow(clippy::cast_possible_truncation)]
+                let bytes = match (data_type.value.as_str(), value) {
+                    ("float32", Some(ast::ConstantValue::Float(f))) => (*f as f32).to_le_bytes().to_vec(),
+                    ("float64", Some(ast::ConstantValue::Float(f))) => f.to_le_bytes().to_vec(),
+                    (_, Some(ast::ConstantValue::Float(_))) => {
+                        return Err(Error::semantic(span, "expected an integer"));
+                    }
+                    (kind, value) => {
+                        let width = match kind {
+                            "int8" => 8,
+                            "int16" => 16,
+                            "int32" | "float32" => 32,
+                            _ => 64,
+                        };
+                        let value = match value {
+                            Some(ast::ConstantValue::Int(i)) => *i,
+                            _ => 0,
+                        };
+                        // integers given for floating point values are their bit patterns, like in constants
+                        bits(value, width, span)?.to_le_bytes()[..width as usize / 8].to_vec()
+                    }
+                };
+                for _ in 0..count {
+                    buf.extend_from_slice(&bytes);
+                }
+            }
+        }
+    }
+    Ok(buf)
+}
+
+#[derive(Debug, Copy, Clone)]
+enum SecurityOwner {
+    Assembly,
+    Type(TypeIndex),
+    Method(MethodIndex),
+}
+
+/// What a `.custom` directive applies to, which depends on the directives before it.
+#[derive(Debug, Copy, Clone)]
+enum Target {
+    Module,
+    Assembly,
+    AssemblyRef(AssemblyRefIndex),
+    File(FileIndex),
+    ExportedType(ExportedTypeIndex),
+    Resource(usize),
+    Type(TypeIndex),
+    Field(FieldIndex),
+    Method(MethodIndex),
+    /// A method's parameter, where 0 is the return value.
+    Parameter(MethodIndex, usize),
+    TypeGeneric(TypeIndex, usize),
+    MethodGeneric(MethodIndex, usize),
+    TypeConstraint(TypeIndex, usize, usize),
+    MethodConstraint(MethodIndex, usize, usize),
+    InterfaceImpl(TypeIndex, usize),
+    Property(PropertyIndex),
+    Event(EventIndex),
+    Security(SecurityOwner),
+}
+
+// a method declaration that has been lowered, but not yet placed in its type
+struct Pending {
+    name: String,
+    signature: ManagedMethod<MethodType>,
+    decl: usize,
+    method: Option<Method<'static>>,
+}
+
+pub(crate) struct Assembler {
+    res: Resolution<'static>,
+    assembly_name: Option<String>,
+    assemblies: HashMap<String, AssemblyRefIndex>,
+    modules: HashMap<String, ModuleRefIndex>,
+    files: HashMap<String, FileIndex>,
+    types: HashMap<String, TypeIndex>,
+    data: HashMap<String, Vec<u8>>,
+    // every method of a type, including property and event accessors
+    methods: HashMap<TypeIndex, Vec<MethodIndex>>,
+    // declarations by the start of their span
+    type_decls: HashMap<usize, TypeIndex>,
+    field_decls: HashMap<usize, FieldIndex>,
+    method_decls: HashMap<usize, MethodIndex>,
+    property_decls: HashMap<usize, PropertyIndex>,
+    event_decls: HashMap<usize, EventIndex>,
+}
+
+// the declarations of a source file, with namespaces applied
+struct Program<'a> {
+    top_level: Vec<&'a ast::Declaration>,
+    classes: Vec<(Option<String>, &'a ast::ClassDecl)>,
+}
+impl<'a> Program<'a> {
+    fn collect(&mut self, namespace: Option<&str>, declarations: &'a [ast::Declaration]) {
+        for d in declarations {
+            match d {
+                ast::Declaration::Namespace(name, inner) => {
+                    let namespace = join_namespace(namespace, Some(&name.value));
+                    self.collect(namespace.as_deref(), inner);
+                }
+                ast::Declaration::Class(c) => {
+                    self.classes
+                        .push((join_namespace(namespace, c.namespace.as_deref()), c));
+                    self.top_level.push(d);
+                }
+                _ => self.top_level.push(d),
+            }
+        }
+    }
+}
+
+impl Assembler {
+    fn module_type(&self) -> TypeIndex {
+        self.res.type_definition_index(0).unwrap()
+    }
+
+    fn is_module_type(&self, index: TypeIndex) -> bool {
+        index == self.module_type()
+    }
+
+    fn type_generics(&self, index: TypeIndex) -> Vec<String> {
+        self.res[index]
+            .generic_parameters
+            .iter()
+            .map(|g| g.name.to_string())
+            .collect()
+    }
+
+    fn data_label(&self, label: &ast::Name) -> Result<Vec<u8>> {
+        self.data
+            .get(&label.value)
+            .cloned()
+            .ok_or_else(|| Error::semantic(label.span, format!("no data is labeled {}", label.value)))
+    }
+
+    fn declare_data(&mut self, decl: &ast::DataDecl) -> Result<()> {
+        let bytes = data(decl)?;
+        if let Some(label) = &decl.label {
+            if self.data.insert(label.value.clone(), bytes).is_some() {
+                return Err(Error::semantic(
+                    label.span,
+                    format!("data label {} is already defined", label.value),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn module_reference_or_new(&mut self, name: &str) -> ModuleRefIndex {
+        match self.modules.get(name) {
+            Some(&m) => m,
+            None => {
+                let m = self
+                    .res
+                    .push_module_reference(ExternalModuleReference::new(name.to_string()));
+                self.modules.insert(name.to_string(), m);
+                m
+            }
+        }
+    }
+
+    // assemblies, modules, files, resources and data, which other declarations refer to by name
+    #[allow(clippy::too_many_lines)]
+    fn manifest(&mut self, program: &Program) -> Result<()> {
+        for d in &program.top_level {
+            match d {
+                ast::Declaration::AssemblyRef(a) => {
+                    let mut reference = ExternalAssemblyReference::new(a.name.value.clone());
+                    for item in &a.items {
+                        match item {
+                            ast::ManifestItem::Attribute(_) => {}
+                            ast::ManifestItem::PublicKey(k) => {
+                                reference.has_full_public_key = true;
+                                reference.public_key_or_token = Some(Cow::Owned(k.clone()));
+                            }
+                            ast::ManifestItem::PublicKeyToken(t) => {
+                                reference.public_key_or_token = Some(Cow::Owned(t.clone()));
+                            }
+                            ast::ManifestItem::Hash(h) => reference.hash_value = Some(Cow::Owned(h.clone())),
+                            ast::ManifestItem::Version(v) => reference.version = version(v)?,
+                            ast::ManifestItem::Locale(l) => reference.culture = Some(Cow::Owned(l.clone())),
+                            ast::ManifestItem::HashAlgorithm(h) => {
+                                return Err(Error::semantic(
+                                    h.span,
+                                    "hash algorithms can only be declared for the current assembly",
+                                ))
+                            }
+                        }
+                    }
+                    let index = self.res.push_assembly_reference(reference);
+                    let key = a.alias.as_ref().unwrap_or(&a.name);
+                    if self.assemblies.insert(key.value.clone(), index).is_some() {
+                        return Err(Error::semantic(
+                            key.span,
+                            format!("assembly {} has already been declared", key.value),
+                        ));
+                    }
+                }
+                ast::Declaration::Assembly(a) => {
+                    let mut assembly = Assembly::new(a.name.value.clone());
+                    assembly.flags.retargetable = a.retargetable;
+                    for item in &a.items {
+                        match item {
+                            ast::ManifestItem::Attribute(_) => {}
+                            ast::ManifestItem::PublicKey(k) => {
+                                assembly.flags.has_full_public_key = true;
+                                assembly.public_key = Some(Cow::Owned(k.clone()));
+                            }
+                            ast::ManifestItem::HashAlgorithm(h) => {
+                                assembly.hash_algorithm = match h.value {
+                                    0 => HashAlgorithm::None,
+                                    0x8003 => HashAlgorithm::ReservedMD5,
+                                    0x8004 => HashAlgorithm::SHA1,
+                                    _ => return Err(Error::semantic(h.span, "unknown hash algorithm")),
+                                };
+                            }
+                            ast::ManifestItem::Version(v) => assembly.version = version(v)?,
+                            ast::ManifestItem::Locale(l) => assembly.culture = Some(Cow::Owned(l.clone())),
+                            ast::ManifestItem::PublicKeyToken(_) | ast::ManifestItem::Hash(_) => {
+                                return Err(Error::semantic(
+                                    a.span,
+                                    "public key tokens and hashes can only be declared for assembly references",
+                                ))
+                            }
+                        }
+                    }
+                    self.res.assembly = Some(assembly);
+                }
+                ast::Declaration::ModuleRef(m) => {
+                    self.module_reference_or_new(&m.value);
+                }
+                ast::Declaration::File(f) => {
+                    let index = self.res.push_file(File {
+                        attributes: vec![],
+                        has_metadata: f.has_metadata,
+                        name: Cow::Owned(f.name.value.clone()),
+                        hash_value: Cow::Owned(f.hash.clone().unwrap_or_default()),
+                    });
+                    if self.files.insert(f.name.value.clone(), index).is_some() {
+                        return Err(Error::semantic(
+                            f.name.span,
+                            format!("file {} has already been declared", f.name.value),
+                        ));
+                    }
+                    if f.entry_point {
+                        self.entry_point(index, f.span)?;
+                    }
+                }
+                ast::Declaration::Data(d) => self.declare_data(d)?,
+                _ => {}
+            }
+        }
+
+        // these can refer to the files and assemblies above, regardless of where they were declared
+        let mut exported = HashMap::new();
+        for d in &program.top_level {
+            match d {
+                ast::Declaration::ExportedType(e) => {
+                    let index = self.exported_type(e, &exported)?;
+                    exported.insert(
+                        type_key(e.namespace.as_deref(), std::slice::from_ref(&e.name.value)),
+                        index,
+                    );
+                }
+                ast::Declaration::Resource(r) => {
+                    let mut implementation = None;
+                    for item in &r.items {
+                        let location = match item {
+                            ast::ResourceItem::Attribute(_) => continue,
+                            ast::ResourceItem::File(name, offset) => resource::Implementation::File {
+                                location: self.file(name)?,
+                                offset: unsigned(offset, "resource offsets")?,
+                            },
+                            ast::ResourceItem::Assembly(name) => resource::Implementation::Assembly {
+                                location: self.assembly(name)?,
+                                offset: 0,
+                            },
+                        };
+                        if implementation.replace(location).is_some() {
+                            return Err(Error::semantic(r.span, "a resource can only have one location"));
+                        }
+                    }
+                    self.res.manifest_resources.push(resource::ManifestResource {
+                        attributes: vec![],
+                        name: Cow::Owned(r.name.value.clone()),
+                        visibility: if r.public {
+                            resource::Visibility::Public
+                        } else {
+                            resource::Visibility::Private
+                        },
+                        // the contents of embedded resources are not part of the source
+                        implementation: implementation
+                            .unwrap_or(resource::Implementation::CurrentFile(Cow::Borrowed(&[]))),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn file(&self, name: &ast::Name) -> Result<FileIndex> {
+        self.files
+            .get(&name.value)
+            .copied()
+            .ok_or_else(|| Error::semantic(name.span, format!("file {} has not been declared", name.value)))
+    }
+
+    fn assembly(&self, name: &ast::Name) -> Result<AssemblyRefIndex> {
+        self.assemblies.get(&name.value).copied().ok_or_else(|| {
+            Error::semantic(
+                name.span,
+                format!("assembly {} has not been declared with .assembly extern", name.value),
+            )
+        })
+    }
+
+    fn exported_type(
+        &mut self,
+        e: &ast::ExportedTypeDecl,
+        exported: &HashMap<String, ExportedTypeIndex>,
+    ) -> Result<ExportedTypeIndex> {
+        let mut file = None;
+        let mut type_def = None;
+        let mut implementation = None;
+        for item in &e.items {
+            let i = match item {
+                ast::ExportedItem::Attribute(_) => continue,
+                ast::ExportedItem::File(f) => {
+                    file = Some(self.file(f)?);
+                    continue;
+                }
+                ast::ExportedItem::TypeDefinition(t) => {
+                    // the hint is written as a TypeDef token
+                    match t.value.checked_sub(0x0200_0001).map(usize::try_from) {
+                        Some(Ok(i)) if t.value < 0x0300_0000 => type_def = Some(TypeIndex::external(i)),
+                        _ => return Err(Error::semantic(t.span, "expected a TypeDef token")),
+                    }
+                    continue;
+                }
+                ast::ExportedItem::Encloser(name) => match exported.get(&name.value) {
+                    Some(&enc) => TypeImplementation::Nested(enc),
+                    None => {
+                        return Err(Error::semantic(
+                            name.span,
+                            format!("no exported type {} has been declared before", name.value),
+                        ))
+                    }
+                },
+                ast::ExportedItem::Assembly(a) => TypeImplementation::TypeForwarder(self.assembly(a)?),
+            };
+            if implementation.replace(i).is_some() {
+                return Err(Error::semantic(e.span, "an exported type can only have one location"));
+            }
+        }
+
+        let implementation = match (implementation, file) {
+            (Some(_), Some(_)) | (None, None) => {
+                return Err(Error::semantic(
+                    e.span,
+                    "an exported type needs exactly one of .file, .class extern or .assembly extern",
+                ))
+            }
+            (None, Some(file)) => TypeImplementation::ModuleFile {
+                file,
+                type_def: type_def.unwrap_or_else(|| TypeIndex::external(0)),
+            },
+            (Some(i), None) => i,
+        };
+        let nested = matches!(implementation, TypeImplementation::Nested(_));
+        if e.forwarder != matches!(implementation, TypeImplementation::TypeForwarder(_)) {
+            return Err(Error::semantic(
+                e.span,
+                "type forwarders must be marked `forwarder` and name an .assembly extern",
+            ));
+        }
+        if nested && e.namespace.is_some() {
+            return Err(Error::semantic(e.name.span, "nested types cannot have a namespace"));
+        }
+
+        Ok(self.res.push_exported_type(ExportedType {
+            attributes: vec![],
+            flags: type_flags(&e.flags, nested)?,
+            name: Cow::Owned(e.name.value.clone()),
+            namespace: e.namespace.clone().map(Cow::Owned),
+            implementation,
+        }))
+    }
+
+    fn entry_point(&mut self, entry_point: impl Into<EntryPoint>, span: Span) -> Result<()> {
+        if self.res.entry_point.is_some() {
+            return Err(Error::semantic(span, "the entry point has already been declared"));
+        }
+        self.res.set_entry_point(entry_point);
+        Ok(())
+    }
+
+    fn declare_type(
+        &mut self,
+        decl: &ast::ClassDecl,
+        namespace: Option<String>,
+        encloser: Option<(TypeIndex, &[String])>,
+    ) -> Result<()> {
+        let mut names = encloser.map_or(vec![], |(_, names)| names.to_vec());
+        if encloser.is_some() && decl.namespace.is_some() {
+            return Err(Error::semantic(decl.name.span, "nested types cannot have a namespace"));
+        }
+        names.push(decl.name.value.clone());
+
+        let key = type_key(namespace.as_deref(), &names);
+        if self.types.contains_key(&key) {
+            return Err(Error::semantic(
+                decl.name.span,
+                format!("type {} is already defined", key),
+            ));
+        }
+
+        let mut definition = TypeDefinition::new(
+            if encloser.is_some() {
+                None
+            } else {
+                namespace.clone().map(Cow::Owned)
+            },
+            decl.name.value.clone(),
+        );
+        definition.encloser = encloser.map(|(e, _)| e);
+        let index = self.res.push_type_definition(definition);
+        self.types.insert(key, index);
+        self.type_decls.insert(decl.span.start, index);
+
+        for m in &decl.members {
+            match m {
+                ast::ClassMember::Class(c) => self.declare_type(c, namespace.clone(), Some((index, &names)))?,
+                ast::ClassMember::Data(d) => self.declare_data(d)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn type_header(&mut self, decl: &ast::ClassDecl) -> Result<()> {
+        let index = self.type_decls[&decl.span.start];
+        let mut flags = type_flags(&decl.flags, self.res[index].encloser.is_some())?;
+
+        let mut pack = None;
+        let mut size = None;
+        for m in &decl.members {
+            let (slot, value) = match m {
+                ast::ClassMember::Pack(p) => (&mut pack, p),
+                ast::ClassMember::Size(s) => (&mut size, s),
+                _ => continue,
+            };
+            if slot.replace(unsigned(value, "sizes")?).is_some() {
+                return Err(Error::semantic(
+                    value.span,
+                    "the layout of a type can only be declared once",
+                ));
+            }
+        }
+        flags.layout = match (flags.layout, pack, size) {
+            (layout, None, None) => layout,
+            (Layout::Sequential(_), pack, size) => Layout::Sequential(Some(SequentialLayout {
+                packing_size: pack.unwrap_or(0),
+                class_size: size.unwrap_or(0),
+            })),
+            (Layout::Explicit(_), None, Some(class_size)) => Layout::Explicit(Some(ExplicitLayout { class_size })),
+            _ => {
+                return Err(Error::semantic(
+                    decl.span,
+                    ".pack is only allowed for sequential types, and .size for sequential and explicit types",
+                ))
+            }
+        };
+
+        let names: Vec<_> = decl.generics.iter().map(|g| g.name.value.clone()).collect();
+        let g = Generics {
+            type_params: &names,
+            method_params: &[],
+        };
+        let generics = self.generic_params(&decl.generics, true, g)?;
+        let extends = decl.extends.as_ref().map(|e| self.type_source(e, g)).transpose()?;
+        let implements = decl
+            .implements
+            .iter()
+            .map(|i| Ok((vec![], self.type_source(i, g)?)))
+            .collect::<Result<_>>()?;
+
+        let t = &mut self.res[index];
+        t.flags = flags;
+        t.generic_parameters = generics;
+        t.extends = extends;
+        t.implements = implements;
+        Ok(())
+    }
+
+    fn field(&mut self, parent: TypeIndex, decl: &ast::FieldDecl, g: Generics) -> Result<()> {
+        let mut lowered = self.lower_type::<MemberType>(&decl.field_type, g)?;
+        let type_modifiers = std::mem::take(&mut lowered.modifiers);
+        let by_ref = std::mem::take(&mut lowered.by_ref);
+
+        let mut field = Field::new(false, Accessibility::Private, decl.name.value.clone(), lowered.plain()?);
+        field.accessibility = member_access(&decl.flags)?.unwrap_or(MemberAccessibility::CompilerControlled);
+        field.type_modifiers = type_modifiers;
+        field.by_ref = by_ref;
+        for f in &decl.flags {
+            match f.value.as_str() {
+                "static" => field.static_member = true,
+                "initonly" => field.init_only = true,
+                "literal" => field.literal = true,
+                "notserialized" => field.not_serialized = true,
+                "specialname" => field.special_name = true,
+                "rtspecialname" => field.runtime_special_name = true,
+                "privatescope" | "private" | "famandassem" | "assembly" | "family" | "famorassem" | "public" => {}
+                other => return Err(Error::semantic(f.span, format!("`{}` is not a field flag", other))),
+            }
+        }
+        field.offset = decl.offset.as_ref().map(|o| unsigned(o, "field offsets")).transpose()?;
+        field.marshal = decl.marshal.as_ref().map(marshal).transpose()?;
+        field.initial_value = decl
+            .data
+            .as_ref()
+            .map(|d| self.data_label(d))
+            .transpose()?
+            .map(Cow::Owned);
+        field.default = decl.default.as_ref().map(constant).transpose()?;
+
+        if self.res.enumerate_fields(parent).any(|(_, f)| f.name == field.name) {
+            return Err(Error::semantic(
+                decl.name.span,
+                format!("field {} is already defined", decl.name.value),
+            ));
+        }
+        let index = self.res.push_field(parent, field);
+        self.field_decls.insert(decl.span.start, index);
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn method_header(&mut self, decl: &ast::MethodDecl, type_params: &[String]) -> Result<Method<'static>> {
+        let method_params: Vec<_> = decl.generics.iter().map(|g| g.name.value.clone()).collect();
+        let g = Generics {
+            type_params,
+            method_params: &method_params,
+        };
+
+        let is_static = decl.flags.iter().any(|f| f.value == "static");
+        if let Some(w) = decl.call_conv.iter().find(|w| w.value == "instance") {
+            if is_static {
+                return Err(Error::semantic(
+                    w.span,
+                    "static methods cannot have an instance calling convention",
+                ));
+            }
+        }
+        let mut signature = self.managed_signature(
+            &decl.call_conv,
+            &decl.return_type,
+            &decl.parameters,
+            decl.generics.len(),
+            decl.span,
+            g,
+        )?;
+        if signature.varargs.is_some() {
+            return Err(Error::semantic(
+                decl.name.span,
+                "`...` is only allowed in call sites of vararg methods",
+            ));
+        }
+        signature.instance = !is_static;
+
+        let mut method = Method::new(Accessibility::Private, signature, decl.name.value.clone(), None);
+        method.accessibility = member_access(&decl.flags)?.unwrap_or(MemberAccessibility::CompilerControlled);
+        method.hide_by_sig = false;
+        for f in &decl.flags {
+            match f.value.as_str() {
+                "final" => method.sealed = true,
+                "virtual" => method.virtual_member = true,
+                "hidebysig" => method.hide_by_sig = true,
+                "newslot" => method.vtable_layout = VtableLayout::NewSlot,
+                "strict" => method.strict = true,
+                "abstract" => method.abstract_member = true,
+                "specialname" => method.special_name = true,
+                "rtspecialname" => method.runtime_special_name = true,
+                "reqsecobj" => method.require_sec_object = true,
+                "static" | "privatescope" | "private" | "famandassem" | "assembly" | "family" | "famorassem"
+                | "public" => {}
+                other => return Err(Error::semantic(f.span, format!("`{}` is not a method flag", other))),
+            }
+        }
+        for f in &decl.impl_flags {
+            match f.value.as_str() {
+                "cil" => method.body_format = BodyFormat::IL,
+                "native" => method.body_format = BodyFormat::Native,
+                "runtime" => method.body_format = BodyFormat::Runtime,
+                "managed" => method.body_management = BodyManagement::Managed,
+                "unmanaged" => method.body_management = BodyManagement::Unmanaged,
+                "forwardref" => method.forward_ref = true,
+                "preservesig" => method.preserve_sig = true,
+                "internalcall" => method.internal_call = true,
+                "synchronized" => method.synchronized = true,
+                "noinlining" => method.no_inlining = true,
+                _ => method.no_optimization = true,
+            }
+        }
+
+        if let Some(p) = &decl.pinvoke {
+            let Some(module) = &p.module else {
+                return Err(Error::semantic(
+                    p.span,
+                    "pinvokeimpl needs the name of the module to import from",
+                ));
+            };
+            let scope = self.module_reference_or_new(&module.value);
+            let mut pinvoke = PInvoke::new(scope, p.name.clone().unwrap_or_else(|| decl.name.value.clone()));
+            for f in &p.flags {
+                match f.value.as_str() {
+                    "nomangle" => pinvoke.no_mangle = true,
+                    "ansi" => pinvoke.character_set = CharacterSet::Ansi,
+                    "unicode" => pinvoke.character_set = CharacterSet::Unicode,
+                    "autochar" => pinvoke.character_set = CharacterSet::Auto,
+                    "lasterr" => pinvoke.supports_last_error = true,
+                    "winapi" => pinvoke.calling_convention = UnmanagedCallingConvention::Platformapi,
+                    "cdecl" => pinvoke.calling_convention = UnmanagedCallingConvention::Cdecl,
+                    "stdcall" => pinvoke.calling_convention = UnmanagedCallingConvention::Stdcall,
+                    "thiscall" => pinvoke.calling_convention = UnmanagedCallingConvention::Thiscall,
+                    _ => pinvoke.calling_convention = UnmanagedCallingConvention::Fastcall,
+                }
+            }
+            method.pinvoke = Some(pinvoke);
+        }
+
+        method.generic_parameters = self.generic_params(&decl.generics, false, g)?;
+
+        if let Some(m) = &decl.return_marshal {
+            method.return_type_metadata = Some(ParameterMetadata::marshal(marshal(m)?));
+        }
+        for a in &decl.parameters {
+            let ast::SigArg::Param(p) = a else { continue };
+            let meta = ParameterMetadata {
+                name: p.name.as_ref().map(|n| Cow::Owned(n.value.clone())),
+                is_in: p.attributes.contains(&ast::ParamAttr::In),
+                is_out: p.attributes.contains(&ast::ParamAttr::Out),
+                optional: p.attributes.contains(&ast::ParamAttr::Opt),
+                marshal: p.marshal.as_ref().map(marshal).transpose()?,
+                ..ParameterMetadata::default()
+            };
+            let is_empty =
+                meta.name.is_none() && !meta.is_in && !meta.is_out && !meta.optional && meta.marshal.is_none();
+            method.parameter_metadata.push((!is_empty).then_some(meta));
+        }
+        while matches!(method.parameter_metadata.last(), Some(None)) {
+            method.parameter_metadata.pop();
+        }
+
+        Ok(method)
+    }
+
+    // claims a method declared in the same type as a property or event accessor
+    fn accessor(
+        &mut self,
+        parent: TypeIndex,
+        pending: &mut [Pending],
+        accessor: &ast::Accessor,
+        g: Generics,
+    ) -> Result<(usize, Method<'static>)> {
+        let m = &accessor.method;
+        let same_type = match &m.parent {
+            None => self.is_module_type(parent),
+            Some(ast::MemberParent::Module(name)) => self.is_module_type(parent) && self.is_current_module(&name.value),
+            Some(ast::MemberParent::Type(spec)) => {
+                let t: MethodType = self.type_spec(spec, g)?;
+                matches!(&t, MethodType::Base(b) if matches!(
+                    &**b,
+                    BaseType::Type { source: TypeSource::User(UserType::Definition(d)), .. } if *d == parent
+                ))
+            }
+        };
+        if !same_type {
+            return Err(Error::semantic(m.span, "accessors must be declared in the same type"));
+        }
+
+        let arity = match &m.generics {
+            ast::MethodGenerics::None => 0,
+            ast::MethodGenerics::Arity(n) => unsigned(n, "generic arities")?,
+            ast::MethodGenerics::Arguments(_) => {
+                return Err(Error::semantic(m.span, "accessors cannot be generic instantiations"));
+            }
+        };
+        let sig = &m.signature;
+        let signature = self.managed_signature(&sig.call_conv, &sig.return_type, &sig.parameters, arity, m.span, g)?;
+
+        let Some(p) = pending
+            .iter_mut()
+            .find(|p| p.name == m.name.value && p.signature == signature)
+        else {
+            return Err(Error::semantic(
+                m.span,
+                format!(
+                    "no method {} with this signature is declared in this type",
+                    m.name.value
+                ),
+            ));
+        };
+        match p.method.take() {
+            Some(method) => Ok((p.decl, method)),
+            None => Err(Error::semantic(
+                m.span,
+                format!("method {} is already an accessor", m.name.value),
+            )),
+        }
+    }
+
+    fn property(
+        &mut self,
+        parent: TypeIndex,
+        pending: &mut [Pending],
+        decl: &ast::PropertyDecl,
+        g: Generics,
+    ) -> Result<()> {
+        let mut is_instance = false;
+        for w in &decl.call_conv {
+            match w.value.as_str() {
+                "instance" => is_instance = true,
+                "default" => {}
+                other => {
+                    return Err(Error::semantic(
+                        w.span,
+                        format!("`{}` is not a property calling convention", other),
+                    ))
+                }
+            }
+        }
+
+        let mut property = Property::new(
+            !is_instance,
+            decl.name.value.clone(),
+            self.parameter(&decl.property_type, g)?,
+        );
+        let (parameters, varargs) = self.parameters(&decl.parameters, g)?;
+        if varargs.is_some() {
+            return Err(Error::semantic(decl.span, "properties cannot be vararg"));
+        }
+        property.parameters = parameters;
+        property.default = decl.default.as_ref().map(constant).transpose()?;
+        for f in &decl.flags {
+            match f.value.as_str() {
+                "specialname" => property.special_name = true,
+                _ => property.runtime_special_name = true,
+            }
+        }
+
+        let index = self.res.push_property(parent, property);
+        self.property_decls.insert(decl.span.start, index);
+
+        for a in &decl.accessors {
+            let (method_decl, method) = self.accessor(parent, pending, a, g)?;
+            let method_index = match a.kind.value.as_str() {
+                ".get" if self.res[index].getter.is_none() => self.res.set_property_getter(index, method),
+                ".set" if self.res[index].setter.is_none() => self.res.set_property_setter(index, method),
+                ".other" => self.res.push_property_other(index, method),
+                ".get" | ".set" => {
+                    return Err(Error::semantic(
+                        a.kind.span,
+                        format!("a property can only have one {} accessor", a.kind.value),
+                    ))
+                }
+                other => {
+                    return Err(Error::semantic(
+                        a.kind.span,
+                        format!("{} is not a property accessor", other),
+                    ))
+                }
+            };
+            self.method_decls.insert(method_decl, method_index);
+            self.methods.entry(parent).or_default().push(method_index);
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, parent: TypeIndex, pending: &mut [Pending], decl: &ast::EventDecl, g: Generics) -> Result<()> {
+        let Some(delegate_type) = &decl.delegate_type else {
+            return Err(Error::semantic(
+                decl.name.span,
+                "events must declare their delegate type",
+            ));
+        };
+        let delegate_type = self.type_spec(delegate_type, g)?;
+
+        let mut add = None;
+        let mut remove = None;
+        let mut rest = vec![];
+        for a in &decl.accessors {
+            let claimed = self.accessor(parent, pending, a, g)?;
+            let slot = match a.kind.value.as_str() {
+                ".addon" => &mut add,
+                ".removeon" => &mut remove,
+                ".fire" | ".other" => {
+                    rest.push((a, claimed));
+                    continue;
+                }
+                other => {
+                    return Err(Error::semantic(
+                        a.kind.span,
+                        format!("{} is not an event accessor", other),
+                    ));
+                }
+            };
+            if slot.replace(claimed).is_some() {
+                return Err(Error::semantic(
+                    a.kind.span,
+                    format!("an event can only have one {} accessor", a.kind.value),
+                ));
+            }
+        }
+        let (Some((add_decl, add)), Some((remove_decl, remove))) = (add, remove) else {
+            return Err(Error::semantic(
+                decl.span,
+                "events must have an .addon and a .removeon accessor",
+            ));
+        };
+
+        let mut event = Event::new(decl.name.value.clone(), delegate_type, add, remove);
+        for f in &decl.flags {
+            match f.value.as_str() {
+                "specialname" => event.special_name = true,
+                _ => event.runtime_special_name = true,
+            }
+        }
+        let index = self.res.push_event(parent, event);
+        self.event_decls.insert(decl.span.start, index);
+
+        let mut accessors = vec![
+            (add_decl, self.res.event_add_index(index)),
+            (remove_decl, self.res.event_remove_index(index)),
+        ];
+        for (a, (method_decl, method)) in rest {
+            let method_index = match a.kind.value.as_str() {
+                ".fire" if self.res[index].raise_event.is_none() => self.res.set_event_raise(index, method),
+                ".fire" => {
+                    return Err(Error::semantic(
+                        a.kind.span,
+                        "an event can only have one .fire accessor",
+                    ));
+                }
+                _ => self.res.push_event_other(index, method),
+            };
+            accessors.push((method_decl, method_index));
+        }
+        for (method_decl, method_index) in accessors {
+            self.method_decls.insert(method_decl, method_index);
+            self.methods.entry(parent).or_default().push(method_index);
+        }
+        Ok(())
+    }
+
+    // fields, methods, properties and events, but not anything that refers to methods
+    fn members<'a>(&mut self, parent: TypeIndex, members: impl IntoIterator<Item = Member<'a>>) -> Result<()> {
+        let type_params = self.type_generics(parent);
+        let g = Generics {
+            type_params: &type_params,
+            method_params: &[],
+        };
+
+        let members: Vec<_> = members.into_iter().collect();
+        let mut pending = vec![];
+        for m in &members {
+            match m {
+                Member::Field(f) => self.field(parent, f, g)?,
+                Member::Method(m) => {
+                    let method = self.method_header(m, &type_params)?;
+                    pending.push(Pending {
+                        name: m.name.value.clone(),
+                        signature: method.signature.clone(),
+                        decl: m.span.start,
+                        method: Some(method),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        for m in &members {
+            match m {
+                Member::Property(p) => self.property(parent, &mut pending, p, g)?,
+                Member::Event(e) => self.event(parent, &mut pending, e, g)?,
+                _ => {}
+            }
+        }
+
+        for p in pending {
+            let Some(method) = p.method else { continue };
+            let duplicate = self.methods.get(&parent).into_iter().flatten().any(|&m| {
+                let other = &self.res[m];
+                other.name == method.name && other.signature == method.signature
+            });
+            if duplicate {
+                return Err(Error::semantic(
+                    Span::new(p.decl, p.decl),
+                    format!("method {} is already defined with the same signature", p.name),
+                ));
+            }
+            let index = self.res.push_method(parent, method);
+            self.method_decls.insert(p.decl, index);
+            self.methods.entry(parent).or_default().push(index);
+        }
+        Ok(())
+    }
+
+    fn attributes_mut(&mut self, target: Target) -> &mut Vec<Attribute<'static>> {
+        if let Target::Security(owner) = target {
+            return &mut self.security_mut(owner).as_mut().unwrap().attributes;
+        }
+
+        let res = &mut self.res;
+        match target {
+            Target::Module => &mut res.module.attributes,
+            Target::Assembly => &mut res.assembly.as_mut().unwrap().attributes,
+            Target::AssemblyRef(a) => &mut res[a].attributes,
+            Target::File(f) => &mut res[f].attributes,
+            Target::ExportedType(e) => &mut res[e].attributes,
+            Target::Resource(r) => &mut res.manifest_resources[r].attributes,
+            Target::Type(t) => &mut res[t].attributes,
+            Target::Field(f) => &mut res[f].attributes,
+            Target::Method(m) => &mut res[m].attributes,
+            Target::Parameter(m, 0) => {
+                &mut res[m]
+                    .return_type_metadata
+                    .get_or_insert_with(ParameterMetadata::default)
+                    .attributes
+            }
+            Target::Parameter(m, i) => {
+                let meta = &mut res[m].parameter_metadata;
+                if meta.len() < i {
+                    meta.resize_with(i, || None);
+                }
+                &mut meta[i - 1].get_or_insert_with(ParameterMetadata::default).attributes
+            }
+            Target::TypeGeneric(t, i) => &mut res[t].generic_parameters[i].attributes,
+            Target::MethodGeneric(m, i) => &mut res[m].generic_parameters[i].attributes,
+            Target::TypeConstraint(t, i, c) => &mut res[t].generic_parameters[i].type_constraints[c].attributes,
+            Target::MethodConstraint(m, i, c) => &mut res[m].generic_parameters[i].type_constraints[c].attributes,
+            Target::InterfaceImpl(t, i) => &mut res[t].implements[i].0,
+            Target::Property(p) => &mut res[p].attributes,
+            Target::Event(e) => &mut res[e].attributes,
+            Target::Security(_) => unreachable!(),
+        }
+    }
+
+    fn security_mut(&mut self, owner: SecurityOwner) -> &mut Option<SecurityDeclaration<'static>> {
+        match owner {
+            SecurityOwner::Assembly => &mut self.res.assembly.as_mut().unwrap().security,
+            SecurityOwner::Type(t) => &mut self.res[t].security,
+            SecurityOwner::Method(m) => &mut self.res[m].security,
+        }
+    }
+
+    fn attribute(&mut self, attribute: &ast::Attribute, target: &mut Target) -> Result<()> {
+        match attribute {
+            ast::Attribute::Custom(c) => {
+                if c.constructor.name.value != ".ctor" {
+                    return Err(Error::semantic(
+                        c.constructor.name.span,
+                        "custom attributes must name a constructor",
+                    ));
+                }
+                let constructor = self.user_method(&c.constructor, Generics::default())?;
+                let value = c.value.clone().map(Cow::Owned);
+                self.attributes_mut(*target)
+                    .push(Attribute::from_bytes(constructor, value));
+            }
+            ast::Attribute::PermissionSet(p) => {
+                let owner = match *target {
+                    Target::Assembly => SecurityOwner::Assembly,
+                    Target::Type(t) => SecurityOwner::Type(t),
+                    Target::Method(m) => SecurityOwner::Method(m),
+                    Target::Security(owner) => owner,
+                    _ => {
+                        return Err(Error::semantic(
+                            p.span,
+                            "permission sets can only be declared for assemblies, types and methods",
+                        ))
+                    }
+                };
+                let action = security_action(&p.action)?;
+                let security = self.security_mut(owner);
+                if security.is_some() {
+                    return Err(Error::semantic(p.span, "only one permission set can be declared here"));
+                }
+                *security = Some(SecurityDeclaration::from_bytes(
+                    vec![],
+                    action,
+                    Cow::Owned(p.value.clone()),
+                ));
+                *target = Target::Security(owner);
+            }
+        }
+        Ok(())
+    }
+
+    fn generic_target<T>(generics: &[generic::Generic<'_, T>], target: &ast::GenericTarget) -> Result<usize> {
+        match target {
+            ast::GenericTarget::Index(i) => match usize::try_from(i.value) {
+                Ok(n @ 1..) if n <= generics.len() => Ok(n - 1),
+                _ => Err(Error::semantic(
+                    i.span,
+                    format!("expected a generic parameter number from 1 to {}", generics.len()),
+                )),
+            },
+            ast::GenericTarget::Name(n) => generics
+                .iter()
+                .position(|g| g.name == n.value)
+                .ok_or_else(|| Error::semantic(n.span, format!("there is no generic parameter named {}", n.value))),
+        }
+    }
+
+    fn constraint_target<T: PartialEq + types::SigType>(
+        &mut self,
+        generic: &generic::Generic<'_, T>,
+        spec: &ast::TypeSpec,
+        g: Generics,
+    ) -> Result<usize> {
+        let (modifiers, constraint) = self.type_spec_with_modifiers::<T>(spec, g)?;
+        generic
+            .type_constraints
+            .iter()
+            .position(|c| c.constraint_type == constraint && c.custom_modifiers == modifiers)
+            .ok_or_else(|| Error::semantic(spec.span(), "the generic parameter has no such constraint"))
+    }
+
+    fn override_target(
+        &mut self,
+        target: &ast::OverrideTarget,
+        current: Option<MethodIndex>,
+        g: Generics,
+        span: Span,
+    ) -> Result<UserMethod> {
+        match target {
+            ast::OverrideTarget::Method(m) => self.user_method(m, g),
+            ast::OverrideTarget::Name(parent, name) => {
+                let Some(current) = current else {
+                    return Err(Error::semantic(
+                        span,
+                        "the short form of .override is only allowed in methods",
+                    ));
+                };
+                // the overridden method has the same signature as the one overriding it
+                let signature = self.res[current].signature.clone();
+                self.method_with_signature(Some(&ast::MemberParent::Type(parent.clone())), name, signature, span, g)
+            }
+        }
+    }
+
+    fn method_override(
+        &mut self,
+        parent: TypeIndex,
+        o: &ast::Override,
+        current: Option<MethodIndex>,
+        g: Generics,
+    ) -> Result<()> {
+        let declaration = self.override_target(&o.declaration, current, g, o.span)?;
+        let implementation = match (&o.implementation, current) {
+            (Some(m), _) => self.user_method(m, g)?,
+            (None, Some(m)) => UserMethod::Definition(m),
+            (None, None) => {
+                return Err(Error::semantic(
+                    o.span,
+                    ".override needs `with method` outside of methods",
+                ));
+            }
+        };
+        self.res[parent].overrides.push(MethodOverride {
+            implementation,
+            declaration,
+        });
+        Ok(())
+    }
+
+    fn method(&mut self, decl: &ast::MethodDecl, type_params: &[String]) -> Result<()> {
+        let index = self.method_decls[&decl.span.start];
+        let method_params = self.res[index]
+            .generic_parameters
+            .iter()
+            .map(|g| g.name.to_string())
+            .collect::<Vec<_>>();
+        let g = Generics {
+            type_params,
+            method_params: &method_params,
+        };
+
+        let mut target = Target::Method(index);
+        for item in &decl.items {
+            match item {
+                ast::MethodItem::EntryPoint(span) => self.entry_point(index, *span)?,
+                ast::MethodItem::Attribute(a) => self.attribute(a, &mut target)?,
+                ast::MethodItem::Param(p, _) => {
+                    target = match p {
+                        ast::ParamDirective::Param { index: i, default } => {
+                            let count = self.res[index].signature.parameters.len();
+                            let n = match usize::try_from(i.value) {
+                                Ok(n) if n <= count => n,
+                                _ => {
+                                    return Err(Error::semantic(
+                                        i.span,
+                                        format!("expected a parameter number from 0 to {}", count),
+                                    ))
+                                }
+                            };
+                            let target = Target::Parameter(index, n);
+                            if let Some(c) = default {
+                                let c = constant(c)?;
+                                // make sure the metadata exists before filling it in
+                                self.attributes_mut(target);
+                                let method = &mut self.res[index];
+                                let meta = if n == 0 {
+                                    method.return_type_metadata.as_mut()
+                                } else {
+                                    method.parameter_metadata[n - 1].as_mut()
+                                };
+                                meta.unwrap().default = Some(c);
+                            }
+                            target
+                        }
+                        ast::ParamDirective::Type(t) => {
+                            Target::MethodGeneric(index, Self::generic_target(&self.res[index].generic_parameters, t)?)
+                        }
+                        ast::ParamDirective::Constraint(t, spec) => {
+                            let i = Self::generic_target(&self.res[index].generic_parameters, t)?;
+                            let generic = self.res[index].generic_parameters[i].clone();
+                            Target::MethodConstraint(index, i, self.constraint_target(&generic, spec, g)?)
+                        }
+                    };
+                }
+                ast::MethodItem::Override(o) => self.method_override(index.parent_type(), o, Some(index), g)?,
+                _ => {}
+            }
+        }
+
+        let body = self.method_body(decl, index, g)?;
+        self.res[index].body = body;
+        Ok(())
+    }
+
+    // everything else in a type, which can refer to any method
+    fn definitions(&mut self, decl: &ast::ClassDecl) -> Result<()> {
+        let index = self.type_decls[&decl.span.start];
+        let type_params = self.type_generics(index);
+        let g = Generics {
+            type_params: &type_params,
+            method_params: &[],
+        };
+
+        let mut target = Target::Type(index);
+        for m in &decl.members {
+            match m {
+                ast::ClassMember::Attribute(a) => self.attribute(a, &mut target)?,
+                ast::ClassMember::Field(f) => target = Target::Field(self.field_decls[&f.span.start]),
+                ast::ClassMember::Param(p, span) => {
+                    target = match p {
+                        ast::ParamDirective::Param { .. } => {
+                            return Err(Error::semantic(*span, ".param [n] is only allowed in methods"));
+                        }
+                        ast::ParamDirective::Type(t) => {
+                            Target::TypeGeneric(index, Self::generic_target(&self.res[index].generic_parameters, t)?)
+                        }
+                        ast::ParamDirective::Constraint(t, spec) => {
+                            let i = Self::generic_target(&self.res[index].generic_parameters, t)?;
+                            let generic = self.res[index].generic_parameters[i].clone();
+                            Target::TypeConstraint(index, i, self.constraint_target(&generic, spec, g)?)
+                        }
+                    };
+                }
+                ast::ClassMember::InterfaceImpl(spec, span) => {
+                    let interface = self.type_source(spec, g)?;
+                    let Some(i) = self.res[index].implements.iter().position(|(_, t)| *t == interface) else {
+                        return Err(Error::semantic(*span, "the type does not implement this interface"));
+                    };
+                    target = Target::InterfaceImpl(index, i);
+                }
+                ast::ClassMember::Override(o) => {
+                    self.method_override(index, o, None, g)?;
+                    target = Target::Type(index);
+                }
+                ast::ClassMember::Method(m) => {
+                    self.method(m, &type_params)?;
+                    target = Target::Type(index);
+                }
+                ast::ClassMember::Property(p) => {
+                    let mut property = Target::Property(self.property_decls[&p.span.start]);
+                    for a in &p.attributes {
+                        self.attribute(a, &mut property)?;
+                    }
+                    target = Target::Type(index);
+                }
+                ast::ClassMember::Event(e) => {
+                    let mut event = Target::Event(self.event_decls[&e.span.start]);
+                    for a in &e.attributes {
+                        self.attribute(a, &mut event)?;
+                    }
+                    target = Target::Type(index);
+                }
+                ast::ClassMember::Class(c) => {
+                    self.definitions(c)?;
+                    target = Target::Type(index);
+                }
+                ast::ClassMember::Pack(_) | ast::ClassMember::Size(_) | ast::ClassMember::Data(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn manifest_attributes(&mut self, program: &Program) -> Result<()> {
+        let mut exported = 0;
+        let mut resources = 0;
+        let mut target = Target::Module;
+        for d in &program.top_level {
+            let (mut item_target, attributes): (_, Vec<_>) = match d {
+                ast::Declaration::AssemblyRef(a) => {
+                    let key = a.alias.as_ref().unwrap_or(&a.name);
+                    (
+                        Target::AssemblyRef(self.assemblies[&key.value]),
+                        a.items
+                            .iter()
+                            .filter_map(|i| match i {
+                                ast::ManifestItem::Attribute(a) => Some(a),
+                                _ => None,
+                            })
+                            .collect(),
+                    )
+                }
+                ast::Declaration::Assembly(a) => (
+                    Target::Assembly,
+                    a.items
+                        .iter()
+                        .filter_map(|i| match i {
+                            ast::ManifestItem::Attribute(a) => Some(a),
+                            _ => None,
+                        })
+                        .collect(),
+                ),
+                ast::Declaration::ExportedType(e) => {
+                    exported += 1;
+                    (
+                        Target::ExportedType(self.res.exported_type_index(exported - 1).unwrap()),
+                        e.items
+                            .iter()
+                            .filter_map(|i| match i {
+                                ast::ExportedItem::Attribute(a) => Some(a),
+                                _ => None,
+                            })
+                            .collect(),
+                    )
+                }
+                ast::Declaration::Resource(r) => {
+                    resources += 1;
+                    (
+                        Target::Resource(resources - 1),
+                        r.items
+                            .iter()
+                            .filter_map(|i| match i {
+                                ast::ResourceItem::Attribute(a) => Some(a),
+                                _ => None,
+                            })
+                            .collect(),
+                    )
+                }
+                // attributes at the top level belong to the module, or to a file or global field right before them
+                ast::Declaration::File(f) => {
+                    target = Target::File(self.files[&f.name.value]);
+                    continue;
+                }
+                ast::Declaration::Field(f) => {
+                    target = Target::Field(self.field_decls[&f.span.start]);
+                    continue;
+                }
+                ast::Declaration::Attribute(a) => {
+                    self.attribute(a, &mut target)?;
+                    continue;
+                }
+                _ => {
+                    target = Target::Module;
+                    continue;
+                }
+            };
+            for a in attributes {
+                self.attribute(a, &mut item_target)?;
+            }
+            target = Target::Module;
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, program: &Program) -> Result<()> {
+        self.manifest(program)?;
+
+        for (namespace, decl) in &program.classes {
+            self.declare_type(decl, namespace.clone(), None)?;
+        }
+        let mut classes = vec![];
+        for (_, decl) in &program.classes {
+            Self::nested_classes(decl, &mut classes);
+        }
+        for decl in &classes {
+            self.type_header(decl)?;
+        }
+
+        let module_type = self.module_type();
+        let globals = program.top_level.iter().filter_map(|d| match d {
+            ast::Declaration::Field(f) => Some(Member::Field(f)),
+            ast::Declaration::Method(m) => Some(Member::Method(m)),
+            _ => None,
+        });
+        self.members(module_type, globals)?;
+        for decl in &classes {
+            let index = self.type_decls[&decl.span.start];
+            let members = decl.members.iter().filter_map(|m| match m {
+                ast::ClassMember::Field(f) => Some(Member::Field(f)),
+                ast::ClassMember::Method(m) => Some(Member::Method(m)),
+                ast::ClassMember::Property(p) => Some(Member::Property(p)),
+                ast::ClassMember::Event(e) => Some(Member::Event(e)),
+                _ => None,
+            });
+            self.members(index, members)?;
+        }
+
+        self.manifest_attributes(program)?;
+        for d in &program.top_level {
+            if let ast::Declaration::Method(m) = d {
+                self.method(m, &[])?;
+            }
+        }
+        for (_, decl) in &program.classes {
+            self.definitions(decl)?;
+        }
+        Ok(())
+    }
+
+    fn nested_classes<'a>(decl: &'a ast::ClassDecl, out: &mut Vec<&'a ast::ClassDecl>) {
+        out.push(decl);
+        for m in &decl.members {
+            if let ast::ClassMember::Class(c) = m {
+                Self::nested_classes(c, out);
+            }
+        }
+    }
+}
+
+enum Member<'a> {
+    Field(&'a ast::FieldDecl),
+    Method(&'a ast::MethodDecl),
+    Property(&'a ast::PropertyDecl),
+    Event(&'a ast::EventDecl),
+}
+
+pub(crate) fn assemble(source: &ast::Source) -> Result<Resolution<'static>> {
+    let mut program = Program {
+        top_level: vec![],
+        classes: vec![],
+    };
+    program.collect(None, &source.declarations);
+
+    let mut module: Option<&ast::Name> = None;
+    let mut assembly: Option<&ast::Name> = None;
+    for d in &program.top_level {
+        let (slot, name, what) = match d {
+            ast::Declaration::Module(m) => (&mut module, m, "module"),
+            ast::Declaration::Assembly(a) => (&mut assembly, &a.name, "assembly"),
+            _ => continue,
+        };
+        if slot.replace(name).is_some() {
+            return Err(Error::semantic(name.span, format!("only one {} can be declared", what)));
+        }
+    }
+    let module_name = match (module, assembly) {
+        (Some(m), _) => m.value.clone(),
+        (None, Some(a)) => format!("{}.dll", a.value),
+        (None, None) => {
+            return Err(Error::semantic(
+                Span::default(),
+                "expected a .module or .assembly declaration to name the module",
+            ))
+        }
+    };
+
+    let mut assembler = Assembler {
+        res: Resolution::new(Module::new(module_name)),
+        assembly_name: assembly.map(|a| a.value.clone()),
+        assemblies: HashMap::new(),
+        modules: HashMap::new(),
+        files: HashMap::new(),
+        types: HashMap::new(),
+        data: HashMap::new(),
+        methods: HashMap::new(),
+        type_decls: HashMap::new(),
+        field_decls: HashMap::new(),
+        method_decls: HashMap::new(),
+        property_decls: HashMap::new(),
+        event_decls: HashMap::new(),
+    };
+    assembler.run(&program)?;
+    Ok(assembler.res)
+}
@@ -0,0 +1,999 @@
+use dotnetdll::{binary::signature::encoded::ArrayShape, prelude::*};
+use std::borrow::Cow;
+
+use super::Assembler;
+use crate::ast::{self, Spanned};
+use crate::error::{Error, Result, Span};
+
+/// The names of the generic parameters in scope, for resolving `!T` and `!!T`.
+#[derive(Debug, Copy, Clone, Default)]
+pub(super) struct Generics<'s> {
+    pub type_params: &'s [String],
+    pub method_params: &'s [String],
+}
+
+/// The two flavors of types that can contain generic type variables.
+pub(super) trait SigType: Sized + From<BaseType<Self>> {
+    fn type_generic(index: usize) -> Self;
+    fn method_generic(index: usize, span: Span) -> Result<Self>;
+}
+impl SigType for MemberType {
+    fn type_generic(index: usize) -> Self {
+        MemberType::TypeGeneric(index)
+    }
+
+    fn method_generic(_: usize, span: Span) -> Result<Self> {
+        Err(Error::semantic(
+            span,
+            "method type parameters can only be used in method signatures and bodies",
+        ))
+    }
+}
+impl SigType for MethodType {
+    fn type_generic(index: usize) -> Self {
+        MethodType::TypeGeneric(index)
+    }
+
+    fn method_generic(index: usize, _: Span) -> Result<Self> {
+        Ok(MethodType::MethodGeneric(index))
+    }
+}
+
+pub(super) enum Core<T> {
+    Void,
+    TypedRef,
+    Type(T),
+}
+
+/// A type, with the parts that are only allowed in some positions split off.
+pub(super) struct Lowered<T> {
+    pub core: Core<T>,
+    pub modifiers: Vec<CustomTypeModifier>,
+    pub by_ref: bool,
+    pub pinned: bool,
+    pub span: Span,
+}
+impl<T> Lowered<T> {
+    pub fn not_pinned(&self) -> Result<()> {
+        if self.pinned {
+            Err(Error::semantic(self.span, "only local variables can be pinned"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The type itself, which cannot be a reference, void, or carry custom modifiers.
+    pub fn plain(self) -> Result<T> {
+        self.not_pinned()?;
+        if self.by_ref {
+            return Err(Error::semantic(self.span, "a by-reference type is not allowed here"));
+        }
+        if !self.modifiers.is_empty() {
+            return Err(Error::semantic(self.span, "custom modifiers are not allowed here"));
+        }
+        match self.core {
+            Core::Void => Err(Error::semantic(self.span, "void is not allowed here")),
+            Core::TypedRef => Err(Error::semantic(self.span, "typedref is not allowed here")),
+            Core::Type(t) => Ok(t),
+        }
+    }
+
+    pub fn parameter_type(self) -> Result<ParameterType<T>> {
+        self.not_pinned()?;
+        match self.core {
+            Core::Void => Err(Error::semantic(self.span, "void is not allowed here")),
+            Core::TypedRef if self.by_ref => Err(Error::semantic(self.span, "typedref cannot be passed by reference")),
+            Core::TypedRef => Ok(ParameterType::TypedReference),
+            Core::Type(t) if self.by_ref => Ok(ParameterType::Ref(t)),
+            Core::Type(t) => Ok(ParameterType::Value(t)),
+        }
+    }
+}
+
+fn primitive<T>(p: ast::Primitive) -> BaseType<T> {
+    use ast::Primitive::*;
+    match p {
+        Bool => BaseType::Boolean,
+        Char => BaseType::Char,
+        Int8 => BaseType::Int8,
+        UInt8 => BaseType::UInt8,
+        Int16 => BaseType::Int16,
+        UInt16 => BaseType::UInt16,
+        Int32 => BaseType::Int32,
+        UInt32 => BaseType::UInt32,
+        Int64 => BaseType::Int64,
+        UInt64 => BaseType::UInt64,
+        Float32 => BaseType::Float32,
+        Float64 => BaseType::Float64,
+        IntPtr => BaseType::IntPtr,
+        UIntPtr => BaseType::UIntPtr,
+        Object => BaseType::Object,
+        String => BaseType::String,
+        Void | TypedRef => unreachable!(),
+    }
+}
+
+fn generic_index(r: &ast::GenericRef, names: &[String], kind: &str) -> Result<usize> {
+    match r {
+        ast::GenericRef::Index(i) => usize::try_from(i.value)
+            .map_err(|_| Error::semantic(i.span, "generic parameter indices cannot be negative")),
+        ast::GenericRef::Name(n) => names
+            .iter()
+            .position(|p| *p == n.value)
+            .ok_or_else(|| Error::semantic(n.span, format!("there is no {} type parameter named {}", kind, n.value))),
+    }
+}
+
+pub(super) fn unsigned(i: &ast::Int, what: &str) -> Result<usize> {
+    usize::try_from(i.value).map_err(|_| Error::semantic(i.span, format!("{} cannot be negative", what)))
+}
+
+/// Checks that an integer fits into `width` bits, either as a signed or an unsigned number, and returns its bits.
+pub(super) fn bits(value: i128, width: u32, span: Span) -> Result<u64> {
+    let min = -(1_i128 << (width - 1));
+    let max = (1_i128 << width) - 1;
+    if (min..=max).contains(&value) {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        Ok((value & max) as u64)
+    } else {
+        Err(Error::semantic(
+            span,
+            format!("{} does not fit into {} bits", value, width),
+        ))
+    }
+}
+
+fn array_shape(dimensions: &[ast::ArrayDimension], span: Span) -> Result<ArrayShape> {
+    use ast::ArrayDimension::*;
+
+    let bad_size = || Error::semantic(span, "array dimensions must have a non-negative size");
+    let mut bounds = vec![];
+    for d in dimensions {
+        bounds.push(match *d {
+            Unbounded => (None, None),
+            Size(n) => (None, Some(usize::try_from(n).map_err(|_| bad_size())?)),
+            LowerBound(lo) => (Some(lo), None),
+            Range(lo, hi) => (Some(lo), Some(usize::try_from(hi - lo + 1).map_err(|_| bad_size())?)),
+        });
+    }
+
+    let known_sizes = bounds.iter().take_while(|(_, s)| s.is_some()).count();
+    if bounds[known_sizes..].iter().any(|(_, s)| s.is_some()) {
+        return Err(Error::semantic(
+            span,
+            "array sizes can only be left out for the last dimensions",
+        ));
+    }
+    // an unspecified lower bound is 0, so it can be filled in when a later dimension has one
+    let known_bounds = bounds.iter().rposition(|(lo, _)| lo.is_some()).map_or(0, |i| i + 1);
+
+    let out_of_range = |_| Error::semantic(span, "array bounds must fit into a native integer");
+    Ok(ArrayShape {
+        rank: bounds.len(),
+        sizes: bounds[..known_sizes].iter().map(|(_, s)| s.unwrap()).collect(),
+        lower_bounds: bounds[..known_bounds]
+            .iter()
+            .map(|(lo, _)| isize::try_from(lo.unwrap_or(0)).map_err(out_of_range))
+            .collect::<Result<_>>()?,
+    })
+}
+
+pub(super) fn marshal(spec: &Spanned<ast::Marshal>) -> Result<MarshalSpec> {
+    use ast::NativeType::*;
+    let native = |n: &ast::NativeType| match n {
+        Bool => NativeIntrinsic::Boolean,
+        Int8 => NativeIntrinsic::Int8,
+        UInt8 => NativeIntrinsic::UInt8,
+        Int16 => NativeIntrinsic::Int16,
+        UInt16 => NativeIntrinsic::UInt16,
+        Int32 => NativeIntrinsic::Int32,
+        UInt32 => NativeIntrinsic::UInt32,
+        Int64 => NativeIntrinsic::Int64,
+        UInt64 => NativeIntrinsic::UInt64,
+        Float32 => NativeIntrinsic::Float32,
+        Float64 => NativeIntrinsic::Float64,
+        LPStr => NativeIntrinsic::LPStr,
+        LPWStr => NativeIntrinsic::LPWStr,
+        LPUTF8Str => NativeIntrinsic::LPUTF8Str,
+        Int => NativeIntrinsic::IntPtr,
+        UInt => NativeIntrinsic::UIntPtr,
+        Method => NativeIntrinsic::Function,
+        Interface => NativeIntrinsic::COMInterface,
+        BStr => NativeIntrinsic::BStr,
+        IUnknown => NativeIntrinsic::COMIUnknown,
+        AsAny => NativeIntrinsic::AsAny,
+    };
+
+    Ok(match &spec.value {
+        ast::Marshal::Native(n) => MarshalSpec::Primitive(native(n)),
+        ast::Marshal::Array {
+            element,
+            size,
+            parameter,
+        } => MarshalSpec::Array {
+            element_type: element.as_ref().map(native),
+            length_parameter: parameter
+                .as_ref()
+                .map(|p| unsigned(p, "parameter indices"))
+                .transpose()?,
+            additional_elements: size.as_ref().map(|s| unsigned(s, "array sizes")).transpose()?,
+        },
+    })
+}
+
+pub(super) fn constant(c: &Spanned<ast::Constant>) -> Result<Constant> {
+    use ast::ConstantValue::{Bool, Float, Int};
+
+    let span = c.span;
+    Ok(match &c.value {
+        ast::Constant::Typed(kind, value) => {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            match (kind.value.as_str(), value) {
+                ("bool", Bool(b)) => Constant::Boolean(*b),
+                ("char", Int(i)) => Constant::Char(bits(*i, 16, span)? as u16),
+                ("int8", Int(i)) => Constant::Int8(bits(*i, 8, span)? as u8 as i8),
+                ("uint8", Int(i)) => Constant::UInt8(bits(*i, 8, span)? as u8),
+                ("int16", Int(i)) => Constant::Int16(bits(*i, 16, span)? as u16 as i16),
+                ("uint16", Int(i)) => Constant::UInt16(bits(*i, 16, span)? as u16),
+                ("int32", Int(i)) => Constant::Int32(bits(*i, 32, span)? as u32 as i32),
+                ("uint32", Int(i)) => Constant::UInt32(bits(*i, 32, span)? as u32),
+                ("int64", Int(i)) => Constant::Int64(bits(*i, 64, span)? as i64),
+                ("uint64", Int(i)) => Constant::UInt64(bits(*i, 64, span)?),
+                // integers given to floating point constants are their bit patterns
+                ("float32", Int(i)) => Constant::Float32(f32::from_bits(bits(*i, 32, span)? as u32)),
+                ("float32", Float(f)) => Constant::Float32(*f as f32),
+                ("float64", Int(i)) => Constant::Float64(f64::from_bits(bits(*i, 64, span)?)),
+                ("float64", Float(f)) => Constant::Float64(*f),
+                (kind, _) => {
+                    return Err(Error::semantic(span, format!("invalid value for a {} constant", kind)));
+                }
+            }
+        }
+        ast::Constant::String(s) => Constant::String(s.clone()),
+        ast::Constant::Bytes(_) => {
+            return Err(Error::semantic(span, "byte array constants are not supported"));
+        }
+        ast::Constant::Null => Constant::Null,
+    })
+}
+
+struct CallConv {
+    instance: bool,
+    explicit_this: bool,
+    vararg: bool,
+    unmanaged: Option<StandAloneCallingConvention>,
+}
+
+fn call_conv(words: &[ast::Name]) -> Result<CallConv> {
+    let mut conv = CallConv {
+        instance: false,
+        explicit_this: false,
+        vararg: false,
+        unmanaged: None,
+    };
+    for w in words {
+        use StandAloneCallingConvention::*;
+        let unmanaged = match w.value.as_str() {
+            "instance" => {
+                conv.instance = true;
+                continue;
+            }
+            "explicit" => {
+                conv.explicit_this = true;
+                continue;
+            }
+            "vararg" => {
+                conv.vararg = true;
+                continue;
+            }
+            "default" => continue,
+            "unmanaged" => DefaultUnmanaged,
+            "cdecl" => Cdecl,
+            "stdcall" => Stdcall,
+            "thiscall" => Thiscall,
+            _ => Fastcall,
+        };
+        // `unmanaged` only introduces the specific convention, if there is one
+        if !matches!((conv.unmanaged, unmanaged), (Some(_), DefaultUnmanaged)) {
+            conv.unmanaged = Some(unmanaged);
+        }
+    }
+    if conv.explicit_this && !conv.instance {
+        return Err(Error::semantic(words[0].span, "explicit requires instance"));
+    }
+    Ok(conv)
+}
+
+fn conv_span(words: &[ast::Name], fallback: Span) -> Span {
+    match (words.first(), words.last()) {
+        (Some(first), Some(last)) => Span::new(first.span.start, last.span.end),
+        _ => fallback,
+    }
+}
+
+// the instantiated type definition, if a type names one of the types in this module
+fn definition(t: &MethodType) -> Option<TypeIndex> {
+    match t {
+        MethodType::Base(b) => match &**b {
+            BaseType::Type {
+                source: TypeSource::User(UserType::Definition(i)),
+                ..
+            } => Some(*i),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn same_parent(a: &MethodReferenceParent, b: &MethodReferenceParent) -> bool {
+    use MethodReferenceParent::*;
+    match (a, b) {
+        (Type(a), Type(b)) => a == b,
+        (Module(a), Module(b)) => a == b,
+        (VarargMethod(a), VarargMethod(b)) => a == b,
+        _ => false,
+    }
+}
+
+impl Assembler {
+    pub(super) fn type_name(&self, index: TypeIndex) -> String {
+        self.res[index].nested_type_name(&self.res)
+    }
+
+    fn type_reference(&mut self, scope: ResolutionScope, namespace: Option<String>, name: &str) -> TypeRefIndex {
+        let existing = self
+            .res
+            .enumerate_type_references()
+            .find(|(_, r)| r.scope == scope && r.namespace.as_deref() == namespace.as_deref() && r.name == name);
+        match existing {
+            Some((idx, _)) => idx,
+            None => self.res.push_type_reference(ExternalTypeReference::new(
+                namespace.map(Cow::Owned),
+                name.to_string(),
+                scope,
+            )),
+        }
+    }
+
+    pub(super) fn module_reference(&mut self, name: &ast::Name) -> Result<ModuleRefIndex> {
+        self.modules.get(&name.value).copied().ok_or_else(|| {
+            Error::semantic(
+                name.span,
+                format!("module {} has not been declared with .module extern", name.value),
+            )
+        })
+    }
+
+    pub(super) fn is_current_module(&self, name: &str) -> bool {
+        *self.res.module.name == *name
+    }
+
+    pub(super) fn user_type(&mut self, r: &ast::TypeRef) -> Result<UserType> {
+        let is_local = match r.scope.as_ref().map(|s| &s.value) {
+            None => true,
+            Some(ast::Scope::Assembly(a)) => self.assembly_name.as_deref() == Some(a.as_str()),
+            Some(ast::Scope::Module(m)) => self.is_current_module(m),
+        };
+
+        if is_local {
+            let key = super::type_key(r.namespace.as_deref(), &r.names);
+            return match self.types.get(&key) {
+                Some(&idx) => Ok(UserType::Definition(idx)),
+                None => Err(Error::semantic(
+                    r.span,
+                    format!("type {} is not defined in this module", key),
+                )),
+            };
+        }
+
+        let scope = r.scope.as_ref().unwrap();
+        let mut resolution_scope = match &scope.value {
+            ast::Scope::Assembly(a) => match self.assemblies.get(a) {
+                Some(&idx) => ResolutionScope::Assembly(idx),
+                None => {
+                    return Err(Error::semantic(
+                        scope.span,
+                        format!("assembly {} has not been declared with .assembly extern", a),
+                    ))
+                }
+            },
+            ast::Scope::Module(m) => match self.modules.get(m) {
+                Some(&idx) => ResolutionScope::ExternalModule(idx),
+                // types in other files of this assembly are found through its exported types
+                None if self.files.contains_key(m) => ResolutionScope::Exported,
+                None => {
+                    return Err(Error::semantic(
+                        scope.span,
+                        format!("module {} has not been declared with .module extern", m),
+                    ))
+                }
+            },
+        };
+
+        let mut namespace = r.namespace.clone();
+        let mut index = None;
+        for name in &r.names {
+            let idx = self.type_reference(resolution_scope, namespace.take(), name);
+            resolution_scope = ResolutionScope::Nested(idx);
+            index = Some(idx);
+        }
+        Ok(UserType::Reference(index.unwrap()))
+    }
+
+    fn modifier(&mut self, required: bool, r: &ast::TypeRef) -> Result<CustomTypeModifier> {
+        let t = self.user_type(r)?;
+        Ok(if required {
+            CustomTypeModifier::Required(t)
+        } else {
+            CustomTypeModifier::Optional(t)
+        })
+    }
+
+    pub(super) fn lower_type<T: SigType>(&mut self, t: &ast::Type, g: Generics) -> Result<Lowered<T>> {
+        let base_span = t.base.span;
+        let mut core = match &t.base.value {
+            ast::TypeBase::Primitive(ast::Primitive::Void) => Core::Void,
+            ast::TypeBase::Primitive(ast::Primitive::TypedRef) => Core::TypedRef,
+            ast::TypeBase::Primitive(p) => Core::Type(primitive(*p).into()),
+            ast::TypeBase::Class {
+                value_type,
+                reference,
+                arguments,
+            } => {
+                let base = self.user_type(reference)?;
+                let source = if arguments.is_empty() {
+                    TypeSource::User(base)
+                } else {
+                    TypeSource::Generic {
+                        base,
+                        parameters: arguments
+                            .iter()
+                            .map(|a| self.lower_type(a, g)?.plain())
+                            .collect::<Result<_>>()?,
+                    }
+                };
+                Core::Type(
+                    if *value_type {
+                        BaseType::valuetype(source)
+                    } else {
+                        BaseType::class(source)
+                    }
+                    .into(),
+                )
+            }
+            ast::TypeBase::TypeGeneric(r) => Core::Type(T::type_generic(generic_index(r, g.type_params, "type")?)),
+            ast::TypeBase::MethodGeneric(r) => Core::Type(T::method_generic(
+                generic_index(r, g.method_params, "method")?,
+                base_span,
+            )?),
+            ast::TypeBase::FunctionPointer(sig) => {
+                let mut return_type = sig.return_type.clone();
+                if !matches!(
+                    return_type.suffixes.pop(),
+                    Some(Spanned {
+                        value: ast::TypeSuffix::Pointer,
+                        ..
+                    })
+                ) {
+                    return Err(Error::semantic(
+                        sig.return_type.span,
+                        "expected a `*` after the return type of a function pointer",
+                    ));
+                }
+                let sig = self.standalone_signature(&sig.call_conv, &return_type, &sig.parameters, base_span, g)?;
+                Core::Type(BaseType::FunctionPointer(sig).into())
+            }
+        };
+
+        let mut modifiers = vec![];
+        let mut by_ref = false;
+        let mut pinned = false;
+        for suffix in &t.suffixes {
+            use ast::TypeSuffix::*;
+
+            let span = suffix.span;
+            if let Modifier { required, modifier } = &suffix.value {
+                modifiers.push(self.modifier(*required, modifier)?);
+                continue;
+            }
+            if pinned {
+                return Err(Error::semantic(span, "only custom modifiers can follow `pinned`"));
+            }
+            if by_ref && !matches!(suffix.value, Pinned) {
+                return Err(Error::semantic(
+                    span,
+                    "only `pinned` and custom modifiers can follow `&`",
+                ));
+            }
+
+            let element = |core: Core<T>| match core {
+                Core::Type(t) => Ok(t),
+                Core::Void => Err(Error::semantic(span, "void can only be pointed to")),
+                Core::TypedRef => Err(Error::semantic(span, "typedref cannot be used as an element type")),
+            };
+            core = match &suffix.value {
+                Vector => Core::Type(BaseType::Vector(std::mem::take(&mut modifiers), element(core)?).into()),
+                Array(dimensions) => {
+                    if !modifiers.is_empty() {
+                        return Err(Error::semantic(
+                            span,
+                            "the element type of a multi-dimensional array cannot have custom modifiers",
+                        ));
+                    }
+                    Core::Type(BaseType::Array(element(core)?, array_shape(dimensions, span)?).into())
+                }
+                Pointer => {
+                    let pointee = match core {
+                        Core::Void => None,
+                        other => Some(element(other)?),
+                    };
+                    Core::Type(BaseType::ValuePointer(std::mem::take(&mut modifiers), pointee).into())
+                }
+                ByRef => {
+                    if matches!(core, Core::Void) {
+                        return Err(Error::semantic(span, "void cannot be referenced"));
+                    }
+                    by_ref = true;
+                    core
+                }
+                Pinned => {
+                    pinned = true;
+                    core
+                }
+                Modifier { .. } => unreachable!(),
+            };
+        }
+
+        Ok(Lowered {
+            core,
+            modifiers,
+            by_ref,
+            pinned,
+            span: t.span,
+        })
+    }
+
+    pub(super) fn plain_type<T: SigType>(&mut self, t: &ast::Type, g: Generics) -> Result<T> {
+        self.lower_type(t, g)?.plain()
+    }
+
+    /// A type in a position where metadata refers to it by token, which may also be written as a bare type name.
+    /// Returns the custom modifiers separately, for the positions that allow them.
+    pub(super) fn type_spec_with_modifiers<T: SigType>(
+        &mut self,
+        spec: &ast::TypeSpec,
+        g: Generics,
+    ) -> Result<(Vec<CustomTypeModifier>, T)> {
+        let t = match spec {
+            ast::TypeSpec::Reference(r) => {
+                return Ok((vec![], BaseType::from(TypeSource::User(self.user_type(r)?)).into()))
+            }
+            ast::TypeSpec::Type(t) => t,
+        };
+
+        let mut lowered = self.lower_type::<T>(t, g)?;
+        let modifiers = std::mem::take(&mut lowered.modifiers);
+        let mut result = lowered.plain()?;
+
+        // `class Foo` names the same token as `Foo`, but a generic instantiation or any other type needs a signature
+        if let ast::TypeBase::Class {
+            reference, arguments, ..
+        } = &t.base.value
+        {
+            let only_modifiers = t
+                .suffixes
+                .iter()
+                .all(|s| matches!(s.value, ast::TypeSuffix::Modifier { .. }));
+            if arguments.is_empty() && only_modifiers {
+                result = BaseType::from(TypeSource::User(self.user_type(reference)?)).into();
+            }
+        }
+
+        Ok((modifiers, result))
+    }
+
+    pub(super) fn type_spec<T: SigType>(&mut self, spec: &ast::TypeSpec, g: Generics) -> Result<T> {
+        let (modifiers, t) = self.type_spec_with_modifiers(spec, g)?;
+        if modifiers.is_empty() {
+            Ok(t)
+        } else {
+            Err(Error::semantic(spec.span(), "custom modifiers are not allowed here"))
+        }
+    }
+
+    /// A base type or an implemented interface.
+    pub(super) fn type_source(&mut self, spec: &ast::TypeSpec, g: Generics) -> Result<TypeSource<MemberType>> {
+        let t = match spec {
+            ast::TypeSpec::Reference(r) => return Ok(TypeSource::User(self.user_type(r)?)),
+            ast::TypeSpec::Type(t) => t,
+        };
+        match &t.base.value {
+            ast::TypeBase::Class {
+                reference, arguments, ..
+            } if t.suffixes.is_empty() => {
+                let base = self.user_type(reference)?;
+                Ok(if arguments.is_empty() {
+                    TypeSource::User(base)
+                } else {
+                    TypeSource::Generic {
+                        base,
+                        parameters: arguments.iter().map(|a| self.plain_type(a, g)).collect::<Result<_>>()?,
+                    }
+                })
+            }
+            _ => Err(Error::semantic(t.span, "expected a class or interface type")),
+        }
+    }
+
+    pub(super) fn parameter<T: SigType>(&mut self, t: &ast::Type, g: Generics) -> Result<Parameter<T>> {
+        let mut lowered = self.lower_type(t, g)?;
+        let modifiers = std::mem::take(&mut lowered.modifiers);
+        Ok(Parameter(modifiers, lowered.parameter_type()?))
+    }
+
+    pub(super) fn return_type<T: SigType>(&mut self, t: &ast::Type, g: Generics) -> Result<ReturnType<T>> {
+        let mut lowered = self.lower_type(t, g)?;
+        let modifiers = std::mem::take(&mut lowered.modifiers);
+        if matches!(lowered.core, Core::Void) && !lowered.by_ref {
+            lowered.not_pinned()?;
+            return Ok(ReturnType(modifiers, None));
+        }
+        Ok(ReturnType(modifiers, Some(lowered.parameter_type()?)))
+    }
+
+    /// The fixed parameters of a signature, and the variable arguments after a `...` sentinel.
+    #[allow(clippy::type_complexity)]
+    pub(super) fn parameters<T: SigType>(
+        &mut self,
+        args: &[ast::SigArg],
+        g: Generics,
+    ) -> Result<(Vec<Parameter<T>>, Option<Vec<Parameter<T>>>)> {
+        let mut fixed = vec![];
+        let mut varargs: Option<Vec<_>> = None;
+        for a in args {
+            match a {
+                ast::SigArg::Sentinel(span) => {
+                    if varargs.is_some() {
+                        return Err(Error::semantic(*span, "a signature can only have one `...`"));
+                    }
+                    varargs = Some(vec![]);
+                }
+                ast::SigArg::Param(p) => {
+                    let p = self.parameter(&p.param_type, g)?;
+                    match &mut varargs {
+                        Some(v) => v.push(p),
+                        None => fixed.push(p),
+                    }
+                }
+            }
+        }
+        Ok((fixed, varargs))
+    }
+
+    /// The signature of a function pointer or `calli` call site.
+    pub(super) fn standalone_signature<T: SigType>(
+        &mut self,
+        words: &[ast::Name],
+        return_type: &ast::Type,
+        args: &[ast::SigArg],
+        span: Span,
+        g: Generics,
+    ) -> Result<MaybeUnmanagedMethod<T>> {
+        let conv = call_conv(words)?;
+        let calling_convention = match (conv.vararg, conv.unmanaged) {
+            (false, None) => StandAloneCallingConvention::DefaultManaged,
+            (true, None) => StandAloneCallingConvention::Vararg,
+            (false, Some(u)) => u,
+            (true, Some(_)) => {
+                return Err(Error::semantic(
+                    conv_span(words, span),
+                    "unmanaged calling conventions cannot be vararg",
+                ))
+            }
+        };
+        let (parameters, varargs) = self.parameters(args, g)?;
+        if varargs.is_some() && !conv.vararg {
+            return Err(Error::semantic(
+                span,
+                "only vararg signatures can have a `...` sentinel",
+            ));
+        }
+        Ok(MethodSignature {
+            instance: conv.instance,
+            explicit_this: conv.explicit_this,
+            calling_convention,
+            parameters,
+            return_type: self.return_type(return_type, g)?,
+            varargs,
+        })
+    }
+
+    /// The signature of a managed method with `arity` generic parameters.
+    pub(super) fn managed_signature(
+        &mut self,
+        words: &[ast::Name],
+        return_type: &ast::Type,
+        args: &[ast::SigArg],
+        arity: usize,
+        span: Span,
+        g: Generics,
+    ) -> Result<ManagedMethod<MethodType>> {
+        let conv = call_conv(words)?;
+        if conv.unmanaged.is_some() {
+            return Err(Error::semantic(
+                conv_span(words, span),
+                "unmanaged calling conventions are only allowed for function pointers and calli",
+            ));
+        }
+        let calling_convention = match (conv.vararg, arity) {
+            (false, 0) => CallingConvention::Default,
+            (true, 0) => CallingConvention::Vararg,
+            (false, n) => CallingConvention::Generic(n),
+            (true, _) => {
+                return Err(Error::semantic(
+                    conv_span(words, span),
+                    "generic methods cannot be vararg",
+                ))
+            }
+        };
+        let (parameters, varargs) = self.parameters(args, g)?;
+        if varargs.is_some() && !conv.vararg {
+            return Err(Error::semantic(
+                span,
+                "only vararg signatures can have a `...` sentinel",
+            ));
+        }
+        Ok(MethodSignature {
+            instance: conv.instance,
+            explicit_this: conv.explicit_this,
+            calling_convention,
+            parameters,
+            return_type: self.return_type(return_type, g)?,
+            varargs,
+        })
+    }
+
+    pub(super) fn generic_params<T: SigType>(
+        &mut self,
+        params: &[ast::GenericParam],
+        allow_variance: bool,
+        g: Generics,
+    ) -> Result<Vec<generic::Generic<'static, T>>> {
+        params
+            .iter()
+            .map(|p| {
+                let mut generic = generic::Generic::new(p.name.value.clone());
+                if let Some(v) = &p.variance {
+                    if !allow_variance {
+                        return Err(Error::semantic(
+                            v.span,
+                            "only interface and delegate type parameters can be variant",
+                        ));
+                    }
+                    generic.variance = if v.value {
+                        generic::Variance::Covariant
+                    } else {
+                        generic::Variance::Contravariant
+                    };
+                }
+                generic.special_constraint = generic::SpecialConstraint {
+                    reference_type: p.reference_type,
+                    value_type: p.value_type,
+                    has_default_constructor: p.default_constructor,
+                };
+                for c in &p.constraints {
+                    let (custom_modifiers, constraint_type) = self.type_spec_with_modifiers(c, g)?;
+                    generic.type_constraints.push(generic::Constraint {
+                        attributes: vec![],
+                        custom_modifiers,
+                        constraint_type,
+                    });
+                }
+                Ok(generic)
+            })
+            .collect()
+    }
+
+    fn method_reference(
+        &mut self,
+        parent: MethodReferenceParent,
+        name: &str,
+        signature: ManagedMethod<MethodType>,
+    ) -> MethodRefIndex {
+        let existing = self
+            .res
+            .enumerate_method_references()
+            .find(|(_, r)| same_parent(&r.parent, &parent) && r.name == name && r.signature == signature);
+        match existing {
+            Some((idx, _)) => idx,
+            None => self
+                .res
+                .push_method_reference(ExternalMethodReference::new(parent, name.to_string(), signature)),
+        }
+    }
+
+    // a method declared in this module, or a call site of one of its vararg methods
+    fn local_method(
+        &mut self,
+        parent: TypeIndex,
+        name: &ast::Name,
+        signature: ManagedMethod<MethodType>,
+        span: Span,
+    ) -> Result<UserMethod> {
+        let mut fixed = signature.clone();
+        fixed.varargs = None;
+
+        let found = self.methods.get(&parent).into_iter().flatten().copied().find(|&m| {
+            let method = &self.res[m];
+            method.name == name.value && method.signature == fixed
+        });
+
+        match found {
+            Some(m) if signature.varargs.is_some() => Ok(UserMethod::Reference(self.method_reference(
+                MethodReferenceParent::VarargMethod(m),
+                &name.value,
+                signature,
+            ))),
+            Some(m) => Ok(UserMethod::Definition(m)),
+            None => Err(Error::semantic(
+                span,
+                if self.is_module_type(parent) {
+                    format!("no global method {} with this signature has been declared", name.value)
+                } else {
+                    format!(
+                        "type {} does not declare a method {} with this signature",
+                        self.type_name(parent),
+                        name.value
+                    )
+                },
+            )),
+        }
+    }
+
+    /// The method with the given parent, name and signature, which is looked up if it is declared in this module.
+    pub(super) fn method_with_signature(
+        &mut self,
+        parent: Option<&ast::MemberParent>,
+        name: &ast::Name,
+        signature: ManagedMethod<MethodType>,
+        span: Span,
+        g: Generics,
+    ) -> Result<UserMethod> {
+        let module_type = self.module_type();
+        Ok(match parent {
+            None => self.local_method(module_type, name, signature, span)?,
+            Some(ast::MemberParent::Module(module)) if self.is_current_module(&module.value) => {
+                self.local_method(module_type, name, signature, span)?
+            }
+            Some(ast::MemberParent::Module(module)) => {
+                let module = self.module_reference(module)?;
+                UserMethod::Reference(self.method_reference(
+                    MethodReferenceParent::Module(module),
+                    &name.value,
+                    signature,
+                ))
+            }
+            Some(ast::MemberParent::Type(spec)) => {
+                let parent: MethodType = self.type_spec(spec, g)?;
+                match definition(&parent) {
+                    Some(def) => self.local_method(def, name, signature, span)?,
+                    None => UserMethod::Reference(self.method_reference(
+                        MethodReferenceParent::Type(parent),
+                        &name.value,
+                        signature,
+                    )),
+                }
+            }
+        })
+    }
+
+    pub(super) fn method_ref(&mut self, m: &ast::MethodRef, g: Generics) -> Result<MethodSource> {
+        let arity = match &m.generics {
+            ast::MethodGenerics::None => 0,
+            ast::MethodGenerics::Arity(n) => unsigned(n, "generic arities")?,
+            ast::MethodGenerics::Arguments(a) => a.len(),
+        };
+        let sig = &m.signature;
+        let signature = self.managed_signature(&sig.call_conv, &sig.return_type, &sig.parameters, arity, m.span, g)?;
+
+        let user = self.method_with_signature(m.parent.as_ref(), &m.name, signature, m.span, g)?;
+
+        Ok(match &m.generics {
+            ast::MethodGenerics::Arguments(arguments) => {
+                let parameters = arguments.iter().map(|a| self.plain_type(a, g)).collect::<Result<_>>()?;
+                MethodSource::Generic(GenericMethodInstantiation::new(user, parameters))
+            }
+            _ => MethodSource::User(user),
+        })
+    }
+
+    /// A method reference that cannot be a generic instantiation, as used by attributes, overrides and `newobj`.
+    pub(super) fn user_method(&mut self, m: &ast::MethodRef, g: Generics) -> Result<UserMethod> {
+        match self.method_ref(m, g)? {
+            MethodSource::User(u) => Ok(u),
+            MethodSource::Generic(_) => Err(Error::semantic(
+                m.span,
+                "a generic method instantiation is not allowed here",
+            )),
+        }
+    }
+
+    pub(super) fn field_ref(&mut self, f: &ast::FieldRef, g: Generics) -> Result<FieldSource> {
+        let mut lowered = self.lower_type::<MemberType>(&f.field_type, g)?;
+        let custom_modifiers = std::mem::take(&mut lowered.modifiers);
+        let by_ref = lowered.by_ref;
+        lowered.by_ref = false;
+        let field_type = lowered.plain()?;
+
+        let parent = match &f.parent {
+            None => None,
+            Some(ast::MemberParent::Module(module)) if self.is_current_module(&module.value) => None,
+            Some(ast::MemberParent::Module(module)) => {
+                Some(FieldReferenceParent::Module(self.module_reference(module)?))
+            }
+            Some(ast::MemberParent::Type(spec)) => {
+                let parent: MethodType = self.type_spec(spec, g)?;
+                match definition(&parent) {
+                    Some(def) => return self.local_field(def, f, &field_type, by_ref),
+                    None => Some(FieldReferenceParent::Type(parent)),
+                }
+            }
+        };
+        let Some(parent) = parent else {
+            let module_type = self.module_type();
+            return self.local_field(module_type, f, &field_type, by_ref);
+        };
+
+        if by_ref {
+            return Err(Error::semantic(
+                f.field_type.span,
+                "references to by-reference fields are not supported",
+            ));
+        }
+
+        let existing = self.res.enumerate_field_references().find(|(_, r)| {
+            r.parent == parent
+                && r.name == f.name.value
+                && r.field_type == field_type
+                && r.custom_modifiers == custom_modifiers
+        });
+        Ok(FieldSource::Reference(match existing {
+            Some((idx, _)) => idx,
+            None => {
+                let mut reference = ExternalFieldReference::new(parent, field_type, f.name.value.clone().into());
+                reference.custom_modifiers = custom_modifiers;
+                self.res.push_field_reference(reference)
+            }
+        }))
+    }
+
+    fn local_field(
+        &self,
+        parent: TypeIndex,
+        f: &ast::FieldRef,
+        field_type: &MemberType,
+        by_ref: bool,
+    ) -> Result<FieldSource> {
+        let Some((idx, field)) = self.res.enumerate_fields(parent).find(|(_, d)| d.name == f.name.value) else {
+            return Err(Error::semantic(
+                f.span,
+                if self.is_module_type(parent) {
+                    format!("no global field {} has been declared", f.name.value)
+                } else {
+                    format!(
+                        "type {} does not declare a field {}",
+                        self.type_name(parent),
+                        f.name.value
+                    )
+                },
+            ));
+        };
+        if field.return_type != *field_type || field.by_ref != by_ref {
+            return Err(Error::semantic(
+                f.field_type.span,
+                format!("field {} is declared with a different type", f.name.value),
+            ));
+        }
+        Ok(FieldSource::Definition(idx))
+    }
+}
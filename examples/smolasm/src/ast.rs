@@ -1,249 +1,619 @@
-use dotnetdll::access;
-use dotnetdll::prelude::{Accessibility, BaseType};
-use std::fmt::{Display, Formatter};
+// the syntax tree mirrors the grammar, so some declarations are much larger than their siblings
+#![allow(clippy::large_enum_variant)]
+
+use crate::error::Span;
 
 pub type Ident = String;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct Dotted(pub Vec<Ident>);
-impl Display for Dotted {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0.join("."))
-    }
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
 }
-impl Dotted {
-    pub fn into_names(self) -> (Option<String>, String) {
-        let Dotted(mut segments) = self;
-        let name = segments.pop().unwrap();
-        let namespace = if segments.is_empty() {
-            None
-        } else {
-            Some(segments.join("."))
-        };
-        (namespace, name)
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: impl Into<Span>) -> Self {
+        Self {
+            value,
+            span: span.into(),
+        }
     }
 }
 
+pub type Name = Spanned<Ident>;
+pub type Int = Spanned<i128>;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Scope {
+    Assembly(Ident),
+    Module(Ident),
+}
+/// A type name, with the names of its enclosing types first.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TypeRef {
+    pub scope: Option<Spanned<Scope>>,
+    /// The namespace of the outermost type.
+    pub namespace: Option<Ident>,
+    pub names: Vec<Ident>,
+    pub span: Span,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Primitive {
+    Void,
+    Bool,
+    Char,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+    IntPtr,
+    UIntPtr,
+    Object,
+    String,
+    TypedRef,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum GenericRef {
+    Index(Int),
+    Name(Name),
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeBase {
+    Primitive(Primitive),
+    Class {
+        value_type: bool,
+        reference: TypeRef,
+        arguments: Vec<Type>,
+    },
+    TypeGeneric(GenericRef),
+    MethodGeneric(GenericRef),
+    FunctionPointer(Box<Signature>),
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum ArrayDimension {
+    Unbounded,
+    Size(i128),
+    LowerBound(i128),
+    Range(i128, i128),
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeSuffix {
+    Vector,
+    Array(Vec<ArrayDimension>),
+    Pointer,
+    ByRef,
+    Pinned,
+    Modifier { required: bool, modifier: TypeRef },
+}
+
 #[derive(Debug, Clone)]
-pub struct Version(pub Vec<u32>);
+pub struct Type {
+    pub base: Spanned<TypeBase>,
+    pub suffixes: Vec<Spanned<TypeSuffix>>,
+    pub span: Span,
+}
+
+/// A type where a bare type name is also allowed, as in `box [mscorlib]System.Int32`.
 #[derive(Debug, Clone)]
-pub struct AssemblySpec {
-    pub assembly: Dotted,
-    pub version: Option<Version>,
+pub enum TypeSpec {
+    Type(Type),
+    Reference(TypeRef),
+}
+impl TypeSpec {
+    pub fn span(&self) -> Span {
+        match self {
+            TypeSpec::Type(t) => t.span,
+            TypeSpec::Reference(r) => r.span,
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-pub enum IntType {
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ParamAttr {
+    In,
+    Out,
+    Opt,
+}
+
+#[derive(Debug, Clone)]
+pub enum NativeType {
     Bool,
-    Char,
-    SByte,
-    Byte,
-    Short,
-    UShort,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+    LPStr,
+    LPWStr,
+    LPUTF8Str,
     Int,
     UInt,
-    Long,
-    ULong,
-    NInt,
-    NUInt,
-}
-impl<T> From<IntType> for BaseType<T> {
-    fn from(i: IntType) -> Self {
-        use IntType::*;
-        match i {
-            Bool => BaseType::Boolean,
-            Char => BaseType::Char,
-            SByte => BaseType::Int8,
-            Byte => BaseType::UInt8,
-            Short => BaseType::Int16,
-            UShort => BaseType::UInt16,
-            Int => BaseType::Int32,
-            UInt => BaseType::UInt32,
-            Long => BaseType::Int64,
-            ULong => BaseType::UInt64,
-            NInt => BaseType::IntPtr,
-            NUInt => BaseType::UIntPtr,
-        }
-    }
+    Method,
+    Interface,
+    BStr,
+    IUnknown,
+    AsAny,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct TypeRef {
-    pub parent: Option<Dotted>,
-    pub target: Dotted,
+#[derive(Debug, Clone)]
+pub enum Marshal {
+    Native(NativeType),
+    Array {
+        element: Option<NativeType>,
+        size: Option<Int>,
+        parameter: Option<Int>,
+    },
 }
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub enum Type {
-    Integer(IntType),
-    String,
-    Object,
-    Float,
-    Double,
-    RefType(TypeRef),
-    ValueType(TypeRef),
-    Vector(Box<Type>),
-    Pointer(Option<Box<Type>>),
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub attributes: Vec<ParamAttr>,
+    pub param_type: Type,
+    pub marshal: Option<Spanned<Marshal>>,
+    pub name: Option<Name>,
 }
 
 #[derive(Debug, Clone)]
-pub struct Enum {
-    pub base: Option<IntType>,
-    pub name: Dotted,
-    pub members: Vec<Ident>,
+pub enum SigArg {
+    /// The `...` that separates the fixed parameters from the variable arguments of a vararg call site.
+    Sentinel(Span),
+    Param(Param),
 }
 
-#[derive(Debug, Copy, Clone)]
-pub enum Access {
-    Public,
-    Private,
-    PrivateProtected,
-    Protected,
-    ProtectedInternal,
-    Internal,
-}
-impl From<Access> for Accessibility {
-    fn from(a: Access) -> Self {
-        use Access::*;
-        match a {
-            Public => access!(public),
-            Private => access!(private),
-            PrivateProtected => access!(private protected),
-            Protected => access!(protected),
-            ProtectedInternal => access!(protected internal),
-            Internal => access!(internal),
-        }
-    }
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub call_conv: Vec<Name>,
+    pub return_type: Type,
+    pub parameters: Vec<SigArg>,
+}
+
+#[derive(Debug, Clone)]
+pub enum MemberParent {
+    Type(TypeSpec),
+    Module(Name),
 }
 
 #[derive(Debug, Clone)]
-pub struct Locals {
-    pub init: bool,
-    pub variables: Vec<(Type, Ident)>,
+pub enum MethodGenerics {
+    None,
+    Arity(Int),
+    Arguments(Vec<Type>),
 }
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+
+#[derive(Debug, Clone)]
 pub struct MethodRef {
-    pub r#static: bool,
-    pub return_type: Option<ParamType>,
-    pub parent: Type,
-    pub method: Ident,
-    pub parameters: Vec<ParamType>,
+    pub signature: Signature,
+    pub parent: Option<MemberParent>,
+    pub name: Name,
+    pub generics: MethodGenerics,
+    pub span: Span,
 }
+
 #[derive(Debug, Clone)]
 pub struct FieldRef {
-    pub return_type: Type,
-    pub parent: Type,
-    pub field: Ident,
+    pub field_type: Type,
+    pub parent: Option<MemberParent>,
+    pub name: Name,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ConstantValue {
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+}
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Typed(Name, ConstantValue),
+    String(Vec<u16>),
+    Bytes(Vec<u8>),
+    Null,
+}
+
+#[derive(Debug, Clone)]
+pub struct Custom {
+    pub constructor: MethodRef,
+    pub value: Option<Vec<u8>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum SecurityAction {
+    Named(Name),
+    Value(Int),
 }
-pub type Label = Ident;
+
 #[derive(Debug, Clone)]
-pub enum Instruction {
-    Add,
-    Box(Type),
-    Branch(Label),
-    Call { r#virtual: bool, method: MethodRef },
-    LoadArgument(Ident),
-    LoadDouble(f64),
-    LoadElement(Type),
-    LoadField(FieldRef),
-    LoadFloat(f32),
-    LoadInt(i32),
-    LoadLocal(Ident),
-    LoadLong(i64),
-    LoadString(String),
-    New(Type, Vec<ParamType>),
-    Return,
-    StoreField(FieldRef),
-    StoreLocal(Ident),
+pub struct PermissionSet {
+    pub action: SecurityAction,
+    pub value: Vec<u8>,
+    pub span: Span,
 }
+
+/// A `.custom` or `.permissionset` directive, in the order in which they appear.
+/// Custom attributes that follow a `.permissionset` belong to that security declaration.
 #[derive(Debug, Clone)]
-pub struct MethodBody {
-    pub max_stack: Option<u32>,
-    pub locals: Option<Locals>,
-    pub instructions: Vec<(Vec<Label>, Instruction)>,
+pub enum Attribute {
+    Custom(Custom),
+    PermissionSet(PermissionSet),
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct ParamType {
-    pub r#ref: bool,
-    pub r#type: Type,
+#[derive(Debug, Clone)]
+pub enum GenericTarget {
+    Index(Int),
+    Name(Name),
 }
+
 #[derive(Debug, Clone)]
-pub struct Method {
-    pub name: Ident,
-    pub parameters: Vec<(ParamType, Ident)>,
-    pub return_type: Option<ParamType>,
-    pub attributes: Vec<Ident>,
-    pub body: Option<MethodBody>,
+pub enum ParamDirective {
+    Param {
+        index: Int,
+        default: Option<Spanned<Constant>>,
+    },
+    Type(GenericTarget),
+    Constraint(GenericTarget, TypeSpec),
 }
 
 #[derive(Debug, Clone)]
-pub struct Field(pub Type, pub Ident);
+pub enum OverrideTarget {
+    Method(MethodRef),
+    Name(TypeSpec, Name),
+}
 
 #[derive(Debug, Clone)]
-pub struct SemanticMethod(pub Ident, pub MethodBody);
+pub struct Override {
+    pub declaration: OverrideTarget,
+    pub implementation: Option<MethodRef>,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
-pub struct Property {
-    pub r#type: Type,
-    pub name: Ident,
-    pub methods: Vec<SemanticMethod>,
+pub struct GenericParam {
+    pub variance: Option<Spanned<bool>>,
+    pub reference_type: bool,
+    pub value_type: bool,
+    pub default_constructor: bool,
+    pub constraints: Vec<TypeSpec>,
+    pub name: Name,
 }
+
+// method bodies
+
 #[derive(Debug, Clone)]
-pub struct Event {
-    pub r#type: Type,
-    pub name: Ident,
-    pub methods: Vec<SemanticMethod>,
+pub enum VariableRef {
+    Index(Int),
+    Name(Name),
 }
 
 #[derive(Debug, Clone)]
-pub enum TypeItemKind {
-    Field(Field),
-    Method(Method),
-    Property(Property),
-    Event(Event),
+pub enum Float {
+    Literal(f64),
+    Integer(i128),
+    Bits(Name, Int),
+    Bytes(Vec<u8>),
 }
+
 #[derive(Debug, Clone)]
-pub struct TypeItem {
-    pub access: Access,
-    pub r#static: bool,
-    pub kind: TypeItemKind,
+pub enum Token {
+    Method(MethodRef),
+    Field(FieldRef),
+    Type(TypeSpec),
 }
+
 #[derive(Debug, Clone)]
-pub enum TypeKind {
-    Class { r#abstract: bool },
-    Struct,
-    Interface,
+pub enum Operand {
+    None,
+    Variable(VariableRef),
+    Int(Int),
+    Float(Spanned<Float>),
+    Branch(Name),
+    Method(MethodRef),
+    Field(FieldRef),
+    Type(TypeSpec),
+    String(Vec<u16>),
+    Signature(Signature),
+    Token(Token),
+    Switch(Vec<Name>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub mnemonic: Name,
+    pub operand: Operand,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Prefix {
+    Tail,
+    Volatile,
+    Readonly,
+    Unaligned(Int),
+    Constrained(TypeSpec),
+    No(Int),
+}
+
+#[derive(Debug, Clone)]
+pub struct Local {
+    pub slot: Option<Int>,
+    pub local_type: Type,
+    pub name: Option<Name>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Region {
+    Labels(Name, Name),
+    Block(Vec<MethodItem>, Span),
+}
+
+/// The code of a filter, which is either a block or runs from a label up to the start of its handler.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Label(Name),
+    Block(Region),
+}
+
+#[derive(Debug, Clone)]
+pub enum HandlerKind {
+    Catch(TypeSpec),
+    Filter(Filter),
+    Finally,
+    Fault,
+}
+
+#[derive(Debug, Clone)]
+pub struct Handler {
+    pub kind: HandlerKind,
+    pub region: Region,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Try {
+    pub region: Region,
+    pub handlers: Vec<Handler>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum MethodItem {
+    EntryPoint(Span),
+    MaxStack(Int),
+    Locals {
+        init: bool,
+        variables: Vec<Local>,
+        span: Span,
+    },
+    Attribute(Attribute),
+    Param(ParamDirective, Span),
+    Override(Override),
+    Try(Try),
+    Label(Name),
+    Prefix(Spanned<Prefix>),
+    Instruction(Instruction),
+}
+
+// declarations
+
+#[derive(Debug, Clone)]
+pub struct PInvoke {
+    pub module: Option<Spanned<String>>,
+    pub name: Option<String>,
+    pub flags: Vec<Name>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldDecl {
+    pub offset: Option<Int>,
+    pub flags: Vec<Name>,
+    pub marshal: Option<Spanned<Marshal>>,
+    pub field_type: Type,
+    pub name: Name,
+    pub data: Option<Name>,
+    pub default: Option<Spanned<Constant>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct MethodDecl {
+    pub flags: Vec<Name>,
+    pub pinvoke: Option<PInvoke>,
+    pub call_conv: Vec<Name>,
+    pub return_type: Type,
+    pub return_marshal: Option<Spanned<Marshal>>,
+    pub name: Name,
+    pub generics: Vec<GenericParam>,
+    pub parameters: Vec<SigArg>,
+    pub impl_flags: Vec<Name>,
+    pub items: Vec<MethodItem>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Accessor {
+    pub kind: Name,
+    pub method: MethodRef,
 }
+
+#[derive(Debug, Clone)]
+pub struct PropertyDecl {
+    pub flags: Vec<Name>,
+    pub call_conv: Vec<Name>,
+    pub property_type: Type,
+    pub name: Name,
+    pub parameters: Vec<SigArg>,
+    pub default: Option<Spanned<Constant>>,
+    pub attributes: Vec<Attribute>,
+    pub accessors: Vec<Accessor>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventDecl {
+    pub flags: Vec<Name>,
+    pub delegate_type: Option<TypeSpec>,
+    pub name: Name,
+    pub attributes: Vec<Attribute>,
+    pub accessors: Vec<Accessor>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ClassMember {
+    Class(ClassDecl),
+    Field(FieldDecl),
+    Method(MethodDecl),
+    Property(PropertyDecl),
+    Event(EventDecl),
+    Attribute(Attribute),
+    Param(ParamDirective, Span),
+    InterfaceImpl(TypeSpec, Span),
+    Pack(Int),
+    Size(Int),
+    Override(Override),
+    Data(DataDecl),
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassDecl {
+    /// Class flags, with `nested` visibilities already joined into a single word such as `nested public`.
+    pub flags: Vec<Name>,
+    /// The namespace written as part of the name, which is only allowed for top-level types.
+    pub namespace: Option<Ident>,
+    pub name: Name,
+    pub generics: Vec<GenericParam>,
+    pub extends: Option<TypeSpec>,
+    pub implements: Vec<TypeSpec>,
+    pub members: Vec<ClassMember>,
+    pub span: Span,
+}
+
+// manifest
+
+#[derive(Debug, Clone)]
+pub enum ManifestItem {
+    Attribute(Attribute),
+    PublicKey(Vec<u8>),
+    PublicKeyToken(Vec<u8>),
+    Hash(Vec<u8>),
+    HashAlgorithm(Int),
+    Version([Int; 4]),
+    Locale(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct AssemblyRefDecl {
+    pub name: Name,
+    pub alias: Option<Name>,
+    pub items: Vec<ManifestItem>,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
-pub struct TypeDeclaration {
-    pub kind: TypeKind,
-    pub name: Dotted,
-    pub extends: Option<TypeRef>,
-    pub implements: Option<Vec<TypeRef>>,
-    pub items: Vec<TypeItem>,
+pub struct AssemblyDecl {
+    pub retargetable: bool,
+    pub name: Name,
+    pub items: Vec<ManifestItem>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
-pub enum TopLevelKind {
-    Enum(Enum),
-    Type(TypeDeclaration),
+pub struct FileDecl {
+    pub has_metadata: bool,
+    pub name: Name,
+    pub hash: Option<Vec<u8>>,
+    pub entry_point: bool,
+    pub span: Span,
 }
+
+#[derive(Debug, Clone)]
+pub enum ExportedItem {
+    Attribute(Attribute),
+    File(Name),
+    TypeDefinition(Int),
+    Encloser(Name),
+    Assembly(Name),
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportedTypeDecl {
+    pub forwarder: bool,
+    pub flags: Vec<Name>,
+    pub namespace: Option<Ident>,
+    pub name: Name,
+    pub items: Vec<ExportedItem>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ResourceItem {
+    Attribute(Attribute),
+    File(Name, Int),
+    Assembly(Name),
+}
+
 #[derive(Debug, Clone)]
-pub struct TopLevel {
+pub struct ResourceDecl {
     pub public: bool,
-    pub kind: TopLevelKind,
+    pub name: Name,
+    pub items: Vec<ResourceItem>,
+    pub span: Span,
 }
-impl TopLevel {
-    pub fn name(&self) -> &Dotted {
-        match &self.kind {
-            TopLevelKind::Enum(e) => &e.name,
-            TopLevelKind::Type(t) => &t.name,
-        }
-    }
+
+#[derive(Debug, Clone)]
+pub enum DataItem {
+    Bytes(Vec<u8>),
+    Value {
+        data_type: Name,
+        value: Option<Spanned<ConstantValue>>,
+        count: Option<Int>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct DataDecl {
+    pub kind: Option<Name>,
+    pub label: Option<Name>,
+    pub items: Vec<DataItem>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Declaration {
+    AssemblyRef(AssemblyRefDecl),
+    Assembly(AssemblyDecl),
+    ModuleRef(Name),
+    Module(Name),
+    File(FileDecl),
+    ExportedType(ExportedTypeDecl),
+    Resource(ResourceDecl),
+    Namespace(Name, Vec<Declaration>),
+    Class(ClassDecl),
+    Field(FieldDecl),
+    Method(MethodDecl),
+    Attribute(Attribute),
+    Data(DataDecl),
 }
 
 #[derive(Debug, Clone)]
-pub struct Assembly {
-    pub assembly_decl: AssemblySpec,
-    pub extern_decls: Vec<AssemblySpec>,
-    pub top_level_decls: Vec<TopLevel>,
+pub struct Source {
+    pub declarations: Vec<Declaration>,
 }
@@ -0,0 +1,117 @@
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+/// A range of byte offsets into the assembled source.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The 1-based line and column of the start of the span.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        (line, column)
+    }
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(s: pest::Span) -> Self {
+        Self::new(s.start(), s.end())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// The source does not follow the ILAsm grammar.
+    Syntax,
+    /// The source is well-formed, but does not describe a valid module, e.g. because it refers to an undeclared type.
+    Semantic,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Syntax => "syntax error",
+            ErrorKind::Semantic => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("{kind}: {message}")]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Error {
+    pub fn syntax(span: impl Into<Span>, message: impl Into<String>) -> Self {
+        Self {
+            kind: ErrorKind::Syntax,
+            span: span.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn semantic(span: impl Into<Span>, message: impl Into<String>) -> Self {
+        Self {
+            kind: ErrorKind::Semantic,
+            span: span.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Formats the error with its location and the offending line of `source`, which must be the text the error
+    /// was reported for.
+    pub fn render(&self, source: &str, filename: &str) -> String {
+        let (line, column) = self.span.line_col(source);
+        let text = source.lines().nth(line - 1).unwrap_or_default();
+        let width = if self.span.end > self.span.start {
+            source[self.span.start..self.span.end.min(source.len())]
+                .lines()
+                .next()
+                .map_or(1, |l| l.chars().count().max(1))
+        } else {
+            1
+        };
+
+        format!(
+            "{}\n --> {}:{}:{}\n  |\n  | {}\n  | {}{}",
+            self,
+            filename,
+            line,
+            column,
+            text,
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl<R: pest::RuleType> From<pest::error::Error<R>> for Error {
+    fn from(e: pest::error::Error<R>) -> Self {
+        use pest::error::{ErrorVariant, InputLocation};
+
+        let span = match e.location {
+            InputLocation::Pos(p) => Span::new(p, p),
+            InputLocation::Span((s, e)) => Span::new(s, e),
+        };
+        let message = match &e.variant {
+            ErrorVariant::ParsingError { .. } => e.variant.message().into_owned(),
+            ErrorVariant::CustomError { message } => message.clone(),
+        };
+
+        Self::syntax(span, message)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
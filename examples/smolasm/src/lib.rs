@@ -0,0 +1,18 @@
+//! An assembler for the ILAsm syntax of ECMA-335 Partition II, producing a [`Resolution`].
+//!
+//! Parsing and lowering report [`Error`]s with the source span they refer to instead of panicking.
+
+use dotnetdll::prelude::*;
+
+pub mod ast;
+pub mod error;
+pub mod parse;
+
+mod assemble;
+
+pub use error::{Error, ErrorKind, Result, Span};
+
+/// Parses ILAsm source code and assembles it into a module.
+pub fn assemble(source: &str) -> Result<Resolution<'static>> {
+    assemble::assemble(&parse::source(source)?)
+}
@@ -1,31 +1,29 @@
 use super::Rule;
+use crate::error::Result;
 use pest::iterators::{Pair, Pairs};
 
 pub trait Combinators {
-    fn maybe<T>(&mut self, target: Rule, body: impl FnOnce(Pair<Rule>) -> T) -> Option<T>;
-    fn many0<T>(&mut self, target: Rule, body: impl FnMut(Pair<Rule>) -> T) -> Vec<T>;
+    fn maybe<T>(&mut self, target: Rule, body: impl FnOnce(Pair<Rule>) -> Result<T>) -> Result<Option<T>>;
+    fn many0<T>(&mut self, target: Rule, body: impl FnMut(Pair<Rule>) -> Result<T>) -> Result<Vec<T>>;
 }
 
 impl Combinators for Pairs<'_, Rule> {
-    fn maybe<T>(&mut self, target: Rule, body: impl FnOnce(Pair<Rule>) -> T) -> Option<T> {
-        self.peek().and_then(|p| {
-            if p.as_rule() == target {
-                Some(body(self.next().unwrap()))
-            } else {
-                None
-            }
-        })
+    fn maybe<T>(&mut self, target: Rule, body: impl FnOnce(Pair<Rule>) -> Result<T>) -> Result<Option<T>> {
+        match self.peek() {
+            Some(p) if p.as_rule() == target => body(self.next().unwrap()).map(Some),
+            _ => Ok(None),
+        }
     }
 
-    fn many0<T>(&mut self, target: Rule, mut body: impl FnMut(Pair<Rule>) -> T) -> Vec<T> {
+    fn many0<T>(&mut self, target: Rule, mut body: impl FnMut(Pair<Rule>) -> Result<T>) -> Result<Vec<T>> {
         let mut result = vec![];
         while let Some(next) = self.peek() {
             if next.as_rule() == target {
-                result.push(body(self.next().unwrap()));
+                result.push(body(self.next().unwrap())?);
             } else {
                 break;
             }
         }
-        result
+        Ok(result)
     }
 }
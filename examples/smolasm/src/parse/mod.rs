@@ -3,434 +3,1164 @@ use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::Parser;
 
-use super::ast;
+use crate::ast::{self, Spanned};
+use crate::error::{Error, Result, Span};
 
 mod combinators;
 
 #[derive(Parser)]
-#[grammar = "parse/assembly.pest"]
-struct AssemblyParser;
+#[grammar = "parse/ilasm.pest"]
+struct ILAsmParser;
 
 macro_rules! build_rule_parsers {
     ($( $rule:ident($input:ident) -> $t:ty { $($body:tt)* } )*) => {
         $(
-            fn $rule($input: Pair<Rule>) -> $t {
-                if $input.as_rule() != Rule::$rule {
-                    let (line, col) = $input.as_span().start_pos().line_col();
-                    panic!(
-                        "expected {:?} but received {:?} at line {}:{}",
-                        Rule::$rule,
-                        $input.as_rule(),
-                        line,
-                        col
-                    );
-                }
+            #[allow(clippy::needless_pass_by_value)]
+            fn $rule($input: Pair<Rule>) -> Result<$t> {
+                // the grammar guarantees the shape of the parse tree, so a mismatch is a bug in this module
+                assert_eq!(
+                    $input.as_rule(),
+                    Rule::$rule,
+                    "unexpected rule at {:?}",
+                    $input.as_span().start_pos().line_col()
+                );
                 $($body)*
             }
         )*
     };
 }
 
-build_rule_parsers! {
-    ident(input) -> ast::Ident {
-        input.as_str().to_string()
+fn span(input: &Pair<Rule>) -> Span {
+    input.as_span().into()
+}
+
+fn syntax<T>(input: &Pair<Rule>, message: impl Into<String>) -> Result<T> {
+    Err(Error::syntax(span(input), message))
+}
+
+fn word(input: Pair<Rule>) -> ast::Name {
+    Spanned::new(input.as_str().to_string(), input.as_span())
+}
+
+fn unescape(input: &Pair<Rule>, quoted: &str) -> Result<String> {
+    let mut buf = String::new();
+    let mut chars = quoted[1..quoted.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            buf.push(c);
+            continue;
+        }
+        buf.push(match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some(c @ ('\\' | '"' | '\'' | '?')) => c,
+            Some(c) => return syntax(input, format!("unknown escape sequence \\{}", c)),
+            None => return syntax(input, "unterminated escape sequence"),
+        });
+    }
+    Ok(buf)
+}
+
+// a single part of an identifier, quoted or not
+fn id_part(input: Pair<Rule>) -> Result<String> {
+    match input.as_rule() {
+        Rule::quoted_id => unescape(&input, input.as_str()),
+        _ => Ok(input.as_str().to_string()),
     }
-    method_ident(input) -> ast::Ident {
-        input.as_str().to_string()
+}
+
+// a type name, whose last part is the name of the type and the rest its namespace
+fn qualified(input: Pair<Rule>) -> Result<(Option<ast::Ident>, ast::Name)> {
+    let span = input.as_span();
+    let mut parts = input.into_inner().map(id_part).collect::<Result<Vec<_>>>()?;
+    let name = parts.pop().unwrap();
+    Ok(((!parts.is_empty()).then(|| parts.join(".")), Spanned::new(name, span)))
+}
+
+fn utf16(bytes: &[u8], input: &Pair<Rule>) -> Result<Vec<u16>> {
+    if !bytes.len().is_multiple_of(2) {
+        return syntax(input, "a UTF-16 byte array must have an even number of bytes");
     }
-    dotted(input) -> ast::Dotted {
-        ast::Dotted(input.as_str().split('.').map(String::from).collect())
+    Ok(bytes.chunks(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect())
+}
+
+build_rule_parsers! {
+    id(input) -> ast::Name {
+        let span = input.as_span();
+        Ok(Spanned::new(id_part(input.into_inner().next().unwrap())?, span))
     }
-    asm_decl(input) -> ast::AssemblySpec {
-        asm_spec(input.into_inner().next().unwrap())
+    dotted(input) -> ast::Name {
+        let span = input.as_span();
+        let parts = input.into_inner().map(id_part).collect::<Result<Vec<_>>>()?;
+        Ok(Spanned::new(parts.join("."), span))
     }
-    asm_spec(input) -> ast::AssemblySpec {
-        let mut inner = input.into_inner();
-        ast::AssemblySpec {
-            assembly: dotted(inner.next().unwrap()),
-            version: inner.next().map(|v| {
-                ast::Version(
-                    v.into_inner().map(|p| p.as_str().parse().unwrap()).collect()
-                )
-            }),
+    method_name(input) -> ast::Name {
+        let inner = input.into_inner().next().unwrap();
+        match inner.as_rule() {
+            Rule::ctor_name => Ok(word(inner)),
+            _ => dotted(inner),
         }
     }
-    int_type(input) -> ast::IntType {
-        use ast::IntType::*;
-        match input.as_str() {
-            "bool" => Bool,
-            "char" => Char,
-            "sbyte" => SByte,
-            "byte" => Byte,
-            "short" => Short,
-            "ushort" => UShort,
-            "int" => Int,
-            "uint" => UInt,
-            "long" => Long,
-            "ulong" => ULong,
-            "nint" => NInt,
-            "nuint" => NUInt,
-            _ => unreachable!()
+    int_lit(input) -> ast::Int {
+        let text = input.as_str();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(d) => (true, d),
+            None => (false, text),
+        };
+        let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+            Some(hex) => i128::from_str_radix(hex, 16),
+            None => digits.parse(),
+        };
+        match value {
+            Ok(v) => Ok(Spanned::new(if negative { -v } else { v }, input.as_span())),
+            Err(_) => syntax(&input, format!("integer literal {} is too large", text)),
         }
     }
-    enum_decl(input) -> ast::Enum {
-        let mut inner = input.into_inner();
-        ast::Enum {
-            base: inner.maybe(Rule::int_type, int_type),
-            name: dotted(inner.next().unwrap()),
-            members: inner.map(ident).collect()
+    float_lit(input) -> f64 {
+        match input.as_str().parse() {
+            Ok(f) => Ok(f),
+            Err(e) => syntax(&input, format!("invalid floating point literal: {}", e)),
         }
     }
-    type_kind(input) -> ast::TypeKind {
-        use ast::TypeKind::*;
-        match input.as_str() {
-            "struct" => Struct,
-            "interface" => Interface,
-            other => Class { r#abstract: other.starts_with("abstract") }
+    string(input) -> String {
+        input.into_inner().map(|p| unescape(&p, p.as_str())).collect()
+    }
+    bytes(input) -> Vec<u8> {
+        Ok(input
+            .into_inner()
+            .map(|p| u8::from_str_radix(p.as_str(), 16).unwrap())
+            .collect())
+    }
+    bytearray(input) -> Vec<u8> {
+        bytes(input.into_inner().next().unwrap())
+    }
+
+    // types
+
+    nested_name(input) -> (Option<ast::Ident>, Vec<ast::Ident>) {
+        let mut inner = input.into_inner();
+        let (namespace, outer) = qualified(inner.next().unwrap())?;
+        let mut names = vec![outer.value];
+        for p in inner {
+            names.push(dotted(p)?.value);
         }
+        Ok((namespace, names))
     }
     type_ref(input) -> ast::TypeRef {
+        let span = span(&input);
         let mut inner = input.into_inner();
-        let first = dotted(inner.next().unwrap());
-
-        // if there is another pair to consume, then the first pair is the parent assembly
-        if let Some(pair) = inner.next() {
-            ast::TypeRef {
-                parent: Some(first),
-                target: dotted(pair)
-            }
-        } else {
-            ast::TypeRef {
-                parent: None,
-                target: first
-            }
-        }
+        let scope = inner.maybe(Rule::resolution_scope, |p| {
+            let span = p.as_span();
+            let target = p.into_inner().next().unwrap();
+            Ok(Spanned::new(
+                match target.as_rule() {
+                    Rule::module_scope => {
+                        ast::Scope::Module(dotted(target.into_inner().next().unwrap())?.value)
+                    }
+                    _ => ast::Scope::Assembly(dotted(target)?.value),
+                },
+                span,
+            ))
+        })?;
+        let (namespace, names) = nested_name(inner.next().unwrap())?;
+        Ok(ast::TypeRef {
+            scope,
+            namespace,
+            names,
+            span,
+        })
     }
-    clitype(input) -> ast::Type {
-        use ast::Type::*;
-        match input.as_str() {
-            "string" => String,
+    primitive(input) -> ast::Primitive {
+        use ast::Primitive::*;
+        Ok(match input.as_str().split_whitespace().collect::<Vec<_>>().join(" ").as_str() {
+            "void" => Void,
+            "bool" => Bool,
+            "char" => Char,
+            "int8" => Int8,
+            "uint8" | "unsigned int8" => UInt8,
+            "int16" => Int16,
+            "uint16" | "unsigned int16" => UInt16,
+            "int32" => Int32,
+            "uint32" | "unsigned int32" => UInt32,
+            "int64" => Int64,
+            "uint64" | "unsigned int64" => UInt64,
+            "float32" => Float32,
+            "float64" => Float64,
+            "native int" => IntPtr,
+            "native uint" | "native unsigned int" => UIntPtr,
             "object" => Object,
-            "float" => Float,
-            "double" => Double,
-            _ => {
-                let valuetype = input.as_str().starts_with("valuetype");
-                let pair = input.into_inner().next().unwrap();
-                match pair.as_rule() {
-                    Rule::int_type => Integer(int_type(pair)),
-                    Rule::vector => Vector(Box::new(clitype(pair.into_inner().next().unwrap()))),
-                    Rule::type_ref => if valuetype {
-                        ValueType(type_ref(pair))
-                    } else {
-                        RefType(type_ref(pair))
-                    },
-                    Rule::pointer => Pointer({
-                        let inner = pair.into_inner().next().unwrap();
-                        if inner.as_rule() == Rule::void_ptr {
-                            None
-                        } else {
-                            Some(Box::new(clitype(inner)))
-                        }
-                    }),
-                    _ => unreachable!()
+            "string" => String,
+            "typedref" => TypedRef,
+            _ => unreachable!(),
+        })
+    }
+    generic_args(input) -> Vec<ast::Type> {
+        input.into_inner().map(r#type).collect()
+    }
+    call_conv(input) -> Vec<ast::Name> {
+        Ok(input.into_inner().map(word).collect())
+    }
+    fn_ptr(input) -> ast::Signature {
+        let mut inner = input.into_inner();
+        Ok(ast::Signature {
+            call_conv: call_conv(inner.next().unwrap())?,
+            return_type: r#type(inner.next().unwrap())?,
+            parameters: sig_args(inner.next().unwrap())?,
+        })
+    }
+    type_base(input) -> Spanned<ast::TypeBase> {
+        let span = input.as_span();
+        let inner = input.into_inner().next().unwrap();
+        let generic = |p: Pair<Rule>| {
+            let target = p.into_inner().next().unwrap();
+            match target.as_rule() {
+                Rule::int_lit => int_lit(target).map(ast::GenericRef::Index),
+                _ => id(target).map(ast::GenericRef::Name),
+            }
+        };
+
+        use ast::TypeBase::*;
+        let base = match inner.as_rule() {
+            Rule::primitive => Primitive(primitive(inner)?),
+            Rule::class_type => {
+                let mut parts = inner.into_inner();
+                let value_type = parts.next().unwrap().as_str() == "valuetype";
+                Class {
+                    value_type,
+                    reference: type_ref(parts.next().unwrap())?,
+                    arguments: parts.maybe(Rule::generic_args, generic_args)?.unwrap_or_default(),
                 }
             }
-        }
+            Rule::method_generic => MethodGeneric(generic(inner)?),
+            Rule::type_generic => TypeGeneric(generic(inner)?),
+            Rule::fn_ptr => FunctionPointer(Box::new(fn_ptr(inner)?)),
+            _ => unreachable!(),
+        };
+        Ok(Spanned::new(base, span))
     }
-    access(input) -> ast::Access {
-        use ast::Access::*;
-        match input.as_str() {
-            "public" => Public,
-            "internal" => Internal,
-            s if s.starts_with("private") => if s.ends_with("protected") {
-                PrivateProtected
-            } else {
-                Private
-            },
-            s if s.starts_with("protected") => if s.ends_with("internal") {
-                ProtectedInternal
-            } else {
-                Protected
-            },
-            _ => unreachable!()
+    array_dim(input) -> ast::ArrayDimension {
+        use ast::ArrayDimension::*;
+        let Some(inner) = input.into_inner().next() else {
+            return Ok(Unbounded);
+        };
+        if inner.as_rule() == Rule::ellipsis {
+            return Ok(Unbounded);
         }
+
+        let mut parts = inner.into_inner();
+        let first = int_lit(parts.next().unwrap())?.value;
+        Ok(match (parts.next(), parts.next()) {
+            (None, _) => Size(first),
+            (Some(_), None) => LowerBound(first),
+            (Some(_), Some(upper)) => Range(first, int_lit(upper)?.value),
+        })
     }
-    field(input) -> ast::Field {
+    type_suffix(input) -> Spanned<ast::TypeSuffix> {
+        let span = input.as_span();
+        let inner = input.into_inner().next().unwrap();
+
+        use ast::TypeSuffix::*;
+        let suffix = match inner.as_rule() {
+            Rule::vector_suffix => Vector,
+            Rule::array_suffix => Array(inner.into_inner().map(array_dim).collect::<Result<_>>()?),
+            Rule::pointer_suffix => Pointer,
+            Rule::byref_suffix => ByRef,
+            Rule::pinned_suffix => Pinned,
+            Rule::modifier => {
+                let mut parts = inner.into_inner();
+                Modifier {
+                    required: parts.next().unwrap().as_str() == "modreq",
+                    modifier: type_ref(parts.next().unwrap())?,
+                }
+            }
+            _ => unreachable!(),
+        };
+        Ok(Spanned::new(suffix, span))
+    }
+    r#type(input) -> ast::Type {
+        let span = span(&input);
         let mut inner = input.into_inner();
-        ast::Field(clitype(inner.next().unwrap()), ident(inner.next().unwrap()))
+        Ok(ast::Type {
+            base: type_base(inner.next().unwrap())?,
+            suffixes: inner.map(type_suffix).collect::<Result<_>>()?,
+            span,
+        })
     }
-    param_type(input) -> ast::ParamType {
-        ast::ParamType {
-            r#ref: input.as_str().starts_with("ref"),
-            r#type: clitype(input.into_inner().next().unwrap())
+    type_spec(input) -> ast::TypeSpec {
+        let inner = input.into_inner().next().unwrap();
+        match inner.as_rule() {
+            Rule::r#type => r#type(inner).map(ast::TypeSpec::Type),
+            _ => type_ref(inner).map(ast::TypeSpec::Reference),
         }
     }
-    locals(input) -> ast::Locals {
-        ast::Locals {
-            init: input.as_str().starts_with("init"),
-            variables: input.into_inner().map(|p| {
-                let mut inner = p.into_inner();
-                (clitype(inner.next().unwrap()), ident(inner.next().unwrap()))
-            }).collect()
-        }
+
+    // signatures and member references
+
+    param(input) -> ast::Param {
+        let mut inner = input.into_inner();
+        Ok(ast::Param {
+            attributes: inner.many0(Rule::param_attr, |p| {
+                Ok(match p.into_inner().next().unwrap().as_str() {
+                    "in" => ast::ParamAttr::In,
+                    "out" => ast::ParamAttr::Out,
+                    _ => ast::ParamAttr::Opt,
+                })
+            })?,
+            param_type: r#type(inner.next().unwrap())?,
+            marshal: inner.maybe(Rule::marshal_spec, marshal_spec)?,
+            name: inner.maybe(Rule::id, id)?,
+        })
     }
-    label(input) -> ast::Label {
-        let mut buf = input.as_str().to_string();
-        // remove trailing colon character
-        buf.pop();
-        buf
-    }
-    return_type(input) -> Option<ast::ParamType> {
-        if input.as_str() == "void" {
-            None
-        } else {
-            Some(param_type(input.into_inner().next().unwrap()))
+    sig_args(input) -> Vec<ast::SigArg> {
+        input
+            .into_inner()
+            .map(|arg| {
+                let inner = arg.into_inner().next().unwrap();
+                match inner.as_rule() {
+                    Rule::ellipsis => Ok(ast::SigArg::Sentinel(span(&inner))),
+                    _ => param(inner).map(ast::SigArg::Param),
+                }
+            })
+            .collect()
+    }
+    member_parent(input) -> ast::MemberParent {
+        let inner = input.into_inner().next().unwrap();
+        match inner.as_rule() {
+            Rule::module_parent => {
+                dotted(inner.into_inner().next().unwrap()).map(ast::MemberParent::Module)
+            }
+            Rule::r#type => r#type(inner).map(|t| ast::MemberParent::Type(ast::TypeSpec::Type(t))),
+            _ => type_ref(inner).map(|r| ast::MemberParent::Type(ast::TypeSpec::Reference(r))),
         }
     }
     method_ref(input) -> ast::MethodRef {
-        let r#static = input.as_str().starts_with("static");
+        let span = span(&input);
         let mut inner = input.into_inner();
-        let mut method_name = inner.next().unwrap().into_inner();
-        let parameters = inner.many0(Rule::param_type, param_type);
-        let return_type = return_type(inner.next().unwrap());
-        ast::MethodRef {
-            r#static,
-            return_type,
-            parent: clitype(method_name.next().unwrap()),
-            method: method_ident(method_name.next().unwrap()),
-            parameters
-        }
+        let call_conv = call_conv(inner.next().unwrap())?;
+        let return_type = r#type(inner.next().unwrap())?;
+        let parent = inner.maybe(Rule::member_parent, member_parent)?;
+        let name = method_name(inner.next().unwrap())?;
+        let generics = match inner.peek().map(|p| p.as_rule()) {
+            Some(Rule::generic_arity) => ast::MethodGenerics::Arity(int_lit(
+                inner.next().unwrap().into_inner().next().unwrap(),
+            )?),
+            Some(Rule::generic_args) => {
+                ast::MethodGenerics::Arguments(generic_args(inner.next().unwrap())?)
+            }
+            _ => ast::MethodGenerics::None,
+        };
+        Ok(ast::MethodRef {
+            signature: ast::Signature {
+                call_conv,
+                return_type,
+                parameters: sig_args(inner.next().unwrap())?,
+            },
+            parent,
+            name,
+            generics,
+            span,
+        })
     }
     field_ref(input) -> ast::FieldRef {
+        let span = span(&input);
         let mut inner = input.into_inner();
-        let return_type = clitype(inner.next().unwrap());
-        let mut field_name = inner.next().unwrap().into_inner();
-        ast::FieldRef {
-            return_type,
-            parent: clitype(field_name.next().unwrap()),
-            field: ident(field_name.next().unwrap())
-        }
-    }
-    ctor_ref(input) -> (ast::Type, Vec<ast::ParamType>) {
-        let mut inner = input.into_inner();
-        (clitype(inner.next().unwrap()), inner.many0(Rule::param_type, param_type))
+        Ok(ast::FieldRef {
+            field_type: r#type(inner.next().unwrap())?,
+            parent: inner.maybe(Rule::member_parent, member_parent)?,
+            name: dotted(inner.next().unwrap())?,
+            span,
+        })
     }
-    instruction(input) -> ast::Instruction {
-        use ast::Instruction::*;
 
-        fn pop_token(s: &str) -> (&str, &str) {
-            match s.find(char::is_whitespace) {
-                Some(i) => (&s[..i], s[i + 1..].trim_start()),
-                None => (s, ""),
+    // constants and marshalling
+
+    constant(input) -> Spanned<ast::Constant> {
+        let span = input.as_span();
+        let inner = input.into_inner().next().unwrap();
+        let constant = match inner.as_rule() {
+            Rule::typed_constant => {
+                let mut parts = inner.into_inner();
+                let const_type = word(parts.next().unwrap());
+                let value = parts.next().unwrap();
+                ast::Constant::Typed(const_type, constant_value(value)?)
             }
-        }
-        fn parse_single(rule: Rule, s: &str) -> Pair<Rule> {
-            match AssemblyParser::parse(rule, s) {
-                Ok(mut ps) => ps.next().unwrap(),
-                Err(e) => panic!("failed to parse {:?} in instruction: {}", rule, e),
+            Rule::string => ast::Constant::String(string(inner)?.encode_utf16().collect()),
+            Rule::bytearray => ast::Constant::Bytes(bytearray(inner)?),
+            _ => ast::Constant::Null,
+        };
+        Ok(Spanned::new(constant, span))
+    }
+    marshal_spec(input) -> Spanned<ast::Marshal> {
+        let span = input.as_span();
+        let mut inner = input.into_inner();
+        let native = inner.maybe(Rule::native_type, native_type)?;
+        let marshal = match inner.next() {
+            Some(array) => {
+                let (mut size, mut parameter) = (None, None);
+                if let Some(s) = array.into_inner().next() {
+                    let starts_with_plus = s.as_str().starts_with('+');
+                    let mut numbers = s.into_inner();
+                    if starts_with_plus {
+                        parameter = Some(int_lit(numbers.next().unwrap())?);
+                    } else {
+                        size = Some(int_lit(numbers.next().unwrap())?);
+                        parameter = numbers.next().map(int_lit).transpose()?;
+                    }
+                }
+                ast::Marshal::Array {
+                    element: native,
+                    size,
+                    parameter,
+                }
             }
-        }
-        macro_rules! single {
-            ($rule:ident, $params:expr) => {
-                $rule(parse_single(Rule::$rule, $params))
+            None => match native {
+                Some(n) => ast::Marshal::Native(n),
+                None => {
+                    return Err(Error::syntax(span, "expected a native type to marshal as"));
+                }
+            },
+        };
+        Ok(Spanned::new(marshal, span))
+    }
+    native_type(input) -> ast::NativeType {
+        use ast::NativeType::*;
+        Ok(match input.as_str() {
+            "bool" => Bool,
+            "int8" => Int8,
+            "uint8" => UInt8,
+            "int16" => Int16,
+            "uint16" => UInt16,
+            "int32" => Int32,
+            "uint32" => UInt32,
+            "int64" => Int64,
+            "uint64" => UInt64,
+            "float32" => Float32,
+            "float64" => Float64,
+            "lpstr" => LPStr,
+            "lpwstr" => LPWStr,
+            "lputf8str" => LPUTF8Str,
+            "int" => Int,
+            "uint" => UInt,
+            "method" => Method,
+            "interface" => Interface,
+            "bstr" => BStr,
+            "iunknown" => IUnknown,
+            _ => AsAny,
+        })
+    }
+
+    // custom attributes and security
+
+    custom(input) -> ast::Custom {
+        let span = span(&input);
+        let mut inner = input.into_inner();
+        Ok(ast::Custom {
+            constructor: method_ref(inner.next().unwrap())?,
+            value: inner.next().map(bytes).transpose()?,
+            span,
+        })
+    }
+    permissionset(input) -> ast::PermissionSet {
+        let span = span(&input);
+        let mut inner = input.into_inner();
+        let action = inner.next().unwrap();
+        let action = match action.as_rule() {
+            Rule::int_lit => ast::SecurityAction::Value(int_lit(action)?),
+            _ => ast::SecurityAction::Named(word(action)),
+        };
+        let value = inner.next().unwrap();
+        Ok(ast::PermissionSet {
+            action,
+            value: match value.as_rule() {
+                Rule::bytearray => bytearray(value)?,
+                _ => bytes(value)?,
+            },
+            span,
+        })
+    }
+
+    // generic parameters
+
+    generic_param(input) -> ast::GenericParam {
+        let mut inner = input.into_inner();
+        let variance = inner.maybe(Rule::variance, |p| Ok(Spanned::new(p.as_str() == "+", p.as_span())))?;
+        let specials = inner.many0(Rule::special_constraint, |p| Ok(p.as_str().to_string()))?;
+        let constraints = inner
+            .maybe(Rule::generic_constraints, |p| p.into_inner().map(type_spec).collect())?
+            .unwrap_or_default();
+        Ok(ast::GenericParam {
+            variance,
+            reference_type: specials.iter().any(|s| s == "class"),
+            value_type: specials.iter().any(|s| s == "valuetype"),
+            default_constructor: specials.iter().any(|s| s == ".ctor"),
+            constraints,
+            name: id(inner.next().unwrap())?,
+        })
+    }
+    generic_params(input) -> Vec<ast::GenericParam> {
+        input.into_inner().map(generic_param).collect()
+    }
+
+    // method bodies
+
+    float_operand(input) -> Spanned<ast::Float> {
+        let span = input.as_span();
+        let inner = input.into_inner().next().unwrap();
+        let float = match inner.as_rule() {
+            Rule::float_bits => {
+                let mut parts = inner.into_inner();
+                ast::Float::Bits(word(parts.next().unwrap()), int_lit(parts.next().unwrap())?)
             }
+            Rule::float_lit => ast::Float::Literal(float_lit(inner)?),
+            Rule::int_lit => ast::Float::Integer(int_lit(inner)?.value),
+            _ => ast::Float::Bytes(bytes(inner)?),
+        };
+        Ok(Spanned::new(float, span))
+    }
+    instruction(input) -> ast::Instruction {
+        let span = span(&input);
+        let inner = input.into_inner().next().unwrap();
+        if inner.as_rule() == Rule::instr_none {
+            return Ok(ast::Instruction {
+                mnemonic: word(inner),
+                operand: ast::Operand::None,
+                span,
+            });
         }
 
-        let (mnemonic, params) = pop_token(input.as_str());
-        match mnemonic {
-            "add" => Add,
-            "box" => Box(single!(clitype, params)),
-            "branch" => Branch(single!(ident, params)),
-            "call" => if let ("virtual", virt_params) = pop_token(params) {
-                Call {
-                    r#virtual: true,
-                    method: single!(method_ref, virt_params)
-                }
-            } else {
-                Call {
-                    r#virtual: false,
-                    method: single!(method_ref, params)
-                }
-            },
-            "load" => {
-                let (load_type, params) = pop_token(params);
-                match load_type {
-                    "argument" => LoadArgument(single!(ident, params)),
-                    "double" => LoadDouble(params.parse().unwrap()),
-                    "element" => LoadElement(single!(clitype, params)),
-                    "field" => LoadField(single!(field_ref, params)),
-                    "float" => LoadFloat(params.parse().unwrap()),
-                    "int" => LoadInt(params.parse().unwrap()),
-                    "local" => LoadLocal(single!(ident, params)),
-                    "long" => LoadLong(params.parse().unwrap()),
-                    "string" => {
-                        let mut buf = String::new();
-                        let mut arg_iter = params.chars();
-
-                        if arg_iter.next() != Some('"') {
-                            panic!("expected string literal");
-                        }
-
-                        loop {
-                            match arg_iter.next().expect("unterminated string literal") {
-                                '"' => break,
-                                '\\' => buf.push(match arg_iter.next().expect("bad escape sequence") {
-                                    '\\' => '\\',
-                                    '"' => '"',
-                                    'n' => '\n',
-                                    't' => '\t',
-                                    other => panic!("unknown escape sequence \\{}", other),
-                                }),
-                                other => buf.push(other),
-                            }
-                        }
+        let kind = inner.as_rule();
+        let mnemonic_span = inner.as_span();
+        let mnemonic = inner.as_str().split_whitespace().next().unwrap().to_string();
+        let mut parts = inner.into_inner();
+        // instructions without an opcode rule match their mnemonic as a literal
+        if matches!(
+            kind,
+            Rule::instr_variable
+                | Rule::instr_int
+                | Rule::instr_float
+                | Rule::instr_branch
+                | Rule::instr_method
+                | Rule::instr_field
+                | Rule::instr_type
+        ) {
+            parts.next();
+        }
+        let mnemonic = Spanned::new(
+            mnemonic.clone(),
+            Span::new(mnemonic_span.start(), mnemonic_span.start() + mnemonic.len()),
+        );
 
-                        LoadString(buf)
+        use ast::Operand;
+        let operand = match kind {
+            Rule::instr_variable => {
+                let target = parts.next().unwrap();
+                Operand::Variable(match target.as_rule() {
+                    Rule::int_lit => ast::VariableRef::Index(int_lit(target)?),
+                    _ => ast::VariableRef::Name(id(target)?),
+                })
+            }
+            Rule::instr_int => Operand::Int(int_lit(parts.next().unwrap())?),
+            Rule::instr_float => Operand::Float(float_operand(parts.next().unwrap())?),
+            Rule::instr_branch => Operand::Branch(id(parts.next().unwrap())?),
+            Rule::instr_method => Operand::Method(method_ref(parts.next().unwrap())?),
+            Rule::instr_field => Operand::Field(field_ref(parts.next().unwrap())?),
+            Rule::instr_type => Operand::Type(type_spec(parts.next().unwrap())?),
+            Rule::instr_string => {
+                let value = parts.next().unwrap();
+                Operand::String(match value.as_rule() {
+                    Rule::bytearray => utf16(&bytearray(value.clone())?, &value)?,
+                    _ => string(value)?.encode_utf16().collect(),
+                })
+            }
+            Rule::instr_sig => Operand::Signature(ast::Signature {
+                call_conv: call_conv(parts.next().unwrap())?,
+                return_type: r#type(parts.next().unwrap())?,
+                parameters: sig_args(parts.next().unwrap())?,
+            }),
+            Rule::instr_token => {
+                let target = parts.next().unwrap();
+                Operand::Token(match target.as_rule() {
+                    Rule::token_method => {
+                        ast::Token::Method(method_ref(target.into_inner().next().unwrap())?)
                     }
-                    other => panic!("unrecognized load type `{}`", other)
-                }
+                    Rule::token_field => {
+                        ast::Token::Field(field_ref(target.into_inner().next().unwrap())?)
+                    }
+                    _ => ast::Token::Type(type_spec(target)?),
+                })
+            }
+            Rule::instr_switch => Operand::Switch(parts.map(id).collect::<Result<_>>()?),
+            _ => unreachable!(),
+        };
+
+        Ok(ast::Instruction {
+            mnemonic,
+            operand,
+            span,
+        })
+    }
+    prefix(input) -> Spanned<ast::Prefix> {
+        let span = input.as_span();
+        let inner = input.into_inner().next().unwrap();
+        let prefix = match inner.as_rule() {
+            Rule::prefix_simple => match inner.as_str() {
+                "tail." => ast::Prefix::Tail,
+                "volatile." => ast::Prefix::Volatile,
+                _ => ast::Prefix::Readonly,
             },
-            "new" => {
-                let (parent, param_types) = single!(ctor_ref, params);
-                New(parent, param_types)
+            Rule::prefix_unaligned => {
+                ast::Prefix::Unaligned(int_lit(inner.into_inner().next().unwrap())?)
+            }
+            Rule::prefix_constrained => {
+                ast::Prefix::Constrained(type_spec(inner.into_inner().next().unwrap())?)
+            }
+            _ => ast::Prefix::No(int_lit(inner.into_inner().next().unwrap())?),
+        };
+        Ok(Spanned::new(prefix, span))
+    }
+    locals(input) -> ast::MethodItem {
+        let span = span(&input);
+        let mut inner = input.into_inner();
+        let init = inner.maybe(Rule::local_init, |_| Ok(()))?.is_some();
+        let variables = inner
+            .map(|l| {
+                let mut parts = l.into_inner();
+                Ok(ast::Local {
+                    slot: parts.maybe(Rule::int_lit, int_lit)?,
+                    local_type: r#type(parts.next().unwrap())?,
+                    name: parts.next().map(id).transpose()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(ast::MethodItem::Locals {
+            init,
+            variables,
+            span,
+        })
+    }
+    param_directive(input) -> ast::ParamDirective {
+        let inner = input.into_inner().next().unwrap();
+        let rule = inner.as_rule();
+        let mut parts = inner.into_inner();
+        let target = |parts: &mut pest::iterators::Pairs<Rule>| {
+            let t = parts.next().unwrap();
+            match t.as_rule() {
+                Rule::int_lit => int_lit(t).map(ast::GenericTarget::Index),
+                _ => id(t).map(ast::GenericTarget::Name),
+            }
+        };
+        Ok(match rule {
+            Rule::param_type_directive => ast::ParamDirective::Type(target(&mut parts)?),
+            Rule::param_constraint_directive => {
+                let t = target(&mut parts)?;
+                ast::ParamDirective::Constraint(t, type_spec(parts.next().unwrap())?)
+            }
+            _ => ast::ParamDirective::Param {
+                index: int_lit(parts.next().unwrap())?,
+                default: parts.next().map(constant).transpose()?,
             },
-            "return" => Return,
-            "store" => {
-                let (store_type, params) = pop_token(params);
-                match store_type {
-                    "field" => StoreField(single!(field_ref, params)),
-                    "local" => StoreLocal(single!(ident, params)),
-                    other => panic!("unrecognized store type `{}`", other)
-                }
+        })
+    }
+    method_override(input) -> ast::Override {
+        let span = span(&input);
+        let mut inner = input.into_inner();
+        let target = inner.next().unwrap().into_inner().next().unwrap();
+        let declaration = match target.as_rule() {
+            Rule::method_ref => ast::OverrideTarget::Method(method_ref(target)?),
+            _ => {
+                let mut parts = target.into_inner();
+                let parent = type_spec(parts.next().unwrap())?;
+                ast::OverrideTarget::Name(parent, method_name(parts.next().unwrap())?)
+            }
+        };
+        let target_pair = inner.next();
+        Ok(ast::Override {
+            declaration,
+            implementation: target_pair.map(method_ref).transpose()?,
+            span,
+        })
+    }
+    label_range(input) -> ast::Region {
+        let mut inner = input.into_inner();
+        Ok(ast::Region::Labels(id(inner.next().unwrap())?, id(inner.next().unwrap())?))
+    }
+    block(input) -> ast::Region {
+        let span = span(&input);
+        Ok(ast::Region::Block(input.into_inner().map(method_item).collect::<Result<_>>()?, span))
+    }
+    handler(input) -> ast::Handler {
+        let span = span(&input);
+        let mut inner = input.into_inner();
+        let clause = inner.next().unwrap();
+        let kind = match clause.as_rule() {
+            Rule::catch_clause => {
+                ast::HandlerKind::Catch(type_spec(clause.into_inner().next().unwrap())?)
+            }
+            Rule::filter_clause => {
+                let target = clause.into_inner().next().unwrap();
+                ast::HandlerKind::Filter(match target.as_rule() {
+                    Rule::block => ast::Filter::Block(block(target)?),
+                    _ => ast::Filter::Label(id(target)?),
+                })
+            }
+            Rule::finally_clause => ast::HandlerKind::Finally,
+            _ => ast::HandlerKind::Fault,
+        };
+        let scope = inner.next().unwrap().into_inner().next().unwrap();
+        Ok(ast::Handler {
+            kind,
+            region: match scope.as_rule() {
+                Rule::label_range => label_range(scope)?,
+                _ => block(scope)?,
             },
-            other => panic!("unrecognized instruction mnemonic `{}`", other)
-        }
+            span,
+        })
     }
-    method_body(input) -> ast::MethodBody {
+    try_block(input) -> ast::Try {
+        let span = span(&input);
         let mut inner = input.into_inner();
+        let scope = inner.next().unwrap().into_inner().next().unwrap();
+        Ok(ast::Try {
+            region: match scope.as_rule() {
+                Rule::label_range => label_range(scope)?,
+                _ => block(scope)?,
+            },
+            handlers: inner.map(handler).collect::<Result<_>>()?,
+            span,
+        })
+    }
+    method_item(input) -> ast::MethodItem {
+        let inner = input.into_inner().next().unwrap();
+        let span = span(&inner);
 
-        let max_stack = inner.maybe(Rule::nat, |p| p.as_str().parse().unwrap());
-        let locals = inner.maybe(Rule::locals, locals);
+        use ast::MethodItem::*;
+        Ok(match inner.as_rule() {
+            Rule::entrypoint => EntryPoint(span),
+            Rule::maxstack => MaxStack(int_lit(inner.into_inner().next().unwrap())?),
+            Rule::locals => locals(inner)?,
+            Rule::custom => Attribute(ast::Attribute::Custom(custom(inner)?)),
+            Rule::permissionset => Attribute(ast::Attribute::PermissionSet(permissionset(inner)?)),
+            Rule::param_directive => Param(param_directive(inner)?, span),
+            Rule::method_override => Override(method_override(inner)?),
+            Rule::try_block => Try(try_block(inner)?),
+            Rule::label_def => Label(id(inner.into_inner().next().unwrap())?),
+            Rule::prefix => Prefix(prefix(inner)?),
+            Rule::instruction => Instruction(instruction(inner)?),
+            _ => unreachable!(),
+        })
+    }
 
-        let mut instructions = vec![];
-        let mut next_labels = vec![];
+    // declarations
 
-        for pair in inner {
-            match pair.as_rule() {
-                Rule::label => {
-                    next_labels.push(label(pair));
-                }
-                Rule::instruction => {
-                    instructions.push((next_labels, instruction(pair)));
-                    next_labels = vec![];
-                }
-                _ => unreachable!()
+    pinvoke(input) -> ast::PInvoke {
+        let span = span(&input);
+        let mut inner = input.into_inner();
+        let module = inner.maybe(Rule::string, |p| {
+            let span = p.as_span();
+            Ok(Spanned::new(string(p)?, span))
+        })?;
+        Ok(ast::PInvoke {
+            module,
+            name: inner.maybe(Rule::string, string)?,
+            flags: inner.map(word).collect(),
+            span,
+        })
+    }
+    field_decl(input) -> ast::FieldDecl {
+        let span = span(&input);
+        let mut inner = input.into_inner();
+        let offset = inner.maybe(Rule::field_offset, |p| int_lit(p.into_inner().next().unwrap()))?;
+
+        let mut flags = vec![];
+        let mut marshal = None;
+        while let Some(p) = inner.peek() {
+            match p.as_rule() {
+                Rule::member_flag => flags.push(word(inner.next().unwrap())),
+                Rule::marshal_spec => marshal = Some(marshal_spec(inner.next().unwrap())?),
+                _ => break,
             }
         }
 
-        ast::MethodBody {
-            max_stack,
-            locals,
-            instructions
-        }
+        Ok(ast::FieldDecl {
+            offset,
+            flags,
+            marshal,
+            field_type: r#type(inner.next().unwrap())?,
+            name: dotted(inner.next().unwrap())?,
+            data: inner.maybe(Rule::field_at, |p| id(p.into_inner().next().unwrap()))?,
+            default: inner.maybe(Rule::constant, constant)?,
+            span,
+        })
     }
-    method(input) -> ast::Method {
+    method_decl(input) -> ast::MethodDecl {
+        let span = span(&input);
         let mut inner = input.into_inner();
-        ast::Method {
-            name: method_ident(inner.next().unwrap()),
-            parameters: inner.many0(Rule::param, |p| {
-                let mut inner = p.into_inner();
-                (param_type(inner.next().unwrap()), ident(inner.next().unwrap()))
-            }),
-            return_type: return_type(inner.next().unwrap()),
-            attributes: inner.many0(Rule::method_attribute, |p| p.as_str()[1..].to_string()),
-            body: inner.maybe(Rule::method_body, method_body),
+
+        let mut flags = vec![];
+        let mut pinvoke_decl = None;
+        while let Some(p) = inner.peek() {
+            match p.as_rule() {
+                Rule::member_flag => flags.push(word(inner.next().unwrap())),
+                Rule::pinvoke => pinvoke_decl = Some(pinvoke(inner.next().unwrap())?),
+                _ => break,
+            }
         }
+
+        Ok(ast::MethodDecl {
+            flags,
+            pinvoke: pinvoke_decl,
+            call_conv: call_conv(inner.next().unwrap())?,
+            return_type: r#type(inner.next().unwrap())?,
+            return_marshal: inner.maybe(Rule::return_marshal, |p| {
+                marshal_spec(p.into_inner().next().unwrap())
+            })?,
+            name: method_name(inner.next().unwrap())?,
+            generics: inner.maybe(Rule::generic_params, generic_params)?.unwrap_or_default(),
+            parameters: sig_args(inner.next().unwrap())?,
+            impl_flags: inner.many0(Rule::impl_flag, |p| Ok(word(p)))?,
+            items: inner.map(method_item).collect::<Result<_>>()?,
+            span,
+        })
     }
-    semantic_method(input) -> ast::SemanticMethod {
+    property_decl(input) -> ast::PropertyDecl {
+        let span = span(&input);
         let mut inner = input.into_inner();
-        ast::SemanticMethod(ident(inner.next().unwrap()), method_body(inner.next().unwrap()))
+        let flags = inner.many0(Rule::special_flag, |p| Ok(word(p)))?;
+        let call_conv = call_conv(inner.next().unwrap())?;
+        let property_type = r#type(inner.next().unwrap())?;
+        let name = dotted(inner.next().unwrap())?;
+        let parameters = sig_args(inner.next().unwrap())?;
+        let default = inner.maybe(Rule::constant, constant)?;
+        let (attributes, accessors) = semantics(inner)?;
+        Ok(ast::PropertyDecl {
+            flags,
+            call_conv,
+            property_type,
+            name,
+            parameters,
+            default,
+            attributes,
+            accessors,
+            span,
+        })
     }
-    property(input) -> ast::Property {
+    event_decl(input) -> ast::EventDecl {
+        let span = span(&input);
         let mut inner = input.into_inner();
-        ast::Property {
-            r#type: clitype(inner.next().unwrap()),
-            name: ident(inner.next().unwrap()),
-            methods: inner.many0(Rule::semantic_method, semantic_method)
-        }
+        let flags = inner.many0(Rule::special_flag, |p| Ok(word(p)))?;
+        let delegate_type = inner.maybe(Rule::type_spec, type_spec)?;
+        let name = dotted(inner.next().unwrap())?;
+        let (attributes, accessors) = semantics(inner)?;
+        Ok(ast::EventDecl {
+            flags,
+            delegate_type,
+            name,
+            attributes,
+            accessors,
+            span,
+        })
     }
-    event(input) -> ast::Event {
-        let mut inner = input.into_inner();
-        ast::Event {
-            r#type: clitype(inner.next().unwrap()),
-            name: ident(inner.next().unwrap()),
-            methods: inner.many0(Rule::event, semantic_method)
-        }
+    class_flag(input) -> ast::Name {
+        let span = input.as_span();
+        let words: Vec<_> = input.as_str().split_whitespace().collect();
+        Ok(Spanned::new(words.join(" "), span))
     }
-    type_item(input) -> ast::TypeItem {
-        let mut inner = input.into_inner();
+    class_member(input) -> ast::ClassMember {
+        let inner = input.into_inner().next().unwrap();
+        let span = span(&inner);
 
-        let access = access(inner.next().unwrap());
-        let r#static = inner.maybe(Rule::static_member, |_| ()).is_some();
-        let item = inner.next().unwrap();
-
-        use ast::TypeItemKind::*;
-        ast::TypeItem {
-            access,
-            r#static,
-            kind: match item.as_rule() {
-                Rule::field => Field(field(item)),
-                Rule::property => Property(property(item)),
-                Rule::method => Method(method(item)),
-                Rule::event => Event(event(item)),
-                _ => unreachable!()
+        use ast::ClassMember::*;
+        Ok(match inner.as_rule() {
+            Rule::class_decl => Class(class_decl(inner)?),
+            Rule::field_decl => Field(field_decl(inner)?),
+            Rule::method_decl => Method(method_decl(inner)?),
+            Rule::property_decl => Property(property_decl(inner)?),
+            Rule::event_decl => Event(event_decl(inner)?),
+            Rule::custom => Attribute(ast::Attribute::Custom(custom(inner)?)),
+            Rule::permissionset => Attribute(ast::Attribute::PermissionSet(permissionset(inner)?)),
+            Rule::param_directive => Param(param_directive(inner)?, span),
+            Rule::interface_impl => {
+                InterfaceImpl(type_spec(inner.into_inner().next().unwrap())?, span)
             }
-        }
+            Rule::pack => Pack(int_lit(inner.into_inner().next().unwrap())?),
+            Rule::size => Size(int_lit(inner.into_inner().next().unwrap())?),
+            Rule::method_override => Override(method_override(inner)?),
+            Rule::data_decl => Data(data_decl(inner)?),
+            _ => unreachable!(),
+        })
     }
-    extends(input) -> ast::TypeRef {
-        type_ref(input.into_inner().next().unwrap())
+    class_decl(input) -> ast::ClassDecl {
+        let span = span(&input);
+        let mut inner = input.into_inner();
+        let flags = inner.many0(Rule::class_flag, class_flag)?;
+        let (namespace, name) = qualified(inner.next().unwrap())?;
+        Ok(ast::ClassDecl {
+            flags,
+            namespace,
+            name,
+            generics: inner.maybe(Rule::generic_params, generic_params)?.unwrap_or_default(),
+            extends: inner.maybe(Rule::extends, |p| type_spec(p.into_inner().next().unwrap()))?,
+            implements: inner
+                .maybe(Rule::implements, |p| p.into_inner().map(type_spec).collect())?
+                .unwrap_or_default(),
+            members: inner.map(class_member).collect::<Result<_>>()?,
+            span,
+        })
     }
-    implements(input) -> Vec<ast::TypeRef> {
-        input.into_inner().map(type_ref).collect()
+
+    // manifest
+
+    data_value(input) -> ast::DataItem {
+        let mut inner = input.into_inner();
+        Ok(ast::DataItem::Value {
+            data_type: word(inner.next().unwrap()),
+            value: match inner.peek().map(|p| p.as_rule()) {
+                Some(Rule::float_lit | Rule::int_lit) => {
+                    let p = inner.next().unwrap();
+                    let span = p.as_span();
+                    Some(Spanned::new(constant_value(p)?, span))
+                }
+                _ => None,
+            },
+            count: inner.next().map(int_lit).transpose()?,
+        })
     }
-    type_decl(input) -> ast::TypeDeclaration {
+    data_decl(input) -> ast::DataDecl {
+        let span = span(&input);
         let mut inner = input.into_inner();
-        ast::TypeDeclaration {
-            kind: type_kind(inner.next().unwrap()),
-            name: dotted(inner.next().unwrap()),
-            extends: inner.maybe(Rule::extends, extends),
-            implements: inner.maybe(Rule::implements, implements),
-            items: inner.map(type_item).collect()
+        let kind = inner.maybe(Rule::data_kind, |p| Ok(word(p)))?;
+        let label = inner.maybe(Rule::id, id)?;
+        let body = inner.next().unwrap();
+        let items = match body.as_rule() {
+            Rule::data_items => body.into_inner().map(data_item).collect::<Result<_>>()?,
+            _ => vec![data_item(body)?],
+        };
+        Ok(ast::DataDecl {
+            kind,
+            label,
+            items,
+            span,
+        })
+    }
+    data_item(input) -> ast::DataItem {
+        let inner = input.into_inner().next().unwrap();
+        match inner.as_rule() {
+            Rule::bytearray => bytearray(inner).map(ast::DataItem::Bytes),
+            _ => data_value(inner),
         }
     }
-    top_level_decl(input) -> ast::TopLevel {
-        let public = input.as_str().starts_with("public");
+    declaration(input) -> ast::Declaration {
         let inner = input.into_inner().next().unwrap();
+        let span = span(&inner);
+        let rule = inner.as_rule();
 
-        use ast::TopLevelKind::*;
-        ast::TopLevel {
-            public,
-            kind: match inner.as_rule() {
-                Rule::enum_decl => Enum(enum_decl(inner)),
-                Rule::type_decl => Type(type_decl(inner)),
-                _ => unreachable!(),
-            },
+        use ast::Declaration::*;
+        Ok(match rule {
+            Rule::assembly_ref_decl => {
+                let mut parts = inner.into_inner();
+                let name = dotted(parts.next().unwrap())?;
+                let alias = parts.maybe(Rule::dotted, dotted)?;
+                AssemblyRef(ast::AssemblyRefDecl {
+                    name,
+                    alias,
+                    items: parts.map(manifest_item).collect::<Result<_>>()?,
+                    span,
+                })
+            }
+            Rule::assembly_decl => {
+                let mut parts = inner.into_inner();
+                Assembly(ast::AssemblyDecl {
+                    retargetable: parts.maybe(Rule::retargetable, |_| Ok(()))?.is_some(),
+                    name: dotted(parts.next().unwrap())?,
+                    items: parts.map(manifest_item).collect::<Result<_>>()?,
+                    span,
+                })
+            }
+            Rule::module_ref_decl => ModuleRef(dotted(inner.into_inner().next().unwrap())?),
+            Rule::module_decl => Module(dotted(inner.into_inner().next().unwrap())?),
+            Rule::file_decl => {
+                let mut parts = inner.into_inner();
+                File(ast::FileDecl {
+                    has_metadata: parts.maybe(Rule::no_metadata, |_| Ok(()))?.is_none(),
+                    name: dotted(parts.next().unwrap())?,
+                    hash: parts
+                        .maybe(Rule::hash_value, |p| bytes(p.into_inner().next().unwrap()))?,
+                    entry_point: parts.next().is_some(),
+                    span,
+                })
+            }
+            Rule::exported_type_decl => {
+                let mut parts = inner.into_inner();
+                let forwarder = parts.maybe(Rule::forwarder, |_| Ok(()))?.is_some();
+                let flags = parts.many0(Rule::class_flag, class_flag)?;
+                let (namespace, name) = qualified(parts.next().unwrap())?;
+                ExportedType(ast::ExportedTypeDecl {
+                    forwarder,
+                    flags,
+                    namespace,
+                    name,
+                    items: parts
+                        .map(|item| {
+                            let item = item.into_inner().next().unwrap();
+                            let rule = item.as_rule();
+                            if rule == Rule::custom {
+                                return custom(item)
+                                    .map(|c| ast::ExportedItem::Attribute(ast::Attribute::Custom(c)));
+                            }
+                            let target = item.into_inner().next().unwrap();
+                            Ok(match rule {
+                                Rule::exported_file => ast::ExportedItem::File(dotted(target)?),
+                                Rule::exported_encloser => ast::ExportedItem::Encloser(dotted(target)?),
+                                Rule::exported_type_def => {
+                                    ast::ExportedItem::TypeDefinition(int_lit(target)?)
+                                }
+                                _ => ast::ExportedItem::Assembly(dotted(target)?),
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                    span,
+                })
+            }
+            Rule::resource_decl => {
+                let mut parts = inner.into_inner();
+                let public = parts
+                    .maybe(Rule::resource_visibility, |p| Ok(p.as_str() == "public"))?
+                    .unwrap_or(true);
+                Resource(ast::ResourceDecl {
+                    public,
+                    name: dotted(parts.next().unwrap())?,
+                    items: parts
+                        .map(|item| {
+                            let item = item.into_inner().next().unwrap();
+                            let rule = item.as_rule();
+                            if rule == Rule::custom {
+                                return custom(item)
+                                    .map(|c| ast::ResourceItem::Attribute(ast::Attribute::Custom(c)));
+                            }
+                            let mut target = item.into_inner();
+                            let name = dotted(target.next().unwrap())?;
+                            Ok(match rule {
+                                Rule::resource_file => {
+                                    ast::ResourceItem::File(name, int_lit(target.next().unwrap())?)
+                                }
+                                _ => ast::ResourceItem::Assembly(name),
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                    span,
+                })
+            }
+            Rule::namespace_decl => {
+                let mut parts = inner.into_inner();
+                let name = dotted(parts.next().unwrap())?;
+                Namespace(name, parts.map(declaration).collect::<Result<_>>()?)
+            }
+            Rule::class_decl => Class(class_decl(inner)?),
+            Rule::field_decl => Field(field_decl(inner)?),
+            Rule::method_decl => Method(method_decl(inner)?),
+            Rule::custom => Attribute(ast::Attribute::Custom(custom(inner)?)),
+            Rule::data_decl => Data(data_decl(inner)?),
+            _ => unreachable!(),
+        })
+    }
+}
+
+fn manifest_item(input: Pair<Rule>) -> Result<ast::ManifestItem> {
+    let inner = input.into_inner().next().unwrap();
+    let rule = inner.as_rule();
+
+    use ast::ManifestItem::*;
+    Ok(match rule {
+        Rule::custom => Attribute(ast::Attribute::Custom(custom(inner)?)),
+        Rule::permissionset => Attribute(ast::Attribute::PermissionSet(permissionset(inner)?)),
+        Rule::public_key_token => PublicKeyToken(bytes(inner.into_inner().next().unwrap())?),
+        Rule::public_key => PublicKey(bytes(inner.into_inner().next().unwrap())?),
+        Rule::hash_value => Hash(bytes(inner.into_inner().next().unwrap())?),
+        Rule::hash_algorithm => HashAlgorithm(int_lit(inner.into_inner().next().unwrap())?),
+        Rule::version => {
+            let mut parts = inner.into_inner().map(int_lit);
+            let mut next = || parts.next().unwrap();
+            Version([next()?, next()?, next()?, next()?])
+        }
+        _ => Locale(string(inner.into_inner().next().unwrap())?),
+    })
+}
+
+fn constant_value(input: Pair<Rule>) -> Result<ast::ConstantValue> {
+    Ok(match input.as_rule() {
+        Rule::bool_lit => ast::ConstantValue::Bool(input.as_str() == "true"),
+        Rule::float_lit => ast::ConstantValue::Float(float_lit(input)?),
+        _ => ast::ConstantValue::Int(int_lit(input)?.value),
+    })
+}
+
+// the custom attributes and accessors in the body of a property or event
+fn semantics(inner: pest::iterators::Pairs<Rule>) -> Result<(Vec<ast::Attribute>, Vec<ast::Accessor>)> {
+    let mut attributes = vec![];
+    let mut accessors = vec![];
+    for p in inner {
+        match p.as_rule() {
+            Rule::custom => attributes.push(ast::Attribute::Custom(custom(p)?)),
+            _ => {
+                let mut parts = p.into_inner();
+                accessors.push(ast::Accessor {
+                    kind: word(parts.next().unwrap()),
+                    method: method_ref(parts.next().unwrap())?,
+                });
+            }
         }
     }
+    Ok((attributes, accessors))
 }
 
-pub fn assembly(input: &str) -> Result<ast::Assembly, pest::error::Error<Rule>> {
-    let mut parse = AssemblyParser::parse(Rule::assembly, input)?;
+// friendlier names for the rules that show up in "expected ..." messages
+fn describe(rule: &Rule) -> String {
+    match rule {
+        Rule::EOI => "end of input",
+        Rule::id | Rule::bare_id | Rule::quoted_id => "identifier",
+        Rule::dotted => "name",
+        Rule::keyword => "keyword",
+        Rule::int_lit => "integer",
+        Rule::float_lit => "floating point number",
+        Rule::string | Rule::string_lit => "string",
+        Rule::bytes => "byte list",
+        Rule::r#type | Rule::type_base | Rule::primitive | Rule::class_type => "type",
+        Rule::type_spec | Rule::type_ref => "type name",
+        Rule::type_suffix => "type suffix",
+        Rule::method_ref => "method reference",
+        Rule::field_ref => "field reference",
+        Rule::method_name => "method name",
+        Rule::sig_args | Rule::sig_arg | Rule::param => "parameter",
+        Rule::constant | Rule::typed_constant => "constant",
+        Rule::declaration => "declaration",
+        Rule::class_member => "class member",
+        Rule::method_item => "instruction or directive",
+        Rule::instruction => "instruction",
+        Rule::label_def => "label",
+        Rule::handler => "exception handler",
+        Rule::member_flag | Rule::class_flag | Rule::impl_flag => "flag",
+        Rule::call_conv | Rule::call_conv_word => "calling convention",
+        Rule::generic_params | Rule::generic_param => "generic parameter",
+        Rule::marshal_spec => "marshal specification",
+        Rule::custom => "custom attribute",
+        Rule::accessor => "accessor",
+        other => return format!("{:?}", other).replace('_', " "),
+    }
+    .to_string()
+}
 
-    let assembly_decl = asm_decl(parse.next().unwrap());
-    let extern_decls = parse.many0(Rule::extern_decl, |p| {
-        asm_spec(p.into_inner().next().unwrap())
-    });
-    let top_level_decls = parse.many0(Rule::top_level_decl, top_level_decl);
+/// Parses ILAsm source text into its syntax tree, without checking whether the declarations make sense together.
+pub fn source(input: &str) -> Result<ast::Source> {
+    let mut parse = ILAsmParser::parse(Rule::source, input).map_err(|e| Error::from(e.renamed_rules(describe)))?;
+    let source = parse.next().unwrap();
 
-    Ok(ast::Assembly {
-        assembly_decl,
-        extern_decls,
-        top_level_decls,
+    Ok(ast::Source {
+        declarations: source
+            .into_inner()
+            .filter(|p| p.as_rule() == Rule::declaration)
+            .map(declaration)
+            .collect::<Result<_>>()?,
     })
 }
@@ -0,0 +1,59 @@
+use smolasm::{ErrorKind, Span};
+
+fn error(source: &str) -> smolasm::Error {
+    match smolasm::assemble(source) {
+        Ok(_) => panic!("expected assembly to fail"),
+        Err(e) => e,
+    }
+}
+
+fn span_of(source: &str, text: &str) -> Span {
+    let start = source.find(text).unwrap();
+    Span::new(start, start + text.len())
+}
+
+#[test]
+pub fn syntax() {
+    let source = ".assembly errors {}\n.class public Foo\n{\n    .bogus\n}\n";
+    let e = error(source);
+    assert_eq!(e.kind, ErrorKind::Syntax);
+    assert_eq!(e.span.line_col(source), (4, 5));
+}
+
+#[test]
+pub fn undeclared_assembly() {
+    let source = ".assembly errors {}\n.class public Foo extends [mscorlib]System.Object {}\n";
+    let e = error(source);
+    assert_eq!(e.kind, ErrorKind::Semantic);
+    assert_eq!(e.span, span_of(source, "[mscorlib]"));
+}
+
+#[test]
+pub fn undefined_label() {
+    let source = "\
+.assembly errors {}
+.method static void Main() cil managed
+{
+    br nowhere
+}
+";
+    let e = error(source);
+    assert_eq!(e.kind, ErrorKind::Semantic);
+    assert_eq!(e.span, span_of(source, "nowhere"));
+}
+
+#[test]
+pub fn invalid_prefix() {
+    let source = "\
+.assembly errors {}
+.method static void Main() cil managed
+{
+    volatile.
+    ret
+}
+";
+    let e = error(source);
+    assert_eq!(e.kind, ErrorKind::Semantic);
+    assert_eq!(e.span.line_col(source).0, 4);
+    assert!(e.render(source, "errors.il").contains("errors.il:4:5"));
+}
@@ -0,0 +1,76 @@
+use dotnetdll::{prelude::*, resolution::disassemble::disassemble};
+
+fn reassemble(source: &str) -> String {
+    let res = smolasm::assemble(source).unwrap_or_else(|e| panic!("{}", e.render(source, "input.il")));
+    let bytes = res.write(WriteOptions::default()).unwrap();
+    disassemble(&Resolution::parse(&bytes, ReadOptions::default()).unwrap())
+}
+
+// the disassembler emits ILAsm, so assembling its output again must give back the same module
+fn assert_fixed_point(source: &str) {
+    let first = reassemble(source);
+    let second = reassemble(&first);
+    assert_eq!(first, second);
+}
+
+#[test]
+pub fn features() {
+    assert_fixed_point(include_str!("../inputs/features.il"));
+}
+
+#[test]
+pub fn hello_world() {
+    assert_fixed_point(include_str!("../inputs/test.il"));
+}
+
+#[test]
+pub fn oop() {
+    assert_fixed_point(include_str!("../inputs/oop.il"));
+}
+
+// float operands that have no exact decimal form are disassembled as bit patterns, which must not be read back as integers
+#[test]
+pub fn float_operands() {
+    let floats32 = [1.5, 0.1, -0.0, f32::NAN, f32::INFINITY, f32::MIN_POSITIVE, 1e30];
+    let floats64 = [1.5, 0.1, -0.0, f64::NAN, f64::NEG_INFINITY, f64::MAX, 1e-300];
+
+    let mut res = Resolution::new(Module::new("floats.dll"));
+    res.assembly = Some(Assembly::new("floats"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Floats"));
+    let mut instructions: Vec<_> = floats32
+        .iter()
+        .map(|&f| Instruction::LoadConstantFloat32(f))
+        .chain(floats64.iter().map(|&f| Instruction::LoadConstantFloat64(f)))
+        .collect();
+    instructions.push(Instruction::Return);
+    res.push_method(
+        class,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "M",
+            Some(body::Method::new(instructions)),
+        ),
+    );
+
+    let source = disassemble(&res);
+    let read = smolasm::assemble(&source).unwrap_or_else(|e| panic!("{}", e.render(&source, "floats.il")));
+    let body = read.type_definitions[1].methods[0].body.as_ref().unwrap();
+
+    let bits: Vec<u64> = body
+        .instructions
+        .iter()
+        .filter_map(|i| match i {
+            Instruction::LoadConstantFloat32(f) => Some(u64::from(f.to_bits())),
+            Instruction::LoadConstantFloat64(f) => Some(f.to_bits()),
+            _ => None,
+        })
+        .collect();
+    let expected: Vec<u64> = floats32
+        .iter()
+        .map(|f| u64::from(f.to_bits()))
+        .chain(floats64.iter().map(|f| f.to_bits()))
+        .collect();
+    assert_eq!(bits, expected);
+    assert_eq!(disassemble(&read), source);
+}
@@ -80,14 +80,14 @@ impl TryIntoCtx for Metadata<'_> {
         into.gwrite_with(self.minor_version, offset, scroll::LE)?;
         into.gwrite_with(self.reserved, offset, scroll::LE)?;
 
-        let (len, rem) = crate::utils::round_up_to_4(self.version.len() + 1);
+        let (len, padding) = crate::utils::round_up_to_4(self.version.len() + 1);
         into.gwrite_with(len as u32, offset, scroll::LE)?;
 
         into.gwrite(self.version, offset)?;
         into.gwrite_with(0_u8, offset, scroll::LE)?;
 
         // pad out to 4 bytes
-        *offset += rem;
+        *offset += padding;
 
         into.gwrite_with(self.flags, offset, scroll::LE)?;
 
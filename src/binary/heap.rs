@@ -107,6 +107,7 @@ pub trait Writer {
 macro_rules! heap_writer {
     ($name:ident, ($buf:expr, $map:expr), $index:ty, $value:ty, |$s:ident, $n:ident| $e:expr) => {
         pub struct $name {
+            start: usize,
             buffer: Vec<u8>,
             index_cache: HashMap<u64, <Self as Writer>::Index>,
         }
@@ -117,6 +118,7 @@ macro_rules! heap_writer {
 
             fn new() -> Self {
                 $name {
+                    start: 0,
                     buffer: $buf,
                     index_cache: $map,
                 }
@@ -140,6 +142,21 @@ macro_rules! heap_writer {
             }
         }
 
+        impl $name {
+            /// Starts a heap that will be appended to an existing heap of `base_len` bytes, like the heaps of `EnC` deltas.
+            /// Indexes refer to the combined heap, while [`Writer::into_vec`] only returns the appended bytes.
+            pub fn appending(base_len: usize) -> Self {
+                let mut writer = Self::new();
+                writer.start = base_len;
+                writer
+            }
+
+            /// The index of a value that has already been written, without writing it.
+            pub fn find(&self, value: &<Self as Writer>::Value) -> Option<<Self as Writer>::Index> {
+                self.index_cache.get(&hash(value)).copied()
+            }
+        }
+
         impl std::fmt::Debug for $name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 f.write_str(stringify!($name))
@@ -154,7 +171,7 @@ heap_writer!(
     index::String,
     str,
     |self, value| {
-        let start = self.buffer.len();
+        let start = self.start + self.buffer.len();
         self.buffer.extend(value.as_bytes());
         self.buffer.push(0_u8);
         index::String(start)
@@ -166,7 +183,7 @@ heap_writer!(
     index::Blob,
     [u8],
     |self, value| {
-        let start = self.buffer.len();
+        let start = self.start + self.buffer.len();
         self.buffer.extend(write_bytes(value)?);
         index::Blob(start)
     }
@@ -177,7 +194,7 @@ heap_writer!(
     index::GUID,
     [u8; 16],
     |self, value| {
-        let start = self.buffer.len();
+        let start = self.start + self.buffer.len();
         self.buffer.extend(value);
         index::GUID(((start + 1) / 16) + 1)
     }
@@ -193,7 +210,7 @@ heap_writer!(
             high != 0 || matches!(low, 0x01..=0x08 | 0x0E..=0x1F | 0x27 | 0x2D | 0x7F)
         }) as u8;
 
-        let start = self.buffer.len();
+        let start = self.start + self.buffer.len();
         self.buffer.extend(write_bytes(
             &value
                 .iter()
@@ -204,3 +221,47 @@ heap_writer!(
         start
     }
 );
+
+impl UserStringWriter {
+    /// Starts from the contents of an existing `#US` heap, so that the strings it contains keep their offsets.
+    pub fn from_heap(bytes: &[u8]) -> Result<Self> {
+        let mut writer = Self::new();
+        if bytes.is_empty() {
+            return Ok(writer);
+        }
+        writer.buffer = bytes.to_vec();
+
+        for (start, entry) in entries(bytes)? {
+            // empty entries only appear as padding at the end of the heap
+            if entry.is_empty() {
+                continue;
+            }
+
+            writer.index_cache.entry(hash(&user_string_chars(entry)[..])).or_insert(start);
+        }
+
+        Ok(writer)
+    }
+}
+
+// the offsets and contents of every entry in a #Blob or #US heap, skipping the empty one at the start
+pub(crate) fn entries(bytes: &[u8]) -> Result<Vec<(usize, &[u8])>> {
+    let mut entries = vec![];
+
+    let mut offset = 1;
+    while offset < bytes.len() {
+        let start = offset;
+        let compressed::Unsigned(size) = bytes.gread(&mut offset)?;
+        entries.push((start, bytes.gread_with(&mut offset, size as usize)?));
+    }
+
+    Ok(entries)
+}
+
+// decodes a non-empty #US entry, which ends with an extra byte after its UTF-16 characters
+pub(crate) fn user_string_chars(entry: &[u8]) -> Vec<u16> {
+    entry[..entry.len() - 1]
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect()
+}
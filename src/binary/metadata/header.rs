@@ -24,10 +24,61 @@ pub struct Header {
     pub tables: Tables,
 }
 
-impl TryFromCtx<'_> for Header {
+/// Row counts for tables that are indexed by a metadata stream but not stored within it.
+///
+/// Portable PDBs index into the tables of the image they describe without containing them,
+/// so the row counts for those tables have to be supplied from outside the stream.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ExternalRows<'a>(pub Option<&'a HashMap<Kind, u32>>);
+
+impl ExternalRows<'_> {
+    fn add_to(self, sizes: &mut HashMap<Kind, u32>) {
+        for (&kind, &size) in self.0.into_iter().flatten() {
+            sizes.entry(kind).or_insert(size);
+        }
+    }
+}
+
+/// How the rows of a table stream are laid out, beyond what the stream's own header records.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Layout<'a> {
+    pub external_rows: ExternalRows<'a>,
+    /// Whether every heap and table index is 4 bytes wide regardless of heap sizes and row counts,
+    /// as in `EnC` deltas whose metadata includes a `#JTD` stream.
+    pub large_indices: bool,
+    /// Whether the rows are written in the order they are given instead of being sorted, as in the uncompressed `#-` stream.
+    pub uncompressed: bool,
+}
+
+impl<'a> From<ExternalRows<'a>> for Layout<'a> {
+    fn from(external_rows: ExternalRows<'a>) -> Self {
+        Layout {
+            external_rows,
+            ..Layout::default()
+        }
+    }
+}
+
+impl Layout<'_> {
+    fn index_sizes(self, heap_sizes: u8, sizes: &mut HashMap<Kind, u32>) -> u8 {
+        self.external_rows.add_to(sizes);
+
+        if self.large_indices {
+            // any row count that doesn't fit in 16 bits makes every index into that table 4 bytes wide
+            for kind in (0..64).filter_map(Kind::from_u8) {
+                sizes.insert(kind, 1 << 16);
+            }
+            heap_sizes | 0x07
+        } else {
+            heap_sizes
+        }
+    }
+}
+
+impl<'a> TryFromCtx<'_, Layout<'a>> for Header {
     type Error = scroll::Error;
 
-    fn try_from_ctx(from: &[u8], _: ()) -> Result<(Self, usize), Self::Error> {
+    fn try_from_ctx(from: &[u8], layout: Layout<'a>) -> Result<(Self, usize), Self::Error> {
         let offset = &mut 0;
         let res0 = from.gread_with(offset, scroll::LE)?;
         let maj = from.gread_with(offset, scroll::LE)?;
@@ -40,6 +91,11 @@ impl TryFromCtx<'_> for Header {
         let mut rows = vec![0_u32; valid.count_ones() as usize];
         from.gread_inout_with(offset, &mut rows, scroll::LE)?;
 
+        // some uncompressed streams have 4 bytes of extra data after the row counts
+        if heap & 0x40 != 0 {
+            *offset += 4;
+        }
+
         let mut kinds = vec![];
         for (num, exists) in valid.view_bits::<Lsb0>().into_iter().enumerate() {
             if *exists {
@@ -47,9 +103,10 @@ impl TryFromCtx<'_> for Header {
             }
         }
         let iter = kinds.into_iter().zip(rows.into_iter());
-        let sizes_map: HashMap<_, _> = iter.clone().collect();
+        let mut sizes_map: HashMap<_, _> = iter.clone().collect();
+        let index_heap_sizes = layout.index_sizes(heap, &mut sizes_map);
 
-        let heap_bits = BitSafeU8::new(heap);
+        let heap_bits = BitSafeU8::new(index_heap_sizes);
         let ctx = Sizes {
             heap: heap_bits.view_bits::<Lsb0>(),
             tables: &sizes_map,
@@ -79,10 +136,10 @@ impl TryFromCtx<'_> for Header {
         ))
     }
 }
-impl TryIntoCtx<(), DynamicBuffer> for Header {
+impl<'a> TryIntoCtx<Layout<'a>, DynamicBuffer> for Header {
     type Error = scroll::Error;
 
-    fn try_into_ctx(mut self, into: &mut DynamicBuffer, _: ()) -> Result<usize, Self::Error> {
+    fn try_into_ctx(mut self, into: &mut DynamicBuffer, layout: Layout<'a>) -> Result<usize, Self::Error> {
         let offset = &mut 0;
 
         into.gwrite_with(self.reserved0, offset, scroll::LE)?;
@@ -99,20 +156,27 @@ impl TryIntoCtx<(), DynamicBuffer> for Header {
             sizes_map.insert(k, t.len() as u32);
         });
 
-        let heap_bits = BitSafeU8::new(self.heap_sizes);
+        // only our own tables get row counts written, but indexes into external tables still need their sizes
+        let mut index_sizes = sizes_map.clone();
+        let index_heap_sizes = layout.index_sizes(self.heap_sizes, &mut index_sizes);
+
+        let heap_bits = BitSafeU8::new(index_heap_sizes);
         let ctx = Sizes {
             heap: heap_bits.view_bits::<Lsb0>(),
-            tables: &sizes_map,
+            tables: &index_sizes,
         };
 
         let mut tables_map = HashMap::new();
 
         // ECMA-335, II.22 (page 210)
-        self.tables.sort();
+        if !layout.uncompressed {
+            self.tables.sort();
+        }
 
         // callers must make sure that TypeDefs that enclose any types
         // precede their nested types (ECMA-335, II.22, page 210)
 
+        // the widest rows are 28 bytes, even when every index is 4 bytes wide
         let mut buf = [0_u8; 32];
         for_each_row!(self.tables, |r, k| {
             let mut offset = 0;
@@ -280,3 +280,33 @@ coded_index!(TypeOrMethodDef, {
     TypeDef,
     MethodDef
 });
+// Portable PDB v1.0, section "CustomDebugInformation Table (0x37)"
+coded_index!(HasCustomDebugInformation, {
+    MethodDef,
+    Field,
+    TypeRef,
+    TypeDef,
+    Param,
+    InterfaceImpl,
+    MemberRef,
+    Module,
+    DeclSecurity,
+    Property,
+    Event,
+    StandAloneSig,
+    ModuleRef,
+    TypeSpec,
+    Assembly,
+    AssemblyRef,
+    File,
+    ExportedType,
+    ManifestResource,
+    GenericParam,
+    GenericParamConstraint,
+    MethodSpec,
+    Document,
+    LocalScope,
+    LocalVariable,
+    LocalConstant,
+    ImportScope
+});
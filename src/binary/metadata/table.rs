@@ -5,6 +5,7 @@ use scroll::{
     ctx::{TryFromCtx, TryIntoCtx},
     Pread, Pwrite,
 };
+use std::collections::HashMap;
 
 // paste!
 use paste::paste;
@@ -96,6 +97,18 @@ macro_rules! tables {
                     )+
                 }
 
+                pub fn row_counts(&self) -> HashMap<Kind, u32> {
+                    let mut counts = HashMap::new();
+
+                    $(
+                        if !self.[<$name:snake>].is_empty() {
+                            counts.insert(Kind::$name, self.[<$name:snake>].len() as u32);
+                        }
+                    )*
+
+                    counts
+                }
+
                 pub fn sorted_mask() -> u64 {
                     let mut mask = 0;
 
@@ -214,15 +227,36 @@ tables! {
         attr_type: index::CustomAttributeType,
         value: index::Blob,
     },
+    CustomDebugInformation = 0x37 [sorted by parent] {
+        parent: index::HasCustomDebugInformation,
+        kind: index::GUID,
+        value: index::Blob,
+    },
     DeclSecurity = 0x0E [sorted by parent] {
         action: u16,
         parent: index::HasDeclSecurity,
         permission_set: index::Blob,
     },
+    Document = 0x30 {
+        name: index::Blob,
+        hash_algorithm: index::GUID,
+        hash: index::Blob,
+        language: index::GUID,
+    },
+    EncLog = 0x1E {
+        token: u32,
+        func_code: u32,
+    },
+    EncMap = 0x1F {
+        token: u32,
+    },
     EventMap = 0x12 {
         parent: index::Simple<TypeDef>,
         event_list: index::Simple<Event>,
     },
+    EventPtr = 0x13 {
+        event: index::Simple<Event>,
+    },
     Event = 0x14 {
         event_flags: u16,
         name: index::String,
@@ -240,6 +274,9 @@ tables! {
         name: index::String,
         signature: index::Blob,
     },
+    FieldPtr = 0x03 {
+        field: index::Simple<Field>,
+    },
     FieldLayout = 0x10 [sorted by field] {
         offset: u32,
         field: index::Simple<Field>,
@@ -273,10 +310,31 @@ tables! {
         import_name: index::String,
         import_scope: index::Simple<ModuleRef>,
     },
+    ImportScope = 0x35 {
+        parent: index::Simple<ImportScope>,
+        imports: index::Blob,
+    },
     InterfaceImpl = 0x09 [sorted by class, interface] {
         class: index::Simple<TypeDef>,
         interface: index::TypeDefOrRef,
     },
+    LocalConstant = 0x34 {
+        name: index::String,
+        signature: index::Blob,
+    },
+    LocalScope = 0x32 [sorted by method, start_offset] {
+        method: index::Simple<MethodDef>,
+        import_scope: index::Simple<ImportScope>,
+        variable_list: index::Simple<LocalVariable>,
+        constant_list: index::Simple<LocalConstant>,
+        start_offset: u32,
+        length: u32,
+    },
+    LocalVariable = 0x33 {
+        attributes: u16,
+        index: u16,
+        name: index::String,
+    },
     ManifestResource = 0x28 {
         offset: u32,
         flags: u32,
@@ -296,11 +354,18 @@ tables! {
         signature: index::Blob,
         param_list: index::Simple<Param>,
     },
+    MethodDebugInformation = 0x31 {
+        document: index::Simple<Document>,
+        sequence_points: index::Blob,
+    },
     MethodImpl = 0x19 [sorted by class] {
         class: index::Simple<TypeDef>,
         method_body: index::MethodDefOrRef,
         method_declaration: index::MethodDefOrRef,
     },
+    MethodPtr = 0x05 {
+        method: index::Simple<MethodDef>,
+    },
     MethodSemantics = 0x18 [sorted by association] {
         semantics: u16,
         method: index::Simple<MethodDef>,
@@ -329,6 +394,9 @@ tables! {
         sequence: u16,
         name: index::String,
     },
+    ParamPtr = 0x07 {
+        param: index::Simple<Param>,
+    },
     Property = 0x17 {
         flags: u16,
         name: index::String,
@@ -338,9 +406,16 @@ tables! {
         parent: index::Simple<TypeDef>,
         property_list: index::Simple<Property>,
     },
+    PropertyPtr = 0x16 {
+        property: index::Simple<Property>,
+    },
     StandAloneSig = 0x11 {
         signature: index::Blob,
     },
+    StateMachineMethod = 0x36 [sorted by move_next_method] {
+        move_next_method: index::Simple<MethodDef>,
+        kickoff_method: index::Simple<MethodDef>,
+    },
     TypeDef = 0x02 {
         flags: u32,
         type_name: index::String,
@@ -12,5 +12,6 @@ pub mod heap;
 pub mod il;
 pub mod metadata;
 pub mod method;
+pub mod pdb;
 pub mod signature;
 pub mod stream;
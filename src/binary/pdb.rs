@@ -0,0 +1,539 @@
+use super::{
+    heap::{BlobReader, BlobWriter, Reader, Writer},
+    metadata::{index, table::Kind},
+    signature::{compressed, encoded::TypeDefOrRefOrSpec},
+};
+use bitvec::{order::Lsb0, view::BitView};
+use num_traits::FromPrimitive;
+use scroll::{
+    ctx::{TryFromCtx, TryIntoCtx},
+    Pread, Pwrite,
+};
+use std::collections::HashMap;
+
+/// The `#Pdb` stream that heads every standalone portable PDB.
+/// See the Portable PDB v1.0 specification, section "#Pdb stream".
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Stream {
+    pub id: [u8; 20],
+    pub entry_point: u32,
+    pub referenced_type_system_tables: u64,
+    pub type_system_table_rows: Vec<u32>,
+}
+
+impl Stream {
+    pub const NAME: &'static str = "#Pdb";
+
+    /// Row counts of the type system tables in the image this PDB describes, for use as
+    /// [`ExternalRows`](super::metadata::header::ExternalRows) when reading or writing the PDB's tables.
+    pub fn table_rows(&self) -> HashMap<Kind, u32> {
+        self.referenced_type_system_tables
+            .view_bits::<Lsb0>()
+            .iter_ones()
+            .filter_map(Kind::from_usize)
+            .zip(self.type_system_table_rows.iter().copied())
+            .collect()
+    }
+}
+
+impl TryFromCtx<'_> for Stream {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(from: &[u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+
+        let mut id = [0_u8; 20];
+        from.gread_inout_with(offset, &mut id, scroll::LE)?;
+        let entry_point = from.gread_with(offset, scroll::LE)?;
+        let referenced_type_system_tables: u64 = from.gread_with(offset, scroll::LE)?;
+
+        let mut type_system_table_rows = vec![0_u32; referenced_type_system_tables.count_ones() as usize];
+        from.gread_inout_with(offset, &mut type_system_table_rows, scroll::LE)?;
+
+        Ok((
+            Stream {
+                id,
+                entry_point,
+                referenced_type_system_tables,
+                type_system_table_rows,
+            },
+            *offset,
+        ))
+    }
+}
+try_into_ctx!(Stream, |self, into| {
+    let offset = &mut 0;
+
+    into.gwrite(&self.id[..], offset)?;
+    into.gwrite_with(self.entry_point, offset, scroll::LE)?;
+    into.gwrite_with(self.referenced_type_system_tables, offset, scroll::LE)?;
+    for r in self.type_system_table_rows {
+        into.gwrite_with(r, offset, scroll::LE)?;
+    }
+
+    Ok(*offset)
+});
+
+/// A single record of a sequence points blob, with all delta compression undone.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SequencePointRecord {
+    Visible {
+        il_offset: u32,
+        start_line: u32,
+        start_column: u16,
+        end_line: u32,
+        end_column: u16,
+    },
+    Hidden {
+        il_offset: u32,
+    },
+    /// Switches the document of all following records to the given row of the `Document` table.
+    Document(usize),
+}
+
+/// The value of the `sequence_points` blob in the `MethodDebugInformation` table.
+/// See the Portable PDB v1.0 specification, section "Sequence Points Blob".
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SequencePoints {
+    pub local_signature: usize,
+    /// Only present if the owning `MethodDebugInformation` row has a null document, i.e. the method spans multiple documents.
+    pub initial_document: Option<usize>,
+    pub records: Vec<SequencePointRecord>,
+}
+
+// the context is whether or not the owning row's document is null
+impl TryFromCtx<'_, bool> for SequencePoints {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(from: &[u8], has_initial_document: bool) -> Result<(Self, usize), Self::Error> {
+        use compressed::{Signed, Unsigned};
+        use SequencePointRecord::*;
+
+        let offset = &mut 0;
+
+        let Unsigned(local_signature) = from.gread(offset)?;
+        let initial_document = if has_initial_document {
+            let Unsigned(doc) = from.gread(offset)?;
+            Some(doc as usize)
+        } else {
+            None
+        };
+
+        let mut records = vec![];
+        let mut il_offset = None;
+        let mut last_start: Option<(u32, u16)> = None;
+
+        while *offset < from.len() {
+            let Unsigned(delta_il) = from.gread(offset)?;
+
+            let current = match il_offset {
+                Some(_) if delta_il == 0 => {
+                    let Unsigned(doc) = from.gread(offset)?;
+                    records.push(Document(doc as usize));
+                    continue;
+                }
+                Some(prev) => prev + delta_il,
+                None => delta_il,
+            };
+            il_offset = Some(current);
+
+            let Unsigned(delta_lines) = from.gread(offset)?;
+            let delta_columns = if delta_lines == 0 {
+                let Unsigned(c) = from.gread(offset)?;
+                c as i32
+            } else {
+                let Signed(c) = from.gread(offset)?;
+                c
+            };
+
+            if delta_lines == 0 && delta_columns == 0 {
+                records.push(Hidden { il_offset: current });
+                continue;
+            }
+
+            let (start_line, start_column) = match last_start {
+                None => {
+                    let Unsigned(line) = from.gread(offset)?;
+                    let Unsigned(column) = from.gread(offset)?;
+                    (line, column as u16)
+                }
+                Some((line, column)) => {
+                    let Signed(d_line) = from.gread(offset)?;
+                    let Signed(d_column) = from.gread(offset)?;
+                    match (
+                        line.checked_add_signed(d_line),
+                        u16::try_from(column as i32 + d_column),
+                    ) {
+                        (Some(line), Ok(column)) => (line, column),
+                        _ => throw!(
+                            "sequence point at IL offset {:#x} starts outside of the document (line {} {:+}, column {} {:+})",
+                            current,
+                            line,
+                            d_line,
+                            column,
+                            d_column
+                        ),
+                    }
+                }
+            };
+            last_start = Some((start_line, start_column));
+
+            let (Some(end_line), Ok(end_column)) = (
+                start_line.checked_add(delta_lines),
+                u16::try_from(start_column as i32 + delta_columns),
+            ) else {
+                throw!(
+                    "sequence point at IL offset {:#x} ends outside of the document (line {} {:+}, column {} {:+})",
+                    current,
+                    start_line,
+                    delta_lines,
+                    start_column,
+                    delta_columns
+                )
+            };
+
+            records.push(Visible {
+                il_offset: current,
+                start_line,
+                start_column,
+                end_line,
+                end_column,
+            });
+        }
+
+        Ok((
+            SequencePoints {
+                local_signature: local_signature as usize,
+                initial_document,
+                records,
+            },
+            *offset,
+        ))
+    }
+}
+try_into_ctx!(SequencePoints, |self, into| {
+    use compressed::{Signed, Unsigned};
+    use SequencePointRecord::*;
+
+    let offset = &mut 0;
+
+    into.gwrite(Unsigned(self.local_signature as u32), offset)?;
+    if let Some(doc) = self.initial_document {
+        into.gwrite(Unsigned(doc as u32), offset)?;
+    }
+
+    let mut il_offset = None;
+    let mut last_start: Option<(u32, u16)> = None;
+
+    for r in self.records {
+        let current = match r {
+            Document(doc) => {
+                if il_offset.is_none() {
+                    throw!("the first sequence point record cannot be a document record");
+                }
+                into.gwrite(Unsigned(0), offset)?;
+                into.gwrite(Unsigned(doc as u32), offset)?;
+                continue;
+            }
+            Visible { il_offset: o, .. } | Hidden { il_offset: o } => o,
+        };
+
+        let delta_il = match il_offset {
+            Some(prev) if current <= prev => throw!(
+                "sequence point IL offsets must be strictly increasing, found {:#x} after {:#x}",
+                current,
+                prev
+            ),
+            Some(prev) => current - prev,
+            None => current,
+        };
+        il_offset = Some(current);
+        into.gwrite(Unsigned(delta_il), offset)?;
+
+        match r {
+            Hidden { .. } => {
+                into.gwrite(Unsigned(0), offset)?;
+                into.gwrite(Unsigned(0), offset)?;
+            }
+            Visible {
+                start_line,
+                start_column,
+                end_line,
+                end_column,
+                ..
+            } => {
+                let Some(delta_lines) = end_line.checked_sub(start_line) else {
+                    throw!(
+                        "sequence point must not end before it starts (lines {} to {})",
+                        start_line,
+                        end_line
+                    )
+                };
+                let delta_columns = end_column as i32 - start_column as i32;
+                if delta_lines == 0 && delta_columns <= 0 {
+                    throw!(
+                        "single-line sequence point must end after it starts (line {}, columns {} to {})",
+                        start_line,
+                        start_column,
+                        end_column
+                    );
+                }
+
+                into.gwrite(Unsigned(delta_lines), offset)?;
+                if delta_lines == 0 {
+                    into.gwrite(Unsigned(delta_columns as u32), offset)?;
+                } else {
+                    into.gwrite(Signed(delta_columns), offset)?;
+                }
+
+                match last_start {
+                    None => {
+                        into.gwrite(Unsigned(start_line), offset)?;
+                        into.gwrite(Unsigned(start_column as u32), offset)?;
+                    }
+                    Some((line, column)) => {
+                        into.gwrite(Signed(start_line as i32 - line as i32), offset)?;
+                        into.gwrite(Signed(start_column as i32 - column as i32), offset)?;
+                    }
+                }
+                last_start = Some((start_line, start_column));
+            }
+            Document(_) => unreachable!(),
+        }
+    }
+
+    Ok(*offset)
+});
+
+/// A single import definition of an imports blob. Names are UTF-8 blobs in the `#Blob` heap,
+/// and assemblies are rows of the `AssemblyRef` table of the image the PDB describes.
+#[derive(Debug, Clone)]
+pub enum ImportDefinition {
+    Namespace {
+        namespace: index::Blob,
+    },
+    AssemblyNamespace {
+        assembly: usize,
+        namespace: index::Blob,
+    },
+    Type(TypeDefOrRefOrSpec),
+    XmlNamespace {
+        alias: index::Blob,
+        namespace: index::Blob,
+    },
+    AssemblyReferenceAlias {
+        alias: index::Blob,
+    },
+    AliasAssemblyReference {
+        alias: index::Blob,
+        assembly: usize,
+    },
+    AliasNamespace {
+        alias: index::Blob,
+        namespace: index::Blob,
+    },
+    AliasAssemblyNamespace {
+        alias: index::Blob,
+        assembly: usize,
+        namespace: index::Blob,
+    },
+    AliasType {
+        alias: index::Blob,
+        target: TypeDefOrRefOrSpec,
+    },
+}
+
+/// The value of the `imports` blob in the `ImportScope` table.
+/// See the Portable PDB v1.0 specification, section "Imports Blob".
+#[derive(Debug, Clone)]
+pub struct Imports(pub Vec<ImportDefinition>);
+
+impl TryFromCtx<'_> for Imports {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(from: &[u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        use ImportDefinition::*;
+
+        // blob indices and AssemblyRef rows are both compressed
+        fn value(from: &[u8], offset: &mut usize) -> scroll::Result<usize> {
+            let compressed::Unsigned(value) = from.gread(offset)?;
+            Ok(value as usize)
+        }
+        fn blob(from: &[u8], offset: &mut usize) -> scroll::Result<index::Blob> {
+            value(from, offset).map(index::Blob)
+        }
+
+        let offset = &mut 0;
+
+        let mut imports = vec![];
+        while *offset < from.len() {
+            let kind = value(from, offset)?;
+            imports.push(match kind {
+                1 => Namespace {
+                    namespace: blob(from, offset)?,
+                },
+                2 => AssemblyNamespace {
+                    assembly: value(from, offset)?,
+                    namespace: blob(from, offset)?,
+                },
+                3 => Type(from.gread(offset)?),
+                4 => XmlNamespace {
+                    alias: blob(from, offset)?,
+                    namespace: blob(from, offset)?,
+                },
+                5 => AssemblyReferenceAlias {
+                    alias: blob(from, offset)?,
+                },
+                6 => AliasAssemblyReference {
+                    alias: blob(from, offset)?,
+                    assembly: value(from, offset)?,
+                },
+                7 => AliasNamespace {
+                    alias: blob(from, offset)?,
+                    namespace: blob(from, offset)?,
+                },
+                8 => AliasAssemblyNamespace {
+                    alias: blob(from, offset)?,
+                    assembly: value(from, offset)?,
+                    namespace: blob(from, offset)?,
+                },
+                9 => AliasType {
+                    alias: blob(from, offset)?,
+                    target: from.gread(offset)?,
+                },
+                _ => throw!("invalid import kind {}", kind),
+            });
+        }
+
+        Ok((Imports(imports), *offset))
+    }
+}
+try_into_ctx!(Imports, |self, into| {
+    use compressed::Unsigned;
+    use ImportDefinition::*;
+
+    let offset = &mut 0;
+
+    for import in self.0 {
+        let (kind, values): (u32, &[usize]) = match import {
+            Namespace { namespace } => (1, &[namespace.0]),
+            AssemblyNamespace { assembly, namespace } => (2, &[assembly, namespace.0]),
+            Type(target) => {
+                into.gwrite(Unsigned(3), offset)?;
+                into.gwrite(target, offset)?;
+                continue;
+            }
+            XmlNamespace { alias, namespace } => (4, &[alias.0, namespace.0]),
+            AssemblyReferenceAlias { alias } => (5, &[alias.0]),
+            AliasAssemblyReference { alias, assembly } => (6, &[alias.0, assembly]),
+            AliasNamespace { alias, namespace } => (7, &[alias.0, namespace.0]),
+            AliasAssemblyNamespace {
+                alias,
+                assembly,
+                namespace,
+            } => (8, &[alias.0, assembly, namespace.0]),
+            AliasType { alias, target } => {
+                into.gwrite(Unsigned(9), offset)?;
+                into.gwrite(Unsigned(alias.0 as u32), offset)?;
+                into.gwrite(target, offset)?;
+                continue;
+            }
+        };
+
+        into.gwrite(Unsigned(kind), offset)?;
+        for &v in values {
+            into.gwrite(Unsigned(v as u32), offset)?;
+        }
+    }
+
+    Ok(*offset)
+});
+
+/// Decodes a Document name blob, which stores the name as separator-delimited parts in the `#Blob` heap.
+/// See the Portable PDB v1.0 specification, section "Document Name Blob".
+pub fn read_document_name(blob: &[u8], blobs: &BlobReader) -> scroll::Result<String> {
+    let offset = &mut 0;
+
+    let separator: u8 = blob.gread_with(offset, scroll::LE)?;
+
+    let mut parts = vec![];
+    while *offset < blob.len() {
+        let compressed::Unsigned(part) = blob.gread(offset)?;
+        let bytes = blobs.at_index(index::Blob(part as usize))?;
+        match std::str::from_utf8(bytes) {
+            Ok(s) => parts.push(s),
+            Err(e) => throw!("invalid UTF-8 in document name part: {}", e),
+        }
+    }
+
+    Ok(if separator == 0 {
+        parts.concat()
+    } else {
+        parts.join(&(separator as char).to_string())
+    })
+}
+
+pub fn write_document_name(name: &str, blobs: &mut BlobWriter) -> scroll::Result<index::Blob> {
+    let separator = if name.contains('\\') {
+        Some('\\')
+    } else if name.contains('/') {
+        Some('/')
+    } else {
+        None
+    };
+
+    let parts: Vec<&str> = match separator {
+        Some(s) => name.split(s).collect(),
+        None => vec![name],
+    };
+
+    let mut buf = vec![0_u8; 1 + 4 * parts.len()];
+    let offset = &mut 0;
+    buf.gwrite_with(separator.map_or(0, |c| c as u8), offset, scroll::LE)?;
+    for p in parts {
+        let part = blobs.write(p.as_bytes())?;
+        buf.gwrite(compressed::Unsigned(part.0 as u32), offset)?;
+    }
+    buf.truncate(*offset);
+
+    blobs.write(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scroll_buffer::DynamicBuffer;
+
+    #[test]
+    fn sequence_points() {
+        use SequencePointRecord::*;
+
+        let points = SequencePoints {
+            local_signature: 2,
+            initial_document: Some(1),
+            records: vec![
+                Visible {
+                    il_offset: 0,
+                    start_line: 10,
+                    start_column: 5,
+                    end_line: 10,
+                    end_column: 20,
+                },
+                Hidden { il_offset: 3 },
+                Document(2),
+                Visible {
+                    il_offset: 8,
+                    start_line: 4,
+                    start_column: 9,
+                    end_line: 6,
+                    end_column: 2,
+                },
+            ],
+        };
+
+        let mut buf = DynamicBuffer::new();
+        buf.pwrite(points.clone(), 0).unwrap();
+        let read: SequencePoints = buf.get().pread_with(0, true).unwrap();
+        assert_eq!(read, points);
+    }
+}
@@ -574,7 +574,7 @@ try_into_ctx!(RetType, |self, into| {
     Ok(*offset)
 });
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum NativeIntrinsic {
     Boolean,
     Int8,
@@ -598,6 +598,27 @@ pub enum NativeIntrinsic {
     AsAny,
     COMIUnknown,
     LPUTF8Str,
+    Variant,
+    Currency,
+    Decimal,
+    Date,
+    LPTStr,
+    COMIDispatch,
+    Struct,
+    ByValStr,
+    AnsiBStr,
+    TBStr,
+    VariantBool,
+    LPStruct,
+    HResult,
+    IInspectable,
+    HString,
+    // deprecated, but still accepted by the runtime
+    Void,
+    SysChar,
+    Pointer,
+    ObjectRef,
+    NestedStruct,
 }
 
 macro_rules! native_types {
@@ -611,6 +632,7 @@ macro_rules! native_types {
 }
 
 native_types! {
+    VOID = 0x01,
     BOOLEAN = 0x02,
     I1 = 0x03,
     U1 = 0x04,
@@ -622,12 +644,40 @@ native_types! {
     U8 = 0x0a,
     R4 = 0x0b,
     R8 = 0x0c,
+    SYSCHAR = 0x0d,
+    VARIANT = 0x0e,
+    CURRENCY = 0x0f,
+    PTR = 0x10,
+    DECIMAL = 0x11,
+    DATE = 0x12,
+    BSTR = 0x13,
     LPSTR = 0x14,
     LPWSTR = 0x15,
+    LPTSTR = 0x16,
+    FIXEDSYSSTRING = 0x17,
+    OBJECTREF = 0x18,
+    IUNKNOWN = 0x19,
+    IDISPATCH = 0x1a,
+    STRUCT = 0x1b,
+    INTF = 0x1c,
+    SAFEARRAY = 0x1d,
+    FIXEDARRAY = 0x1e,
     INT = 0x1f,
     UINT = 0x20,
+    NESTEDSTRUCT = 0x21,
+    BYVALSTR = 0x22,
+    ANSIBSTR = 0x23,
+    TBSTR = 0x24,
+    VARIANTBOOL = 0x25,
     FUNC = 0x26,
+    ASANY = 0x28,
     ARRAY = 0x2a,
+    LPSTRUCT = 0x2b,
+    CUSTOMMARSHALER = 0x2c,
+    ERROR = 0x2d,
+    IINSPECTABLE = 0x2e,
+    HSTRING = 0x2f,
+    LPUTF8STR = 0x30,
     MAX = 0x50
 }
 
@@ -657,11 +707,31 @@ impl TryFromCtx<'_> for NativeIntrinsic {
             NATIVE_TYPE_UINT => UIntPtr,
             NATIVE_TYPE_FUNC => Function,
             // Microsoft specials
-            0x13 => BStr,
-            0x19 => COMIUnknown,
-            0x1c => COMInterface,
-            0x28 => AsAny,
-            0x30 => LPUTF8Str,
+            NATIVE_TYPE_BSTR => BStr,
+            NATIVE_TYPE_IUNKNOWN => COMIUnknown,
+            NATIVE_TYPE_INTF => COMInterface,
+            NATIVE_TYPE_ASANY => AsAny,
+            NATIVE_TYPE_LPUTF8STR => LPUTF8Str,
+            NATIVE_TYPE_VARIANT => Variant,
+            NATIVE_TYPE_CURRENCY => Currency,
+            NATIVE_TYPE_DECIMAL => Decimal,
+            NATIVE_TYPE_DATE => Date,
+            NATIVE_TYPE_LPTSTR => LPTStr,
+            NATIVE_TYPE_IDISPATCH => COMIDispatch,
+            NATIVE_TYPE_STRUCT => Struct,
+            NATIVE_TYPE_BYVALSTR => ByValStr,
+            NATIVE_TYPE_ANSIBSTR => AnsiBStr,
+            NATIVE_TYPE_TBSTR => TBStr,
+            NATIVE_TYPE_VARIANTBOOL => VariantBool,
+            NATIVE_TYPE_LPSTRUCT => LPStruct,
+            NATIVE_TYPE_ERROR => HResult,
+            NATIVE_TYPE_IINSPECTABLE => IInspectable,
+            NATIVE_TYPE_HSTRING => HString,
+            NATIVE_TYPE_VOID => Void,
+            NATIVE_TYPE_SYSCHAR => SysChar,
+            NATIVE_TYPE_PTR => Pointer,
+            NATIVE_TYPE_OBJECTREF => ObjectRef,
+            NATIVE_TYPE_NESTEDSTRUCT => NestedStruct,
             bad => throw!("bad native instrinsic value {:#04x}", bad),
         };
 
@@ -690,11 +760,31 @@ try_into_ctx!(NativeIntrinsic, |self, into| {
             IntPtr => NATIVE_TYPE_INT,
             UIntPtr => NATIVE_TYPE_UINT,
             Function => NATIVE_TYPE_FUNC,
-            BStr => 0x13,
-            COMIUnknown => 0x19,
-            COMInterface => 0x1c,
-            AsAny => 0x28,
-            LPUTF8Str => 0x30,
+            BStr => NATIVE_TYPE_BSTR,
+            COMIUnknown => NATIVE_TYPE_IUNKNOWN,
+            COMInterface => NATIVE_TYPE_INTF,
+            AsAny => NATIVE_TYPE_ASANY,
+            LPUTF8Str => NATIVE_TYPE_LPUTF8STR,
+            Variant => NATIVE_TYPE_VARIANT,
+            Currency => NATIVE_TYPE_CURRENCY,
+            Decimal => NATIVE_TYPE_DECIMAL,
+            Date => NATIVE_TYPE_DATE,
+            LPTStr => NATIVE_TYPE_LPTSTR,
+            COMIDispatch => NATIVE_TYPE_IDISPATCH,
+            Struct => NATIVE_TYPE_STRUCT,
+            ByValStr => NATIVE_TYPE_BYVALSTR,
+            AnsiBStr => NATIVE_TYPE_ANSIBSTR,
+            TBStr => NATIVE_TYPE_TBSTR,
+            VariantBool => NATIVE_TYPE_VARIANTBOOL,
+            LPStruct => NATIVE_TYPE_LPSTRUCT,
+            HResult => NATIVE_TYPE_ERROR,
+            IInspectable => NATIVE_TYPE_IINSPECTABLE,
+            HString => NATIVE_TYPE_HSTRING,
+            Void => NATIVE_TYPE_VOID,
+            SysChar => NATIVE_TYPE_SYSCHAR,
+            Pointer => NATIVE_TYPE_PTR,
+            ObjectRef => NATIVE_TYPE_OBJECTREF,
+            NestedStruct => NATIVE_TYPE_NESTEDSTRUCT,
         },
         offset,
         scroll::LE,
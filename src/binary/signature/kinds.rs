@@ -566,16 +566,109 @@ impl TryIntoCtx<(), DynamicBuffer> for MethodSpec {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// The kind of COM interface pointer in a [`MarshalSpec::Interface`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InterfaceKind {
+    Interface,
+    IUnknown,
+    IDispatch,
+    IInspectable,
+}
+
+/// A native type descriptor, as stored in the `FieldMarshal` table (ECMA-335, II.23.4).
+///
+/// Besides the grammar from the standard, this models the extensions understood by the `CoreCLR` marshaler.
+/// Descriptors that consist of a single native type are read as [`MarshalSpec::Primitive`], except for a bare
+/// `NATIVE_TYPE_ARRAY` or `NATIVE_TYPE_SAFEARRAY`, which is read as a [`MarshalSpec::Array`] or [`MarshalSpec::SafeArray`]
+/// whose fields are all `None`. Either way, every blob has exactly one representation.
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum MarshalSpec {
     Primitive(NativeIntrinsic),
+    /// `NATIVE_TYPE_ARRAY`, a C-style array whose length is determined at runtime.
+    ///
+    /// The length is the value of the parameter at `length_parameter` plus `additional_elements`.
     Array {
         element_type: Option<NativeIntrinsic>,
         length_parameter: Option<usize>,
         additional_elements: Option<usize>,
     },
+    /// `NATIVE_TYPE_FIXEDARRAY` (`UnmanagedType.ByValArray`), an inline array of a constant length.
+    FixedArray {
+        size: usize,
+        element_type: Option<NativeIntrinsic>,
+    },
+    /// `NATIVE_TYPE_FIXEDSYSSTRING` (`UnmanagedType.ByValTStr`), an inline character buffer of a constant length.
+    FixedString {
+        size: usize,
+    },
+    /// `NATIVE_TYPE_SAFEARRAY`, a COM `SAFEARRAY`.
+    ///
+    /// `variant_type` is the raw `VARTYPE` of the elements, including any `VT_ARRAY`/`VT_BYREF` flags.
+    SafeArray {
+        variant_type: Option<u16>,
+        user_defined_subtype: Option<String>,
+    },
+    /// `NATIVE_TYPE_CUSTOMMARSHALER`, marshaled by a managed `ICustomMarshaler` implementation.
+    ///
+    /// `marshaler` is the serialized name of the marshaler type. The GUID and native type name are unused by the
+    /// runtime, but are preserved when present.
+    CustomMarshaler {
+        guid: String,
+        native_type_name: String,
+        marshaler: String,
+        cookie: String,
+    },
+    /// A COM interface pointer whose IID is passed in the parameter at `iid_parameter`.
+    ///
+    /// Interfaces without an IID parameter are represented as [`MarshalSpec::Primitive`].
+    Interface {
+        kind: InterfaceKind,
+        iid_parameter: usize,
+    },
+}
+
+// a compressed length followed by UTF-8, without the null marker of SerString
+struct MarshalString<'a>(&'a str);
+impl<'a> TryFromCtx<'a> for MarshalString<'a> {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(from: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+        let len = from.gread::<compressed::Unsigned>(offset)?.0 as usize;
+        let value = from.gread_with(offset, scroll::ctx::StrCtx::Length(len))?;
+        Ok((MarshalString(value), *offset))
+    }
+}
+try_into_ctx!(MarshalString<'_>, |self, into| {
+    let offset = &mut 0;
+    into.gwrite(compressed::Unsigned(self.0.len() as u32), offset)?;
+    into.gwrite(self.0.as_bytes(), offset)?;
+    Ok(*offset)
+});
+
+// optional trailing values may simply be omitted from the end of the blob
+fn read_optional(from: &[u8], offset: &mut usize) -> scroll::Result<Option<usize>> {
+    if *offset < from.len() {
+        Ok(Some(from.gread::<compressed::Unsigned>(offset)?.0 as usize))
+    } else {
+        Ok(None)
+    }
 }
 
+fn read_element_type(from: &[u8], offset: &mut usize) -> scroll::Result<Option<NativeIntrinsic>> {
+    match from.get(*offset) {
+        None => Ok(None),
+        Some(&NATIVE_TYPE_MAX) => {
+            *offset += 1;
+            Ok(None)
+        }
+        Some(_) => Ok(Some(from.gread(offset)?)),
+    }
+}
+
+// whether the runtime should read the array length from the parameter (CoreCLR extension)
+const ARRAY_SIZE_PARAMETER_SPECIFIED: usize = 1;
+
 impl TryFromCtx<'_> for MarshalSpec {
     type Error = scroll::Error;
 
@@ -584,29 +677,76 @@ impl TryFromCtx<'_> for MarshalSpec {
 
         use MarshalSpec::*;
 
-        if from[*offset] == NATIVE_TYPE_ARRAY {
-            *offset += 1;
-        } else {
-            return Ok((Primitive(from.gread(offset)?), *offset));
-        }
+        let tag: u8 = from.gread_with(offset, scroll::LE)?;
+        let value = match tag {
+            NATIVE_TYPE_ARRAY => {
+                let element_type = read_element_type(from, offset)?;
+                let mut length_parameter = read_optional(from, offset)?;
+                let additional_elements = read_optional(from, offset)?;
+                if let Some(flags) = read_optional(from, offset)? {
+                    if flags & ARRAY_SIZE_PARAMETER_SPECIFIED == 0 {
+                        length_parameter = None;
+                    }
+                }
 
-        let element_type = if from[*offset] == NATIVE_TYPE_MAX {
-            *offset += 1;
-            None
-        } else {
-            Some(from.gread(offset)?)
-        };
-        let length_parameter = from.gread::<compressed::Unsigned>(offset).ok().map(|u| u.0 as usize);
-        let additional_elements = from.gread::<compressed::Unsigned>(offset).ok().map(|u| u.0 as usize);
+                Array {
+                    element_type,
+                    length_parameter,
+                    additional_elements,
+                }
+            }
+            NATIVE_TYPE_FIXEDARRAY => FixedArray {
+                size: from.gread::<compressed::Unsigned>(offset)?.0 as usize,
+                element_type: read_element_type(from, offset)?,
+            },
+            NATIVE_TYPE_FIXEDSYSSTRING => FixedString {
+                size: from.gread::<compressed::Unsigned>(offset)?.0 as usize,
+            },
+            NATIVE_TYPE_SAFEARRAY => {
+                let variant_type = match read_optional(from, offset)? {
+                    Some(v) => match u16::try_from(v) {
+                        Ok(v) => Some(v),
+                        Err(_) => throw!("bad safe array variant type {:#x}", v),
+                    },
+                    None => None,
+                };
+                let user_defined_subtype = if *offset < from.len() {
+                    Some(from.gread::<MarshalString>(offset)?.0.to_string())
+                } else {
+                    None
+                };
 
-        Ok((
-            Array {
-                element_type,
-                length_parameter,
-                additional_elements,
+                SafeArray {
+                    variant_type,
+                    user_defined_subtype,
+                }
+            }
+            NATIVE_TYPE_CUSTOMMARSHALER => CustomMarshaler {
+                guid: from.gread::<MarshalString>(offset)?.0.to_string(),
+                native_type_name: from.gread::<MarshalString>(offset)?.0.to_string(),
+                marshaler: from.gread::<MarshalString>(offset)?.0.to_string(),
+                cookie: from.gread::<MarshalString>(offset)?.0.to_string(),
             },
-            *offset,
-        ))
+            NATIVE_TYPE_INTF | NATIVE_TYPE_IUNKNOWN | NATIVE_TYPE_IDISPATCH | NATIVE_TYPE_IINSPECTABLE
+                if *offset < from.len() =>
+            {
+                Interface {
+                    kind: match tag {
+                        NATIVE_TYPE_INTF => InterfaceKind::Interface,
+                        NATIVE_TYPE_IUNKNOWN => InterfaceKind::IUnknown,
+                        NATIVE_TYPE_IDISPATCH => InterfaceKind::IDispatch,
+                        _ => InterfaceKind::IInspectable,
+                    },
+                    iid_parameter: from.gread::<compressed::Unsigned>(offset)?.0 as usize,
+                }
+            }
+            _ => {
+                *offset -= 1;
+                Primitive(from.gread(offset)?)
+            }
+        };
+
+        Ok((value, *offset))
     }
 }
 try_into_ctx!(MarshalSpec, |self, into| {
@@ -621,24 +761,143 @@ try_into_ctx!(MarshalSpec, |self, into| {
             length_parameter,
             additional_elements,
         } => {
+            into.gwrite_with(NATIVE_TYPE_ARRAY, offset, scroll::LE)?;
             match element_type {
                 Some(t) => into.gwrite(t, offset)?,
                 None => into.gwrite_with(NATIVE_TYPE_MAX, offset, scroll::LE)?,
             };
 
-            if additional_elements.is_some() && length_parameter.is_none() {
-                throw!("length parameter must be specified if additional elements is specified");
+            match (length_parameter, additional_elements) {
+                (Some(p), n) => {
+                    into.gwrite(compressed::Unsigned(p as u32), offset)?;
+                    if let Some(n) = n {
+                        into.gwrite(compressed::Unsigned(n as u32), offset)?;
+                    }
+                }
+                // a constant length requires a placeholder parameter, which the flags then mark as unused
+                (None, Some(n)) => {
+                    into.gwrite(compressed::Unsigned(0), offset)?;
+                    into.gwrite(compressed::Unsigned(n as u32), offset)?;
+                    into.gwrite(compressed::Unsigned(0), offset)?;
+                }
+                (None, None) => {}
             }
-
-            if let Some(p) = length_parameter {
-                into.gwrite(compressed::Unsigned(p as u32), offset)?;
+        }
+        MarshalSpec::FixedArray { size, element_type } => {
+            into.gwrite_with(NATIVE_TYPE_FIXEDARRAY, offset, scroll::LE)?;
+            into.gwrite(compressed::Unsigned(size as u32), offset)?;
+            if let Some(t) = element_type {
+                into.gwrite(t, offset)?;
             }
-
-            if let Some(n) = additional_elements {
-                into.gwrite(compressed::Unsigned(n as u32), offset)?;
+        }
+        MarshalSpec::FixedString { size } => {
+            into.gwrite_with(NATIVE_TYPE_FIXEDSYSSTRING, offset, scroll::LE)?;
+            into.gwrite(compressed::Unsigned(size as u32), offset)?;
+        }
+        MarshalSpec::SafeArray {
+            variant_type,
+            user_defined_subtype,
+        } => {
+            into.gwrite_with(NATIVE_TYPE_SAFEARRAY, offset, scroll::LE)?;
+            match (variant_type, user_defined_subtype) {
+                (Some(v), subtype) => {
+                    into.gwrite(compressed::Unsigned(u32::from(v)), offset)?;
+                    if let Some(s) = subtype {
+                        into.gwrite(MarshalString(&s), offset)?;
+                    }
+                }
+                (None, Some(_)) => throw!("variant type must be specified if a user-defined subtype is specified"),
+                (None, None) => {}
             }
         }
+        MarshalSpec::CustomMarshaler {
+            guid,
+            native_type_name,
+            marshaler,
+            cookie,
+        } => {
+            into.gwrite_with(NATIVE_TYPE_CUSTOMMARSHALER, offset, scroll::LE)?;
+            for s in [guid, native_type_name, marshaler, cookie] {
+                into.gwrite(MarshalString(&s), offset)?;
+            }
+        }
+        MarshalSpec::Interface { kind, iid_parameter } => {
+            let tag = match kind {
+                InterfaceKind::Interface => NATIVE_TYPE_INTF,
+                InterfaceKind::IUnknown => NATIVE_TYPE_IUNKNOWN,
+                InterfaceKind::IDispatch => NATIVE_TYPE_IDISPATCH,
+                InterfaceKind::IInspectable => NATIVE_TYPE_IINSPECTABLE,
+            };
+            into.gwrite_with(tag, offset, scroll::LE)?;
+            into.gwrite(compressed::Unsigned(iid_parameter as u32), offset)?;
+        }
     }
 
     Ok(*offset)
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marshal_spec_round_trip() {
+        let blobs: &[&[u8]] = &[
+            &[NATIVE_TYPE_LPSTR],
+            &[NATIVE_TYPE_IDISPATCH],
+            &[NATIVE_TYPE_ARRAY, NATIVE_TYPE_MAX],
+            &[NATIVE_TYPE_ARRAY, NATIVE_TYPE_I4, 0x01],
+            &[NATIVE_TYPE_ARRAY, NATIVE_TYPE_I4, 0x02, 0x04],
+            &[NATIVE_TYPE_ARRAY, NATIVE_TYPE_I4, 0x00, 0x10, 0x00],
+            &[NATIVE_TYPE_FIXEDARRAY, 0x08, NATIVE_TYPE_U1],
+            &[NATIVE_TYPE_FIXEDSYSSTRING, 0x20],
+            &[NATIVE_TYPE_SAFEARRAY],
+            &[NATIVE_TYPE_SAFEARRAY, 0x1d, 0x03, b'R', b'e', b'c'],
+            &[NATIVE_TYPE_CUSTOMMARSHALER, 0x00, 0x00, 0x01, b'M', 0x01, b'c'],
+            &[NATIVE_TYPE_INTF, 0x02],
+        ];
+
+        for &blob in blobs {
+            let spec: MarshalSpec = blob.pread(0).unwrap();
+            let mut buf = [0_u8; 16];
+            let len = buf.pwrite(spec.clone(), 0).unwrap();
+            assert_eq!(&buf[..len], blob, "{:?}", spec);
+        }
+
+        let spec: MarshalSpec = [NATIVE_TYPE_ARRAY, NATIVE_TYPE_I4, 0x00, 0x10, 0x00].pread(0).unwrap();
+        assert_eq!(
+            spec,
+            MarshalSpec::Array {
+                element_type: Some(NativeIntrinsic::Int32),
+                length_parameter: None,
+                additional_elements: Some(16),
+            }
+        );
+
+        let spec: MarshalSpec = [NATIVE_TYPE_SAFEARRAY].pread(0).unwrap();
+        assert_eq!(
+            spec,
+            MarshalSpec::SafeArray {
+                variant_type: None,
+                user_defined_subtype: None,
+            }
+        );
+        // VARTYPE is 16 bits
+        assert!([NATIVE_TYPE_SAFEARRAY, 0xc0, 0x01, 0x00, 0x00]
+            .pread::<MarshalSpec>(0)
+            .is_err());
+
+        let spec: MarshalSpec = [NATIVE_TYPE_CUSTOMMARSHALER, 0x00, 0x00, 0x01, b'M', 0x01, b'c']
+            .pread(0)
+            .unwrap();
+        assert_eq!(
+            spec,
+            MarshalSpec::CustomMarshaler {
+                guid: String::new(),
+                native_type_name: String::new(),
+                marshaler: "M".to_string(),
+                cookie: "c".to_string(),
+            }
+        );
+    }
+}
@@ -12,7 +12,7 @@ use crate::{
             kinds::{MethodDefSig, MethodSpec as MethodSpecSig, StandAloneCallingConvention, StandAloneMethodSig},
         },
     },
-    dll::{DLLError, Result},
+    dll::{DLLError, ErrorContext, Result},
     resolution::*,
     resolved::{self, members::*, signature, types::*},
 };
@@ -25,6 +25,28 @@ macro_rules! throw {
     }
 }
 
+macro_rules! throw_index {
+    ($table:ident, $row:expr, $item:expr) => {
+        return Err(DLLError::invalid_index(Kind::$table, $row, ErrorContext::new($item)))
+    };
+}
+
+macro_rules! throw_token {
+    ($item:expr) => {
+        return Err(DLLError::unresolvable("metadata token", ErrorContext::new($item)))
+    };
+}
+
+macro_rules! signature {
+    ($ctx:expr, $idx:expr, $item:expr) => {{
+        let idx = $idx;
+        $ctx.blobs
+            .at_index(idx)?
+            .pread(0)
+            .map_err(|e| DLLError::bad_signature(idx.0, e, ErrorContext::new($item)))?
+    }};
+}
+
 #[derive(Debug)]
 pub struct Context<'r, 'data: 'r> {
     pub def_len: usize,
@@ -44,19 +66,18 @@ pub fn user_type(TypeDefOrRefOrSpec(token): TypeDefOrRefOrSpec, ctx: &Context) -
             if idx < ctx.def_len {
                 Ok(UserType::Definition(TypeIndex(idx)))
             } else {
-                Err(format!("invalid type definition index {} for user type", idx))
+                throw_index!(TypeDef, idx, "user type")
             }
         }
         Table(Kind::TypeRef) => {
             if idx < ctx.ref_len {
                 Ok(UserType::Reference(TypeRefIndex(idx)))
             } else {
-                Err(format!("invalid type reference index {} for user type", idx))
+                throw_index!(TypeRef, idx, "user type")
             }
         }
-        bad => Err(format!("bad metadata token target {:?} for a user type", bad)),
+        _ => throw_token!("user type"),
     }
-    .map_err(|e| DLLError::CLI(scroll::Error::Custom(e)))
 }
 
 #[tracing::instrument]
@@ -140,7 +161,7 @@ pub fn type_idx<T: TypeKind>(idx: TypeDefOrRef, ctx: &Context) -> Result<T> {
                     source: TypeIndex(idx).into(),
                 }))
             } else {
-                throw!("invalid type definition index {} while parsing a type", idx)
+                throw_index!(TypeDef, idx, "type")
             }
         }
         TypeDefOrRef::TypeRef(i) => {
@@ -151,17 +172,17 @@ pub fn type_idx<T: TypeKind>(idx: TypeDefOrRef, ctx: &Context) -> Result<T> {
                     source: TypeRefIndex(idx).into(),
                 }))
             } else {
-                throw!("invalid type reference index {} while parsing a type", idx)
+                throw_index!(TypeRef, idx, "type")
             }
         }
         TypeDefOrRef::TypeSpec(i) => {
             let idx = i - 1;
             match ctx.specs.get(idx) {
-                Some(s) => T::from_sig(ctx.blobs.at_index(s.signature)?.pread(0)?, ctx),
-                None => throw!("invalid type spec index {} while parsing a type", idx),
+                Some(s) => T::from_sig(signature!(ctx, s.signature, "type specification"), ctx),
+                None => throw_index!(TypeSpec, idx, "type"),
             }
         }
-        TypeDefOrRef::Null => throw!("invalid null type index"),
+        TypeDefOrRef::Null => Err(DLLError::unresolvable("TypeDefOrRef", ErrorContext::new("type"))),
     }
 }
 
@@ -179,10 +200,15 @@ pub fn idx_with_mod<T: TypeKind>(idx: TypeDefOrRef, ctx: &Context) -> Result<(Ve
                     mods.into_iter()
                         .map(|c| custom_modifier(c, ctx))
                         .collect::<Result<_>>()?,
-                    T::from_sig(blob.pread(offset)?, ctx)?,
+                    T::from_sig(
+                        blob.pread(offset).map_err(|e| {
+                            DLLError::bad_signature(s.signature.0, e, ErrorContext::new("type specification"))
+                        })?,
+                        ctx,
+                    )?,
                 ))
             }
-            None => throw!("invalid type spec index {} while parsing a type", t_idx),
+            None => throw_index!(TypeSpec, t_idx, "type"),
         }
     } else {
         Ok((vec![], type_idx(idx, ctx)?))
@@ -258,7 +284,7 @@ pub fn type_token(tok: Token, ctx: &Context) -> Result<MethodType> {
         Table(Kind::TypeDef) => type_idx(TypeDefOrRef::TypeDef(tok.index), ctx),
         Table(Kind::TypeRef) => type_idx(TypeDefOrRef::TypeRef(tok.index), ctx),
         Table(Kind::TypeSpec) => type_idx(TypeDefOrRef::TypeSpec(tok.index), ctx),
-        bad => throw!("invalid token {:?} for method type", bad),
+        _ => throw_token!("method type"),
     }
 }
 
@@ -278,17 +304,22 @@ pub fn user_method(idx: MethodDefOrRef, ctx: &MethodContext) -> Result<UserMetho
             let m_idx = i - 1;
             match ctx.method_indices.get(m_idx) {
                 Some(&m) => UserMethod::Definition(m),
-                None => throw!("invalid method index {} for user method", m_idx),
+                None => throw_index!(MethodDef, m_idx, "user method"),
             }
         }
         MethodDefOrRef::MemberRef(i) => {
             let r_idx = i - 1;
             match ctx.method_map.get(&r_idx) {
                 Some(&m_idx) => UserMethod::Reference(MethodRefIndex(m_idx)),
-                None => throw!("invalid member reference index {} for user method", r_idx),
+                None => throw_index!(MemberRef, r_idx, "user method"),
             }
         }
-        MethodDefOrRef::Null => throw!("invalid null index for user method"),
+        MethodDefOrRef::Null => {
+            return Err(DLLError::unresolvable(
+                "MethodDefOrRef",
+                ErrorContext::new("user method"),
+            ))
+        }
     })
 }
 
@@ -298,7 +329,7 @@ fn user_method_token(tok: Token, ctx: &MethodContext) -> Result<UserMethod> {
     match tok.target {
         Table(Kind::MethodDef) => user_method(MethodDefOrRef::MethodDef(tok.index), ctx),
         Table(Kind::MemberRef) => user_method(MethodDefOrRef::MemberRef(tok.index), ctx),
-        bad => throw!("invalid token {:?} for user method", bad),
+        _ => throw_token!("user method"),
     }
 }
 
@@ -311,16 +342,15 @@ fn method_source<'r>(tok: Token, ctx: &Context<'r, '_>, m_ctx: &MethodContext<'r
             match m_ctx.method_specs.get(idx) {
                 Some(m) => MethodSource::Generic(GenericMethodInstantiation {
                     base: user_method(m.method, m_ctx)?,
-                    parameters: ctx
-                        .blobs
-                        .at_index(m.instantiation)?
-                        .pread::<MethodSpecSig>(0)?
-                        .0
-                        .into_iter()
-                        .map(|t| MethodType::from_sig(t, ctx))
-                        .collect::<Result<_>>()?,
+                    parameters: {
+                        let sig: MethodSpecSig = signature!(ctx, m.instantiation, "generic method instantiation");
+                        sig.0
+                    }
+                    .into_iter()
+                    .map(|t| MethodType::from_sig(t, ctx))
+                    .collect::<Result<_>>()?,
                 }),
-                None => throw!("invalid method spec index {} for method source", idx),
+                None => throw_index!(MethodSpec, idx, "method source"),
             }
         }
         _ => MethodSource::User(user_method_token(tok, m_ctx)?),
@@ -334,13 +364,13 @@ fn field_source(tok: Token, ctx: &MethodContext) -> Result<FieldSource> {
     Ok(match tok.target {
         Table(Kind::Field) => match ctx.field_indices.get(idx) {
             Some(&i) => FieldSource::Definition(i),
-            None => throw!("bad field index {} for field source", idx),
+            None => throw_index!(Field, idx, "field source"),
         },
         Table(Kind::MemberRef) => match ctx.field_map.get(&idx) {
             Some(&i) => FieldSource::Reference(FieldRefIndex(i)),
-            None => throw!("bad member reference index {} for field source", idx),
+            None => throw_index!(MemberRef, idx, "field source"),
         },
-        bad => throw!("invalid token {:?} for field source", bad),
+        _ => throw_token!("field source"),
     })
 }
 
@@ -350,6 +380,8 @@ pub fn instruction<'r>(
     instruction: il::Instruction,
     index: usize,
     all_offsets: &'r [usize],
+    token: u32,
+    context: &ErrorContext,
     ctx: &Context<'r, '_>,
     m_ctx: &MethodContext<'r>,
 ) -> Result<resolved::il::Instruction> {
@@ -382,7 +414,7 @@ pub fn instruction<'r>(
                     let idx = $t.index - 1;
                     match ctx.sigs.get(idx) {
                         Some(s) => {
-                            let sig: StandAloneMethodSig = ctx.blobs.at_index(s.signature)?.pread(0)?;
+                            let sig: StandAloneMethodSig = signature!(ctx, s.signature, "calli instruction");
                             let mut parsed = maybe_unmanaged_method(sig.clone(), ctx)?;
                             if matches!(
                                 sig.calling_convention,
@@ -400,10 +432,10 @@ pub fn instruction<'r>(
                                 param0: parsed,
                             }
                         }
-                        None => throw!("invalid signature index {} for calli instruction", idx),
+                        None => throw_index!(StandAloneSig, idx, "calli instruction"),
                     }
                 }
-                bad => throw!("invalid metadata token {:?} for calli instruction", bad),
+                _ => throw_token!("calli instruction"),
             }
         };
     }
@@ -596,7 +628,16 @@ pub fn instruction<'r>(
             .try_into()
             .ok()
             .and_then(|other: usize| all_offsets.iter().position(|&o| o == other))
-            .ok_or_else(|| DLLError::CLI(scroll::Error::Custom(format!("invalid instruction offset {}", i))))
+            .ok_or_else(|| {
+                DLLError::invalid_offset(
+                    token,
+                    (offset + bytesize) as i64 + i64::from(i),
+                    ErrorContext {
+                        item: "branch target".to_string(),
+                        ..context.clone()
+                    },
+                )
+            })
     };
 
     Ok(match instruction {
@@ -760,7 +801,7 @@ pub fn instruction<'r>(
         Ldsflda(t) => Instruction::LoadStaticFieldAddress(field_source(t, m_ctx)?),
         Ldstr(t) => match t.target {
             TokenTarget::UserString => Instruction::LoadString(ctx.userstrings.at_index(t.index)?),
-            bad => throw!("invalid metadata token {:?} for ldstr instruction", bad),
+            _ => throw_token!("ldstr instruction"),
         },
         Ldtoken(t) => {
             use TokenTarget::*;
@@ -780,7 +821,7 @@ pub fn instruction<'r>(
                         Instruction::LoadTokenMethod(method_source(t, ctx, m_ctx)?)
                     }
                 }
-                bad => throw!("invalid metadata token {:?} for ldtoken instruction", bad),
+                _ => throw_token!("ldtoken instruction"),
             }
         }
         Ldvirtftn(t) => ldvirtftn!(t | nullcheck false),
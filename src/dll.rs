@@ -4,7 +4,9 @@ use super::{
         heap::Reader,
         metadata, method,
     },
-    resolution::{read, Resolution},
+    pdb::PDB,
+    resolution::{lazy::LazyResolution, read, Resolution},
+    win32,
 };
 use object::{
     endian::{LittleEndian, U32Bytes},
@@ -15,6 +17,7 @@ use object::{
     },
 };
 use scroll::{Error as ScrollError, Pread};
+use std::fmt::{Display, Formatter};
 use thiserror::Error;
 use DLLError::*;
 
@@ -25,10 +28,10 @@ pub struct DLL<'a> {
     /// The CLI header of the DLL, read from the 15th PE data directory. See ECMA-335, II.25.3.3 (page 283) for more information.
     pub cli: Header,
     sections: SectionTable<'a>,
+    resource_rva: Option<u32>,
 }
 
 // TODO: now that Resolution is the typical entry point, move this into maybe its own module
-// TODO: also, eventually we need to expand CLI and the ScrollError into our own meaningful variants
 /// The general error type for all dotnetdll operations.
 #[derive(Debug, Error)]
 pub enum DLLError {
@@ -36,26 +39,136 @@ pub enum DLLError {
     /// This might happen if you try to load an invalid DLL with [`DLL::parse`].
     #[error("PE parsing: {0}")]
     PERead(#[from] ObjectReadError),
-    /// Errors from CLI metadata reading or writing.
+    /// Errors from CLI metadata reading or writing that do not have a more specific variant.
     /// Messages are communicated through the [`ScrollError::Custom`] enum variant.
     #[error("CLI metadata: {0}")]
     CLI(#[from] ScrollError),
+    /// A metadata row or token refers to a row that does not exist in another table.
+    #[error("invalid {table:?} index {row} for {context}")]
+    InvalidTableIndex {
+        table: metadata::table::Kind,
+        /// The 0-based row that was referred to.
+        row: usize,
+        context: Box<ErrorContext>,
+    },
+    /// A blob could not be decoded as the signature it was expected to hold.
+    #[error("bad signature blob at #Blob offset {offset:#x} for {context}: {source}")]
+    BadSignature {
+        /// The offset of the blob in the `#Blob` heap.
+        offset: usize,
+        source: ScrollError,
+        context: Box<ErrorContext>,
+    },
+    /// A branch or exception clause refers to a byte offset that is not the start of an instruction.
+    #[error("invalid instruction offset {offset} in method {token:#010x} for {context}")]
+    InvalidInstructionOffset {
+        /// The metadata token of the method whose body contains the offset.
+        token: u32,
+        /// The byte offset that was referred to, relative to the start of the method body.
+        offset: i64,
+        context: Box<ErrorContext>,
+    },
+    /// A coded index or metadata token is null or refers to a table it cannot be used with in its position.
+    #[error("unresolvable {index} for {context}")]
+    UnresolvableCodedIndex {
+        /// What could not be resolved, e.g. `"HasConstant coded index"` or `"metadata token"`.
+        index: &'static str,
+        context: Box<ErrorContext>,
+    },
     /// Errors from DLL parsing that are not PE format errors, such as .NET metadata and method bodies.
     /// This might happen if you try to load an invalid DLL with [`DLL::parse`].
     #[error("Other parsing: {0}")]
     Other(&'static str),
 }
 
+impl DLLError {
+    pub(crate) fn invalid_index(table: metadata::table::Kind, row: usize, context: ErrorContext) -> Self {
+        InvalidTableIndex {
+            table,
+            row,
+            context: Box::new(context),
+        }
+    }
+
+    pub(crate) fn bad_signature(offset: usize, source: ScrollError, context: ErrorContext) -> Self {
+        BadSignature {
+            offset,
+            source,
+            context: Box::new(context),
+        }
+    }
+
+    pub(crate) fn invalid_offset(token: u32, offset: i64, context: ErrorContext) -> Self {
+        InvalidInstructionOffset {
+            token,
+            offset,
+            context: Box::new(context),
+        }
+    }
+
+    pub(crate) fn unresolvable(index: &'static str, context: ErrorContext) -> Self {
+        UnresolvableCodedIndex {
+            index,
+            context: Box::new(context),
+        }
+    }
+}
+
+/// Describes which part of a module an error was found in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The metadata item being resolved, such as `"field layout"` or `"custom attribute 3"`.
+    pub item: String,
+    /// The name of the type the item belongs to, if any.
+    pub type_name: Option<String>,
+    /// The name of the method or other member the item belongs to, if any.
+    pub member_name: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn new(item: impl Into<String>) -> Self {
+        Self {
+            item: item.into(),
+            type_name: None,
+            member_name: None,
+        }
+    }
+
+    #[must_use]
+    pub fn in_type(mut self, name: impl Into<String>) -> Self {
+        self.type_name = Some(name.into());
+        self
+    }
+
+    #[must_use]
+    pub fn in_member(mut self, name: impl Into<String>) -> Self {
+        self.member_name = Some(name.into());
+        self
+    }
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.item)?;
+        match (&self.type_name, &self.member_name) {
+            (Some(t), Some(m)) => write!(f, " (in {}::{})", t, m),
+            (Some(n), None) | (None, Some(n)) => write!(f, " (in {})", n),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, DLLError>;
 
 impl<'a> DLL<'a> {
     pub fn parse(bytes: &'a [u8]) -> Result<DLL<'a>> {
-        let (sections, dir) = match FileKind::parse(bytes)? {
+        let (sections, dir, resource_dir) = match FileKind::parse(bytes)? {
             FileKind::Pe32 => {
                 let file = PeFile32::parse(bytes)?;
                 (
                     file.section_table(),
                     file.data_directory(pe::IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR),
+                    file.data_directory(pe::IMAGE_DIRECTORY_ENTRY_RESOURCE),
                 )
             }
             FileKind::Pe64 => {
@@ -63,6 +176,7 @@ impl<'a> DLL<'a> {
                 (
                     file.section_table(),
                     file.data_directory(pe::IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR),
+                    file.data_directory(pe::IMAGE_DIRECTORY_ENTRY_RESOURCE),
                 )
             }
             _ => return Err(Other("invalid object type, must be PE32 or PE64")),
@@ -71,13 +185,31 @@ impl<'a> DLL<'a> {
         let cli_b = dir
             .ok_or(Other("missing CLI metadata data directory in PE image"))?
             .data(bytes, &sections)?;
+
         Ok(DLL {
             buffer: bytes,
             cli: cli_b.pread_with(0, scroll::LE)?,
             sections,
+            resource_rva: resource_dir.map(|d| d.virtual_address.get(LittleEndian)),
         })
     }
 
+    /// Reads the Win32 resources in the image's resource directory, which are empty if it doesn't have one.
+    ///
+    /// They are only read when this is called, so that a resource directory that can't be read doesn't stop the rest
+    /// of the image from being read.
+    pub fn win32_resources(&self) -> Result<win32::Resources<'a>> {
+        let Some(rva) = self.resource_rva else {
+            return Ok(win32::Resources::default());
+        };
+        // resource data can follow the directory anywhere in its section
+        let section = self
+            .sections
+            .pe_data_at(self.buffer, rva)
+            .ok_or(Other("bad resource directory RVA"))?;
+        win32::Resources::parse(section, rva)
+    }
+
     pub fn at_rva(&self, rva: &RVASize) -> Result<&'a [u8]> {
         let dir = ImageDataDirectory {
             virtual_address: U32Bytes::new(LittleEndian, rva.rva),
@@ -92,7 +224,7 @@ impl<'a> DLL<'a> {
             .ok_or(Other("bad stream offset"))
     }
 
-    fn get_stream(&self, name: &'static str) -> Result<Option<&'a [u8]>> {
+    pub(crate) fn get_stream(&self, name: &'static str) -> Result<Option<&'a [u8]>> {
         let meta = self.get_cli_metadata()?;
         let Some(header) = meta.stream_headers.iter().find(|h| h.name == name) else { return Ok(None) };
         let data = self.raw_rva(self.cli.metadata.rva + header.offset)?;
@@ -109,10 +241,27 @@ impl<'a> DLL<'a> {
         self.at_rva(&self.cli.metadata)?.pread(0).map_err(CLI)
     }
 
+    /// Reads the table stream, which is either the usual compressed `#~` stream or the uncompressed `#-` stream.
+    ///
+    /// Uncompressed streams may contain pointer tables like [`metadata::table::MethodPtr`], which the member lists of
+    /// other tables index into instead of the tables they point to.
     pub fn get_logical_metadata(&self) -> Result<metadata::header::Header> {
-        self.get_stream("#~")?
-            .ok_or(Other("unable to find metadata stream"))?
-            .pread(0)
+        let stream = match self.get_stream("#~")? {
+            Some(s) => s,
+            None => self.get_stream("#-")?.ok_or(Other("unable to find metadata stream"))?,
+        };
+
+        // a #JTD stream, whatever its contents, makes every index 4 bytes wide
+        let large_indices = self.get_cli_metadata()?.stream_headers.iter().any(|h| h.name == "#JTD");
+
+        stream
+            .pread_with(
+                0,
+                metadata::header::Layout {
+                    large_indices,
+                    ..metadata::header::Layout::default()
+                },
+            )
             .map_err(CLI)
     }
 
@@ -128,6 +277,17 @@ impl<'a> DLL<'a> {
     }
 
     pub fn resolve(&self, opts: read::Options) -> Result<Resolution<'a>> {
-        read::read_impl(self, opts)
+        self.resolve_lazy()?.resolve(opts)
+    }
+
+    /// Like [`DLL::resolve`], but also attaches the debugging information from the DLL's portable PDB
+    /// to [`Resolution::documents`] and the method bodies.
+    pub fn resolve_with_pdb(&self, pdb: &PDB<'a>, opts: read::Options) -> Result<Resolution<'a>> {
+        read::read_impl(&self.resolve_lazy()?, Some(pdb), opts)
+    }
+
+    /// Creates a [`LazyResolution`], which only resolves the parts of the DLL that are accessed.
+    pub fn resolve_lazy(&self) -> Result<LazyResolution<'a>> {
+        LazyResolution::new(DLL::parse(self.buffer)?)
     }
 }
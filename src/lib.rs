@@ -75,30 +75,44 @@ mod utils {
         hasher.finish()
     }
 
-    pub fn round_up_to_4(mut val: usize) -> (usize, usize) {
-        let rem = val % 4;
-        if rem != 0 {
-            val += 4 - rem;
-        }
-        (val, rem)
+    /// Returns `val` rounded up to a multiple of 4, along with the amount of padding that was added.
+    pub fn round_up_to_4(val: usize) -> (usize, usize) {
+        let padding = (4 - val % 4) % 4;
+        (val + padding, padding)
     }
 }
 
 pub mod binary;
 mod convert;
 pub mod dll;
+pub mod pdb;
 pub mod resolution;
 pub mod resolved;
+pub mod resources;
+pub mod win32;
 
 pub mod prelude {
     pub use crate::{
         access, asm,
-        dll::{DLLError, DLL},
-        resolution::{read::Options as ReadOptions, utils::*, write::Options as WriteOptions, *},
+        dll::{DLLError, ErrorContext, DLL},
+        pdb::PDB,
+        resolution::{
+            lookup::Lookup,
+            merge::Options as MergeOptions,
+            read::Options as ReadOptions,
+            resolver::AssemblyResolver,
+            strong_name::StrongNameKey,
+            tokens::TokenRemapping,
+            utils::*,
+            write::{DllCharacteristics, Options as WriteOptions, Platform, Subsystem},
+            *,
+        },
         resolved::{
             assembly::*,
             attribute::*,
-            body, generic,
+            body,
+            builder::*,
+            debug, generic,
             il::*,
             members::{Accessibility as MemberAccessibility, *},
             module::*,
@@ -107,6 +121,7 @@ pub mod prelude {
             types::{Accessibility as TypeAccessibility, *},
             Accessibility, ResolvedDebug,
         },
+        resources::ResourceSet,
     };
 }
 
@@ -0,0 +1,62 @@
+use super::{
+    binary::{cli::Metadata, heap::Reader, metadata, pdb},
+    dll::{DLLError::*, Result},
+};
+use scroll::Pread;
+
+/// Represents a standalone portable PDB file, which stores debugging information for a DLL in its own metadata image.
+///
+/// Unlike a [`DLL`](crate::dll::DLL), a portable PDB is not a PE file: it consists of only the metadata root and its streams.
+/// See the Portable PDB v1.0 specification for more information.
+#[derive(Debug)]
+pub struct PDB<'a> {
+    buffer: &'a [u8],
+    /// The metadata root of the PDB, containing the stream headers.
+    pub metadata: Metadata<'a>,
+}
+
+impl<'a> PDB<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<PDB<'a>> {
+        let metadata: Metadata = bytes.pread(0)?;
+        if metadata.signature != 0x424A_5342 {
+            return Err(Other("invalid metadata signature for portable PDB"));
+        }
+        Ok(PDB {
+            buffer: bytes,
+            metadata,
+        })
+    }
+
+    fn get_stream(&self, name: &'static str) -> Result<Option<&'a [u8]>> {
+        let Some(header) = self.metadata.stream_headers.iter().find(|h| h.name == name) else {
+            return Ok(None);
+        };
+        let start = header.offset as usize;
+        self.buffer
+            .get(start..start + header.size as usize)
+            .map(Some)
+            .ok_or(Other("bad stream offset"))
+    }
+
+    pub fn get_heap<T: Reader<'a>>(&self) -> Result<T> {
+        Ok(T::new(self.get_stream(T::NAME)?.unwrap_or(&[])))
+    }
+
+    pub fn get_pdb_stream(&self) -> Result<pdb::Stream> {
+        self.get_stream(pdb::Stream::NAME)?
+            .ok_or(Other("unable to find #Pdb stream"))?
+            .pread(0)
+            .map_err(CLI)
+    }
+
+    pub fn get_logical_metadata(&self) -> Result<metadata::header::Header> {
+        let rows = self.get_pdb_stream()?.table_rows();
+        self.get_stream("#~")?
+            .ok_or(Other("unable to find metadata stream"))?
+            .pread_with(
+                0,
+                metadata::header::Layout::from(metadata::header::ExternalRows(Some(&rows))),
+            )
+            .map_err(CLI)
+    }
+}
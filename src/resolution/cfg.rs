@@ -0,0 +1,470 @@
+//! Control flow graphs over the instructions of a [`body::Method`].
+//!
+//! A [`ControlFlowGraph`] splits a body into [`BasicBlock`]s, which are maximal runs of instructions that are only
+//! entered at their first instruction and only left after their last one. Blocks are linked by [`Edge`]s for
+//! fallthrough, branches, `switch` tables and `leave` instructions, and for the ways exception handling transfers
+//! control: every block in a protected region can reach the entry of each of its handlers (and filters), and the
+//! `endfinally` at the end of a `finally` handler continues at the targets of the `leave` instructions that exit
+//! its try block.
+//!
+//! [`ControlFlowGraph::dominators`] and [`ControlFlowGraph::loops`] are built on top of the graph.
+
+use super::stack::{flow, Flow};
+use crate::dll::{DLLError::CLI, Result};
+use crate::resolved::{
+    body::{self, DataSection, ExceptionKind},
+    il::Instruction,
+};
+use std::collections::BTreeSet;
+
+macro_rules! throw {
+    ($($arg:tt)*) => {
+        return Err(CLI(scroll::Error::Custom(format!($($arg)*))))
+    }
+}
+
+/// How control is transferred along an [`Edge`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Falling through to the next instruction, including when a conditional branch or `switch` is not taken.
+    FallThrough,
+    /// Taking an unconditional or conditional branch, or one of the cases of a `switch`.
+    Branch,
+    /// A `leave` instruction exiting a protected region or handler.
+    Leave,
+    /// An exception thrown in a protected region, which enters a handler or filter of that region.
+    Exception,
+    /// An `endfinally` returning from a `finally` handler to the target of a `leave` that exited its try block.
+    EndFinally,
+}
+
+/// A directed edge between two blocks of a [`ControlFlowGraph`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Edge {
+    /// The index of the block on the other end of the edge.
+    pub block: usize,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that is only entered at its first instruction and only left after its last one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// The index of the first instruction in the block.
+    pub start: usize,
+    /// The index just past the last instruction in the block.
+    pub end: usize,
+    /// The blocks that control can be transferred to from the end of this block, or from anywhere inside it for
+    /// [`EdgeKind::Exception`] edges.
+    pub successors: Vec<Edge>,
+    /// The blocks with an edge to this block, where [`Edge::block`] is the block the edge comes from.
+    pub predecessors: Vec<Edge>,
+}
+
+impl BasicBlock {
+    /// The indices of the instructions in the block.
+    pub fn instructions(&self) -> std::ops::Range<usize> {
+        self.start..self.end
+    }
+}
+
+/// The basic blocks of a method body and the edges between them. See the [module documentation](self).
+///
+/// Block 0 is the entry block of the method, and blocks are numbered in the order of their instructions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    blocks: Vec<BasicBlock>,
+    instruction_blocks: Vec<usize>,
+}
+
+fn exception_handlers(body: &body::Method) -> impl Iterator<Item = &body::Exception> {
+    body.data_sections.iter().flat_map(|d| match d {
+        DataSection::ExceptionHandlers(es) => es.as_slice(),
+        DataSection::Unrecognized { .. } => &[],
+    })
+}
+
+impl ControlFlowGraph {
+    /// Builds the graph of a method body.
+    ///
+    /// Returns an error if a branch, `switch` or `leave` targets an instruction that does not exist, or if an
+    /// exception handler covers instructions outside of the body.
+    #[allow(clippy::too_many_lines)]
+    pub fn new(body: &body::Method) -> Result<Self> {
+        let instructions = &body.instructions;
+        let len = instructions.len();
+
+        // the instructions that start a block, plus len for the end of the last block
+        let mut leaders = BTreeSet::from([0, len]);
+        macro_rules! leader {
+            ($target:expr, $what:literal) => {{
+                let target = $target;
+                if target > len {
+                    throw!(
+                        "{} {} is out of bounds for a method with {} instructions",
+                        $what,
+                        target,
+                        len
+                    );
+                }
+                leaders.insert(target);
+            }};
+        }
+
+        for (idx, i) in instructions.iter().enumerate() {
+            match flow(i) {
+                Flow::Next => continue,
+                Flow::Branch(t) | Flow::Leave(t) => leader!(t, "control flow target"),
+                Flow::ConditionalBranch(ts) => {
+                    for &t in ts {
+                        leader!(t, "control flow target");
+                    }
+                }
+                Flow::End => {}
+            }
+            leaders.insert(idx + 1);
+        }
+        for e in exception_handlers(body) {
+            leader!(e.try_offset, "try block start");
+            leader!(e.try_offset + e.try_length, "try block end");
+            leader!(e.handler_offset, "handler start");
+            leader!(e.handler_offset + e.handler_length, "handler end");
+            if let ExceptionKind::Filter { offset } = e.kind {
+                leader!(offset, "filter start");
+            }
+        }
+
+        // a branch to len is allowed by the bounds check above, but there's no block there to branch to
+        let check_target = |t: usize| {
+            if t == len {
+                throw!("control flow target {} is past the end of the method", t);
+            }
+            Ok(t)
+        };
+
+        let leaders: Vec<_> = leaders.into_iter().collect();
+        let mut blocks: Vec<_> = leaders
+            .windows(2)
+            .map(|w| BasicBlock {
+                start: w[0],
+                end: w[1],
+                successors: vec![],
+                predecessors: vec![],
+            })
+            .collect();
+
+        let mut instruction_blocks = vec![0; len];
+        for (idx, b) in blocks.iter().enumerate() {
+            instruction_blocks[b.instructions()].fill(idx);
+        }
+
+        let mut edges = vec![];
+        for (idx, b) in blocks.iter().enumerate() {
+            let last = b.end - 1;
+            let mut add = |target: usize, kind| -> Result<()> {
+                edges.push((
+                    idx,
+                    Edge {
+                        block: instruction_blocks[check_target(target)?],
+                        kind,
+                    },
+                ));
+                Ok(())
+            };
+
+            match flow(&instructions[last]) {
+                Flow::Next if b.end < len => add(b.end, EdgeKind::FallThrough)?,
+                Flow::Next | Flow::End => {}
+                Flow::Branch(t) => add(t, EdgeKind::Branch)?,
+                Flow::ConditionalBranch(ts) => {
+                    for &t in ts {
+                        add(t, EdgeKind::Branch)?;
+                    }
+                    if b.end < len {
+                        add(b.end, EdgeKind::FallThrough)?;
+                    }
+                }
+                Flow::Leave(t) => add(t, EdgeKind::Leave)?,
+            }
+        }
+
+        for e in exception_handlers(body) {
+            let try_range = e.try_offset..e.try_offset + e.try_length;
+            let handler_range = e.handler_offset..e.handler_offset + e.handler_length;
+            if try_range.is_empty() || handler_range.is_empty() {
+                continue;
+            }
+
+            let entries = std::iter::once(e.handler_offset).chain(match e.kind {
+                ExceptionKind::Filter { offset } => Some(offset),
+                _ => None,
+            });
+            for entry in entries {
+                let entry = instruction_blocks[check_target(entry)?];
+                for from in instruction_blocks[try_range.start]..=instruction_blocks[try_range.end - 1] {
+                    edges.push((
+                        from,
+                        Edge {
+                            block: entry,
+                            kind: EdgeKind::Exception,
+                        },
+                    ));
+                }
+            }
+
+            if e.kind == ExceptionKind::Finally {
+                let exits: Vec<_> = instructions[try_range.clone()]
+                    .iter()
+                    .filter_map(|i| match i {
+                        Instruction::Leave(t) if !try_range.contains(t) => Some(instruction_blocks[*t]),
+                        _ => None,
+                    })
+                    .collect();
+
+                for from in instruction_blocks[handler_range.start]..=instruction_blocks[handler_range.end - 1] {
+                    if matches!(instructions[blocks[from].end - 1], Instruction::EndFinally) {
+                        edges.extend(exits.iter().map(|&block| {
+                            (
+                                from,
+                                Edge {
+                                    block,
+                                    kind: EdgeKind::EndFinally,
+                                },
+                            )
+                        }));
+                    }
+                }
+            }
+        }
+
+        for (from, edge) in edges {
+            // switch tables and nested handlers can produce the same edge more than once
+            if blocks[from].successors.contains(&edge) {
+                continue;
+            }
+            blocks[from].successors.push(edge);
+            blocks[edge.block].predecessors.push(Edge {
+                block: from,
+                kind: edge.kind,
+            });
+        }
+
+        Ok(Self {
+            blocks,
+            instruction_blocks,
+        })
+    }
+
+    /// The blocks of the graph, in the order of their instructions.
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// The index of the block containing an instruction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `instruction` is out of bounds for the body that the graph was built from.
+    pub fn block_of(&self, instruction: usize) -> usize {
+        self.instruction_blocks[instruction]
+    }
+
+    /// The blocks reachable from the entry block, in reverse postorder, which visits every block before its
+    /// successors except along the back edges of loops.
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.blocks.len());
+        if self.blocks.is_empty() {
+            return order;
+        }
+
+        let mut visited = vec![false; self.blocks.len()];
+        visited[0] = true;
+        // each entry is a block and how many of its successors have been visited so far
+        let mut stack = vec![(0, 0)];
+        while let Some((block, next)) = stack.last_mut() {
+            if let Some(edge) = self.blocks[*block].successors.get(*next) {
+                *next += 1;
+                if !visited[edge.block] {
+                    visited[edge.block] = true;
+                    stack.push((edge.block, 0));
+                }
+            } else {
+                order.push(*block);
+                stack.pop();
+            }
+        }
+
+        order.reverse();
+        order
+    }
+
+    /// Computes the dominator tree of the graph, rooted at the entry block.
+    pub fn dominators(&self) -> Dominators {
+        // Cooper, Harvey and Kennedy, "A Simple, Fast Dominance Algorithm"
+        let order = self.reverse_postorder();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (idx, &b) in order.iter().enumerate() {
+            position[b] = idx;
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        if let Some(&entry) = order.first() {
+            idom[entry] = Some(entry);
+        }
+
+        let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while position[a] > position[b] {
+                    a = idom[a].unwrap();
+                }
+                while position[b] > position[a] {
+                    b = idom[b].unwrap();
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in order.iter().skip(1) {
+                let new_idom = self.blocks[b]
+                    .predecessors
+                    .iter()
+                    .map(|p| p.block)
+                    .filter(|&p| idom[p].is_some())
+                    .reduce(|a, p| intersect(&idom, a, p));
+                if new_idom.is_some() && idom[b] != new_idom {
+                    idom[b] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        // the entry block has no immediate dominator of its own
+        if let Some(&entry) = order.first() {
+            idom[entry] = None;
+        }
+
+        Dominators { idom, position }
+    }
+
+    /// Finds the natural loops of the graph, ordered by header.
+    ///
+    /// A loop is formed by the back edges into a header block that dominates their sources, and contains every block
+    /// that can reach one of those back edges without passing through the header. Loops that are only entered by
+    /// jumping into their middle (irreducible control flow) have no header, so they are not reported.
+    pub fn loops(&self) -> Vec<Loop> {
+        let dominators = self.dominators();
+
+        let mut loops: Vec<Loop> = vec![];
+        for (from, b) in self.blocks.iter().enumerate() {
+            for edge in &b.successors {
+                if !dominators.dominates(edge.block, from) {
+                    continue;
+                }
+
+                let header = edge.block;
+                let position = loops.iter().position(|l| l.header == header).unwrap_or_else(|| {
+                    loops.push(Loop {
+                        header,
+                        latches: vec![],
+                        blocks: BTreeSet::from([header]),
+                    });
+                    loops.len() - 1
+                });
+                let l = &mut loops[position];
+                if !l.latches.contains(&from) {
+                    l.latches.push(from);
+                }
+
+                let mut worklist = vec![from];
+                while let Some(block) = worklist.pop() {
+                    if l.blocks.insert(block) {
+                        worklist.extend(
+                            self.blocks[block]
+                                .predecessors
+                                .iter()
+                                .map(|p| p.block)
+                                .filter(|&p| dominators.is_reachable(p)),
+                        );
+                    }
+                }
+            }
+        }
+
+        loops.sort_by_key(|l| l.header);
+        loops
+    }
+}
+
+/// The dominator tree of a [`ControlFlowGraph`], from [`ControlFlowGraph::dominators`].
+///
+/// A block dominates another if every path from the entry block to the other block passes through it.
+/// Every block dominates itself. Blocks that cannot be reached from the entry block are not part of the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dominators {
+    idom: Vec<Option<usize>>,
+    // the position of each block in reverse postorder, or usize::MAX if it is unreachable
+    position: Vec<usize>,
+}
+
+impl Dominators {
+    /// Whether a block can be reached from the entry block.
+    pub fn is_reachable(&self, block: usize) -> bool {
+        self.position[block] != usize::MAX
+    }
+
+    /// The closest block that strictly dominates `block`, or `None` for the entry block and unreachable blocks.
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        self.idom[block]
+    }
+
+    /// Whether every path from the entry block to `block` passes through `dominator`.
+    pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+        if !self.is_reachable(dominator) || !self.is_reachable(block) {
+            return false;
+        }
+
+        let mut current = block;
+        loop {
+            if current == dominator {
+                return true;
+            }
+            // walking up the tree only moves to earlier blocks in reverse postorder, so we can stop early
+            if self.position[current] < self.position[dominator] {
+                return false;
+            }
+            match self.idom[current] {
+                Some(d) => current = d,
+                None => return false,
+            }
+        }
+    }
+
+    /// The blocks whose immediate dominator is `block`.
+    pub fn children(&self, block: usize) -> impl Iterator<Item = usize> + '_ {
+        self.idom
+            .iter()
+            .enumerate()
+            .filter(move |(_, d)| **d == Some(block))
+            .map(|(b, _)| b)
+    }
+}
+
+/// A natural loop of a [`ControlFlowGraph`], from [`ControlFlowGraph::loops`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    /// The block that every iteration of the loop starts at, which dominates the rest of the loop.
+    pub header: usize,
+    /// The blocks with a back edge to the header.
+    pub latches: Vec<usize>,
+    /// All blocks in the loop, including the header and the latches. Nested loops are included too.
+    pub blocks: BTreeSet<usize>,
+}
+
+impl Loop {
+    /// Whether this loop is nested inside `other`.
+    pub fn is_inside(&self, other: &Loop) -> bool {
+        self.header != other.header && other.blocks.contains(&self.header)
+    }
+}
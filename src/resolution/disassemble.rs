@@ -0,0 +1,2106 @@
+use super::{
+    stack::{flow, Flow},
+    EntryPoint, MethodIndex, MethodMemberIndex, Resolution, TypeIndex,
+};
+use crate::resolved::{
+    assembly::HashAlgorithm,
+    attribute::{Attribute, SecurityDeclaration},
+    body::{self, DataSection, ExceptionKind},
+    generic::{Generic, Variance},
+    il::*,
+    members::{self, *},
+    resource::{Implementation, Visibility},
+    signature::{
+        CallingConvention, MethodSignature, Parameter, ParameterType, ReturnType, StandAloneCallingConvention,
+    },
+    types::{self, *},
+    Accessibility,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+};
+
+// ILAsm keywords, including instruction names, that must be quoted when used as identifiers
+const KEYWORDS: &[&str] = &[
+    "abstract",
+    "add",
+    "algorithm",
+    "and",
+    "ansi",
+    "any",
+    "arglist",
+    "array",
+    "as",
+    "assembly",
+    "assert",
+    "at",
+    "auto",
+    "autochar",
+    "beforefieldinit",
+    "bool",
+    "box",
+    "br",
+    "break",
+    "bytearray",
+    "call",
+    "calli",
+    "callvirt",
+    "castclass",
+    "catch",
+    "cdecl",
+    "ceq",
+    "cgt",
+    "char",
+    "cil",
+    "ckfinite",
+    "class",
+    "clt",
+    "const",
+    "constrained",
+    "cpblk",
+    "cpobj",
+    "custom",
+    "default",
+    "demand",
+    "deny",
+    "div",
+    "dup",
+    "endfilter",
+    "endfinally",
+    "explicit",
+    "extends",
+    "extern",
+    "false",
+    "famandassem",
+    "family",
+    "famorassem",
+    "fastcall",
+    "fault",
+    "field",
+    "filter",
+    "final",
+    "finally",
+    "fixed",
+    "float",
+    "float32",
+    "float64",
+    "forwarder",
+    "forwardref",
+    "handler",
+    "hidebysig",
+    "import",
+    "in",
+    "inheritcheck",
+    "init",
+    "initblk",
+    "initobj",
+    "initonly",
+    "instance",
+    "int",
+    "int16",
+    "int32",
+    "int64",
+    "int8",
+    "interface",
+    "internalcall",
+    "isinst",
+    "iunknown",
+    "jmp",
+    "lasterr",
+    "ldlen",
+    "ldnull",
+    "ldobj",
+    "ldstr",
+    "ldtoken",
+    "leave",
+    "linkcheck",
+    "literal",
+    "localloc",
+    "managed",
+    "marshal",
+    "method",
+    "mkrefany",
+    "modopt",
+    "modreq",
+    "mul",
+    "native",
+    "neg",
+    "nested",
+    "newarr",
+    "newobj",
+    "newslot",
+    "no",
+    "noinlining",
+    "nomangle",
+    "nometadata",
+    "nop",
+    "not",
+    "notserialized",
+    "null",
+    "nullref",
+    "object",
+    "objectref",
+    "opt",
+    "or",
+    "out",
+    "permitonly",
+    "pinned",
+    "pinvokeimpl",
+    "pop",
+    "preservesig",
+    "private",
+    "privatescope",
+    "public",
+    "readonly",
+    "refanytype",
+    "refanyval",
+    "rem",
+    "request",
+    "ret",
+    "rethrow",
+    "retargetable",
+    "rtspecialname",
+    "runtime",
+    "sealed",
+    "sequential",
+    "serializable",
+    "shl",
+    "shr",
+    "sizeof",
+    "specialname",
+    "static",
+    "stdcall",
+    "stobj",
+    "strict",
+    "string",
+    "struct",
+    "sub",
+    "switch",
+    "synchronized",
+    "tail",
+    "thiscall",
+    "throw",
+    "to",
+    "true",
+    "typedref",
+    "uint",
+    "uint8",
+    "uint16",
+    "uint32",
+    "uint64",
+    "unaligned",
+    "unicode",
+    "unmanaged",
+    "unsigned",
+    "valuetype",
+    "vararg",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar",
+    "winapi",
+    "with",
+    "xor",
+];
+
+fn id(name: &str) -> String {
+    let is_id_char = |c: char| c.is_ascii_alphanumeric() || "_$@?`".contains(c);
+    let simple = name.starts_with(|c: char| !c.is_ascii_digit() && is_id_char(c)) && name.chars().all(is_id_char);
+
+    if name == ".ctor" || name == ".cctor" || (simple && !KEYWORDS.contains(&name)) {
+        name.to_string()
+    } else {
+        format!("'{}'", name.replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+}
+
+fn dotted(name: &str) -> String {
+    name.split('.').map(id).collect::<Vec<_>>().join(".")
+}
+
+fn type_name(namespace: Option<&str>, name: &str) -> String {
+    match namespace {
+        Some(ns) if !ns.is_empty() => format!("{}.{}", dotted(ns), id(name)),
+        _ => id(name),
+    }
+}
+
+fn bytes(data: &[u8]) -> String {
+    let hex: Vec<_> = data.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("({})", hex.join(" "))
+}
+
+// string literals are only used when they survive the trip through ILAsm's source encoding unchanged
+fn string(utf16: &[u16]) -> String {
+    match String::from_utf16(utf16) {
+        Ok(s)
+            if s.chars()
+                .all(|c| c == ' ' || c.is_ascii_graphic() || "\n\r\t".contains(c)) =>
+        {
+            let mut buf = String::from('"');
+            for c in s.chars() {
+                match c {
+                    '"' => buf.push_str("\\\""),
+                    '\\' => buf.push_str("\\\\"),
+                    '\n' => buf.push_str("\\n"),
+                    '\r' => buf.push_str("\\r"),
+                    '\t' => buf.push_str("\\t"),
+                    c => buf.push(c),
+                }
+            }
+            buf.push('"');
+            buf
+        }
+        _ => format!(
+            "bytearray {}",
+            bytes(&utf16.iter().flat_map(|c| c.to_le_bytes()).collect::<Vec<_>>())
+        ),
+    }
+}
+
+fn float32(f: f32) -> String {
+    let s = format!("{:?}", f);
+    if f.is_finite() && s.contains('.') && !s.contains('e') {
+        s
+    } else {
+        format!("{:#010x}", f.to_bits())
+    }
+}
+
+fn float64(f: f64) -> String {
+    let s = format!("{:?}", f);
+    if f.is_finite() && s.contains('.') && !s.contains('e') {
+        s
+    } else {
+        format!("{:#018x}", f.to_bits())
+    }
+}
+
+fn constant(c: &Constant) -> String {
+    use Constant::*;
+    match c {
+        Boolean(b) => format!("bool({})", b),
+        Char(c) => format!("char({:#06x})", c),
+        Int8(i) => format!("int8({})", i),
+        UInt8(i) => format!("uint8({})", i),
+        Int16(i) => format!("int16({})", i),
+        UInt16(i) => format!("uint16({})", i),
+        Int32(i) => format!("int32({})", i),
+        UInt32(i) => format!("uint32({})", i),
+        Int64(i) => format!("int64({})", i),
+        UInt64(i) => format!("uint64({})", i),
+        Float32(f) => format!("float32({})", float32(*f)),
+        Float64(f) => format!("float64({})", float64(*f)),
+        String(s) => string(s),
+        Null => "nullref".to_string(),
+    }
+}
+
+fn native_type(n: NativeIntrinsic) -> &'static str {
+    use NativeIntrinsic::*;
+    match n {
+        Boolean => "bool",
+        Int8 => "int8",
+        UInt8 => "uint8",
+        Int16 => "int16",
+        UInt16 => "uint16",
+        Int32 => "int32",
+        UInt32 => "uint32",
+        Int64 => "int64",
+        UInt64 => "uint64",
+        Float32 => "float32",
+        Float64 => "float64",
+        LPStr => "lpstr",
+        LPWStr => "lpwstr",
+        IntPtr => "int",
+        UIntPtr => "uint",
+        Function => "method",
+        COMInterface => "interface",
+        BStr => "bstr",
+        AsAny => "as any",
+        COMIUnknown => "iunknown",
+        LPUTF8Str => "lputf8str",
+        Variant => "variant",
+        Currency => "currency",
+        Decimal => "decimal",
+        Date => "date",
+        LPTStr => "lptstr",
+        COMIDispatch => "idispatch",
+        Struct => "struct",
+        ByValStr => "byvalstr",
+        AnsiBStr => "ansi bstr",
+        TBStr => "tbstr",
+        VariantBool => "variant bool",
+        LPStruct => "lpstruct",
+        HResult => "error",
+        IInspectable => "iinspectable",
+        HString => "hstring",
+        Void => "void",
+        SysChar => "syschar",
+        Pointer => "*",
+        ObjectRef => "objectref",
+        NestedStruct => "nested struct",
+    }
+}
+
+fn variant_type(vt: u16) -> String {
+    let base = match vt & 0x0fff {
+        0 => "",
+        1 => "null",
+        2 => "int16",
+        3 => "int32",
+        4 => "float32",
+        5 => "float64",
+        6 => "currency",
+        7 => "date",
+        8 => "bstr",
+        9 => "idispatch",
+        10 => "error",
+        11 => "bool",
+        12 => "variant",
+        13 => "iunknown",
+        14 => "decimal",
+        16 => "int8",
+        17 => "uint8",
+        18 => "uint16",
+        19 => "uint32",
+        20 => "int64",
+        21 => "uint64",
+        22 => "int",
+        23 => "uint",
+        24 => "void",
+        25 => "hresult",
+        26 => "*",
+        27 => "safearray",
+        28 => "carray",
+        29 => "userdefined",
+        30 => "lpstr",
+        31 => "lpwstr",
+        36 => "record",
+        64 => "filetime",
+        65 => "blob",
+        66 => "stream",
+        67 => "storage",
+        68 => "streamed_object",
+        69 => "stored_object",
+        70 => "blob_object",
+        71 => "cf",
+        72 => "clsid",
+        _ => return vt.to_string(),
+    };
+
+    let mut buf = base.to_string();
+    if vt & 0x1000 != 0 {
+        buf.push_str(" vector");
+    }
+    if vt & 0x2000 != 0 {
+        buf.push_str("[]");
+    }
+    if vt & 0x4000 != 0 {
+        buf.push('&');
+    }
+    buf
+}
+
+fn quoted(s: &str) -> String {
+    string(&s.encode_utf16().collect::<Vec<_>>())
+}
+
+fn marshal(spec: &MarshalSpec) -> String {
+    match spec {
+        MarshalSpec::Primitive(n) => format!("marshal({})", native_type(*n)),
+        MarshalSpec::Array {
+            element_type,
+            length_parameter,
+            additional_elements,
+        } => {
+            let element = element_type.map_or("", native_type);
+            let size = match (additional_elements, length_parameter) {
+                (Some(n), Some(p)) => format!("{} + {}", n, p),
+                (None, Some(p)) => format!("+ {}", p),
+                (Some(n), None) => n.to_string(),
+                (None, None) => String::new(),
+            };
+            format!("marshal({}[{}])", element, size)
+        }
+        MarshalSpec::FixedArray { size, element_type } => match element_type {
+            Some(t) => format!("marshal(fixed array [{}] {})", size, native_type(*t)),
+            None => format!("marshal(fixed array [{}])", size),
+        },
+        MarshalSpec::FixedString { size } => format!("marshal(fixed sysstring [{}])", size),
+        MarshalSpec::SafeArray {
+            variant_type: vt,
+            user_defined_subtype,
+        } => {
+            let mut buf = String::from("marshal(safearray");
+            if let Some(vt) = vt {
+                write!(buf, " {}", variant_type(*vt)).unwrap();
+            }
+            if let Some(s) = user_defined_subtype {
+                write!(buf, ", {}", quoted(s)).unwrap();
+            }
+            buf.push(')');
+            buf
+        }
+        MarshalSpec::CustomMarshaler {
+            guid,
+            native_type_name,
+            marshaler,
+            cookie,
+        } => {
+            if guid.is_empty() && native_type_name.is_empty() {
+                format!("marshal(custom({}, {}))", quoted(marshaler), quoted(cookie))
+            } else {
+                format!(
+                    "marshal(custom({}, {}, {}, {}))",
+                    quoted(guid),
+                    quoted(native_type_name),
+                    quoted(marshaler),
+                    quoted(cookie)
+                )
+            }
+        }
+        MarshalSpec::Interface { kind, iid_parameter } => {
+            let name = match kind {
+                InterfaceKind::Interface => "interface",
+                InterfaceKind::IUnknown => "iunknown",
+                InterfaceKind::IDispatch => "idispatch",
+                InterfaceKind::IInspectable => "iinspectable",
+            };
+            format!("marshal({}(iidparam = {}))", name, iid_parameter)
+        }
+    }
+}
+
+fn member_access(access: members::Accessibility) -> &'static str {
+    use Accessibility::*;
+    match access {
+        members::Accessibility::CompilerControlled => "privatescope",
+        members::Accessibility::Access(a) => match a {
+            Private => "private",
+            FamilyANDAssembly => "famandassem",
+            Assembly => "assembly",
+            Family => "family",
+            FamilyORAssembly => "famorassem",
+            Public => "public",
+        },
+    }
+}
+
+fn is_module_type(idx: TypeIndex) -> bool {
+    idx.0 == 0
+}
+
+// ILAsm type syntax, as opposed to the C#-like syntax of ResolvedDebug
+trait Syntax {
+    fn syntax(&self, res: &Resolution) -> String;
+}
+
+impl Syntax for UserType {
+    fn syntax(&self, res: &Resolution) -> String {
+        match self {
+            UserType::Definition(idx) => {
+                let t = &res[*idx];
+                match t.encloser {
+                    Some(enc) => format!("{}/{}", UserType::Definition(enc).syntax(res), id(&t.name)),
+                    None => type_name(t.namespace.as_deref(), &t.name),
+                }
+            }
+            UserType::Reference(idx) => {
+                let t = &res[*idx];
+                let name = type_name(t.namespace.as_deref(), &t.name);
+
+                use ResolutionScope::*;
+                match t.scope {
+                    Nested(enc) => format!("{}/{}", UserType::Reference(enc).syntax(res), id(&t.name)),
+                    ExternalModule(m) => format!("[.module {}]{}", dotted(&res[m].name), name),
+                    CurrentModule => name,
+                    Assembly(a) => format!("[{}]{}", dotted(&res[a].name), name),
+                    Exported => match res
+                        .exported_types
+                        .iter()
+                        .find(|e| e.name == t.name && e.namespace == t.namespace)
+                        .map(|e| e.implementation)
+                    {
+                        Some(TypeImplementation::ModuleFile { file, .. }) => {
+                            format!("[.module {}]{}", dotted(&res[file].name), name)
+                        }
+                        Some(TypeImplementation::TypeForwarder(a)) => format!("[{}]{}", dotted(&res[a].name), name),
+                        _ => name,
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl Syntax for CustomTypeModifier {
+    fn syntax(&self, res: &Resolution) -> String {
+        match self {
+            CustomTypeModifier::Optional(t) => format!("modopt({})", t.syntax(res)),
+            CustomTypeModifier::Required(t) => format!("modreq({})", t.syntax(res)),
+        }
+    }
+}
+
+fn modifiers(res: &Resolution, mods: &[CustomTypeModifier]) -> String {
+    let mut buf = String::new();
+    for m in mods {
+        write!(buf, " {}", m.syntax(res)).unwrap();
+    }
+    buf
+}
+
+fn list<T: Syntax>(res: &Resolution, items: &[T]) -> String {
+    items.iter().map(|i| i.syntax(res)).collect::<Vec<_>>().join(", ")
+}
+
+impl<T: Syntax> Syntax for TypeSource<T> {
+    fn syntax(&self, res: &Resolution) -> String {
+        match self {
+            TypeSource::User(u) => u.syntax(res),
+            TypeSource::Generic { base, parameters } => format!("{}<{}>", base.syntax(res), list(res, parameters)),
+        }
+    }
+}
+
+// signatures read from metadata always say whether a type is a value type, but constructed ones may not
+fn value_kind<T>(res: &Resolution, source: &TypeSource<T>) -> ValueKind {
+    let (TypeSource::User(base) | TypeSource::Generic { base, .. }) = source;
+    match base {
+        UserType::Definition(d) => match &res[*d].extends {
+            Some(TypeSource::User(u))
+                if matches!(u.type_name(res).as_str(), "System.ValueType" | "System.Enum")
+                    && res[*d].type_name() != "System.Enum" =>
+            {
+                ValueKind::ValueType
+            }
+            _ => ValueKind::Class,
+        },
+        UserType::Reference(_) => ValueKind::Class,
+    }
+}
+
+fn call_convention<T>(sig: &MethodSignature<StandAloneCallingConvention, T>) -> String {
+    use StandAloneCallingConvention::*;
+
+    let mut buf = String::new();
+    if sig.instance {
+        buf.push_str("instance ");
+    }
+    if sig.explicit_this {
+        buf.push_str("explicit ");
+    }
+    buf.push_str(match sig.calling_convention {
+        DefaultManaged => "",
+        Vararg => "vararg ",
+        Cdecl => "unmanaged cdecl ",
+        Stdcall => "unmanaged stdcall ",
+        Thiscall => "unmanaged thiscall ",
+        Fastcall => "unmanaged fastcall ",
+        DefaultUnmanaged => "unmanaged ",
+    });
+    buf
+}
+
+fn parameters<T: Syntax>(res: &Resolution, fixed: &[Parameter<T>], varargs: Option<&[Parameter<T>]>) -> String {
+    let mut params: Vec<_> = fixed.iter().map(|p| p.syntax(res)).collect();
+    if let Some(v) = varargs {
+        params.push("...".to_string());
+        params.extend(v.iter().map(|p| p.syntax(res)));
+    }
+    params.join(", ")
+}
+
+impl<T: Syntax> Syntax for BaseType<T> {
+    fn syntax(&self, res: &Resolution) -> String {
+        use BaseType::*;
+        match self {
+            Type {
+                value_kind: kind,
+                source,
+            } => format!(
+                "{} {}",
+                match kind.unwrap_or_else(|| value_kind(res, source)) {
+                    ValueKind::Class => "class",
+                    ValueKind::ValueType => "valuetype",
+                },
+                source.syntax(res)
+            ),
+            Boolean => "bool".to_string(),
+            Char => "char".to_string(),
+            Int8 => "int8".to_string(),
+            UInt8 => "uint8".to_string(),
+            Int16 => "int16".to_string(),
+            UInt16 => "uint16".to_string(),
+            Int32 => "int32".to_string(),
+            UInt32 => "uint32".to_string(),
+            Int64 => "int64".to_string(),
+            UInt64 => "uint64".to_string(),
+            Float32 => "float32".to_string(),
+            Float64 => "float64".to_string(),
+            IntPtr => "native int".to_string(),
+            UIntPtr => "native uint".to_string(),
+            Object => "object".to_string(),
+            String => "string".to_string(),
+            Vector(mods, t) => format!("{}{}[]", t.syntax(res), modifiers(res, mods)),
+            Array(t, shape) => {
+                let dimensions: Vec<_> = (0..shape.rank)
+                    .map(|i| match (shape.lower_bounds.get(i), shape.sizes.get(i)) {
+                        (Some(lo), Some(size)) => format!("{}...{}", lo, *lo + *size as isize - 1),
+                        (Some(lo), None) => format!("{}...", lo),
+                        (None, Some(size)) => size.to_string(),
+                        (None, None) => std::string::String::new(),
+                    })
+                    .collect();
+                // a rank 1 array without bounds would read back as a vector
+                let dimensions = if dimensions == [""] {
+                    "...".to_string()
+                } else {
+                    dimensions.join(",")
+                };
+                format!("{}[{}]", t.syntax(res), dimensions)
+            }
+            ValuePointer(mods, t) => format!(
+                "{}{}*",
+                t.as_ref().map_or_else(|| "void".to_string(), |t| t.syntax(res)),
+                modifiers(res, mods)
+            ),
+            FunctionPointer(sig) => format!(
+                "method {}{} *({})",
+                call_convention(sig),
+                sig.return_type.syntax(res),
+                parameters(res, &sig.parameters, sig.varargs.as_deref())
+            ),
+        }
+    }
+}
+
+impl Syntax for MemberType {
+    fn syntax(&self, res: &Resolution) -> String {
+        match self {
+            MemberType::Base(b) => b.syntax(res),
+            MemberType::TypeGeneric(i) => format!("!{}", i),
+        }
+    }
+}
+
+impl Syntax for MethodType {
+    fn syntax(&self, res: &Resolution) -> String {
+        match self {
+            MethodType::Base(b) => b.syntax(res),
+            MethodType::TypeGeneric(i) => format!("!{}", i),
+            MethodType::MethodGeneric(i) => format!("!!{}", i),
+        }
+    }
+}
+
+impl<T: Syntax> Syntax for ParameterType<T> {
+    fn syntax(&self, res: &Resolution) -> String {
+        match self {
+            ParameterType::Value(t) => t.syntax(res),
+            ParameterType::Ref(t) => format!("{}&", t.syntax(res)),
+            ParameterType::TypedReference => "typedref".to_string(),
+        }
+    }
+}
+
+impl<T: Syntax> Syntax for Parameter<T> {
+    fn syntax(&self, res: &Resolution) -> String {
+        format!("{}{}", self.1.syntax(res), modifiers(res, &self.0))
+    }
+}
+
+impl<T: Syntax> Syntax for ReturnType<T> {
+    fn syntax(&self, res: &Resolution) -> String {
+        let t = self.1.as_ref().map_or_else(|| "void".to_string(), |t| t.syntax(res));
+        format!("{}{}", t, modifiers(res, &self.0))
+    }
+}
+
+impl Syntax for LocalVariable {
+    fn syntax(&self, res: &Resolution) -> String {
+        match self {
+            LocalVariable::TypedReference => "typedref".to_string(),
+            LocalVariable::Variable {
+                custom_modifiers,
+                pinned,
+                by_ref,
+                var_type,
+            } => format!(
+                "{}{}{}{}",
+                var_type.syntax(res),
+                if *by_ref { "&" } else { "" },
+                if *pinned { " pinned" } else { "" },
+                modifiers(res, custom_modifiers)
+            ),
+        }
+    }
+}
+
+// where ILAsm expects a type specification rather than a type, plain type names need no class/valuetype keyword
+fn type_spec(res: &Resolution, t: &MethodType) -> String {
+    match t {
+        MethodType::Base(b) => match &**b {
+            BaseType::Type {
+                source: TypeSource::User(u),
+                ..
+            } => u.syntax(res),
+            _ => t.syntax(res),
+        },
+        _ => t.syntax(res),
+    }
+}
+
+fn type_source_spec(res: &Resolution, t: &TypeSource<MemberType>) -> String {
+    match t {
+        TypeSource::User(u) => u.syntax(res),
+        TypeSource::Generic { .. } => format!("class {}", t.syntax(res)),
+    }
+}
+
+fn method_parent(res: &Resolution, parent: TypeIndex) -> String {
+    if is_module_type(parent) {
+        String::new()
+    } else {
+        format!("{}::", UserType::Definition(parent).syntax(res))
+    }
+}
+
+fn user_method(res: &Resolution, method: UserMethod, instantiation: Option<&[MethodType]>) -> String {
+    let (signature, parent, name) = match method {
+        UserMethod::Definition(i) => (&res[i].signature, method_parent(res, i.parent_type), &res[i].name),
+        UserMethod::Reference(i) => {
+            let r = &res[i];
+            let parent = match &r.parent {
+                MethodReferenceParent::Type(t) => format!("{}::", type_spec(res, t)),
+                MethodReferenceParent::Module(m) => format!("[.module {}]::", dotted(&res[*m].name)),
+                MethodReferenceParent::VarargMethod(m) => method_parent(res, m.parent_type),
+            };
+            (&r.signature, parent, &r.name)
+        }
+    };
+
+    let mut buf = String::new();
+    if signature.instance {
+        buf.push_str("instance ");
+    }
+    if signature.explicit_this {
+        buf.push_str("explicit ");
+    }
+    if matches!(signature.calling_convention, CallingConvention::Vararg) {
+        buf.push_str("vararg ");
+    }
+
+    let generics = match (instantiation, signature.calling_convention) {
+        (Some(i), _) => format!("<{}>", list(res, i)),
+        (None, CallingConvention::Generic(n)) => format!("<[{}]>", n),
+        (None, _) => String::new(),
+    };
+
+    write!(
+        buf,
+        "{} {}{}{}({})",
+        signature.return_type.syntax(res),
+        parent,
+        id(name),
+        generics,
+        parameters(res, &signature.parameters, signature.varargs.as_deref())
+    )
+    .unwrap();
+
+    buf
+}
+
+fn method_source(res: &Resolution, method: &MethodSource) -> String {
+    match method {
+        MethodSource::User(u) => user_method(res, *u, None),
+        MethodSource::Generic(g) => user_method(res, g.base, Some(&g.parameters)),
+    }
+}
+
+fn field_source(res: &Resolution, field: FieldSource) -> String {
+    match field {
+        FieldSource::Definition(i) => {
+            let f = &res[i];
+            format!(
+                "{}{}{} {}{}",
+                f.return_type.syntax(res),
+                if f.by_ref { "&" } else { "" },
+                modifiers(res, &f.type_modifiers),
+                method_parent(res, i.parent_type),
+                id(&f.name)
+            )
+        }
+        FieldSource::Reference(i) => {
+            let f = &res[i];
+            let parent = match &f.parent {
+                FieldReferenceParent::Type(t) => format!("{}::", type_spec(res, t)),
+                FieldReferenceParent::Module(m) => format!("[.module {}]::", dotted(&res[*m].name)),
+            };
+            format!(
+                "{}{} {}{}",
+                f.field_type.syntax(res),
+                modifiers(res, &f.custom_modifiers),
+                parent,
+                id(&f.name)
+            )
+        }
+    }
+}
+
+fn generic_parameters<T: Syntax>(res: &Resolution, generics: &[Generic<'_, T>]) -> String {
+    if generics.is_empty() {
+        return String::new();
+    }
+
+    let params: Vec<_> = generics
+        .iter()
+        .map(|g| {
+            let mut buf = String::new();
+            match g.variance {
+                Variance::Invariant => {}
+                Variance::Covariant => buf.push_str("+ "),
+                Variance::Contravariant => buf.push_str("- "),
+            }
+            if g.special_constraint.reference_type {
+                buf.push_str("class ");
+            }
+            if g.special_constraint.value_type {
+                buf.push_str("valuetype ");
+            }
+            if g.special_constraint.has_default_constructor {
+                buf.push_str(".ctor ");
+            }
+            if !g.type_constraints.is_empty() {
+                let constraints: Vec<_> = g
+                    .type_constraints
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{}{}",
+                            c.constraint_type.syntax(res),
+                            modifiers(res, &c.custom_modifiers)
+                        )
+                    })
+                    .collect();
+                write!(buf, "({}) ", constraints.join(", ")).unwrap();
+            }
+            buf.push_str(&id(&g.name));
+            buf
+        })
+        .collect();
+
+    format!("<{}>", params.join(", "))
+}
+
+fn label(index: usize) -> String {
+    format!("L{:04}", index)
+}
+
+#[allow(clippy::too_many_lines)]
+fn instruction(res: &Resolution, instruction: &Instruction) -> String {
+    use Instruction::*;
+    use NumberSign::*;
+
+    let mut prefixes = vec![];
+    let access = |unaligned: &Option<Alignment>, volatile: bool| {
+        let mut flags = vec![];
+        if let Some(a) = unaligned {
+            flags.push(format!("unaligned. {}", *a as u8));
+        }
+        if volatile {
+            flags.push("volatile.".to_string());
+        }
+        flags
+    };
+
+    let sign = |s: &NumberSign, op: &str| match s {
+        Signed => op.to_string(),
+        Unsigned => format!("{}.un", op),
+    };
+    let conversion = |t: &ConversionType| match t {
+        ConversionType::Int8 => "i1",
+        ConversionType::UInt8 => "u1",
+        ConversionType::Int16 => "i2",
+        ConversionType::UInt16 => "u2",
+        ConversionType::Int32 => "i4",
+        ConversionType::UInt32 => "u4",
+        ConversionType::Int64 => "i8",
+        ConversionType::UInt64 => "u8",
+        ConversionType::IntPtr => "i",
+        ConversionType::UIntPtr => "u",
+    };
+    let load_type = |t: &LoadType| match t {
+        LoadType::Int8 => "i1",
+        LoadType::UInt8 => "u1",
+        LoadType::Int16 => "i2",
+        LoadType::UInt16 => "u2",
+        LoadType::Int32 => "i4",
+        LoadType::UInt32 => "u4",
+        LoadType::Int64 => "i8",
+        LoadType::Float32 => "r4",
+        LoadType::Float64 => "r8",
+        LoadType::IntPtr => "i",
+        LoadType::Object => "ref",
+    };
+    let store_type = |t: &StoreType| match t {
+        StoreType::Int8 => "i1",
+        StoreType::Int16 => "i2",
+        StoreType::Int32 => "i4",
+        StoreType::Int64 => "i8",
+        StoreType::Float32 => "r4",
+        StoreType::Float64 => "r8",
+        StoreType::IntPtr => "i",
+        StoreType::Object => "ref",
+    };
+    // the same short forms the writer picks
+    let variable = |op: &str, i: u16, has_macros: bool| {
+        if has_macros && i <= 3 {
+            format!("{}.{}", op, i)
+        } else if u8::try_from(i).is_ok() {
+            format!("{}.s {}", op, i)
+        } else {
+            format!("{} {}", op, i)
+        }
+    };
+    let no_check = |skip_type: bool, skip_range: bool, skip_null: bool| {
+        let mask = u8::from(skip_type) | u8::from(skip_range) << 1 | u8::from(skip_null) << 2;
+        (mask != 0).then(|| format!("no. {:#x}", mask))
+    };
+    let ty = |t: &MethodType| type_spec(res, t);
+    let method = |m: &MethodSource| method_source(res, m);
+    let field = |f: &FieldSource| field_source(res, *f);
+
+    let body = match instruction {
+        Add => "add".to_string(),
+        AddOverflow(s) => sign(s, "add.ovf"),
+        And => "and".to_string(),
+        ArgumentList => "arglist".to_string(),
+        BranchEqual(i) => format!("beq {}", label(*i)),
+        BranchGreaterOrEqual(s, i) => format!("{} {}", sign(s, "bge"), label(*i)),
+        BranchGreater(s, i) => format!("{} {}", sign(s, "bgt"), label(*i)),
+        BranchLessOrEqual(s, i) => format!("{} {}", sign(s, "ble"), label(*i)),
+        BranchLess(s, i) => format!("{} {}", sign(s, "blt"), label(*i)),
+        BranchNotEqual(i) => format!("bne.un {}", label(*i)),
+        Branch(i) => format!("br {}", label(*i)),
+        Breakpoint => "break".to_string(),
+        BranchFalsy(i) => format!("brfalse {}", label(*i)),
+        BranchTruthy(i) => format!("brtrue {}", label(*i)),
+        Call { tail_call, param0 } => {
+            if *tail_call {
+                prefixes.push("tail.".to_string());
+            }
+            format!("call {}", method(param0))
+        }
+        CallConstrained(c, m) => {
+            prefixes.push(format!("constrained. {}", ty(c)));
+            format!("call {}", method(m))
+        }
+        CallIndirect { tail_call, param0 } => {
+            if *tail_call {
+                prefixes.push("tail.".to_string());
+            }
+            format!(
+                "calli {}{}({})",
+                call_convention(param0),
+                param0.return_type.syntax(res),
+                parameters(res, &param0.parameters, param0.varargs.as_deref())
+            )
+        }
+        CompareEqual => "ceq".to_string(),
+        CompareGreater(s) => sign(s, "cgt"),
+        CheckFinite => "ckfinite".to_string(),
+        CompareLess(s) => sign(s, "clt"),
+        Convert(c) => format!("conv.{}", conversion(c)),
+        ConvertOverflow(c, s) => sign(s, &format!("conv.ovf.{}", conversion(c))),
+        ConvertFloat32 => "conv.r4".to_string(),
+        ConvertFloat64 => "conv.r8".to_string(),
+        ConvertUnsignedToFloat => "conv.r.un".to_string(),
+        CopyMemoryBlock { unaligned, volatile } => {
+            prefixes.extend(access(unaligned, *volatile));
+            "cpblk".to_string()
+        }
+        Divide(s) => sign(s, "div"),
+        Duplicate => "dup".to_string(),
+        EndFilter => "endfilter".to_string(),
+        EndFinally => "endfinally".to_string(),
+        InitializeMemoryBlock { unaligned, volatile } => {
+            prefixes.extend(access(unaligned, *volatile));
+            "initblk".to_string()
+        }
+        Jump(m) => format!("jmp {}", method(m)),
+        LoadArgument(i) => variable("ldarg", *i, true),
+        LoadArgumentAddress(i) => variable("ldarga", *i, false),
+        LoadConstantInt32(-1) => "ldc.i4.m1".to_string(),
+        LoadConstantInt32(i @ 0..=8) => format!("ldc.i4.{}", i),
+        LoadConstantInt32(i @ -128..=127) => format!("ldc.i4.s {}", i),
+        LoadConstantInt32(i) => format!("ldc.i4 {}", i),
+        LoadConstantInt64(i) => format!("ldc.i8 {}", i),
+        // a bare integer operand would be converted to a float, so bit patterns need the explicit form
+        LoadConstantFloat32(v) => match float32(*v) {
+            bits if bits.starts_with("0x") => format!("ldc.r4 float32({})", bits),
+            f => format!("ldc.r4 {}", f),
+        },
+        LoadConstantFloat64(v) => match float64(*v) {
+            bits if bits.starts_with("0x") => format!("ldc.r8 float64({})", bits),
+            f => format!("ldc.r8 {}", f),
+        },
+        LoadMethodPointer(m) => format!("ldftn {}", method(m)),
+        LoadIndirect {
+            unaligned,
+            volatile,
+            param0,
+        } => {
+            prefixes.extend(access(unaligned, *volatile));
+            format!("ldind.{}", load_type(param0))
+        }
+        LoadLocal(i) => variable("ldloc", *i, true),
+        LoadLocalAddress(i) => variable("ldloca", *i, false),
+        LoadNull => "ldnull".to_string(),
+        Leave(i) => format!("leave {}", label(*i)),
+        LocalMemoryAllocate => "localloc".to_string(),
+        Multiply => "mul".to_string(),
+        MultiplyOverflow(s) => sign(s, "mul.ovf"),
+        Negate => "neg".to_string(),
+        NoOperation => "nop".to_string(),
+        Not => "not".to_string(),
+        Or => "or".to_string(),
+        Pop => "pop".to_string(),
+        Remainder(s) => sign(s, "rem"),
+        Return => "ret".to_string(),
+        ShiftLeft => "shl".to_string(),
+        ShiftRight(s) => sign(s, "shr"),
+        StoreArgument(i) => variable("starg", *i, false),
+        StoreIndirect {
+            unaligned,
+            volatile,
+            param0,
+        } => {
+            prefixes.extend(access(unaligned, *volatile));
+            format!("stind.{}", store_type(param0))
+        }
+        StoreLocal(i) => variable("stloc", *i, true),
+        Subtract => "sub".to_string(),
+        SubtractOverflow(s) => sign(s, "sub.ovf"),
+        Switch(targets) => format!(
+            "switch ({})",
+            targets.iter().map(|i| label(*i)).collect::<Vec<_>>().join(", ")
+        ),
+        Xor => "xor".to_string(),
+
+        BoxValue(t) => format!("box {}", ty(t)),
+        CallVirtual {
+            skip_null_check,
+            param0,
+        } => {
+            prefixes.extend(no_check(false, false, *skip_null_check));
+            format!("callvirt {}", method(param0))
+        }
+        CallVirtualConstrained(c, m) => {
+            prefixes.push(format!("constrained. {}", ty(c)));
+            format!("callvirt {}", method(m))
+        }
+        CallVirtualTail(m) => {
+            prefixes.push("tail.".to_string());
+            format!("callvirt {}", method(m))
+        }
+        CastClass {
+            skip_type_check,
+            param0,
+        } => {
+            prefixes.extend(no_check(*skip_type_check, false, false));
+            format!("castclass {}", ty(param0))
+        }
+        CopyObject(t) => format!("cpobj {}", ty(t)),
+        InitializeForObject(t) => format!("initobj {}", ty(t)),
+        IsInstance(t) => format!("isinst {}", ty(t)),
+        LoadElement {
+            skip_range_check,
+            skip_null_check,
+            param0,
+        } => {
+            prefixes.extend(no_check(false, *skip_range_check, *skip_null_check));
+            format!("ldelem {}", ty(param0))
+        }
+        LoadElementPrimitive {
+            skip_range_check,
+            skip_null_check,
+            param0,
+        } => {
+            prefixes.extend(no_check(false, *skip_range_check, *skip_null_check));
+            format!("ldelem.{}", load_type(param0))
+        }
+        LoadElementAddress {
+            skip_type_check,
+            skip_range_check,
+            skip_null_check,
+            param0,
+        } => {
+            prefixes.extend(no_check(*skip_type_check, *skip_range_check, *skip_null_check));
+            format!("ldelema {}", ty(param0))
+        }
+        LoadElementAddressReadonly(t) => {
+            prefixes.push("readonly.".to_string());
+            format!("ldelema {}", ty(t))
+        }
+        LoadField {
+            unaligned,
+            volatile,
+            param0,
+        } => {
+            prefixes.extend(access(unaligned, *volatile));
+            format!("ldfld {}", field(param0))
+        }
+        LoadFieldAddress(f) => format!("ldflda {}", field(f)),
+        LoadFieldSkipNullCheck(f) => {
+            prefixes.extend(no_check(false, false, true));
+            format!("ldfld {}", field(f))
+        }
+        LoadLength => "ldlen".to_string(),
+        LoadObject {
+            unaligned,
+            volatile,
+            param0,
+        } => {
+            prefixes.extend(access(unaligned, *volatile));
+            format!("ldobj {}", ty(param0))
+        }
+        LoadStaticField { volatile, param0 } => {
+            prefixes.extend(access(&None, *volatile));
+            format!("ldsfld {}", field(param0))
+        }
+        LoadStaticFieldAddress(f) => format!("ldsflda {}", field(f)),
+        LoadString(s) => format!("ldstr {}", string(s)),
+        LoadTokenField(f) => format!("ldtoken field {}", field(f)),
+        LoadTokenMethod(m) => format!("ldtoken method {}", method(m)),
+        LoadTokenType(t) => format!("ldtoken {}", ty(t)),
+        LoadVirtualMethodPointer {
+            skip_null_check,
+            param0,
+        } => {
+            prefixes.extend(no_check(false, false, *skip_null_check));
+            format!("ldvirtftn {}", method(param0))
+        }
+        MakeTypedReference(t) => format!("mkrefany {}", ty(t)),
+        NewArray(t) => format!("newarr {}", ty(t)),
+        NewObject(m) => format!("newobj {}", user_method(res, *m, None)),
+        ReadTypedReferenceType => "refanytype".to_string(),
+        ReadTypedReferenceValue(t) => format!("refanyval {}", ty(t)),
+        Rethrow => "rethrow".to_string(),
+        Sizeof(t) => format!("sizeof {}", ty(t)),
+        StoreElement {
+            skip_type_check,
+            skip_range_check,
+            skip_null_check,
+            param0,
+        } => {
+            prefixes.extend(no_check(*skip_type_check, *skip_range_check, *skip_null_check));
+            format!("stelem {}", ty(param0))
+        }
+        StoreElementPrimitive {
+            skip_type_check,
+            skip_range_check,
+            skip_null_check,
+            param0,
+        } => {
+            prefixes.extend(no_check(*skip_type_check, *skip_range_check, *skip_null_check));
+            format!("stelem.{}", store_type(param0))
+        }
+        StoreField {
+            unaligned,
+            volatile,
+            param0,
+        } => {
+            prefixes.extend(access(unaligned, *volatile));
+            format!("stfld {}", field(param0))
+        }
+        StoreFieldSkipNullCheck(f) => {
+            prefixes.extend(no_check(false, false, true));
+            format!("stfld {}", field(f))
+        }
+        StoreObject {
+            unaligned,
+            volatile,
+            param0,
+        } => {
+            prefixes.extend(access(unaligned, *volatile));
+            format!("stobj {}", ty(param0))
+        }
+        StoreStaticField { volatile, param0 } => {
+            prefixes.extend(access(&None, *volatile));
+            format!("stsfld {}", field(param0))
+        }
+        Throw => "throw".to_string(),
+        UnboxIntoAddress {
+            skip_type_check,
+            param0,
+        } => {
+            prefixes.extend(no_check(*skip_type_check, false, false));
+            format!("unbox {}", ty(param0))
+        }
+        UnboxIntoValue(t) => format!("unbox.any {}", ty(t)),
+    };
+
+    prefixes.push(body);
+    prefixes.join(" ")
+}
+
+fn security_action(action: u16) -> String {
+    match action {
+        1 => "request",
+        2 => "demand",
+        3 => "assert",
+        4 => "deny",
+        5 => "permitonly",
+        6 => "linkcheck",
+        7 => "inheritcheck",
+        8 => "reqmin",
+        9 => "reqopt",
+        10 => "reqrefuse",
+        11 => "prejitgrant",
+        12 => "prejitdeny",
+        13 => "noncasdemand",
+        14 => "noncaslinkdemand",
+        15 => "noncasinheritance",
+        other => return format!("{:#x}", other),
+    }
+    .to_string()
+}
+
+macro_rules! emit {
+    ($d:expr, $($arg:tt)*) => {
+        $d.line(format_args!($($arg)*))
+    };
+}
+
+struct Disassembler<'r, 'a> {
+    res: &'r Resolution<'a>,
+    out: String,
+    indent: usize,
+    // field initial values, written as .data declarations at the end
+    data: Vec<&'r [u8]>,
+}
+
+impl<'r, 'a> Disassembler<'r, 'a> {
+    fn line(&mut self, args: std::fmt::Arguments) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.write_fmt(args).unwrap();
+        self.out.push('\n');
+    }
+
+    fn open(&mut self) {
+        emit!(self, "{{");
+        self.indent += 1;
+    }
+
+    fn close(&mut self) {
+        self.indent -= 1;
+        emit!(self, "}}");
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute]) {
+        for a in attributes {
+            let constructor = user_method(self.res, a.constructor, None);
+            match &a.value {
+                Some(v) => emit!(self, ".custom {} = {}", constructor, bytes(v)),
+                None => emit!(self, ".custom {}", constructor),
+            }
+        }
+    }
+
+    fn security(&mut self, security: Option<&SecurityDeclaration>) {
+        if let Some(s) = security {
+            emit!(
+                self,
+                ".permissionset {} = {}",
+                security_action(s.action),
+                bytes(&s.value)
+            );
+            self.attributes(&s.attributes);
+        }
+    }
+
+    fn generic_attributes<T>(&mut self, generics: &[Generic<'_, T>]) {
+        for (i, g) in generics.iter().enumerate() {
+            if !g.attributes.is_empty() {
+                emit!(self, ".param type [{}]", i + 1);
+                self.attributes(&g.attributes);
+            }
+        }
+    }
+
+    fn assembly_references(&mut self) {
+        for a in &self.res.assembly_references {
+            emit!(self, ".assembly extern {}", dotted(&a.name));
+            self.open();
+            self.attributes(&a.attributes);
+            if let Some(key) = &a.public_key_or_token {
+                let directive = if a.has_full_public_key {
+                    ".publickey"
+                } else {
+                    ".publickeytoken"
+                };
+                emit!(self, "{} = {}", directive, bytes(key));
+            }
+            if let Some(hash) = &a.hash_value {
+                emit!(self, ".hash = {}", bytes(hash));
+            }
+            let v = a.version;
+            emit!(self, ".ver {}:{}:{}:{}", v.major, v.minor, v.build, v.revision);
+            if let Some(c) = &a.culture {
+                emit!(self, ".locale {}", string(&c.encode_utf16().collect::<Vec<_>>()));
+            }
+            self.close();
+        }
+    }
+
+    fn assembly(&mut self) {
+        let Some(a) = &self.res.assembly else { return };
+
+        emit!(
+            self,
+            ".assembly {}{}",
+            if a.flags.retargetable { "retargetable " } else { "" },
+            dotted(&a.name)
+        );
+        self.open();
+        self.attributes(&a.attributes);
+        self.security(a.security.as_ref());
+        if let Some(key) = &a.public_key {
+            emit!(self, ".publickey = {}", bytes(key));
+        }
+        match a.hash_algorithm {
+            HashAlgorithm::None => {}
+            HashAlgorithm::ReservedMD5 => emit!(self, ".hash algorithm 0x00008003"),
+            HashAlgorithm::SHA1 => emit!(self, ".hash algorithm 0x00008004"),
+        }
+        let v = a.version;
+        emit!(self, ".ver {}:{}:{}:{}", v.major, v.minor, v.build, v.revision);
+        if let Some(c) = &a.culture {
+            emit!(self, ".locale {}", string(&c.encode_utf16().collect::<Vec<_>>()));
+        }
+        self.close();
+    }
+
+    fn module(&mut self) {
+        let res = self.res;
+
+        emit!(self, ".module {}", dotted(&res.module.name));
+        let mvid: Vec<_> = res.module.mvid.iter().map(|b| format!("{:02x}", b)).collect();
+        emit!(self, "// MVID: {}", mvid.concat());
+        self.attributes(&res.module.attributes);
+
+        for m in &res.module_references {
+            emit!(self, ".module extern {}", dotted(&m.name));
+        }
+
+        for f in &res.files {
+            emit!(
+                self,
+                ".file {}{} .hash = {}",
+                if f.has_metadata { "" } else { "nometadata " },
+                dotted(&f.name),
+                bytes(&f.hash_value)
+            );
+            self.attributes(&f.attributes);
+        }
+    }
+
+    fn exported_types(&mut self) {
+        let res = self.res;
+
+        for e in &res.exported_types {
+            let visibility = match e.flags.accessibility {
+                types::Accessibility::NotPublic => "private".to_string(),
+                types::Accessibility::Public => "public".to_string(),
+                types::Accessibility::Nested(a) => format!("nested {}", member_access(a.into())),
+            };
+            let name = match e.implementation {
+                TypeImplementation::Nested(_) => id(&e.name),
+                _ => type_name(e.namespace.as_deref(), &e.name),
+            };
+            let forwarder = if matches!(e.implementation, TypeImplementation::TypeForwarder(_)) {
+                "forwarder "
+            } else {
+                ""
+            };
+
+            emit!(self, ".class extern {}{} {}", forwarder, visibility, name);
+            self.open();
+            match e.implementation {
+                TypeImplementation::Nested(enc) => {
+                    let enc = &res[enc];
+                    emit!(self, ".class extern {}", type_name(enc.namespace.as_deref(), &enc.name));
+                }
+                TypeImplementation::ModuleFile { file, type_def } => {
+                    emit!(self, ".file {}", dotted(&res[file].name));
+                    emit!(self, ".class {:#010x}", 0x0200_0001 + type_def.0);
+                }
+                TypeImplementation::TypeForwarder(a) => emit!(self, ".assembly extern {}", dotted(&res[a].name)),
+            }
+            self.attributes(&e.attributes);
+            self.close();
+        }
+    }
+
+    fn manifest_resources(&mut self) {
+        let res = self.res;
+
+        for r in &res.manifest_resources {
+            let visibility = match r.visibility {
+                Visibility::Public => "public",
+                Visibility::Private => "private",
+            };
+            emit!(self, ".mresource {} {}", visibility, dotted(&r.name));
+            self.open();
+            match &r.implementation {
+                Implementation::File { location, offset } => {
+                    emit!(self, ".file {} at {:#x}", dotted(&res[*location].name), offset);
+                }
+                Implementation::Assembly { location, .. } => {
+                    emit!(self, ".assembly extern {}", dotted(&res[*location].name));
+                }
+                Implementation::CurrentFile(data) => {
+                    emit!(self, "// embedded, {} bytes", data.len());
+                }
+            }
+            self.attributes(&r.attributes);
+            self.close();
+        }
+    }
+
+    fn field(&mut self, field: &'r Field<'a>) {
+        let res = self.res;
+
+        let mut buf = String::from(".field ");
+        if let Some(o) = field.offset {
+            write!(buf, "[{}] ", o).unwrap();
+        }
+        write!(buf, "{} ", member_access(field.accessibility)).unwrap();
+        if field.static_member {
+            buf.push_str("static ");
+        }
+        if field.init_only {
+            buf.push_str("initonly ");
+        }
+        if field.literal {
+            buf.push_str("literal ");
+        }
+        if field.not_serialized {
+            buf.push_str("notserialized ");
+        }
+        if field.special_name {
+            buf.push_str("specialname ");
+        }
+        if field.runtime_special_name {
+            buf.push_str("rtspecialname ");
+        }
+        if let Some(m) = &field.marshal {
+            write!(buf, "{} ", marshal(m)).unwrap();
+        }
+        write!(
+            buf,
+            "{}{}{} {}",
+            field.return_type.syntax(res),
+            if field.by_ref { "&" } else { "" },
+            modifiers(res, &field.type_modifiers),
+            id(&field.name)
+        )
+        .unwrap();
+        if let Some(data) = &field.initial_value {
+            write!(buf, " at D_{:04}", self.data.len()).unwrap();
+            self.data.push(data);
+        }
+        if let Some(c) = &field.default {
+            write!(buf, " = {}", constant(c)).unwrap();
+        }
+
+        emit!(self, "{}", buf);
+        self.attributes(&field.attributes);
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn method_header(&self, method: &Method) -> String {
+        let res = self.res;
+
+        let mut buf = format!(".method {} ", member_access(method.accessibility));
+        if method.sealed {
+            buf.push_str("final ");
+        }
+        if method.virtual_member {
+            buf.push_str("virtual ");
+        }
+        if method.hide_by_sig {
+            buf.push_str("hidebysig ");
+        }
+        if matches!(method.vtable_layout, VtableLayout::NewSlot) {
+            buf.push_str("newslot ");
+        }
+        if method.strict {
+            buf.push_str("strict ");
+        }
+        if method.abstract_member {
+            buf.push_str("abstract ");
+        }
+        if method.special_name {
+            buf.push_str("specialname ");
+        }
+        if method.runtime_special_name {
+            buf.push_str("rtspecialname ");
+        }
+        if let Some(p) = &method.pinvoke {
+            write!(
+                buf,
+                "pinvokeimpl({} as {}",
+                string(&res[p.import_scope].name.encode_utf16().collect::<Vec<_>>()),
+                string(&p.import_name.encode_utf16().collect::<Vec<_>>())
+            )
+            .unwrap();
+            if p.no_mangle {
+                buf.push_str(" nomangle");
+            }
+            buf.push_str(match p.character_set {
+                CharacterSet::NotSpecified => "",
+                CharacterSet::Ansi => " ansi",
+                CharacterSet::Unicode => " unicode",
+                CharacterSet::Auto => " autochar",
+            });
+            if p.supports_last_error {
+                buf.push_str(" lasterr");
+            }
+            buf.push_str(match p.calling_convention {
+                UnmanagedCallingConvention::Platformapi => " winapi",
+                UnmanagedCallingConvention::Cdecl => " cdecl",
+                UnmanagedCallingConvention::Stdcall => " stdcall",
+                UnmanagedCallingConvention::Thiscall => " thiscall",
+                UnmanagedCallingConvention::Fastcall => " fastcall",
+            });
+            buf.push_str(") ");
+        }
+        if method.is_static() {
+            buf.push_str("static ");
+        }
+        if method.require_sec_object {
+            buf.push_str("reqsecobj ");
+        }
+
+        let sig = &method.signature;
+        if sig.instance {
+            buf.push_str("instance ");
+        }
+        if sig.explicit_this {
+            buf.push_str("explicit ");
+        }
+        if matches!(sig.calling_convention, CallingConvention::Vararg) {
+            buf.push_str("vararg ");
+        }
+
+        buf.push_str(&sig.return_type.syntax(res));
+        if let Some(m) = method.return_type_metadata.as_ref().and_then(|m| m.marshal.as_ref()) {
+            write!(buf, " {}", marshal(m)).unwrap();
+        }
+
+        let params: Vec<_> = sig
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let mut param = String::new();
+                let meta = method.parameter_metadata.get(i).and_then(Option::as_ref);
+                if let Some(meta) = meta {
+                    if meta.is_in {
+                        param.push_str("[in] ");
+                    }
+                    if meta.is_out {
+                        param.push_str("[out] ");
+                    }
+                    if meta.optional {
+                        param.push_str("[opt] ");
+                    }
+                }
+                param.push_str(&p.syntax(res));
+                if let Some(meta) = meta {
+                    if let Some(m) = &meta.marshal {
+                        write!(param, " {}", marshal(m)).unwrap();
+                    }
+                    if let Some(name) = &meta.name {
+                        write!(param, " {}", id(name)).unwrap();
+                    }
+                }
+                param
+            })
+            .collect();
+
+        write!(
+            buf,
+            " {}{}({}) ",
+            id(&method.name),
+            generic_parameters(res, &method.generic_parameters),
+            params.join(", ")
+        )
+        .unwrap();
+
+        buf.push_str(match method.body_format {
+            BodyFormat::IL => "cil",
+            BodyFormat::Native => "native",
+            BodyFormat::Runtime => "runtime",
+        });
+        buf.push_str(match method.body_management {
+            BodyManagement::Managed => " managed",
+            BodyManagement::Unmanaged => " unmanaged",
+        });
+        if method.forward_ref {
+            buf.push_str(" forwardref");
+        }
+        if method.preserve_sig {
+            buf.push_str(" preservesig");
+        }
+        if method.internal_call {
+            buf.push_str(" internalcall");
+        }
+        if method.synchronized {
+            buf.push_str(" synchronized");
+        }
+        if method.no_inlining {
+            buf.push_str(" noinlining");
+        }
+        if method.no_optimization {
+            buf.push_str(" nooptimization");
+        }
+
+        buf
+    }
+
+    fn parameter_metadata(&mut self, index: usize, meta: Option<&ParameterMetadata>) {
+        let Some(meta) = meta else { return };
+        if meta.attributes.is_empty() && meta.default.is_none() {
+            return;
+        }
+
+        match &meta.default {
+            Some(c) => emit!(self, ".param [{}] = {}", index, constant(c)),
+            None => emit!(self, ".param [{}]", index),
+        }
+        self.attributes(&meta.attributes);
+    }
+
+    fn method(&mut self, index: MethodIndex, method: &Method) {
+        let header = self.method_header(method);
+        emit!(self, "{}", header);
+        self.open();
+
+        if matches!(self.res.entry_point, Some(EntryPoint::Method(e)) if e == index) {
+            emit!(self, ".entrypoint");
+        }
+        self.attributes(&method.attributes);
+        self.security(method.security.as_ref());
+        self.parameter_metadata(0, method.return_type_metadata.as_ref());
+        for (i, meta) in method.parameter_metadata.iter().enumerate() {
+            self.parameter_metadata(i + 1, meta.as_ref());
+        }
+        self.generic_attributes(&method.generic_parameters);
+
+        if let Some(body) = &method.body {
+            self.body(body);
+        }
+
+        self.close();
+    }
+
+    fn body(&mut self, body: &body::Method) {
+        let res = self.res;
+
+        emit!(self, ".maxstack {}", body.header.maximum_stack_size);
+        if !body.header.local_variables.is_empty() {
+            let locals: Vec<_> = body
+                .header
+                .local_variables
+                .iter()
+                .enumerate()
+                .map(|(i, l)| format!("{} V_{}", l.syntax(res), i))
+                .collect();
+            emit!(
+                self,
+                ".locals {}({})",
+                if body.header.initialize_locals { "init " } else { "" },
+                locals.join(", ")
+            );
+        }
+
+        let handlers: Vec<_> = body
+            .data_sections
+            .iter()
+            .filter_map(|s| match s {
+                DataSection::ExceptionHandlers(h) => Some(h),
+                DataSection::Unrecognized { .. } => None,
+            })
+            .flatten()
+            .collect();
+
+        let mut targets = BTreeSet::new();
+        for i in &body.instructions {
+            match flow(i) {
+                Flow::Branch(t) | Flow::Leave(t) => {
+                    targets.insert(t);
+                }
+                Flow::ConditionalBranch(ts) => targets.extend(ts),
+                Flow::Next | Flow::End => {}
+            }
+        }
+        for h in &handlers {
+            targets.extend([
+                h.try_offset,
+                h.try_offset + h.try_length,
+                h.handler_offset,
+                h.handler_offset + h.handler_length,
+            ]);
+            if let ExceptionKind::Filter { offset } = h.kind {
+                targets.insert(offset);
+            }
+        }
+
+        for (idx, i) in body.instructions.iter().enumerate() {
+            let text = instruction(res, i);
+            if targets.contains(&idx) {
+                emit!(self, "{}: {}", label(idx), text);
+            } else {
+                emit!(self, "       {}", text);
+            }
+        }
+        // labels past the last instruction mark the ends of protected regions
+        for t in targets.range(body.instructions.len()..) {
+            emit!(self, "{}:", label(*t));
+        }
+
+        for h in handlers {
+            let protected = format!(".try {} to {}", label(h.try_offset), label(h.try_offset + h.try_length));
+            let handler = format!(
+                "handler {} to {}",
+                label(h.handler_offset),
+                label(h.handler_offset + h.handler_length)
+            );
+            match &h.kind {
+                ExceptionKind::TypedException(t) => {
+                    emit!(self, "{} catch {} {}", protected, type_spec(res, t), handler);
+                }
+                ExceptionKind::Filter { offset } => emit!(self, "{} filter {} {}", protected, label(*offset), handler),
+                ExceptionKind::Finally => emit!(self, "{} finally {}", protected, handler),
+                ExceptionKind::Fault => emit!(self, "{} fault {}", protected, handler),
+            }
+        }
+    }
+
+    fn accessor(&mut self, directive: &str, parent: TypeIndex, member: MethodMemberIndex) {
+        let method = user_method(
+            self.res,
+            UserMethod::Definition(MethodIndex {
+                parent_type: parent,
+                member,
+            }),
+            None,
+        );
+        emit!(self, "{} {}", directive, method);
+    }
+
+    fn property(&mut self, parent: TypeIndex, index: usize, property: &Property) {
+        let res = self.res;
+
+        let mut buf = String::from(".property ");
+        if property.special_name {
+            buf.push_str("specialname ");
+        }
+        if property.runtime_special_name {
+            buf.push_str("rtspecialname ");
+        }
+        if !property.static_member {
+            buf.push_str("instance ");
+        }
+        write!(
+            buf,
+            "{} {}({})",
+            property.property_type.syntax(res),
+            id(&property.name),
+            list(res, &property.parameters)
+        )
+        .unwrap();
+        if let Some(c) = &property.default {
+            write!(buf, " = {}", constant(c)).unwrap();
+        }
+
+        emit!(self, "{}", buf);
+        self.open();
+        self.attributes(&property.attributes);
+        if property.getter.is_some() {
+            self.accessor(".get", parent, MethodMemberIndex::PropertyGetter(index));
+        }
+        if property.setter.is_some() {
+            self.accessor(".set", parent, MethodMemberIndex::PropertySetter(index));
+        }
+        for other in 0..property.other.len() {
+            self.accessor(
+                ".other",
+                parent,
+                MethodMemberIndex::PropertyOther { property: index, other },
+            );
+        }
+        self.close();
+    }
+
+    fn event(&mut self, parent: TypeIndex, index: usize, event: &Event) {
+        let mut buf = String::from(".event ");
+        if event.special_name {
+            buf.push_str("specialname ");
+        }
+        if event.runtime_special_name {
+            buf.push_str("rtspecialname ");
+        }
+        write!(
+            buf,
+            "{} {}",
+            type_spec(self.res, &event.delegate_type.clone().into()),
+            id(&event.name)
+        )
+        .unwrap();
+
+        emit!(self, "{}", buf);
+        self.open();
+        self.attributes(&event.attributes);
+        self.accessor(".addon", parent, MethodMemberIndex::EventAdd(index));
+        self.accessor(".removeon", parent, MethodMemberIndex::EventRemove(index));
+        if event.raise_event.is_some() {
+            self.accessor(".fire", parent, MethodMemberIndex::EventRaise(index));
+        }
+        for other in 0..event.other.len() {
+            self.accessor(".other", parent, MethodMemberIndex::EventOther { event: index, other });
+        }
+        self.close();
+    }
+
+    // all methods of a type, including property and event accessors
+    fn methods(&mut self, parent: TypeIndex) {
+        let res = self.res;
+        let t = &res[parent];
+
+        let mut methods: Vec<_> = res.enumerate_methods(parent).collect();
+        for (i, p) in t.properties.iter().enumerate() {
+            let accessors = [
+                p.getter.as_ref().map(|m| (MethodMemberIndex::PropertyGetter(i), m)),
+                p.setter.as_ref().map(|m| (MethodMemberIndex::PropertySetter(i), m)),
+            ];
+            let others = p
+                .other
+                .iter()
+                .enumerate()
+                .map(|(other, m)| (MethodMemberIndex::PropertyOther { property: i, other }, m));
+            methods.extend(accessors.into_iter().flatten().chain(others).map(|(member, m)| {
+                (
+                    MethodIndex {
+                        parent_type: parent,
+                        member,
+                    },
+                    m,
+                )
+            }));
+        }
+        for (i, e) in t.events.iter().enumerate() {
+            let accessors = [
+                Some((MethodMemberIndex::EventAdd(i), &e.add_listener)),
+                Some((MethodMemberIndex::EventRemove(i), &e.remove_listener)),
+                e.raise_event.as_ref().map(|m| (MethodMemberIndex::EventRaise(i), m)),
+            ];
+            let others = e
+                .other
+                .iter()
+                .enumerate()
+                .map(|(other, m)| (MethodMemberIndex::EventOther { event: i, other }, m));
+            methods.extend(accessors.into_iter().flatten().chain(others).map(|(member, m)| {
+                (
+                    MethodIndex {
+                        parent_type: parent,
+                        member,
+                    },
+                    m,
+                )
+            }));
+        }
+
+        for (idx, m) in methods {
+            self.method(idx, m);
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn type_definition(&mut self, index: TypeIndex, nested: &HashMap<TypeIndex, Vec<TypeIndex>>) {
+        let res = self.res;
+        let t = &res[index];
+        let flags = &t.flags;
+
+        let mut buf = String::from(".class ");
+        if matches!(flags.kind, Kind::Interface) {
+            buf.push_str("interface ");
+        }
+        match flags.accessibility {
+            types::Accessibility::NotPublic => buf.push_str("private "),
+            types::Accessibility::Public => buf.push_str("public "),
+            types::Accessibility::Nested(a) => write!(buf, "nested {} ", member_access(a.into())).unwrap(),
+        }
+        buf.push_str(match flags.layout {
+            Layout::Automatic => "auto ",
+            Layout::Sequential(_) => "sequential ",
+            Layout::Explicit(_) => "explicit ",
+        });
+        buf.push_str(match flags.string_formatting {
+            StringFormatting::ANSI => "ansi ",
+            StringFormatting::Unicode => "unicode ",
+            StringFormatting::Automatic => "autochar ",
+            StringFormatting::Custom(_) => "",
+        });
+        if flags.abstract_type {
+            buf.push_str("abstract ");
+        }
+        if flags.sealed {
+            buf.push_str("sealed ");
+        }
+        if flags.special_name {
+            buf.push_str("specialname ");
+        }
+        if flags.imported {
+            buf.push_str("import ");
+        }
+        if flags.serializable {
+            buf.push_str("serializable ");
+        }
+        if flags.before_field_init {
+            buf.push_str("beforefieldinit ");
+        }
+        if flags.runtime_special_name {
+            buf.push_str("rtspecialname ");
+        }
+        if t.encloser.is_some() {
+            buf.push_str(&id(&t.name));
+        } else {
+            buf.push_str(&type_name(t.namespace.as_deref(), &t.name));
+        }
+        buf.push_str(&generic_parameters(res, &t.generic_parameters));
+
+        emit!(self, "{}", buf);
+        self.indent += 1;
+        if let Some(e) = &t.extends {
+            emit!(self, "extends {}", type_source_spec(res, e));
+        }
+        if !t.implements.is_empty() {
+            let interfaces: Vec<_> = t.implements.iter().map(|(_, i)| type_source_spec(res, i)).collect();
+            emit!(self, "implements {}", interfaces.join(", "));
+        }
+        self.indent -= 1;
+        self.open();
+
+        self.attributes(&t.attributes);
+        self.security(t.security.as_ref());
+        self.generic_attributes(&t.generic_parameters);
+        for (i, g) in t.generic_parameters.iter().enumerate() {
+            for c in g.type_constraints.iter().filter(|c| !c.attributes.is_empty()) {
+                emit!(self, ".param constraint [{}], {}", i + 1, c.constraint_type.syntax(res));
+                self.attributes(&c.attributes);
+            }
+        }
+        for (attributes, i) in &t.implements {
+            if !attributes.is_empty() {
+                emit!(self, ".interfaceimpl type {}", type_source_spec(res, i));
+                self.attributes(attributes);
+            }
+        }
+        match flags.layout {
+            Layout::Sequential(Some(l)) => {
+                emit!(self, ".pack {}", l.packing_size);
+                emit!(self, ".size {}", l.class_size);
+            }
+            Layout::Explicit(Some(l)) => emit!(self, ".size {}", l.class_size),
+            _ => {}
+        }
+
+        for n in nested.get(&index).into_iter().flatten() {
+            self.type_definition(*n, nested);
+        }
+
+        for f in &t.fields {
+            self.field(f);
+        }
+        self.methods(index);
+        for (i, p) in t.properties.iter().enumerate() {
+            self.property(index, i, p);
+        }
+        for (i, e) in t.events.iter().enumerate() {
+            self.event(index, i, e);
+        }
+        for o in &t.overrides {
+            emit!(
+                self,
+                ".override method {} with method {}",
+                user_method(res, o.declaration, None),
+                user_method(res, o.implementation, None)
+            );
+        }
+
+        self.close();
+    }
+}
+
+/// Renders a whole [`Resolution`] as IL assembly source, in the style of `ildasm`.
+///
+/// The output declares the assembly and its references, the module, exported types, manifest resources,
+/// and every type definition with its custom attributes, fields, methods, properties and events.
+/// Global fields and methods of the `<Module>` type are written at the top level.
+///
+/// Method bodies are written with one instruction per line. Only instructions that are branched to or that
+/// bound an exception handling region get a label, named after the instruction's index (e.g. `L0012`),
+/// so that they can be matched up with the indices in [`body::Method::instructions`].
+/// Exception handlers are written as label-based `.try` directives after the instructions.
+///
+/// The output is meant to be assembled again by `ilasm`. Metadata that the syntax cannot express, such as custom
+/// attributes on type or member references, is left out, and the bytes of embedded resources are not written.
+pub fn disassemble(res: &Resolution) -> String {
+    let mut d = Disassembler {
+        res,
+        out: String::new(),
+        indent: 0,
+        data: vec![],
+    };
+
+    d.assembly_references();
+    d.assembly();
+    d.module();
+    d.exported_types();
+    d.manifest_resources();
+
+    let mut nested: HashMap<_, Vec<_>> = HashMap::new();
+    let mut top_level = vec![];
+    for (idx, t) in res.enumerate_type_definitions() {
+        match t.encloser {
+            Some(enc) => nested.entry(enc).or_default().push(idx),
+            None => top_level.push(idx),
+        }
+    }
+
+    for idx in top_level {
+        if is_module_type(idx) {
+            d.attributes(&res[idx].attributes);
+            for f in &res[idx].fields {
+                d.field(f);
+            }
+            d.methods(idx);
+        } else {
+            d.type_definition(idx, &nested);
+        }
+    }
+
+    for (i, data) in std::mem::take(&mut d.data).into_iter().enumerate() {
+        emit!(d, ".data D_{:04} = bytearray {}", i, bytes(data));
+    }
+
+    d.out
+}
@@ -0,0 +1,1015 @@
+//! Edit-and-Continue metadata deltas, for updating a module that has already been loaded by a running process.
+//!
+//! `System.Reflection.Metadata.MetadataUpdater.ApplyUpdate` takes a metadata delta and an IL delta that describe how a
+//! loaded module changed. A [`Baseline`] records what a delta needs to know about the module as it was loaded, and
+//! [`Resolution::write_delta`] compares the resolution that was loaded with an edited copy of it to produce a [`Delta`].
+//! Each delta comes with the baseline for the next generation, so that edits can be applied one after another.
+//!
+//! Existing members are matched up by their indices, so edits should only push new members instead of removing or
+//! reordering existing ones. A delta contains:
+//!
+//! - the bodies of existing methods that changed,
+//! - new type definitions, fields, methods and parameters, along with their attributes,
+//! - new assembly, module, type and member references, and
+//! - the signatures and user strings that all of these refer to.
+//!
+//! Changing the metadata of existing rows, like renaming a method or changing its signature, is reported as an error.
+//! Adding properties, events, generic parameters or security declarations is not supported.
+//!
+//! The metadata delta uses the uncompressed `#-` table stream with the `EncLog` and `EncMap` tables. Its heaps are appended
+//! to those of earlier generations and every index in it is 4 bytes wide, which is signaled by an empty `#JTD` stream.
+//! Method body RVAs are offsets into the IL delta, which starts with its own size.
+//! No PDB delta is produced, and an empty one can be passed to `ApplyUpdate` instead.
+//!
+//! The layout of deltas follows what Roslyn emits, but they are not compared against deltas produced by Roslyn. Like the
+//! other tests that run code, the test that applies a delta to a running module with `ApplyUpdate` needs a .NET SDK.
+
+use super::{
+    sha1::Sha1, write, FieldIndex, FieldRefIndex, MethodIndex, MethodMemberIndex, MethodRefIndex, Resolution, TypeIndex,
+};
+use crate::binary::{
+    heap::*,
+    metadata::{header, index, table::*},
+};
+use crate::convert;
+use crate::dll::{DLLError::*, Result, DLL};
+use crate::resolved::{
+    attribute::Attribute,
+    members::{self, FieldReferenceParent, FieldSource, MethodReferenceParent, UserMethod},
+    types::{Layout, ResolutionScope, TypeDefinition, ValueKind},
+};
+use scroll::Pwrite;
+use scroll_buffer::DynamicBuffer;
+use std::collections::{hash_map::Entry, HashMap};
+use tracing::debug;
+
+macro_rules! throw {
+    ($($arg:tt)*) => {
+        return Err(CLI(scroll::Error::Custom(format!($($arg)*))))
+    }
+}
+
+macro_rules! heap_idx {
+    ($heap:ident, $val:expr) => {
+        $heap.write(&$val)?
+    };
+}
+
+macro_rules! opt_heap {
+    ($heap:ident, $val:expr) => {
+        match &$val {
+            Some(v) => heap_idx!($heap, v),
+            None => 0.into(),
+        }
+    };
+}
+
+/// The operations that `EncLog` rows apply to the rows they refer to. See `CorDeltaFuncCode` in the runtime's `cor.h`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(u32)]
+pub enum FuncCode {
+    Default = 0,
+    AddMethod = 1,
+    AddField = 2,
+    AddParameter = 3,
+    AddProperty = 4,
+    AddEvent = 5,
+}
+
+fn token(kind: Kind, row: usize) -> u32 {
+    (kind as u32) << 24 | row as u32
+}
+
+#[derive(Debug, Clone, Default)]
+struct HeapSizes {
+    strings: usize,
+    guids: usize,
+    blobs: usize,
+    user_strings: usize,
+}
+
+/// The state of a module that the next delta is applied to.
+///
+/// For the first delta, this is the module as it was loaded from disk. Every [`Delta`] carries the baseline for the
+/// generation after it.
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    generation: u16,
+    metadata_version: String,
+    module_name: usize,
+    mvid: usize,
+    enc_id: usize,
+    rows: HashMap<Kind, u32>,
+    heaps: HeapSizes,
+    param_lists: Vec<usize>,
+    methods: HashMap<MethodIndex, usize>,
+    fields: HashMap<FieldIndex, usize>,
+    method_references: HashMap<MethodRefIndex, usize>,
+    field_references: HashMap<FieldRefIndex, usize>,
+}
+
+impl Baseline {
+    /// Captures the module that `res` was read from, which must have been read with
+    /// [`ReadOptions::record_tokens`](super::read::Options::record_tokens) set.
+    pub fn new(dll: &DLL, res: &Resolution) -> Result<Self> {
+        let Some(original) = &res.original_tokens else {
+            return Err(Other("EnC baselines require the original tokens of the resolution"));
+        };
+
+        let tables = dll.get_logical_metadata()?.tables;
+        let Some(module) = tables.module.first() else {
+            return Err(Other("missing module row in metadata"));
+        };
+
+        let heap_len = |name| Ok::<_, crate::dll::DLLError>(dll.get_stream(name)?.map_or(0, <[u8]>::len));
+
+        Ok(Baseline {
+            generation: module.generation,
+            metadata_version: dll.get_cli_metadata()?.version.to_string(),
+            module_name: module.name.0,
+            mvid: module.mvid.0,
+            enc_id: module.enc_id.0,
+            rows: tables.row_counts(),
+            heaps: HeapSizes {
+                strings: heap_len(StringsReader::NAME)?,
+                guids: heap_len(GUIDReader::NAME)?,
+                blobs: heap_len(BlobReader::NAME)?,
+                user_strings: heap_len(UserStringReader::NAME)?,
+            },
+            param_lists: tables.method_def.iter().map(|m| m.param_list.0).collect(),
+            methods: original.methods.clone(),
+            fields: original.fields.clone(),
+            method_references: original.method_references.clone(),
+            field_references: original.field_references.clone(),
+        })
+    }
+
+    /// The generation of the module, which is 0 for the module on disk and increases by 1 with every delta.
+    pub fn generation(&self) -> u16 {
+        self.generation
+    }
+
+    fn rows(&self, kind: Kind) -> usize {
+        self.rows.get(&kind).copied().unwrap_or(0) as usize
+    }
+}
+
+/// The deltas that update a module from one generation to the next.
+#[derive(Debug, Clone)]
+pub struct Delta {
+    /// The metadata delta, to be passed as the `metadataDelta` argument of `ApplyUpdate`.
+    pub metadata: Vec<u8>,
+    /// The IL delta, to be passed as the `ilDelta` argument of `ApplyUpdate`.
+    pub il: Vec<u8>,
+    /// The `MethodDef` tokens of the methods whose bodies are contained in the IL delta.
+    pub updated_methods: Vec<u32>,
+    /// The baseline that the next delta is applied to.
+    pub baseline: Baseline,
+}
+
+fn method<'r, 'a>(res: &'r Resolution<'a>, idx: MethodIndex) -> Option<&'r members::Method<'a>> {
+    use MethodMemberIndex::*;
+
+    let parent = res.type_definitions.get(idx.parent_type.0)?;
+    match idx.member {
+        Method(i) => parent.methods.get(i),
+        PropertyGetter(i) => parent.properties.get(i)?.getter.as_ref(),
+        PropertySetter(i) => parent.properties.get(i)?.setter.as_ref(),
+        PropertyOther { property, other } => parent.properties.get(property)?.other.get(other),
+        EventAdd(i) => parent.events.get(i).map(|e| &e.add_listener),
+        EventRemove(i) => parent.events.get(i).map(|e| &e.remove_listener),
+        EventRaise(i) => parent.events.get(i)?.raise_event.as_ref(),
+        EventOther { event, other } => parent.events.get(event)?.other.get(other),
+    }
+}
+
+fn field<'r, 'a>(res: &'r Resolution<'a>, idx: FieldIndex) -> Option<&'r members::Field<'a>> {
+    res.type_definitions.get(idx.parent_type.0)?.fields.get(idx.field)
+}
+
+fn body_changed(previous: &members::Method, current: &members::Method) -> bool {
+    match (&previous.body, &current.body) {
+        (Some(p), Some(c)) => {
+            p.header != c.header || p.instructions != c.instructions || p.data_sections != c.data_sections
+        }
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+// members are checked in row order, so that the first changed row is the one reported
+fn by_row<T: Copy>(rows: &HashMap<T, usize>) -> Vec<T> {
+    let mut rows: Vec<_> = rows.iter().map(|(&idx, &row)| (row, idx)).collect();
+    rows.sort_unstable_by_key(|&(row, _)| row);
+    rows.into_iter().map(|(_, idx)| idx).collect()
+}
+
+// existing rows are only written again for methods whose bodies changed, so edits to them can't be expressed in a delta
+fn check_existing(baseline: &Baseline, previous: &Resolution, current: &Resolution) -> Result<()> {
+    for idx in 0..baseline
+        .rows(Kind::TypeDef)
+        .min(previous.type_definitions.len())
+        .min(current.type_definitions.len())
+    {
+        let (p, t) = (&previous.type_definitions[idx], &current.type_definitions[idx]);
+        let interfaces = |t: &TypeDefinition| t.implements.iter().map(|(_, i)| i.clone()).collect::<Vec<_>>();
+        if p.name != t.name
+            || p.namespace != t.namespace
+            || write::type_flags(p) != write::type_flags(t)
+            || p.extends != t.extends
+            || p.encloser != t.encloser
+            || interfaces(p) != interfaces(t)
+        {
+            throw!("changing existing type {} is not supported in EnC deltas", p.name);
+        }
+    }
+
+    for m_idx in by_row(&baseline.methods) {
+        // removed methods are reported along with the bodies
+        let (Some(p), Some(m)) = (method(previous, m_idx), method(current, m_idx)) else {
+            continue;
+        };
+        let params = |m: &members::Method| {
+            std::iter::once(&m.return_type_metadata)
+                .chain(&m.parameter_metadata)
+                .map(|p| {
+                    p.as_ref()
+                        .map(|p| (p.name.as_deref().map(String::from), write::param_flags(p)))
+                })
+                .collect::<Vec<_>>()
+        };
+        if p.name != m.name
+            || p.signature != m.signature
+            || write::method_flags(p) != write::method_flags(m)
+            || write::method_impl_flags(p) != write::method_impl_flags(m)
+            || params(p) != params(m)
+        {
+            throw!("changing existing method {} is not supported in EnC deltas", p.name);
+        }
+    }
+
+    for f_idx in by_row(&baseline.fields) {
+        let (Some(p), Some(f)) = (field(previous, f_idx), field(current, f_idx)) else {
+            throw!("removing fields is not supported in EnC deltas");
+        };
+        if p.name != f.name
+            || p.return_type != f.return_type
+            || p.type_modifiers != f.type_modifiers
+            || p.by_ref != f.by_ref
+            || write::field_flags(p) != write::field_flags(f)
+        {
+            throw!("changing existing field {} is not supported in EnC deltas", p.name);
+        }
+    }
+
+    Ok(())
+}
+
+fn check_references(baseline: &Baseline, previous: &Resolution, current: &Resolution) -> Result<()> {
+    let existing = |kind: Kind, len: usize| 0..baseline.rows(kind).min(len);
+
+    for idx in existing(Kind::AssemblyRef, previous.assembly_references.len()) {
+        let (p, a) = (&previous.assembly_references[idx], &current.assembly_references[idx]);
+        if p.name != a.name
+            || p.version != a.version
+            || p.culture != a.culture
+            || p.public_key_or_token != a.public_key_or_token
+        {
+            throw!(
+                "changing existing assembly reference {} is not supported in EnC deltas",
+                p.name
+            );
+        }
+    }
+
+    for idx in existing(Kind::ModuleRef, previous.module_references.len()) {
+        let (p, r) = (&previous.module_references[idx], &current.module_references[idx]);
+        if p.name != r.name {
+            throw!(
+                "changing existing module reference {} is not supported in EnC deltas",
+                p.name
+            );
+        }
+    }
+
+    for idx in existing(Kind::TypeRef, previous.type_references.len()) {
+        let (p, r) = (&previous.type_references[idx], &current.type_references[idx]);
+        if p.name != r.name || p.namespace != r.namespace || p.scope != r.scope {
+            throw!(
+                "changing existing type reference {} is not supported in EnC deltas",
+                p.name
+            );
+        }
+    }
+
+    for r_idx in by_row(&baseline.method_references) {
+        let (Some(p), Some(r)) = (
+            previous.method_references.get(r_idx.0),
+            current.method_references.get(r_idx.0),
+        ) else {
+            throw!("removing method references is not supported in EnC deltas");
+        };
+        if p.name != r.name || p.parent != r.parent || p.signature != r.signature {
+            throw!(
+                "changing existing method reference {} is not supported in EnC deltas",
+                p.name
+            );
+        }
+    }
+
+    for r_idx in by_row(&baseline.field_references) {
+        let (Some(p), Some(r)) = (
+            previous.field_references.get(r_idx.0),
+            current.field_references.get(r_idx.0),
+        ) else {
+            throw!("removing field references is not supported in EnC deltas");
+        };
+        if p.name != r.name
+            || p.parent != r.parent
+            || p.field_type != r.field_type
+            || p.custom_modifiers != r.custom_modifiers
+        {
+            throw!(
+                "changing existing field reference {} is not supported in EnC deltas",
+                p.name
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_lines)]
+pub(crate) fn delta_impl(baseline: &Baseline, previous: &Resolution, current: &Resolution) -> Result<Delta> {
+    let base_rows = |kind| baseline.rows(kind);
+
+    macro_rules! check_removed {
+        ($($field:ident => $kind:ident),+) => {
+            $(
+                if current.$field.len() < base_rows(Kind::$kind) {
+                    throw!("removing {} is not supported in EnC deltas", stringify!($field).replace('_', " "));
+                }
+            )+
+        };
+    }
+    check_removed!(
+        assembly_references => AssemblyRef,
+        module_references => ModuleRef,
+        type_definitions => TypeDef,
+        type_references => TypeRef
+    );
+    check_existing(baseline, previous, current)?;
+    check_references(baseline, previous, current)?;
+
+    debug!("enc rows");
+
+    let mut methods = baseline.methods.clone();
+    let mut fields = baseline.fields.clone();
+    let mut new_fields = vec![];
+    let mut new_methods = vec![];
+
+    for (idx, t) in current.type_definitions.iter().enumerate() {
+        let parent_type = TypeIndex(idx);
+        let existing = previous
+            .type_definitions
+            .get(idx)
+            .filter(|_| idx < base_rows(Kind::TypeDef));
+        if let Some(p) = existing {
+            if t.properties.len() > p.properties.len() || t.events.len() > p.events.len() {
+                throw!(
+                    "adding properties or events to {} is not supported in EnC deltas",
+                    t.name
+                );
+            }
+        } else {
+            if !t.properties.is_empty() || !t.events.is_empty() || !t.generic_parameters.is_empty() {
+                throw!(
+                    "new type {} cannot have properties, events or generic parameters in an EnC delta",
+                    t.name
+                );
+            }
+            if t.security.is_some() {
+                throw!("new type {} cannot have a security declaration in an EnC delta", t.name);
+            }
+        }
+
+        for (i, f) in t.fields.iter().enumerate() {
+            let f_idx = FieldIndex { parent_type, field: i };
+            if let Entry::Vacant(e) = fields.entry(f_idx) {
+                e.insert(base_rows(Kind::Field) + new_fields.len() + 1);
+                new_fields.push((f_idx, f));
+            }
+        }
+
+        for (i, m) in t.methods.iter().enumerate() {
+            let m_idx = MethodIndex {
+                parent_type,
+                member: MethodMemberIndex::Method(i),
+            };
+            if let Entry::Vacant(e) = methods.entry(m_idx) {
+                e.insert(base_rows(Kind::MethodDef) + new_methods.len() + 1);
+                new_methods.push((m_idx, m));
+            }
+        }
+    }
+
+    let mut updated_methods: Vec<_> = baseline
+        .methods
+        .iter()
+        .map(
+            |(&m_idx, &row)| match (method(previous, m_idx), method(current, m_idx)) {
+                (Some(p), Some(c)) => Ok((row, m_idx, body_changed(p, c))),
+                _ => throw!("removing methods is not supported in EnC deltas"),
+            },
+        )
+        .filter(|r| !matches!(r, Ok((.., false))))
+        .map(|r| r.map(|(row, m_idx, _)| (row, m_idx)))
+        .collect::<Result<_>>()?;
+    updated_methods.sort_unstable_by_key(|&(row, _)| row);
+
+    let mut method_references = baseline.method_references.clone();
+    let mut field_references = baseline.field_references.clone();
+    let mut new_member_refs = vec![];
+    for i in 0..current.method_references.len() {
+        let r_idx = MethodRefIndex(i);
+        if let Entry::Vacant(e) = method_references.entry(r_idx) {
+            new_member_refs.push(write::MemberRefSource::Method(r_idx));
+            e.insert(base_rows(Kind::MemberRef) + new_member_refs.len());
+        }
+    }
+    for i in 0..current.field_references.len() {
+        let r_idx = FieldRefIndex(i);
+        if let Entry::Vacant(e) = field_references.entry(r_idx) {
+            new_member_refs.push(write::MemberRefSource::Field(r_idx));
+            e.insert(base_rows(Kind::MemberRef) + new_member_refs.len());
+        }
+    }
+
+    let user_method = |u: UserMethod| match u {
+        UserMethod::Definition(m) => index::MethodDefOrRef::MethodDef(methods[&m]),
+        UserMethod::Reference(r) => index::MethodDefOrRef::MemberRef(method_references[&r]),
+    };
+
+    let field_source = |f: FieldSource| match f {
+        FieldSource::Definition(d) => index::Token {
+            target: index::TokenTarget::Table(Kind::Field),
+            index: fields[&d],
+        },
+        FieldSource::Reference(r) => index::Token {
+            target: index::TokenTarget::Table(Kind::MemberRef),
+            index: field_references[&r],
+        },
+    };
+
+    let mut strings = StringsWriter::appending(baseline.heaps.strings);
+    let mut blobs = BlobWriter::appending(baseline.heaps.blobs);
+    let mut guids = GUIDWriter::appending(baseline.heaps.guids);
+    let mut userstrings = UserStringWriter::appending(baseline.heaps.user_strings);
+
+    let mut tables = Tables::new();
+
+    // prefilled so that the rows that signatures add are numbered after those of the baseline
+    let mut type_specs = vec![TypeSpec { signature: 0.into() }; base_rows(Kind::TypeSpec)];
+    let mut stand_alone_sigs = vec![StandAloneSig { signature: 0.into() }; base_rows(Kind::StandAloneSig)];
+    let mut method_specs = vec![
+        MethodSpec {
+            method: index::MethodDefOrRef::Null,
+            instantiation: 0.into(),
+        };
+        base_rows(Kind::MethodSpec)
+    ];
+
+    let mut type_cache = HashMap::new();
+    let mut blob_scratch = DynamicBuffer::with_increment(8);
+
+    macro_rules! build_ctx {
+        () => {
+            &mut convert::write::Context {
+                blobs: &mut blobs,
+                specs: &mut type_specs,
+                type_cache: &mut type_cache,
+                blob_scratch: &mut blob_scratch,
+            }
+        };
+    }
+
+    let mut attributes: Vec<(&Attribute, index::HasCustomAttribute)> = vec![];
+
+    macro_rules! write_attrs {
+        ($a:expr, $parent:ident($idx:expr)) => {
+            write::write_attrs(&mut attributes, &$a, index::HasCustomAttribute::$parent($idx))
+        };
+    }
+
+    debug!("enc references");
+
+    for (idx, a) in current
+        .assembly_references
+        .iter()
+        .enumerate()
+        .skip(base_rows(Kind::AssemblyRef))
+    {
+        tables.assembly_ref.push(AssemblyRef {
+            major_version: a.version.major,
+            minor_version: a.version.minor,
+            build_number: a.version.build,
+            revision_number: a.version.revision,
+            flags: a.has_full_public_key as u32,
+            public_key_or_token: opt_heap!(blobs, a.public_key_or_token),
+            name: heap_idx!(strings, a.name),
+            culture: opt_heap!(strings, a.culture),
+            hash_value: opt_heap!(blobs, a.hash_value),
+        });
+        write_attrs!(a.attributes, AssemblyRef(idx + 1));
+    }
+
+    for (idx, r) in current
+        .module_references
+        .iter()
+        .enumerate()
+        .skip(base_rows(Kind::ModuleRef))
+    {
+        tables.module_ref.push(ModuleRef {
+            name: heap_idx!(strings, r.name),
+        });
+        write_attrs!(r.attributes, ModuleRef(idx + 1));
+    }
+
+    for (idx, r) in current
+        .type_references
+        .iter()
+        .enumerate()
+        .skip(base_rows(Kind::TypeRef))
+    {
+        tables.type_ref.push(TypeRef {
+            resolution_scope: match r.scope {
+                ResolutionScope::Nested(t) => index::ResolutionScope::TypeRef(t.0 + 1),
+                ResolutionScope::ExternalModule(m) => index::ResolutionScope::ModuleRef(m.0 + 1),
+                ResolutionScope::CurrentModule => index::ResolutionScope::Module(1),
+                ResolutionScope::Assembly(a) => index::ResolutionScope::AssemblyRef(a.0 + 1),
+                ResolutionScope::Exported => index::ResolutionScope::Null,
+            },
+            type_name: heap_idx!(strings, r.name),
+            type_namespace: opt_heap!(strings, r.namespace),
+        });
+        write_attrs!(r.attributes, TypeRef(idx + 1));
+    }
+
+    for r in new_member_refs {
+        let row = tables.member_ref.len() + base_rows(Kind::MemberRef) + 1;
+        match r {
+            write::MemberRefSource::Method(m) => {
+                let m = &current[m];
+                tables.member_ref.push(MemberRef {
+                    class: match &m.parent {
+                        MethodReferenceParent::Type(t) => write::type_to_parent(t, build_ctx!())?,
+                        MethodReferenceParent::Module(m) => index::MemberRefParent::ModuleRef(m.0 + 1),
+                        MethodReferenceParent::VarargMethod(m) => index::MemberRefParent::MethodDef(methods[m]),
+                    },
+                    name: heap_idx!(strings, m.name),
+                    signature: convert::write::method_ref(&m.signature, build_ctx!())?,
+                });
+                write_attrs!(m.attributes, MemberRef(row));
+            }
+            write::MemberRefSource::Field(f) => {
+                let f = &current[f];
+                tables.member_ref.push(MemberRef {
+                    class: match &f.parent {
+                        FieldReferenceParent::Type(t) => write::type_to_parent(t, build_ctx!())?,
+                        FieldReferenceParent::Module(m) => index::MemberRefParent::ModuleRef(m.0 + 1),
+                    },
+                    name: heap_idx!(strings, f.name),
+                    signature: convert::write::field_ref(f, build_ctx!())?,
+                });
+                write_attrs!(f.attributes, MemberRef(row));
+            }
+        }
+    }
+
+    debug!("enc type definitions");
+
+    for (idx, t) in current
+        .type_definitions
+        .iter()
+        .enumerate()
+        .skip(base_rows(Kind::TypeDef))
+    {
+        let parent_type = TypeIndex(idx);
+        let simple_idx = (idx + 1).into();
+
+        // new types only have new members, which were given consecutive rows
+        let field_list = if t.fields.is_empty() {
+            base_rows(Kind::Field) + new_fields.len() + 1
+        } else {
+            fields[&FieldIndex { parent_type, field: 0 }]
+        };
+        let method_list = if t.methods.is_empty() {
+            base_rows(Kind::MethodDef) + new_methods.len() + 1
+        } else {
+            methods[&MethodIndex {
+                parent_type,
+                member: MethodMemberIndex::Method(0),
+            }]
+        };
+
+        tables.type_def.push(TypeDef {
+            flags: write::type_flags(t),
+            type_name: heap_idx!(strings, t.name),
+            type_namespace: opt_heap!(strings, t.namespace),
+            extends: match &t.extends {
+                Some(t) => convert::write::source_index(Some(ValueKind::Class), t, build_ctx!())?,
+                None => index::TypeDefOrRef::Null,
+            },
+            field_list: field_list.into(),
+            method_list: method_list.into(),
+        });
+
+        for (attrs, i) in &t.implements {
+            let impl_idx = base_rows(Kind::InterfaceImpl) + tables.interface_impl.len() + 1;
+            tables.interface_impl.push(InterfaceImpl {
+                class: simple_idx,
+                interface: convert::write::source_index(Some(ValueKind::Class), i, build_ctx!())?,
+            });
+            write_attrs!(attrs, InterfaceImpl(impl_idx));
+        }
+
+        write_attrs!(t.attributes, TypeDef(idx + 1));
+
+        match t.flags.layout {
+            Layout::Sequential(Some(s)) => {
+                tables.class_layout.push(ClassLayout {
+                    packing_size: s.packing_size as u16,
+                    class_size: s.class_size as u32,
+                    parent: simple_idx,
+                });
+            }
+            Layout::Explicit(Some(e)) => {
+                tables.class_layout.push(ClassLayout {
+                    packing_size: 0,
+                    class_size: e.class_size as u32,
+                    parent: simple_idx,
+                });
+            }
+            _ => {}
+        }
+
+        if let Some(enc) = t.encloser {
+            tables.nested_class.push(NestedClass {
+                nested_class: simple_idx,
+                enclosing_class: (enc.0 + 1).into(),
+            });
+        }
+    }
+
+    // the log records the rows of each table in the order the runtime should apply them
+    let mut log = vec![];
+
+    debug!("enc fields");
+
+    for &(f_idx, f) in &new_fields {
+        let row = fields[&f_idx];
+        if f.initial_value.is_some() {
+            throw!("new field {} cannot have an initial value in an EnC delta", f.name);
+        }
+
+        log.push((token(Kind::TypeDef, f_idx.parent_type.0 + 1), FuncCode::AddField));
+        log.push((token(Kind::Field, row), FuncCode::Default));
+
+        tables.field.push(Field {
+            flags: write::field_flags(f),
+            name: heap_idx!(strings, f.name),
+            signature: convert::write::field_def(f, build_ctx!())?,
+        });
+
+        write_attrs!(f.attributes, Field(row));
+        write::write_pinvoke(
+            &f.pinvoke,
+            index::MemberForwarded::Field(row),
+            &mut strings,
+            &mut tables,
+        )?;
+        write::write_marshal(
+            f.marshal.as_ref(),
+            index::HasFieldMarshal::Field(row),
+            &mut tables.field_marshal,
+            build_ctx!(),
+        )?;
+        write::write_default(&f.default, index::HasConstant::Field(row), &mut blobs, &mut tables)?;
+
+        if let Some(o) = f.offset {
+            tables.field_layout.push(FieldLayout {
+                offset: o as u32,
+                field: row.into(),
+            });
+        }
+    }
+
+    debug!("enc methods");
+
+    // the IL delta starts with its size, so that no body is at RVA 0
+    let mut il = vec![0_u8; 4];
+    let mut body_tokens = vec![];
+    let mut new_param_lists = vec![];
+
+    let all_methods = updated_methods
+        .iter()
+        .map(|&(row, m_idx)| (row, m_idx, false))
+        .chain(new_methods.iter().map(|&(m_idx, _)| (methods[&m_idx], m_idx, true)));
+
+    for (row, m_idx, is_new) in all_methods {
+        let m = &current[m_idx];
+
+        let rva = match &m.body {
+            Some(body) => {
+                let (encoded, _) = write::method_body(
+                    body,
+                    body.header.maximum_stack_size,
+                    build_ctx!(),
+                    &mut convert::write::MethodContext {
+                        stand_alone_sigs: &mut stand_alone_sigs,
+                        method_specs: &mut method_specs,
+                        userstrings: &mut userstrings,
+                        user_method: &user_method,
+                        field_source: &field_source,
+                    },
+                )?;
+
+                // fat method headers must be aligned
+                if matches!(encoded.header, crate::binary::method::Header::Fat { .. }) {
+                    il.resize(crate::utils::round_up_to_4(il.len()).0, 0);
+                }
+
+                let rva = il.len() as u32;
+                let mut buf = DynamicBuffer::with_increment(16);
+                buf.pwrite(encoded, 0)?;
+                il.extend_from_slice(buf.get());
+
+                body_tokens.push(token(Kind::MethodDef, row));
+                rva
+            }
+            None => 0,
+        };
+
+        let param_list = if is_new {
+            if !m.generic_parameters.is_empty() || m.security.is_some() {
+                throw!(
+                    "new method {} cannot have generic parameters or a security declaration in an EnC delta",
+                    m.name
+                );
+            }
+
+            log.push((token(Kind::TypeDef, m_idx.parent_type.0 + 1), FuncCode::AddMethod));
+            let list = base_rows(Kind::Param) + tables.param.len() + 1;
+            new_param_lists.push(list);
+            list
+        } else {
+            baseline.param_lists[row - 1]
+        };
+        log.push((token(Kind::MethodDef, row), FuncCode::Default));
+
+        tables.method_def.push(MethodDef {
+            rva,
+            impl_flags: write::method_impl_flags(m),
+            flags: write::method_flags(m),
+            name: heap_idx!(strings, m.name),
+            signature: write::method_signature(m, build_ctx!())?,
+            param_list: param_list.into(),
+        });
+
+        if !is_new {
+            continue;
+        }
+
+        write_attrs!(m.attributes, MethodDef(row));
+        write::write_pinvoke(
+            &m.pinvoke,
+            index::MemberForwarded::MethodDef(row),
+            &mut strings,
+            &mut tables,
+        )?;
+
+        for (sequence, p) in std::iter::once(&m.return_type_metadata)
+            .chain(m.parameter_metadata.iter())
+            .enumerate()
+        {
+            if let Some(p) = p {
+                let param_row = base_rows(Kind::Param) + tables.param.len() + 1;
+                log.push((token(Kind::MethodDef, row), FuncCode::AddParameter));
+                log.push((token(Kind::Param, param_row), FuncCode::Default));
+
+                tables.param.push(Param {
+                    flags: write::param_flags(p),
+                    sequence: sequence as u16,
+                    name: opt_heap!(strings, p.name),
+                });
+
+                write_attrs!(p.attributes, Param(param_row));
+                write::write_marshal(
+                    p.marshal.as_ref(),
+                    index::HasFieldMarshal::Param(param_row),
+                    &mut tables.field_marshal,
+                    build_ctx!(),
+                )?;
+                write::write_default(
+                    &p.default,
+                    index::HasConstant::Param(param_row),
+                    &mut blobs,
+                    &mut tables,
+                )?;
+            }
+        }
+    }
+
+    let il_len = il.len() as u32;
+    il[..4].copy_from_slice(&il_len.to_le_bytes());
+
+    debug!("enc overrides");
+
+    for (idx, t) in current.type_definitions.iter().enumerate() {
+        let existing = previous
+            .type_definitions
+            .get(idx)
+            .filter(|_| idx < base_rows(Kind::TypeDef))
+            .map_or(0, |p| p.overrides.len());
+        for o in t.overrides.iter().skip(existing) {
+            tables.method_impl.push(MethodImpl {
+                class: (idx + 1).into(),
+                method_body: user_method(o.implementation),
+                method_declaration: user_method(o.declaration),
+            });
+        }
+    }
+
+    debug!("enc attributes");
+
+    for (a, parent) in attributes {
+        tables.custom_attribute.push(CustomAttribute {
+            parent,
+            attr_type: match user_method(a.constructor) {
+                index::MethodDefOrRef::MethodDef(i) => index::CustomAttributeType::MethodDef(i),
+                index::MethodDefOrRef::MemberRef(i) => index::CustomAttributeType::MemberRef(i),
+                index::MethodDefOrRef::Null => index::CustomAttributeType::Null,
+            },
+            value: opt_heap!(blobs, a.value.as_ref()),
+        });
+    }
+
+    tables.type_spec = type_specs.split_off(base_rows(Kind::TypeSpec));
+    tables.stand_alone_sig = stand_alone_sigs.split_off(base_rows(Kind::StandAloneSig));
+    tables.method_spec = method_specs.split_off(base_rows(Kind::MethodSpec));
+
+    debug!("enc log and map");
+
+    // every other table only gains rows at its end
+    let added = tables.row_counts();
+    let appended = |kinds: &[Kind]| {
+        kinds
+            .iter()
+            .flat_map(|&k| {
+                let start = base_rows(k);
+                (1..=added.get(&k).copied().unwrap_or(0) as usize)
+                    .map(move |i| (token(k, start + i), FuncCode::Default))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut full_log = appended(&[
+        Kind::AssemblyRef,
+        Kind::ModuleRef,
+        Kind::MemberRef,
+        Kind::MethodSpec,
+        Kind::TypeRef,
+        Kind::TypeSpec,
+        Kind::StandAloneSig,
+        Kind::TypeDef,
+    ]);
+    full_log.extend(log);
+    full_log.extend(appended(&[
+        Kind::InterfaceImpl,
+        Kind::Constant,
+        Kind::CustomAttribute,
+        Kind::FieldMarshal,
+        Kind::ClassLayout,
+        Kind::FieldLayout,
+        Kind::MethodImpl,
+        Kind::ImplMap,
+        Kind::NestedClass,
+    ]));
+
+    let mut map: Vec<_> = full_log
+        .iter()
+        .filter(|&&(_, code)| code == FuncCode::Default)
+        .map(|&(t, _)| t)
+        .collect();
+    map.sort_unstable();
+
+    tables.enc_log = full_log
+        .into_iter()
+        .map(|(token, code)| EncLog {
+            token,
+            func_code: code as u32,
+        })
+        .collect();
+    tables.enc_map = map.into_iter().map(|token| EncMap { token }).collect();
+
+    let generation = baseline.generation + 1;
+    let enc_id = heap_idx!(guids, [0; 16]);
+    tables.module.push(Module {
+        generation,
+        name: baseline.module_name.into(),
+        mvid: baseline.mvid.into(),
+        enc_id,
+        enc_base_id: baseline.enc_id.into(),
+    });
+
+    let strings_vec = strings.into_vec();
+    let mut guids_vec = guids.into_vec();
+    let blobs_vec = blobs.into_vec();
+    let userstrings_vec = userstrings.into_vec();
+
+    let mut rows = baseline.rows.clone();
+    for (&kind, &count) in &tables.row_counts() {
+        match kind {
+            Kind::Module | Kind::EncLog | Kind::EncMap => {}
+            Kind::MethodDef => *rows.entry(kind).or_default() += new_methods.len() as u32,
+            _ => *rows.entry(kind).or_default() += count,
+        }
+    }
+
+    let header = header::Header {
+        reserved0: 0,
+        major_version: 2,
+        minor_version: 0,
+        // the runtime's flags for streams that only contain deltas and may contain deleted rows
+        heap_sizes: 0x07 | 0x20 | 0x80,
+        reserved1: 1,
+        valid: tables.valid_mask(),
+        sorted: 0,
+        tables,
+    };
+
+    let mut header_buf = DynamicBuffer::with_increment(32);
+    header_buf.pwrite_with(
+        header,
+        0,
+        header::Layout {
+            large_indices: true,
+            uncompressed: true,
+            ..header::Layout::default()
+        },
+    )?;
+    let header_stream = header_buf.get();
+
+    // the EncId only has to be unique, so derive it from the contents of the delta like deterministic MVIDs
+    let mut hash = Sha1::new();
+    hash.update(&generation.to_le_bytes());
+    for part in [header_stream, &strings_vec, &blobs_vec, &userstrings_vec, &il] {
+        hash.update(&(part.len() as u64).to_le_bytes());
+        hash.update(part);
+    }
+    let digest = hash.finish();
+    guids_vec[..16].copy_from_slice(&digest[..16]);
+    guids_vec[7] = (guids_vec[7] & 0x0f) | 0x40;
+    guids_vec[8] = (guids_vec[8] & 0x3f) | 0x80;
+
+    let metadata = write::metadata_root(
+        &baseline.metadata_version,
+        &[
+            (header_stream, "#-"),
+            (&strings_vec, StringsReader::NAME),
+            (&userstrings_vec, UserStringReader::NAME),
+            (&guids_vec, GUIDReader::NAME),
+            (&blobs_vec, BlobReader::NAME),
+            (&[], "#JTD"),
+        ],
+    )?;
+
+    let padded = |len: usize| crate::utils::round_up_to_4(len).0;
+    let mut param_lists = baseline.param_lists.clone();
+    param_lists.extend(new_param_lists);
+
+    Ok(Delta {
+        metadata,
+        il,
+        updated_methods: body_tokens,
+        baseline: Baseline {
+            generation,
+            metadata_version: baseline.metadata_version.clone(),
+            module_name: baseline.module_name,
+            mvid: baseline.mvid,
+            enc_id: enc_id.0,
+            rows,
+            heaps: HeapSizes {
+                strings: baseline.heaps.strings + padded(strings_vec.len()),
+                guids: baseline.heaps.guids + padded(guids_vec.len()),
+                blobs: baseline.heaps.blobs + padded(blobs_vec.len()),
+                user_strings: baseline.heaps.user_strings + padded(userstrings_vec.len()),
+            },
+            param_lists,
+            methods,
+            fields,
+            method_references,
+            field_references,
+        },
+    })
+}
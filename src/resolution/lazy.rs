@@ -0,0 +1,1060 @@
+//! A view over a [`DLL`] that only resolves the parts of it that are actually used.
+//!
+//! [`Resolution::parse`] resolves every type, member, signature and (unless
+//! [`skip_method_bodies`](read::Options::skip_method_bodies) is set) method body up front, which is wasteful for large
+//! assemblies like System.Private.CoreLib when only a handful of types are needed. A [`LazyResolution`] instead resolves
+//! each type definition, reference and method body the first time it is accessed, and keeps the result for later accesses.
+//!
+//! The metadata tables themselves are still read when the view is created, since their rows have a fixed size and
+//! reading them does not touch the heaps. The first access that needs to know the [`MethodIndex`] or [`FieldIndex`] of
+//! a member also works out how the rows are distributed between types, properties and events, without decoding any names
+//! or signatures.
+//!
+//! The resolved values are the same as the ones in the corresponding [`Resolution`], except that the methods of type
+//! definitions never have bodies, which are available from [`LazyResolution::method_body`] instead.
+//! Debugging information from portable PDBs is not supported.
+//!
+//! [`Resolution::parse`] resolves everything through this view as well, so the two always agree.
+//! [`LazyResolution::resolve`] does the same, but reuses everything that has already been resolved.
+
+use super::{
+    read, FieldIndex, FieldRefIndex, MethodIndex, MethodMemberIndex, MethodRefIndex, Resolution, TypeIndex,
+    TypeRefIndex,
+};
+use crate::binary::{
+    heap::*,
+    metadata::{
+        self,
+        index::{HasConstant, HasCustomAttribute, HasDeclSecurity, HasFieldMarshal, MemberForwarded, TypeOrMethodDef},
+        table::{Kind, Tables},
+    },
+    signature::kinds::{FieldSig, MethodRefSig},
+};
+use crate::convert;
+use crate::dll::{DLLError, DLLError::*, ErrorContext, Result, DLL};
+use crate::resolved::{attribute::*, body, members::*, types::*};
+use scroll::Pread;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+macro_rules! throw {
+    ($($arg:tt)*) => {
+        return Err(CLI(scroll::Error::Custom(format!($($arg)*))))
+    }
+}
+
+macro_rules! throw_index {
+    ($table:ident, $row:expr, $context:expr) => {
+        return Err(DLLError::invalid_index(Kind::$table, $row, $context))
+    };
+}
+
+// the cells are filled in after the value is computed, so that computing one value can access others
+fn memoize<T>(cell: &OnceCell<T>, init: impl FnOnce() -> Result<T>) -> Result<&T> {
+    if let Some(value) = cell.get() {
+        return Ok(value);
+    }
+    let value = init()?;
+    Ok(cell.get_or_init(|| value))
+}
+
+// the value in the cell if it has already been computed, and a value computed without filling the cell otherwise
+fn owned<T: Clone>(cell: &OnceCell<T>, init: impl FnOnce() -> Result<T>) -> Result<T> {
+    match cell.get() {
+        Some(value) => Ok(value.clone()),
+        None => init(),
+    }
+}
+
+// the physical rows owned by an entry of a table with member lists, as (table, pointer table) kinds and list names
+fn owned_rows(
+    lists: &[usize],
+    owner: usize,
+    rows: usize,
+    pointers: &[usize],
+    kinds: (Kind, Kind),
+    names: (&str, &str),
+) -> Result<Vec<usize>> {
+    // when a pointer table is present, member lists index into it instead of the table it points to
+    let len = if pointers.is_empty() { rows } else { pointers.len() };
+    let range = (lists[owner] - 1)..(lists.get(owner + 1).copied().unwrap_or(len + 1) - 1);
+    let context = || ErrorContext::new(format!("{} list of {} {}", names.0, names.1, owner));
+    if range.start > range.end || range.end > len {
+        let kind = if pointers.is_empty() { kinds.0 } else { kinds.1 };
+        // report the first row of the list that doesn't exist
+        let row = if range.end > len {
+            range.start.max(len)
+        } else {
+            range.start
+        };
+        return Err(DLLError::invalid_index(kind, row, context()));
+    }
+
+    range
+        .map(|logical| {
+            let physical = match pointers.get(logical) {
+                Some(&p) => p.wrapping_sub(1),
+                None => logical,
+            };
+            if physical < rows {
+                Ok(physical)
+            } else {
+                Err(DLLError::invalid_index(kinds.0, physical, context()))
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct PropertyRows {
+    row: usize,
+    getter: Option<usize>,
+    setter: Option<usize>,
+    other: Vec<usize>,
+}
+
+#[derive(Debug, Default)]
+struct EventRows {
+    row: usize,
+    add: usize,
+    remove: usize,
+    raise: Option<usize>,
+    other: Vec<usize>,
+}
+
+#[derive(Debug, Default)]
+struct TypeRows {
+    fields: Vec<usize>,
+    methods: Vec<usize>,
+    properties: Vec<PropertyRows>,
+    events: Vec<EventRows>,
+}
+
+// which rows make up each type, and the indices that the rows of the member tables resolve to
+#[derive(Debug)]
+pub(super) struct Members {
+    types: Vec<TypeRows>,
+    pub(super) fields: Vec<FieldIndex>,
+    pub(super) methods: Vec<MethodIndex>,
+    params: Vec<Vec<usize>>,
+}
+
+// member references are split into field and method references by the kind of signature they have
+#[derive(Debug, Default)]
+pub(super) struct References {
+    pub(super) field_map: HashMap<usize, usize>,
+    pub(super) method_map: HashMap<usize, usize>,
+    pub(super) fields: Vec<usize>,
+    pub(super) methods: Vec<usize>,
+}
+
+// the rows of the tables that attach extra information to other rows, by the row they attach it to
+#[derive(Debug, Default)]
+struct Associations {
+    class_layouts: HashMap<usize, usize>,
+    enclosers: HashMap<usize, usize>,
+    interfaces: HashMap<usize, Vec<usize>>,
+    field_layouts: HashMap<usize, usize>,
+    field_rvas: HashMap<usize, usize>,
+    constants: HashMap<HasConstant, usize>,
+    marshals: HashMap<HasFieldMarshal, usize>,
+    pinvokes: HashMap<MemberForwarded, usize>,
+    security: HashMap<HasDeclSecurity, usize>,
+    generic_params: HashMap<TypeOrMethodDef, Vec<usize>>,
+    constraints: HashMap<usize, Vec<usize>>,
+    overrides: HashMap<usize, Vec<usize>>,
+    attributes: HashMap<HasCustomAttribute, Vec<usize>>,
+}
+
+/// A lazily resolved view over a [`DLL`]. See the [module documentation](self) for details.
+pub struct LazyResolution<'a> {
+    dll: DLL<'a>,
+    pub(super) strings: StringsReader<'a>,
+    pub(super) blobs: BlobReader<'a>,
+    userstrings: UserStringReader<'a>,
+    pub(super) tables: Tables,
+    members: OnceCell<Members>,
+    references: OnceCell<References>,
+    associations: OnceCell<Associations>,
+    type_definitions: Vec<OnceCell<TypeDefinition<'a>>>,
+    type_references: Vec<OnceCell<ExternalTypeReference<'a>>>,
+    field_references: Vec<OnceCell<ExternalFieldReference<'a>>>,
+    method_references: Vec<OnceCell<ExternalMethodReference<'a>>>,
+    method_bodies: Vec<OnceCell<Option<body::Method>>>,
+}
+
+impl std::fmt::Debug for LazyResolution<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LazyResolution")
+            .field("type_definitions", &self.tables.type_def.len())
+            .field("type_references", &self.tables.type_ref.len())
+            .field("member_references", &self.tables.member_ref.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> LazyResolution<'a> {
+    pub fn new(dll: DLL<'a>) -> Result<Self> {
+        let tables = dll.get_logical_metadata()?.tables;
+
+        Ok(LazyResolution {
+            strings: dll.get_heap()?,
+            blobs: dll.get_heap()?,
+            userstrings: dll.get_heap()?,
+            members: OnceCell::new(),
+            references: OnceCell::new(),
+            associations: OnceCell::new(),
+            type_definitions: std::iter::repeat_with(OnceCell::new)
+                .take(tables.type_def.len())
+                .collect(),
+            type_references: std::iter::repeat_with(OnceCell::new)
+                .take(tables.type_ref.len())
+                .collect(),
+            field_references: std::iter::repeat_with(OnceCell::new)
+                .take(tables.member_ref.len())
+                .collect(),
+            method_references: std::iter::repeat_with(OnceCell::new)
+                .take(tables.member_ref.len())
+                .collect(),
+            method_bodies: std::iter::repeat_with(OnceCell::new)
+                .take(tables.method_def.len())
+                .collect(),
+            tables,
+            dll,
+        })
+    }
+
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        Self::new(DLL::parse(bytes)?)
+    }
+
+    pub fn dll(&self) -> &DLL<'a> {
+        &self.dll
+    }
+
+    /// Resolves the whole DLL at once, exactly like [`DLL::resolve`].
+    ///
+    /// Everything that has already been resolved through this view is reused instead of being resolved again.
+    pub fn resolve(&self, opts: read::Options) -> Result<Resolution<'a>> {
+        read::read_impl(self, None, opts)
+    }
+
+    pub(super) fn ctx(&self) -> convert::read::Context<'_, 'a> {
+        convert::read::Context {
+            def_len: self.tables.type_def.len(),
+            ref_len: self.tables.type_ref.len(),
+            specs: &self.tables.type_spec,
+            sigs: &self.tables.stand_alone_sig,
+            blobs: &self.blobs,
+            userstrings: &self.userstrings,
+        }
+    }
+
+    pub(super) fn method_ctx(&self) -> Result<convert::read::MethodContext<'_>> {
+        let members = self.members()?;
+        let references = self.references()?;
+
+        Ok(convert::read::MethodContext {
+            field_map: &references.field_map,
+            field_indices: &members.fields,
+            method_specs: &self.tables.method_spec,
+            method_indices: &members.methods,
+            method_map: &references.method_map,
+        })
+    }
+
+    pub fn type_definition_count(&self) -> usize {
+        self.tables.type_def.len()
+    }
+
+    pub fn type_definition_index(&self, index: usize) -> Option<TypeIndex> {
+        (index < self.tables.type_def.len()).then_some(TypeIndex(index))
+    }
+
+    /// Finds a type definition by its name, only reading the names of the other types.
+    ///
+    /// The first matching type is returned, including nested types, which have no namespace of their own.
+    pub fn find_type(&self, namespace: Option<&str>, name: &str) -> Result<Option<TypeIndex>> {
+        for (idx, t) in self.tables.type_def.iter().enumerate() {
+            if self.strings.at_index(t.type_name)? != name {
+                continue;
+            }
+            let t_namespace = if t.type_namespace.is_null() {
+                None
+            } else {
+                Some(self.strings.at_index(t.type_namespace)?)
+            };
+            if t_namespace == namespace {
+                return Ok(Some(TypeIndex(idx)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Resolves a type definition along with all of its members, except for method bodies.
+    pub fn type_definition(&self, index: TypeIndex) -> Result<&TypeDefinition<'a>> {
+        match self.type_definitions.get(index.0) {
+            Some(cell) => memoize(cell, || self.build_type_definition(index.0)),
+            None => throw_index!(TypeDef, index.0, ErrorContext::new("lazy type definition")),
+        }
+    }
+
+    pub fn field(&self, index: FieldIndex) -> Result<&Field<'a>> {
+        match self.type_definition(index.parent_type)?.fields.get(index.field) {
+            Some(f) => Ok(f),
+            None => throw!("invalid field index {:?}", index),
+        }
+    }
+
+    pub fn method(&self, index: MethodIndex) -> Result<&Method<'a>> {
+        use MethodMemberIndex::*;
+
+        let parent = self.type_definition(index.parent_type)?;
+        let method = match index.member {
+            Method(i) => parent.methods.get(i),
+            PropertyGetter(i) => parent.properties.get(i).and_then(|p| p.getter.as_ref()),
+            PropertySetter(i) => parent.properties.get(i).and_then(|p| p.setter.as_ref()),
+            PropertyOther { property, other } => parent.properties.get(property).and_then(|p| p.other.get(other)),
+            EventAdd(i) => parent.events.get(i).map(|e| &e.add_listener),
+            EventRemove(i) => parent.events.get(i).map(|e| &e.remove_listener),
+            EventRaise(i) => parent.events.get(i).and_then(|e| e.raise_event.as_ref()),
+            EventOther { event, other } => parent.events.get(event).and_then(|e| e.other.get(other)),
+        };
+
+        match method {
+            Some(m) => Ok(m),
+            None => throw!("invalid method index {:?}", index),
+        }
+    }
+
+    /// Resolves the body of a method, which is `None` for methods without one (like abstract methods).
+    pub fn method_body(&self, index: MethodIndex) -> Result<Option<&body::Method>> {
+        let Some(row) = self.method_row(index)? else {
+            throw!("invalid method index {:?}", index)
+        };
+
+        memoize(&self.method_bodies[row], || {
+            let m = &self.tables.method_def[row];
+            if m.rva == 0 {
+                return Ok(None);
+            }
+
+            let context = ErrorContext::new("method body")
+                .in_type(
+                    self.strings
+                        .at_index(self.tables.type_def[index.parent_type.0].type_name)?,
+                )
+                .in_member(self.strings.at_index(m.name)?);
+
+            let (body, ..) = read::method_body(
+                &self.dll,
+                m,
+                read::method_token(row),
+                &context,
+                &self.ctx(),
+                &self.method_ctx()?,
+            )?;
+            Ok(Some(body))
+        })
+        .map(Option::as_ref)
+    }
+
+    pub fn type_reference(&self, index: TypeRefIndex) -> Result<&ExternalTypeReference<'a>> {
+        let Some(cell) = self.type_references.get(index.0) else {
+            throw_index!(TypeRef, index.0, ErrorContext::new("lazy type reference"))
+        };
+
+        memoize(cell, || self.build_type_reference(index.0))
+    }
+
+    pub fn field_reference(&self, index: FieldRefIndex) -> Result<&ExternalFieldReference<'a>> {
+        let Some(&row) = self.references()?.fields.get(index.0) else {
+            throw!("invalid field reference index {:?}", index)
+        };
+
+        memoize(&self.field_references[row], || self.build_field_reference(row))
+    }
+
+    pub fn method_reference(&self, index: MethodRefIndex) -> Result<&ExternalMethodReference<'a>> {
+        let Some(&row) = self.references()?.methods.get(index.0) else {
+            throw!("invalid method reference index {:?}", index)
+        };
+
+        memoize(&self.method_references[row], || self.build_method_reference(row))
+    }
+
+    // these give read_impl the values that have already been resolved, and resolve the rest without keeping them,
+    // since the Resolution it builds owns them anyway
+
+    pub(super) fn owned_type_definition(&self, index: usize) -> Result<TypeDefinition<'a>> {
+        owned(&self.type_definitions[index], || self.build_type_definition(index))
+    }
+
+    pub(super) fn owned_type_reference(&self, row: usize) -> Result<ExternalTypeReference<'a>> {
+        owned(&self.type_references[row], || self.build_type_reference(row))
+    }
+
+    pub(super) fn owned_field_reference(&self, row: usize) -> Result<ExternalFieldReference<'a>> {
+        owned(&self.field_references[row], || self.build_field_reference(row))
+    }
+
+    pub(super) fn owned_method_reference(&self, row: usize) -> Result<ExternalMethodReference<'a>> {
+        owned(&self.method_references[row], || self.build_method_reference(row))
+    }
+
+    // bodies are decoded by read_impl itself, since it may decode them in parallel and attach debugging information
+    pub(super) fn resolved_method_body(&self, row: usize) -> Option<&Option<body::Method>> {
+        self.method_bodies[row].get()
+    }
+
+    fn method_row(&self, index: MethodIndex) -> Result<Option<usize>> {
+        use MethodMemberIndex::*;
+
+        let Some(t) = self.members()?.types.get(index.parent_type.0) else {
+            return Ok(None);
+        };
+        Ok(match index.member {
+            Method(i) => t.methods.get(i).copied(),
+            PropertyGetter(i) => t.properties.get(i).and_then(|p| p.getter),
+            PropertySetter(i) => t.properties.get(i).and_then(|p| p.setter),
+            PropertyOther { property, other } => t.properties.get(property).and_then(|p| p.other.get(other).copied()),
+            EventAdd(i) => t.events.get(i).map(|e| e.add),
+            EventRemove(i) => t.events.get(i).map(|e| e.remove),
+            EventRaise(i) => t.events.get(i).and_then(|e| e.raise),
+            EventOther { event, other } => t.events.get(event).and_then(|e| e.other.get(other).copied()),
+        })
+    }
+
+    // which rows make up each type, without decoding any names or signatures
+    #[allow(clippy::too_many_lines)]
+    pub(super) fn members(&self) -> Result<&Members> {
+        memoize(&self.members, || {
+            use metadata::index::HasSemantics;
+
+            let tables = &self.tables;
+
+            macro_rules! ptrs {
+                ($table:ident.$field:ident) => {
+                    tables.$table.iter().map(|p| p.$field.0).collect::<Vec<_>>()
+                };
+            }
+            macro_rules! lists {
+                ($table:ident.$field:ident) => {
+                    tables.$table.iter().map(|r| r.$field.0).collect::<Vec<_>>()
+                };
+            }
+
+            let mut types: Vec<TypeRows> = std::iter::repeat_with(TypeRows::default)
+                .take(tables.type_def.len())
+                .collect();
+
+            let field_lists = lists!(type_def.field_list);
+            let field_ptrs = ptrs!(field_ptr.field);
+            let method_lists = lists!(type_def.method_list);
+            let method_ptrs = ptrs!(method_ptr.method);
+            let mut fields = vec![None; tables.field.len()];
+            let mut method_parents = vec![None; tables.method_def.len()];
+            for (idx, t) in types.iter_mut().enumerate() {
+                t.fields = owned_rows(
+                    &field_lists,
+                    idx,
+                    tables.field.len(),
+                    &field_ptrs,
+                    (Kind::Field, Kind::FieldPtr),
+                    ("field", "type_def"),
+                )?;
+                for (i, &f) in t.fields.iter().enumerate() {
+                    fields[f] = Some(FieldIndex {
+                        parent_type: TypeIndex(idx),
+                        field: i,
+                    });
+                }
+
+                t.methods = owned_rows(
+                    &method_lists,
+                    idx,
+                    tables.method_def.len(),
+                    &method_ptrs,
+                    (Kind::MethodDef, Kind::MethodPtr),
+                    ("method_def", "type_def"),
+                )?;
+                for &m in &t.methods {
+                    method_parents[m] = Some(idx);
+                }
+            }
+
+            let param_lists = lists!(method_def.param_list);
+            let param_ptrs = ptrs!(param_ptr.param);
+            let params = (0..tables.method_def.len())
+                .map(|m| {
+                    owned_rows(
+                        &param_lists,
+                        m,
+                        tables.param.len(),
+                        &param_ptrs,
+                        (Kind::Param, Kind::ParamPtr),
+                        ("param", "method_def"),
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut properties = HashMap::new();
+            let property_lists = lists!(property_map.property_list);
+            let property_ptrs = ptrs!(property_ptr.property);
+            for (map_idx, map) in tables.property_map.iter().enumerate() {
+                let type_idx = map.parent.0 - 1;
+                let Some(t) = types.get_mut(type_idx) else {
+                    throw_index!(
+                        TypeDef,
+                        type_idx,
+                        ErrorContext::new(format!("property map {}", map_idx))
+                    )
+                };
+                for row in owned_rows(
+                    &property_lists,
+                    map_idx,
+                    tables.property.len(),
+                    &property_ptrs,
+                    (Kind::Property, Kind::PropertyPtr),
+                    ("property", "property_map"),
+                )? {
+                    t.properties.push(PropertyRows {
+                        row,
+                        ..PropertyRows::default()
+                    });
+                    properties.insert(row, (type_idx, t.properties.len() - 1));
+                }
+            }
+
+            // semantics are assigned to each method at most once, and the rest become accessors
+            let mut accessors = HashMap::new();
+            let mut extract = |types: &mut [TypeRows], type_idx: usize, m_idx: usize, member: MethodMemberIndex| {
+                match types[type_idx].methods.iter().position(|&m| m == m_idx) {
+                    Some(pos) => {
+                        types[type_idx].methods.swap_remove(pos);
+                        accessors.insert(m_idx, member);
+                        Ok(())
+                    }
+                    None => throw!(
+                        "method {} cannot be an accessor of type {} more than once",
+                        m_idx,
+                        type_idx
+                    ),
+                }
+            };
+
+            let mut semantics: Vec<_> = tables.method_semantics.iter().collect();
+            let mut events = HashMap::new();
+            let event_lists = lists!(event_map.event_list);
+            let event_ptrs = ptrs!(event_ptr.event);
+            for (map_idx, map) in tables.event_map.iter().enumerate() {
+                let type_idx = map.parent.0 - 1;
+                if type_idx >= types.len() {
+                    throw_index!(TypeDef, type_idx, ErrorContext::new(format!("event map {}", map_idx)));
+                }
+
+                for e_idx in owned_rows(
+                    &event_lists,
+                    map_idx,
+                    tables.event.len(),
+                    &event_ptrs,
+                    (Kind::Event, Kind::EventPtr),
+                    ("event", "event_map"),
+                )? {
+                    let internal_idx = types[type_idx].events.len();
+
+                    let mut listener = |l_name: &str, flag: u16, member: MethodMemberIndex| {
+                        let name = || self.strings.at_index(tables.event[e_idx].name);
+                        let Some(position) = semantics.iter().position(|s| {
+                            s.semantics & flag == flag
+                                && matches!(s.association, HasSemantics::Event(e) if e_idx == e - 1)
+                        }) else {
+                            throw!("could not find {} listener for event {}", l_name, name()?)
+                        };
+                        let m_idx = semantics.remove(position).method.0 - 1;
+                        if m_idx >= tables.method_def.len() {
+                            throw_index!(
+                                MethodDef,
+                                m_idx,
+                                ErrorContext::new(format!("{} listener", l_name))
+                                    .in_type(self.strings.at_index(tables.type_def[type_idx].type_name)?)
+                                    .in_member(name()?)
+                            );
+                        }
+                        extract(&mut types, type_idx, m_idx, member)?;
+                        Ok(m_idx)
+                    };
+
+                    let add = listener("add", 0x8, MethodMemberIndex::EventAdd(internal_idx))?;
+                    let remove = listener("remove", 0x10, MethodMemberIndex::EventRemove(internal_idx))?;
+
+                    types[type_idx].events.push(EventRows {
+                        row: e_idx,
+                        add,
+                        remove,
+                        ..EventRows::default()
+                    });
+                    events.insert(e_idx, (type_idx, internal_idx));
+                }
+            }
+
+            for s in semantics {
+                let m_idx = s.method.0 - 1;
+                let Some(&Some(type_idx)) = method_parents.get(m_idx) else {
+                    throw_index!(MethodDef, m_idx, ErrorContext::new("method semantics"))
+                };
+
+                match s.association {
+                    HasSemantics::Event(i) => {
+                        let idx = i - 1;
+                        let Some(&(_, internal_idx)) = events.get(&idx) else {
+                            throw_index!(Event, idx, ErrorContext::new("method semantics"))
+                        };
+
+                        if check_bitmask!(s.semantics, 0x20) {
+                            extract(&mut types, type_idx, m_idx, MethodMemberIndex::EventRaise(internal_idx))?;
+                            types[type_idx].events[internal_idx].raise = Some(m_idx);
+                        } else if check_bitmask!(s.semantics, 0x4) {
+                            let event = &types[type_idx].events[internal_idx];
+                            let member = MethodMemberIndex::EventOther {
+                                event: internal_idx,
+                                other: event.other.len(),
+                            };
+                            extract(&mut types, type_idx, m_idx, member)?;
+                            types[type_idx].events[internal_idx].other.push(m_idx);
+                        }
+                    }
+                    HasSemantics::Property(i) => {
+                        let idx = i - 1;
+                        let Some(&(_, internal_idx)) = properties.get(&idx) else {
+                            throw_index!(Property, idx, ErrorContext::new("method semantics"))
+                        };
+
+                        if check_bitmask!(s.semantics, 0x1) {
+                            extract(
+                                &mut types,
+                                type_idx,
+                                m_idx,
+                                MethodMemberIndex::PropertySetter(internal_idx),
+                            )?;
+                            types[type_idx].properties[internal_idx].setter = Some(m_idx);
+                        } else if check_bitmask!(s.semantics, 0x2) {
+                            extract(
+                                &mut types,
+                                type_idx,
+                                m_idx,
+                                MethodMemberIndex::PropertyGetter(internal_idx),
+                            )?;
+                            types[type_idx].properties[internal_idx].getter = Some(m_idx);
+                        } else if check_bitmask!(s.semantics, 0x4) {
+                            let property = &types[type_idx].properties[internal_idx];
+                            let member = MethodMemberIndex::PropertyOther {
+                                property: internal_idx,
+                                other: property.other.len(),
+                            };
+                            extract(&mut types, type_idx, m_idx, member)?;
+                            types[type_idx].properties[internal_idx].other.push(m_idx);
+                        }
+                    }
+                    HasSemantics::Null => {
+                        return Err(DLLError::unresolvable(
+                            "HasSemantics coded index",
+                            ErrorContext::new("method semantics"),
+                        ))
+                    }
+                }
+            }
+
+            for t in &types {
+                for (i, &m) in t.methods.iter().enumerate() {
+                    accessors.insert(m, MethodMemberIndex::Method(i));
+                }
+            }
+
+            Ok(Members {
+                fields: fields
+                    .into_iter()
+                    .enumerate()
+                    .map(|(f, idx)| match idx {
+                        Some(idx) => Ok(idx),
+                        None => throw!("field {} does not belong to any type", f),
+                    })
+                    .collect::<Result<_>>()?,
+                methods: method_parents
+                    .into_iter()
+                    .enumerate()
+                    .map(|(m, parent)| match (parent, accessors.get(&m)) {
+                        (Some(parent), Some(&member)) => Ok(MethodIndex {
+                            parent_type: TypeIndex(parent),
+                            member,
+                        }),
+                        _ => throw!("method {} does not belong to any type", m),
+                    })
+                    .collect::<Result<_>>()?,
+                params,
+                types,
+            })
+        })
+    }
+
+    pub(super) fn references(&self) -> Result<&References> {
+        memoize(&self.references, || {
+            use metadata::index::MemberRefParent;
+
+            let mut references = References::default();
+            for (row, r) in self.tables.member_ref.iter().enumerate() {
+                let blob = self.blobs.at_index(r.signature)?;
+                let field_parent = matches!(
+                    r.class,
+                    MemberRefParent::TypeDef(_)
+                        | MemberRefParent::TypeRef(_)
+                        | MemberRefParent::TypeSpec(_)
+                        | MemberRefParent::ModuleRef(_)
+                );
+
+                if field_parent && blob.pread::<FieldSig>(0).is_ok() {
+                    references.field_map.insert(row, references.fields.len());
+                    references.fields.push(row);
+                } else if blob.pread::<MethodRefSig>(0).is_ok() {
+                    references.method_map.insert(row, references.methods.len());
+                    references.methods.push(row);
+                }
+            }
+
+            Ok(references)
+        })
+    }
+
+    fn associations(&self) -> &Associations {
+        self.associations.get_or_init(|| {
+            let tables = &self.tables;
+            let mut a = Associations::default();
+
+            // the first class layout is used, while later rows of the other tables replace earlier ones
+            for (idx, l) in tables.class_layout.iter().enumerate() {
+                a.class_layouts.entry(l.parent.0 - 1).or_insert(idx);
+            }
+            for n in &tables.nested_class {
+                a.enclosers.insert(n.nested_class.0 - 1, n.enclosing_class.0 - 1);
+            }
+            for (idx, i) in tables.interface_impl.iter().enumerate() {
+                a.interfaces.entry(i.class.0 - 1).or_default().push(idx);
+            }
+            for (idx, l) in tables.field_layout.iter().enumerate() {
+                a.field_layouts.insert(l.field.0 - 1, idx);
+            }
+            for (idx, r) in tables.field_rva.iter().enumerate() {
+                a.field_rvas.insert(r.field.0 - 1, idx);
+            }
+            for (idx, c) in tables.constant.iter().enumerate() {
+                a.constants.insert(c.parent, idx);
+            }
+            for (idx, m) in tables.field_marshal.iter().enumerate() {
+                a.marshals.insert(m.parent, idx);
+            }
+            for (idx, i) in tables.impl_map.iter().enumerate() {
+                a.pinvokes.insert(i.member_forwarded, idx);
+            }
+            for (idx, s) in tables.decl_security.iter().enumerate() {
+                a.security.insert(s.parent, idx);
+            }
+            for (idx, g) in tables.generic_param.iter().enumerate() {
+                a.generic_params.entry(g.owner).or_default().push(idx);
+            }
+            for (idx, c) in tables.generic_param_constraint.iter().enumerate() {
+                a.constraints.entry(c.owner.0 - 1).or_default().push(idx);
+            }
+            for (idx, i) in tables.method_impl.iter().enumerate() {
+                a.overrides.entry(i.class.0 - 1).or_default().push(idx);
+            }
+            for (idx, c) in tables.custom_attribute.iter().enumerate() {
+                a.attributes.entry(c.parent).or_default().push(idx);
+            }
+
+            a
+        })
+    }
+
+    pub(super) fn attributes(&self, parent: HasCustomAttribute) -> Result<Vec<Attribute<'a>>> {
+        let Some(rows) = self.associations().attributes.get(&parent) else {
+            return Ok(vec![]);
+        };
+        let methods = &self.members()?.methods;
+        let method_map = &self.references()?.method_map;
+
+        rows.iter()
+            .map(|&idx| {
+                read::attribute(
+                    &self.tables.custom_attribute[idx],
+                    idx,
+                    methods,
+                    method_map,
+                    &self.blobs,
+                )
+            })
+            .collect()
+    }
+
+    pub(super) fn security(&self, parent: HasDeclSecurity) -> Result<Option<SecurityDeclaration<'a>>> {
+        let Some(&idx) = self.associations().security.get(&parent) else {
+            return Ok(None);
+        };
+        let s = &self.tables.decl_security[idx];
+
+        Ok(Some(SecurityDeclaration {
+            attributes: self.attributes(HasCustomAttribute::DeclSecurity(idx + 1))?,
+            action: s.action,
+            value: self.blobs.at_index(s.permission_set)?.into(),
+        }))
+    }
+
+    fn constant(&self, parent: HasConstant) -> Result<Option<Constant>> {
+        match self.associations().constants.get(&parent) {
+            Some(&idx) => Ok(Some(read::constant(&self.tables.constant[idx], idx, &self.blobs)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn marshal(&self, parent: HasFieldMarshal) -> Result<Option<MarshalSpec>> {
+        match self.associations().marshals.get(&parent) {
+            Some(&idx) => {
+                let blob = self.tables.field_marshal[idx].native_type;
+                Ok(Some(self.blobs.at_index(blob)?.pread(0).map_err(|e| {
+                    DLLError::bad_signature(blob.0, e, ErrorContext::new("field marshal"))
+                })?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn pinvoke(&self, parent: MemberForwarded) -> Result<Option<PInvoke<'a>>> {
+        match self.associations().pinvokes.get(&parent) {
+            Some(&idx) => Ok(Some(read::pinvoke(
+                &self.tables.impl_map[idx],
+                self.tables.module_ref.len(),
+                &self.strings,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    fn generic_parameters<T: convert::TypeKind>(
+        &self,
+        owner: TypeOrMethodDef,
+    ) -> Result<Vec<crate::resolved::generic::Generic<'a, T>>> {
+        let Some(rows) = self.associations().generic_params.get(&owner) else {
+            return Ok(vec![]);
+        };
+
+        rows.iter()
+            .map(|&param_idx| {
+                let p = &self.tables.generic_param[param_idx];
+                let constraints = self
+                    .associations()
+                    .constraints
+                    .get(&param_idx)
+                    .map_or(&[][..], Vec::as_slice);
+
+                let mut constraint_map = HashMap::new();
+                let mut generic = read::make_generic(
+                    self.strings.at_index(p.name)?.into(),
+                    p,
+                    param_idx,
+                    &mut constraint_map,
+                    constraints
+                        .iter()
+                        .map(|&c| (c, &self.tables.generic_param_constraint[c])),
+                    &self.ctx(),
+                )?;
+
+                generic.attributes = self.attributes(HasCustomAttribute::GenericParam(param_idx + 1))?;
+                for (c_idx, (_, internal)) in constraint_map {
+                    generic.type_constraints[internal].attributes =
+                        self.attributes(HasCustomAttribute::GenericParamConstraint(c_idx + 1))?;
+                }
+
+                Ok(generic)
+            })
+            .collect()
+    }
+
+    fn build_type_reference(&self, row: usize) -> Result<ExternalTypeReference<'a>> {
+        let mut r = read::type_reference(
+            &self.tables.type_ref[row],
+            self.tables.module_ref.len(),
+            self.tables.assembly_ref.len(),
+            self.tables.type_ref.len(),
+            &self.strings,
+        )?;
+        r.attributes = self.attributes(HasCustomAttribute::TypeRef(row + 1))?;
+        Ok(r)
+    }
+
+    fn build_field_reference(&self, row: usize) -> Result<ExternalFieldReference<'a>> {
+        let r = &self.tables.member_ref[row];
+        let Some(field) =
+            read::field_reference(r, self.tables.module_ref.len(), &self.strings, &self.blobs, &self.ctx())
+        else {
+            throw!("member reference {} is not a field reference", row)
+        };
+        let mut field = field?;
+        field.attributes = self.attributes(HasCustomAttribute::MemberRef(row + 1))?;
+        Ok(field)
+    }
+
+    fn build_method_reference(&self, row: usize) -> Result<ExternalMethodReference<'a>> {
+        let r = &self.tables.member_ref[row];
+        let Some(method) = read::method_reference(
+            r,
+            self.tables.module_ref.len(),
+            &self.members()?.methods,
+            &self.strings,
+            &self.blobs,
+            &self.ctx(),
+        ) else {
+            throw!("member reference {} is not a method reference", row)
+        };
+        let mut method = method?;
+        method.attributes = self.attributes(HasCustomAttribute::MemberRef(row + 1))?;
+        Ok(method)
+    }
+
+    fn build_method(&self, row: usize, type_name: &str) -> Result<Method<'a>> {
+        let m = &self.tables.method_def[row];
+        let mut method = read::method(m, type_name, &self.strings, &self.blobs, &self.ctx())?;
+
+        method.attributes = self.attributes(HasCustomAttribute::MethodDef(row + 1))?;
+        method.pinvoke = self.pinvoke(MemberForwarded::MethodDef(row + 1))?;
+        method.security = self.security(HasDeclSecurity::MethodDef(row + 1))?;
+        method.generic_parameters = self.generic_parameters(TypeOrMethodDef::MethodDef(row + 1))?;
+
+        for &p_idx in &self.members()?.params[row] {
+            let sequence = read::parameter(&mut method, &self.tables.param[p_idx], &self.strings)?;
+            let param_meta = if sequence == 0 {
+                &mut method.return_type_metadata
+            } else {
+                &mut method.parameter_metadata[sequence - 1]
+            };
+            let param_meta = param_meta.as_mut().unwrap();
+
+            param_meta.attributes = self.attributes(HasCustomAttribute::Param(p_idx + 1))?;
+            param_meta.marshal = self.marshal(HasFieldMarshal::Param(p_idx + 1))?;
+            param_meta.default = self.constant(HasConstant::Param(p_idx + 1))?;
+        }
+
+        Ok(method)
+    }
+
+    fn build_type_definition(&self, idx: usize) -> Result<TypeDefinition<'a>> {
+        let tables = &self.tables;
+        let ctx = self.ctx();
+        let members = self.members()?;
+        let associations = self.associations();
+        let class_layout = |t: usize| associations.class_layouts.get(&t).map(|&l| &tables.class_layout[l]);
+
+        let mut t = read::type_definition(&tables.type_def[idx], || class_layout(idx), &self.strings, &ctx)?;
+        let type_name = t.name.to_string();
+
+        t.attributes = self.attributes(HasCustomAttribute::TypeDef(idx + 1))?;
+        t.security = self.security(HasDeclSecurity::TypeDef(idx + 1))?;
+        t.generic_parameters = self.generic_parameters(TypeOrMethodDef::TypeDef(idx + 1))?;
+
+        if let Some(&enclose_idx) = associations.enclosers.get(&idx) {
+            if enclose_idx < tables.type_def.len() {
+                t.encloser = Some(TypeIndex(enclose_idx));
+            } else {
+                throw_index!(
+                    TypeDef,
+                    enclose_idx,
+                    ErrorContext::new("nested class declaration").in_type(type_name)
+                );
+            }
+        }
+
+        for &i in associations.interfaces.get(&idx).into_iter().flatten() {
+            t.implements.push((
+                self.attributes(HasCustomAttribute::InterfaceImpl(i + 1))?,
+                convert::read::type_source(tables.interface_impl[i].interface, &ctx)?,
+            ));
+        }
+
+        let rows = &members.types[idx];
+
+        for &f_idx in &rows.fields {
+            let mut field = read::field(&tables.field[f_idx], &type_name, &self.strings, &self.blobs, &ctx)?;
+
+            field.attributes = self.attributes(HasCustomAttribute::Field(f_idx + 1))?;
+            field.offset = associations
+                .field_layouts
+                .get(&f_idx)
+                .map(|&l| tables.field_layout[l].offset as usize);
+            if let Some(&r) = associations.field_rvas.get(&f_idx) {
+                let data = self.dll.raw_rva(tables.field_rva[r].rva)?;
+                let data = match read::static_size(&field.return_type, class_layout) {
+                    Some(size) if size <= data.len() => &data[..size],
+                    _ => data,
+                };
+                field.initial_value = Some(data.into());
+            }
+            field.pinvoke = self.pinvoke(MemberForwarded::Field(f_idx + 1))?;
+            field.default = self.constant(HasConstant::Field(f_idx + 1))?;
+            field.marshal = self.marshal(HasFieldMarshal::Field(f_idx + 1))?;
+
+            t.fields.push(field);
+        }
+
+        for &m_idx in &rows.methods {
+            t.methods.push(self.build_method(m_idx, &type_name)?);
+        }
+
+        for p in &rows.properties {
+            let mut property = read::property(&tables.property[p.row], &type_name, &self.strings, &self.blobs, &ctx)?;
+
+            property.attributes = self.attributes(HasCustomAttribute::Property(p.row + 1))?;
+            property.default = self.constant(HasConstant::Property(p.row + 1))?;
+            property.getter = p.getter.map(|m| self.build_method(m, &type_name)).transpose()?;
+            property.setter = p.setter.map(|m| self.build_method(m, &type_name)).transpose()?;
+            property.other = p
+                .other
+                .iter()
+                .map(|&m| self.build_method(m, &type_name))
+                .collect::<Result<_>>()?;
+
+            t.properties.push(property);
+        }
+
+        for e in &rows.events {
+            let event = &tables.event[e.row];
+
+            t.events.push(Event {
+                attributes: self.attributes(HasCustomAttribute::Event(e.row + 1))?,
+                name: self.strings.at_index(event.name)?.into(),
+                delegate_type: convert::read::type_idx(event.event_type, &ctx)?,
+                add_listener: self.build_method(e.add, &type_name)?,
+                remove_listener: self.build_method(e.remove, &type_name)?,
+                raise_event: e.raise.map(|m| self.build_method(m, &type_name)).transpose()?,
+                other: e
+                    .other
+                    .iter()
+                    .map(|&m| self.build_method(m, &type_name))
+                    .collect::<Result<_>>()?,
+                special_name: check_bitmask!(event.event_flags, 0x200),
+                runtime_special_name: check_bitmask!(event.event_flags, 0x400),
+            });
+        }
+
+        if let Some(overrides) = associations.overrides.get(&idx) {
+            let m_ctx = self.method_ctx()?;
+            for &o in overrides {
+                let i = &tables.method_impl[o];
+                t.overrides.push(MethodOverride {
+                    implementation: convert::read::user_method(i.method_body, &m_ctx)?,
+                    declaration: convert::read::user_method(i.method_declaration, &m_ctx)?,
+                });
+            }
+        }
+
+        Ok(t)
+    }
+}
@@ -0,0 +1,301 @@
+//! Hash indexes for finding the members of a [`Resolution`] by name.
+//!
+//! Types are identified by their full name, with nested types separated from their enclosing type by a `/`
+//! (for example, `System.Environment/SpecialFolder`). Method signatures are compared by the names of the types they
+//! mention rather than by their indices, so that a reference from one [`Resolution`] can be matched against a definition
+//! in another.
+
+use super::{
+    FieldIndex, FieldRefIndex, MethodIndex, MethodMemberIndex, MethodRefIndex, Resolution, TypeIndex, TypeRefIndex,
+};
+use crate::resolved::{
+    members::{FieldReferenceParent, Method, MethodReferenceParent},
+    signature::{ManagedMethod, Parameter, ParameterType, ReturnType},
+    types::*,
+    ResolvedDebug,
+};
+use std::{collections::HashMap, fmt::Write};
+
+/// Indexes the types, methods and fields defined by a [`Resolution`].
+///
+/// The indexes are built once by [`Lookup::new`], so the resolution cannot be modified while a lookup borrows it.
+#[derive(Debug)]
+pub struct Lookup<'r, 'a> {
+    res: &'r Resolution<'a>,
+    types: HashMap<String, TypeIndex>,
+    methods: HashMap<TypeIndex, HashMap<&'r str, Vec<MethodIndex>>>,
+    fields: HashMap<TypeIndex, HashMap<&'r str, FieldIndex>>,
+}
+
+impl<'r, 'a> Lookup<'r, 'a> {
+    pub fn new(res: &'r Resolution<'a>) -> Self {
+        let mut types = HashMap::with_capacity(res.type_definitions.len());
+        let mut methods = HashMap::with_capacity(res.type_definitions.len());
+        let mut fields = HashMap::with_capacity(res.type_definitions.len());
+
+        for (index, t) in res.enumerate_type_definitions() {
+            types.insert(definition_name(res, index), index);
+
+            let mut type_fields = HashMap::with_capacity(t.fields.len());
+            for (field, f) in res.enumerate_fields(index) {
+                type_fields.entry(f.name.as_ref()).or_insert(field);
+            }
+            fields.insert(index, type_fields);
+
+            let mut type_methods: HashMap<_, Vec<_>> = HashMap::with_capacity(t.methods.len());
+            for (member, m) in all_methods(t) {
+                type_methods.entry(m.name.as_ref()).or_default().push(MethodIndex {
+                    parent_type: index,
+                    member,
+                });
+            }
+            methods.insert(index, type_methods);
+        }
+
+        Self {
+            res,
+            types,
+            methods,
+            fields,
+        }
+    }
+
+    pub fn resolution(&self) -> &'r Resolution<'a> {
+        self.res
+    }
+
+    /// Finds a type by its full name, such as `System.Object` or `System.Environment/SpecialFolder`.
+    pub fn find_type(&self, full_name: &str) -> Option<TypeIndex> {
+        self.types.get(full_name).copied()
+    }
+
+    /// Returns every method on the type with the given name, including property and event accessors.
+    pub fn find_methods(&self, parent: TypeIndex, name: &str) -> impl Iterator<Item = MethodIndex> + '_ {
+        self.methods
+            .get(&parent)
+            .and_then(|m| m.get(name))
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    /// Finds the method on the type with the given name and signature.
+    ///
+    /// The types in `signature` are interpreted relative to `signature_res`, which may be a different resolution than the
+    /// one being searched.
+    pub fn find_method(
+        &self,
+        parent: TypeIndex,
+        name: &str,
+        signature: &ManagedMethod<MethodType>,
+        signature_res: &Resolution,
+    ) -> Option<MethodIndex> {
+        let key = signature_key(signature_res, signature);
+        self.find_methods(parent, name)
+            .find(|&m| signature_key(self.res, &self.res[m].signature) == key)
+    }
+
+    pub fn find_field(&self, parent: TypeIndex, name: &str) -> Option<FieldIndex> {
+        self.fields.get(&parent)?.get(name).copied()
+    }
+
+    /// Finds the definition of a type referenced by `from`.
+    ///
+    /// The resolution scope of the reference is not checked, so the caller is responsible for choosing the lookup of the
+    /// right assembly (for example, with [`AssemblyResolver::resolve_assembly`](super::resolver::AssemblyResolver::resolve_assembly)).
+    pub fn resolve_type_ref(&self, from: &Resolution, index: TypeRefIndex) -> Option<TypeIndex> {
+        self.find_type(&reference_name(from, index))
+    }
+
+    /// Finds the definition of a method referenced by `from`, matching both its name and its signature.
+    ///
+    /// Methods on generic instantiations resolve to the method on the generic type definition.
+    pub fn resolve_method_ref(&self, from: &Resolution, index: MethodRefIndex) -> Option<MethodIndex> {
+        let reference = &from[index];
+        let parent = match &reference.parent {
+            MethodReferenceParent::Type(t) => self.find_type(&user_type_name(from, parent_user_type(t)?))?,
+            MethodReferenceParent::Module(_) => self.find_type("<Module>")?,
+            MethodReferenceParent::VarargMethod(_) => return None,
+        };
+
+        self.find_method(parent, &reference.name, &reference.signature, from)
+    }
+
+    /// Finds the definition of a field referenced by `from`.
+    pub fn resolve_field_ref(&self, from: &Resolution, index: FieldRefIndex) -> Option<FieldIndex> {
+        let reference = &from[index];
+        let parent = match &reference.parent {
+            FieldReferenceParent::Type(t) => self.find_type(&user_type_name(from, parent_user_type(t)?))?,
+            FieldReferenceParent::Module(_) => self.find_type("<Module>")?,
+        };
+
+        self.find_field(parent, &reference.name)
+    }
+}
+
+fn all_methods<'r, 'a>(t: &'r TypeDefinition<'a>) -> impl Iterator<Item = (MethodMemberIndex, &'r Method<'a>)> {
+    use MethodMemberIndex::*;
+
+    let methods = t.methods.iter().enumerate().map(|(i, m)| (Method(i), m));
+    let properties = t.properties.iter().enumerate().flat_map(|(i, p)| {
+        p.getter
+            .iter()
+            .map(move |m| (PropertyGetter(i), m))
+            .chain(p.setter.iter().map(move |m| (PropertySetter(i), m)))
+            .chain(
+                p.other
+                    .iter()
+                    .enumerate()
+                    .map(move |(o, m)| (PropertyOther { property: i, other: o }, m)),
+            )
+    });
+    let events = t.events.iter().enumerate().flat_map(|(i, e)| {
+        [(EventAdd(i), &e.add_listener), (EventRemove(i), &e.remove_listener)]
+            .into_iter()
+            .chain(e.raise_event.iter().map(move |m| (EventRaise(i), m)))
+            .chain(
+                e.other
+                    .iter()
+                    .enumerate()
+                    .map(move |(o, m)| (EventOther { event: i, other: o }, m)),
+            )
+    });
+
+    methods.chain(properties).chain(events)
+}
+
+pub(super) fn definition_name(res: &Resolution, index: TypeIndex) -> String {
+    let t = &res[index];
+    match t.encloser {
+        Some(enc) => format!("{}/{}", definition_name(res, enc), t),
+        None => t.type_name(),
+    }
+}
+
+fn reference_name(res: &Resolution, index: TypeRefIndex) -> String {
+    let r = &res[index];
+    match r.scope {
+        ResolutionScope::Nested(enc) => format!("{}/{}", reference_name(res, enc), r),
+        _ => r.type_name(),
+    }
+}
+
+fn user_type_name(res: &Resolution, user: UserType) -> String {
+    match user {
+        UserType::Definition(d) => definition_name(res, d),
+        UserType::Reference(r) => reference_name(res, r),
+    }
+}
+
+fn parent_user_type(t: &MethodType) -> Option<UserType> {
+    match t.as_base()? {
+        BaseType::Type {
+            source: TypeSource::User(u) | TypeSource::Generic { base: u, .. },
+            ..
+        } => Some(*u),
+        _ => None,
+    }
+}
+
+// a rendering of a signature that only depends on the names of the types it mentions
+fn signature_key(res: &Resolution, sig: &ManagedMethod<MethodType>) -> String {
+    let mut buf = format!("{} {:?} ", sig.instance, sig.calling_convention);
+
+    let ReturnType(mods, ret) = &sig.return_type;
+    write_modifiers(res, mods, &mut buf);
+    match ret {
+        Some(p) => write_parameter_type(res, p, &mut buf),
+        None => buf.push_str("void"),
+    }
+
+    buf.push('(');
+    for Parameter(mods, p) in &sig.parameters {
+        write_modifiers(res, mods, &mut buf);
+        write_parameter_type(res, p, &mut buf);
+        buf.push(',');
+    }
+    buf.push(')');
+
+    buf
+}
+
+fn write_modifiers(res: &Resolution, mods: &[CustomTypeModifier], buf: &mut String) {
+    for m in mods {
+        match m {
+            CustomTypeModifier::Optional(u) => write!(buf, "modopt({}) ", user_type_name(res, *u)),
+            CustomTypeModifier::Required(u) => write!(buf, "modreq({}) ", user_type_name(res, *u)),
+        }
+        .unwrap();
+    }
+}
+
+fn write_parameter_type(res: &Resolution, p: &ParameterType<MethodType>, buf: &mut String) {
+    match p {
+        ParameterType::Value(t) => write_type(res, t, buf),
+        ParameterType::Ref(t) => {
+            write_type(res, t, buf);
+            buf.push('&');
+        }
+        ParameterType::TypedReference => buf.push_str("typedref"),
+    }
+}
+
+fn write_type(res: &Resolution, t: &MethodType, buf: &mut String) {
+    let base = match t {
+        MethodType::Base(b) => b,
+        MethodType::TypeGeneric(i) => return write!(buf, "!{}", i).unwrap(),
+        MethodType::MethodGeneric(i) => return write!(buf, "!!{}", i).unwrap(),
+    };
+
+    match &**base {
+        BaseType::Type { source, .. } => match source {
+            TypeSource::User(u) => buf.push_str(&user_type_name(res, *u)),
+            TypeSource::Generic { base, parameters } => {
+                write!(buf, "{}<", user_type_name(res, *base)).unwrap();
+                for p in parameters {
+                    write_type(res, p, buf);
+                    buf.push(',');
+                }
+                buf.push('>');
+            }
+        },
+        BaseType::Vector(mods, t) => {
+            write_type(res, t, buf);
+            write_modifiers(res, mods, buf);
+            buf.push_str("[]");
+        }
+        BaseType::Array(t, shape) => {
+            write_type(res, t, buf);
+            write!(buf, "[{}; {:?}; {:?}]", shape.rank, shape.sizes, shape.lower_bounds).unwrap();
+        }
+        BaseType::ValuePointer(mods, t) => {
+            match t {
+                Some(t) => write_type(res, t, buf),
+                None => buf.push_str("void"),
+            }
+            write_modifiers(res, mods, buf);
+            buf.push('*');
+        }
+        BaseType::FunctionPointer(sig) => {
+            write!(
+                buf,
+                "method {} {} {:?} ",
+                sig.instance, sig.explicit_this, sig.calling_convention
+            )
+            .unwrap();
+            match &sig.return_type.1 {
+                Some(p) => write_parameter_type(res, p, buf),
+                None => buf.push_str("void"),
+            }
+            buf.push('(');
+            for Parameter(mods, p) in &sig.parameters {
+                write_modifiers(res, mods, buf);
+                write_parameter_type(res, p, buf);
+                buf.push(',');
+            }
+            buf.push(')');
+        }
+        // primitive types do not mention any other types
+        other => buf.push_str(&other.show(res)),
+    }
+}
@@ -0,0 +1,1302 @@
+//! Combining several modules into one, in the manner of `ILMerge`.
+//!
+//! The first module passed to [`Resolution::merge`] is the primary module: the merged module takes its name, assembly
+//! manifest, Win32 resources and entry point from it. The types and global members of every module are concatenated, and references
+//! from one merged module to another are turned into references to the merged definitions.
+//!
+//! Custom attribute values that name types from merged assemblies, such as `typeof` arguments, are rewritten to name the
+//! merged types instead. Since decoding a value requires knowing the underlying types of the enums it uses, values that
+//! name a merged assembly and use enums from assemblies outside the merge cannot be rewritten, and are reported as a
+//! [`MergeError::AttributeValue`] error.
+
+use super::{
+    lookup::{self, Lookup},
+    AssemblyRefIndex, DocumentIndex, EntryPoint, ExportedTypeIndex, FieldIndex, FileIndex, ImportScopeIndex,
+    MethodIndex, MethodMemberIndex, ModuleRefIndex, Resolution, TypeIndex, TypeRefIndex,
+};
+use crate::resolved::{
+    assembly::{Assembly, ExternalAssemblyReference},
+    attribute::{Attribute, FixedArg, NamedArg, SecurityDeclaration},
+    body, debug, generic,
+    il::OperandVisitor,
+    members::{
+        Event, ExternalFieldReference, ExternalMethodReference, Field, FieldReferenceParent, FieldSource, Method,
+        MethodReferenceParent, MethodSource, PInvoke, ParameterMetadata, Property, UserMethod,
+    },
+    module::{ExternalModuleReference, File, Module},
+    resource::{Implementation, ManifestResource},
+    signature::{MaybeUnmanagedMethod, MethodSignature, Parameter, ParameterType, ReturnType},
+    types::*,
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("no modules to merge")]
+    NoModules,
+    #[error("types are defined by more than one module: {}", .0.join(", "))]
+    DuplicateTypes(Vec<String>),
+    #[error("could not find {member} in merged assembly {assembly}")]
+    UnresolvedReference { member: String, assembly: String },
+    #[error("could not rewrite a custom attribute value that names a merged assembly: {0}")]
+    AttributeValue(String),
+}
+
+type Result<T> = std::result::Result<T, MergeError>;
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Options {
+    /// If this flag is set, the public types of every module other than the primary one are made internal.
+    ///
+    /// Internal types whose names conflict with a type from an earlier module are renamed to `<Assembly>Name`,
+    /// whereas conflicting public types are reported as a [`MergeError::DuplicateTypes`] error.
+    ///
+    /// [`Default`] value of `false`.
+    pub internalize: bool,
+}
+
+// how the indices of a single input module translate into the merged module
+#[derive(Default)]
+struct Mapping {
+    types: Vec<TypeIndex>,
+    // offsets of the input's global methods, fields, properties and events within the merged <Module> type
+    globals: [usize; 4],
+    type_refs: Vec<UserType>,
+    method_refs: Vec<UserMethod>,
+    field_refs: Vec<FieldSource>,
+    assembly_refs: Vec<Option<AssemblyRefIndex>>,
+    module_refs: Vec<ModuleRefIndex>,
+    files: Vec<FileIndex>,
+    documents: Vec<DocumentIndex>,
+    import_scopes: Vec<ImportScopeIndex>,
+    exported_types: Vec<Option<ExportedTypeIndex>>,
+}
+
+impl Mapping {
+    fn method(&self, index: MethodIndex) -> MethodIndex {
+        use MethodMemberIndex::*;
+
+        if index.parent_type.0 != 0 {
+            return MethodIndex {
+                parent_type: self.types[index.parent_type.0],
+                member: index.member,
+            };
+        }
+
+        let [methods, _, properties, events] = self.globals;
+        MethodIndex {
+            parent_type: TypeIndex(0),
+            member: match index.member {
+                Method(i) => Method(i + methods),
+                PropertyGetter(i) => PropertyGetter(i + properties),
+                PropertySetter(i) => PropertySetter(i + properties),
+                PropertyOther { property, other } => PropertyOther {
+                    property: property + properties,
+                    other,
+                },
+                EventAdd(i) => EventAdd(i + events),
+                EventRemove(i) => EventRemove(i + events),
+                EventRaise(i) => EventRaise(i + events),
+                EventOther { event, other } => EventOther {
+                    event: event + events,
+                    other,
+                },
+            },
+        }
+    }
+
+    fn field(&self, index: FieldIndex) -> FieldIndex {
+        FieldIndex {
+            parent_type: self.types[index.parent_type.0],
+            field: index.field + if index.parent_type.0 == 0 { self.globals[1] } else { 0 },
+        }
+    }
+}
+
+// the name an assembly is referenced by, for modules that define one
+fn assembly_name<'r>(res: &'r Resolution) -> Option<&'r str> {
+    res.assembly.as_ref().map(|a| a.name.as_ref())
+}
+
+fn type_ref_name(res: &Resolution, index: TypeRefIndex) -> String {
+    let r = &res[index];
+    match r.scope {
+        ResolutionScope::Nested(enc) => format!("{}/{}", type_ref_name(res, enc), r),
+        _ => r.type_name(),
+    }
+}
+
+fn unresolved(member: &str, assembly: &Resolution) -> MergeError {
+    MergeError::UnresolvedReference {
+        member: member.to_string(),
+        assembly: assembly_name(assembly).unwrap_or_default().to_string(),
+    }
+}
+
+// (kind, index) pairs that identify a resolution scope in the merged module, for deduplicating type references
+fn scope_key(scope: ResolutionScope) -> (u8, usize) {
+    match scope {
+        ResolutionScope::Nested(r) => (0, r.0),
+        ResolutionScope::ExternalModule(m) => (1, m.0),
+        ResolutionScope::CurrentModule => (2, 0),
+        ResolutionScope::Assembly(a) => (3, a.0),
+        ResolutionScope::Exported => (4, 0),
+    }
+}
+
+// scope, namespace and name of a type reference in the merged module
+type TypeRefKey = ((u8, usize), Option<String>, String);
+
+struct Merger<'m, 'a> {
+    modules: &'m [Resolution<'a>],
+    lookups: Vec<Lookup<'m, 'a>>,
+    // merged modules by their assembly name
+    merged: HashMap<&'m str, usize>,
+    mappings: Vec<Mapping>,
+    out: Resolution<'a>,
+    // the input module that each element of the merged module's lists was copied from
+    type_ref_origins: Vec<usize>,
+    method_ref_origins: Vec<usize>,
+    field_ref_origins: Vec<usize>,
+    assembly_ref_origins: Vec<usize>,
+    module_ref_origins: Vec<usize>,
+    import_scope_origins: Vec<usize>,
+    // where each type reference from an input module points, if it is defined by another merged module
+    type_ref_targets: Vec<Vec<Option<(usize, TypeIndex)>>>,
+}
+
+impl Merger<'_, '_> {
+    fn map_types(&mut self) {
+        let mut globals = [0; 4];
+
+        for (i, res) in self.modules.iter().enumerate() {
+            let mapping = &mut self.mappings[i];
+            mapping.globals = globals;
+
+            for (index, t) in res.enumerate_type_definitions() {
+                if index.0 == 0 {
+                    // every module's globals are appended to the members of the merged <Module> type by copy
+                    globals[0] += t.methods.len();
+                    globals[1] += t.fields.len();
+                    globals[2] += t.properties.len();
+                    globals[3] += t.events.len();
+                    if i == 0 {
+                        self.out.type_definitions[0] = t.clone();
+                    }
+                    mapping.types.push(TypeIndex(0));
+                } else {
+                    mapping.types.push(self.out.push_type_definition(t.clone()));
+                }
+            }
+        }
+    }
+
+    fn check_names(&mut self, opts: Options) -> Result<()> {
+        let mut names = HashSet::new();
+        let mut duplicates = vec![];
+
+        for (i, res) in self.modules.iter().enumerate() {
+            for (index, t) in res.enumerate_type_definitions().skip(1) {
+                if t.encloser.is_some() {
+                    continue;
+                }
+
+                let merged = &mut self.out.type_definitions[self.mappings[i].types[index.0].0];
+                if opts.internalize && i > 0 && matches!(merged.flags.accessibility, Accessibility::Public) {
+                    merged.flags.accessibility = Accessibility::NotPublic;
+                }
+
+                let name = t.type_name();
+                if names.contains(&name) {
+                    if matches!(merged.flags.accessibility, Accessibility::Public) {
+                        duplicates.push(name);
+                    } else {
+                        let prefix = assembly_name(res).unwrap_or(&res.module.name);
+                        merged.name = format!("<{}>{}", prefix, t.name).into();
+                        names.insert(merged.type_name());
+                    }
+                } else {
+                    names.insert(name);
+                }
+            }
+        }
+
+        if duplicates.is_empty() {
+            Ok(())
+        } else {
+            Err(MergeError::DuplicateTypes(duplicates))
+        }
+    }
+
+    fn map_scopes(&mut self) {
+        let mut assemblies: HashMap<String, AssemblyRefIndex> = HashMap::new();
+        let mut module_refs: HashMap<String, ModuleRefIndex> = HashMap::new();
+
+        for (i, res) in self.modules.iter().enumerate() {
+            let mapping = &mut self.mappings[i];
+
+            for a in &res.assembly_references {
+                if self.merged.contains_key(a.name.as_ref()) {
+                    mapping.assembly_refs.push(None);
+                    continue;
+                }
+
+                let index = *assemblies.entry(a.name.to_string()).or_insert_with(|| {
+                    self.assembly_ref_origins.push(i);
+                    self.out.push_assembly_reference(a.clone())
+                });
+                mapping.assembly_refs.push(Some(index));
+            }
+
+            for m in &res.module_references {
+                let index = *module_refs.entry(m.name.to_string()).or_insert_with(|| {
+                    self.module_ref_origins.push(i);
+                    self.out.push_module_reference(m.clone())
+                });
+                mapping.module_refs.push(index);
+            }
+
+            for f in &res.files {
+                mapping.files.push(self.out.push_file(f.clone()));
+            }
+
+            for d in &res.documents {
+                mapping.documents.push(self.out.push_document(d.clone()));
+            }
+
+            for s in &res.import_scopes {
+                self.import_scope_origins.push(i);
+                mapping.import_scopes.push(self.out.push_import_scope(s.clone()));
+            }
+        }
+    }
+
+    // the merged module that a type reference points into, if any
+    fn merged_target(&self, i: usize, index: TypeRefIndex) -> Option<usize> {
+        let res = &self.modules[i];
+        match res[index].scope {
+            ResolutionScope::Nested(enc) => self.merged_target(i, enc),
+            ResolutionScope::Assembly(a) => self.merged.get(res[a].name.as_ref()).copied(),
+            ResolutionScope::CurrentModule => Some(i),
+            _ => None,
+        }
+    }
+
+    fn map_type_refs(&mut self) -> Result<()> {
+        let mut existing: HashMap<TypeRefKey, TypeRefIndex> = HashMap::new();
+
+        let modules = self.modules;
+        for (i, res) in modules.iter().enumerate() {
+            let mut targets = vec![None; res.type_references.len()];
+
+            // enclosing types can come after the types nested in them, so references are mapped on demand
+            let mut mapped: Vec<Option<UserType>> = vec![None; res.type_references.len()];
+            for (index, _) in res.enumerate_type_references() {
+                self.map_type_ref(i, index, &mut mapped, &mut targets, &mut existing)?;
+            }
+
+            self.mappings[i].type_refs = mapped.into_iter().map(Option::unwrap).collect();
+            self.type_ref_targets.push(targets);
+        }
+
+        Ok(())
+    }
+
+    fn map_type_ref(
+        &mut self,
+        i: usize,
+        index: TypeRefIndex,
+        mapped: &mut [Option<UserType>],
+        targets: &mut [Option<(usize, TypeIndex)>],
+        existing: &mut HashMap<TypeRefKey, TypeRefIndex>,
+    ) -> Result<UserType> {
+        if let Some(u) = mapped[index.0] {
+            return Ok(u);
+        }
+
+        let modules = self.modules;
+        let res = &modules[i];
+        let r = &res[index];
+
+        let result = if let Some(j) = self.merged_target(i, index) {
+            let t = self.lookups[j]
+                .resolve_type_ref(res, index)
+                .ok_or_else(|| unresolved(&type_ref_name(res, index), &modules[j]))?;
+            targets[index.0] = Some((j, t));
+            UserType::Definition(self.mappings[j].types[t.0])
+        } else {
+            let scope = match r.scope {
+                ResolutionScope::Nested(enc) => match self.map_type_ref(i, enc, mapped, targets, existing)? {
+                    UserType::Reference(e) => ResolutionScope::Nested(e),
+                    // merged_target would have caught a definition encloser
+                    UserType::Definition(_) => unreachable!(),
+                },
+                ResolutionScope::ExternalModule(m) => {
+                    ResolutionScope::ExternalModule(self.mappings[i].module_refs[m.0])
+                }
+                ResolutionScope::Assembly(a) => ResolutionScope::Assembly(self.mappings[i].assembly_refs[a.0].unwrap()),
+                other => other,
+            };
+
+            let key = (
+                scope_key(scope),
+                r.namespace.as_ref().map(ToString::to_string),
+                r.name.to_string(),
+            );
+            let out_index = *existing.entry(key).or_insert_with(|| {
+                self.type_ref_origins.push(i);
+                self.out.push_type_reference(r.clone())
+            });
+            UserType::Reference(out_index)
+        };
+
+        mapped[index.0] = Some(result);
+        Ok(result)
+    }
+
+    fn map_member_refs(&mut self) -> Result<()> {
+        let modules = self.modules;
+        for (i, res) in modules.iter().enumerate() {
+            for r in &res.method_references {
+                let target = if let MethodReferenceParent::Type(t) = &r.parent {
+                    self.definition_target(i, t)
+                } else {
+                    None
+                };
+
+                let mapped = if let Some((j, parent)) = target {
+                    let m = self.lookups[j]
+                        .find_method(parent, &r.name, &r.signature, res)
+                        .ok_or_else(|| unresolved(&r.name, &modules[j]))?;
+                    UserMethod::Definition(self.mappings[j].method(m))
+                } else {
+                    self.method_ref_origins.push(i);
+                    UserMethod::Reference(self.out.push_method_reference(r.clone()))
+                };
+                self.mappings[i].method_refs.push(mapped);
+            }
+
+            for r in &res.field_references {
+                let target = if let FieldReferenceParent::Type(t) = &r.parent {
+                    self.definition_target(i, t)
+                } else {
+                    None
+                };
+
+                let mapped = if let Some((j, parent)) = target {
+                    let f = self.lookups[j]
+                        .find_field(parent, &r.name)
+                        .ok_or_else(|| unresolved(&r.name, &modules[j]))?;
+                    FieldSource::Definition(self.mappings[j].field(f))
+                } else {
+                    self.field_ref_origins.push(i);
+                    FieldSource::Reference(self.out.push_field_reference(r.clone()))
+                };
+                self.mappings[i].field_refs.push(mapped);
+            }
+        }
+
+        Ok(())
+    }
+
+    // the merged module and type that a member reference's parent is defined by
+    // members of generic instantiations stay references, since their signatures are instantiated
+    fn definition_target(&self, i: usize, parent: &MethodType) -> Option<(usize, TypeIndex)> {
+        match parent.as_base()? {
+            BaseType::Type {
+                source: TypeSource::User(UserType::Reference(r)),
+                ..
+            } => self.type_ref_targets[i][r.0],
+            _ => None,
+        }
+    }
+
+    fn map_exports(&mut self) {
+        let modules = self.modules;
+        for (i, res) in modules.iter().enumerate() {
+            let mut exported: Vec<Option<ExportedTypeIndex>> = Vec::with_capacity(res.exported_types.len());
+
+            for e in &res.exported_types {
+                // forwarders into merged assemblies (and the types nested in them) are no longer needed
+                let keep = match e.implementation {
+                    TypeImplementation::Nested(enc) => exported.get(enc.0).is_none_or(Option::is_some),
+                    TypeImplementation::TypeForwarder(a) => self.mappings[i].assembly_refs[a.0].is_some(),
+                    TypeImplementation::ModuleFile { .. } => true,
+                };
+
+                exported.push(keep.then(|| self.out.push_exported_type(e.clone())));
+            }
+
+            self.mappings[i].exported_types = exported;
+        }
+    }
+
+    fn copy(&mut self) {
+        for (i, res) in self.modules.iter().enumerate() {
+            let m = &self.mappings[i];
+
+            for (index, t) in res.enumerate_type_definitions() {
+                if index.0 == 0 && i > 0 {
+                    let globals = &mut self.out.type_definitions[0];
+                    macro_rules! append {
+                        ($($members:ident),*) => {
+                            $(
+                                for member in &t.$members {
+                                    let mut member = member.clone();
+                                    member.remap(m);
+                                    globals.$members.push(member);
+                                }
+                            )*
+                        };
+                    }
+                    append!(methods, fields, properties, events);
+                } else {
+                    self.out[m.types[index.0]].remap(m);
+                }
+            }
+
+            for index in m.exported_types.iter().flatten() {
+                self.out[*index].remap(m);
+            }
+
+            for r in &res.manifest_resources {
+                // a resource located in another merged assembly is copied along with that assembly
+                if matches!(r.implementation, Implementation::Assembly { location, .. } if m.assembly_refs[location.0].is_none())
+                {
+                    continue;
+                }
+
+                let mut r = r.clone();
+                r.remap(m);
+                self.out.manifest_resources.push(r);
+            }
+        }
+
+        macro_rules! remap_list {
+            ($($list:ident by $origins:ident),*) => {
+                $(
+                    for (item, &origin) in self.out.$list.iter_mut().zip(&self.$origins) {
+                        item.remap(&self.mappings[origin]);
+                    }
+                )*
+            };
+        }
+        remap_list!(
+            type_references by type_ref_origins,
+            method_references by method_ref_origins,
+            field_references by field_ref_origins,
+            assembly_references by assembly_ref_origins,
+            module_references by module_ref_origins,
+            import_scopes by import_scope_origins
+        );
+
+        let primary = &self.mappings[0];
+        self.out.module.remap(primary);
+        if let Some(a) = &mut self.out.assembly {
+            a.remap(primary);
+        }
+        self.out.entry_point = self.modules[0].entry_point.map(|e| match e {
+            EntryPoint::Method(m) => EntryPoint::Method(primary.method(m)),
+            EntryPoint::File(f) => EntryPoint::File(primary.files[f.0]),
+        });
+    }
+
+    // the merged names of the types defined by each merged assembly, by assembly name and then by their name in it
+    fn type_names(&self) -> HashMap<String, HashMap<String, String>> {
+        let mut names: HashMap<_, HashMap<_, _>> = HashMap::new();
+        for (i, res) in self.modules.iter().enumerate() {
+            let Some(assembly) = assembly_name(res) else { continue };
+            let assembly_names = names.entry(assembly.to_string()).or_default();
+            for (index, _) in res.enumerate_type_definitions().skip(1) {
+                assembly_names.insert(
+                    lookup::definition_name(res, index),
+                    reflection_name(&self.out, self.mappings[i].types[index.0]),
+                );
+            }
+        }
+        names
+    }
+
+    fn rewrite_attribute_values(&mut self) -> Result<()> {
+        let names = self.type_names();
+        let mentions: Vec<Vec<u8>> = names.keys().map(|a| format!(", {}", a).into_bytes()).collect();
+
+        // only values that mention a merged assembly by name are decoded
+        let mut candidates = vec![];
+        let mut position = 0;
+        for_each_attribute(&mut self.out, &mut |a| {
+            if let Some(value) = &a.value {
+                if mentions
+                    .iter()
+                    .any(|m| value.windows(m.len()).any(|w| w == m.as_slice()))
+                {
+                    candidates.push((position, a.clone()));
+                }
+            }
+            position += 1;
+        });
+        if candidates.is_empty() {
+            return Ok(());
+        }
+
+        let lookup = Lookup::new(&self.out);
+        let resolver = MergedTypes {
+            lookup: &lookup,
+            names: &names,
+        };
+        let mut values = HashMap::new();
+        for (position, a) in &candidates {
+            if let Some(value) = rewrite_attribute_value(a, &self.out, &resolver)? {
+                values.insert(*position, value);
+            }
+        }
+        drop(lookup);
+
+        let mut position = 0;
+        for_each_attribute(&mut self.out, &mut |a| {
+            if let Some(value) = values.remove(&position) {
+                a.value = Some(value.into());
+            }
+            position += 1;
+        });
+
+        Ok(())
+    }
+}
+
+pub(super) fn merge_impl<'a>(modules: &[Resolution<'a>], opts: Options) -> Result<Resolution<'a>> {
+    let primary = modules.first().ok_or(MergeError::NoModules)?;
+
+    let mut out = Resolution::new(primary.module.clone());
+    out.assembly.clone_from(&primary.assembly);
+    out.win32_resources.clone_from(&primary.win32_resources);
+
+    let mut merger = Merger {
+        modules,
+        lookups: modules.iter().map(Lookup::new).collect(),
+        merged: modules
+            .iter()
+            .enumerate()
+            .filter_map(|(i, res)| Some((assembly_name(res)?, i)))
+            .collect(),
+        mappings: modules.iter().map(|_| Mapping::default()).collect(),
+        out,
+        type_ref_origins: vec![],
+        method_ref_origins: vec![],
+        field_ref_origins: vec![],
+        assembly_ref_origins: vec![],
+        module_ref_origins: vec![],
+        import_scope_origins: vec![],
+        type_ref_targets: vec![],
+    };
+
+    merger.map_types();
+    merger.check_names(opts)?;
+    merger.map_scopes();
+    merger.map_type_refs()?;
+    merger.map_member_refs()?;
+    merger.map_exports();
+    merger.copy();
+    merger.rewrite_attribute_values()?;
+
+    Ok(merger.out)
+}
+
+// rewrites the indices in a value from an input module to point into the merged module
+trait Remap {
+    fn remap(&mut self, m: &Mapping);
+}
+
+impl<T: Remap> Remap for Vec<T> {
+    fn remap(&mut self, m: &Mapping) {
+        for t in self {
+            t.remap(m);
+        }
+    }
+}
+impl<T: Remap> Remap for Option<T> {
+    fn remap(&mut self, m: &Mapping) {
+        if let Some(t) = self {
+            t.remap(m);
+        }
+    }
+}
+impl<A: Remap, B: Remap> Remap for (A, B) {
+    fn remap(&mut self, m: &Mapping) {
+        self.0.remap(m);
+        self.1.remap(m);
+    }
+}
+
+impl Remap for UserType {
+    fn remap(&mut self, m: &Mapping) {
+        *self = match *self {
+            UserType::Definition(t) => UserType::Definition(m.types[t.0]),
+            UserType::Reference(r) => m.type_refs[r.0],
+        };
+    }
+}
+impl Remap for CustomTypeModifier {
+    fn remap(&mut self, m: &Mapping) {
+        match self {
+            CustomTypeModifier::Optional(u) | CustomTypeModifier::Required(u) => u.remap(m),
+        }
+    }
+}
+impl<T: Remap> Remap for TypeSource<T> {
+    fn remap(&mut self, m: &Mapping) {
+        match self {
+            TypeSource::User(u) => u.remap(m),
+            TypeSource::Generic { base, parameters } => {
+                base.remap(m);
+                parameters.remap(m);
+            }
+        }
+    }
+}
+impl<T: Remap> Remap for BaseType<T> {
+    fn remap(&mut self, m: &Mapping) {
+        match self {
+            BaseType::Type { source, .. } => source.remap(m),
+            BaseType::Vector(mods, t) => {
+                mods.remap(m);
+                t.remap(m);
+            }
+            BaseType::Array(t, _) => t.remap(m),
+            BaseType::ValuePointer(mods, t) => {
+                mods.remap(m);
+                t.remap(m);
+            }
+            BaseType::FunctionPointer(sig) => sig.remap(m),
+            _ => {}
+        }
+    }
+}
+impl Remap for MemberType {
+    fn remap(&mut self, m: &Mapping) {
+        if let MemberType::Base(b) = self {
+            b.remap(m);
+        }
+    }
+}
+impl Remap for MethodType {
+    fn remap(&mut self, m: &Mapping) {
+        if let MethodType::Base(b) = self {
+            b.remap(m);
+        }
+    }
+}
+impl Remap for LocalVariable {
+    fn remap(&mut self, m: &Mapping) {
+        if let LocalVariable::Variable {
+            custom_modifiers,
+            var_type,
+            ..
+        } = self
+        {
+            custom_modifiers.remap(m);
+            var_type.remap(m);
+        }
+    }
+}
+
+impl<T: Remap> Remap for ParameterType<T> {
+    fn remap(&mut self, m: &Mapping) {
+        match self {
+            ParameterType::Value(t) | ParameterType::Ref(t) => t.remap(m),
+            ParameterType::TypedReference => {}
+        }
+    }
+}
+impl<T: Remap> Remap for Parameter<T> {
+    fn remap(&mut self, m: &Mapping) {
+        self.0.remap(m);
+        self.1.remap(m);
+    }
+}
+impl<T: Remap> Remap for ReturnType<T> {
+    fn remap(&mut self, m: &Mapping) {
+        self.0.remap(m);
+        self.1.remap(m);
+    }
+}
+impl<C, T: Remap> Remap for MethodSignature<C, T> {
+    fn remap(&mut self, m: &Mapping) {
+        self.parameters.remap(m);
+        self.return_type.remap(m);
+        self.varargs.remap(m);
+    }
+}
+
+impl Remap for UserMethod {
+    fn remap(&mut self, m: &Mapping) {
+        *self = match *self {
+            UserMethod::Definition(d) => UserMethod::Definition(m.method(d)),
+            UserMethod::Reference(r) => m.method_refs[r.0],
+        };
+    }
+}
+impl Remap for MethodSource {
+    fn remap(&mut self, m: &Mapping) {
+        match self {
+            MethodSource::User(u) => u.remap(m),
+            MethodSource::Generic(g) => {
+                g.base.remap(m);
+                g.parameters.remap(m);
+            }
+        }
+    }
+}
+impl Remap for FieldSource {
+    fn remap(&mut self, m: &Mapping) {
+        *self = match *self {
+            FieldSource::Definition(d) => FieldSource::Definition(m.field(d)),
+            FieldSource::Reference(r) => m.field_refs[r.0],
+        };
+    }
+}
+
+impl Remap for Attribute<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.constructor.remap(m);
+    }
+}
+impl Remap for SecurityDeclaration<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+    }
+}
+
+struct RemapOperands<'m>(&'m Mapping);
+impl OperandVisitor for RemapOperands<'_> {
+    fn visit_type(&mut self, operand: &mut MethodType) {
+        operand.remap(self.0);
+    }
+
+    fn visit_method(&mut self, operand: &mut MethodSource) {
+        operand.remap(self.0);
+    }
+
+    fn visit_constructor(&mut self, operand: &mut UserMethod) {
+        operand.remap(self.0);
+    }
+
+    fn visit_field(&mut self, operand: &mut FieldSource) {
+        operand.remap(self.0);
+    }
+
+    fn visit_signature(&mut self, operand: &mut MaybeUnmanagedMethod<MethodType>) {
+        operand.remap(self.0);
+    }
+}
+
+impl Remap for body::Method {
+    fn remap(&mut self, m: &Mapping) {
+        self.header.local_variables.remap(m);
+
+        for i in &mut self.instructions {
+            i.visit_operands(&mut RemapOperands(m));
+        }
+
+        for section in &mut self.data_sections {
+            if let body::DataSection::ExceptionHandlers(handlers) = section {
+                for h in handlers {
+                    if let body::ExceptionKind::TypedException(t) = &mut h.kind {
+                        t.remap(m);
+                    }
+                }
+            }
+        }
+
+        if let Some(debug) = &mut self.debug {
+            for p in &mut debug.sequence_points {
+                p.document = m.documents[p.document.0];
+            }
+            for s in &mut debug.scopes {
+                s.import_scope = s.import_scope.map(|i| m.import_scopes[i.0]);
+            }
+        }
+    }
+}
+
+impl Remap for debug::ImportScope {
+    fn remap(&mut self, m: &Mapping) {
+        self.parent = self.parent.map(|p| m.import_scopes[p.0]);
+        // imports from a merged assembly now refer to the merged module, which an assembly alias can't name
+        self.imports.retain_mut(|i| match i {
+            debug::Import::Namespace { assembly, .. } => {
+                *assembly = assembly.and_then(|a| m.assembly_refs[a.0]);
+                true
+            }
+            debug::Import::Type { target, .. } => {
+                target.remap(m);
+                true
+            }
+            debug::Import::AssemblyAlias { assembly, .. } => match m.assembly_refs[assembly.0] {
+                Some(a) => {
+                    *assembly = a;
+                    true
+                }
+                None => false,
+            },
+            debug::Import::XmlNamespace { .. } | debug::Import::AssemblyAliasReference(_) => true,
+        });
+    }
+}
+
+impl<T: Remap> Remap for generic::Generic<'_, T> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        for c in &mut self.type_constraints {
+            c.attributes.remap(m);
+            c.custom_modifiers.remap(m);
+            c.constraint_type.remap(m);
+        }
+    }
+}
+
+impl Remap for ParameterMetadata<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+    }
+}
+impl Remap for PInvoke<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.import_scope = m.module_refs[self.import_scope.0];
+    }
+}
+
+impl Remap for Method<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        self.body.remap(m);
+        self.signature.remap(m);
+        self.generic_parameters.remap(m);
+        self.return_type_metadata.remap(m);
+        self.parameter_metadata.remap(m);
+        self.pinvoke.remap(m);
+        self.security.remap(m);
+    }
+}
+impl Remap for Field<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        self.type_modifiers.remap(m);
+        self.return_type.remap(m);
+        self.pinvoke.remap(m);
+    }
+}
+impl Remap for Property<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        self.getter.remap(m);
+        self.setter.remap(m);
+        self.other.remap(m);
+        self.property_type.remap(m);
+        self.parameters.remap(m);
+    }
+}
+impl Remap for Event<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        self.delegate_type.remap(m);
+        self.add_listener.remap(m);
+        self.remove_listener.remap(m);
+        self.raise_event.remap(m);
+        self.other.remap(m);
+    }
+}
+impl Remap for MethodOverride {
+    fn remap(&mut self, m: &Mapping) {
+        self.implementation.remap(m);
+        self.declaration.remap(m);
+    }
+}
+
+impl Remap for TypeDefinition<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        self.fields.remap(m);
+        self.properties.remap(m);
+        self.methods.remap(m);
+        self.events.remap(m);
+        self.encloser = self.encloser.map(|e| m.types[e.0]);
+        self.overrides.remap(m);
+        self.extends.remap(m);
+        self.implements.remap(m);
+        self.generic_parameters.remap(m);
+        self.security.remap(m);
+    }
+}
+
+impl Remap for ExternalTypeReference<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        self.scope = match self.scope {
+            ResolutionScope::Nested(enc) => match m.type_refs[enc.0] {
+                UserType::Reference(r) => ResolutionScope::Nested(r),
+                UserType::Definition(_) => unreachable!("references nested in merged types are resolved"),
+            },
+            ResolutionScope::ExternalModule(r) => ResolutionScope::ExternalModule(m.module_refs[r.0]),
+            ResolutionScope::Assembly(a) => {
+                ResolutionScope::Assembly(m.assembly_refs[a.0].expect("references to merged assemblies are resolved"))
+            }
+            other => other,
+        };
+    }
+}
+impl Remap for ExternalMethodReference<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        match &mut self.parent {
+            MethodReferenceParent::Type(t) => t.remap(m),
+            MethodReferenceParent::Module(r) => *r = m.module_refs[r.0],
+            MethodReferenceParent::VarargMethod(d) => *d = m.method(*d),
+        }
+        self.signature.remap(m);
+    }
+}
+impl Remap for ExternalFieldReference<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        match &mut self.parent {
+            FieldReferenceParent::Type(t) => t.remap(m),
+            FieldReferenceParent::Module(r) => *r = m.module_refs[r.0],
+        }
+        self.custom_modifiers.remap(m);
+        self.field_type.remap(m);
+    }
+}
+
+impl Remap for ExportedType<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        self.implementation = match self.implementation {
+            TypeImplementation::Nested(e) => TypeImplementation::Nested(m.exported_types[e.0].unwrap()),
+            TypeImplementation::ModuleFile { type_def, file } => TypeImplementation::ModuleFile {
+                type_def,
+                file: m.files[file.0],
+            },
+            TypeImplementation::TypeForwarder(a) => TypeImplementation::TypeForwarder(m.assembly_refs[a.0].unwrap()),
+        };
+    }
+}
+impl Remap for ManifestResource<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        match &mut self.implementation {
+            Implementation::File { location, .. } => *location = m.files[location.0],
+            Implementation::Assembly { location, .. } => *location = m.assembly_refs[location.0].unwrap(),
+            Implementation::CurrentFile(_) => {}
+        }
+    }
+}
+
+macro_rules! remap_attributes {
+    ($($t:ty),*) => {
+        $(
+            impl Remap for $t {
+                fn remap(&mut self, m: &Mapping) {
+                    self.attributes.remap(m);
+                }
+            }
+        )*
+    };
+}
+remap_attributes!(
+    ExternalAssemblyReference<'_>,
+    ExternalModuleReference<'_>,
+    File<'_>,
+    Module<'_>
+);
+
+impl Remap for Assembly<'_> {
+    fn remap(&mut self, m: &Mapping) {
+        self.attributes.remap(m);
+        self.security.remap(m);
+    }
+}
+
+// calls f with every custom attribute in the module
+fn for_each_attribute<'a>(res: &mut Resolution<'a>, f: &mut impl FnMut(&mut Attribute<'a>)) {
+    let mut all = |attributes: &mut Vec<Attribute<'a>>| attributes.iter_mut().for_each(&mut *f);
+
+    macro_rules! lists {
+        ($($list:expr),*) => {
+            $(
+                for item in &mut $list {
+                    all(&mut item.attributes);
+                }
+            )*
+        };
+    }
+    macro_rules! security {
+        ($s:expr) => {
+            if let Some(s) = &mut $s {
+                all(&mut s.attributes);
+            }
+        };
+    }
+    macro_rules! generics {
+        ($g:expr) => {
+            for g in &mut $g {
+                all(&mut g.attributes);
+                lists!(g.type_constraints);
+            }
+        };
+    }
+
+    all(&mut res.module.attributes);
+    if let Some(a) = &mut res.assembly {
+        all(&mut a.attributes);
+        security!(a.security);
+    }
+    lists!(
+        res.assembly_references,
+        res.type_references,
+        res.method_references,
+        res.field_references,
+        res.module_references,
+        res.files,
+        res.exported_types,
+        res.manifest_resources
+    );
+
+    for t in &mut res.type_definitions {
+        all(&mut t.attributes);
+        security!(t.security);
+        for (attributes, _) in &mut t.implements {
+            all(attributes);
+        }
+        generics!(t.generic_parameters);
+        lists!(t.fields, t.properties, t.events);
+
+        for m in &mut t.methods {
+            all(&mut m.attributes);
+            security!(m.security);
+            generics!(m.generic_parameters);
+            for p in m
+                .return_type_metadata
+                .iter_mut()
+                .chain(m.parameter_metadata.iter_mut().flatten())
+            {
+                all(&mut p.attributes);
+            }
+        }
+    }
+}
+
+// the name of a type as it appears in custom attribute values (ECMA-335, II.23.3), e.g. "System.Environment+SpecialFolder"
+fn reflection_name(res: &Resolution, index: TypeIndex) -> String {
+    let t = &res[index];
+    let escape = |s: &str| {
+        s.chars().fold(String::with_capacity(s.len()), |mut buf, c| {
+            if ",+&*[]\\".contains(c) {
+                buf.push('\\');
+            }
+            buf.push(c);
+            buf
+        })
+    };
+    match t.encloser {
+        Some(enc) => format!("{}+{}", reflection_name(res, enc), escape(&t.name)),
+        None => escape(&t.type_name()),
+    }
+}
+
+// splits a reflection type name at the first unescaped `separator` outside of brackets
+fn split_unbracketed(name: &str, separator: char) -> (&str, Option<&str>) {
+    let mut depth = 0_usize;
+    let mut escaped = false;
+    for (i, c) in name.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            c if c == separator && depth == 0 => return (&name[..i], Some(&name[i + 1..])),
+            _ => {}
+        }
+    }
+    (name, None)
+}
+
+// the name of a type within its assembly as Lookup names it, from the name it has in custom attribute values
+fn lookup_name(reflection_name: &str) -> String {
+    let mut name = String::with_capacity(reflection_name.len());
+    let mut chars = reflection_name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => name.extend(chars.next()),
+            '+' => name.push('/'),
+            c => name.push(c),
+        }
+    }
+    name
+}
+
+// rewrites a type name from a custom attribute value (including the arguments of generic instantiations) that names a
+// type in a merged assembly to name the merged type, returning None if the name doesn't need to change
+fn rewrite_type_name(name: &str, names: &HashMap<String, HashMap<String, String>>) -> Result<Option<String>> {
+    let (type_name, assembly) = split_unbracketed(name, ',');
+    let (base, rest) = match type_name.find('[') {
+        // escaped brackets can't be told apart from the start of generic arguments this way, but they're never used
+        Some(i) => type_name.split_at(i),
+        None => (type_name, ""),
+    };
+
+    let mut rewritten = false;
+    let mut suffix = String::with_capacity(rest.len());
+    let is_generic = rest.starts_with('[') && !rest[1..].starts_with([']', ',', '*']);
+    let mut rest = rest;
+    if is_generic {
+        // find the bracket that closes the argument list
+        let mut depth = 0_usize;
+        let close = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map_or(rest.len() - 1, |(i, _)| i);
+
+        let mut arguments = vec![];
+        let mut remaining = Some(&rest[1..close]);
+        while let Some(list) = remaining {
+            let (argument, next) = split_unbracketed(list, ',');
+            remaining = next;
+
+            let argument = argument.trim();
+            let (open, inner, end) = match argument.strip_prefix('[').and_then(|a| a.strip_suffix(']')) {
+                Some(inner) => ("[", inner, "]"),
+                None => ("", argument, ""),
+            };
+            arguments.push(match rewrite_type_name(inner, names)? {
+                Some(new) => {
+                    rewritten = true;
+                    format!("{}{}{}", open, new, end)
+                }
+                None => argument.to_string(),
+            });
+        }
+
+        suffix = format!("[{}]", arguments.join(","));
+        rest = &rest[close + 1..];
+    }
+    suffix.push_str(rest);
+
+    let assembly_name = assembly.map(|a| split_unbracketed(a, ',').0.trim());
+    if let Some(types) = assembly_name.and_then(|a| names.get(a)) {
+        let merged = types
+            .get(&lookup_name(base))
+            .ok_or_else(|| MergeError::UnresolvedReference {
+                member: base.to_string(),
+                assembly: assembly_name.unwrap_or_default().to_string(),
+            })?;
+        // the merged assembly is the current one, so the name doesn't need an assembly
+        return Ok(Some(format!("{}{}", merged, suffix)));
+    }
+
+    Ok(rewritten.then(|| match assembly {
+        Some(a) => format!("{}{},{}", base, suffix, a),
+        None => format!("{}{}", base, suffix),
+    }))
+}
+
+// resolves the enum types used by custom attribute values in the merged module
+struct MergedTypes<'l, 'r, 'a> {
+    lookup: &'l Lookup<'r, 'a>,
+    names: &'l HashMap<String, HashMap<String, String>>,
+}
+
+impl<'l, 'r: 'l, 'a: 'r> Resolver<'l> for MergedTypes<'l, 'r, 'a> {
+    type Error = MergeError;
+
+    fn find_type(&self, name: &str) -> Result<(&TypeDefinition<'l>, &Resolution<'l>)> {
+        let name = rewrite_type_name(name, self.names)?.unwrap_or_else(|| name.to_string());
+        let (type_name, assembly) = split_unbracketed(&name, ',');
+        let index = self
+            .lookup
+            .find_type(&lookup_name(type_name))
+            .filter(|_| assembly.is_none())
+            .ok_or_else(|| MergeError::AttributeValue(format!("could not find type {}", name)))?;
+        let res = self.lookup.resolution();
+        Ok((&res[index], res))
+    }
+}
+
+fn rewrite_attribute_value(a: &Attribute, res: &Resolution, resolver: &MergedTypes) -> Result<Option<Vec<u8>>> {
+    fn collect<'v>(
+        arg: &FixedArg<'v>,
+        names: &HashMap<String, HashMap<String, String>>,
+        new_names: &mut HashMap<&'v str, String>,
+    ) -> Result<()> {
+        match arg {
+            FixedArg::Type(name) | FixedArg::Enum(name, _) => {
+                if let Some(new) = rewrite_type_name(name, names)? {
+                    new_names.insert(name, new);
+                }
+            }
+            FixedArg::Array(Some(args)) => {
+                for arg in args {
+                    collect(arg, names, new_names)?;
+                }
+            }
+            FixedArg::Object(arg) => collect(arg, names, new_names)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn replace<'n>(arg: &mut FixedArg<'n>, new_names: &'n HashMap<&str, String>) {
+        match arg {
+            FixedArg::Type(name) | FixedArg::Enum(name, _) => {
+                if let Some(new) = new_names.get(name) {
+                    *name = new;
+                }
+            }
+            FixedArg::Array(Some(args)) => {
+                for arg in args {
+                    replace(arg, new_names);
+                }
+            }
+            FixedArg::Object(arg) => replace(arg, new_names),
+            _ => {}
+        }
+    }
+
+    let data = a
+        .instantiation_data(resolver, res)
+        .map_err(|e| MergeError::AttributeValue(e.to_string()))?;
+
+    let mut new_names = HashMap::new();
+    let named = data
+        .named_args
+        .iter()
+        .map(|(NamedArg::Field(_, arg) | NamedArg::Property(_, arg))| arg);
+    for arg in data.constructor_args.iter().chain(named) {
+        collect(arg, resolver.names, &mut new_names)?;
+    }
+    if new_names.is_empty() {
+        return Ok(None);
+    }
+
+    let mut data = data;
+    let named = data
+        .named_args
+        .iter_mut()
+        .map(|(NamedArg::Field(_, arg) | NamedArg::Property(_, arg))| arg);
+    for arg in data.constructor_args.iter_mut().chain(named) {
+        replace(arg, &new_names);
+    }
+
+    Ok(Attribute::new(a.constructor, data).value.map(Cow::into_owned))
+}
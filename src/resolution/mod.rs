@@ -1,8 +1,20 @@
+pub mod cfg;
+pub mod disassemble;
+pub mod enc;
+pub mod lazy;
+pub mod lookup;
+pub mod merge;
 pub mod read;
+pub mod resolver;
+mod sha1;
+pub mod stack;
+pub mod strong_name;
+pub mod tokens;
 pub mod utils;
+pub mod verify;
 pub mod write;
 
-use crate::prelude::*;
+use crate::{prelude::*, win32};
 use dotnetdll_macros::From;
 use paste::paste;
 use std::ops::{Index, IndexMut};
@@ -17,6 +29,8 @@ pub struct Resolution<'a> {
     pub assembly: Option<Assembly<'a>>,
     /// All external assemblies referenced by the DLL.
     pub assembly_references: Vec<ExternalAssemblyReference<'a>>,
+    /// Source documents referenced by the debugging information of method bodies.
+    pub documents: Vec<debug::Document<'a>>,
     /// Entry point for the DLL, if one is defined.
     pub entry_point: Option<EntryPoint>,
     /// Types that this assembly exports, but are not defined in the assembly's main module.
@@ -29,14 +43,21 @@ pub struct Resolution<'a> {
     pub manifest_resources: Vec<resource::ManifestResource<'a>>,
     /// References to methods defined in external assemblies.
     pub method_references: Vec<ExternalMethodReference<'a>>,
+    /// Sets of imports referenced by the lexical scopes of method bodies.
+    pub import_scopes: Vec<debug::ImportScope>,
     /// The module defined by the DLL. Note that this is a distinct object from an assembly.
     pub module: Module<'a>,
     /// References to modules defined in external assemblies.
     pub module_references: Vec<ExternalModuleReference<'a>>,
+    /// The rows that members were read from, if [`ReadOptions::record_tokens`] was set. See the [`tokens`] module for details.
+    pub original_tokens: Option<tokens::OriginalTokens<'a>>,
     /// Types defined within the DLL.
     pub type_definitions: Vec<TypeDefinition<'a>>,
     /// References to types defined in external assemblies.
     pub type_references: Vec<ExternalTypeReference<'a>>,
+    /// Win32 resources of the image, such as version information and an application manifest.
+    /// These are only written if there are any, and are left empty when the resource directory can't be read.
+    pub win32_resources: win32::Resources<'a>,
 }
 
 impl<'a> Resolution<'a> {
@@ -44,16 +65,20 @@ impl<'a> Resolution<'a> {
         Resolution {
             assembly: None,
             assembly_references: vec![],
+            documents: vec![],
             entry_point: None,
             exported_types: vec![],
             field_references: vec![],
             files: vec![],
+            import_scopes: vec![],
             manifest_resources: vec![],
             method_references: vec![],
             module,
             module_references: vec![],
+            original_tokens: None,
             type_definitions: vec![TypeDefinition::new(None, "<Module>")],
             type_references: vec![],
+            win32_resources: win32::Resources::default(),
         }
     }
 
@@ -62,8 +87,39 @@ impl<'a> Resolution<'a> {
         dll.resolve(opts)
     }
 
+    /// Parses a DLL along with its portable PDB, filling in [`Resolution::documents`] and the debugging information of method bodies.
+    pub fn parse_with_pdb(bytes: &'a [u8], pdb: &'a [u8], opts: ReadOptions) -> crate::dll::Result<Self> {
+        let dll = DLL::parse(bytes)?;
+        dll.resolve_with_pdb(&PDB::parse(pdb)?, opts)
+    }
+
     pub fn write(&self, opts: WriteOptions) -> crate::dll::Result<Vec<u8>> {
-        write::write_impl(self, opts)
+        write::write_impl(self, opts, false).map(|(dll, ..)| dll)
+    }
+
+    /// Writes the DLL along with a portable PDB built from the debugging information of method bodies.
+    ///
+    /// Returns the bytes of the DLL and the PDB, in that order. The DLL's debug directory refers to the PDB by the
+    /// module name with a `.pdb` extension, so the PDB should be saved next to the DLL under that name.
+    pub fn write_with_pdb(&self, opts: WriteOptions) -> crate::dll::Result<(Vec<u8>, Vec<u8>)> {
+        write::write_impl(self, opts, true).map(|(dll, pdb, _)| (dll, pdb.unwrap()))
+    }
+
+    /// Writes the DLL along with the new tokens of every member in [`Resolution::original_tokens`] whose row moved.
+    /// See the [`tokens`] module for details.
+    pub fn write_with_remapping(&self, opts: WriteOptions) -> crate::dll::Result<(Vec<u8>, tokens::TokenRemapping)> {
+        write::write_impl(self, opts, false).map(|(dll, _, remapping)| (dll, remapping))
+    }
+
+    /// Writes the Edit-and-Continue deltas that turn `previous`, the resolution that `baseline` was captured from,
+    /// into this one. See the [`enc`] module for details.
+    pub fn write_delta(&self, previous: &Self, baseline: &enc::Baseline) -> crate::dll::Result<enc::Delta> {
+        enc::delta_impl(baseline, previous, self)
+    }
+
+    /// Combines several modules into one. See the [`merge`] module for details.
+    pub fn merge(modules: &[Self], opts: MergeOptions) -> Result<Self, merge::MergeError> {
+        merge::merge_impl(modules, opts)
     }
 
     pub fn set_entry_point(&mut self, entry_point: impl Into<EntryPoint>) {
@@ -118,14 +174,25 @@ macro_rules! basic_index {
 }
 
 basic_index!(AssemblyRefIndex indexes assembly_reference as ExternalAssemblyReference<'a>);
+basic_index!(DocumentIndex indexes document as debug::Document<'a>);
 basic_index!(ExportedTypeIndex indexes exported_type as ExportedType<'a>);
 basic_index!(FieldRefIndex indexes field_reference as ExternalFieldReference<'a>);
 basic_index!(FileIndex indexes file as File<'a>);
+basic_index!(ImportScopeIndex indexes import_scope as debug::ImportScope);
 basic_index!(MethodRefIndex indexes method_reference as ExternalMethodReference<'a>);
 basic_index!(ModuleRefIndex indexes module_reference as ExternalModuleReference<'a>);
 basic_index!(TypeIndex indexes type_definition as TypeDefinition<'a>);
 basic_index!(TypeRefIndex indexes type_reference as ExternalTypeReference<'a>);
 
+impl TypeIndex {
+    /// Creates an index into the type definitions of another module, as used by [`TypeImplementation::ModuleFile`].
+    ///
+    /// Indices into the current module should come from [`Resolution::push_type_definition`] or [`Resolution::type_definition_index`] instead.
+    pub fn external(index: usize) -> Self {
+        TypeIndex(index)
+    }
+}
+
 macro_rules! internal_index {
     ($name:ident indexes $sing:ident / $plural:ident as $t:ty) => {
         #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
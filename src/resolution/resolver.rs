@@ -0,0 +1,314 @@
+//! An implementation of [`Resolver`] that loads referenced assemblies from disk.
+//!
+//! Custom attribute blobs refer to enum types by name only (ECMA-335, II.23.3), so decoding them needs access to the
+//! assemblies that define those types. [`AssemblyResolver`] looks for `<name>.dll` (or `<name>.exe`) in a list of probe
+//! directories, such as an SDK reference pack or a `NuGet` package cache, and reads each assembly at most once.
+//!
+//! Like [`Lookup`](super::lookup::Lookup), the resolver names nested types with a `/` after their enclosing type
+//! (for example, `System.Environment/SpecialFolder`). The names that custom attribute blobs pass to
+//! [`Resolver::find_type`] use the reflection syntax instead (`System.Environment+SpecialFolder, System.Runtime`), and
+//! are translated before they are looked up.
+
+use super::{read::Options as ReadOptions, Resolution};
+use crate::{
+    dll::DLLError,
+    resolved::{assembly::ExternalAssemblyReference, types::*},
+};
+use elsa::FrozenMap;
+use self_cell::self_cell;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ResolveError {
+    #[error("could not find assembly {0} in any probe path")]
+    AssemblyNotFound(String),
+    #[error("could not find type {0}")]
+    TypeNotFound(String),
+    #[error("type forwarding for {0} does not terminate")]
+    ForwardingCycle(String),
+    #[error("could not read {}: {source}", path.display())]
+    Io { path: PathBuf, source: std::io::Error },
+    #[error("could not parse {}: {source}", path.display())]
+    Parse { path: PathBuf, source: DLLError },
+}
+
+type Result<T> = std::result::Result<T, ResolveError>;
+
+// how many type forwarders will be followed before giving up on a name
+const MAX_FORWARDS: usize = 16;
+
+self_cell!(
+    // a parsed assembly along with the file contents it borrows from
+    struct Loaded {
+        owner: Box<[u8]>,
+
+        #[covariant]
+        dependent: Resolution,
+    }
+);
+
+/// Loads the assemblies referenced by a module from a set of probe directories on demand.
+///
+/// Assemblies are parsed lazily the first time a type from them is requested, and kept for the lifetime of the resolver.
+/// Since the resolutions it returns borrow from the resolver, it is used as a [`Resolver`] through a reference:
+///
+/// ```no_run
+/// # use dotnetdll::prelude::*;
+/// # fn example(res: &Resolution, attribute: &Attribute) -> Result<(), DLLError> {
+/// let mut resolver = AssemblyResolver::new(["/usr/share/dotnet/packs/Microsoft.NETCore.App.Ref/8.0.0/ref/net8.0"]);
+/// resolver.search_references_of(res);
+///
+/// let data = attribute.instantiation_data(&&resolver, res)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AssemblyResolver {
+    /// Directories searched, in order, when loading an assembly.
+    pub probe_paths: Vec<PathBuf>,
+    /// Assemblies searched, in order, for type names that are not qualified with an assembly name.
+    pub default_assemblies: Vec<String>,
+    // entries are only ever added, so references into them live as long as the resolver does
+    // a None entry records that the assembly could not be found in any probe path
+    loaded: FrozenMap<String, Box<Option<Loaded>>>,
+}
+
+impl AssemblyResolver {
+    pub fn new(probe_paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        Self {
+            probe_paths: probe_paths.into_iter().map(Into::into).collect(),
+            default_assemblies: vec!["System.Private.CoreLib".to_string(), "mscorlib".to_string()],
+            loaded: FrozenMap::new(),
+        }
+    }
+
+    /// Searches the assembly defined by `res` and the assemblies it references for type names without an assembly name,
+    /// ahead of the existing [`default_assemblies`](Self::default_assemblies).
+    ///
+    /// The defining assembly is only found if its file is in one of the probe paths.
+    pub fn search_references_of(&mut self, res: &Resolution) {
+        let names: Vec<_> = res
+            .assembly
+            .iter()
+            .map(|a| a.name.to_string())
+            .chain(res.assembly_references.iter().map(|a| a.name.to_string()))
+            .filter(|n| !self.default_assemblies.contains(n))
+            .collect();
+        self.default_assemblies.splice(0..0, names);
+    }
+
+    fn find_file(&self, name: &str) -> Option<PathBuf> {
+        self.probe_paths
+            .iter()
+            .flat_map(|dir| ["dll", "exe"].map(|ext| dir.join(format!("{}.{}", name, ext))))
+            .find(|p| p.is_file())
+    }
+
+    fn read(path: &Path) -> Result<Loaded> {
+        let bytes = std::fs::read(path).map_err(|source| ResolveError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Loaded::try_new(bytes.into_boxed_slice(), |data| {
+            Resolution::parse(
+                data,
+                ReadOptions {
+                    skip_method_bodies: true,
+                    ..ReadOptions::default()
+                },
+            )
+        })
+        .map_err(|source| ResolveError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Loads the assembly with the given name from the probe paths, or returns it if it has already been loaded.
+    pub fn load(&self, name: &str) -> Result<&Resolution<'_>> {
+        let entry = if let Some(entry) = self.loaded.get(name) {
+            entry
+        } else {
+            let loaded = match self.find_file(name) {
+                Some(path) => Some(Self::read(&path)?),
+                None => None,
+            };
+            self.loaded.insert(name.to_string(), Box::new(loaded))
+        };
+
+        entry
+            .as_ref()
+            .map(Loaded::borrow_dependent)
+            .ok_or_else(|| ResolveError::AssemblyNotFound(name.to_string()))
+    }
+
+    /// Loads the assembly that an assembly reference refers to.
+    pub fn resolve_assembly(&self, reference: &ExternalAssemblyReference) -> Result<&Resolution<'_>> {
+        self.load(&reference.name)
+    }
+
+    /// Looks up a type by its full name, such as `System.Environment/SpecialFolder`, within a single assembly,
+    /// following type forwarders into other assemblies.
+    pub fn find_in<'r>(&'r self, assembly: &str, name: &str) -> Result<(&'r TypeDefinition<'r>, &'r Resolution<'r>)> {
+        let path = TypePath::parse(name);
+        self.find_path(self.load(assembly)?, &path, name, MAX_FORWARDS)
+    }
+
+    fn find_path<'r>(
+        &'r self,
+        res: &'r Resolution<'r>,
+        path: &TypePath,
+        name: &str,
+        forwards: usize,
+    ) -> Result<(&'r TypeDefinition<'r>, &'r Resolution<'r>)> {
+        let (namespace, top) = path.top_level();
+
+        let Some(mut current) = res.enumerate_type_definitions().find(|(_, t)| {
+            t.encloser.is_none() && t.name == top && t.namespace.as_deref().unwrap_or_default() == namespace
+        }) else {
+            let forwarder = res.exported_types.iter().find_map(|e| match e.implementation {
+                TypeImplementation::TypeForwarder(a)
+                    if e.name == top && e.namespace.as_deref().unwrap_or_default() == namespace =>
+                {
+                    Some(a)
+                }
+                _ => None,
+            });
+
+            return match forwarder {
+                Some(_) if forwards == 0 => Err(ResolveError::ForwardingCycle(name.to_string())),
+                Some(a) => {
+                    let target = self.resolve_assembly(&res[a])?;
+                    self.find_path(target, path, name, forwards - 1)
+                }
+                None => Err(ResolveError::TypeNotFound(name.to_string())),
+            };
+        };
+
+        for nested in &path.nested {
+            current = res
+                .enumerate_type_definitions()
+                .find(|(_, t)| t.encloser == Some(current.0) && t.name == *nested)
+                .ok_or_else(|| ResolveError::TypeNotFound(name.to_string()))?;
+        }
+
+        Ok((current.1, res))
+    }
+}
+
+impl std::fmt::Debug for AssemblyResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssemblyResolver")
+            .field("probe_paths", &self.probe_paths)
+            .field("default_assemblies", &self.default_assemblies)
+            .field("loaded", &self.loaded.len())
+            .finish()
+    }
+}
+
+impl<'a> Resolver<'a> for &'a AssemblyResolver {
+    type Error = ResolveError;
+
+    fn find_type(&self, name: &str) -> Result<(&TypeDefinition<'a>, &Resolution<'a>)> {
+        let resolver: &'a AssemblyResolver = self;
+        let path = TypePath::parse_reflection(name);
+        let name = &path.name();
+
+        if let Some(assembly) = &path.assembly {
+            return resolver.find_path(resolver.load(assembly)?, &path, name, MAX_FORWARDS);
+        }
+
+        for assembly in &resolver.default_assemblies {
+            match resolver.load(assembly) {
+                Ok(res) => match resolver.find_path(res, &path, name, MAX_FORWARDS) {
+                    Err(ResolveError::TypeNotFound(_)) => {}
+                    found => return found,
+                },
+                Err(ResolveError::AssemblyNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(ResolveError::TypeNotFound(name.clone()))
+    }
+}
+
+// a type name split into the full name of its top-level type and the names of the types nested within it
+#[derive(Debug, PartialEq)]
+struct TypePath {
+    full_name: String,
+    nested: Vec<String>,
+    assembly: Option<String>,
+}
+
+impl TypePath {
+    // a name as used by Lookup, e.g. "System.Outer/Inner"
+    fn parse(name: &str) -> Self {
+        let mut parts = name.split('/').map(str::to_string);
+        Self {
+            full_name: parts.next().unwrap_or_default(),
+            nested: parts.collect(),
+            assembly: None,
+        }
+    }
+
+    // a name as it appears in custom attribute blobs, e.g. "System.Outer+Inner, System.Runtime, Version=8.0.0.0"
+    // see ECMA-335, II.23.3 (page 268) and the reflection type name grammar
+    fn parse_reflection(name: &str) -> Self {
+        let mut parts = vec![String::new()];
+        let mut assembly = None;
+        let mut depth = 0_usize;
+        let mut chars = name.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        parts.last_mut().unwrap().push(escaped);
+                    }
+                }
+                '[' => {
+                    depth += 1;
+                    parts.last_mut().unwrap().push(c);
+                }
+                ']' => {
+                    depth = depth.saturating_sub(1);
+                    parts.last_mut().unwrap().push(c);
+                }
+                '+' if depth == 0 => parts.push(String::new()),
+                ',' if depth == 0 => {
+                    let rest = chars.as_str();
+                    let assembly_name = rest.split(',').next().unwrap_or_default().trim();
+                    if !assembly_name.is_empty() {
+                        assembly = Some(assembly_name.to_string());
+                    }
+                    break;
+                }
+                _ => parts.last_mut().unwrap().push(c),
+            }
+        }
+
+        let full_name = parts.remove(0).trim().to_string();
+        Self {
+            full_name,
+            nested: parts,
+            assembly,
+        }
+    }
+
+    fn name(&self) -> String {
+        std::iter::once(&self.full_name)
+            .chain(&self.nested)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn top_level(&self) -> (&str, &str) {
+        match self.full_name.rsplit_once('.') {
+            Some((namespace, name)) => (namespace, name),
+            None => ("", &self.full_name),
+        }
+    }
+}
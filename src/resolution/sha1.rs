@@ -0,0 +1,103 @@
+//! SHA-1, see FIPS 180-4.
+//!
+//! This is used for strong names, which are defined in terms of SHA-1, and for content hashes in deterministic output.
+
+pub(crate) struct Sha1 {
+    state: [u32; 5],
+    block: Vec<u8>,
+    length: u64,
+}
+
+impl Sha1 {
+    pub fn new() -> Self {
+        Self {
+            state: [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476, 0xc3d2_e1f0],
+            block: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        while !data.is_empty() {
+            let take = (64 - self.block.len()).min(data.len());
+            self.block.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.block.len() == 64 {
+                let block = std::mem::take(&mut self.block);
+                self.compress(&block);
+                self.block = block;
+                self.block.clear();
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> [u8; 20] {
+        let bit_length = self.length * 8;
+        self.update(&[0x80]);
+        while self.block.len() != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_length.to_be_bytes());
+
+        let mut digest = [0; 20];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    #[allow(clippy::many_single_char_names)]
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0_u32; 80];
+        for (i, chunk) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1() {
+        let mut hash = Sha1::new();
+        hash.update(b"abc");
+        assert_eq!(
+            hash.finish(),
+            [
+                0xa9, 0x99, 0x3e, 0x36, 0x47, 0x06, 0x81, 0x6a, 0xba, 0x3e, 0x25, 0x71, 0x78, 0x50, 0xc2, 0x6c, 0x9c,
+                0xd0, 0xd8, 0x9d
+            ]
+        );
+    }
+}
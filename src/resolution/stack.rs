@@ -0,0 +1,278 @@
+use super::Resolution;
+use crate::dll::{DLLError::CLI, Result};
+use crate::resolved::{
+    body::{self, DataSection, ExceptionKind},
+    il::Instruction,
+    members::{MethodSource, UserMethod},
+    signature::MethodSignature,
+};
+
+macro_rules! throw {
+    ($($arg:tt)*) => {
+        return Err(CLI(scroll::Error::Custom(format!($($arg)*))))
+    }
+}
+
+// where control can go after an instruction
+pub(super) enum Flow<'a> {
+    Next,
+    Branch(usize),
+    ConditionalBranch(&'a [usize]),
+    // leave empties the evaluation stack before transferring control
+    Leave(usize),
+    End,
+}
+
+fn signature_effect<C, T>(sig: &MethodSignature<C, T>, pushes_this: bool) -> (usize, usize) {
+    let pops = sig.parameters.len()
+        + sig.varargs.as_ref().map_or(0, Vec::len)
+        + usize::from(sig.instance && !sig.explicit_this && !pushes_this);
+    let pushes = usize::from(sig.return_type.1.is_some() || pushes_this);
+
+    (pops, pushes)
+}
+
+fn user_method_effect(res: &Resolution, method: UserMethod, is_constructor: bool) -> (usize, usize) {
+    match method {
+        UserMethod::Definition(i) => signature_effect(&res[i].signature, is_constructor),
+        UserMethod::Reference(i) => signature_effect(&res[i].signature, is_constructor),
+    }
+}
+
+fn method_effect(res: &Resolution, method: &MethodSource) -> (usize, usize) {
+    match method {
+        MethodSource::User(u) => user_method_effect(res, *u, false),
+        MethodSource::Generic(g) => user_method_effect(res, g.base, false),
+    }
+}
+
+pub(super) fn flow(instruction: &Instruction) -> Flow<'_> {
+    use Instruction::*;
+
+    match instruction {
+        BranchEqual(t)
+        | BranchGreaterOrEqual(_, t)
+        | BranchGreater(_, t)
+        | BranchLessOrEqual(_, t)
+        | BranchLess(_, t)
+        | BranchNotEqual(t)
+        | BranchFalsy(t)
+        | BranchTruthy(t) => Flow::ConditionalBranch(std::slice::from_ref(t)),
+        Switch(ts) => Flow::ConditionalBranch(ts),
+        Branch(t) => Flow::Branch(*t),
+        Leave(t) => Flow::Leave(*t),
+        Return | Jump(_) | EndFinally | Rethrow | EndFilter | Throw => Flow::End,
+        _ => Flow::Next,
+    }
+}
+
+// returns the number of values popped and pushed by the instruction
+#[allow(clippy::too_many_lines)]
+fn effect(res: &Resolution, instruction: &Instruction) -> (usize, usize) {
+    use Instruction::*;
+
+    match instruction {
+        ArgumentList
+        | LoadArgument(_)
+        | LoadArgumentAddress(_)
+        | LoadConstantInt32(_)
+        | LoadConstantInt64(_)
+        | LoadConstantFloat32(_)
+        | LoadConstantFloat64(_)
+        | LoadMethodPointer(_)
+        | LoadLocal(_)
+        | LoadLocalAddress(_)
+        | LoadNull
+        | LoadStaticField { .. }
+        | LoadStaticFieldAddress(_)
+        | LoadString(_)
+        | LoadTokenField(_)
+        | LoadTokenMethod(_)
+        | LoadTokenType(_)
+        | Sizeof(_) => (0, 1),
+
+        Breakpoint | NoOperation | Branch(_) | Leave(_) | Return | Jump(_) | EndFinally | Rethrow => (0, 0),
+
+        CheckFinite
+        | Convert(_)
+        | ConvertOverflow(..)
+        | ConvertFloat32
+        | ConvertFloat64
+        | ConvertUnsignedToFloat
+        | LoadIndirect { .. }
+        | LocalMemoryAllocate
+        | Negate
+        | Not
+        | BoxValue(_)
+        | CastClass { .. }
+        | IsInstance(_)
+        | LoadField { .. }
+        | LoadFieldAddress(_)
+        | LoadFieldSkipNullCheck(_)
+        | LoadLength
+        | LoadObject { .. }
+        | LoadVirtualMethodPointer { .. }
+        | MakeTypedReference(_)
+        | NewArray(_)
+        | ReadTypedReferenceType
+        | ReadTypedReferenceValue(_)
+        | UnboxIntoAddress { .. }
+        | UnboxIntoValue(_) => (1, 1),
+
+        Add
+        | AddOverflow(_)
+        | And
+        | CompareEqual
+        | CompareGreater(_)
+        | CompareLess(_)
+        | Divide(_)
+        | Multiply
+        | MultiplyOverflow(_)
+        | Or
+        | Remainder(_)
+        | ShiftLeft
+        | ShiftRight(_)
+        | Subtract
+        | SubtractOverflow(_)
+        | Xor
+        | LoadElement { .. }
+        | LoadElementPrimitive { .. }
+        | LoadElementAddress { .. }
+        | LoadElementAddressReadonly(_) => (2, 1),
+
+        Duplicate => (1, 2),
+
+        Pop
+        | StoreArgument(_)
+        | StoreLocal(_)
+        | InitializeForObject(_)
+        | StoreStaticField { .. }
+        | BranchFalsy(_)
+        | BranchTruthy(_)
+        | Switch(_)
+        | EndFilter
+        | Throw => (1, 0),
+
+        StoreIndirect { .. }
+        | CopyObject(_)
+        | StoreField { .. }
+        | StoreFieldSkipNullCheck(_)
+        | StoreObject { .. }
+        | BranchEqual(_)
+        | BranchGreaterOrEqual(..)
+        | BranchGreater(..)
+        | BranchLessOrEqual(..)
+        | BranchLess(..)
+        | BranchNotEqual(_) => (2, 0),
+
+        CopyMemoryBlock { .. } | InitializeMemoryBlock { .. } | StoreElement { .. } | StoreElementPrimitive { .. } => {
+            (3, 0)
+        }
+
+        Call { param0: m, .. }
+        | CallConstrained(_, m)
+        | CallVirtual { param0: m, .. }
+        | CallVirtualConstrained(_, m)
+        | CallVirtualTail(m) => method_effect(res, m),
+        CallIndirect { param0: sig, .. } => {
+            let (pops, pushes) = signature_effect(sig, false);
+            // the function pointer is on top of the arguments
+            (pops + 1, pushes)
+        }
+        NewObject(m) => user_method_effect(res, *m, true),
+    }
+}
+
+/// Computes the maximum depth of the evaluation stack over all execution paths of a method body, following branches,
+/// `switch` tables, `leave` instructions and the entry points of exception handlers.
+///
+/// Method signatures are looked up in `res` to determine how many values each call consumes and produces.
+/// Returns an error if the body underflows the stack, reaches an instruction with two different stack depths,
+/// or branches to an instruction that does not exist.
+pub fn max_stack(res: &Resolution, body: &body::Method) -> Result<usize> {
+    let len = body.instructions.len();
+    let mut depths: Vec<Option<usize>> = vec![None; len];
+    let mut worklist = vec![];
+
+    macro_rules! enter {
+        ($from:expr, $target:expr, $depth:expr) => {{
+            let (target, depth) = ($target, $depth);
+            match depths.get_mut(target) {
+                Some(Some(d)) if *d != depth => throw!(
+                    "inconsistent stack depth at instruction {} (found {} and {}){}",
+                    target,
+                    d,
+                    depth,
+                    $from.map_or(String::new(), |f: usize| format!(", reached from instruction {}", f))
+                ),
+                Some(Some(_)) => {}
+                Some(slot) => {
+                    *slot = Some(depth);
+                    worklist.push(target);
+                }
+                None => throw!(
+                    "control flow target {} is out of bounds for a method with {} instructions",
+                    target,
+                    len
+                ),
+            }
+        }};
+    }
+
+    if len > 0 {
+        enter!(None, 0, 0);
+    }
+
+    for section in &body.data_sections {
+        if let DataSection::ExceptionHandlers(handlers) = section {
+            for h in handlers {
+                enter!(None, h.try_offset, 0);
+
+                // catch and filter blocks start with the exception object on the stack
+                let handler_depth = match h.kind {
+                    ExceptionKind::TypedException(_) => 1,
+                    ExceptionKind::Filter { offset } => {
+                        enter!(None, offset, 1);
+                        1
+                    }
+                    ExceptionKind::Finally | ExceptionKind::Fault => 0,
+                };
+                enter!(None, h.handler_offset, handler_depth);
+            }
+        }
+    }
+
+    let mut max = 0;
+    while let Some(idx) = worklist.pop() {
+        let depth = depths[idx].unwrap();
+        max = max.max(depth);
+
+        let instruction = &body.instructions[idx];
+        let (pops, pushes) = effect(res, instruction);
+        let Some(after) = depth.checked_sub(pops) else {
+            throw!(
+                "stack underflow at instruction {} ({} values needed, {} present)",
+                idx,
+                pops,
+                depth
+            )
+        };
+        let after = after + pushes;
+        max = max.max(after);
+
+        match flow(instruction) {
+            Flow::Next => enter!(Some(idx), idx + 1, after),
+            Flow::Branch(t) => enter!(Some(idx), t, after),
+            Flow::ConditionalBranch(ts) => {
+                for &t in ts {
+                    enter!(Some(idx), t, after);
+                }
+                enter!(Some(idx), idx + 1, after);
+            }
+            Flow::Leave(t) => enter!(Some(idx), t, 0),
+            Flow::End => {}
+        }
+    }
+
+    Ok(max)
+}
@@ -0,0 +1,264 @@
+//! Strong-name signing of assemblies (ECMA-335, II.6.2.1.3).
+//!
+//! Key pairs are read from the SNK format produced by `sn -k`, which is a `CryptoAPI` `PRIVATEKEYBLOB` holding an RSA key.
+//! Signatures are RSA PKCS #1 v1.5 signatures of the SHA-1 hash of the image, stored in little-endian byte order.
+
+use super::sha1::Sha1;
+use rsa::{rand_core::OsRng, BigUint, Pkcs1v15Sign, RsaPrivateKey};
+use scroll::{Pread, LE};
+
+macro_rules! throw {
+    ($($arg:tt)*) => {
+        return Err(scroll::Error::Custom(format!($($arg)*)))
+    }
+}
+
+const CALG_RSA_SIGN: u32 = 0x2400;
+const CALG_SHA1: u32 = 0x8004;
+const PUBLICKEYBLOB: u8 = 0x06;
+const PRIVATEKEYBLOB: u8 = 0x07;
+const RSA1: u32 = 0x3141_5352;
+const RSA2: u32 = 0x3241_5352;
+
+// DER encoding of the SHA-1 DigestInfo that precedes the digest, see RFC 8017, section 9.2
+const SHA1_DIGEST_INFO: [u8; 15] = [
+    0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04, 0x14,
+];
+
+fn padding() -> Pkcs1v15Sign {
+    Pkcs1v15Sign {
+        hash_len: Some(20),
+        prefix: SHA1_DIGEST_INFO.into(),
+    }
+}
+
+/// An RSA key pair used to sign assemblies.
+#[derive(Clone)]
+pub struct StrongNameKey {
+    bit_length: u32,
+    public_exponent: u32,
+    // little-endian, as in the key blob
+    modulus: Vec<u8>,
+    key: RsaPrivateKey,
+}
+
+// never print the private key
+impl std::fmt::Debug for StrongNameKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StrongNameKey")
+            .field("bit_length", &self.bit_length)
+            .field("public_key_token", &self.public_key_token())
+            .finish_non_exhaustive()
+    }
+}
+
+impl StrongNameKey {
+    /// Reads a key pair from the contents of an SNK file.
+    ///
+    /// Public-only key files, as used for delay signing, cannot be used to sign and are rejected.
+    pub fn from_snk(bytes: &[u8]) -> scroll::Result<Self> {
+        let offset = &mut 0;
+
+        let blob_type: u8 = bytes.gread_with(offset, LE)?;
+        if blob_type == PUBLICKEYBLOB {
+            throw!("strong name key only contains a public key");
+        } else if blob_type != PRIVATEKEYBLOB {
+            throw!("invalid strong name key blob type {:#04x}", blob_type);
+        }
+        *offset += 7; // version, reserved, key algorithm
+
+        let magic: u32 = bytes.gread_with(offset, LE)?;
+        if magic != RSA2 {
+            throw!("strong name key is not an RSA private key");
+        }
+        let bit_length: u32 = bytes.gread_with(offset, LE)?;
+        if bit_length == 0 || !bit_length.is_multiple_of(16) {
+            throw!("invalid strong name key length {}", bit_length);
+        }
+        let public_exponent: u32 = bytes.gread_with(offset, LE)?;
+
+        let len = bit_length as usize / 8;
+        let modulus: &[u8] = bytes.gread_with(offset, len)?;
+        let prime1: &[u8] = bytes.gread_with(offset, len / 2)?;
+        let prime2: &[u8] = bytes.gread_with(offset, len / 2)?;
+        // the CRT exponents and coefficient are computed again from the primes
+        *offset += 3 * len / 2;
+        let private_exponent: &[u8] = bytes.gread_with(offset, len)?;
+
+        let key = match RsaPrivateKey::from_components(
+            BigUint::from_bytes_le(modulus),
+            BigUint::from(public_exponent),
+            BigUint::from_bytes_le(private_exponent),
+            vec![BigUint::from_bytes_le(prime1), BigUint::from_bytes_le(prime2)],
+        ) {
+            Ok(k) => k,
+            Err(e) => throw!("invalid strong name key: {}", e),
+        };
+
+        Ok(Self {
+            bit_length,
+            public_exponent,
+            modulus: modulus.to_vec(),
+            key,
+        })
+    }
+
+    /// The public key blob that identifies this key, as stored in [`Assembly::public_key`](crate::resolved::assembly::Assembly::public_key).
+    pub fn public_key(&self) -> Vec<u8> {
+        let mut blob = Vec::with_capacity(32 + self.modulus.len());
+        blob.extend(CALG_RSA_SIGN.to_le_bytes());
+        blob.extend(CALG_SHA1.to_le_bytes());
+        blob.extend((20 + self.modulus.len() as u32).to_le_bytes());
+        // CryptoAPI PUBLICKEYBLOB
+        blob.extend([PUBLICKEYBLOB, 2, 0, 0]);
+        blob.extend(CALG_RSA_SIGN.to_le_bytes());
+        blob.extend(RSA1.to_le_bytes());
+        blob.extend(self.bit_length.to_le_bytes());
+        blob.extend(self.public_exponent.to_le_bytes());
+        blob.extend(&self.modulus);
+        blob
+    }
+
+    pub fn public_key_token(&self) -> [u8; 8] {
+        public_key_token(&self.public_key())
+    }
+
+    /// The size of the signature in bytes.
+    pub fn signature_size(&self) -> usize {
+        self.modulus.len()
+    }
+
+    // the private key operation is blinded, so that its timing doesn't depend on the key
+    fn sign(&self, digest: &[u8; 20]) -> scroll::Result<Vec<u8>> {
+        let mut signature = match self.key.sign_with_rng(&mut OsRng, padding(), digest) {
+            Ok(s) => s,
+            Err(e) => throw!("could not sign image: {}", e),
+        };
+        signature.reverse();
+        Ok(signature)
+    }
+
+    fn check(&self, signature: &[u8], digest: &[u8; 20]) -> bool {
+        let mut signature = signature.to_vec();
+        signature.reverse();
+        self.key.to_public_key().verify(padding(), digest, &signature).is_ok()
+    }
+
+    /// Checks the signature in the `signature_size()` bytes at `signature_offset` of a complete PE image against the
+    /// public key of this key pair.
+    pub fn verify_image(&self, image: &[u8], signature_offset: usize) -> scroll::Result<bool> {
+        let signature_range = signature_offset..signature_offset + self.signature_size();
+        let digest = image_hash(image, signature_range.clone())?;
+        let signature = image
+            .get(signature_range)
+            .ok_or(scroll::Error::BadOffset(signature_offset))?;
+        Ok(self.check(signature, &digest))
+    }
+
+    /// Hashes a complete PE image and writes its signature into the `signature_size()` bytes at `signature_offset`.
+    pub(crate) fn sign_image(&self, image: &mut [u8], signature_offset: usize) -> scroll::Result<()> {
+        let signature_range = signature_offset..signature_offset + self.signature_size();
+        let digest = image_hash(image, signature_range.clone())?;
+        let signature = self.sign(&digest)?;
+        image[signature_range].copy_from_slice(&signature);
+        Ok(())
+    }
+}
+
+/// Computes the token of a full public key blob, which is the last 8 bytes of its SHA-1 hash in reverse order.
+pub fn public_key_token(public_key: &[u8]) -> [u8; 8] {
+    let mut hash = Sha1::new();
+    hash.update(public_key);
+    let digest = hash.finish();
+
+    let mut token = [0; 8];
+    token.copy_from_slice(&digest[12..]);
+    token.reverse();
+    token
+}
+
+// the PE headers and section table with the checksum and certificate directory zeroed, followed by every section except
+// the signature itself
+// the padding between the section table and the first section is not hashed
+fn image_hash(image: &[u8], signature: std::ops::Range<usize>) -> scroll::Result<[u8; 20]> {
+    let pe_offset = image.pread_with::<u32>(0x3c, LE)? as usize;
+    let file_header = pe_offset + 4;
+    let num_sections: u16 = image.pread_with(file_header + 2, LE)?;
+    let optional_header_size: u16 = image.pread_with(file_header + 16, LE)?;
+
+    let optional_header = file_header + 20;
+    let data_directories = match image.pread_with::<u16>(optional_header, LE)? {
+        0x10b => optional_header + 96,
+        0x20b => optional_header + 112,
+        bad => throw!("invalid PE optional header magic {:#06x}", bad),
+    };
+    let section_headers = optional_header + optional_header_size as usize;
+    let headers_size = section_headers + 40 * num_sections as usize;
+
+    let mut headers = image
+        .get(..headers_size)
+        .ok_or(scroll::Error::BadOffset(headers_size))?
+        .to_vec();
+    headers[optional_header + 64..optional_header + 68].fill(0);
+    let certificate_directory = data_directories + 4 * 8;
+    headers[certificate_directory..certificate_directory + 8].fill(0);
+
+    let mut hash = Sha1::new();
+    hash.update(&headers);
+
+    for i in 0..num_sections as usize {
+        let header = section_headers + i * 40;
+        let size = image.pread_with::<u32>(header + 16, LE)? as usize;
+        let start = image.pread_with::<u32>(header + 20, LE)? as usize;
+        let end = start + size;
+        if end > image.len() {
+            throw!("section {} extends past the end of the image", i);
+        }
+
+        if start <= signature.start && signature.end <= end {
+            hash.update(&image[start..signature.start]);
+            hash.update(&image[signature.end..end]);
+        } else {
+            hash.update(&image[start..end]);
+        }
+    }
+
+    Ok(hash.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ecma_public_key_token() {
+        let ecma_key = [0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(
+            public_key_token(&ecma_key),
+            [0xb7, 0x7a, 0x5c, 0x56, 0x19, 0x34, 0xe0, 0x89]
+        );
+    }
+
+    // tests/test.snk converted with `openssl rsa -inform MSBLOB`, and "abc" signed with `openssl dgst -sha1 -sign`
+    const ABC_SIGNATURE: &str = "39b9a7438f08547269f2bf01241fa7885d04c1b767c07e4119f90f10ac75b763215c40af3772d2a8bb94791e05e1d89449805e527ff8af4a9ea95ec6b68b75e45deb0db4dfd5de089e8fab3f1d6458d2341fdab13de31c4ffa9daf21a8a363be4c95064311e6f57dd98f72abc89fc0cb4f911331a28cfdc69a1d927a19c027e4";
+
+    #[test]
+    fn known_signature() {
+        let key = StrongNameKey::from_snk(include_bytes!("../../tests/test.snk")).unwrap();
+        let mut hash = Sha1::new();
+        hash.update(b"abc");
+        let digest = hash.finish();
+
+        // strong name signatures are stored in little-endian byte order
+        let mut expected: Vec<_> = (0..ABC_SIGNATURE.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&ABC_SIGNATURE[i..i + 2], 16).unwrap())
+            .collect();
+        expected.reverse();
+
+        assert_eq!(key.sign(&digest).unwrap(), expected);
+        assert!(key.check(&expected, &digest));
+        expected[0] ^= 1;
+        assert!(!key.check(&expected, &digest));
+    }
+}
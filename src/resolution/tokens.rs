@@ -0,0 +1,94 @@
+//! Keeping metadata tokens stable across a read and write of the same module.
+//!
+//! When [`ReadOptions::record_tokens`](super::read::Options::record_tokens) is set, [`Resolution::parse`] records the
+//! row that every method, field, method reference and field reference was read from in
+//! [`Resolution::original_tokens`]. Writing with [`WriteOptions::preserve_tokens`](super::write::Options::preserve_tokens)
+//! then lays out those tables in their original order and keeps the original `#US` heap, so that the tokens of
+//! everything that was not added or removed stay the same. [`Resolution::write_with_remapping`] reports the tokens of
+//! any recorded rows that moved anyway.
+//!
+//! Type definitions, type references and the other entities that are addressed by their index in a [`Resolution`]
+//! already keep their rows, since their indices are the rows they were read from. Rows that only hold signatures
+//! (`TypeSpec`, `MethodSpec` and `StandAloneSig`) are rebuilt on every write, so their original signatures are recorded
+//! instead, and each one that moved is reported under the first row written with the same signature (and, for
+//! `MethodSpec`, the same method). Signatures that are no longer used by anything are not written, and are not reported.
+
+use super::{FieldIndex, FieldRefIndex, MethodIndex, MethodRefIndex};
+use crate::binary::metadata::table::Kind;
+use std::collections::{BTreeMap, HashMap};
+
+pub(crate) fn token(kind: Kind, row: usize) -> u32 {
+    (kind as u32) << 24 | row as u32
+}
+
+/// The rows that the members of a [`Resolution`](super::Resolution) were read from.
+#[derive(Debug, Clone)]
+pub struct OriginalTokens<'a> {
+    pub(crate) methods: HashMap<MethodIndex, usize>,
+    pub(crate) fields: HashMap<FieldIndex, usize>,
+    pub(crate) method_references: HashMap<MethodRefIndex, usize>,
+    pub(crate) field_references: HashMap<FieldRefIndex, usize>,
+    pub(crate) user_strings: &'a [u8],
+    // the signature blobs of the rows of the signature tables, in row order
+    pub(crate) type_specs: Vec<&'a [u8]>,
+    // the token of the method each instantiation is of
+    pub(crate) method_specs: Vec<(u32, &'a [u8])>,
+    pub(crate) stand_alone_sigs: Vec<&'a [u8]>,
+}
+
+impl OriginalTokens<'_> {
+    /// The `MethodDef` token that a method was read from.
+    pub fn method(&self, index: MethodIndex) -> Option<u32> {
+        self.methods.get(&index).map(|&r| token(Kind::MethodDef, r))
+    }
+
+    /// The `Field` token that a field was read from.
+    pub fn field(&self, index: FieldIndex) -> Option<u32> {
+        self.fields.get(&index).map(|&r| token(Kind::Field, r))
+    }
+
+    /// The `MemberRef` token that a method reference was read from.
+    pub fn method_reference(&self, index: MethodRefIndex) -> Option<u32> {
+        self.method_references.get(&index).map(|&r| token(Kind::MemberRef, r))
+    }
+
+    /// The `MemberRef` token that a field reference was read from.
+    pub fn field_reference(&self, index: FieldRefIndex) -> Option<u32> {
+        self.field_references.get(&index).map(|&r| token(Kind::MemberRef, r))
+    }
+}
+
+/// A map from the original tokens of rows that were written to a different row than they were read from to their new tokens.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TokenRemapping(BTreeMap<u32, u32>);
+
+impl TokenRemapping {
+    pub(crate) fn record(&mut self, kind: Kind, original_row: usize, new_row: usize) {
+        if original_row != new_row {
+            self.0.insert(token(kind, original_row), token(kind, new_row));
+        }
+    }
+
+    /// The new token of a row that moved, or `None` if the row kept its token or was never recorded.
+    pub fn get(&self, original: u32) -> Option<u32> {
+        self.0.get(&original).copied()
+    }
+
+    /// The token that `original` refers to in the written module, which is `original` itself if its row did not move.
+    pub fn remap(&self, original: u32) -> u32 {
+        self.get(original).unwrap_or(original)
+    }
+
+    /// Iterates over the original and new tokens of every row that moved, ordered by original token.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.0.iter().map(|(&o, &n)| (o, n))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
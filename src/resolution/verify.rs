@@ -0,0 +1,1041 @@
+use super::{
+    stack::{flow, Flow},
+    MethodIndex, Resolution, TypeIndex,
+};
+use crate::resolved::{
+    body::{self, DataSection, ExceptionKind},
+    il::*,
+    members::{FieldSource, MethodReferenceParent, MethodSource, UserMethod},
+    signature::{ManagedMethod, MethodSignature, ParameterType},
+    types::{BaseType, Kind, LocalVariable, MethodType, TypeSource, UserType, ValueKind},
+};
+use std::ops::Range;
+use thiserror::Error;
+
+/// The type of a value on the evaluation stack, as tracked by [`verify`].
+///
+/// These correspond to the verification types of ECMA-335, III.1.8.1.2 (page 318), with small integers and
+/// booleans widened to `Int32`. See also the table in ECMA-335, I.12.3.2.1 (page 85).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackType {
+    Int32,
+    Int64,
+    NativeInt,
+    Float,
+    /// An object reference. `None` means that the exact type is unknown, e.g. after two branches with different
+    /// reference types join.
+    Object(Option<MethodType>),
+    /// The `null` reference pushed by `ldnull`, which is assignable to any object reference.
+    Null,
+    /// A managed pointer (`&`) to a value of the given type, if it is known.
+    ManagedPointer(Option<MethodType>),
+    /// An unboxed value type.
+    Value(MethodType),
+    /// A value the verifier does not track the type of, such as a generic type variable or a `TypedReference`.
+    /// It is compatible with every other type.
+    Unknown,
+}
+
+/// The reason a method body failed verification.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ErrorKind {
+    #[error("stack underflow")]
+    StackUnderflow,
+    #[error("stack state {found:?} does not match {expected:?} where control flow joins")]
+    StackMismatch {
+        expected: Vec<StackType>,
+        found: Vec<StackType>,
+    },
+    #[error("the stack must be empty here, but it has {0} values")]
+    NonEmptyStack(usize),
+    #[error("branch target {0} is out of bounds")]
+    InvalidBranchTarget(usize),
+    #[error("control flow falls off the end of the method body")]
+    FallsOffEnd,
+    #[error("leave is only allowed in a try block or a catch handler")]
+    LeaveOutsideProtectedRegion,
+    #[error("endfinally is only allowed in a finally or fault handler")]
+    EndFinallyOutsideHandler,
+    #[error("control transfer to {0} crosses the boundary of a try block, handler or filter")]
+    CrossesRegionBoundary(usize),
+    #[error("argument {0} does not exist")]
+    InvalidArgument(u16),
+    #[error("local variable {0} does not exist")]
+    InvalidLocal(u16),
+    #[error("expected a value of type {expected:?}, found {found:?}")]
+    TypeMismatch { expected: StackType, found: StackType },
+    #[error("invalid operand of type {0:?}")]
+    InvalidOperand(StackType),
+    #[error("invalid operands of types {0:?} and {1:?}")]
+    InvalidOperands(StackType, StackType),
+}
+
+/// A verification error, along with the index of the instruction where it was found.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("instruction {instruction}: {kind}")]
+pub struct Error {
+    pub instruction: usize,
+    pub kind: ErrorKind,
+}
+
+fn is_value_type(res: &Resolution, idx: TypeIndex) -> bool {
+    matches!(
+        &res[idx].extends,
+        Some(TypeSource::User(u)) if matches!(u.type_name(res).as_str(), "System.ValueType" | "System.Enum")
+    )
+}
+
+fn stack_type(res: &Resolution, t: &MethodType) -> StackType {
+    use BaseType::*;
+
+    let MethodType::Base(b) = t else {
+        return StackType::Unknown;
+    };
+
+    match &**b {
+        Boolean | Char | Int8 | UInt8 | Int16 | UInt16 | Int32 | UInt32 => StackType::Int32,
+        Int64 | UInt64 => StackType::Int64,
+        IntPtr | UIntPtr | ValuePointer(..) | FunctionPointer(_) => StackType::NativeInt,
+        Float32 | Float64 => StackType::Float,
+        Object | String | Vector(..) | Array(..) => StackType::Object(Some(t.clone())),
+        Type { value_kind, source } => {
+            let definition = match source {
+                TypeSource::User(UserType::Definition(d)) => Some(*d),
+                _ => None,
+            };
+
+            // enums are verified as their underlying type
+            if let Some(d) = definition {
+                let def = &res[d];
+                if matches!(&def.extends, Some(TypeSource::User(u)) if u.type_name(res) == "System.Enum") {
+                    return def
+                        .fields
+                        .iter()
+                        .find(|f| !f.static_member)
+                        .map_or(StackType::Unknown, |f| {
+                            stack_type(res, &MethodType::from(f.return_type.clone()))
+                        });
+                }
+            }
+
+            match value_kind {
+                Some(ValueKind::ValueType) => StackType::Value(t.clone()),
+                Some(ValueKind::Class) => StackType::Object(Some(t.clone())),
+                None => match definition {
+                    Some(d) if is_value_type(res, d) => StackType::Value(t.clone()),
+                    Some(_) => StackType::Object(Some(t.clone())),
+                    None => StackType::Unknown,
+                },
+            }
+        }
+    }
+}
+
+fn parameter_type(res: &Resolution, p: &ParameterType<MethodType>) -> StackType {
+    match p {
+        ParameterType::Value(t) => stack_type(res, t),
+        ParameterType::Ref(t) => StackType::ManagedPointer(Some(t.clone())),
+        ParameterType::TypedReference => StackType::Unknown,
+    }
+}
+
+fn local_type(res: &Resolution, l: &LocalVariable) -> StackType {
+    match l {
+        LocalVariable::TypedReference => StackType::Unknown,
+        LocalVariable::Variable {
+            by_ref: true, var_type, ..
+        } => StackType::ManagedPointer(Some(var_type.clone())),
+        LocalVariable::Variable { var_type, .. } => stack_type(res, var_type),
+    }
+}
+
+fn field_type(res: &Resolution, f: FieldSource) -> MethodType {
+    match f {
+        FieldSource::Definition(i) => res[i].return_type.clone().into(),
+        FieldSource::Reference(i) => res[i].field_type.clone().into(),
+    }
+}
+
+fn load_type(t: LoadType) -> StackType {
+    use LoadType::*;
+    match t {
+        Int8 | UInt8 | Int16 | UInt16 | Int32 | UInt32 => StackType::Int32,
+        Int64 => StackType::Int64,
+        Float32 | Float64 => StackType::Float,
+        IntPtr => StackType::NativeInt,
+        Object => StackType::Object(None),
+    }
+}
+
+fn store_type(t: StoreType) -> StackType {
+    use StoreType::*;
+    match t {
+        Int8 | Int16 | Int32 => StackType::Int32,
+        Int64 => StackType::Int64,
+        Float32 | Float64 => StackType::Float,
+        IntPtr => StackType::NativeInt,
+        Object => StackType::Object(None),
+    }
+}
+
+fn is_external(t: &MethodType) -> bool {
+    matches!(
+        t.as_base(),
+        Some(BaseType::Type {
+            source: TypeSource::User(UserType::Reference(_)) | TypeSource::Generic { .. },
+            ..
+        })
+    )
+}
+
+// whether an object of type `from` may be used where `to` is expected
+// this is deliberately lenient, since types defined in other assemblies cannot be inspected
+fn object_assignable(res: &Resolution, from: &MethodType, to: &MethodType) -> bool {
+    let user_type = |t: &MethodType| match t.as_base() {
+        Some(BaseType::Type { source, .. }) => Some(match source {
+            TypeSource::User(u) | TypeSource::Generic { base: u, .. } => *u,
+        }),
+        _ => None,
+    };
+
+    if from == to || matches!(to.as_base(), Some(BaseType::Object)) {
+        return true;
+    }
+
+    let Some(UserType::Definition(target)) = user_type(to) else {
+        return true;
+    };
+    if matches!(res[target].flags.kind, Kind::Interface) {
+        return true;
+    }
+
+    let mut current = match user_type(from) {
+        Some(UserType::Definition(d)) => d,
+        Some(UserType::Reference(_)) => return true,
+        None => return false,
+    };
+    loop {
+        if current == target {
+            return true;
+        }
+        match &res[current].extends {
+            Some(
+                TypeSource::User(UserType::Definition(d))
+                | TypeSource::Generic {
+                    base: UserType::Definition(d),
+                    ..
+                },
+            ) => current = *d,
+            Some(_) => return true,
+            None => return false,
+        }
+    }
+}
+
+fn assignable(res: &Resolution, from: &StackType, to: &StackType) -> bool {
+    use StackType::*;
+
+    match (from, to) {
+        (Unknown, _)
+        | (_, Unknown)
+        | (Int32 | NativeInt, Int32 | NativeInt)
+        | (Int64, Int64)
+        | (Float, Float)
+        | (ManagedPointer(_), NativeInt)
+        | (NativeInt, ManagedPointer(_))
+        | (Null | Object(None), Object(_))
+        | (Object(_), Object(None)) => true,
+        (Object(Some(a)), Object(Some(b))) => object_assignable(res, a, b),
+        (ManagedPointer(a), ManagedPointer(b)) => match (a, b) {
+            (Some(a), Some(b)) => a == b || stack_type(res, a) == stack_type(res, b),
+            _ => true,
+        },
+        (Value(a), Value(b)) => a == b,
+        // a value type from another assembly could be an enum
+        (Int32 | Int64 | NativeInt, Value(t)) | (Value(t), Int32 | Int64 | NativeInt) => is_external(t),
+        _ => false,
+    }
+}
+
+// the type of a stack slot where two control flow paths join, if they are compatible
+fn merge(a: &StackType, b: &StackType) -> Option<StackType> {
+    use StackType::*;
+
+    Some(match (a, b) {
+        _ if a == b => a.clone(),
+        (Unknown, _) | (_, Unknown) => Unknown,
+        (Null, Object(t)) | (Object(t), Null) => Object(t.clone()),
+        (Object(_), Object(_)) => Object(None),
+        (Int32 | NativeInt, Int32 | NativeInt) => NativeInt,
+        (ManagedPointer(_), ManagedPointer(_)) => ManagedPointer(None),
+        _ => return None,
+    })
+}
+
+fn is_integer(t: &StackType) -> bool {
+    matches!(
+        t,
+        StackType::Int32 | StackType::Int64 | StackType::NativeInt | StackType::Unknown
+    )
+}
+
+fn is_numeric(t: &StackType) -> bool {
+    is_integer(t) || matches!(t, StackType::Float)
+}
+
+// ECMA-335, III.1.5 (page 303), tables III.2 and III.5
+fn binary_numeric(
+    a: StackType,
+    b: StackType,
+    integer_only: bool,
+    pointer_arithmetic: bool,
+) -> Result<StackType, ErrorKind> {
+    use StackType::*;
+
+    Ok(match (&a, &b) {
+        (Unknown, other) | (other, Unknown) if is_numeric(other) || *other == Unknown => other.clone(),
+        (Int32, Int32) => Int32,
+        (Int64, Int64) => Int64,
+        (Int32 | NativeInt, Int32 | NativeInt) => NativeInt,
+        (Float, Float) if !integer_only => Float,
+        (ManagedPointer(t), Int32 | NativeInt) | (Int32 | NativeInt, ManagedPointer(t)) if pointer_arithmetic => {
+            ManagedPointer(t.clone())
+        }
+        _ => return Err(ErrorKind::InvalidOperands(a, b)),
+    })
+}
+
+// ECMA-335, III.1.5 (page 303), table III.4
+fn comparable(a: &StackType, b: &StackType, equality: bool) -> bool {
+    use StackType::*;
+
+    match (a, b) {
+        (Unknown, _)
+        | (_, Unknown)
+        | (Int32 | NativeInt, Int32 | NativeInt)
+        | (Int64, Int64)
+        | (Float, Float)
+        | (ManagedPointer(_) | NativeInt, ManagedPointer(_) | NativeInt) => true,
+        (Object(_) | Null, Object(_) | Null) => equality,
+        _ => false,
+    }
+}
+
+fn protected_regions(body: &body::Method) -> impl Iterator<Item = &body::Exception> {
+    body.data_sections.iter().flat_map(|d| match d {
+        DataSection::ExceptionHandlers(es) => es.as_slice(),
+        DataSection::Unrecognized { .. } => &[],
+    })
+}
+
+enum Block {
+    Try,
+    // the handler of a typed or filtered exception clause
+    Catch,
+    // finally and fault handlers
+    Finally,
+    Filter,
+}
+
+fn blocks(body: &body::Method) -> impl Iterator<Item = (Range<usize>, Block)> + '_ {
+    protected_regions(body).flat_map(|e| {
+        let handler = e.handler_offset..e.handler_offset + e.handler_length;
+        [
+            Some((e.try_offset..e.try_offset + e.try_length, Block::Try)),
+            Some(match e.kind {
+                ExceptionKind::TypedException(_) | ExceptionKind::Filter { .. } => (handler, Block::Catch),
+                ExceptionKind::Finally | ExceptionKind::Fault => (handler, Block::Finally),
+            }),
+            match e.kind {
+                ExceptionKind::Filter { offset } => Some((offset..e.handler_offset, Block::Filter)),
+                _ => None,
+            },
+        ]
+        .into_iter()
+        .flatten()
+    })
+}
+
+struct Context<'r, 'a> {
+    res: &'r Resolution<'a>,
+    body: &'r body::Method,
+    arguments: Vec<(StackType, Option<MethodType>)>,
+    locals: Vec<(StackType, Option<MethodType>)>,
+    return_type: Option<StackType>,
+}
+
+impl Context<'_, '_> {
+    fn object_type(&self, t: &MethodType) -> StackType {
+        match stack_type(self.res, t) {
+            o @ StackType::Object(_) => o,
+            _ => StackType::Object(None),
+        }
+    }
+
+    fn constructed_type(&self, m: UserMethod) -> StackType {
+        match m {
+            UserMethod::Definition(i) => {
+                let t = BaseType::Type {
+                    value_kind: None,
+                    source: TypeSource::User(UserType::Definition(i.parent_type)),
+                }
+                .into();
+                stack_type(self.res, &t)
+            }
+            UserMethod::Reference(i) => match &self.res[i].parent {
+                MethodReferenceParent::Type(t) => match stack_type(self.res, t) {
+                    StackType::Unknown => StackType::Object(None),
+                    other => other,
+                },
+                _ => StackType::Object(None),
+            },
+        }
+    }
+
+    // pops the arguments of a call, checking them against the signature, and returns the type of the result
+    fn call<C>(
+        &self,
+        stack: &mut Vec<StackType>,
+        sig: &MethodSignature<C, MethodType>,
+        pop_this: bool,
+    ) -> Result<Option<StackType>, ErrorKind> {
+        let parameters: Vec<_> = sig
+            .parameters
+            .iter()
+            .chain(sig.varargs.iter().flatten())
+            .map(|p| parameter_type(self.res, &p.1))
+            .collect();
+
+        for expected in parameters.iter().rev() {
+            let found = pop(stack)?;
+            expect(self.res, &found, expected)?;
+        }
+
+        if pop_this && sig.instance && !sig.explicit_this {
+            let this = pop(stack)?;
+            if !matches!(
+                this,
+                StackType::Object(_)
+                    | StackType::Null
+                    | StackType::ManagedPointer(_)
+                    | StackType::Value(_)
+                    | StackType::Unknown
+            ) {
+                return Err(ErrorKind::InvalidOperand(this));
+            }
+        }
+
+        Ok(sig.return_type.1.as_ref().map(|t| parameter_type(self.res, t)))
+    }
+
+    fn user_method_signature(&self, m: UserMethod) -> &ManagedMethod<MethodType> {
+        match m {
+            UserMethod::Definition(i) => &self.res[i].signature,
+            UserMethod::Reference(i) => &self.res[i].signature,
+        }
+    }
+
+    fn method_source_signature(&self, m: &MethodSource) -> &ManagedMethod<MethodType> {
+        match m {
+            MethodSource::User(u) => self.user_method_signature(*u),
+            MethodSource::Generic(g) => self.user_method_signature(g.base),
+        }
+    }
+
+    // ECMA-335, I.12.4.2.8 (page 102): try blocks can only be entered at their first instruction,
+    // and only leave can exit a try block or catch handler. Finally, fault and filter blocks can't be exited by branches at all
+    fn check_transfer(&self, from: usize, to: usize, leave: bool) -> Result<(), ErrorKind> {
+        for (range, block) in blocks(self.body) {
+            let (from_inside, to_inside) = (range.contains(&from), range.contains(&to));
+            let valid = if to_inside && !from_inside {
+                matches!(block, Block::Try) && to == range.start
+            } else if from_inside && !to_inside {
+                leave && matches!(block, Block::Try | Block::Catch)
+            } else {
+                true
+            };
+            if !valid {
+                return Err(ErrorKind::CrossesRegionBoundary(to));
+            }
+        }
+        Ok(())
+    }
+
+    fn in_leave_region(&self, idx: usize) -> bool {
+        protected_regions(self.body).any(|e| {
+            let in_try = (e.try_offset..e.try_offset + e.try_length).contains(&idx);
+            let in_catch = matches!(e.kind, ExceptionKind::TypedException(_) | ExceptionKind::Filter { .. })
+                && (e.handler_offset..e.handler_offset + e.handler_length).contains(&idx);
+            in_try || in_catch
+        })
+    }
+
+    // simulates a single instruction, returning the stack state afterwards
+    #[allow(clippy::too_many_lines)]
+    fn step(&self, idx: usize, mut stack: Vec<StackType>) -> Result<Vec<StackType>, ErrorKind> {
+        use Instruction::*;
+        use StackType as S;
+
+        let res = self.res;
+        let s = &mut stack;
+
+        macro_rules! argument {
+            ($i:expr) => {
+                self.arguments
+                    .get(*$i as usize)
+                    .ok_or(ErrorKind::InvalidArgument(*$i))?
+            };
+        }
+        macro_rules! local {
+            ($i:expr) => {
+                self.locals
+                    .get(*$i as usize)
+                    .ok_or(ErrorKind::InvalidLocal(*$i))?
+            };
+        }
+        macro_rules! pop_expect {
+            ($check:expr) => {{
+                let v = pop(s)?;
+                #[allow(clippy::redundant_closure_call)]
+                if !($check)(&v) {
+                    return Err(ErrorKind::InvalidOperand(v));
+                }
+                v
+            }};
+        }
+
+        let is_address = |t: &StackType| matches!(t, S::ManagedPointer(_) | S::NativeInt | S::Unknown);
+        let is_reference = |t: &StackType| matches!(t, S::Object(_) | S::Null | S::Unknown);
+        let is_index = |t: &StackType| matches!(t, S::Int32 | S::NativeInt | S::Unknown);
+        let is_instance = |t: &StackType| {
+            matches!(
+                t,
+                S::Object(_) | S::ManagedPointer(_) | S::NativeInt | S::Value(_) | S::Unknown
+            )
+        };
+
+        match &self.body.instructions[idx] {
+            LoadArgument(i) => s.push(argument!(i).0.clone()),
+            LoadArgumentAddress(i) => s.push(S::ManagedPointer(argument!(i).1.clone())),
+            StoreArgument(i) => {
+                let expected = &argument!(i).0;
+                let found = pop(s)?;
+                expect(res, &found, expected)?;
+            }
+            LoadLocal(i) => s.push(local!(i).0.clone()),
+            LoadLocalAddress(i) => s.push(S::ManagedPointer(local!(i).1.clone())),
+            StoreLocal(i) => {
+                let expected = &local!(i).0;
+                let found = pop(s)?;
+                expect(res, &found, expected)?;
+            }
+
+            LoadConstantInt32(_) | Sizeof(_) => s.push(S::Int32),
+            LoadConstantInt64(_) => s.push(S::Int64),
+            LoadConstantFloat32(_) | LoadConstantFloat64(_) => s.push(S::Float),
+            LoadNull => s.push(S::Null),
+            LoadString(_) => s.push(S::Object(Some(BaseType::String.into()))),
+            ArgumentList | LoadTokenField(_) | LoadTokenMethod(_) | LoadTokenType(_) => s.push(S::Unknown),
+            LoadMethodPointer(_) => s.push(S::NativeInt),
+            LoadVirtualMethodPointer { .. } | LoadLength => {
+                pop_expect!(is_reference);
+                s.push(S::NativeInt);
+            }
+
+            Breakpoint | NoOperation | Branch(_) | Rethrow => {}
+
+            CheckFinite => {
+                pop_expect!(|t: &StackType| matches!(t, S::Float | S::Unknown));
+                s.push(S::Float);
+            }
+            Convert(t) | ConvertOverflow(t, _) => {
+                pop_expect!(|t: &StackType| is_numeric(t) || matches!(t, S::ManagedPointer(_)));
+                s.push(match t {
+                    ConversionType::Int64 | ConversionType::UInt64 => S::Int64,
+                    ConversionType::IntPtr | ConversionType::UIntPtr => S::NativeInt,
+                    _ => S::Int32,
+                });
+            }
+            ConvertFloat32 | ConvertFloat64 | ConvertUnsignedToFloat => {
+                pop_expect!(is_numeric);
+                s.push(S::Float);
+            }
+            Negate => {
+                let v = pop_expect!(is_numeric);
+                s.push(v);
+            }
+            Not => {
+                let v = pop_expect!(is_integer);
+                s.push(v);
+            }
+
+            Add | Subtract => {
+                let b = pop(s)?;
+                let a = pop(s)?;
+                let result = match (&a, &b) {
+                    (S::ManagedPointer(_), S::ManagedPointer(_))
+                        if matches!(&self.body.instructions[idx], Subtract) =>
+                    {
+                        S::NativeInt
+                    }
+                    _ => binary_numeric(a, b, false, true)?,
+                };
+                s.push(result);
+            }
+            Multiply | Divide(NumberSign::Signed) | Remainder(NumberSign::Signed) => {
+                let b = pop(s)?;
+                let a = pop(s)?;
+                s.push(binary_numeric(a, b, false, false)?);
+            }
+            AddOverflow(_) | SubtractOverflow(_) => {
+                let b = pop(s)?;
+                let a = pop(s)?;
+                s.push(binary_numeric(a, b, true, true)?);
+            }
+            MultiplyOverflow(_) | Divide(NumberSign::Unsigned) | Remainder(NumberSign::Unsigned) | And | Or | Xor => {
+                let b = pop(s)?;
+                let a = pop(s)?;
+                s.push(binary_numeric(a, b, true, false)?);
+            }
+            ShiftLeft | ShiftRight(_) => {
+                let amount = pop(s)?;
+                let value = pop(s)?;
+                if !is_integer(&value) || !is_index(&amount) {
+                    return Err(ErrorKind::InvalidOperands(value, amount));
+                }
+                s.push(value);
+            }
+            CompareEqual | CompareGreater(_) | CompareLess(_) => {
+                let b = pop(s)?;
+                let a = pop(s)?;
+                let equality = matches!(
+                    &self.body.instructions[idx],
+                    CompareEqual | CompareGreater(NumberSign::Unsigned)
+                );
+                if !comparable(&a, &b, equality) {
+                    return Err(ErrorKind::InvalidOperands(a, b));
+                }
+                s.push(S::Int32);
+            }
+            BranchEqual(_)
+            | BranchNotEqual(_)
+            | BranchGreaterOrEqual(..)
+            | BranchGreater(..)
+            | BranchLessOrEqual(..)
+            | BranchLess(..) => {
+                let b = pop(s)?;
+                let a = pop(s)?;
+                let equality = matches!(&self.body.instructions[idx], BranchEqual(_) | BranchNotEqual(_));
+                if !comparable(&a, &b, equality) {
+                    return Err(ErrorKind::InvalidOperands(a, b));
+                }
+            }
+            BranchFalsy(_) | BranchTruthy(_) => {
+                pop_expect!(|t: &StackType| !matches!(t, S::Float | S::Value(_)));
+            }
+            Switch(_) => {
+                pop_expect!(is_index);
+            }
+            Leave(_) => {
+                if !self.in_leave_region(idx) {
+                    return Err(ErrorKind::LeaveOutsideProtectedRegion);
+                }
+                s.clear();
+            }
+            Duplicate => {
+                let v = pop(s)?;
+                s.push(v.clone());
+                s.push(v);
+            }
+            Pop => {
+                pop(s)?;
+            }
+
+            LoadIndirect { param0: t, .. } => {
+                pop_expect!(is_address);
+                s.push(load_type(*t));
+            }
+            StoreIndirect { param0: t, .. } => {
+                let value = pop(s)?;
+                expect(res, &value, &store_type(*t))?;
+                pop_expect!(is_address);
+            }
+            LoadObject { param0: t, .. } => {
+                pop_expect!(is_address);
+                s.push(stack_type(res, t));
+            }
+            StoreObject { param0: t, .. } => {
+                let value = pop(s)?;
+                expect(res, &value, &stack_type(res, t))?;
+                pop_expect!(is_address);
+            }
+            CopyObject(_) => {
+                pop_expect!(is_address);
+                pop_expect!(is_address);
+            }
+            InitializeForObject(_) => {
+                pop_expect!(is_address);
+            }
+            CopyMemoryBlock { .. } | InitializeMemoryBlock { .. } => {
+                pop_expect!(is_index);
+                pop(s)?;
+                pop_expect!(is_address);
+            }
+            LocalMemoryAllocate => {
+                pop_expect!(is_index);
+                s.push(S::NativeInt);
+            }
+
+            LoadField { param0: f, .. } | LoadFieldSkipNullCheck(f) => {
+                pop_expect!(is_instance);
+                s.push(stack_type(res, &field_type(res, *f)));
+            }
+            LoadFieldAddress(f) => {
+                pop_expect!(is_instance);
+                s.push(S::ManagedPointer(Some(field_type(res, *f))));
+            }
+            StoreField { param0: f, .. } | StoreFieldSkipNullCheck(f) => {
+                let value = pop(s)?;
+                expect(res, &value, &stack_type(res, &field_type(res, *f)))?;
+                pop_expect!(|t: &StackType| !matches!(t, S::Value(_)) && is_instance(t));
+            }
+            LoadStaticField { param0: f, .. } => s.push(stack_type(res, &field_type(res, *f))),
+            LoadStaticFieldAddress(f) => s.push(S::ManagedPointer(Some(field_type(res, *f)))),
+            StoreStaticField { param0: f, .. } => {
+                let value = pop(s)?;
+                expect(res, &value, &stack_type(res, &field_type(res, *f)))?;
+            }
+
+            BoxValue(t) => {
+                let value = pop(s)?;
+                expect(res, &value, &stack_type(res, t))?;
+                s.push(self.object_type(t));
+            }
+            UnboxIntoAddress { param0: t, .. } => {
+                pop_expect!(is_reference);
+                s.push(S::ManagedPointer(Some(t.clone())));
+            }
+            UnboxIntoValue(t) => {
+                pop_expect!(is_reference);
+                s.push(stack_type(res, t));
+            }
+            CastClass { param0: t, .. } | IsInstance(t) => {
+                pop_expect!(is_reference);
+                s.push(self.object_type(t));
+            }
+
+            NewArray(t) => {
+                pop_expect!(is_index);
+                s.push(S::Object(Some(BaseType::vector(t.clone()).into())));
+            }
+            LoadElement { param0: t, .. } => {
+                pop_expect!(is_index);
+                pop_expect!(is_reference);
+                s.push(stack_type(res, t));
+            }
+            LoadElementPrimitive { param0: t, .. } => {
+                pop_expect!(is_index);
+                pop_expect!(is_reference);
+                s.push(load_type(*t));
+            }
+            LoadElementAddress { param0: t, .. } | LoadElementAddressReadonly(t) => {
+                pop_expect!(is_index);
+                pop_expect!(is_reference);
+                s.push(S::ManagedPointer(Some(t.clone())));
+            }
+            StoreElement { param0: t, .. } => {
+                let value = pop(s)?;
+                expect(res, &value, &stack_type(res, t))?;
+                pop_expect!(is_index);
+                pop_expect!(is_reference);
+            }
+            StoreElementPrimitive { param0: t, .. } => {
+                let value = pop(s)?;
+                expect(res, &value, &store_type(*t))?;
+                pop_expect!(is_index);
+                pop_expect!(is_reference);
+            }
+
+            MakeTypedReference(_) => {
+                pop_expect!(is_address);
+                s.push(S::Unknown);
+            }
+            ReadTypedReferenceType => {
+                pop(s)?;
+                s.push(S::Unknown);
+            }
+            ReadTypedReferenceValue(t) => {
+                pop(s)?;
+                s.push(S::ManagedPointer(Some(t.clone())));
+            }
+
+            Call { param0: m, .. }
+            | CallConstrained(_, m)
+            | CallVirtual { param0: m, .. }
+            | CallVirtualConstrained(_, m)
+            | CallVirtualTail(m) => {
+                let sig = self.method_source_signature(m);
+                if let Some(ret) = self.call(s, sig, true)? {
+                    s.push(ret);
+                }
+            }
+            CallIndirect { param0: sig, .. } => {
+                pop_expect!(|t: &StackType| matches!(t, S::NativeInt | S::Unknown));
+                if let Some(ret) = self.call(s, sig, true)? {
+                    s.push(ret);
+                }
+            }
+            NewObject(m) => {
+                self.call(s, self.user_method_signature(*m), false)?;
+                s.push(self.constructed_type(*m));
+            }
+
+            Return => {
+                if let Some(expected) = &self.return_type {
+                    let found = pop(s)?;
+                    expect(res, &found, expected)?;
+                }
+                if !s.is_empty() {
+                    return Err(ErrorKind::NonEmptyStack(s.len()));
+                }
+            }
+            Jump(_) => {
+                if !s.is_empty() {
+                    return Err(ErrorKind::NonEmptyStack(s.len()));
+                }
+            }
+            EndFinally => {
+                let in_handler = blocks(self.body).any(|(r, b)| matches!(b, Block::Finally) && r.contains(&idx));
+                if !in_handler {
+                    return Err(ErrorKind::EndFinallyOutsideHandler);
+                }
+                s.clear();
+            }
+            EndFilter => {
+                pop_expect!(is_index);
+                if !s.is_empty() {
+                    return Err(ErrorKind::NonEmptyStack(s.len()));
+                }
+            }
+            Throw => {
+                pop_expect!(is_reference);
+            }
+        }
+
+        Ok(stack)
+    }
+}
+
+fn pop(stack: &mut Vec<StackType>) -> Result<StackType, ErrorKind> {
+    stack.pop().ok_or(ErrorKind::StackUnderflow)
+}
+
+fn expect(res: &Resolution, found: &StackType, expected: &StackType) -> Result<(), ErrorKind> {
+    if assignable(res, found, expected) {
+        Ok(())
+    } else {
+        Err(ErrorKind::TypeMismatch {
+            expected: expected.clone(),
+            found: found.clone(),
+        })
+    }
+}
+
+// records the state on entry to an instruction, merging it with any existing state
+fn enter(
+    states: &mut [Option<Vec<StackType>>],
+    from: usize,
+    target: usize,
+    state: Vec<StackType>,
+    worklist: &mut Vec<usize>,
+) -> Result<(), Error> {
+    let error = |kind| Error {
+        instruction: from,
+        kind,
+    };
+
+    let len = states.len();
+    let Some(slot) = states.get_mut(target) else {
+        return Err(error(if target == len {
+            ErrorKind::FallsOffEnd
+        } else {
+            ErrorKind::InvalidBranchTarget(target)
+        }));
+    };
+
+    match slot {
+        None => {
+            *slot = Some(state);
+            worklist.push(target);
+        }
+        Some(existing) => {
+            let mismatch = || {
+                error(ErrorKind::StackMismatch {
+                    expected: existing.clone(),
+                    found: state.clone(),
+                })
+            };
+            if existing.len() != state.len() {
+                return Err(mismatch());
+            }
+
+            let merged = existing
+                .iter()
+                .zip(&state)
+                .map(|(a, b)| merge(a, b))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(mismatch)?;
+
+            if merged != *existing {
+                *existing = merged;
+                worklist.push(target);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the body of a method by simulating the evaluation stack along every control flow path.
+///
+/// This reports stack underflow, stack states that differ where branches join, out of bounds branch targets,
+/// branches into or out of protected regions and handlers other than through `leave` or to the start of a try block,
+/// `leave` instructions outside of try blocks and catch handlers, `endfinally` instructions outside of finally and
+/// fault handlers, and operands whose types do not match what an instruction, a call's signature, or a field's type
+/// requires. Types that cannot be inspected, such as types from other assemblies or generic type variables, are
+/// checked leniently, so a body that passes may still be rejected by the runtime.
+///
+/// At most one error is reported per instruction, and paths are not followed past an error.
+/// Returns no errors if the method has no body.
+#[allow(clippy::too_many_lines)]
+pub fn verify(res: &Resolution, method: MethodIndex) -> Vec<Error> {
+    let m = &res[method];
+    let Some(body) = &m.body else {
+        return vec![];
+    };
+
+    let mut arguments = vec![];
+    if m.signature.instance && !m.signature.explicit_this {
+        let parent = method.parent_type;
+        let generics = res[parent].generic_parameters.len();
+        let source = if generics == 0 {
+            TypeSource::User(UserType::Definition(parent))
+        } else {
+            TypeSource::generic(parent, (0..generics).map(MethodType::TypeGeneric).collect())
+        };
+
+        arguments.push(if is_value_type(res, parent) {
+            let t: MethodType = BaseType::valuetype(source).into();
+            (StackType::ManagedPointer(Some(t.clone())), Some(t))
+        } else {
+            let t: MethodType = BaseType::class(source).into();
+            (StackType::Object(Some(t.clone())), Some(t))
+        });
+    }
+    for p in &m.signature.parameters {
+        arguments.push((
+            parameter_type(res, &p.1),
+            match &p.1 {
+                ParameterType::Value(t) => Some(t.clone()),
+                _ => None,
+            },
+        ));
+    }
+
+    let ctx = Context {
+        res,
+        body,
+        arguments,
+        locals: body
+            .header
+            .local_variables
+            .iter()
+            .map(|l| {
+                (
+                    local_type(res, l),
+                    match l {
+                        LocalVariable::Variable {
+                            by_ref: false,
+                            var_type,
+                            ..
+                        } => Some(var_type.clone()),
+                        _ => None,
+                    },
+                )
+            })
+            .collect(),
+        return_type: m.signature.return_type.1.as_ref().map(|t| parameter_type(res, t)),
+    };
+
+    let len = body.instructions.len();
+    let mut states: Vec<Option<Vec<StackType>>> = vec![None; len];
+    let mut failed = vec![false; len];
+    let mut errors = vec![];
+    let mut worklist = vec![];
+
+    if len > 0 {
+        worklist.push(0);
+        states[0] = Some(vec![]);
+    }
+
+    for e in protected_regions(body) {
+        let handler_entry = match &e.kind {
+            ExceptionKind::TypedException(t) => vec![ctx.object_type(t)],
+            ExceptionKind::Filter { offset } => {
+                let entry = vec![StackType::Object(None)];
+                if let Err(e) = enter(&mut states, *offset, *offset, entry.clone(), &mut worklist) {
+                    errors.push(e);
+                }
+                entry
+            }
+            ExceptionKind::Finally | ExceptionKind::Fault => vec![],
+        };
+
+        for (target, state) in [(e.try_offset, vec![]), (e.handler_offset, handler_entry)] {
+            if let Err(e) = enter(&mut states, target, target, state, &mut worklist) {
+                errors.push(e);
+            }
+        }
+    }
+
+    while let Some(idx) = worklist.pop() {
+        if failed[idx] {
+            continue;
+        }
+
+        let state = states[idx].clone().unwrap();
+        let after = match ctx.step(idx, state) {
+            Ok(s) => s,
+            Err(kind) => {
+                failed[idx] = true;
+                errors.push(Error { instruction: idx, kind });
+                continue;
+            }
+        };
+
+        let transfer = |t: usize, leave: bool| {
+            ctx.check_transfer(idx, t, leave)
+                .map_err(|kind| Error { instruction: idx, kind })
+        };
+        let result = match flow(&body.instructions[idx]) {
+            Flow::Next => enter(&mut states, idx, idx + 1, after, &mut worklist),
+            Flow::Branch(t) => transfer(t, false).and_then(|()| enter(&mut states, idx, t, after, &mut worklist)),
+            Flow::Leave(t) => transfer(t, true).and_then(|()| enter(&mut states, idx, t, after, &mut worklist)),
+            Flow::ConditionalBranch(ts) => ts.iter().chain(std::iter::once(&(idx + 1))).try_for_each(|&t| {
+                // falling through to the next instruction is not a branch
+                if t != idx + 1 {
+                    transfer(t, false)?;
+                }
+                enter(&mut states, idx, t, after.clone(), &mut worklist)
+            }),
+            Flow::End => Ok(()),
+        };
+        if let Err(e) = result {
+            failed[idx] = true;
+            errors.push(e);
+        }
+    }
+
+    errors.sort_by_key(|e| e.instruction);
+    errors
+}
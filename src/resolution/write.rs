@@ -1,42 +1,205 @@
-use super::{EntryPoint, FieldIndex, MethodIndex, MethodMemberIndex, Resolution, TypeIndex};
+use super::{
+    sha1::Sha1,
+    stack,
+    strong_name::StrongNameKey,
+    tokens::{self, TokenRemapping},
+    EntryPoint, FieldIndex, FieldRefIndex, MethodIndex, MethodMemberIndex, MethodRefIndex, Resolution, TypeIndex,
+};
 use crate::binary::{
     cli::{Header, Metadata, RVASize},
     heap::*,
     metadata::{header, index, table::*},
-    method,
+    method, pdb,
     signature::kinds::MarshalSpec,
     stream,
 };
 use crate::convert;
-use crate::dll::Result;
+use crate::dll::{DLLError::CLI, Result};
 use crate::prelude::SecurityDeclaration;
 use crate::resolved::{
     assembly::HashAlgorithm,
     attribute::Attribute,
-    body,
+    body, debug,
     generic::{Generic, Variance},
     members::{
-        BodyFormat, BodyManagement, CharacterSet, Constant as ConstantValue, FieldReferenceParent, FieldSource,
-        MethodReferenceParent, PInvoke, UnmanagedCallingConvention, UserMethod, VtableLayout,
+        self, BodyFormat, BodyManagement, CharacterSet, Constant as ConstantValue, FieldReferenceParent, FieldSource,
+        Method, MethodReferenceParent, PInvoke, ParameterMetadata, UnmanagedCallingConvention, UserMethod,
+        VtableLayout,
     },
     resource::{Implementation, Visibility},
     signature::CallingConvention,
-    types::{Layout, ResolutionScope, TypeImplementation},
+    types::{Layout, ResolutionScope, TypeDefinition, TypeImplementation, ValueKind},
 };
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use object::{
-    endian::{LittleEndian, U32Bytes},
+    endian::{LittleEndian, U16Bytes, U32Bytes},
     pe,
     write::pe::{Writer as PEWriter, *},
 };
 use scroll::Pwrite;
 use scroll_buffer::DynamicBuffer;
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap};
 use tracing::debug;
 
+/// The processor architecture that an image targets, which determines the PE format, machine type and CLI header flags.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum Platform {
+    /// A PE32 image that runs as a 64-bit process where possible.
+    AnyCpu,
+    /// A PE32 image that runs as a 32-bit process where possible.
+    AnyCpu32BitPreferred,
+    /// A PE32 image that must run as a 32-bit x86 process.
+    X86,
+    /// A PE32+ image for x64.
+    #[default]
+    X64,
+    /// A PE32+ image for ARM64.
+    Arm64,
+}
+
+impl Platform {
+    pub fn is_64_bit(self) -> bool {
+        matches!(self, Platform::X64 | Platform::Arm64)
+    }
+
+    fn machine(self) -> u16 {
+        match self {
+            Platform::AnyCpu | Platform::AnyCpu32BitPreferred | Platform::X86 => pe::IMAGE_FILE_MACHINE_I386,
+            Platform::X64 => pe::IMAGE_FILE_MACHINE_AMD64,
+            Platform::Arm64 => pe::IMAGE_FILE_MACHINE_ARM64,
+        }
+    }
+
+    fn cli_flags(self) -> u32 {
+        match self {
+            Platform::AnyCpu32BitPreferred => {
+                pe::COMIMAGE_FLAGS_ILONLY | pe::COMIMAGE_FLAGS_32BITREQUIRED | pe::COMIMAGE_FLAGS_32BITPREFERRED
+            }
+            Platform::X86 => pe::COMIMAGE_FLAGS_ILONLY | pe::COMIMAGE_FLAGS_32BITREQUIRED,
+            _ => pe::COMIMAGE_FLAGS_ILONLY,
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum Subsystem {
+    #[default]
+    Console,
+    Windows,
+}
+
+/// The security features that an image opts into, which are written to the `DllCharacteristics` field of the PE header.
 #[derive(Debug, Default, Copy, Clone)]
+pub struct DllCharacteristics {
+    pub high_entropy_va: bool,
+    pub dynamic_base: bool,
+    pub nx_compatible: bool,
+    pub no_seh: bool,
+    pub terminal_server_aware: bool,
+}
+
+impl DllCharacteristics {
+    pub fn to_mask(self) -> u16 {
+        build_bitmask!(self,
+            high_entropy_va => 0x0020,
+            dynamic_base => 0x0040,
+            nx_compatible => 0x0100,
+            no_seh => 0x0400,
+            terminal_server_aware => 0x8000)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Options {
+    /// [`Default`] value of [`Platform::X64`].
+    pub platform: Platform,
+    /// If this flag is set and `platform` is a 64-bit platform, [`Platform::AnyCpu`] is used instead,
+    /// which writes the same PE32 image that this flag did before platforms could be selected.
+    ///
+    /// [`Default`] value of `false`.
+    #[deprecated(note = "set `platform` instead")]
     pub is_32_bit: bool,
     pub is_executable: bool,
+    /// [`Default`] value of [`Subsystem::Console`].
+    pub subsystem: Subsystem,
+    /// The version string in the metadata root, which selects the runtime that loads the image (for example, `v4.0.30319`).
+    ///
+    /// [`Default`] value of `"Standard CLI 2005"`.
+    pub metadata_version: Cow<'static, str>,
+    /// The major and minor runtime version in the CLI header.
+    ///
+    /// [`Default`] value of `(0, 0)`.
+    pub runtime_version: (u16, u16),
+    /// [`Default`] value of `0x400000`.
+    pub image_base: u64,
+    /// [`Default`] value of no characteristics.
+    pub dll_characteristics: DllCharacteristics,
+    /// The timestamp in the PE header and debug directory, in seconds since the Unix epoch.
+    /// If not set, the current time is used, so setting this is required for reproducible output.
+    ///
+    /// [`Default`] value of `None`.
+    pub time_date_stamp: Option<u32>,
+    /// If this flag is set, the module's MVID and the PE timestamp are derived from a hash of the metadata and IL,
+    /// so that writing the same resolution always produces the same bytes.
+    /// [`Module::mvid`](crate::resolved::module::Module::mvid) is ignored, but an explicit `time_date_stamp` is still used.
+    ///
+    /// [`Default`] value of `false`.
+    pub deterministic: bool,
+    /// If this flag is set, the maximum stack size of every method body is computed with [`stack::max_stack`]
+    /// instead of being taken from [`body::Header::maximum_stack_size`].
+    ///
+    /// [`Default`] value of `false`.
+    pub compute_max_stack: bool,
+    /// If a key is given, the written assembly is strong-name signed with it.
+    /// The assembly's public key is filled in from the key if it is not already set.
+    ///
+    /// [`Default`] value of `None`.
+    pub strong_name_key: Option<StrongNameKey>,
+    /// If this flag is set and the resolution has [`Resolution::original_tokens`], methods, fields and member references
+    /// are written in the order they were read in and the original `#US` heap is kept, so that their tokens do not change.
+    /// See the [`tokens`](super::tokens) module for details.
+    ///
+    /// [`Default`] value of `false`.
+    pub preserve_tokens: bool,
+}
+
+impl Options {
+    #[allow(deprecated)]
+    fn platform(&self) -> Platform {
+        if self.is_32_bit && self.platform.is_64_bit() {
+            Platform::AnyCpu
+        } else {
+            self.platform
+        }
+    }
+}
+
+#[allow(deprecated)]
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            platform: Platform::default(),
+            is_32_bit: false,
+            is_executable: false,
+            subsystem: Subsystem::default(),
+            metadata_version: Cow::Borrowed("Standard CLI 2005"),
+            runtime_version: (0, 0),
+            image_base: 0x0040_0000,
+            dll_characteristics: DllCharacteristics::default(),
+            time_date_stamp: None,
+            deterministic: false,
+            compute_max_stack: false,
+            strong_name_key: None,
+            preserve_tokens: false,
+        }
+    }
+}
+
+macro_rules! throw {
+    ($($arg:tt)*) => {
+        return Err(CLI(scroll::Error::Custom(format!($($arg)*))))
+    }
 }
 
 macro_rules! heap_idx {
@@ -54,7 +217,18 @@ macro_rules! opt_heap {
     };
 }
 
-fn _write_attrs<'r, 'data: 'r>(
+#[derive(Debug, Copy, Clone)]
+pub(super) enum MemberRefSource {
+    Method(MethodRefIndex),
+    Field(FieldRefIndex),
+}
+
+// the sort is stable, so anything without an original row keeps its place after everything that has one
+fn sort_by_original_row<T>(items: &mut [T], row: impl Fn(&T) -> Option<usize>) {
+    items.sort_by_key(|i| row(i).unwrap_or(usize::MAX));
+}
+
+pub(super) fn write_attrs<'r, 'data: 'r>(
     all_attrs: &mut Vec<(&'r Attribute<'data>, index::HasCustomAttribute)>,
     source: &'r [Attribute<'data>],
     parent: index::HasCustomAttribute,
@@ -62,7 +236,7 @@ fn _write_attrs<'r, 'data: 'r>(
     all_attrs.extend(source.iter().map(|r| (r, parent)));
 }
 
-fn _write_security<'r, 'data: 'r>(
+fn write_security<'r, 'data: 'r>(
     s: &'r Option<SecurityDeclaration<'data>>,
     parent: index::HasDeclSecurity,
     blobs: &mut BlobWriter,
@@ -77,12 +251,12 @@ fn _write_security<'r, 'data: 'r>(
             permission_set: heap_idx!(blobs, &s.value),
         });
 
-        _write_attrs(all_attrs, &s.attributes, index::HasCustomAttribute::DeclSecurity(idx));
+        write_attrs(all_attrs, &s.attributes, index::HasCustomAttribute::DeclSecurity(idx));
     }
     Ok(())
 }
 
-fn _write_generic<'r, 'data: 'r, T: convert::TypeKind>(
+fn write_generic<'r, 'data: 'r, T: convert::TypeKind>(
     gs: &'r [Generic<'data, T>],
     parent: index::TypeOrMethodDef,
     strings: &mut StringsWriter,
@@ -109,7 +283,7 @@ fn _write_generic<'r, 'data: 'r, T: convert::TypeKind>(
             owner: parent,
             name: heap_idx!(strings, g.name),
         });
-        _write_attrs(
+        write_attrs(
             all_attrs,
             &g.attributes,
             index::HasCustomAttribute::GenericParam(table_idx),
@@ -122,7 +296,7 @@ fn _write_generic<'r, 'data: 'r, T: convert::TypeKind>(
                 owner: table_idx.into(),
                 constraint: convert::write::idx_with_modifiers(&c.constraint_type, &c.custom_modifiers, ctx)?,
             });
-            _write_attrs(
+            write_attrs(
                 all_attrs,
                 &c.attributes,
                 index::HasCustomAttribute::GenericParamConstraint(constraint_idx),
@@ -133,7 +307,7 @@ fn _write_generic<'r, 'data: 'r, T: convert::TypeKind>(
     Ok(())
 }
 
-fn _write_default(
+pub(super) fn write_default(
     d: &Option<ConstantValue>,
     parent: index::HasConstant,
     blobs: &mut BlobWriter,
@@ -178,7 +352,7 @@ fn _write_default(
     Ok(())
 }
 
-fn _write_pinvoke(
+pub(super) fn write_pinvoke(
     p: &Option<PInvoke>,
     parent: index::MemberForwarded,
     strings: &mut StringsWriter,
@@ -208,41 +382,899 @@ fn _write_pinvoke(
     Ok(())
 }
 
-fn _write_marshal(
-    spec: Option<MarshalSpec>,
-    parent: index::HasFieldMarshal,
-    field_marshal: &mut Vec<FieldMarshal>,
-    ctx: &mut convert::write::Context,
-) -> Result<()> {
-    if let Some(s) = spec {
-        field_marshal.push(FieldMarshal {
-            parent,
-            native_type: convert::write::into_blob(s, ctx)?,
-        });
-    }
-    Ok(())
-}
+pub(super) fn write_marshal(
+    spec: Option<&MarshalSpec>,
+    parent: index::HasFieldMarshal,
+    field_marshal: &mut Vec<FieldMarshal>,
+    ctx: &mut convert::write::Context,
+) -> Result<()> {
+    if let Some(s) = spec {
+        field_marshal.push(FieldMarshal {
+            parent,
+            native_type: convert::write::into_blob(s.clone(), ctx)?,
+        });
+    }
+    Ok(())
+}
+
+pub(super) fn type_flags(t: &TypeDefinition) -> u32 {
+    let mut f = t.flags.to_mask();
+    if t.security.is_some() {
+        f |= 0x0004_0000;
+    }
+    f
+}
+
+pub(super) fn field_flags(f: &members::Field) -> u16 {
+    let mut mask = build_bitmask!(f,
+        static_member => 0x0010,
+        init_only => 0x0020,
+        literal => 0x0040,
+        not_serialized => 0x0080,
+        special_name => 0x0200,
+        runtime_special_name => 0x0400);
+    mask |= f.accessibility.to_mask();
+    if f.pinvoke.is_some() {
+        mask |= 0x2000;
+    }
+    if f.marshal.is_some() {
+        mask |= 0x1000;
+    }
+    if f.default.is_some() {
+        mask |= 0x8000;
+    }
+    if f.initial_value.is_some() {
+        mask |= 0x0100;
+    }
+    mask
+}
+
+pub(super) fn method_impl_flags(m: &Method) -> u16 {
+    let mut mask = build_bitmask!(m,
+        forward_ref => 0x0010,
+        preserve_sig => 0x0080,
+        internal_call => 0x1000,
+        synchronized => 0x0020,
+        no_inlining => 0x0008,
+        no_optimization => 0x0040);
+    mask |= match m.body_format {
+        BodyFormat::IL => 0x0,
+        BodyFormat::Native => 0x1,
+        BodyFormat::Runtime => 0x3,
+    };
+    mask |= match m.body_management {
+        BodyManagement::Unmanaged => 0x4,
+        BodyManagement::Managed => 0x0,
+    };
+    mask
+}
+
+pub(super) fn method_flags(m: &Method) -> u16 {
+    let mut mask = build_bitmask!(m,
+        sealed => 0x0020,
+        virtual_member => 0x0040,
+        hide_by_sig => 0x0080,
+        strict => 0x0200,
+        abstract_member => 0x0400,
+        special_name => 0x0800,
+        runtime_special_name => 0x1000,
+        require_sec_object => 0x8000);
+    if m.is_static() {
+        mask |= 0x0010;
+    }
+    mask |= m.accessibility.to_mask();
+    mask |= match m.vtable_layout {
+        VtableLayout::ReuseSlot => 0x0000,
+        VtableLayout::NewSlot => 0x0100,
+    };
+    if m.pinvoke.is_some() {
+        mask |= 0x2000;
+    }
+    if m.security.is_some() {
+        mask |= 0x4000;
+    }
+    mask
+}
+
+pub(super) fn method_signature(m: &Method, ctx: &mut convert::write::Context) -> Result<index::Blob> {
+    if m.generic_parameters.is_empty() {
+        convert::write::method_def(&m.signature, ctx)
+    } else {
+        let mut sig = m.signature.clone();
+        sig.calling_convention = CallingConvention::Generic(m.generic_parameters.len());
+        convert::write::method_def(&sig, ctx)
+    }
+}
+
+pub(super) fn param_flags(p: &ParameterMetadata) -> u16 {
+    let mut mask = build_bitmask!(p,
+        is_in => 0x0001,
+        is_out => 0x0002,
+        optional => 0x0010);
+    if p.default.is_some() {
+        mask |= 0x1000;
+    }
+    if p.marshal.is_some() {
+        mask |= 0x2000;
+    }
+    mask
+}
+
+pub(super) fn type_to_parent(
+    t: &impl convert::TypeKind,
+    ctx: &mut convert::write::Context,
+) -> Result<index::MemberRefParent> {
+    Ok(match convert::write::index(t, ctx)? {
+        index::TypeDefOrRef::TypeDef(d) => index::MemberRefParent::TypeDef(d),
+        index::TypeDefOrRef::TypeRef(r) => index::MemberRefParent::TypeRef(r),
+        index::TypeDefOrRef::TypeSpec(s) => index::MemberRefParent::TypeSpec(s),
+        _ => unreachable!(),
+    })
+}
+
+fn heap_sizes(strings: &[u8], guids: &[u8], blobs: &[u8]) -> u8 {
+    let mut mask = 0;
+
+    if strings.len() >= (1_usize << 16) {
+        mask |= 0x01;
+    }
+    if guids.len() >= (1_usize << 16) {
+        mask |= 0x02;
+    }
+    if blobs.len() >= (1_usize << 16) {
+        mask |= 0x04;
+    }
+
+    mask
+}
+
+pub(super) fn metadata_root(version: &str, streams: &[(&[u8], &str)]) -> Result<Vec<u8>> {
+    // the #JTD stream of EnC deltas has no contents, since only its presence matters
+    let streams: Vec<_> = streams.iter().filter(|(s, n)| !s.is_empty() || *n == "#JTD").collect();
+
+    // ECMA-335, II.24.2.1 (page 271)
+    let root_and_header_size: usize = 20_usize
+        + crate::utils::round_up_to_4(version.len() + 1_usize).0
+        + streams
+            .iter()
+            .map(|(_, n)| {
+                // add offset and size fields
+                8 + crate::utils::round_up_to_4(n.len() + 1).0
+            })
+            .sum::<usize>();
+
+    let mut metadata_buf = vec![0_u8; root_and_header_size];
+    metadata_buf.pwrite(
+        Metadata {
+            signature: 0x424A_5342, // magic value, same page of ECMA as above
+            major_version: 1,
+            minor_version: 1,
+            reserved: 0,
+            version,
+            flags: 0,
+            stream_headers: streams
+                .iter()
+                .scan(root_and_header_size, |offset, (s, name)| {
+                    let prev = *offset;
+                    // stream sizes must be multiples of 4 (II.24.2.2, page 272)
+                    let size = crate::utils::round_up_to_4(s.len()).0;
+                    *offset += size;
+
+                    Some(stream::Header {
+                        offset: prev as u32,
+                        size: size as u32,
+                        name,
+                    })
+                })
+                .collect(),
+        },
+        0,
+    )?;
+    for (s, _) in streams {
+        metadata_buf.extend(*s);
+        metadata_buf.extend(vec![0; crate::utils::round_up_to_4(s.len()).1]);
+    }
+
+    Ok(metadata_buf)
+}
+
+// every method body with debug information, along with its MethodDef row, the offsets of its instructions (followed by
+// the size of the body) and its locals signature row
+type DebugRows<'r> = Vec<(usize, &'r debug::MethodDebugInformation, Vec<usize>, usize)>;
+
+#[allow(clippy::too_many_lines)]
+fn write_pdb(
+    res: &Resolution,
+    id: [u8; 20],
+    entry_point: u32,
+    type_system_rows: &HashMap<Kind, u32>,
+    referenced_type_system_tables: u64,
+    debug_rows: &DebugRows,
+    import_types: &[index::TypeDefOrRef],
+) -> Result<Vec<u8>> {
+    let mut strings = StringsWriter::new();
+    let mut blobs = BlobWriter::new();
+    let mut guids = GUIDWriter::new();
+
+    let mut tables = Tables::new();
+
+    debug!("documents");
+
+    tables.document.reserve(res.documents.len());
+    for d in &res.documents {
+        tables.document.push(Document {
+            name: pdb::write_document_name(&d.name, &mut blobs)?,
+            hash_algorithm: opt_heap!(guids, d.hash_algorithm),
+            hash: heap_idx!(blobs, d.hash),
+            language: heap_idx!(guids, &d.language),
+        });
+    }
+
+    debug!("method debug information");
+
+    // the MethodDebugInformation table runs parallel to the MethodDef table
+    tables.method_debug_information = vec![
+        MethodDebugInformation {
+            document: 0.into(),
+            sequence_points: 0.into(),
+        };
+        type_system_rows.get(&Kind::MethodDef).copied().unwrap_or(0) as usize
+    ];
+    for (def_idx, info, offsets, local_signature) in debug_rows {
+        let Some(first) = info.sequence_points.first() else {
+            continue;
+        };
+
+        let single_document = info.sequence_points.iter().all(|p| p.document == first.document);
+
+        let mut records = Vec::with_capacity(info.sequence_points.len());
+        let mut document = first.document;
+        for p in &info.sequence_points {
+            if p.document != document {
+                records.push(pdb::SequencePointRecord::Document(p.document.0 + 1));
+                document = p.document;
+            }
+
+            let Some(&il_offset) = offsets[..offsets.len() - 1].get(p.instruction) else {
+                throw!(
+                    "sequence point refers to instruction {}, but method {} only has {} instructions",
+                    p.instruction,
+                    def_idx + 1,
+                    offsets.len() - 1
+                )
+            };
+            let il_offset = il_offset as u32;
+
+            records.push(match p.span {
+                Some(s) => pdb::SequencePointRecord::Visible {
+                    il_offset,
+                    start_line: s.start_line,
+                    start_column: s.start_column,
+                    end_line: s.end_line,
+                    end_column: s.end_column,
+                },
+                None => pdb::SequencePointRecord::Hidden { il_offset },
+            });
+        }
+
+        let mut buf = DynamicBuffer::with_increment(16);
+        buf.pwrite(
+            pdb::SequencePoints {
+                local_signature: *local_signature,
+                initial_document: if single_document {
+                    None
+                } else {
+                    Some(first.document.0 + 1)
+                },
+                records,
+            },
+            0,
+        )?;
+
+        tables.method_debug_information[*def_idx] = MethodDebugInformation {
+            document: if single_document {
+                (first.document.0 + 1).into()
+            } else {
+                0.into()
+            },
+            sequence_points: heap_idx!(blobs, buf.get()),
+        };
+    }
+
+    debug!("import scopes");
+
+    let mut import_types = import_types.iter().copied();
+    tables.import_scope.reserve(res.import_scopes.len());
+    for (idx, scope) in res.import_scopes.iter().enumerate() {
+        use pdb::ImportDefinition::*;
+
+        let parent = match scope.parent {
+            Some(p) if p.0 >= res.import_scopes.len() => throw!(
+                "import scope {} has parent {}, but there are only {} import scopes",
+                idx,
+                p.0,
+                res.import_scopes.len()
+            ),
+            Some(p) => p.0 + 1,
+            None => 0,
+        };
+
+        let mut name = |s: &str| blobs.write(s.as_bytes());
+        let mut definitions = Vec::with_capacity(scope.imports.len());
+        for import in &scope.imports {
+            definitions.push(match import {
+                debug::Import::Namespace {
+                    alias,
+                    assembly,
+                    namespace,
+                } => {
+                    let namespace = name(namespace)?;
+                    match (alias, assembly) {
+                        (None, None) => Namespace { namespace },
+                        (None, Some(a)) => AssemblyNamespace {
+                            assembly: a.0 + 1,
+                            namespace,
+                        },
+                        (Some(alias), None) => AliasNamespace {
+                            alias: name(alias)?,
+                            namespace,
+                        },
+                        (Some(alias), Some(a)) => AliasAssemblyNamespace {
+                            alias: name(alias)?,
+                            assembly: a.0 + 1,
+                            namespace,
+                        },
+                    }
+                }
+                debug::Import::Type { alias, .. } => {
+                    let target = import_types.next().unwrap().into();
+                    match alias {
+                        None => Type(target),
+                        Some(alias) => AliasType {
+                            alias: name(alias)?,
+                            target,
+                        },
+                    }
+                }
+                debug::Import::XmlNamespace { alias, namespace } => XmlNamespace {
+                    alias: name(alias)?,
+                    namespace: name(namespace)?,
+                },
+                debug::Import::AssemblyAlias { alias, assembly } => AliasAssemblyReference {
+                    alias: name(alias)?,
+                    assembly: assembly.0 + 1,
+                },
+                debug::Import::AssemblyAliasReference(alias) => AssemblyReferenceAlias { alias: name(alias)? },
+            });
+        }
+
+        let imports = if definitions.is_empty() {
+            0.into()
+        } else {
+            let mut buf = DynamicBuffer::with_increment(16);
+            buf.pwrite(pdb::Imports(definitions), 0)?;
+            heap_idx!(blobs, buf.get())
+        };
+
+        tables.import_scope.push(ImportScope {
+            parent: parent.into(),
+            imports,
+        });
+    }
+
+    // every local scope needs an import scope, so scopes without one share an empty root scope like compilers emit
+    let mut empty_import_scope = None;
+
+    debug!("local scopes");
+
+    for (def_idx, info, offsets, _) in debug_rows {
+        // the table is sorted by start offset, with enclosing scopes before the scopes they contain
+        let mut scopes: Vec<_> = info.scopes.iter().collect();
+        scopes.sort_by_key(|s| (s.start, std::cmp::Reverse(s.length)));
+
+        for s in scopes {
+            let (Some(&start), Some(&end)) = (offsets.get(s.start), offsets.get(s.start + s.length)) else {
+                throw!(
+                    "local scope covers instructions {} to {}, but method {} only has {} instructions",
+                    s.start,
+                    s.start + s.length,
+                    def_idx + 1,
+                    offsets.len() - 1
+                )
+            };
+
+            let import_scope = match s.import_scope {
+                Some(i) if i.0 >= res.import_scopes.len() => throw!(
+                    "local scope of method {} refers to import scope {}, but there are only {} import scopes",
+                    def_idx + 1,
+                    i.0,
+                    res.import_scopes.len()
+                ),
+                Some(i) => i.0 + 1,
+                None => *empty_import_scope.get_or_insert_with(|| {
+                    tables.import_scope.push(ImportScope {
+                        parent: 0.into(),
+                        imports: 0.into(),
+                    });
+                    tables.import_scope.len()
+                }),
+            };
+
+            tables.local_scope.push(LocalScope {
+                method: (def_idx + 1).into(),
+                import_scope: import_scope.into(),
+                variable_list: (tables.local_variable.len() + 1).into(),
+                constant_list: (tables.local_constant.len() + 1).into(),
+                start_offset: start as u32,
+                length: (end - start) as u32,
+            });
+
+            for v in &s.variables {
+                tables.local_variable.push(LocalVariable {
+                    attributes: u16::from(v.debugger_hidden),
+                    index: v.index as u16,
+                    name: heap_idx!(strings, v.name),
+                });
+            }
+        }
+    }
+
+    debug!("write to PDB");
+
+    let mut row_counts: Vec<_> = type_system_rows.iter().collect();
+    row_counts.sort_by_key(|&(&k, _)| k as u8);
+
+    let mut pdb_stream = DynamicBuffer::with_increment(32);
+    pdb_stream.pwrite(
+        pdb::Stream {
+            id,
+            entry_point,
+            referenced_type_system_tables,
+            type_system_table_rows: row_counts.into_iter().map(|(_, &r)| r).collect(),
+        },
+        0,
+    )?;
+
+    let strings_vec = strings.into_vec();
+    let guids_vec = guids.into_vec();
+    let blobs_vec = blobs.into_vec();
+
+    let header = header::Header {
+        reserved0: 0,
+        major_version: 2,
+        minor_version: 0,
+        heap_sizes: heap_sizes(&strings_vec, &guids_vec, &blobs_vec),
+        reserved1: 1,
+        valid: tables.valid_mask(),
+        sorted: Tables::sorted_mask(),
+        tables,
+    };
+
+    let mut header_buf = DynamicBuffer::with_increment(32);
+    header_buf.pwrite_with(
+        header,
+        0,
+        header::Layout::from(header::ExternalRows(Some(type_system_rows))),
+    )?;
+
+    metadata_root(
+        "PDB v1.0",
+        &[
+            (pdb_stream.get(), pdb::Stream::NAME),
+            (header_buf.get(), "#~"),
+            (&strings_vec, StringsReader::NAME),
+            (&guids_vec, GUIDReader::NAME),
+            (&blobs_vec, BlobReader::NAME),
+        ],
+    )
+}
+
+// encodes a method body, returning it along with the offsets of its instructions followed by the size of the body,
+// since the PDB's scope table needs the offset just past the last instruction too
+#[allow(clippy::too_many_lines)]
+pub(super) fn method_body(
+    body: &body::Method,
+    max_stack: usize,
+    ctx: &mut convert::write::Context,
+    m_ctx: &mut convert::write::MethodContext<
+        '_,
+        impl Fn(UserMethod) -> index::MethodDefOrRef,
+        impl Fn(FieldSource) -> index::Token,
+    >,
+) -> Result<(method::Method, Vec<usize>)> {
+    let mut instructions: Vec<_> = body
+        .instructions
+        .iter()
+        .map(|i| convert::write::instruction(i, ctx, m_ctx))
+        .collect::<Result<_>>()?;
+
+    use crate::binary::il::Instruction;
+    use paste::paste;
+
+    macro_rules! branches {
+        ($($ins:ident),+) => {
+            // the target of a branch with a short form, which is still an instruction index at this point
+            fn branch_target(i: &Instruction) -> Option<usize> {
+                match i {
+                    $(Instruction::$ins(o))|+ => Some(*o as usize),
+                    _ => None,
+                }
+            }
+
+            // sets the final offset of a branch, relative to the end of the instruction
+            fn encode_branch(i: &mut Instruction, offset: i32, short: bool) {
+                match i {
+                    $(
+                        Instruction::$ins(o) => {
+                            if short {
+                                *i = paste! { Instruction::[<$ins S>](offset as i8) };
+                            } else {
+                                *o = offset;
+                            }
+                        }
+                    )+
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    branches!(Beq, Bge, BgeUn, Bgt, BgtUn, Ble, BleUn, Blt, BltUn, BneUn, Br, Brfalse, Brtrue, Leave);
+
+    let len = instructions.len();
+    let targets: Vec<_> = instructions.iter().map(branch_target).collect();
+    let switch_targets = instructions.iter().flat_map(|i| match i {
+        Instruction::Switch(os) => os.as_slice(),
+        _ => &[],
+    });
+    for t in targets.iter().flatten().copied().chain(switch_targets.map(|&o| o as usize)) {
+        if t >= len {
+            throw!(
+                "branch target {} is out of bounds for a method with {} instructions",
+                t,
+                len
+            );
+        }
+    }
+
+    // the offset of every instruction, followed by the size of the body
+    let compute_offsets = |short: &[bool]| -> Vec<usize> {
+        std::iter::once(0)
+            .chain(instructions.iter().zip(short).scan(0, |state, (i, &s)| {
+                *state += i.bytesize() - if s { 3 } else { 0 };
+                Some(*state)
+            }))
+            .collect()
+    };
+
+    // branches start out long, and are shortened as long as their offsets fit in a byte
+    // shortening a branch only ever brings others closer to their targets, so this repeats until nothing changes
+    let mut short = vec![false; len];
+    let offsets = loop {
+        let offsets = compute_offsets(&short);
+
+        let mut changed = false;
+        for (idx, t) in targets.iter().enumerate() {
+            let Some(t) = *t else {
+                continue;
+            };
+            if short[idx] {
+                continue;
+            }
+
+            // a short branch is 3 bytes smaller, which moves its own end, and its target too if it comes after it
+            let end = offsets[idx + 1] - 3;
+            let target = if t > idx { offsets[t] - 3 } else { offsets[t] };
+            if i8::try_from(target as isize - end as isize).is_ok() {
+                short[idx] = true;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break offsets;
+        }
+    };
+
+    for (idx, i) in instructions.iter_mut().enumerate() {
+        let end = offsets[idx + 1] as i32;
+        if let Some(t) = targets[idx] {
+            encode_branch(i, offsets[t] as i32 - end, short[idx]);
+        } else if let Instruction::Switch(os) = i {
+            for o in os {
+                *o = offsets[*o as usize] as i32 - end;
+            }
+        }
+    }
+
+    let body_size = offsets[len];
+
+    let mut data_sections: Vec<_> = body
+        .data_sections
+        .iter()
+        .map(|d| {
+            let section = match d {
+                body::DataSection::Unrecognized { fat, size } => method::SectionKind::Unrecognized {
+                    is_fat: *fat,
+                    length: *size,
+                },
+                body::DataSection::ExceptionHandlers(es) => {
+                    let exs = es
+                        .iter()
+                        .map(|e| {
+                            use body::ExceptionKind::*;
+
+                            let class_token_or_filter = match &e.kind {
+                                TypedException(t) => {
+                                    let mut buf = [0; 4];
+                                    buf.pwrite(index::Token::from(convert::write::index(t, ctx)?), 0)?;
+                                    u32::from_le_bytes(buf)
+                                }
+                                Filter { offset } => offsets[*offset] as u32,
+                                _ => 0,
+                            };
+
+                            let convert_pair = |off: usize, len: usize| {
+                                (offsets[off] as u32, (offsets[off + len] - offsets[off]) as u32)
+                            };
+
+                            let (try_offset, try_length) = convert_pair(e.try_offset, e.try_length);
+                            let (handler_offset, handler_length) = convert_pair(e.handler_offset, e.handler_length);
+
+                            Ok(method::Exception {
+                                flags: match &e.kind {
+                                    TypedException(_) => 0x0,
+                                    Filter { .. } => 0x1,
+                                    Finally => 0x2,
+                                    Fault => 0x4,
+                                },
+                                try_offset,
+                                try_length,
+                                handler_offset,
+                                handler_length,
+                                class_token_or_filter,
+                            })
+                        })
+                        .collect::<Result<_>>()?;
+                    method::SectionKind::Exceptions(exs)
+                }
+            };
+            Ok(method::DataSection {
+                section,
+                more_sections: true,
+            })
+        })
+        .collect::<Result<_>>()?;
+    if let Some(last) = data_sections.last_mut() {
+        last.more_sections = false;
+    }
+
+    let m = method::Method {
+        header: if body_size < 64
+            && max_stack <= 8
+            && body.header.local_variables.is_empty()
+            && !body.header.initialize_locals
+            && body.data_sections.is_empty()
+        {
+            method::Header::Tiny { size: body_size }
+        } else {
+            let local_var_sig_tok = if body.header.local_variables.is_empty() {
+                0
+            } else {
+                m_ctx.stand_alone_sigs.push(StandAloneSig {
+                    signature: convert::write::local_vars(&body.header.local_variables, ctx)?,
+                });
+
+                let mut buf = [0_u8; 4];
+                buf.pwrite(
+                    index::Token {
+                        target: index::TokenTarget::Table(Kind::StandAloneSig),
+                        index: m_ctx.stand_alone_sigs.len(),
+                    },
+                    0,
+                )?;
+                u32::from_le_bytes(buf)
+            };
+
+            method::Header::Fat {
+                more_sects: !body.data_sections.is_empty(),
+                init_locals: body.header.initialize_locals,
+                max_stack: max_stack as u16,
+                size: body_size,
+                local_var_sig_tok,
+            }
+        },
+        body: instructions,
+        data_sections,
+    };
+
+    Ok((m, offsets))
+}
+
+// with the rayon feature, each body is first encoded against heaps and tables of its own on the thread pool,
+// which are then merged into the real ones in body order, so that every row and heap entry lands where it would have
+// if the bodies had been encoded one after another
+#[cfg(feature = "rayon")]
+struct DetachedBody {
+    method: method::Method,
+    offsets: Vec<usize>,
+    blobs: Vec<u8>,
+    userstrings: Vec<u8>,
+    // specs created through the type cache also carry the hash that they were cached under
+    specs: Vec<(TypeSpec, Option<u64>)>,
+    stand_alone_sigs: Vec<StandAloneSig>,
+    method_specs: Vec<MethodSpec>,
+}
+
+#[cfg(feature = "rayon")]
+impl DetachedBody {
+    fn encode(
+        body: &body::Method,
+        max_stack: usize,
+        user_method: &impl Fn(UserMethod) -> index::MethodDefOrRef,
+        field_source: &impl Fn(FieldSource) -> index::Token,
+    ) -> Result<Self> {
+        let mut blobs = BlobWriter::new();
+        let mut specs = vec![];
+        let mut type_cache = HashMap::new();
+        let mut stand_alone_sigs = vec![];
+        let mut method_specs = vec![];
+        let mut userstrings = UserStringWriter::new();
+
+        let (method, offsets) = method_body(
+            body,
+            max_stack,
+            &mut convert::write::Context {
+                blobs: &mut blobs,
+                specs: &mut specs,
+                type_cache: &mut type_cache,
+                blob_scratch: &mut DynamicBuffer::with_increment(8),
+            },
+            &mut convert::write::MethodContext {
+                stand_alone_sigs: &mut stand_alone_sigs,
+                method_specs: &mut method_specs,
+                userstrings: &mut userstrings,
+                user_method,
+                field_source,
+            },
+        )?;
+
+        let mut keys: HashMap<_, _> = type_cache
+            .into_iter()
+            .filter_map(|(hash, idx)| {
+                if let index::TypeDefOrRef::TypeSpec(row) = idx {
+                    Some((row, hash))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            method,
+            offsets,
+            blobs: blobs.into_vec(),
+            userstrings: userstrings.into_vec(),
+            specs: specs
+                .into_iter()
+                .enumerate()
+                .map(|(idx, s)| (s, keys.remove(&(idx + 1))))
+                .collect(),
+            stand_alone_sigs,
+            method_specs,
+        })
+    }
+
+    fn merge(
+        mut self,
+        ctx: &mut convert::write::Context,
+        stand_alone_sigs: &mut Vec<StandAloneSig>,
+        method_specs: &mut Vec<MethodSpec>,
+        userstrings: &mut UserStringWriter,
+    ) -> Result<(method::Method, Vec<usize>)> {
+        // entries are written in the order they were first written here,
+        // which is also the order that the entries not already in the real heaps would have been added in
+        let mut blob_map = HashMap::from([(0, index::Blob(0))]);
+        for (offset, blob) in entries(&self.blobs)? {
+            blob_map.insert(offset, ctx.blobs.write(blob)?);
+        }
+        let mut string_map = HashMap::new();
+        for (offset, entry) in entries(&self.userstrings)? {
+            string_map.insert(offset, userstrings.write(&user_string_chars(entry))?);
+        }
+
+        // specs for types that were cached by earlier bodies reuse that row instead, like they would have
+        let mut spec_tokens = vec![];
+        for (spec, key) in self.specs {
+            let idx = if let Some(&idx) = key.and_then(|k| ctx.type_cache.get(&k)) {
+                idx
+            } else {
+                ctx.specs.push(TypeSpec {
+                    signature: blob_map[&spec.signature.0],
+                });
+                let idx = index::TypeDefOrRef::TypeSpec(ctx.specs.len());
+                if let Some(k) = key {
+                    ctx.type_cache.insert(k, idx);
+                }
+                idx
+            };
+            spec_tokens.push(index::Token::from(idx));
+        }
+
+        let sig_offset = stand_alone_sigs.len();
+        stand_alone_sigs.extend(self.stand_alone_sigs.into_iter().map(|s| StandAloneSig {
+            signature: blob_map[&s.signature.0],
+        }));
+        let method_spec_offset = method_specs.len();
+        method_specs.extend(self.method_specs.into_iter().map(|s| MethodSpec {
+            method: s.method,
+            instantiation: blob_map[&s.instantiation.0],
+        }));
+
+        let remap = |t: &mut index::Token| match t.target {
+            index::TokenTarget::UserString => t.index = string_map[&t.index],
+            index::TokenTarget::Table(Kind::TypeSpec) => *t = spec_tokens[t.index - 1],
+            index::TokenTarget::Table(Kind::StandAloneSig) => t.index += sig_offset,
+            index::TokenTarget::Table(Kind::MethodSpec) => t.index += method_spec_offset,
+            _ => {}
+        };
+        let remap_raw = |raw: &mut u32| -> Result<()> {
+            let mut token = scroll::Pread::pread(&raw.to_le_bytes()[..], 0)?;
+            remap(&mut token);
+            let mut buf = [0_u8; 4];
+            buf.pwrite(token, 0)?;
+            *raw = u32::from_le_bytes(buf);
+            Ok(())
+        };
 
-fn type_to_parent(t: &impl convert::TypeKind, ctx: &mut convert::write::Context) -> Result<index::MemberRefParent> {
-    Ok(match convert::write::index(t, ctx)? {
-        index::TypeDefOrRef::TypeDef(d) => index::MemberRefParent::TypeDef(d),
-        index::TypeDefOrRef::TypeRef(r) => index::MemberRefParent::TypeRef(r),
-        index::TypeDefOrRef::TypeSpec(s) => index::MemberRefParent::TypeSpec(s),
-        _ => unreachable!(),
-    })
+        for i in &mut self.method.body {
+            i.tokens_mut().into_iter().for_each(remap);
+        }
+        if let method::Header::Fat {
+            local_var_sig_tok, ..
+        } = &mut self.method.header
+        {
+            if *local_var_sig_tok != 0 {
+                remap_raw(local_var_sig_tok)?;
+            }
+        }
+        for d in &mut self.method.data_sections {
+            if let method::SectionKind::Exceptions(es) = &mut d.section {
+                // only typed exception clauses (flags of 0) have a token here, filters have an offset
+                for e in es.iter_mut().filter(|e| e.flags == 0) {
+                    remap_raw(&mut e.class_token_or_filter)?;
+                }
+            }
+        }
+
+        Ok((self.method, self.offsets))
+    }
 }
 
 #[allow(clippy::too_many_lines)]
-pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
+pub(crate) fn write_impl(
+    res: &Resolution,
+    opts: Options,
+    emit_pdb: bool,
+) -> Result<(Vec<u8>, Option<Vec<u8>>, TokenRemapping)> {
     // writer setup
+    let platform = opts.platform();
     let mut buffer = vec![];
-    let mut writer = PEWriter::new(!opts.is_32_bit, 0x200, 0x200, &mut buffer);
+    let mut writer = PEWriter::new(platform.is_64_bit(), 0x200, 0x200, &mut buffer);
+
+    if opts.metadata_version.len() >= 256 {
+        throw!("metadata version string is too long");
+    }
 
     let mut num_sections = 1; // .text
     if opts.is_executable {
         // add .idata and .reloc
         num_sections += 2;
     }
+    let has_win32_resources = !res.win32_resources.entries.is_empty();
+    if has_win32_resources {
+        num_sections += 1;
+    }
 
     // begin reservations
 
@@ -269,12 +1301,12 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
 
         let import_lookup_rva = current_rva!();
         let mut lookup_table: Vec<u8> = vec![];
-        if opts.is_32_bit {
-            lookup_table.extend(hint_name_rva.to_le_bytes());
-            lookup_table.extend([0; 4]);
-        } else {
+        if platform.is_64_bit() {
             lookup_table.extend((hint_name_rva as u64).to_le_bytes());
             lookup_table.extend([0; 8]);
+        } else {
+            lookup_table.extend(hint_name_rva.to_le_bytes());
+            lookup_table.extend([0; 4]);
         }
         // write lookup table
         idata.extend_from_slice(&lookup_table);
@@ -330,7 +1362,12 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     let mut strings = StringsWriter::new();
     let mut blobs = BlobWriter::new();
     let mut guids = GUIDWriter::new();
-    let mut userstrings = UserStringWriter::new();
+    let original_tokens = res.original_tokens.as_ref();
+    let preserved = original_tokens.filter(|_| opts.preserve_tokens);
+    let mut userstrings = match preserved {
+        Some(o) => UserStringWriter::from_heap(o.user_strings)?,
+        None => UserStringWriter::new(),
+    };
 
     let mut tables = Tables::new();
 
@@ -353,12 +1390,12 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     // convenience macros for temporary mutable borrows
     macro_rules! write_attrs {
         ($a:expr, $parent:ident($idx:expr)) => {
-            _write_attrs(&mut attributes, &$a, index::HasCustomAttribute::$parent($idx))
+            write_attrs(&mut attributes, &$a, index::HasCustomAttribute::$parent($idx))
         };
     }
     macro_rules! write_security {
         ($s:expr, $parent:ident($idx:expr)) => {
-            _write_security(
+            write_security(
                 &$s,
                 index::HasDeclSecurity::$parent($idx),
                 &mut blobs,
@@ -369,7 +1406,7 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     }
     macro_rules! build_generic {
         ($gs:expr, $parent:ident($idx:expr)) => {
-            _write_generic(
+            write_generic(
                 &$gs,
                 index::TypeOrMethodDef::$parent($idx),
                 &mut strings,
@@ -382,7 +1419,7 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     }
     macro_rules! write_pinvoke {
         ($p:expr, $parent:ident($idx:expr)) => {
-            _write_pinvoke(
+            write_pinvoke(
                 &$p,
                 index::MemberForwarded::$parent($idx),
                 &mut strings,
@@ -392,8 +1429,8 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     }
     macro_rules! write_marshal {
         ($spec:expr, $parent:ident($idx:expr)) => {
-            _write_marshal(
-                $spec,
+            write_marshal(
+                $spec.as_ref(),
                 index::HasFieldMarshal::$parent($idx),
                 &mut tables.field_marshal,
                 build_ctx!(),
@@ -402,12 +1439,18 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     }
     macro_rules! write_default {
         ($d:expr, $parent:ident($idx:expr)) => {
-            _write_default(&$d, index::HasConstant::$parent($idx), &mut blobs, &mut tables)?
+            write_default(&$d, index::HasConstant::$parent($idx), &mut blobs, &mut tables)?
         };
     }
 
     debug!("assembly");
 
+    let strong_name_key = opts.strong_name_key;
+    let signing_key = strong_name_key.as_ref();
+    if signing_key.is_some() && res.assembly.is_none() {
+        throw!("only assemblies can be strong-name signed");
+    }
+
     if let Some(a) = &res.assembly {
         tables.assembly.push(Assembly {
             hash_alg_id: match a.hash_algorithm {
@@ -419,8 +1462,17 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
             minor_version: a.version.minor,
             build_number: a.version.build,
             revision_number: a.version.revision,
-            flags: a.flags.to_mask(),
-            public_key: opt_heap!(blobs, a.public_key),
+            flags: a.flags.to_mask() | u32::from(signing_key.is_some()),
+            public_key: match signing_key {
+                Some(k) => {
+                    let key = k.public_key();
+                    if a.public_key.as_ref().is_some_and(|p| **p != *key) {
+                        throw!("the assembly's public key does not match the strong name key");
+                    }
+                    heap_idx!(blobs, key)
+                }
+                None => opt_heap!(blobs, a.public_key),
+            },
             name: heap_idx!(strings, a.name),
             culture: opt_heap!(strings, a.culture),
         });
@@ -526,7 +1578,8 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     tables.module.push(Module {
         generation: 0,
         name: heap_idx!(strings, res.module.name),
-        mvid: heap_idx!(guids, &res.module.mvid),
+        // patched once the content hash is known
+        mvid: heap_idx!(guids, if opts.deterministic { [0; 16] } else { res.module.mvid }),
         enc_id: 0.into(),
         enc_base_id: 0.into(),
     });
@@ -557,28 +1610,18 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
         let simple_idx = (idx + 1).into();
 
         tables.type_def.push(TypeDef {
-            flags: {
-                let mut f = t.flags.to_mask();
-                if t.security.is_some() {
-                    f |= 0x0004_0000;
-                }
-                f
-            },
+            flags: type_flags(t),
             type_name: heap_idx!(strings, t.name),
             type_namespace: opt_heap!(strings, t.namespace),
+            // base types and interfaces are always classes, which matters for generic instantiations
             extends: match &t.extends {
-                Some(t) => convert::write::source_index(None, t, build_ctx!())?,
+                Some(t) => convert::write::source_index(Some(ValueKind::Class), t, build_ctx!())?,
                 None => index::TypeDefOrRef::Null,
             },
             // for some reason, things break if I use 0 for null index instead of 1
             // doesn't make any sense, but ildasm fully crashes otherwise
             field_list: (tables.field.len() + 1).into(),
-            method_list: if t.methods.is_empty() {
-                1
-            } else {
-                tables.method_def.len() + 1
-            }
-            .into(),
+            method_list: (tables.method_def.len() + 1).into(),
         });
 
         build_generic!(t.generic_parameters, TypeDef(idx + 1));
@@ -589,7 +1632,7 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
             let impl_idx = tables.interface_impl.len() + 1;
             tables.interface_impl.push(InterfaceImpl {
                 class: simple_idx,
-                interface: convert::write::source_index(None, i, build_ctx!())?,
+                interface: convert::write::source_index(Some(ValueKind::Class), i, build_ctx!())?,
             });
             write_attrs!(attrs, InterfaceImpl(impl_idx));
         }
@@ -625,15 +1668,27 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
         if let Some(enc) = t.encloser {
             tables.nested_class.push(NestedClass {
                 nested_class: simple_idx,
-                enclosing_class: enc.0.into(),
+                enclosing_class: (enc.0 + 1).into(),
             });
         }
 
         debug!("fields for type {}", t.name);
 
+        let mut fields: Vec<_> = t.fields.iter().enumerate().collect();
+        if let Some(o) = preserved {
+            sort_by_original_row(&mut fields, |(internal_idx, _)| {
+                o.fields
+                    .get(&FieldIndex {
+                        parent_type: TypeIndex(idx),
+                        field: *internal_idx,
+                    })
+                    .copied()
+            });
+        }
+
         field_index_map.reserve(t.fields.len());
         tables.field.reserve(t.fields.len());
-        for (internal_idx, f) in t.fields.iter().enumerate() {
+        for (internal_idx, f) in fields {
             let table_idx = tables.field.len() + 1;
             field_index_map.insert(
                 FieldIndex {
@@ -644,29 +1699,7 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
             );
 
             tables.field.push(Field {
-                flags: {
-                    let mut mask = build_bitmask!(f,
-                        static_member => 0x0010,
-                        init_only => 0x0020,
-                        literal => 0x0040,
-                        not_serialized => 0x0080,
-                        special_name => 0x0200,
-                        runtime_special_name => 0x0400);
-                    mask |= f.accessibility.to_mask();
-                    if f.pinvoke.is_some() {
-                        mask |= 0x2000;
-                    }
-                    if f.marshal.is_some() {
-                        mask |= 0x1000;
-                    }
-                    if f.default.is_some() {
-                        mask |= 0x8000;
-                    }
-                    if f.initial_value.is_some() {
-                        mask |= 0x0100;
-                    }
-                    mask
-                },
+                flags: field_flags(f),
                 name: heap_idx!(strings, f.name),
                 signature: convert::write::field_def(f, build_ctx!())?,
             });
@@ -805,6 +1838,17 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
 
         debug!("methods for type {}", t.name);
 
+        if let Some(o) = preserved {
+            sort_by_original_row(&mut all_methods, |(member, ..)| {
+                o.methods
+                    .get(&MethodIndex {
+                        parent_type: TypeIndex(idx),
+                        member: *member,
+                    })
+                    .copied()
+            });
+        }
+
         method_index_map.reserve(all_methods.len());
         tables.method_def.reserve(all_methods.len());
         for (member_idx, assoc, m) in all_methods {
@@ -840,62 +1884,10 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
             }
             tables.method_def.push(MethodDef {
                 rva: 0,
-                impl_flags: {
-                    let mut mask = build_bitmask!(m,
-                        forward_ref => 0x0010,
-                        preserve_sig => 0x0080,
-                        internal_call => 0x1000,
-                        synchronized => 0x0020,
-                        no_inlining => 0x0008,
-                        no_optimization => 0x0040);
-                    mask |= match m.body_format {
-                        BodyFormat::IL => 0x0,
-                        BodyFormat::Native => 0x1,
-                        BodyFormat::Runtime => 0x3,
-                    };
-                    mask |= match m.body_management {
-                        BodyManagement::Unmanaged => 0x4,
-                        BodyManagement::Managed => 0x0,
-                    };
-                    mask
-                },
-                flags: {
-                    let mut mask = build_bitmask!(m,
-                        sealed => 0x0020,
-                        virtual_member => 0x0040,
-                        hide_by_sig => 0x0080,
-                        strict => 0x0200,
-                        abstract_member => 0x0400,
-                        special_name => 0x0800,
-                        runtime_special_name => 0x1000,
-                        require_sec_object => 0x8000);
-                    if m.is_static() {
-                        mask |= 0x0010;
-                    }
-                    mask |= m.accessibility.to_mask();
-                    mask |= match m.vtable_layout {
-                        VtableLayout::ReuseSlot => 0x0000,
-                        VtableLayout::NewSlot => 0x0100,
-                    };
-                    if m.pinvoke.is_some() {
-                        mask |= 0x2000;
-                    }
-                    if m.security.is_some() {
-                        mask |= 0x4000;
-                    }
-                    mask
-                },
+                impl_flags: method_impl_flags(m),
+                flags: method_flags(m),
                 name: heap_idx!(strings, &m.name),
-                signature: {
-                    let ctx = build_ctx!();
-                    if m.generic_parameters.is_empty() {
-                        convert::write::method_def(&m.signature, ctx)?
-                    } else {
-                        let mut sig = m.signature.clone();
-                        sig.calling_convention = CallingConvention::Generic(m.generic_parameters.len());
-                        convert::write::method_def(&sig, ctx)?
-                    }
-                },
+                signature: method_signature(m, build_ctx!())?,
                 param_list: (tables.param.len() + 1).into(),
             });
 
@@ -914,19 +1906,7 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
                     let param_idx = tables.param.len() + 1;
 
                     tables.param.push(Param {
-                        flags: {
-                            let mut mask = build_bitmask!(p,
-                                is_in => 0x0001,
-                                is_out => 0x0002,
-                                optional => 0x0010);
-                            if p.default.is_some() {
-                                mask |= 0x1000;
-                            }
-                            if p.marshal.is_some() {
-                                mask |= 0x2000;
-                            }
-                            mask
-                        },
+                        flags: param_flags(p),
                         sequence: idx as u16,
                         name: opt_heap!(strings, p.name),
                     });
@@ -939,15 +1919,32 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
         }
     }
 
-    // NOTE: method ref indexes are the same as member ref indexes
-    // field refs come after method refs in the member ref table, so their indexes are offset by method_ref.len()
+    // method refs and field refs share the member ref table
+    // by default, field refs come after method refs, so their rows are offset by method_references.len()
+    let mut member_refs: Vec<_> = (0..res.method_references.len())
+        .map(|i| MemberRefSource::Method(MethodRefIndex(i)))
+        .chain((0..res.field_references.len()).map(|i| MemberRefSource::Field(FieldRefIndex(i))))
+        .collect();
+    if let Some(o) = preserved {
+        sort_by_original_row(&mut member_refs, |r| match r {
+            MemberRefSource::Method(m) => o.method_references.get(m).copied(),
+            MemberRefSource::Field(f) => o.field_references.get(f).copied(),
+        });
+    }
+    let mut method_ref_rows = vec![0; res.method_references.len()];
+    let mut field_ref_rows = vec![0; res.field_references.len()];
+    for (idx, r) in member_refs.iter().enumerate() {
+        match r {
+            MemberRefSource::Method(m) => method_ref_rows[m.0] = idx + 1,
+            MemberRefSource::Field(f) => field_ref_rows[f.0] = idx + 1,
+        }
+    }
 
     let user_method = |u: UserMethod| match u {
         UserMethod::Definition(m) => index::MethodDefOrRef::MethodDef(method_index_map[&m]),
-        UserMethod::Reference(r) => index::MethodDefOrRef::MemberRef(r.0 + 1),
+        UserMethod::Reference(r) => index::MethodDefOrRef::MemberRef(method_ref_rows[r.0]),
     };
 
-    let field_offset = res.method_references.len();
     let field_source = |f: FieldSource| match f {
         FieldSource::Definition(d) => index::Token {
             target: index::TokenTarget::Table(Kind::Field),
@@ -955,190 +1952,50 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
         },
         FieldSource::Reference(r) => index::Token {
             target: index::TokenTarget::Table(Kind::MemberRef),
-            index: r.0 + 1 + field_offset,
+            index: field_ref_rows[r.0],
         },
     };
 
     debug!("method bodies");
 
-    for (def_idx, body) in bodies {
-        let ctx = build_ctx!();
-        let m_ctx = &mut convert::write::MethodContext {
-            stand_alone_sigs: &mut tables.stand_alone_sig,
-            method_specs: &mut tables.method_spec,
-            userstrings: &mut userstrings,
-            user_method: &user_method,
-            field_source: &field_source,
-        };
-
-        let mut instructions: Vec<_> = body
-            .instructions
-            .iter()
-            .map(|i| convert::write::instruction(i, ctx, m_ctx))
-            .collect::<Result<_>>()?;
-        let mut offsets: Vec<_> = instructions
-            .iter()
-            .scan(0, |state, i| {
-                let my_offset = *state;
-                *state += i.bytesize();
-                Some(my_offset)
-            })
-            .collect();
-
-        use crate::binary::il::Instruction;
-
-        let mut n_short = 0;
-        let mut deltas = vec![];
-        for i in &instructions {
-            use Instruction::*;
-
-            deltas.push(3 * n_short);
-            if matches!(i, Beq(o) | Bge(o) | BgeUn(o) | Bgt(o) | BgtUn(o) | Ble(o) | BleUn(o) | Blt(o) | BltUn(o)
-                    | BneUn(o) | Br(o) | Brfalse(o) | Brtrue(o) | Leave(o) if i8::try_from(*o).is_ok())
-            {
-                n_short += 1;
-            }
-        }
-        for (offset, delta) in offsets.iter_mut().zip(deltas.into_iter()) {
-            *offset -= delta;
-        }
-
-        for (idx, i) in instructions.iter_mut().enumerate() {
-            let bytesize = i.bytesize();
-            let convert_offset = |o: &mut i32, can_shorten: bool| {
-                let base = offsets[idx] + bytesize;
-                let target = offsets[*o as usize];
-                *o = (target as i32) - (base as i32);
-                if can_shorten && i8::try_from(*o).is_ok() {
-                    // this instruction will become 3 bytes shorter, need to adjust offset for change in bytesize
-                    *o += 3;
-                }
-            };
-
-            use paste::paste;
-            macro_rules! build_match {
-                ($($ins:ident),+) => {
-                    match i {
-                        $(
-                            Instruction::$ins(o) => {
-                                convert_offset(o, true);
-                                if let Ok(int) = i8::try_from(*o) {
-                                    *i = paste! { Instruction::[<$ins S>](int) };
-                                }
-                            }
-                        )+
-                        Instruction::Switch(os) => os.iter_mut().for_each(|o| convert_offset(o, false)),
-                        _ => {}
-                    }
-                }
-            }
-
-            build_match!(Beq, Bge, BgeUn, Bgt, BgtUn, Ble, BleUn, Blt, BltUn, BneUn, Br, Brfalse, Brtrue, Leave);
-        }
-
-        let body_size = instructions.iter().map(Instruction::bytesize).sum();
+    let mut debug_rows = vec![];
 
-        let mut data_sections: Vec<_> = body
-            .data_sections
-            .iter()
-            .map(|d| {
-                let section = match d {
-                    body::DataSection::Unrecognized { fat, size } => method::SectionKind::Unrecognized {
-                        is_fat: *fat,
-                        length: *size,
-                    },
-                    body::DataSection::ExceptionHandlers(es) => {
-                        let exs = es
-                            .iter()
-                            .map(|e| {
-                                use body::ExceptionKind::*;
-
-                                let class_token_or_filter = match &e.kind {
-                                    TypedException(t) => {
-                                        let mut buf = [0; 4];
-                                        buf.pwrite(index::Token::from(convert::write::index(t, build_ctx!())?), 0)?;
-                                        u32::from_le_bytes(buf)
-                                    }
-                                    Filter { offset } => offsets[*offset] as u32,
-                                    _ => 0,
-                                };
-
-                                let convert_pair = |off: usize, len: usize| {
-                                    (
-                                        offsets[off] as u32,
-                                        instructions[off..off + len].iter().map(|i| i.bytesize() as u32).sum(),
-                                    )
-                                };
-
-                                let (try_offset, try_length) = convert_pair(e.try_offset, e.try_length);
-                                let (handler_offset, handler_length) = convert_pair(e.handler_offset, e.handler_length);
-
-                                Ok(method::Exception {
-                                    flags: match &e.kind {
-                                        TypedException(_) => 0x0,
-                                        Filter { .. } => 0x1,
-                                        Finally => 0x2,
-                                        Fault => 0x4,
-                                    },
-                                    try_offset,
-                                    try_length,
-                                    handler_offset,
-                                    handler_length,
-                                    class_token_or_filter,
-                                })
-                            })
-                            .collect::<Result<_>>()?;
-                        method::SectionKind::Exceptions(exs)
-                    }
-                };
-                Ok(method::DataSection {
-                    section,
-                    more_sections: true,
-                })
-            })
-            .collect::<Result<_>>()?;
-        if let Some(last) = data_sections.last_mut() {
-            last.more_sections = false;
+    let max_stack = |body: &body::Method| {
+        if opts.compute_max_stack {
+            stack::max_stack(res, body)
+        } else {
+            Ok(body.header.maximum_stack_size)
         }
+    };
 
-        let m = method::Method {
-            header: if body_size < 64
-                && body.header.maximum_stack_size <= 8
-                && body.header.local_variables.is_empty()
-                && !body.header.initialize_locals
-                && body.data_sections.is_empty()
-            {
-                method::Header::Tiny { size: body_size }
-            } else {
-                let local_var_sig_tok = if body.header.local_variables.is_empty() {
-                    0
-                } else {
-                    tables.stand_alone_sig.push(StandAloneSig {
-                        signature: convert::write::local_vars(&body.header.local_variables, build_ctx!())?,
-                    });
-
-                    let mut buf = [0_u8; 4];
-                    buf.pwrite(
-                        index::Token {
-                            target: index::TokenTarget::Table(Kind::StandAloneSig),
-                            index: tables.stand_alone_sig.len(),
-                        },
-                        0,
-                    )?;
-                    u32::from_le_bytes(buf)
-                };
+    #[cfg(feature = "rayon")]
+    let mut detached = bodies
+        .par_iter()
+        .map(|&(_, body)| DetachedBody::encode(body, max_stack(body)?, &user_method, &field_source))
+        .collect::<Vec<_>>()
+        .into_iter();
 
-                method::Header::Fat {
-                    more_sects: !body.data_sections.is_empty(),
-                    init_locals: body.header.initialize_locals,
-                    max_stack: body.header.maximum_stack_size as u16,
-                    size: body_size,
-                    local_var_sig_tok,
-                }
+    for (def_idx, body) in bodies {
+        #[cfg(feature = "rayon")]
+        let (m, offsets) = detached.next().unwrap()?.merge(
+            build_ctx!(),
+            &mut tables.stand_alone_sig,
+            &mut tables.method_spec,
+            &mut userstrings,
+        )?;
+        #[cfg(not(feature = "rayon"))]
+        let (m, offsets) = method_body(
+            body,
+            max_stack(body)?,
+            build_ctx!(),
+            &mut convert::write::MethodContext {
+                stand_alone_sigs: &mut tables.stand_alone_sig,
+                method_specs: &mut tables.method_spec,
+                userstrings: &mut userstrings,
+                user_method: &user_method,
+                field_source: &field_source,
             },
-            body: instructions,
-            data_sections,
-        };
+        )?;
 
         // fat method headers must be aligned
         if matches!(m.header, method::Header::Fat { .. }) {
@@ -1150,12 +2007,35 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
 
         tables.method_def[def_idx].rva = current_rva!();
 
+        if emit_pdb {
+            if let Some(info) = &body.debug {
+                let local_signature = if body.header.local_variables.is_empty() {
+                    0
+                } else {
+                    tables.stand_alone_sig.len()
+                };
+                debug_rows.push((def_idx, info, offsets, local_signature));
+            }
+        }
+
         let mut buf = DynamicBuffer::with_increment(16);
         buf.pwrite(m, 0)?;
 
         text.extend_from_slice(buf.get());
     }
 
+    // type imports can refer to type specs, which have to be in the DLL before its tables are written
+    let mut import_types = vec![];
+    if emit_pdb {
+        for scope in &res.import_scopes {
+            for import in &scope.imports {
+                if let debug::Import::Type { target, .. } = import {
+                    import_types.push(convert::write::index(target, build_ctx!())?);
+                }
+            }
+        }
+    }
+
     tables
         .method_impl
         .extend(overrides.into_iter().map(|(parent, body, decl)| MethodImpl {
@@ -1166,33 +2046,39 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
 
     debug!("member refs");
 
-    tables
-        .member_ref
-        .reserve(res.method_references.len() + res.field_references.len());
-    for (idx, m) in res.method_references.iter().enumerate() {
-        tables.member_ref.push(MemberRef {
-            class: match &m.parent {
-                MethodReferenceParent::Type(t) => type_to_parent(t, build_ctx!())?,
-                MethodReferenceParent::Module(m) => index::MemberRefParent::ModuleRef(m.0 + 1),
-                MethodReferenceParent::VarargMethod(m) => index::MemberRefParent::MethodDef(method_index_map[m]),
-            },
-            name: heap_idx!(strings, m.name),
-            signature: convert::write::method_ref(&m.signature, build_ctx!())?,
-        });
+    tables.member_ref.reserve(member_refs.len());
+    for (idx, r) in member_refs.into_iter().enumerate() {
+        match r {
+            MemberRefSource::Method(m) => {
+                let m = &res[m];
+                tables.member_ref.push(MemberRef {
+                    class: match &m.parent {
+                        MethodReferenceParent::Type(t) => type_to_parent(t, build_ctx!())?,
+                        MethodReferenceParent::Module(m) => index::MemberRefParent::ModuleRef(m.0 + 1),
+                        MethodReferenceParent::VarargMethod(m) => {
+                            index::MemberRefParent::MethodDef(method_index_map[m])
+                        }
+                    },
+                    name: heap_idx!(strings, m.name),
+                    signature: convert::write::method_ref(&m.signature, build_ctx!())?,
+                });
 
-        write_attrs!(m.attributes, MemberRef(idx + 1));
-    }
-    for (idx, f) in res.field_references.iter().enumerate() {
-        tables.member_ref.push(MemberRef {
-            class: match &f.parent {
-                FieldReferenceParent::Type(t) => type_to_parent(t, build_ctx!())?,
-                FieldReferenceParent::Module(m) => index::MemberRefParent::ModuleRef(m.0 + 1),
-            },
-            name: heap_idx!(strings, f.name),
-            signature: convert::write::field_ref(f, build_ctx!())?,
-        });
+                write_attrs!(m.attributes, MemberRef(idx + 1));
+            }
+            MemberRefSource::Field(f) => {
+                let f = &res[f];
+                tables.member_ref.push(MemberRef {
+                    class: match &f.parent {
+                        FieldReferenceParent::Type(t) => type_to_parent(t, build_ctx!())?,
+                        FieldReferenceParent::Module(m) => index::MemberRefParent::ModuleRef(m.0 + 1),
+                    },
+                    name: heap_idx!(strings, f.name),
+                    signature: convert::write::field_ref(f, build_ctx!())?,
+                });
 
-        write_attrs!(f.attributes, MemberRef(field_offset + idx + 1));
+                write_attrs!(f.attributes, MemberRef(idx + 1));
+            }
+        }
     }
 
     debug!("type refs");
@@ -1222,12 +2108,71 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
             parent,
             attr_type: match a.constructor {
                 UserMethod::Definition(m) => index::CustomAttributeType::MethodDef(method_index_map[&m]),
-                UserMethod::Reference(r) => index::CustomAttributeType::MemberRef(r.0 + 1),
+                UserMethod::Reference(r) => index::CustomAttributeType::MemberRef(method_ref_rows[r.0]),
             },
             value: opt_heap!(blobs, a.value.as_ref()),
         });
     }
 
+    let mut remapping = TokenRemapping::default();
+    if let Some(o) = original_tokens {
+        for (m, &row) in &o.methods {
+            if let Some(&new_row) = method_index_map.get(m) {
+                remapping.record(Kind::MethodDef, row, new_row);
+            }
+        }
+        for (f, &row) in &o.fields {
+            if let Some(&new_row) = field_index_map.get(f) {
+                remapping.record(Kind::Field, row, new_row);
+            }
+        }
+        for (m, &row) in &o.method_references {
+            if let Some(&new_row) = method_ref_rows.get(m.0) {
+                remapping.record(Kind::MemberRef, row, new_row);
+            }
+        }
+        for (f, &row) in &o.field_references {
+            if let Some(&new_row) = field_ref_rows.get(f.0) {
+                remapping.record(Kind::MemberRef, row, new_row);
+            }
+        }
+
+        // rows that only hold signatures are matched to the first row written with the same signature
+        macro_rules! record_signatures {
+            ($kind:ident, $table:ident, $original:ident) => {{
+                let mut rows = HashMap::new();
+                for (row, s) in tables.$table.iter().enumerate() {
+                    rows.entry(s.signature.0).or_insert(row + 1);
+                }
+                for (row, signature) in o.$original.iter().enumerate() {
+                    if let Some(&new_row) = blobs.find(signature).and_then(|b| rows.get(&b.0)) {
+                        remapping.record(Kind::$kind, row + 1, new_row);
+                    }
+                }
+            }};
+        }
+        record_signatures!(TypeSpec, type_spec, type_specs);
+        record_signatures!(StandAloneSig, stand_alone_sig, stand_alone_sigs);
+
+        let method_token = |m: index::MethodDefOrRef| match m {
+            index::MethodDefOrRef::MethodDef(i) => tokens::token(Kind::MethodDef, i),
+            index::MethodDefOrRef::MemberRef(i) => tokens::token(Kind::MemberRef, i),
+            index::MethodDefOrRef::Null => 0,
+        };
+        let mut method_spec_rows = HashMap::new();
+        for (row, s) in tables.method_spec.iter().enumerate() {
+            method_spec_rows
+                .entry((method_token(s.method), s.instantiation.0))
+                .or_insert(row + 1);
+        }
+        for (row, &(method, instantiation)) in o.method_specs.iter().enumerate() {
+            let key = blobs.find(instantiation).map(|b| (remapping.remap(method), b.0));
+            if let Some(&new_row) = key.and_then(|k| method_spec_rows.get(&k)) {
+                remapping.record(Kind::MethodSpec, row + 1, new_row);
+            }
+        }
+    }
+
     let entry_point_token = match res.entry_point {
         Some(e) => {
             let tok = match e {
@@ -1252,29 +2197,19 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     debug!("write to DLL");
 
     let strings_vec = strings.into_vec();
-    let guids_vec = guids.into_vec();
+    let mut guids_vec = guids.into_vec();
     let blobs_vec = blobs.into_vec();
     let userstrings_vec = userstrings.into_vec();
 
+    // the PDB only needs the shape of the type system tables, which are consumed by the table stream
+    let type_system_rows = tables.row_counts();
+    let referenced_type_system_tables = tables.valid_mask();
+
     let header = header::Header {
         reserved0: 0,
         major_version: 2,
         minor_version: 0,
-        heap_sizes: {
-            let mut mask = 0;
-
-            if strings_vec.len() >= (1_usize << 16) {
-                mask |= 0x01;
-            }
-            if guids_vec.len() >= (1_usize << 16) {
-                mask |= 0x02;
-            }
-            if blobs_vec.len() >= (1_usize << 16) {
-                mask |= 0x04;
-            }
-
-            mask
-        },
+        heap_sizes: heap_sizes(&strings_vec, &guids_vec, &blobs_vec),
         reserved1: 1,
         valid: tables.valid_mask(),
         sorted: Tables::sorted_mask(),
@@ -1285,61 +2220,83 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     header_buf.pwrite(header, 0)?;
     let header_stream = header_buf.get();
 
-    const VERSION_STRING: &str = "Standard CLI 2005";
+    let mut mvid = res.module.mvid;
+    let mut time_date_stamp = opts.time_date_stamp;
+    if opts.deterministic {
+        let mut hash = Sha1::new();
+        for part in [
+            header_stream,
+            &strings_vec,
+            &guids_vec,
+            &blobs_vec,
+            &userstrings_vec,
+            &text,
+            &resources,
+        ] {
+            hash.update(&(part.len() as u64).to_le_bytes());
+            hash.update(part);
+        }
+        let digest = hash.finish();
 
-    let streams: Vec<_> = [
-        (header_stream, "#~"),
-        (&strings_vec, StringsReader::NAME),
-        (&guids_vec, GUIDReader::NAME),
-        (&blobs_vec, BlobReader::NAME),
-        (&userstrings_vec, UserStringReader::NAME),
-    ]
-    .into_iter()
-    .filter(|(s, _)| !s.is_empty())
-    .collect();
+        // mark the MVID as a version 4 GUID and the timestamp as not being a real time, like other compilers do
+        mvid.copy_from_slice(&digest[..16]);
+        mvid[7] = (mvid[7] & 0x0f) | 0x40;
+        mvid[8] = (mvid[8] & 0x3f) | 0x80;
+        // the MVID is the only GUID in the heap
+        guids_vec[..16].copy_from_slice(&mvid);
 
-    // ECMA-335, II.24.2.1 (page 271)
-    let root_and_header_size: usize = 20_usize
-        + crate::utils::round_up_to_4(VERSION_STRING.len() + 1_usize).0
-        + streams
-            .iter()
-            .map(|(_, n)| {
-                // add offset and size fields
-                8 + crate::utils::round_up_to_4(n.len() + 1).0
-            })
-            .sum::<usize>();
+        time_date_stamp.get_or_insert(u32::from_le_bytes(digest[16..].try_into().unwrap()) | 0x8000_0000);
+    }
+    let time_date_stamp =
+        time_date_stamp.unwrap_or_else(
+            || match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+                Ok(d) => d.as_secs() as u32,
+                _ => 0,
+            },
+        );
 
-    let metadata_rva = current_rva!();
+    let pdb = if emit_pdb {
+        debug!("portable pdb");
 
-    let mut metadata_buf = vec![0_u8; root_and_header_size];
-    metadata_buf.pwrite(
-        Metadata {
-            signature: 0x424A_5342, // magic value, same page of ECMA as above
-            major_version: 1,
-            minor_version: 1,
-            reserved: 0,
-            version: VERSION_STRING,
-            flags: 0,
-            stream_headers: streams
-                .iter()
-                .scan(root_and_header_size, |offset, (s, name)| {
-                    let prev = *offset;
-                    let size = s.len();
-                    *offset += size;
+        // the PDB is identified by the GUID and timestamp from the DLL's CodeView debug directory entry
+        let mut id = [0_u8; 20];
+        id[..16].copy_from_slice(&mvid);
+        id[16..].copy_from_slice(&time_date_stamp.to_le_bytes());
 
-                    Some(stream::Header {
-                        offset: prev as u32,
-                        size: size as u32,
-                        name,
-                    })
-                })
-                .collect(),
-        },
-        0,
+        let pdb_entry_point = if entry_point_token >> 24 == Kind::MethodDef as u32 {
+            entry_point_token
+        } else {
+            0
+        };
+
+        Some((
+            id,
+            write_pdb(
+                res,
+                id,
+                pdb_entry_point,
+                &type_system_rows,
+                referenced_type_system_tables,
+                &debug_rows,
+                &import_types,
+            )?,
+        ))
+    } else {
+        None
+    };
+
+    let metadata_rva = current_rva!();
+
+    let metadata_buf = metadata_root(
+        &opts.metadata_version,
+        &[
+            (header_stream, "#~"),
+            (&strings_vec, StringsReader::NAME),
+            (&guids_vec, GUIDReader::NAME),
+            (&blobs_vec, BlobReader::NAME),
+            (&userstrings_vec, UserStringReader::NAME),
+        ],
     )?;
-    for (s, _) in streams {
-        metadata_buf.extend(s);
-    }
 
     let metadata_len = metadata_buf.len();
     text.extend(metadata_buf);
@@ -1350,19 +2307,43 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     };
     text.extend(resources);
 
+    let (signature_offset, strong_name_signature) = match signing_key {
+        Some(k) => {
+            while text.len() % 4 != 0 {
+                text.push(0);
+            }
+            let offset = text.len();
+            let rva = current_rva!();
+            // the signature is filled in once the rest of the image has been written
+            text.resize(offset + k.signature_size(), 0);
+            (
+                Some(offset),
+                RVASize {
+                    rva,
+                    size: k.signature_size() as u32,
+                },
+            )
+        }
+        None => (None, RVASize::default()),
+    };
+
     let cli_rva = current_rva!();
     let cli_header = Header {
         cb: 72,
-        major_runtime_version: 0,
-        minor_runtime_version: 0,
+        major_runtime_version: opts.runtime_version.0,
+        minor_runtime_version: opts.runtime_version.1,
         metadata: RVASize {
             rva: metadata_rva,
             size: metadata_len as u32,
         },
-        flags: pe::COMIMAGE_FLAGS_ILONLY,
+        flags: if signing_key.is_some() {
+            platform.cli_flags() | pe::COMIMAGE_FLAGS_STRONGNAMESIGNED
+        } else {
+            platform.cli_flags()
+        },
         entry_point_token,
         resources: resources_rva,
-        strong_name_signature: RVASize::default(),
+        strong_name_signature,
         code_manager_table: RVASize::default(),
         vtable_fixups: RVASize::default(),
         export_address_table_jumps: RVASize::default(),
@@ -1374,15 +2355,63 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
 
     writer.set_data_directory(pe::IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR, cli_rva, 72);
 
+    let debug_directory = match &pdb {
+        Some((id, _)) => {
+            let directory_offset = text.len();
+            let directory_rva = current_rva!();
+            let directory_size = std::mem::size_of::<pe::ImageDebugDirectory>() as u32;
+
+            // CodeView entry pointing to a portable PDB, see the Portable PDB v1.0 specification, section "CodeView Debug Directory Entry"
+            let path = std::path::Path::new(res.module.name.as_ref()).with_extension("pdb");
+            let mut codeview = b"RSDS".to_vec();
+            codeview.extend(&id[..16]);
+            codeview.extend(1_u32.to_le_bytes());
+            codeview.extend(path.to_string_lossy().as_bytes());
+            codeview.push(0);
+
+            text.extend_from_slice(object::pod::bytes_of(&pe::ImageDebugDirectory {
+                characteristics: U32Bytes::new(LittleEndian, 0),
+                time_date_stamp: U32Bytes::new(LittleEndian, time_date_stamp),
+                major_version: U16Bytes::new(LittleEndian, 0x0100),
+                minor_version: U16Bytes::new(LittleEndian, 0x504D),
+                typ: U32Bytes::new(LittleEndian, pe::IMAGE_DEBUG_TYPE_CODEVIEW),
+                size_of_data: U32Bytes::new(LittleEndian, codeview.len() as u32),
+                address_of_raw_data: U32Bytes::new(LittleEndian, directory_rva + directory_size),
+                // the file offset isn't known until the text section is reserved
+                pointer_to_raw_data: U32Bytes::new(LittleEndian, 0),
+            }));
+            text.extend(codeview);
+
+            writer.set_data_directory(pe::IMAGE_DIRECTORY_ENTRY_DEBUG, directory_rva, directory_size);
+
+            Some(directory_offset)
+        }
+        None => None,
+    };
+
     let text_range = writer.reserve_text_section(text.len() as u32);
 
+    if let Some(offset) = debug_directory {
+        let raw_data_offset = text_range.file_offset + (offset + std::mem::size_of::<pe::ImageDebugDirectory>()) as u32;
+        text[offset + 24..offset + 28].copy_from_slice(&raw_data_offset.to_le_bytes());
+    }
+
+    let rsrc = if has_win32_resources {
+        // the directory refers to its data by RVA, so it has to be laid out for the address the section will get
+        let rsrc = res.win32_resources.write(writer.virtual_len())?;
+        let range = writer.reserve_rsrc_section(rsrc.len() as u32);
+        Some((rsrc, range))
+    } else {
+        None
+    };
+
     if opts.is_executable {
         writer.add_reloc(
             text_range.virtual_address,
-            if opts.is_32_bit {
-                pe::IMAGE_REL_BASED_HIGHLOW
-            } else {
+            if platform.is_64_bit() {
                 pe::IMAGE_REL_BASED_DIR64
+            } else {
+                pe::IMAGE_REL_BASED_HIGHLOW
             },
         );
         writer.reserve_reloc_section();
@@ -1393,15 +2422,8 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
     // because this is just writing to a Vec, the buffer always succeeds to allocate and there's no need to handle the error
     writer.write_dos_header_and_stub().unwrap();
     writer.write_nt_headers(NtHeaders {
-        machine: if opts.is_32_bit {
-            pe::IMAGE_FILE_MACHINE_I386
-        } else {
-            pe::IMAGE_FILE_MACHINE_AMD64
-        },
-        time_date_stamp: match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
-            Ok(d) => d.as_secs() as u32,
-            _ => 0,
-        },
+        machine: platform.machine(),
+        time_date_stamp,
         characteristics: {
             let mut flags = pe::IMAGE_FILE_EXECUTABLE_IMAGE;
             if !opts.is_executable {
@@ -1416,15 +2438,18 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
         } else {
             0
         },
-        image_base: 0x0040_0000,
+        image_base: opts.image_base,
         major_operating_system_version: 5,
         minor_operating_system_version: 0,
         major_image_version: 0,
         minor_image_version: 0,
         major_subsystem_version: 5,
         minor_subsystem_version: 0,
-        subsystem: pe::IMAGE_SUBSYSTEM_WINDOWS_CUI,
-        dll_characteristics: 0,
+        subsystem: match opts.subsystem {
+            Subsystem::Console => pe::IMAGE_SUBSYSTEM_WINDOWS_CUI,
+            Subsystem::Windows => pe::IMAGE_SUBSYSTEM_WINDOWS_GUI,
+        },
+        dll_characteristics: opts.dll_characteristics.to_mask(),
         size_of_stack_reserve: 0x0010_0000,
         size_of_stack_commit: 0x1000,
         size_of_heap_reserve: 0x0010_0000,
@@ -1435,8 +2460,15 @@ pub(crate) fn write_impl(res: &Resolution, opts: Options) -> Result<Vec<u8>> {
         writer.write_section(section.file_offset, &idata);
     }
     writer.write_section(text_range.file_offset, &text);
+    if let Some((rsrc, range)) = rsrc {
+        writer.write_section(range.file_offset, &rsrc);
+    }
     // ignored if no relocs have been set
     writer.write_reloc_section();
 
-    Ok(buffer)
+    if let (Some(key), Some(offset)) = (signing_key, signature_offset) {
+        key.sign_image(&mut buffer, text_range.file_offset as usize + offset)?;
+    }
+
+    Ok((buffer, pdb.map(|(_, p)| p), remapping))
 }
@@ -1,4 +1,5 @@
 use super::attribute::{Attribute, SecurityDeclaration};
+use crate::resolution::strong_name;
 use std::borrow::Cow;
 
 #[derive(Debug, Default, Copy, Clone)]
@@ -28,7 +29,7 @@ impl Flags {
     }
 }
 
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Version {
     pub major: u16,
     pub minor: u16,
@@ -104,4 +105,14 @@ impl<'a> ExternalAssemblyReference<'a> {
             hash_value: None,
         }
     }
+
+    /// Returns the token of the referenced assembly's public key, computing it if the full key is stored.
+    pub fn public_key_token(&self) -> Option<[u8; 8]> {
+        let key = self.public_key_or_token.as_ref()?;
+        if self.has_full_public_key {
+            Some(strong_name::public_key_token(key))
+        } else {
+            key.as_ref().try_into().ok()
+        }
+    }
 }
@@ -196,7 +196,7 @@ pub struct Attribute<'a> {
 impl<'a> Attribute<'a> {
     pub fn instantiation_data(
         &'a self,
-        resolver: &'a impl Resolver<'a>,
+        resolver: &impl Resolver<'a>,
         resolution: &'a Resolution<'a>,
     ) -> Result<CustomAttributeData<'a>> {
         let bytes = self
@@ -256,6 +256,11 @@ impl<'a> Attribute<'a> {
             value: Some(buffer.into_vec().into()),
         }
     }
+
+    /// Creates an attribute from its already encoded value blob (ECMA-335, II.23.3), as written by assemblers.
+    pub fn from_bytes(constructor: members::UserMethod, value: Option<Cow<'a, [u8]>>) -> Self {
+        Attribute { constructor, value }
+    }
 }
 
 // we abstract away all the StandAloneSigs and TypeSpecs, so there's no good place to put attributes that belong to them
@@ -275,10 +280,7 @@ pub struct Permission<'a> {
 }
 
 impl<'a> SecurityDeclaration<'a> {
-    pub fn requested_permissions(
-        &'a self,
-        resolver: &'a impl Resolver<'a>,
-    ) -> Result<Vec<Permission<'a>>> {
+    pub fn requested_permissions(&'a self, resolver: &impl Resolver<'a>) -> Result<Vec<Permission<'a>>> {
         let offset = &mut 0;
 
         let value = self.value.as_ref();
@@ -329,4 +331,13 @@ impl<'a> SecurityDeclaration<'a> {
             value: buffer.into_vec().into(),
         })
     }
+
+    /// Creates a security declaration from its already encoded permission set blob (ECMA-335, II.23.1.3).
+    pub fn from_bytes(attributes: Vec<Attribute<'a>>, action: u16, value: Cow<'a, [u8]>) -> Self {
+        SecurityDeclaration {
+            attributes,
+            action,
+            value,
+        }
+    }
 }
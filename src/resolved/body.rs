@@ -1,9 +1,12 @@
+use std::ops::Range;
+
 use super::{
+    debug::MethodDebugInformation,
     il::Instruction,
     types::{LocalVariable, MethodType},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Header {
     pub initialize_locals: bool,
     pub maximum_stack_size: usize,
@@ -24,6 +27,7 @@ pub struct Method {
     pub header: Header,
     pub instructions: Vec<Instruction>,
     pub data_sections: Vec<DataSection>,
+    pub debug: Option<MethodDebugInformation>,
 }
 impl Method {
     pub fn new(instructions: Vec<Instruction>) -> Self {
@@ -39,15 +43,141 @@ impl Method {
         m.header.initialize_locals = true;
         m
     }
+
+    // applies a mapping of old instruction positions to new ones to every branch target and exception handler.
+    // ends of ranges are mapped as positions too, so a clause is kept only while it still covers an instruction
+    fn remap(&mut self, map: impl Fn(usize) -> usize) {
+        for i in &mut self.instructions {
+            for t in i.targets_mut() {
+                *t = map(*t);
+            }
+        }
+
+        for section in &mut self.data_sections {
+            if let DataSection::ExceptionHandlers(clauses) = section {
+                for e in clauses.iter_mut() {
+                    let try_end = map(e.try_offset + e.try_length);
+                    e.try_offset = map(e.try_offset);
+                    e.try_length = try_end - e.try_offset;
+
+                    let handler_end = map(e.handler_offset + e.handler_length);
+                    e.handler_offset = map(e.handler_offset);
+                    e.handler_length = handler_end - e.handler_offset;
+
+                    if let ExceptionKind::Filter { offset } = &mut e.kind {
+                        *offset = map(*offset);
+                    }
+                }
+                clauses.retain(|e| e.try_length > 0 && e.handler_length > 0);
+            }
+        }
+        self.data_sections
+            .retain(|s| !matches!(s, DataSection::ExceptionHandlers(clauses) if clauses.is_empty()));
+    }
+
+    fn insert(&mut self, index: usize, instructions: impl IntoIterator<Item = Instruction>, before: bool) {
+        assert!(
+            index <= self.instructions.len(),
+            "insertion index (is {}) should be <= len (is {})",
+            index,
+            self.instructions.len()
+        );
+
+        let instructions: Vec<_> = instructions.into_iter().collect();
+        let count = instructions.len();
+        if count == 0 {
+            return;
+        }
+
+        // remapping before splicing leaves the targets of the new instructions alone
+        self.remap(|p| if p > index || (p == index && !before) { p + count } else { p });
+        self.instructions.splice(index..index, instructions);
+
+        if let Some(debug) = &mut self.debug {
+            debug.instructions_inserted(index, count);
+        }
+    }
+
+    /// Inserts instructions so that they run right before the instruction at `index`.
+    ///
+    /// Branches to `index` are redirected to the first inserted instruction, and exception blocks and handlers that
+    /// start at `index` grow to include the new instructions. Branches in the inserted instructions are left as they
+    /// are, so they should target indices in the body after insertion.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of instructions.
+    pub fn insert_before(&mut self, index: usize, instructions: impl IntoIterator<Item = Instruction>) {
+        self.insert(index, instructions, true);
+    }
+
+    /// Inserts instructions so that they run right after the instruction at `index`.
+    ///
+    /// Branches to the following instruction still go to that instruction, and exception blocks and handlers that
+    /// end with the instruction at `index` grow to include the new instructions. As with
+    /// [`insert_before`](Self::insert_before), branches in the inserted instructions are left as they are.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn insert_after(&mut self, index: usize, instructions: impl IntoIterator<Item = Instruction>) {
+        assert!(
+            index < self.instructions.len(),
+            "insertion index (is {}) should be < len (is {})",
+            index,
+            self.instructions.len()
+        );
+        self.insert(index + 1, instructions, false);
+    }
+
+    /// Replaces the instruction at `index`, returning the old one. Branches to `index` now go to the new instruction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn replace(&mut self, index: usize, instruction: Instruction) -> Instruction {
+        std::mem::replace(&mut self.instructions[index], instruction)
+    }
+
+    /// Removes the instructions in `range`, returning them.
+    ///
+    /// Branches into the removed instructions are redirected to the instruction that followed them.
+    /// Exception handling clauses whose protected block or handler no longer contains any instructions are removed,
+    /// as are sequence points and scopes in the debugging information.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn remove(&mut self, range: Range<usize>) -> Vec<Instruction> {
+        let removed: Vec<_> = self.instructions.drain(range.clone()).collect();
+        if removed.is_empty() {
+            return removed;
+        }
+
+        let count = removed.len();
+        self.remap(|p| {
+            if p >= range.end {
+                p - count
+            } else {
+                p.min(range.start)
+            }
+        });
+
+        if let Some(debug) = &mut self.debug {
+            debug.instructions_removed(range);
+        }
+
+        removed
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DataSection {
     Unrecognized { fat: bool, size: usize },
     ExceptionHandlers(Vec<Exception>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Exception {
     pub kind: ExceptionKind,
     pub try_offset: usize,
@@ -56,7 +186,7 @@ pub struct Exception {
     pub handler_length: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ExceptionKind {
     TypedException(MethodType),
     Filter { offset: usize },
@@ -0,0 +1,389 @@
+//! A runtime emitter for method bodies, in the style of .NET's `System.Reflection.Emit.ILGenerator`.
+//!
+//! Unlike the [`asm!`](crate::asm) macro, labels can be created and placed while the body is being generated, and
+//! exception handling clauses are built from `begin_*` calls instead of instruction offsets:
+//!
+//! ```
+//! use dotnetdll::prelude::*;
+//!
+//! let mut b = MethodBodyBuilder::new();
+//! let counter = b.declare_local(LocalVariable::new(ctype! { int }));
+//! let done = b.define_label();
+//!
+//! b.emit(Instruction::LoadArgument(0)).store_local(counter);
+//! b.begin_try();
+//! b.load_local(counter).emit_branch(Instruction::BranchTruthy, done);
+//! b.emit(Instruction::LoadNull).emit(Instruction::Throw);
+//! b.mark_label(done);
+//! b.begin_finally();
+//! b.emit(Instruction::NoOperation);
+//! b.end_block();
+//! b.emit(Instruction::Return);
+//!
+//! // the try block is ended by a leave, and the finally handler by an endfinally
+//! let body = b.build();
+//! assert!(matches!(body.instructions[6], Instruction::Leave(9)));
+//! assert!(matches!(body.instructions[8], Instruction::EndFinally));
+//! ```
+
+use super::{
+    body::{self, DataSection, Exception, ExceptionKind},
+    il::Instruction,
+    types::{LocalVariable, MethodType},
+};
+
+/// A position in a method body that branches can target, from [`MethodBodyBuilder::define_label`].
+///
+/// Labels can be used by branches before they are marked with [`MethodBodyBuilder::mark_label`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+/// A local variable of a method body, from [`MethodBodyBuilder::declare_local`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Local(u16);
+
+impl Local {
+    /// The index of the variable in [`Header::local_variables`](body::Header::local_variables), as used by
+    /// instructions like [`Instruction::LoadLocal`].
+    pub fn index(self) -> u16 {
+        self.0
+    }
+}
+
+// the part of an exception block that is currently being emitted
+#[derive(Debug)]
+enum Section {
+    Try,
+    Filter { start: usize },
+    Handler { kind: ExceptionKind, start: usize },
+}
+
+#[derive(Debug)]
+struct ExceptionBlock {
+    try_start: usize,
+    // where the try block ends for catch and filter handlers, which is after the leave at the end of it
+    try_end: Option<usize>,
+    end: Label,
+    section: Section,
+    clauses: Vec<Exception>,
+}
+
+/// Builds a [`body::Method`] one instruction at a time. See the [module documentation](self).
+///
+/// Misusing the builder, such as marking a label twice or starting a catch handler outside of an exception block,
+/// is a bug in the code generating the body, so it panics instead of returning an error.
+#[derive(Debug, Default)]
+pub struct MethodBodyBuilder {
+    instructions: Vec<Instruction>,
+    locals: Vec<LocalVariable>,
+    // locals that went out of scope, which can be reused by later declarations of the same type
+    free_locals: Vec<u16>,
+    scopes: Vec<Vec<u16>>,
+    labels: Vec<Option<usize>>,
+    // the instructions whose targets are still labels
+    fixups: Vec<usize>,
+    blocks: Vec<ExceptionBlock>,
+    exceptions: Vec<Exception>,
+}
+
+impl MethodBodyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The index that the next emitted instruction will have.
+    pub fn position(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Appends an instruction to the body.
+    ///
+    /// Any branch targets of the instruction are used as they are, so use [`emit_branch`](Self::emit_branch) or
+    /// [`emit_switch`](Self::emit_switch) for branches to labels.
+    pub fn emit(&mut self, instruction: Instruction) -> &mut Self {
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// Appends a branch to a label, which does not need to be marked yet.
+    /// `make` receives the label's placeholder target, e.g. `builder.emit_branch(Instruction::Branch, label)`.
+    pub fn emit_branch(&mut self, make: impl FnOnce(usize) -> Instruction, label: Label) -> &mut Self {
+        self.fixups.push(self.instructions.len());
+        self.instructions.push(make(label.0));
+        self
+    }
+
+    /// Appends a `switch` whose cases branch to the given labels.
+    pub fn emit_switch(&mut self, labels: &[Label]) -> &mut Self {
+        self.fixups.push(self.instructions.len());
+        self.emit(Instruction::Switch(labels.iter().map(|l| l.0).collect()))
+    }
+
+    /// Creates a label, which can be marked at any point before the body is built.
+    pub fn define_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Places a label at the next instruction that will be emitted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the label has already been marked.
+    pub fn mark_label(&mut self, label: Label) -> &mut Self {
+        let position = &mut self.labels[label.0];
+        assert!(position.is_none(), "label {} has already been marked", label.0);
+        *position = Some(self.instructions.len());
+        self
+    }
+
+    /// Declares a local variable. If a variable of the same type went out of scope with [`end_scope`](Self::end_scope),
+    /// its slot is reused.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the body would have more than 65535 local variables.
+    pub fn declare_local(&mut self, variable: LocalVariable) -> Local {
+        let reused = self
+            .free_locals
+            .iter()
+            .position(|&l| self.locals[l as usize] == variable)
+            .map(|i| self.free_locals.swap_remove(i));
+
+        let index = reused.unwrap_or_else(|| {
+            self.locals.push(variable);
+            u16::try_from(self.locals.len() - 1).expect("too many local variables")
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(index);
+        }
+
+        Local(index)
+    }
+
+    /// Starts a scope for local variables. Variables declared in the scope can be reused once it ends.
+    pub fn begin_scope(&mut self) -> &mut Self {
+        self.scopes.push(vec![]);
+        self
+    }
+
+    /// Ends the innermost scope started by [`begin_scope`](Self::begin_scope).
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no scope to end.
+    pub fn end_scope(&mut self) -> &mut Self {
+        let scope = self
+            .scopes
+            .pop()
+            .expect("end_scope called without a matching begin_scope");
+        self.free_locals.extend(scope);
+        self
+    }
+
+    pub fn load_local(&mut self, local: Local) -> &mut Self {
+        self.emit(Instruction::LoadLocal(local.0))
+    }
+
+    pub fn load_local_address(&mut self, local: Local) -> &mut Self {
+        self.emit(Instruction::LoadLocalAddress(local.0))
+    }
+
+    pub fn store_local(&mut self, local: Local) -> &mut Self {
+        self.emit(Instruction::StoreLocal(local.0))
+    }
+
+    /// Starts an exception block with its try block, returning the label that the block ends at.
+    ///
+    /// The block needs at least one handler, started by [`begin_catch`](Self::begin_catch),
+    /// [`begin_filter`](Self::begin_filter), [`begin_finally`](Self::begin_finally) or
+    /// [`begin_fault`](Self::begin_fault), and is closed by [`end_block`](Self::end_block).
+    pub fn begin_try(&mut self) -> Label {
+        let end = self.define_label();
+        self.blocks.push(ExceptionBlock {
+            try_start: self.instructions.len(),
+            try_end: None,
+            end,
+            section: Section::Try,
+            clauses: vec![],
+        });
+        end
+    }
+
+    // finishes the try block or handler being emitted, with the leave or endfinally that ends it
+    fn close_section(&mut self, block: &mut ExceptionBlock) {
+        match &block.section {
+            Section::Try => {
+                self.emit_branch(Instruction::Leave, block.end);
+                block.try_end = Some(self.instructions.len());
+            }
+            Section::Filter { .. } => panic!("a filter must be followed by begin_filter_handler"),
+            Section::Handler { kind, start } => {
+                let try_offset = block.try_start;
+                let try_end = match kind {
+                    ExceptionKind::TypedException(_) | ExceptionKind::Filter { .. } => {
+                        self.emit_branch(Instruction::Leave, block.end);
+                        block.try_end.unwrap()
+                    }
+                    // finally and fault handlers come after any catch handlers, so they protect those too
+                    ExceptionKind::Finally | ExceptionKind::Fault => {
+                        self.emit(Instruction::EndFinally);
+                        *start
+                    }
+                };
+
+                block.clauses.push(Exception {
+                    kind: kind.clone(),
+                    try_offset,
+                    try_length: try_end - try_offset,
+                    handler_offset: *start,
+                    handler_length: self.instructions.len() - start,
+                });
+            }
+        }
+    }
+
+    fn begin_section(&mut self, method: &str, section: impl FnOnce(usize) -> Section) {
+        let mut block = self
+            .blocks
+            .pop()
+            .unwrap_or_else(|| panic!("{} called outside of an exception block", method));
+        if let Section::Handler {
+            kind: ExceptionKind::Finally | ExceptionKind::Fault,
+            ..
+        } = block.section
+        {
+            panic!(
+                "{} called after a finally or fault handler, which must be the last one",
+                method
+            );
+        }
+
+        self.close_section(&mut block);
+        block.section = section(self.instructions.len());
+        self.blocks.push(block);
+    }
+
+    /// Ends the current try block or handler and starts a handler for exceptions of the given type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no exception block, or if the current handler is a finally or fault handler.
+    pub fn begin_catch(&mut self, exception_type: MethodType) -> &mut Self {
+        self.begin_section("begin_catch", |start| Section::Handler {
+            kind: ExceptionKind::TypedException(exception_type),
+            start,
+        });
+        self
+    }
+
+    /// Ends the current try block or handler and starts a filter, which decides whether the handler started by
+    /// [`begin_filter_handler`](Self::begin_filter_handler) will run.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no exception block, or if the current handler is a finally or fault handler.
+    pub fn begin_filter(&mut self) -> &mut Self {
+        self.begin_section("begin_filter", |start| Section::Filter { start });
+        self
+    }
+
+    /// Ends the current filter with `endfilter` and starts its handler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current exception block is not in a filter.
+    pub fn begin_filter_handler(&mut self) -> &mut Self {
+        let block = self
+            .blocks
+            .last_mut()
+            .expect("begin_filter_handler called outside of an exception block");
+        let Section::Filter { start } = block.section else {
+            panic!("begin_filter_handler called outside of a filter");
+        };
+
+        self.instructions.push(Instruction::EndFilter);
+        let block = self.blocks.last_mut().unwrap();
+        block.section = Section::Handler {
+            kind: ExceptionKind::Filter { offset: start },
+            start: self.instructions.len(),
+        };
+        self
+    }
+
+    /// Ends the current try block or handler and starts a finally handler, which must be the last handler of the
+    /// block. If the block has catch handlers, the finally handler protects them as well as the try block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no exception block, or if the current handler is a finally or fault handler.
+    pub fn begin_finally(&mut self) -> &mut Self {
+        self.begin_section("begin_finally", |start| Section::Handler {
+            kind: ExceptionKind::Finally,
+            start,
+        });
+        self
+    }
+
+    /// Like [`begin_finally`](Self::begin_finally), but the handler only runs when an exception is thrown.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no exception block, or if the current handler is a finally or fault handler.
+    pub fn begin_fault(&mut self) -> &mut Self {
+        self.begin_section("begin_fault", |start| Section::Handler {
+            kind: ExceptionKind::Fault,
+            start,
+        });
+        self
+    }
+
+    /// Ends the current handler and the exception block, and marks the label returned by
+    /// [`begin_try`](Self::begin_try).
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no exception block, or if it is still in its try block or a filter.
+    pub fn end_block(&mut self) -> &mut Self {
+        let mut block = self
+            .blocks
+            .pop()
+            .expect("end_block called outside of an exception block");
+        assert!(
+            matches!(block.section, Section::Handler { .. }),
+            "an exception block must end with a handler"
+        );
+
+        self.close_section(&mut block);
+        self.mark_label(block.end);
+        // clauses are added as blocks end, so nested blocks always come before the blocks that contain them
+        self.exceptions.extend(block.clauses);
+        self
+    }
+
+    /// Finishes the body, resolving the targets of branches to labels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a label used by a branch was never marked, or if an exception block or scope is still open.
+    pub fn build(mut self) -> body::Method {
+        assert!(self.blocks.is_empty(), "an exception block was not ended");
+        assert!(self.scopes.is_empty(), "a scope was not ended");
+
+        for idx in self.fixups {
+            for t in self.instructions[idx].targets_mut() {
+                *t = self.labels[*t].unwrap_or_else(|| panic!("label {} was never marked", *t));
+            }
+        }
+
+        let mut body = body::Method::new(self.instructions);
+        if !self.locals.is_empty() {
+            body.header.local_variables = self.locals;
+            body.header.initialize_locals = true;
+        }
+        if !self.exceptions.is_empty() {
+            body.data_sections.push(DataSection::ExceptionHandlers(self.exceptions));
+        }
+        body
+    }
+}
@@ -0,0 +1,261 @@
+use super::types::MemberType;
+use crate::resolution::{AssemblyRefIndex, DocumentIndex, ImportScopeIndex};
+use std::{borrow::Cow, ops::Range};
+
+// GUIDs are stored in the same mixed-endian byte order as the #GUID heap
+pub const LANGUAGE_CSHARP: [u8; 16] = [
+    0xf8, 0x62, 0x51, 0x3f, 0xc6, 0x07, 0xd3, 0x11, 0x90, 0x53, 0x00, 0xc0, 0x4f, 0xa3, 0x02, 0xa1,
+];
+pub const LANGUAGE_VISUAL_BASIC: [u8; 16] = [
+    0xb8, 0xd0, 0x12, 0x3a, 0x6c, 0xc2, 0xd0, 0x11, 0xb4, 0x42, 0x00, 0xa0, 0x24, 0x4a, 0x1d, 0xd2,
+];
+pub const LANGUAGE_FSHARP: [u8; 16] = [
+    0xc9, 0x38, 0x4f, 0xab, 0xe6, 0xb6, 0xba, 0x43, 0xbe, 0x3b, 0x58, 0x08, 0x0b, 0x2c, 0xcc, 0xe3,
+];
+
+pub const HASH_SHA1: [u8; 16] = [
+    0xec, 0x16, 0x18, 0xff, 0x5e, 0xaa, 0x10, 0x4d, 0x87, 0xf7, 0x6f, 0x49, 0x63, 0x83, 0x34, 0x60,
+];
+pub const HASH_SHA256: [u8; 16] = [
+    0x0f, 0xd0, 0x29, 0x88, 0xb8, 0x11, 0x13, 0x42, 0x87, 0x8b, 0x77, 0x0e, 0x85, 0x97, 0xac, 0x16,
+];
+
+/// A source file that sequence points refer to.
+#[derive(Debug, Clone)]
+pub struct Document<'a> {
+    /// Path of the source file, as it should be presented to a debugger.
+    pub name: Cow<'a, str>,
+    /// GUID of the source language, e.g. [`LANGUAGE_CSHARP`].
+    pub language: [u8; 16],
+    /// GUID of the algorithm used to compute `hash`, e.g. [`HASH_SHA256`], if the document is hashed.
+    pub hash_algorithm: Option<[u8; 16]>,
+    /// Checksum of the source file, used by debuggers to check that the source matches the binary.
+    pub hash: Cow<'a, [u8]>,
+}
+impl<'a> Document<'a> {
+    pub fn new(name: impl Into<Cow<'a, str>>, language: [u8; 16]) -> Self {
+        Self {
+            name: name.into(),
+            language,
+            hash_algorithm: None,
+            hash: Cow::Borrowed(&[]),
+        }
+    }
+}
+
+/// A range of source text, with 1-based lines and columns. The end is exclusive.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct SourceSpan {
+    pub start_line: u32,
+    pub start_column: u16,
+    pub end_line: u32,
+    pub end_column: u16,
+}
+
+/// Maps an instruction (and all following instructions up to the next sequence point) to a location in source.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SequencePoint {
+    /// Index of the instruction in [`body::Method::instructions`](super::body::Method::instructions).
+    pub instruction: usize,
+    pub document: DocumentIndex,
+    /// The source location, or `None` for a hidden sequence point that debuggers should step over.
+    pub span: Option<SourceSpan>,
+}
+impl SequencePoint {
+    pub const fn new(instruction: usize, document: DocumentIndex, span: SourceSpan) -> Self {
+        Self {
+            instruction,
+            document,
+            span: Some(span),
+        }
+    }
+
+    pub const fn hidden(instruction: usize, document: DocumentIndex) -> Self {
+        Self {
+            instruction,
+            document,
+            span: None,
+        }
+    }
+}
+
+/// The source name of a local variable.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LocalVariableName {
+    /// Index of the variable in [`body::Header::local_variables`](super::body::Header::local_variables).
+    pub index: usize,
+    pub name: String,
+    /// Whether debuggers should hide the variable, e.g. because it is a compiler-generated temporary.
+    pub debugger_hidden: bool,
+}
+impl LocalVariableName {
+    pub fn new(index: usize, name: impl Into<String>) -> Self {
+        Self {
+            index,
+            name: name.into(),
+            debugger_hidden: false,
+        }
+    }
+}
+
+/// A namespace, type or alias that source code in a scope refers to without qualification,
+/// e.g. through a C# `using` directive or a VB `Imports` statement.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Import {
+    /// Imports the types of a namespace, optionally only those defined in the given assembly,
+    /// or defines an alias for the namespace.
+    Namespace {
+        alias: Option<String>,
+        assembly: Option<AssemblyRefIndex>,
+        namespace: String,
+    },
+    /// Imports the members of a type, or defines an alias for the type.
+    /// Like other type tokens, a non-generic target doesn't record whether it is a value type.
+    Type { alias: Option<String>, target: MemberType },
+    /// Defines an alias for an XML namespace, as used by VB XML literals.
+    XmlNamespace { alias: String, namespace: String },
+    /// Defines an alias for an assembly reference, like a C# `extern alias`.
+    AssemblyAlias { alias: String, assembly: AssemblyRefIndex },
+    /// Imports an assembly alias defined by an enclosing import scope.
+    AssemblyAliasReference(String),
+}
+
+/// A set of imports, shared by the lexical scopes of all methods whose source is in it.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ImportScope {
+    /// The enclosing scope, whose imports are visible in this one too.
+    pub parent: Option<ImportScopeIndex>,
+    pub imports: Vec<Import>,
+}
+
+/// A lexical scope, i.e. a range of instructions in which a set of local variables is visible.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LocalScope {
+    /// Index of the first instruction in the scope.
+    pub start: usize,
+    /// Number of instructions in the scope.
+    pub length: usize,
+    pub variables: Vec<LocalVariableName>,
+    /// The imports in effect in the scope. Scopes without any are written with an empty import scope.
+    pub import_scope: Option<ImportScopeIndex>,
+}
+impl LocalScope {
+    pub const fn new(start: usize, length: usize, variables: Vec<LocalVariableName>) -> Self {
+        Self {
+            start,
+            length,
+            variables,
+            import_scope: None,
+        }
+    }
+}
+
+/// Debugging information attached to a method body, written to and read from portable PDBs.
+///
+/// All positions are instruction indices, like branch targets. When instructions are added to or removed from
+/// the body, call [`instructions_inserted`](Self::instructions_inserted) or
+/// [`instructions_removed`](Self::instructions_removed) to keep them in step.
+#[derive(Debug, Clone, Default)]
+pub struct MethodDebugInformation {
+    /// Sequence points, sorted by instruction index.
+    pub sequence_points: Vec<SequencePoint>,
+    /// Lexical scopes. Scopes may nest, but must not otherwise overlap.
+    pub scopes: Vec<LocalScope>,
+}
+impl MethodDebugInformation {
+    /// Shifts all positions after `count` instructions have been inserted at `index`.
+    /// Scopes that contain `index` (other than at their start) grow to include the new instructions.
+    pub fn instructions_inserted(&mut self, index: usize, count: usize) {
+        for p in &mut self.sequence_points {
+            if p.instruction >= index {
+                p.instruction += count;
+            }
+        }
+
+        for s in &mut self.scopes {
+            if s.start >= index {
+                s.start += count;
+            } else if s.start + s.length > index {
+                s.length += count;
+            }
+        }
+    }
+
+    /// Shifts all positions after the instructions in `range` have been removed.
+    /// Sequence points on removed instructions are dropped, as are scopes that no longer contain any instructions.
+    pub fn instructions_removed(&mut self, range: Range<usize>) {
+        let removed = range.len();
+        let shift = |i: usize| {
+            if i >= range.end {
+                i - removed
+            } else {
+                i.min(range.start)
+            }
+        };
+
+        self.sequence_points.retain(|p| !range.contains(&p.instruction));
+        for p in &mut self.sequence_points {
+            p.instruction = shift(p.instruction);
+        }
+
+        for s in &mut self.scopes {
+            let end = shift(s.start + s.length);
+            s.start = shift(s.start);
+            s.length = end - s.start;
+        }
+        self.scopes.retain(|s| s.length > 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(points: &[usize], scopes: &[(usize, usize)]) -> MethodDebugInformation {
+        MethodDebugInformation {
+            sequence_points: points
+                .iter()
+                .map(|&i| SequencePoint::hidden(i, DocumentIndex(0)))
+                .collect(),
+            scopes: scopes
+                .iter()
+                .map(|&(start, length)| LocalScope::new(start, length, vec![]))
+                .collect(),
+        }
+    }
+
+    fn positions(info: &MethodDebugInformation) -> (Vec<usize>, Vec<(usize, usize)>) {
+        (
+            info.sequence_points.iter().map(|p| p.instruction).collect(),
+            info.scopes.iter().map(|s| (s.start, s.length)).collect(),
+        )
+    }
+
+    #[test]
+    fn instructions_inserted() {
+        let mut debug = info(&[0, 2, 5], &[(0, 10), (0, 2), (2, 3), (5, 1)]);
+        debug.instructions_inserted(2, 3);
+        // scopes that end at the insertion point don't grow, and scopes that start at it move
+        assert_eq!(
+            positions(&debug),
+            (vec![0, 5, 8], vec![(0, 13), (0, 2), (5, 3), (8, 1)])
+        );
+
+        debug.instructions_inserted(0, 1);
+        assert_eq!(
+            positions(&debug),
+            (vec![1, 6, 9], vec![(1, 13), (1, 2), (6, 3), (9, 1)])
+        );
+    }
+
+    #[test]
+    fn instructions_removed() {
+        let mut debug = info(&[0, 3, 5, 7], &[(0, 10), (3, 2), (1, 3), (4, 4)]);
+        debug.instructions_removed(2..5);
+        // scopes are trimmed to the instructions they still contain, and dropped once they have none
+        assert_eq!(positions(&debug), (vec![0, 2, 4], vec![(0, 7), (1, 1), (2, 3)]));
+
+        debug.instructions_removed(0..7);
+        assert_eq!(positions(&debug), (vec![], vec![]));
+    }
+}
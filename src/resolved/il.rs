@@ -131,6 +131,62 @@ impl InstructionShow for Vec<u16> {
     }
 }
 
+/// Receives the metadata operands of an [`Instruction`] from [`Instruction::visit_operands`].
+///
+/// Every method does nothing by default, so implementors only need to override the operands they care about.
+pub trait OperandVisitor {
+    fn visit_type(&mut self, _operand: &mut MethodType) {}
+    fn visit_method(&mut self, _operand: &mut MethodSource) {}
+    fn visit_constructor(&mut self, _operand: &mut UserMethod) {}
+    fn visit_field(&mut self, _operand: &mut FieldSource) {}
+    fn visit_signature(&mut self, _operand: &mut signature::MaybeUnmanagedMethod<MethodType>) {}
+}
+
+trait InstructionOperand {
+    fn visit(&mut self, visitor: &mut impl OperandVisitor);
+}
+macro_rules! impl_operand {
+    ($($t:ty => $method:ident),*) => {
+        $(
+            impl InstructionOperand for $t {
+                fn visit(&mut self, visitor: &mut impl OperandVisitor) {
+                    visitor.$method(self);
+                }
+            }
+        )*
+    }
+}
+impl_operand!(
+    MethodType => visit_type,
+    MethodSource => visit_method,
+    UserMethod => visit_constructor,
+    FieldSource => visit_field,
+    signature::MaybeUnmanagedMethod<MethodType> => visit_signature
+);
+macro_rules! impl_no_operand {
+    ($($t:ty),*) => {
+        $(
+            impl InstructionOperand for $t {
+                fn visit(&mut self, _visitor: &mut impl OperandVisitor) {}
+            }
+        )*
+    }
+}
+impl_no_operand!(
+    NumberSign,
+    ConversionType,
+    LoadType,
+    StoreType,
+    u16,
+    i32,
+    i64,
+    f32,
+    f64,
+    usize,
+    Vec<usize>,
+    Vec<u16>
+);
+
 r_instructions! {
     Add,
     AddOverflow(NumberSign),
@@ -265,6 +321,26 @@ impl Instruction {
     pub fn load_string(s: impl AsRef<str>) -> Self {
         Instruction::LoadString(s.as_ref().encode_utf16().collect())
     }
+
+    /// The instruction indices that this instruction can transfer control to, from branches, `leave` and `switch`.
+    pub fn targets_mut(&mut self) -> Vec<&mut usize> {
+        use Instruction::*;
+
+        match self {
+            BranchEqual(t)
+            | BranchGreaterOrEqual(_, t)
+            | BranchGreater(_, t)
+            | BranchLessOrEqual(_, t)
+            | BranchLess(_, t)
+            | BranchNotEqual(t)
+            | Branch(t)
+            | BranchFalsy(t)
+            | BranchTruthy(t)
+            | Leave(t) => vec![t],
+            Switch(ts) => ts.iter_mut().collect(),
+            _ => vec![],
+        }
+    }
 }
 
 #[macro_export]
@@ -11,7 +11,10 @@ use dotnetdll_macros::From;
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter, Write};
 
-pub use crate::binary::signature::{encoded::NativeIntrinsic, kinds::MarshalSpec};
+pub use crate::binary::signature::{
+    encoded::NativeIntrinsic,
+    kinds::{InterfaceKind, MarshalSpec},
+};
 pub use dotnetdll_macros::{field_ref, method_ref};
 
 macro_rules! name_display {
@@ -599,7 +602,7 @@ impl<'a> PInvoke<'a> {
 
 /// Outlines the possible locations where an externally defined method could be, thus specifying the parent type for an [`ExternalMethodReference`].
 
-#[derive(Debug, Clone, From)]
+#[derive(Debug, Clone, From, Eq, PartialEq)]
 pub enum MethodReferenceParent {
     /// The method is part of a specific type (e.g., an instance method of a class or a static method).
     Type(MethodType),
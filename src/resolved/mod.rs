@@ -1,6 +1,8 @@
 pub mod assembly;
 pub mod attribute;
 pub mod body;
+pub mod builder;
+pub mod debug;
 pub mod generic;
 pub mod il;
 pub mod members;
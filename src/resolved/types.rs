@@ -874,7 +874,7 @@ impl From<MemberType> for MethodType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LocalVariable {
     TypedReference,
     Variable {
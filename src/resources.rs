@@ -0,0 +1,419 @@
+//! Reading and writing of `.resources` files, the format produced by .NET's `System.Resources.ResourceWriter` and
+//! consumed by `ResourceReader` and `ResourceManager`.
+//!
+//! These files are usually embedded in an assembly as a [`ManifestResource`](crate::resolved::resource::ManifestResource)
+//! named after the resource set, e.g. `strings.resources` for `new ResourceManager("strings", assembly)`:
+//!
+//! ```
+//! use dotnetdll::resources::{ResourceSet, Value};
+//!
+//! let mut set = ResourceSet::new();
+//! set.insert("greeting", Value::String("hello".into()));
+//! let bytes = set.write().unwrap();
+//!
+//! let read = ResourceSet::parse(&bytes).unwrap();
+//! assert_eq!(read.get("greeting"), Some(&Value::String("hello".into())));
+//! ```
+
+use crate::dll::{DLLError::*, Result};
+use scroll::Pread;
+use std::borrow::Cow;
+
+macro_rules! throw {
+    ($($arg:tt)*) => {
+        return Err(CLI(scroll::Error::Custom(format!($($arg)*))))
+    }
+}
+
+/// The first 4 bytes of every resource file.
+pub const MAGIC: u32 = 0xBEEF_CACE;
+pub const DEFAULT_READER_TYPE: &str =
+    "System.Resources.ResourceReader, mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089";
+pub const DEFAULT_SET_TYPE: &str = "System.Resources.RuntimeResourceSet";
+
+// first type code of values that are stored by their index in the type table
+const USER_TYPES: u32 = 0x40;
+
+/// The value of a resource, stored with one of the `ResourceTypeCode`s of the version 2 format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'a> {
+    Null,
+    String(Cow<'a, str>),
+    Boolean(bool),
+    /// A UTF-16 code unit.
+    Char(u16),
+    Byte(u8),
+    SByte(i8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Single(f32),
+    Double(f64),
+    /// A `System.Decimal`, as returned by `decimal.GetBits`.
+    Decimal([i32; 4]),
+    /// A `System.DateTime`, as returned by `DateTime.ToBinary`.
+    DateTime(i64),
+    /// A `System.TimeSpan`, in ticks.
+    TimeSpan(i64),
+    ByteArray(Cow<'a, [u8]>),
+    /// The contents of a `System.IO.Stream`, which `ResourceManager.GetStream` returns as an `UnmanagedMemoryStream`.
+    Stream(Cow<'a, [u8]>),
+    /// An object of any other type, serialized by `BinaryFormatter`.
+    /// The data is kept as it is, since it can only be deserialized with the type's assembly.
+    Serialized {
+        /// The assembly-qualified name of the type.
+        type_name: Cow<'a, str>,
+        data: Cow<'a, [u8]>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry<'a> {
+    pub name: Cow<'a, str>,
+    pub value: Value<'a>,
+}
+
+/// A set of named resources, i.e. the contents of a `.resources` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceSet<'a> {
+    /// The assembly-qualified name of the `IResourceReader` that reads the file, usually [`DEFAULT_READER_TYPE`].
+    pub reader_type: Cow<'a, str>,
+    /// The assembly-qualified name of the `ResourceSet` that `ResourceManager` creates, usually [`DEFAULT_SET_TYPE`].
+    pub set_type: Cow<'a, str>,
+    /// The resources, in the order they are stored in the file.
+    /// When writing, they are sorted by name like `ResourceWriter` does, so the order is not significant.
+    pub entries: Vec<Entry<'a>>,
+}
+
+impl Default for ResourceSet<'_> {
+    fn default() -> Self {
+        Self {
+            reader_type: DEFAULT_READER_TYPE.into(),
+            set_type: DEFAULT_SET_TYPE.into(),
+            entries: vec![],
+        }
+    }
+}
+
+/// The hash of resource names that `ResourceReader` uses to look up resources (`FastResourceComparer.HashFunction`).
+pub fn hash_name(name: &str) -> u32 {
+    name.encode_utf16()
+        .fold(5381_u32, |hash, c| ((hash << 5).wrapping_add(hash)) ^ u32::from(c))
+}
+
+fn read_7bit(bytes: &[u8], offset: &mut usize) -> Result<usize> {
+    let mut value = 0_usize;
+    for shift in (0..35).step_by(7) {
+        let b: u8 = bytes.gread_with(offset, scroll::LE)?;
+        value |= ((b & 0x7f) as usize) << shift;
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    throw!("bad 7-bit encoded integer at offset {:#x}", *offset)
+}
+
+fn read_bytes<'a>(bytes: &'a [u8], offset: &mut usize, len: usize) -> Result<&'a [u8]> {
+    match bytes.get(*offset..*offset + len) {
+        Some(b) => {
+            *offset += len;
+            Ok(b)
+        }
+        None => throw!("{} bytes at offset {:#x} are out of bounds", len, *offset),
+    }
+}
+
+// strings in the header and data section are UTF-8, prefixed by their length in bytes
+fn read_string<'a>(bytes: &'a [u8], offset: &mut usize) -> Result<&'a str> {
+    let len = read_7bit(bytes, offset)?;
+    let start = *offset;
+    match std::str::from_utf8(read_bytes(bytes, offset, len)?) {
+        Ok(s) => Ok(s),
+        Err(e) => throw!("invalid UTF-8 string at offset {:#x}: {}", start, e),
+    }
+}
+
+fn write_7bit(buf: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    write_7bit(buf, s.len());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn write_i32(buf: &mut Vec<u8>, value: usize) -> Result<()> {
+    match i32::try_from(value) {
+        Ok(v) => {
+            buf.extend_from_slice(&v.to_le_bytes());
+            Ok(())
+        }
+        Err(_) => throw!("{} is too large for a resource file", value),
+    }
+}
+
+impl<'a> ResourceSet<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of the resource with the given name, if there is one.
+    pub fn get(&self, name: &str) -> Option<&Value<'a>> {
+        self.entries.iter().find(|e| e.name == name).map(|e| &e.value)
+    }
+
+    /// Adds a resource, replacing the value of any existing resource with the same name.
+    pub fn insert(&mut self, name: impl Into<Cow<'a, str>>, value: Value<'a>) {
+        let name = name.into();
+        match self.entries.iter_mut().find(|e| e.name == name) {
+            Some(e) => e.value = value,
+            None => self.entries.push(Entry { name, value }),
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        let offset = &mut 0;
+
+        let magic: u32 = bytes.gread_with(offset, scroll::LE)?;
+        if magic != MAGIC {
+            throw!("invalid resource file magic number {:#010x}", magic);
+        }
+        let header_version: u32 = bytes.gread_with(offset, scroll::LE)?;
+        let header_size: u32 = bytes.gread_with(offset, scroll::LE)?;
+        let header_end = *offset + header_size as usize;
+        // like ResourceManager, skip headers from later versions, which only their own readers know how to use
+        let (reader_type, set_type) = if header_version > 1 {
+            (DEFAULT_READER_TYPE, DEFAULT_SET_TYPE)
+        } else {
+            (read_string(bytes, offset)?, read_string(bytes, offset)?)
+        };
+        *offset = header_end;
+
+        let version: u32 = bytes.gread_with(offset, scroll::LE)?;
+        if version != 2 {
+            throw!("unsupported resource file version {}", version);
+        }
+        let count: u32 = bytes.gread_with(offset, scroll::LE)?;
+        let type_count: u32 = bytes.gread_with(offset, scroll::LE)?;
+        let types = (0..type_count)
+            .map(|_| read_string(bytes, offset))
+            .collect::<Result<Vec<_>>>()?;
+
+        *offset += (8 - *offset % 8) % 8;
+        // the hashes are only needed for lookups by the runtime
+        *offset += 4 * count as usize;
+        let name_positions = (0..count)
+            .map(|_| bytes.gread_with::<u32>(offset, scroll::LE).map(|p| p as usize))
+            .collect::<scroll::Result<Vec<_>>>()?;
+        let data_section: u32 = bytes.gread_with(offset, scroll::LE)?;
+        let (names_start, data_section) = (*offset, data_section as usize);
+
+        let mut entries = name_positions
+            .into_iter()
+            .map(|position| {
+                let offset = &mut (names_start + position);
+                let len = read_7bit(bytes, offset)?;
+                let name_bytes = read_bytes(bytes, offset, len)?;
+                let name: Vec<_> = name_bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                let Ok(name) = String::from_utf16(&name) else {
+                    throw!("invalid UTF-16 resource name at offset {:#x}", names_start + position)
+                };
+                let data: u32 = bytes.gread_with(offset, scroll::LE)?;
+                Ok((position, name, data_section + data as usize))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // read in the order of the name section, which is the order the resources were written in
+        entries.sort_by_key(|&(position, ..)| position);
+
+        // serialized objects aren't length-prefixed, so they extend up to the next value
+        let mut data_offsets: Vec<_> = entries.iter().map(|&(_, _, data)| data).collect();
+        data_offsets.sort_unstable();
+
+        let entries = entries
+            .into_iter()
+            .map(|(_, name, data)| {
+                let mut position = data;
+                let offset = &mut position;
+                let type_code = read_7bit(bytes, offset)? as u32;
+
+                macro_rules! read {
+                    ($t:ty) => {
+                        bytes.gread_with::<$t>(offset, scroll::LE)?
+                    };
+                }
+
+                let value = match type_code {
+                    0 => Value::Null,
+                    1 => Value::String(read_string(bytes, offset)?.into()),
+                    2 => Value::Boolean(read!(u8) != 0),
+                    3 => Value::Char(read!(u16)),
+                    4 => Value::Byte(read!(u8)),
+                    5 => Value::SByte(read!(i8)),
+                    6 => Value::Int16(read!(i16)),
+                    7 => Value::UInt16(read!(u16)),
+                    8 => Value::Int32(read!(i32)),
+                    9 => Value::UInt32(read!(u32)),
+                    0xa => Value::Int64(read!(i64)),
+                    0xb => Value::UInt64(read!(u64)),
+                    0xc => Value::Single(read!(f32)),
+                    0xd => Value::Double(read!(f64)),
+                    0xe => Value::Decimal([read!(i32), read!(i32), read!(i32), read!(i32)]),
+                    0xf => Value::DateTime(read!(i64)),
+                    0x10 => Value::TimeSpan(read!(i64)),
+                    0x20 | 0x21 => {
+                        let len = read!(u32) as usize;
+                        let data = read_bytes(bytes, offset, len)?.into();
+                        if type_code == 0x20 {
+                            Value::ByteArray(data)
+                        } else {
+                            Value::Stream(data)
+                        }
+                    }
+                    USER_TYPES.. => {
+                        let Some(&type_name) = types.get((type_code - USER_TYPES) as usize) else {
+                            throw!("invalid type index {} for resource {}", type_code - USER_TYPES, name)
+                        };
+                        let end = data_offsets.iter().find(|&&o| o > data).copied().unwrap_or(bytes.len());
+                        let Some(data) = bytes.get(*offset..end) else {
+                            throw!("invalid data offset {:#x} for resource {}", data, name)
+                        };
+                        Value::Serialized {
+                            type_name: type_name.into(),
+                            data: data.into(),
+                        }
+                    }
+                    _ => throw!("invalid type code {:#x} for resource {}", type_code, name),
+                };
+
+                Ok(Entry {
+                    name: name.into(),
+                    value,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            reader_type: reader_type.into(),
+            set_type: set_type.into(),
+            entries,
+        })
+    }
+
+    /// Writes the resources in the same layout as `ResourceWriter`.
+    pub fn write(&self) -> Result<Vec<u8>> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.name.encode_utf16().cmp(b.name.encode_utf16()));
+        if let Some(w) = entries.windows(2).find(|w| w[0].name == w[1].name) {
+            throw!("duplicate resource name {}", w[0].name);
+        }
+
+        let mut types: Vec<&str> = vec![];
+        let mut names = vec![];
+        let mut data = vec![];
+        let mut hashes = vec![];
+        for Entry { name, value } in entries {
+            hashes.push((hash_name(name) as i32, names.len()));
+
+            let utf16: Vec<_> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            write_7bit(&mut names, utf16.len());
+            names.extend(utf16);
+            write_i32(&mut names, data.len())?;
+
+            macro_rules! value {
+                ($code:literal, $($val:expr),+) => {{
+                    write_7bit(&mut data, $code);
+                    $(
+                        data.extend_from_slice(&$val.to_le_bytes());
+                    )+
+                }};
+            }
+
+            match value {
+                Value::Null => write_7bit(&mut data, 0),
+                Value::String(s) => {
+                    write_7bit(&mut data, 1);
+                    write_string(&mut data, s);
+                }
+                Value::Boolean(b) => value!(2, u8::from(*b)),
+                Value::Char(c) => value!(3, c),
+                Value::Byte(b) => value!(4, b),
+                Value::SByte(b) => value!(5, b),
+                Value::Int16(i) => value!(6, i),
+                Value::UInt16(i) => value!(7, i),
+                Value::Int32(i) => value!(8, i),
+                Value::UInt32(i) => value!(9, i),
+                Value::Int64(i) => value!(0xa, i),
+                Value::UInt64(i) => value!(0xb, i),
+                Value::Single(f) => value!(0xc, f),
+                Value::Double(f) => value!(0xd, f),
+                Value::Decimal([lo, mid, hi, flags]) => value!(0xe, lo, mid, hi, flags),
+                Value::DateTime(d) => value!(0xf, d),
+                Value::TimeSpan(t) => value!(0x10, t),
+                Value::ByteArray(b) | Value::Stream(b) => {
+                    write_7bit(
+                        &mut data,
+                        if matches!(value, Value::ByteArray(_)) {
+                            0x20
+                        } else {
+                            0x21
+                        },
+                    );
+                    write_i32(&mut data, b.len())?;
+                    data.extend_from_slice(b);
+                }
+                Value::Serialized { type_name, data: d } => {
+                    let index = types.iter().position(|&t| t == type_name.as_ref()).unwrap_or_else(|| {
+                        types.push(type_name);
+                        types.len() - 1
+                    });
+                    write_7bit(&mut data, USER_TYPES as usize + index);
+                    data.extend_from_slice(d);
+                }
+            }
+        }
+        hashes.sort_by_key(|&(hash, _)| hash);
+
+        let mut header = vec![];
+        write_string(&mut header, &self.reader_type);
+        write_string(&mut header, &self.set_type);
+
+        let mut buf = vec![];
+        buf.extend_from_slice(&MAGIC.to_le_bytes());
+        buf.extend_from_slice(&1_u32.to_le_bytes());
+        write_i32(&mut buf, header.len())?;
+        buf.extend(header);
+
+        buf.extend_from_slice(&2_u32.to_le_bytes());
+        write_i32(&mut buf, hashes.len())?;
+        write_i32(&mut buf, types.len())?;
+        for t in types {
+            write_string(&mut buf, t);
+        }
+        let padding = (8 - buf.len() % 8) % 8;
+        buf.extend(b"PAD".iter().cycle().take(padding));
+
+        for &(hash, _) in &hashes {
+            buf.extend_from_slice(&hash.to_le_bytes());
+        }
+        for &(_, position) in &hashes {
+            write_i32(&mut buf, position)?;
+        }
+        let data_section = buf.len() + 4 + names.len();
+        write_i32(&mut buf, data_section)?;
+        buf.extend(names);
+        buf.extend(data);
+
+        Ok(buf)
+    }
+}
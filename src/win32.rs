@@ -0,0 +1,953 @@
+//! Win32 resources, which are stored in the `.rsrc` section of a PE image and read by Windows itself rather than the CLI.
+//!
+//! Resources are identified by a type, a name and a language, and form the three levels of the resource directory.
+//! Besides storing raw resources, this module supports the types that .NET compilers emit:
+//! version information ([`VersionInfo`]), application manifests and icons ([`IconGroup`]).
+//!
+//! ```
+//! use dotnetdll::prelude::*;
+//! use dotnetdll::win32::VersionInfo;
+//!
+//! let mut res = Resolution::new(Module::new("Example.dll"));
+//! let mut assembly = Assembly::new("Example");
+//! assembly.version = Version { major: 1, minor: 2, build: 3, revision: 0 };
+//! res.assembly = Some(assembly);
+//!
+//! // the file version shown by Explorer
+//! let info = VersionInfo::from_resolution(&res);
+//! res.win32_resources.set_version_info(&info).unwrap();
+//! ```
+
+use crate::{
+    binary::signature::attribute::SerString,
+    dll::{DLLError::*, Result},
+    resolution::Resolution,
+    resolved::{
+        assembly::Version,
+        members::{MethodReferenceParent, UserMethod},
+        types::{BaseType, MethodType, TypeSource},
+    },
+};
+use object::{
+    pe,
+    read::pe::{ResourceDirectory, ResourceDirectoryEntryData, ResourceNameOrId},
+    LittleEndian as LE,
+};
+use scroll::Pread;
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+
+macro_rules! throw {
+    ($($arg:tt)*) => {
+        return Err(CLI(scroll::Error::Custom(format!($($arg)*))))
+    }
+}
+
+pub const RT_ICON: u16 = 3;
+pub const RT_GROUP_ICON: u16 = 14;
+pub const RT_VERSION: u16 = 16;
+pub const RT_MANIFEST: u16 = 24;
+
+pub const LANGUAGE_NEUTRAL: u16 = 0;
+/// The manifest ID that Windows reads when starting an executable.
+pub const CREATEPROCESS_MANIFEST_RESOURCE_ID: u16 = 1;
+/// The manifest ID that Windows reads when loading a DLL.
+pub const ISOLATIONAWARE_MANIFEST_RESOURCE_ID: u16 = 2;
+
+/// The type, name or language of a resource.
+///
+/// Named identifiers are ordered before numeric ones, as they are in the resource directory.
+/// Names are compared case-insensitively, like `rc` and `FindResource` do, so `"Icon"` and `"ICON"` are the same name.
+#[derive(Debug, Clone)]
+pub enum ResourceId<'a> {
+    Name(Cow<'a, str>),
+    Id(u16),
+}
+
+impl ResourceId<'_> {
+    // names are sorted by their UTF-16 code units in the directory
+    fn upper_case(name: &str) -> Vec<u16> {
+        name.to_uppercase().encode_utf16().collect()
+    }
+}
+
+impl Ord for ResourceId<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (ResourceId::Name(a), ResourceId::Name(b)) => Self::upper_case(a).cmp(&Self::upper_case(b)),
+            (ResourceId::Name(_), ResourceId::Id(_)) => Ordering::Less,
+            (ResourceId::Id(_), ResourceId::Name(_)) => Ordering::Greater,
+            (ResourceId::Id(a), ResourceId::Id(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl PartialOrd for ResourceId<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ResourceId<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ResourceId<'_> {}
+
+impl Hash for ResourceId<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            ResourceId::Name(n) => Self::upper_case(n).hash(state),
+            ResourceId::Id(i) => i.hash(state),
+        }
+    }
+}
+
+impl From<u16> for ResourceId<'_> {
+    fn from(id: u16) -> Self {
+        ResourceId::Id(id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resource<'a> {
+    /// The resource type, e.g. [`RT_VERSION`].
+    pub kind: ResourceId<'a>,
+    pub name: ResourceId<'a>,
+    pub language: u16,
+    /// The code page of any text in the data, which is usually 0.
+    pub code_page: u32,
+    pub data: Cow<'a, [u8]>,
+}
+
+/// The resources of an image, in the order they are stored in the resource directory.
+///
+/// When writing, resources are sorted by type, name and language as the directory requires,
+/// so the order of [`entries`](Self::entries) is not significant.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resources<'a> {
+    pub entries: Vec<Resource<'a>>,
+}
+
+fn read_id(directory: ResourceDirectory, id: &ResourceNameOrId) -> Result<ResourceId<'static>> {
+    Ok(match *id {
+        ResourceNameOrId::Name(n) => {
+            let chars: Vec<_> = n.data(directory)?.iter().map(|c| c.get(LE)).collect();
+            match String::from_utf16(&chars) {
+                Ok(s) => ResourceId::Name(s.into()),
+                Err(e) => throw!("invalid resource name: {}", e),
+            }
+        }
+        ResourceNameOrId::Id(i) => ResourceId::Id(i),
+    })
+}
+
+// resources grouped by type, then by name, for laying out the directory levels
+type Names<'r, 'a> = Vec<(&'r ResourceId<'a>, Vec<&'r Resource<'a>>)>;
+
+fn align(buf: &mut Vec<u8>, to: usize) {
+    buf.resize(buf.len().next_multiple_of(to), 0);
+}
+
+fn write_directory_table(buf: &mut Vec<u8>, entries: &[(u32, u32)], named: usize) {
+    buf.extend([0; 12]);
+    buf.extend((named as u16).to_le_bytes());
+    buf.extend(((entries.len() - named) as u16).to_le_bytes());
+    for (name, data) in entries {
+        buf.extend(name.to_le_bytes());
+        buf.extend(data.to_le_bytes());
+    }
+}
+
+impl<'a> Resources<'a> {
+    /// Reads a resource directory from the data of the section that contains it, starting at the directory.
+    /// `rva` is the address of the directory, which the addresses of resource data are relative to.
+    pub fn parse(section: &'a [u8], rva: u32) -> Result<Self> {
+        let directory = ResourceDirectory::new(section);
+        let mut entries = vec![];
+
+        // the directory has a fixed depth of type, name and language
+        macro_rules! table {
+            ($entry:expr) => {
+                match $entry.data(directory)? {
+                    ResourceDirectoryEntryData::Table(t) => t,
+                    ResourceDirectoryEntryData::Data(_) => throw!("resource directory is missing a level"),
+                }
+            };
+        }
+
+        for kind_entry in directory.root()?.entries {
+            let kind = read_id(directory, &kind_entry.name_or_id())?;
+            for name_entry in table!(kind_entry).entries {
+                let name = read_id(directory, &name_entry.name_or_id())?;
+                for language_entry in table!(name_entry).entries {
+                    let ResourceNameOrId::Id(language) = language_entry.name_or_id() else {
+                        throw!("named resource language for resource {:?} of type {:?}", name, kind)
+                    };
+                    let ResourceDirectoryEntryData::Data(data) = language_entry.data(directory)? else {
+                        throw!(
+                            "resource directory is too deep for resource {:?} of type {:?}",
+                            name,
+                            kind
+                        )
+                    };
+
+                    let (data_rva, size) = (data.offset_to_data.get(LE), data.size.get(LE) as usize);
+                    let Some(data_bytes) = data_rva
+                        .checked_sub(rva)
+                        .and_then(|offset| section.get(offset as usize..offset as usize + size))
+                    else {
+                        throw!(
+                            "invalid data RVA {:#x} for resource {:?} of type {:?}",
+                            data_rva,
+                            name,
+                            kind
+                        )
+                    };
+
+                    entries.push(Resource {
+                        kind: kind.clone(),
+                        name: name.clone(),
+                        language,
+                        code_page: data.code_page.get(LE),
+                        data: data_bytes.into(),
+                    });
+                }
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Writes the resource directory and data, as it will be placed at `rva`.
+    #[allow(clippy::too_many_lines)]
+    pub fn write(&self, rva: u32) -> Result<Vec<u8>> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|a, b| (&a.kind, &a.name, a.language).cmp(&(&b.kind, &b.name, b.language)));
+        if let Some(w) = entries
+            .windows(2)
+            .find(|w| (&w[0].kind, &w[0].name, w[0].language) == (&w[1].kind, &w[1].name, w[1].language))
+        {
+            throw!(
+                "duplicate resource {:?} of type {:?} and language {:#x}",
+                w[0].name,
+                w[0].kind,
+                w[0].language
+            );
+        }
+
+        let mut tree: Vec<(&ResourceId, Names)> = vec![];
+        for r in entries {
+            match tree.last_mut() {
+                Some((kind, names)) if *kind == &r.kind => match names.last_mut() {
+                    Some((name, languages)) if *name == &r.name => languages.push(r),
+                    _ => names.push((&r.name, vec![r])),
+                },
+                _ => tree.push((&r.kind, vec![(&r.name, vec![r])])),
+            }
+        }
+
+        // all tables come first, level by level, followed by the data entries, names and data
+        let table_size = |len: usize| 16 + 8 * len;
+        let name_tables_start = table_size(tree.len());
+        let language_tables_start = name_tables_start + tree.iter().map(|(_, n)| table_size(n.len())).sum::<usize>();
+        let data_entries_start = language_tables_start
+            + tree
+                .iter()
+                .flat_map(|(_, n)| n.iter().map(|(_, l)| table_size(l.len())))
+                .sum::<usize>();
+        let strings_start = data_entries_start + 16 * self.entries.len();
+
+        let mut tables = vec![];
+        let mut strings = vec![];
+        let mut string_entry = |id: &ResourceId| match id {
+            ResourceId::Name(n) => {
+                let offset = strings_start + strings.len();
+                let chars: Vec<_> = n.encode_utf16().collect();
+                strings.extend((chars.len() as u16).to_le_bytes());
+                strings.extend(chars.into_iter().flat_map(u16::to_le_bytes));
+                offset as u32 | pe::IMAGE_RESOURCE_NAME_IS_STRING
+            }
+            ResourceId::Id(i) => u32::from(*i),
+        };
+        let named =
+            |ids: &mut dyn Iterator<Item = &&ResourceId>| ids.filter(|i| matches!(i, ResourceId::Name(_))).count();
+
+        let mut next_table = name_tables_start;
+        let root: Vec<_> = tree
+            .iter()
+            .map(|(kind, names)| {
+                let offset = next_table;
+                next_table += table_size(names.len());
+                (string_entry(kind), offset as u32 | pe::IMAGE_RESOURCE_DATA_IS_DIRECTORY)
+            })
+            .collect();
+        write_directory_table(&mut tables, &root, named(&mut tree.iter().map(|(k, _)| k)));
+
+        for (_, names) in &tree {
+            let table: Vec<_> = names
+                .iter()
+                .map(|(name, languages)| {
+                    let offset = next_table;
+                    next_table += table_size(languages.len());
+                    (string_entry(name), offset as u32 | pe::IMAGE_RESOURCE_DATA_IS_DIRECTORY)
+                })
+                .collect();
+            write_directory_table(&mut tables, &table, named(&mut names.iter().map(|(n, _)| n)));
+        }
+
+        let mut next_entry = data_entries_start;
+        let mut resources = vec![];
+        for (_, names) in &tree {
+            for (_, languages) in names {
+                let table: Vec<_> = languages
+                    .iter()
+                    .map(|r| {
+                        resources.push(*r);
+                        next_entry += 16;
+                        (u32::from(r.language), (next_entry - 16) as u32)
+                    })
+                    .collect();
+                write_directory_table(&mut tables, &table, 0);
+            }
+        }
+
+        let mut data_entries = vec![];
+        let mut data = vec![];
+        let data_start = (strings_start + strings.len()).next_multiple_of(8);
+        for r in resources {
+            let Ok(size) = u32::try_from(r.data.len()) else {
+                throw!("resource {:?} of type {:?} is too large", r.name, r.kind)
+            };
+            data_entries.extend((rva + (data_start + data.len()) as u32).to_le_bytes());
+            data_entries.extend(size.to_le_bytes());
+            data_entries.extend(r.code_page.to_le_bytes());
+            data_entries.extend([0; 4]);
+            data.extend_from_slice(&r.data);
+            align(&mut data, 8);
+        }
+
+        let mut buf = tables;
+        buf.extend(data_entries);
+        buf.extend(strings);
+        align(&mut buf, 8);
+        buf.extend(data);
+        Ok(buf)
+    }
+
+    /// Returns the first resource with the given type and name, in any language.
+    pub fn get(&self, kind: &ResourceId, name: &ResourceId) -> Option<&Resource<'a>> {
+        self.entries.iter().find(|r| &r.kind == kind && &r.name == name)
+    }
+
+    /// Adds a resource, replacing any existing resource with the same type, name and language.
+    pub fn insert(&mut self, resource: Resource<'a>) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|r| r.kind == resource.kind && r.name == resource.name && r.language == resource.language)
+        {
+            Some(r) => *r = resource,
+            None => self.entries.push(resource),
+        }
+    }
+
+    /// Returns the version information of the image, if it has any.
+    pub fn version_info(&self) -> Result<Option<VersionInfo>> {
+        self.entries
+            .iter()
+            .find(|r| r.kind == ResourceId::Id(RT_VERSION))
+            .map(|r| VersionInfo::parse(&r.data))
+            .transpose()
+    }
+
+    /// Sets the version information of the image, replacing any existing version resources.
+    pub fn set_version_info(&mut self, info: &VersionInfo) -> Result<()> {
+        let data = info.write()?;
+        self.entries.retain(|r| r.kind != ResourceId::Id(RT_VERSION));
+        self.entries.push(Resource {
+            kind: RT_VERSION.into(),
+            name: 1.into(),
+            language: LANGUAGE_NEUTRAL,
+            code_page: 0,
+            data: data.into(),
+        });
+        Ok(())
+    }
+
+    /// Returns the XML of the application manifest, if the image has one.
+    pub fn manifest(&self) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|r| r.kind == ResourceId::Id(RT_MANIFEST))
+            .map(|r| r.data.as_ref())
+    }
+
+    /// Sets the application manifest, replacing any existing manifests.
+    /// `id` is usually [`CREATEPROCESS_MANIFEST_RESOURCE_ID`] for executables and
+    /// [`ISOLATIONAWARE_MANIFEST_RESOURCE_ID`] for DLLs.
+    pub fn set_manifest(&mut self, id: u16, xml: impl Into<Cow<'a, [u8]>>) {
+        self.entries.retain(|r| r.kind != ResourceId::Id(RT_MANIFEST));
+        self.entries.push(Resource {
+            kind: RT_MANIFEST.into(),
+            name: id.into(),
+            language: LANGUAGE_NEUTRAL,
+            code_page: 0,
+            data: xml.into(),
+        });
+    }
+
+    /// Returns the icon groups of the image along with their names, combining each group with its icon images.
+    pub fn icon_groups(&self) -> Result<Vec<(&ResourceId<'a>, IconGroup<'a>)>> {
+        self.entries
+            .iter()
+            .filter(|r| r.kind == ResourceId::Id(RT_GROUP_ICON))
+            .map(|r| {
+                let group = IconGroup::parse_group(&r.data, |id| {
+                    let icon = self
+                        .entries
+                        .iter()
+                        .filter(|i| i.kind == ResourceId::Id(RT_ICON) && i.name == ResourceId::Id(id))
+                        .min_by_key(|i| i.language != r.language);
+                    match icon {
+                        Some(i) => Ok(i.data.clone()),
+                        None => throw!("missing icon {} for icon group {:?}", id, r.name),
+                    }
+                })?;
+                Ok((&r.name, group))
+            })
+            .collect()
+    }
+
+    /// Adds an icon group and its icon images, which are given IDs after those of any existing icons.
+    ///
+    /// The first icon group of an executable is the icon that Explorer shows for it.
+    pub fn add_icon_group(&mut self, name: ResourceId<'a>, group: IconGroup<'a>) -> Result<()> {
+        let first_id = self
+            .entries
+            .iter()
+            .filter_map(|r| match r.name {
+                ResourceId::Id(i) if r.kind == ResourceId::Id(RT_ICON) => Some(i),
+                _ => None,
+            })
+            .max()
+            .map_or(1, |i| i + 1);
+        if first_id as usize + group.icons.len() > u16::MAX as usize {
+            throw!("too many icons");
+        }
+
+        let mut directory = icon_header(group.icons.len());
+        for (icon, id) in group.icons.into_iter().zip(first_id..) {
+            directory.extend(icon.entry_header()?);
+            directory.extend(id.to_le_bytes());
+            self.entries.push(Resource {
+                kind: RT_ICON.into(),
+                name: id.into(),
+                language: LANGUAGE_NEUTRAL,
+                code_page: 0,
+                data: icon.data,
+            });
+        }
+        self.insert(Resource {
+            kind: RT_GROUP_ICON.into(),
+            name,
+            language: LANGUAGE_NEUTRAL,
+            code_page: 0,
+            data: directory.into(),
+        });
+        Ok(())
+    }
+}
+
+/// One image of an icon, in any size and color depth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Icon<'a> {
+    /// Width in pixels, where 0 means 256.
+    pub width: u8,
+    /// Height in pixels, where 0 means 256.
+    pub height: u8,
+    /// Number of colors in the palette, or 0 if the image doesn't use one.
+    pub color_count: u8,
+    pub planes: u16,
+    pub bit_count: u16,
+    /// The image, either as a BMP without its file header or as a PNG.
+    pub data: Cow<'a, [u8]>,
+}
+
+impl Icon<'_> {
+    // the fields shared by the entries of .ico files and icon group resources
+    fn entry_header(&self) -> Result<[u8; 12]> {
+        let Ok(size) = u32::try_from(self.data.len()) else {
+            throw!("icon image is too large")
+        };
+        let mut header = [0; 12];
+        header[..4].copy_from_slice(&[self.width, self.height, self.color_count, 0]);
+        header[4..6].copy_from_slice(&self.planes.to_le_bytes());
+        header[6..8].copy_from_slice(&self.bit_count.to_le_bytes());
+        header[8..].copy_from_slice(&size.to_le_bytes());
+        Ok(header)
+    }
+}
+
+/// A set of images of the same icon, like an `.ico` file. Stored as an [`RT_GROUP_ICON`] resource that refers to
+/// an [`RT_ICON`] resource for each image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IconGroup<'a> {
+    pub icons: Vec<Icon<'a>>,
+}
+
+fn icon_header(count: usize) -> Vec<u8> {
+    let mut header = vec![0, 0, 1, 0];
+    header.extend((count as u16).to_le_bytes());
+    header
+}
+
+impl<'a> IconGroup<'a> {
+    // both .ico files and icon groups are a header and a list of entries, which only differ in how they find the image
+    fn parse_entries(
+        bytes: &[u8],
+        entry_size: usize,
+        mut image: impl FnMut(&[u8]) -> Result<Cow<'a, [u8]>>,
+    ) -> Result<Self> {
+        let offset = &mut 0;
+        let reserved: u16 = bytes.gread_with(offset, scroll::LE)?;
+        let kind: u16 = bytes.gread_with(offset, scroll::LE)?;
+        if reserved != 0 || kind != 1 {
+            throw!("invalid icon directory header");
+        }
+        let count: u16 = bytes.gread_with(offset, scroll::LE)?;
+
+        let icons = (0..count)
+            .map(|_| {
+                let Some(entry) = bytes.get(*offset..*offset + entry_size) else {
+                    throw!("icon directory entry at offset {:#x} is out of bounds", *offset)
+                };
+                *offset += entry_size;
+
+                Ok(Icon {
+                    width: entry[0],
+                    height: entry[1],
+                    color_count: entry[2],
+                    planes: entry.pread_with(4, scroll::LE)?,
+                    bit_count: entry.pread_with(6, scroll::LE)?,
+                    data: image(entry)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { icons })
+    }
+
+    fn parse_group(bytes: &[u8], mut icon: impl FnMut(u16) -> Result<Cow<'a, [u8]>>) -> Result<Self> {
+        Self::parse_entries(bytes, 14, |entry| icon(entry.pread_with(12, scroll::LE)?))
+    }
+
+    /// Reads the images of an `.ico` file.
+    pub fn from_ico(bytes: &'a [u8]) -> Result<Self> {
+        Self::parse_entries(bytes, 16, |entry| {
+            let size: u32 = entry.pread_with(8, scroll::LE)?;
+            let offset: u32 = entry.pread_with(12, scroll::LE)?;
+            match bytes.get(offset as usize..offset as usize + size as usize) {
+                Some(data) => Ok(data.into()),
+                None => throw!("icon image at offset {:#x} is out of bounds", offset),
+            }
+        })
+    }
+
+    /// Writes the images as an `.ico` file.
+    pub fn to_ico(&self) -> Result<Vec<u8>> {
+        let mut buf = icon_header(self.icons.len());
+        let mut offset = buf.len() + 16 * self.icons.len();
+        for icon in &self.icons {
+            buf.extend(icon.entry_header()?);
+            buf.extend((offset as u32).to_le_bytes());
+            offset += icon.data.len();
+        }
+        for icon in &self.icons {
+            buf.extend_from_slice(&icon.data);
+        }
+        Ok(buf)
+    }
+}
+
+pub const VS_FF_DEBUG: u32 = 0x01;
+pub const VS_FF_PRERELEASE: u32 = 0x02;
+pub const VS_FF_PATCHED: u32 = 0x04;
+pub const VS_FF_PRIVATEBUILD: u32 = 0x08;
+pub const VS_FF_INFOINFERRED: u32 = 0x10;
+pub const VS_FF_SPECIALBUILD: u32 = 0x20;
+
+pub const VOS_WINDOWS32: u32 = 0x0000_0004;
+pub const VOS_NT_WINDOWS32: u32 = 0x0004_0004;
+
+pub const VFT_UNKNOWN: u32 = 0;
+pub const VFT_APP: u32 = 1;
+pub const VFT_DLL: u32 = 2;
+
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF_04BD;
+// the code page for UTF-16, which all .NET compilers use for their string tables
+const UNICODE_CODE_PAGE: u16 = 1200;
+
+/// The strings of a version resource in one language, such as `FileDescription` and `CompanyName`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringTable {
+    pub language: u16,
+    pub code_page: u16,
+    /// Pairs of keys and values, in the order they are stored.
+    pub strings: Vec<(String, String)>,
+}
+
+impl StringTable {
+    /// Returns the value of a string, e.g. `"ProductName"`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.strings.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+/// The contents of an [`RT_VERSION`] resource (`VS_VERSIONINFO`), which Explorer shows in a file's properties.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub file_version: Version,
+    pub product_version: Version,
+    /// Which bits of `file_flags` are valid.
+    pub file_flags_mask: u32,
+    /// A combination of the `VS_FF_*` flags.
+    pub file_flags: u32,
+    /// The operating system the file was designed for, e.g. [`VOS_WINDOWS32`].
+    pub file_os: u32,
+    /// The general type of the file, e.g. [`VFT_DLL`].
+    pub file_type: u32,
+    pub file_subtype: u32,
+    pub file_date: u64,
+    pub string_tables: Vec<StringTable>,
+    /// The pairs of language and code page that the file supports, from the `Translation` value.
+    pub translations: Vec<(u16, u16)>,
+}
+
+// a node of the VS_VERSIONINFO tree: every structure in it has a length, a key, a value and children
+struct Block<'b> {
+    key: String,
+    text: bool,
+    value: &'b [u8],
+    children: Vec<Block<'b>>,
+}
+
+fn read_block<'b>(bytes: &'b [u8], offset: &mut usize) -> Result<Block<'b>> {
+    let start = *offset;
+    let length: u16 = bytes.gread_with(offset, scroll::LE)?;
+    let value_length: u16 = bytes.gread_with(offset, scroll::LE)?;
+    let kind: u16 = bytes.gread_with(offset, scroll::LE)?;
+    let Some(bytes) = bytes.get(..start + length as usize) else {
+        throw!("version resource block at offset {:#x} is out of bounds", start)
+    };
+
+    let mut key = vec![];
+    loop {
+        match bytes.gread_with::<u16>(offset, scroll::LE)? {
+            0 => break,
+            c => key.push(c),
+        }
+    }
+    let Ok(key) = String::from_utf16(&key) else {
+        throw!("invalid version resource key at offset {:#x}", start)
+    };
+    *offset = offset.next_multiple_of(4);
+
+    // the value length of text is in characters, but some compilers use bytes, so only trust it for binary values
+    let text = kind == 1;
+    let value_end = if text {
+        (*offset + 2 * value_length as usize).min(bytes.len())
+    } else {
+        *offset + value_length as usize
+    };
+    let Some(value) = bytes.get(*offset..value_end) else {
+        throw!("version resource value at offset {:#x} is out of bounds", *offset)
+    };
+    *offset = value_end.next_multiple_of(4);
+
+    let mut children = vec![];
+    while *offset < bytes.len() {
+        children.push(read_block(bytes, offset)?);
+        *offset = offset.next_multiple_of(4);
+    }
+
+    Ok(Block {
+        key,
+        text,
+        value,
+        children,
+    })
+}
+
+// lengths are 16-bit, so a block can't be larger than 64 KiB
+fn write_block(key: &str, value: &[u8], value_length: usize, text: bool, children: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let Ok(value_length) = u16::try_from(value_length) else {
+        throw!("value of version resource block {} is too large", key)
+    };
+    let mut buf = vec![0; 6];
+    buf[2..4].copy_from_slice(&value_length.to_le_bytes());
+    buf[4..6].copy_from_slice(&u16::from(text).to_le_bytes());
+    buf.extend(key.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+    align(&mut buf, 4);
+    buf.extend_from_slice(value);
+    for c in children {
+        align(&mut buf, 4);
+        buf.extend(c);
+    }
+    let Ok(length) = u16::try_from(buf.len()) else {
+        throw!("version resource block {} is too large", key)
+    };
+    buf[..2].copy_from_slice(&length.to_le_bytes());
+    Ok(buf)
+}
+
+fn text_value(value: &[u8]) -> String {
+    let chars: Vec<_> = value
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0)
+        .collect();
+    String::from_utf16_lossy(&chars)
+}
+
+fn version_from_parts(high: u32, low: u32) -> Version {
+    Version {
+        major: (high >> 16) as u16,
+        minor: high as u16,
+        build: (low >> 16) as u16,
+        revision: low as u16,
+    }
+}
+
+fn version_parts(v: Version) -> [u32; 2] {
+    [
+        (u32::from(v.major) << 16) | u32::from(v.minor),
+        (u32::from(v.build) << 16) | u32::from(v.revision),
+    ]
+}
+
+fn version_string(v: Version) -> String {
+    format!("{}.{}.{}.{}", v.major, v.minor, v.build, v.revision)
+}
+
+// parses the leading numeric parts of a version string like "1.2.3-beta+abc", like the compilers do
+fn parse_version(s: &str) -> Option<Version> {
+    let mut parts = [0; 4];
+    let mut count = 0;
+    for (part, slot) in s.split('.').zip(&mut parts) {
+        let digits = part.find(|c: char| !c.is_ascii_digit()).unwrap_or(part.len());
+        *slot = part[..digits].parse().ok()?;
+        count += 1;
+        if digits < part.len() {
+            break;
+        }
+    }
+    (count > 0).then_some(Version {
+        major: parts[0],
+        minor: parts[1],
+        build: parts[2],
+        revision: parts[3],
+    })
+}
+
+// the string argument of an assembly attribute with a single string parameter, like AssemblyTitleAttribute
+fn attribute_string<'r>(res: &'r Resolution, attribute: &str) -> Option<&'r str> {
+    res.assembly.as_ref()?.attributes.iter().find_map(|a| {
+        let parent = match a.constructor {
+            UserMethod::Definition(m) => res[m.parent_type()].type_name(),
+            UserMethod::Reference(r) => match &res[r].parent {
+                MethodReferenceParent::Type(MethodType::Base(b)) => match &**b {
+                    BaseType::Type {
+                        source: TypeSource::User(u),
+                        ..
+                    } => u.type_name(res),
+                    _ => return None,
+                },
+                _ => return None,
+            },
+        };
+        if parent != attribute {
+            return None;
+        }
+        a.value.as_ref()?.pread::<SerString>(2).ok()?.0
+    })
+}
+
+impl VersionInfo {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let root = read_block(bytes, &mut 0)?;
+        if root.key != "VS_VERSION_INFO" {
+            throw!("invalid version resource key {}", root.key);
+        }
+
+        let fixed = root.value;
+        let fields: Vec<u32> = (0..13)
+            .map(|i| fixed.pread_with(4 * i, scroll::LE))
+            .collect::<scroll::Result<_>>()?;
+        if fields[0] != FIXED_FILE_INFO_SIGNATURE {
+            throw!("invalid fixed file info signature {:#010x}", fields[0]);
+        }
+
+        let mut info = VersionInfo {
+            file_version: version_from_parts(fields[2], fields[3]),
+            product_version: version_from_parts(fields[4], fields[5]),
+            file_flags_mask: fields[6],
+            file_flags: fields[7],
+            file_os: fields[8],
+            file_type: fields[9],
+            file_subtype: fields[10],
+            file_date: (u64::from(fields[11]) << 32) | u64::from(fields[12]),
+            string_tables: vec![],
+            translations: vec![],
+        };
+
+        for child in root.children {
+            match child.key.as_str() {
+                "StringFileInfo" => {
+                    for table in child.children {
+                        let Ok(id) = u32::from_str_radix(&table.key, 16) else {
+                            throw!("invalid string table key {}", table.key)
+                        };
+                        info.string_tables.push(StringTable {
+                            language: (id >> 16) as u16,
+                            code_page: id as u16,
+                            strings: table
+                                .children
+                                .into_iter()
+                                .map(|s| (s.key, text_value(s.value)))
+                                .collect(),
+                        });
+                    }
+                }
+                "VarFileInfo" => {
+                    for var in child.children.iter().filter(|v| v.key == "Translation" && !v.text) {
+                        info.translations.extend(
+                            var.value
+                                .chunks_exact(4)
+                                .map(|c| (u16::from_le_bytes([c[0], c[1]]), u16::from_le_bytes([c[2], c[3]]))),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(info)
+    }
+
+    pub fn write(&self) -> Result<Vec<u8>> {
+        let [file_high, file_low] = version_parts(self.file_version);
+        let [product_high, product_low] = version_parts(self.product_version);
+        let fixed: Vec<u8> = [
+            FIXED_FILE_INFO_SIGNATURE,
+            0x0001_0000,
+            file_high,
+            file_low,
+            product_high,
+            product_low,
+            self.file_flags_mask,
+            self.file_flags,
+            self.file_os,
+            self.file_type,
+            self.file_subtype,
+            (self.file_date >> 32) as u32,
+            self.file_date as u32,
+        ]
+        .into_iter()
+        .flat_map(u32::to_le_bytes)
+        .collect();
+
+        let tables: Vec<_> = self
+            .string_tables
+            .iter()
+            .map(|t| {
+                let strings: Vec<_> = t
+                    .strings
+                    .iter()
+                    .map(|(key, value)| {
+                        let value: Vec<u8> = value.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect();
+                        write_block(key, &value, value.len() / 2, true, &[])
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                write_block(
+                    &format!("{:04x}{:04x}", t.language, t.code_page),
+                    &[],
+                    0,
+                    true,
+                    &strings,
+                )
+            })
+            .collect::<Result<_>>()?;
+
+        let mut children = vec![];
+        if !tables.is_empty() {
+            children.push(write_block("StringFileInfo", &[], 0, true, &tables)?);
+        }
+        if !self.translations.is_empty() {
+            let value: Vec<u8> = self
+                .translations
+                .iter()
+                .flat_map(|(l, c)| l.to_le_bytes().into_iter().chain(c.to_le_bytes()))
+                .collect();
+            let translation = write_block("Translation", &value, value.len(), false, &[])?;
+            children.push(write_block("VarFileInfo", &[], 0, true, &[translation])?);
+        }
+
+        write_block("VS_VERSION_INFO", &fixed, fixed.len(), false, &children)
+    }
+
+    /// Derives version information from a module's assembly in the same way as the C# compiler.
+    ///
+    /// The file version comes from `AssemblyFileVersionAttribute`, or [`Assembly::version`](crate::resolved::assembly::Assembly::version)
+    /// if there isn't one, and the product version from `AssemblyInformationalVersionAttribute`, or the file version.
+    /// The string table is filled in from attributes such as `AssemblyTitleAttribute` and `AssemblyCompanyAttribute`.
+    pub fn from_resolution(res: &Resolution) -> Self {
+        let assembly_version = res.assembly.as_ref().map_or(Version::ZERO, |a| a.version);
+        let attribute = |name: &str| attribute_string(res, &format!("System.Reflection.Assembly{}Attribute", name));
+
+        let file_version_string = attribute("FileVersion").map_or_else(|| version_string(assembly_version), Into::into);
+        let file_version = parse_version(&file_version_string).unwrap_or(assembly_version);
+        let product_version_string =
+            attribute("InformationalVersion").map_or_else(|| file_version_string.clone(), Into::into);
+        let product_version = parse_version(&product_version_string).unwrap_or(file_version);
+
+        let mut strings = vec![];
+        let mut push = |key: &str, value: Option<String>| {
+            if let Some(v) = value {
+                strings.push((key.to_string(), v));
+            }
+        };
+        push("Comments", attribute("Description").map(Into::into));
+        push("CompanyName", attribute("Company").map(Into::into));
+        push("FileDescription", attribute("Title").map(Into::into));
+        push("FileVersion", Some(file_version_string));
+        push("InternalName", Some(res.module.name.to_string()));
+        push("LegalCopyright", attribute("Copyright").map(Into::into));
+        push("LegalTrademarks", attribute("Trademark").map(Into::into));
+        push("OriginalFilename", Some(res.module.name.to_string()));
+        push("ProductName", attribute("Product").map(Into::into));
+        push("ProductVersion", Some(product_version_string));
+        push("Assembly Version", Some(version_string(assembly_version)));
+
+        VersionInfo {
+            file_version,
+            product_version,
+            file_flags_mask: 0x3f,
+            file_flags: 0,
+            file_os: VOS_WINDOWS32,
+            file_type: if res.entry_point.is_some() { VFT_APP } else { VFT_DLL },
+            file_subtype: 0,
+            file_date: 0,
+            string_tables: vec![StringTable {
+                language: LANGUAGE_NEUTRAL,
+                code_page: UNICODE_CODE_PAGE,
+                strings,
+            }],
+            translations: vec![(LANGUAGE_NEUTRAL, UNICODE_CODE_PAGE)],
+        }
+    }
+}
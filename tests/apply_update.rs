@@ -0,0 +1,52 @@
+use dotnetdll::prelude::*;
+
+mod common;
+
+#[test]
+pub fn method_body() {
+    common::apply_update_fixture(
+        "apply_update",
+        r#"
+        .class public Program extends [System.Private.CoreLib]System.Object {
+            .method public static void Print() {
+                ldstr "before"
+                call void [mscorlib]System.Console::WriteLine(string)
+                ret
+            }
+
+            .method public static void Main(string[] args) {
+                .entrypoint
+                .locals init (valuetype [System.Private.CoreLib]System.ReadOnlySpan`1<uint8> pdb)
+                call void Program::Print()
+
+                ldtoken Program
+                call class [System.Private.CoreLib]System.Type [System.Private.CoreLib]System.Type::GetTypeFromHandle(valuetype [System.Private.CoreLib]System.RuntimeTypeHandle)
+                callvirt instance class [System.Private.CoreLib]System.Reflection.Assembly [System.Private.CoreLib]System.Type::get_Assembly()
+                ldarg.0
+                ldc.i4.0
+                ldelem.ref
+                call uint8[] [System.Private.CoreLib]System.IO.File::ReadAllBytes(string)
+                call valuetype [System.Private.CoreLib]System.ReadOnlySpan`1<!0> valuetype [System.Private.CoreLib]System.ReadOnlySpan`1<uint8>::op_Implicit(!0[])
+                ldarg.0
+                ldc.i4.1
+                ldelem.ref
+                call uint8[] [System.Private.CoreLib]System.IO.File::ReadAllBytes(string)
+                call valuetype [System.Private.CoreLib]System.ReadOnlySpan`1<!0> valuetype [System.Private.CoreLib]System.ReadOnlySpan`1<uint8>::op_Implicit(!0[])
+                // no PDB delta is written, so an empty one is passed instead
+                ldloc.0
+                call void [System.Private.CoreLib]System.Reflection.Metadata.MetadataUpdater::ApplyUpdate(class [System.Private.CoreLib]System.Reflection.Assembly, valuetype [System.Private.CoreLib]System.ReadOnlySpan`1<uint8>, valuetype [System.Private.CoreLib]System.ReadOnlySpan`1<uint8>, valuetype [System.Private.CoreLib]System.ReadOnlySpan`1<uint8>)
+
+                call void Program::Print()
+                ret
+            }
+        }
+        "#,
+        |res| {
+            let program = res.type_definition_index(1).unwrap();
+            let print = res.method_index(program, 0).unwrap();
+            res[print].body.as_mut().unwrap().instructions[0] = Instruction::load_string("after");
+        },
+        b"before\nafter\n",
+    )
+    .unwrap();
+}
@@ -0,0 +1,79 @@
+use dotnetdll::binary::{il::Instruction as BInstruction, method};
+use dotnetdll::prelude::*;
+
+// writes each body as a static method, then reads back the encoded bodies alongside the resolved ones
+fn round_trip(bodies: Vec<Vec<Instruction>>) -> Vec<(method::Method, Vec<Instruction>)> {
+    let mut res = Resolution::new(Module::new("Branches.dll"));
+    res.assembly = Some(Assembly::new("Branches"));
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    for (idx, instructions) in bodies.into_iter().enumerate() {
+        res.push_method(
+            program,
+            Method::new(
+                Accessibility::Public,
+                msig! { static void () },
+                format!("M{}", idx),
+                Some(body::Method::new(instructions)),
+            ),
+        );
+    }
+    let bytes = res.write(WriteOptions::default()).unwrap();
+
+    let dll = DLL::parse(&bytes).unwrap();
+    let tables = dll.get_logical_metadata().unwrap().tables;
+    let read = dll.resolve(ReadOptions::default()).unwrap();
+    tables
+        .method_def
+        .iter()
+        .zip(&read.type_definitions[1].methods)
+        .map(|(def, m)| {
+            (
+                dll.get_method(def).unwrap(),
+                m.body.as_ref().unwrap().instructions.clone(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+pub fn short_and_long() {
+    let short_loop = asm! {
+        LoadConstantInt32 0;
+        StoreLocal 0;
+    @start
+        LoadLocal 0;
+        LoadConstantInt32 100;
+        BranchLess NumberSign::Signed, start;
+        Return;
+    };
+
+    let mut long_forward = vec![Instruction::BranchTruthy(202)];
+    long_forward.extend(std::iter::repeat_with(|| Instruction::NoOperation).take(200));
+    long_forward.extend([Instruction::Return, Instruction::Return]);
+
+    let results = round_trip(vec![short_loop.clone(), long_forward.clone()]);
+
+    let (encoded, read) = &results[0];
+    assert_eq!(format!("{:?}", read), format!("{:?}", short_loop));
+    assert!(matches!(encoded.header, method::Header::Tiny { size: 8 }));
+    // back over ldloc.0 and ldc.i4.s 100, and the 2 bytes of blt.s itself
+    assert!(matches!(encoded.body[4], BInstruction::BltS(-5)));
+
+    let (encoded, read) = &results[1];
+    assert_eq!(format!("{:?}", read), format!("{:?}", long_forward));
+    assert!(matches!(encoded.body[0], BInstruction::Brtrue(201)));
+}
+
+#[test]
+pub fn relaxation() {
+    // the first branch only fits in a byte once the second one has been shortened
+    let mut instructions = vec![Instruction::Branch(125), Instruction::Branch(0)];
+    instructions.extend(std::iter::repeat_with(|| Instruction::NoOperation).take(123));
+    instructions.push(Instruction::Return);
+
+    let results = round_trip(vec![instructions.clone()]);
+    let (encoded, read) = &results[0];
+    assert_eq!(format!("{:?}", read), format!("{:?}", instructions));
+    assert!(matches!(encoded.body[0], BInstruction::BrS(125)));
+    assert!(matches!(encoded.body[1], BInstruction::BrS(-4)));
+}
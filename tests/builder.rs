@@ -0,0 +1,192 @@
+use dotnetdll::prelude::*;
+
+#[test]
+pub fn labels() {
+    let mut b = MethodBodyBuilder::new();
+    let (first, second, end) = (b.define_label(), b.define_label(), b.define_label());
+
+    b.emit(Instruction::LoadArgument(0)).emit_switch(&[first, second]);
+    b.emit_branch(Instruction::Branch, end);
+    b.mark_label(first).emit(Instruction::LoadConstantInt32(1));
+    b.emit_branch(Instruction::Branch, end);
+    b.mark_label(second).emit(Instruction::LoadConstantInt32(2));
+    b.mark_label(end).emit(Instruction::Return);
+    // targets that don't come from labels are left alone
+    b.emit(Instruction::Branch(0));
+
+    let body = b.build();
+    assert_eq!(
+        format!("{:?}", body.instructions),
+        format!(
+            "{:?}",
+            vec![
+                Instruction::LoadArgument(0),
+                Instruction::Switch(vec![3, 5]),
+                Instruction::Branch(6),
+                Instruction::LoadConstantInt32(1),
+                Instruction::Branch(6),
+                Instruction::LoadConstantInt32(2),
+                Instruction::Return,
+                Instruction::Branch(0),
+            ]
+        )
+    );
+    assert!(body.header.local_variables.is_empty());
+    assert!(body.data_sections.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "was never marked")]
+pub fn unmarked_label() {
+    let mut b = MethodBodyBuilder::new();
+    let label = b.define_label();
+    b.emit_branch(Instruction::Branch, label);
+    b.build();
+}
+
+#[test]
+pub fn scoped_locals() {
+    let mut b = MethodBodyBuilder::new();
+    let outer = b.declare_local(LocalVariable::new(ctype! { int }));
+
+    b.begin_scope();
+    let first = b.declare_local(LocalVariable::new(ctype! { string }));
+    let other = b.declare_local(LocalVariable::new(ctype! { int }));
+    b.end_scope();
+
+    b.begin_scope();
+    let second = b.declare_local(LocalVariable::new(ctype! { string }));
+    let flag = b.declare_local(LocalVariable::new(ctype! { bool }));
+    b.store_local(second).load_local_address(flag);
+    b.end_scope();
+
+    assert_eq!([outer, first, other, second, flag].map(|l| l.index()), [0, 1, 2, 1, 3]);
+
+    let body = b.build();
+    assert!(body.header.initialize_locals);
+    assert_eq!(
+        body.header.local_variables,
+        [
+            LocalVariable::new(ctype! { int }),
+            LocalVariable::new(ctype! { string }),
+            LocalVariable::new(ctype! { int }),
+            LocalVariable::new(ctype! { bool }),
+        ]
+    );
+    assert!(matches!(body.instructions[0], Instruction::StoreLocal(1)));
+    assert!(matches!(body.instructions[1], Instruction::LoadLocalAddress(3)));
+}
+
+#[test]
+pub fn exception_blocks() {
+    let exception: MethodType = ctype! { object };
+
+    let mut b = MethodBodyBuilder::new();
+    b.begin_try();
+    b.emit(Instruction::NoOperation);
+    let inner_end = b.begin_try();
+    b.emit(Instruction::LoadNull).emit(Instruction::Throw);
+    b.begin_fault();
+    b.emit(Instruction::NoOperation);
+    b.end_block();
+    b.begin_catch(exception.clone());
+    b.emit(Instruction::Pop);
+    b.begin_filter();
+    b.emit(Instruction::Pop).emit(Instruction::LoadConstantInt32(1));
+    b.begin_filter_handler();
+    b.emit(Instruction::Pop);
+    b.begin_finally();
+    b.emit(Instruction::NoOperation);
+    b.end_block();
+    b.emit(Instruction::Return);
+    // labels returned by begin_try can be used like any other
+    b.emit_branch(Instruction::Branch, inner_end);
+
+    let body = b.build();
+    assert_eq!(
+        format!("{:?}", body.instructions),
+        format!(
+            "{:?}",
+            asm! {
+                    NoOperation;
+                    LoadNull;
+                    Throw;
+                    Leave inner_end;
+                    NoOperation;
+                    EndFinally;
+                @inner_end
+                    Leave end;
+                    Pop;
+                    Leave end;
+                    Pop;
+                    LoadConstantInt32 1;
+                    EndFilter;
+                    Pop;
+                    Leave end;
+                    NoOperation;
+                    EndFinally;
+                @end
+                    Return;
+                    Branch inner_end;
+            }
+        )
+    );
+
+    assert_eq!(
+        body.data_sections,
+        [body::DataSection::ExceptionHandlers(vec![
+            body::Exception {
+                kind: body::ExceptionKind::Fault,
+                try_offset: 1,
+                try_length: 3,
+                handler_offset: 4,
+                handler_length: 2,
+            },
+            body::Exception {
+                kind: body::ExceptionKind::TypedException(exception),
+                try_offset: 0,
+                try_length: 7,
+                handler_offset: 7,
+                handler_length: 2,
+            },
+            body::Exception {
+                kind: body::ExceptionKind::Filter { offset: 9 },
+                try_offset: 0,
+                try_length: 7,
+                handler_offset: 12,
+                handler_length: 2,
+            },
+            // the finally handler protects the catch and filter handlers too
+            body::Exception {
+                kind: body::ExceptionKind::Finally,
+                try_offset: 0,
+                try_length: 14,
+                handler_offset: 14,
+                handler_length: 2,
+            },
+        ])]
+    );
+
+    // the blocks are valid for the encoder and decoder
+    let mut res = Resolution::new(Module::new("Builder.dll"));
+    res.assembly = Some(Assembly::new("Builder"));
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    res.push_method(
+        program,
+        Method::new(Accessibility::Public, msig! { static void () }, "M", Some(body.clone())),
+    );
+    let bytes = res.write(WriteOptions::default()).unwrap();
+    let read = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+    let read = read.type_definitions[1].methods[0].body.as_ref().unwrap();
+    assert_eq!(format!("{:?}", read.instructions), format!("{:?}", body.instructions));
+    assert_eq!(read.data_sections, body.data_sections);
+}
+
+#[test]
+#[should_panic(expected = "must be the last one")]
+pub fn catch_after_finally() {
+    let mut b = MethodBodyBuilder::new();
+    b.begin_try();
+    b.begin_finally();
+    b.begin_catch(ctype! { object });
+}
@@ -0,0 +1,135 @@
+use dotnetdll::prelude::*;
+use dotnetdll::resolution::cfg::{ControlFlowGraph, Edge, EdgeKind};
+
+fn edge(block: usize, kind: EdgeKind) -> Edge {
+    Edge { block, kind }
+}
+
+#[test]
+pub fn loops() {
+    let body = body::Method::new(asm! {
+        LoadConstantInt32 0;
+        StoreLocal 0;
+        Branch condition;
+    @loop_body
+        LoadLocal 0;
+        LoadConstantInt32 1;
+        Add;
+        StoreLocal 0;
+    @condition
+        LoadLocal 0;
+        LoadConstantInt32 10;
+        BranchLess NumberSign::Signed, loop_body;
+        Return;
+    });
+    let cfg = ControlFlowGraph::new(&body).unwrap();
+
+    let blocks = cfg.blocks();
+    assert_eq!(
+        blocks.iter().map(|b| (b.start, b.end)).collect::<Vec<_>>(),
+        [(0, 3), (3, 7), (7, 10), (10, 11)]
+    );
+    assert_eq!(cfg.block_of(5), 1);
+    assert_eq!(blocks[0].successors, [edge(2, EdgeKind::Branch)]);
+    assert_eq!(blocks[1].successors, [edge(2, EdgeKind::FallThrough)]);
+    assert_eq!(
+        blocks[2].successors,
+        [edge(1, EdgeKind::Branch), edge(3, EdgeKind::FallThrough)]
+    );
+    assert_eq!(
+        blocks[2].predecessors,
+        [edge(0, EdgeKind::Branch), edge(1, EdgeKind::FallThrough)]
+    );
+    assert_eq!(cfg.reverse_postorder(), [0, 2, 3, 1]);
+
+    let dominators = cfg.dominators();
+    assert_eq!(dominators.immediate_dominator(0), None);
+    assert_eq!(dominators.immediate_dominator(1), Some(2));
+    assert_eq!(dominators.immediate_dominator(2), Some(0));
+    assert_eq!(dominators.immediate_dominator(3), Some(2));
+    assert!(dominators.dominates(2, 1));
+    assert!(!dominators.dominates(1, 3));
+    assert_eq!(dominators.children(2).collect::<Vec<_>>(), [1, 3]);
+
+    let loops = cfg.loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].header, 2);
+    assert_eq!(loops[0].latches, [1]);
+    assert_eq!(loops[0].blocks.iter().copied().collect::<Vec<_>>(), [1, 2]);
+}
+
+#[test]
+pub fn exception_handlers() {
+    let (instructions, try_end, end) = asm! {
+        LoadArgument 0;
+        Switch vec![case_a, case_b];
+        Leave end;
+    @case_a
+        Leave end;
+    @case_b
+        Leave end;
+    +try_end
+        NoOperation;
+        EndFinally;
+    +end
+        Return;
+    };
+    let mut body = body::Method::new(instructions);
+    body.data_sections
+        .push(body::DataSection::ExceptionHandlers(vec![body::Exception {
+            kind: body::ExceptionKind::Finally,
+            try_offset: 0,
+            try_length: try_end,
+            handler_offset: try_end,
+            handler_length: end - try_end,
+        }]));
+    let cfg = ControlFlowGraph::new(&body).unwrap();
+
+    let blocks = cfg.blocks();
+    assert_eq!(
+        blocks.iter().map(|b| (b.start, b.end)).collect::<Vec<_>>(),
+        [(0, 2), (2, 3), (3, 4), (4, 5), (5, 7), (7, 8)]
+    );
+    assert_eq!(
+        blocks[0].successors,
+        [
+            edge(2, EdgeKind::Branch),
+            edge(3, EdgeKind::Branch),
+            edge(1, EdgeKind::FallThrough),
+            edge(4, EdgeKind::Exception)
+        ]
+    );
+    assert_eq!(
+        blocks[3].successors,
+        [edge(5, EdgeKind::Leave), edge(4, EdgeKind::Exception)]
+    );
+    // all three leaves go to the same block, so the finally only needs to return there once
+    assert_eq!(blocks[4].successors, [edge(5, EdgeKind::EndFinally)]);
+    assert_eq!(blocks[4].predecessors.len(), 4);
+
+    let dominators = cfg.dominators();
+    assert_eq!(dominators.immediate_dominator(4), Some(0));
+    assert_eq!(dominators.immediate_dominator(5), Some(0));
+    assert!(cfg.loops().is_empty());
+}
+
+#[test]
+pub fn unreachable_and_invalid() {
+    let body = body::Method::new(vec![Instruction::Return, Instruction::Branch(0)]);
+    let cfg = ControlFlowGraph::new(&body).unwrap();
+    let dominators = cfg.dominators();
+    assert!(!dominators.is_reachable(1));
+    assert!(!dominators.dominates(0, 1));
+    // the branch back to the entry block is not a back edge, since the entry block doesn't dominate its source
+    assert!(cfg.loops().is_empty());
+
+    let body = body::Method::new(vec![Instruction::Branch(5)]);
+    assert!(ControlFlowGraph::new(&body).is_err());
+    let body = body::Method::new(vec![Instruction::Branch(1)]);
+    assert!(ControlFlowGraph::new(&body).is_err());
+
+    assert!(ControlFlowGraph::new(&body::Method::new(vec![]))
+        .unwrap()
+        .blocks()
+        .is_empty());
+}
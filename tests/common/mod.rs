@@ -2,7 +2,7 @@
 
 use dotnetdll::prelude::*;
 use regex::Regex;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use tempfile::TempDir;
 
@@ -37,14 +37,51 @@ pub struct WriteContext<'a> {
     pub object: TypeRefIndex,
 }
 
+// assembles `source` with ilasm, returning the path of the DLL
+fn assemble(dir: &Path, name: &str, source: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    std::fs::write(dir.join(format!("{}.il", name)), source)?;
+
+    Command::new(env::ILASM.clone())
+        .current_dir(dir)
+        .arg("-DLL")
+        .arg(name)
+        .spawn()?
+        .wait()?;
+
+    Ok(dir.join(format!("{}.dll", name)))
+}
+
+// writes a runtimeconfig.json, so that `dotnet` can run the image called `name` in `dir`
+fn runtime_config(dir: &Path, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    // introspect installed .NET for available runtimes
+    let versions = Command::new(env::DOTNET_SDK.clone())
+        .arg("--list-runtimes")
+        .output()?
+        .stdout;
+    let versions = String::from_utf8(versions)?;
+    let regex = Regex::new(r"^(?<sdkname>[\w.]+) (?<version>(?<major>\d+\.\d+)\.\d+)")?;
+    let Some(caps) = regex.captures(&versions) else {
+        panic!("Could not automatically determine installed .NET runtime")
+    };
+
+    // substitute first available runtime into our config template
+    let template = include_str!("./template.runtimeconfig.json");
+    let config = template
+        .replace("{{name}}", &caps["sdkname"])
+        .replace("{{target}}", &caps["major"])
+        .replace("{{version}}", &caps["version"]);
+    std::fs::write(dir.join(format!("{}.runtimeconfig.json", name)), config)?;
+
+    Ok(())
+}
+
 pub fn read_fixture(name: &str, source: &str, test: impl FnOnce(Resolution)) -> Result<(), Box<dyn std::error::Error>> {
     let dir = TempDir::new()?;
 
-    let il_path = dir.path().join(format!("{}.il", name));
-
-    std::fs::write(
-        &il_path,
-        format!(
+    let dll_path = assemble(
+        dir.path(),
+        name,
+        &format!(
             r".assembly {} {{ }}
             .assembly extern mscorlib {{ }}
             {}",
@@ -52,20 +89,78 @@ pub fn read_fixture(name: &str, source: &str, test: impl FnOnce(Resolution)) ->
         ),
     )?;
 
-    Command::new(env::ILASM.clone())
-        .current_dir(dir.path())
-        .arg("-DLL")
-        .arg(name)
-        .spawn()?
-        .wait()?;
-
-    let dll_file = std::fs::read(dir.path().join(format!("{}.dll", name)))?;
+    let dll_file = std::fs::read(dll_path)?;
 
     test(Resolution::parse(&dll_file, ReadOptions::default())?);
 
     Ok(())
 }
 
+/// Assembles the program in `source`, lets `edit` change the resolution read from it, and runs the program with the
+/// paths of the resulting metadata and IL deltas as its arguments, so that it can apply them to itself with
+/// `MetadataUpdater.ApplyUpdate`.
+pub fn apply_update_fixture(
+    name: &str,
+    source: &str,
+    edit: impl FnOnce(&mut Resolution),
+    expect: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = TempDir::new()?;
+
+    // the runtime only applies updates to modules that are debuggable without optimizations,
+    // i.e. DebuggingModes.Default | DisableOptimizations | EnableEditAndContinue
+    let dll_path = assemble(
+        dir.path(),
+        name,
+        &format!(
+            r".assembly {} {{
+                .custom instance void [System.Private.CoreLib]System.Diagnostics.DebuggableAttribute::.ctor(valuetype [System.Private.CoreLib]System.Diagnostics.DebuggableAttribute/DebuggingModes) = (01 00 05 01 00 00 00 00)
+            }}
+            .assembly extern mscorlib {{ }}
+            .assembly extern System.Private.CoreLib {{ }}
+            {}",
+            name, source
+        ),
+    )?;
+
+    let original = std::fs::read(&dll_path)?;
+    let dll = DLL::parse(&original)?;
+    let previous = dll.resolve(ReadOptions {
+        record_tokens: true,
+        ..ReadOptions::default()
+    })?;
+    let baseline = enc::Baseline::new(&dll, &previous)?;
+
+    let mut current = previous.clone();
+    edit(&mut current);
+    let delta = current.write_delta(&previous, &baseline)?;
+
+    let metadata_path = dir.path().join(format!("{}.1.dmeta", name));
+    let il_path = dir.path().join(format!("{}.1.dil", name));
+    std::fs::write(&metadata_path, &delta.metadata)?;
+    std::fs::write(&il_path, &delta.il)?;
+
+    runtime_config(dir.path(), name)?;
+
+    let output = Command::new(env::DOTNET_SDK.clone())
+        .env("DOTNET_MODIFIABLE_ASSEMBLIES", "debug")
+        .arg(&dll_path)
+        .arg(&metadata_path)
+        .arg(&il_path)
+        .output()?;
+
+    if output.stdout != expect {
+        panic!(
+            "--- EXPECTED ---\n{}\n--- ACTUAL ---\n{}\n--- STDERR ---\n{}",
+            String::from_utf8(expect.into())?,
+            String::from_utf8(output.stdout)?,
+            String::from_utf8(output.stderr)?
+        );
+    }
+
+    Ok(())
+}
+
 pub enum MainMethod {
     Body(Vec<Instruction>),
     WithVariables {
@@ -139,8 +234,9 @@ pub fn write_fixture(
     ctx.resolution.set_entry_point(main);
 
     let written = ctx.resolution.write(WriteOptions {
-        is_32_bit: false,
         is_executable: true,
+        compute_max_stack: true,
+        ..WriteOptions::default()
     })?;
 
     let dir = TempDir::new()?;
@@ -148,24 +244,7 @@ pub fn write_fixture(
     let dll_path = dir.path().join(&dll_name);
     std::fs::write(&dll_path, written)?;
 
-    // introspect installed .NET for available runtimes
-    let versions = Command::new(env::DOTNET_SDK.clone())
-        .arg("--list-runtimes")
-        .output()?
-        .stdout;
-    let versions = String::from_utf8(versions)?;
-    let regex = Regex::new(r"^(?<sdkname>[\w.]+) (?<version>(?<major>\d+\.\d+)\.\d+)")?;
-    let Some(caps) = regex.captures(&versions) else {
-        panic!("Could not automatically determine installed .NET runtime")
-    };
-
-    // substitute first available runtime into our config template
-    let template = include_str!("./template.runtimeconfig.json");
-    let config = template
-        .replace("{{name}}", &caps["sdkname"])
-        .replace("{{target}}", &caps["major"])
-        .replace("{{version}}", &caps["version"]);
-    std::fs::write(dir.path().join(format!("{}.runtimeconfig.json", name)), config)?;
+    runtime_config(dir.path(), name)?;
 
     let output = Command::new(env::DOTNET_SDK.clone()).arg(&dll_path).output()?;
 
@@ -0,0 +1,111 @@
+use dotnetdll::prelude::*;
+use dotnetdll::resolution::disassemble::disassemble;
+
+#[test]
+pub fn disassemble_resolution() {
+    let mut res = Resolution::new(Module::new("disassemble.dll"));
+    res.assembly = Some(Assembly::new("disassemble"));
+
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let object = res.push_type_reference(type_ref! { System.Object in #mscorlib });
+    let exception = res.push_type_reference(type_ref! { System.Exception in #mscorlib });
+    let console = res.push_type_reference(type_ref! { System.Console in #mscorlib });
+
+    let class = res.push_type_definition(TypeDefinition::new(Some("Example".into()), "Program"));
+    res[class].set_extends(object);
+    res[class].flags.accessibility = TypeAccessibility::Public;
+
+    let field = res.push_field(
+        class,
+        Field::static_member(Accessibility::Private, "count", ctype! { int }),
+    );
+
+    let console_type = BaseType::class(console).into();
+    let write_line = res.push_method_reference(method_ref! { static void #console_type::WriteLine(string) });
+
+    let instructions = asm! {
+                LoadConstantInt32 1;
+                store_static_field field;
+                Leave 5;
+                Pop;
+                Leave 5;
+                load_string "it's \"done\"";
+                call write_line;
+                Return;
+    };
+
+    let main = res.push_method(
+        class,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void (string[]) },
+            "Main",
+            Some(body::Method {
+                data_sections: vec![body::DataSection::ExceptionHandlers(vec![body::Exception {
+                    kind: body::ExceptionKind::TypedException(BaseType::class(exception).into()),
+                    try_offset: 0,
+                    try_length: 3,
+                    handler_offset: 3,
+                    handler_length: 2,
+                }])],
+                ..body::Method::new(instructions)
+            }),
+        ),
+    );
+    res.set_entry_point(main);
+
+    let text = disassemble(&res);
+    let lines: Vec<_> = text.lines().map(str::trim).collect();
+
+    for expected in [
+        ".assembly extern mscorlib",
+        ".class public auto ansi Example.Program",
+        "extends [mscorlib]System.Object",
+        "stsfld int32 Example.Program::count",
+        ".entrypoint",
+        "L0000: ldc.i4.1",
+        "leave L0005",
+        r#"L0005: ldstr "it's \"done\"""#,
+        "call void [mscorlib]System.Console::WriteLine(string)",
+        ".try L0000 to L0003 catch [mscorlib]System.Exception handler L0003 to L0005",
+    ] {
+        assert!(lines.contains(&expected), "missing {:?} in:\n{}", expected, text);
+    }
+}
+
+#[test]
+pub fn float_operands() {
+    let mut res = Resolution::new(Module::new("floats.dll"));
+    res.assembly = Some(Assembly::new("floats"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Floats"));
+    res.push_method(
+        class,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "M",
+            Some(body::Method::new(vec![
+                Instruction::LoadConstantFloat32(1.5),
+                Instruction::LoadConstantFloat32(f32::NAN),
+                Instruction::LoadConstantFloat64(-0.25),
+                Instruction::LoadConstantFloat64(1e300),
+                Instruction::LoadConstantFloat64(f64::NEG_INFINITY),
+                Instruction::Return,
+            ])),
+        ),
+    );
+
+    let text = disassemble(&res);
+    let lines: Vec<_> = text.lines().map(str::trim).collect();
+
+    // values without an exact decimal form are written as their bits, which a bare integer operand can't express
+    for expected in [
+        "ldc.r4 1.5",
+        "ldc.r4 float32(0x7fc00000)",
+        "ldc.r8 -0.25",
+        "ldc.r8 float64(0x7e37e43c8800759c)",
+        "ldc.r8 float64(0xfff0000000000000)",
+    ] {
+        assert!(lines.contains(&expected), "missing {:?} in:\n{}", expected, text);
+    }
+}
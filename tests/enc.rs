@@ -0,0 +1,228 @@
+use dotnetdll::binary::{
+    cli::Metadata,
+    heap::{Reader, StringsReader, UserStringReader},
+    metadata::{header, table::Kind},
+};
+use dotnetdll::prelude::*;
+use scroll::Pread;
+
+const RECORD: ReadOptions = ReadOptions {
+    skip_method_bodies: false,
+    record_tokens: true,
+};
+
+fn original() -> Vec<u8> {
+    let mut res = Resolution::new(Module::new("Enc.dll"));
+    res.assembly = Some(Assembly::new("Enc"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let console = res.push_type_reference(type_ref! { System.Console in #mscorlib });
+    let console_t: MethodType = BaseType::class(console).into();
+    let write_line = res.push_method_reference(method_ref! { static void @console_t::WriteLine(string) });
+
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    res.push_method(
+        program,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "Main",
+            Some(body::Method::new(vec![
+                Instruction::load_string("before"),
+                Instruction::call(write_line),
+                Instruction::Return,
+            ])),
+        ),
+    );
+    res.push_method(
+        program,
+        Method::new(
+            Accessibility::Public,
+            msig! { static int () },
+            "Unchanged",
+            Some(body::Method::new(vec![
+                Instruction::LoadConstantInt32(1),
+                Instruction::Return,
+            ])),
+        ),
+    );
+
+    res.write(WriteOptions::default()).unwrap()
+}
+
+struct ParsedDelta<'a> {
+    header: header::Header,
+    streams: Vec<(&'a str, &'a [u8])>,
+}
+
+impl<'a> ParsedDelta<'a> {
+    fn parse(metadata: &'a [u8]) -> Self {
+        let root: Metadata = metadata.pread(0).unwrap();
+        let streams: Vec<_> = root
+            .stream_headers
+            .iter()
+            .map(|h| (h.name, &metadata[h.offset as usize..(h.offset + h.size) as usize]))
+            .collect();
+        let tables = streams.iter().find(|(n, _)| *n == "#-").unwrap().1;
+        let header = tables
+            .pread_with(
+                0,
+                header::Layout {
+                    large_indices: true,
+                    uncompressed: true,
+                    ..header::Layout::default()
+                },
+            )
+            .unwrap();
+
+        ParsedDelta { header, streams }
+    }
+
+    fn stream(&self, name: &str) -> &'a [u8] {
+        self.streams.iter().find(|(n, _)| *n == name).unwrap().1
+    }
+}
+
+fn heap_len(dll: &DLL, name: &str) -> usize {
+    let meta = dll.get_cli_metadata().unwrap();
+    meta.stream_headers.iter().find(|h| h.name == name).unwrap().size as usize
+}
+
+fn token(kind: Kind, row: u32) -> u32 {
+    (kind as u32) << 24 | row
+}
+
+// the delta is only checked by reading it back here, tests/apply_update.rs applies one with MetadataUpdater.ApplyUpdate
+#[test]
+pub fn delta() {
+    let bytes = original();
+    let dll = DLL::parse(&bytes).unwrap();
+    let previous = dll.resolve(RECORD).unwrap();
+    let baseline = enc::Baseline::new(&dll, &previous).unwrap();
+    assert_eq!(baseline.generation(), 0);
+
+    let mut current = previous.clone();
+    let program = current.type_definition_index(1).unwrap();
+    let main = current.method_index(program, 0).unwrap();
+    let write_line = current.method_reference_index(0).unwrap();
+    current[main].body.as_mut().unwrap().instructions[0] = Instruction::load_string("after");
+    current.push_method(
+        program,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "Added",
+            Some(body::Method::new(vec![
+                Instruction::load_string("added"),
+                Instruction::call(write_line),
+                Instruction::Return,
+            ])),
+        ),
+    );
+
+    let delta = current.write_delta(&previous, &baseline).unwrap();
+    assert_eq!(delta.baseline.generation(), 1);
+    assert_eq!(delta.updated_methods, [0x0600_0001, 0x0600_0003]);
+
+    let il_len = u32::from_le_bytes(delta.il[..4].try_into().unwrap());
+    assert_eq!(il_len as usize, delta.il.len());
+
+    let parsed = ParsedDelta::parse(&delta.metadata);
+    assert!(parsed.streams.iter().any(|(n, s)| *n == "#JTD" && s.is_empty()));
+
+    let tables = &parsed.header.tables;
+    assert_eq!(tables.module[0].generation, 1);
+    assert_eq!(tables.module[0].enc_base_id.0, 0);
+    assert_ne!(tables.module[0].enc_id.0, 0);
+
+    assert_eq!(
+        tables
+            .enc_log
+            .iter()
+            .map(|l| (l.token, l.func_code))
+            .collect::<Vec<_>>(),
+        [
+            (token(Kind::MethodDef, 1), 0),
+            (token(Kind::TypeDef, 2), 1),
+            (token(Kind::MethodDef, 3), 0),
+        ]
+    );
+    assert_eq!(
+        tables.enc_map.iter().map(|m| m.token).collect::<Vec<_>>(),
+        [token(Kind::MethodDef, 1), token(Kind::MethodDef, 3)]
+    );
+
+    // heap indexes continue from the end of the baseline's heaps
+    let strings_len = heap_len(&dll, StringsReader::NAME);
+    let added = &tables.method_def[1];
+    assert_eq!(added.param_list.0, 1);
+    let strings = StringsReader::new(parsed.stream(StringsReader::NAME));
+    assert_eq!(strings.at_index((added.name.0 - strings_len).into()).unwrap(), "Added");
+
+    let userstrings = UserStringReader::new(parsed.stream(UserStringReader::NAME));
+    let new: Vec<_> = [1, 13]
+        .into_iter()
+        .map(|i| String::from_utf16(&userstrings.at_index(i).unwrap()).unwrap())
+        .collect();
+    assert_eq!(new, ["after", "added"]);
+
+    // a second generation builds on the first one
+    let mut next = current.clone();
+    next[main].body.as_mut().unwrap().instructions[0] = Instruction::load_string("again");
+    let second = next.write_delta(&current, &delta.baseline).unwrap();
+    assert_eq!(second.baseline.generation(), 2);
+    assert_eq!(second.updated_methods, [0x0600_0001]);
+
+    let parsed = ParsedDelta::parse(&second.metadata);
+    assert_eq!(parsed.header.tables.module[0].enc_base_id, tables.module[0].enc_id);
+}
+
+#[test]
+pub fn unchanged() {
+    let bytes = original();
+    let dll = DLL::parse(&bytes).unwrap();
+    let previous = dll.resolve(RECORD).unwrap();
+    let baseline = enc::Baseline::new(&dll, &previous).unwrap();
+
+    let delta = previous.write_delta(&previous, &baseline).unwrap();
+    assert!(delta.updated_methods.is_empty());
+    assert_eq!(delta.il.len(), 4);
+
+    let parsed = ParsedDelta::parse(&delta.metadata);
+    assert!(parsed.header.tables.enc_log.is_empty());
+    assert!(parsed.header.tables.enc_map.is_empty());
+}
+
+#[test]
+pub fn requires_tokens() {
+    let bytes = original();
+    let dll = DLL::parse(&bytes).unwrap();
+    let res = dll.resolve(ReadOptions::default()).unwrap();
+    assert!(enc::Baseline::new(&dll, &res).is_err());
+}
+
+#[test]
+pub fn changed_rows() {
+    let bytes = original();
+    let dll = DLL::parse(&bytes).unwrap();
+    let previous = dll.resolve(RECORD).unwrap();
+    let baseline = enc::Baseline::new(&dll, &previous).unwrap();
+    let program = previous.type_definition_index(1).unwrap();
+    let unchanged = previous.method_index(program, 1).unwrap();
+
+    // existing rows aren't written again, so changes to them would otherwise be dropped from the delta
+    let mut renamed = previous.clone();
+    renamed[unchanged].name = "Renamed".into();
+    assert!(renamed.write_delta(&previous, &baseline).is_err());
+
+    let mut retyped = previous.clone();
+    retyped[unchanged].signature = msig! { static void () };
+    assert!(retyped.write_delta(&previous, &baseline).is_err());
+
+    let mut moved = previous.clone();
+    moved[program].namespace = Some("Moved".into());
+    assert!(moved.write_delta(&previous, &baseline).is_err());
+
+    let mut retargeted = previous.clone();
+    retargeted.type_references[0].name = "Debug".into();
+    assert!(retargeted.write_delta(&previous, &baseline).is_err());
+}
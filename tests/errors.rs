@@ -0,0 +1,203 @@
+use dotnetdll::binary::metadata::table::Kind;
+use dotnetdll::prelude::*;
+
+#[test]
+pub fn invalid_table_index() {
+    let mut res = Resolution::new(Module::new("errors.dll"));
+    let outer = res.push_type_definition(TypeDefinition::new(None, "Outer"));
+    let inner = res.push_type_definition(TypeDefinition::new(None, "Inner"));
+    res[inner].encloser = Some(outer);
+
+    let mut bytes = res.write(WriteOptions::default()).unwrap();
+
+    // point the NestedClass row's enclosing class past the end of the TypeDef table
+    let row = bytes
+        .windows(4)
+        .rposition(|w| w == [3, 0, 2, 0])
+        .expect("could not find NestedClass row");
+    bytes[row + 2] = 9;
+
+    match Resolution::parse(&bytes, ReadOptions::default()) {
+        Err(DLLError::InvalidTableIndex { table, row, context }) => {
+            assert_eq!(table, Kind::TypeDef);
+            assert_eq!(row, 8);
+            assert_eq!(context.type_name.as_deref(), Some("Inner"));
+        }
+        other => panic!("expected an invalid TypeDef index, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+pub fn bad_signature() {
+    let mut res = Resolution::new(Module::new("errors.dll"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Class"));
+    res.push_field(class, Field::instance(Accessibility::Public, "value", ctype! { int }));
+
+    let mut bytes = res.write(WriteOptions::default()).unwrap();
+
+    // the field's signature blob is its length followed by FIELD (0x06) and ELEMENT_TYPE_I4 (0x08)
+    let blob = bytes
+        .windows(3)
+        .position(|w| w == [2, 6, 8])
+        .expect("could not find field signature");
+    bytes[blob + 1] = 0xFF;
+
+    match Resolution::parse(&bytes, ReadOptions::default()) {
+        Err(DLLError::BadSignature { context, .. }) => {
+            assert_eq!(context.type_name.as_deref(), Some("Class"));
+            assert_eq!(context.member_name.as_deref(), Some("value"));
+        }
+        other => panic!("expected a bad signature, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+pub fn invalid_instruction_offset() {
+    let mut res = Resolution::new(Module::new("errors.dll"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Class"));
+    res.push_method(
+        class,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "Method",
+            Some(body::Method::new(asm! {
+                    Branch end;
+                    NoOperation;
+                @end
+                    Return;
+            })),
+        ),
+    );
+
+    let mut bytes = res.write(WriteOptions::default()).unwrap();
+
+    // a tiny header for 4 bytes of code, then br.s +1, nop, ret
+    let code = bytes
+        .windows(5)
+        .position(|w| w == [0x12, 0x2B, 0x01, 0x00, 0x2A])
+        .expect("could not find method body");
+    // branch back into the middle of the br.s instruction
+    bytes[code + 2] = 0xFF;
+
+    match Resolution::parse(&bytes, ReadOptions::default()) {
+        Err(DLLError::InvalidInstructionOffset { token, offset, context }) => {
+            assert_eq!(token, 0x0600_0001);
+            assert_eq!(offset, 1);
+            assert_eq!(context.item, "branch target");
+            assert_eq!(context.type_name.as_deref(), Some("Class"));
+            assert_eq!(context.member_name.as_deref(), Some("Method"));
+        }
+        other => panic!("expected an invalid instruction offset, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+pub fn unresolvable_coded_index() {
+    let mut res = Resolution::new(Module::new("errors.dll"));
+    res.assembly = Some(Assembly::new("errors"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let attribute: MethodType = BaseType::class(res.push_type_reference(ExternalTypeReference::new(
+        Some("System".into()),
+        "CLSCompliantAttribute",
+        ResolutionScope::Assembly(mscorlib),
+    )))
+    .into();
+    let ctor = res.push_method_reference(method_ref! { void @attribute::.ctor(bool) });
+    res.assembly.as_mut().unwrap().attributes.push(Attribute::new(
+        ctor.into(),
+        CustomAttributeData {
+            constructor_args: vec![FixedArg::Boolean(true)],
+            named_args: vec![],
+        },
+    ));
+
+    let mut bytes = res.write(WriteOptions::default()).unwrap();
+
+    // the CustomAttribute row's parent is Assembly 1 and its constructor is MemberRef 1
+    let row = bytes
+        .windows(4)
+        .rposition(|w| w == [0x2E, 0, 0x0B, 0])
+        .expect("could not find CustomAttribute row");
+    // keep the MemberRef tag, but make the index null
+    bytes[row + 2] = 0x03;
+
+    match Resolution::parse(&bytes, ReadOptions::default()) {
+        Err(DLLError::UnresolvableCodedIndex { index, context }) => {
+            assert_eq!(index, "CustomAttributeType coded index");
+            assert_eq!(context.item, "constructor of custom attribute 0");
+        }
+        other => panic!("expected an unresolvable coded index, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+pub fn unresolvable_token() {
+    let mut res = Resolution::new(Module::new("errors.dll"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Class"));
+    res.push_method(
+        class,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "Method",
+            Some(body::Method::new(asm! {
+                LoadString "text".encode_utf16().collect();
+                Pop;
+                Return;
+            })),
+        ),
+    );
+
+    let mut bytes = res.write(WriteOptions::default()).unwrap();
+
+    // ldstr takes a #US token, so make it a TypeDef token instead
+    let code = bytes
+        .windows(8)
+        .position(|w| w[0] == 0x1E && w[1] == 0x72 && w[5] == 0x70 && w[6..] == [0x26, 0x2A])
+        .expect("could not find method body");
+    bytes[code + 5] = 0x02;
+
+    match Resolution::parse(&bytes, ReadOptions::default()) {
+        Err(DLLError::UnresolvableCodedIndex { index, context }) => {
+            assert_eq!(index, "metadata token");
+            assert_eq!(context.item, "ldstr instruction");
+        }
+        other => panic!("expected an unresolvable token, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+pub fn invalid_member_list() {
+    let mut res = Resolution::new(Module::new("errors.dll"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Class"));
+    res.push_method(
+        class,
+        Method::new(Accessibility::Public, msig! { static void () }, "Method", None),
+    );
+
+    let mut bytes = res.write(WriteOptions::default()).unwrap();
+
+    // find the TypeDef row of Class by its flags, name and namespace, which come before its member lists
+    let header = DLL::parse(&bytes).unwrap().get_logical_metadata().unwrap();
+    let class_row = &header.tables.type_def[1];
+    let mut prefix = class_row.flags.to_le_bytes().to_vec();
+    prefix.extend((class_row.type_name.0 as u16).to_le_bytes());
+    prefix.extend((class_row.type_namespace.0 as u16).to_le_bytes());
+    let row = bytes
+        .windows(prefix.len())
+        .position(|w| w == prefix)
+        .expect("could not find TypeDef row");
+    // move the start of its method list past the end of the MethodDef table
+    bytes[row + 12] = 9;
+
+    // the list of <Module> ends where the list of Class starts, so it's the first to run past the end
+    match Resolution::parse(&bytes, ReadOptions::default()) {
+        Err(DLLError::InvalidTableIndex { table, row, context }) => {
+            assert_eq!(table, Kind::MethodDef);
+            assert_eq!(row, 1);
+            assert_eq!(context.item, "method_def list of type_def 0");
+        }
+        other => panic!("expected an invalid MethodDef index, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,208 @@
+use dotnetdll::prelude::*;
+use dotnetdll::resolution::lazy::LazyResolution;
+
+fn empty_attribute(constructor: MethodIndex) -> Attribute<'static> {
+    Attribute::new(
+        constructor.into(),
+        CustomAttributeData {
+            constructor_args: vec![],
+            named_args: vec![],
+        },
+    )
+}
+
+fn original() -> Vec<u8> {
+    let mut res = Resolution::new(Module::new("Lazy.dll"));
+    res.assembly = Some(Assembly::new("Lazy"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let object = res.push_type_reference(type_ref! { System.Object in #mscorlib });
+    let console = res.push_type_reference(type_ref! { System.Console in #mscorlib });
+    let console_t: MethodType = BaseType::class(console).into();
+    let write_line = res.push_method_reference(method_ref! { static void @console_t::WriteLine(string) });
+    let out = res.push_field_reference(field_ref! { object @console_t::Out });
+
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    res[program].extends = Some(object.into());
+    let marker = res.push_method(
+        program,
+        Method::constructor(
+            Accessibility::Public,
+            vec![],
+            Some(body::Method::new(vec![Instruction::Return])),
+        ),
+    );
+    res[program].attributes.push(empty_attribute(marker));
+
+    let mut value = Field::static_member(Accessibility::Public, "value", ctype! { int });
+    value.literal = true;
+    value.default = Some(Constant::Int32(42));
+    res.push_field(program, value);
+
+    let mut main = Method::new(
+        Accessibility::Public,
+        msig! { static void (string) },
+        "Main",
+        Some(body::Method::new(vec![
+            Instruction::LoadArgument(0),
+            Instruction::call(write_line),
+            Instruction::load_static_field(out),
+            Instruction::Pop,
+            Instruction::Return,
+        ])),
+    );
+    main.parameter_metadata = vec![Some(ParameterMetadata::name("message"))];
+    let main = res.push_method(program, main);
+
+    let count = res.push_property(program, Property::new(true, "Count", Parameter::value(ctype! { int })));
+    res.set_property_getter(
+        count,
+        Method::new(
+            Accessibility::Public,
+            msig! { static int () },
+            "get_Count",
+            Some(body::Method::new(vec![
+                Instruction::LoadConstantInt32(1),
+                Instruction::Return,
+            ])),
+        ),
+    );
+    let handler = res.push_type_reference(type_ref! { System.EventHandler in #mscorlib });
+    let handler_t: MethodType = BaseType::class(handler).into();
+    res.push_event(
+        program,
+        Event::new(
+            "Changed",
+            BaseType::class(handler).into(),
+            Method::new(
+                Accessibility::Public,
+                msig! { static void (@handler_t) },
+                "add_Changed",
+                None,
+            ),
+            Method::new(
+                Accessibility::Public,
+                msig! { static void (@handler_t) },
+                "remove_Changed",
+                None,
+            ),
+        ),
+    );
+    res.push_method(
+        program,
+        Method::new(Accessibility::Public, msig! { static void () }, "After", None),
+    );
+
+    let mut nested = TypeDefinition::new(None, "Nested");
+    nested.encloser = Some(program);
+    nested.generic_parameters.push(generic::Type::new("T"));
+    let nested = res.push_type_definition(nested);
+    res.push_method(
+        nested,
+        Method::new(Accessibility::Public, msig! { void () }, "Instance", None),
+    );
+
+    let mut other = TypeDefinition::new(Some("Lazy".into()), "Other");
+    other.attributes.push(empty_attribute(main));
+    res.push_type_definition(other);
+
+    res.write(WriteOptions::default()).unwrap()
+}
+
+fn without_bodies(t: &TypeDefinition) -> String {
+    let mut t = t.clone();
+    for m in &mut t.methods {
+        m.body = None;
+    }
+    for p in &mut t.properties {
+        for m in p.getter.iter_mut().chain(&mut p.setter).chain(&mut p.other) {
+            m.body = None;
+        }
+    }
+    format!("{:?}", t)
+}
+
+#[test]
+pub fn matches_eager() {
+    let bytes = original();
+    let lazy = LazyResolution::parse(&bytes).unwrap();
+    let eager = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+
+    assert_eq!(lazy.type_definition_count(), eager.type_definitions.len());
+    for (index, t) in eager.enumerate_type_definitions() {
+        assert_eq!(format!("{:?}", lazy.type_definition(index).unwrap()), without_bodies(t));
+
+        for (method, m) in eager.enumerate_methods(index) {
+            assert_eq!(
+                format!("{:?}", lazy.method_body(method).unwrap()),
+                format!("{:?}", m.body.as_ref())
+            );
+        }
+    }
+
+    let program = eager.type_definition_index(1).unwrap();
+    let getter = eager
+        .property_getter_index(eager.property_index(program, 0).unwrap())
+        .unwrap();
+    assert_eq!(lazy.method(getter).unwrap().name, "get_Count");
+    assert_eq!(
+        format!("{:?}", lazy.method_body(getter).unwrap()),
+        format!("{:?}", eager[getter].body.as_ref())
+    );
+
+    for (index, r) in eager.enumerate_type_references() {
+        assert_eq!(format!("{:?}", lazy.type_reference(index).unwrap()), format!("{:?}", r));
+    }
+    for (index, r) in eager.enumerate_method_references() {
+        assert_eq!(
+            format!("{:?}", lazy.method_reference(index).unwrap()),
+            format!("{:?}", r)
+        );
+    }
+    for (index, r) in eager.enumerate_field_references() {
+        assert_eq!(
+            format!("{:?}", lazy.field_reference(index).unwrap()),
+            format!("{:?}", r)
+        );
+    }
+}
+
+#[test]
+pub fn find_type() {
+    let bytes = original();
+    let dll = DLL::parse(&bytes).unwrap();
+    let lazy = dll.resolve_lazy().unwrap();
+
+    let other = lazy.find_type(Some("Lazy"), "Other").unwrap().unwrap();
+    assert_eq!(lazy.type_definition(other).unwrap().name, "Other");
+    assert_eq!(lazy.find_type(None, "Other").unwrap(), None);
+
+    let nested = lazy.find_type(None, "Nested").unwrap().unwrap();
+    let nested = lazy.type_definition(nested).unwrap();
+    assert_eq!(nested.generic_parameters[0].name, "T");
+    assert_eq!(nested.encloser, lazy.find_type(None, "Program").unwrap());
+
+    // later accesses return the value resolved by the first one
+    assert!(std::ptr::eq(
+        lazy.type_definition(other).unwrap(),
+        lazy.type_definition(other).unwrap()
+    ));
+}
+
+#[test]
+pub fn resolve_after_access() {
+    let bytes = original();
+    let eager = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+
+    let lazy = LazyResolution::parse(&bytes).unwrap();
+    let program = lazy.find_type(None, "Program").unwrap().unwrap();
+    lazy.type_definition(program).unwrap();
+    let main = eager.method_index(program, 1).unwrap();
+    assert!(lazy.method_body(main).unwrap().is_some());
+    lazy.method_reference(eager.method_reference_index(0).unwrap()).unwrap();
+
+    // the values resolved so far are reused, and the rest are resolved the same way
+    assert_eq!(
+        format!("{:?}", lazy.resolve(ReadOptions::default()).unwrap()),
+        format!("{:?}", eager)
+    );
+}
@@ -0,0 +1,61 @@
+use dotnetdll::prelude::*;
+
+#[test]
+pub fn resolve_references() {
+    let mut lib = Resolution::new(Module::new("Shapes.dll"));
+    let outer = lib.push_type_definition(TypeDefinition::new(Some("Shapes".into()), "Outer"));
+    let inner = lib.push_type_definition(TypeDefinition::new(None, "Inner"));
+    lib[inner].encloser = Some(outer);
+
+    let outer_t: MethodType = BaseType::class(outer).into();
+    lib.push_method(
+        outer,
+        Method::new(Accessibility::Public, msig! { static void (int) }, "Add", None),
+    );
+    let add_string = lib.push_method(
+        outer,
+        Method::new(Accessibility::Public, msig! { static void (string) }, "Add", None),
+    );
+    let add_outer = lib.push_method(
+        outer,
+        Method::new(Accessibility::Public, msig! { static void (@outer_t) }, "Add", None),
+    );
+    let count = lib.push_field(
+        outer,
+        Field::static_member(Accessibility::Public, "Count", ctype! { int }),
+    );
+
+    let mut app = Resolution::new(Module::new("App.dll"));
+    let shapes = app.push_assembly_reference(ExternalAssemblyReference::new("Shapes"));
+    let outer_ref = app.push_type_reference(type_ref! { Shapes.Outer in #shapes });
+    let inner_ref = app.push_type_reference(ExternalTypeReference::new(
+        None,
+        "Inner",
+        ResolutionScope::Nested(outer_ref),
+    ));
+    let missing_ref = app.push_type_reference(type_ref! { Shapes.Missing in #shapes });
+
+    let outer_ref_t: MethodType = BaseType::class(outer_ref).into();
+    let add_ref = app.push_method_reference(method_ref! { static void @outer_ref_t::Add(@outer_ref_t) });
+    let add_long_ref = app.push_method_reference(method_ref! { static void @outer_ref_t::Add(long) });
+    let count_ref = app.push_field_reference(field_ref! { int @outer_ref_t::Count });
+
+    let lookup = Lookup::new(&lib);
+
+    assert_eq!(lookup.find_type("Shapes.Outer"), Some(outer));
+    assert_eq!(lookup.find_type("Shapes.Outer/Inner"), Some(inner));
+    assert_eq!(lookup.find_type("Inner"), None);
+    assert_eq!(lookup.find_methods(outer, "Add").count(), 3);
+    assert_eq!(
+        lookup.find_method(outer, "Add", &msig! { static void (string) }, &lib),
+        Some(add_string)
+    );
+    assert_eq!(lookup.find_field(outer, "Count"), Some(count));
+
+    assert_eq!(lookup.resolve_type_ref(&app, outer_ref), Some(outer));
+    assert_eq!(lookup.resolve_type_ref(&app, inner_ref), Some(inner));
+    assert_eq!(lookup.resolve_type_ref(&app, missing_ref), None);
+    assert_eq!(lookup.resolve_method_ref(&app, add_ref), Some(add_outer));
+    assert_eq!(lookup.resolve_method_ref(&app, add_long_ref), None);
+    assert_eq!(lookup.resolve_field_ref(&app, count_ref), Some(count));
+}
@@ -0,0 +1,186 @@
+use dotnetdll::prelude::*;
+use dotnetdll::resolution::merge::MergeError;
+
+fn public_type<'a>(res: &mut Resolution<'a>, namespace: &'a str, name: &'a str) -> TypeIndex {
+    let mut t = TypeDefinition::new(Some(namespace.into()), name);
+    t.flags.accessibility = TypeAccessibility::Public;
+    res.push_type_definition(t)
+}
+
+fn library() -> Resolution<'static> {
+    let mut res = Resolution::new(Module::new("Core.dll"));
+    res.assembly = Some(Assembly::new("Core"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let object = res.push_type_reference(type_ref! { System.Object in #mscorlib });
+
+    let greeter = public_type(&mut res, "Core", "Greeter");
+    res[greeter].set_extends(object);
+    res.push_method(
+        greeter,
+        Method::new(
+            Accessibility::Public,
+            msig! { static string (string) },
+            "Greet",
+            Some(body::Method::new(vec![
+                Instruction::LoadArgument(0),
+                Instruction::Return,
+            ])),
+        ),
+    );
+    res.push_field(
+        greeter,
+        Field::static_member(Accessibility::Public, "Count", ctype! { int }),
+    );
+
+    public_type(&mut res, "Shared", "Util");
+
+    res
+}
+
+fn application() -> Resolution<'static> {
+    let mut res = Resolution::new(Module::new("App.exe"));
+    res.assembly = Some(Assembly::new("App"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let core = res.push_assembly_reference(ExternalAssemblyReference::new("Core"));
+    let object = res.push_type_reference(type_ref! { System.Object in #mscorlib });
+    let greeter = res.push_type_reference(type_ref! { Core.Greeter in #core });
+
+    let greeter_t: MethodType = BaseType::class(greeter).into();
+    let greet = res.push_method_reference(method_ref! { static string @greeter_t::Greet(string) });
+    let count = res.push_field_reference(field_ref! { int @greeter_t::Count });
+
+    let program = public_type(&mut res, "App", "Program");
+    res[program].set_extends(object);
+    let main = res.push_method(
+        program,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "Main",
+            Some(body::Method::new(vec![
+                Instruction::LoadNull,
+                Instruction::call(greet),
+                Instruction::Pop,
+                Instruction::load_static_field(count),
+                Instruction::Pop,
+                Instruction::Return,
+            ])),
+        ),
+    );
+    res.set_entry_point(main);
+
+    public_type(&mut res, "Shared", "Util");
+
+    res
+}
+
+#[test]
+pub fn duplicate_types() {
+    match Resolution::merge(&[application(), library()], MergeOptions::default()) {
+        Err(MergeError::DuplicateTypes(names)) => assert_eq!(names, ["Shared.Util"]),
+        other => panic!("expected a duplicate type error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+pub fn merge() {
+    let merged = Resolution::merge(&[application(), library()], MergeOptions { internalize: true }).unwrap();
+
+    assert_eq!(merged.assembly.as_ref().unwrap().name, "App");
+    assert_eq!(merged.assembly_references.len(), 1);
+    assert_eq!(merged.type_references.len(), 1);
+    assert!(merged.method_references.is_empty());
+    assert!(merged.field_references.is_empty());
+
+    let renamed = merged
+        .type_definitions
+        .iter()
+        .find(|t| t.name == "<Core>Util")
+        .expect("conflicting internal type was not renamed");
+    assert!(matches!(renamed.flags.accessibility, TypeAccessibility::NotPublic));
+
+    let Some(EntryPoint::Method(main)) = merged.entry_point else {
+        panic!("entry point was not kept");
+    };
+    let body = merged[main].body.as_ref().unwrap();
+    match &body.instructions[1] {
+        Instruction::Call {
+            param0: MethodSource::User(UserMethod::Definition(greet)),
+            ..
+        } => {
+            assert_eq!(merged[*greet].name, "Greet");
+            assert_eq!(merged[greet.parent_type()].name, "Greeter");
+        }
+        other => panic!("expected a call to a definition, got {:?}", other),
+    }
+    match &body.instructions[3] {
+        Instruction::LoadStaticField {
+            param0: FieldSource::Definition(count),
+            ..
+        } => assert_eq!(merged[*count].name, "Count"),
+        other => panic!("expected a load of a field definition, got {:?}", other),
+    }
+
+    let bytes = merged.write(WriteOptions::default()).unwrap();
+    let reread = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+    assert_eq!(reread.type_definitions.len(), merged.type_definitions.len());
+}
+
+#[test]
+pub fn attribute_values() {
+    let mut app = application();
+    let (mscorlib, _) = app.enumerate_assembly_references().next().unwrap();
+    let type_t: MethodType = BaseType::class(app.push_type_reference(type_ref! { System.Type in #mscorlib })).into();
+    let proxy: MethodType = BaseType::class(
+        app.push_type_reference(type_ref! { System.Diagnostics.DebuggerTypeProxyAttribute in #mscorlib }),
+    )
+    .into();
+    let ctor = app.push_method_reference(method_ref! { void @proxy::.ctor(@type_t) });
+
+    let program = app.type_definitions.iter().position(|t| t.name == "Program").unwrap();
+    let names = [
+        "Core.Greeter, Core, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null",
+        "System.Collections.Generic.List`1[[Core.Greeter, Core]], mscorlib",
+        "Shared.Util, Core",
+        "Shared.Util",
+    ];
+    app.type_definitions[program].attributes = names
+        .iter()
+        .map(|name| {
+            Attribute::new(
+                ctor.into(),
+                CustomAttributeData {
+                    constructor_args: vec![FixedArg::Type(name)],
+                    named_args: vec![],
+                },
+            )
+        })
+        .collect();
+
+    let merged = Resolution::merge(&[app, library()], MergeOptions { internalize: true }).unwrap();
+
+    let program = merged.type_definitions.iter().find(|t| t.name == "Program").unwrap();
+    let values: Vec<_> = program
+        .attributes
+        .iter()
+        .map(|a| {
+            match a
+                .instantiation_data(&AlwaysFailsResolver, &merged)
+                .unwrap()
+                .constructor_args[..]
+            {
+                [FixedArg::Type(name)] => name.to_string(),
+                ref other => panic!("expected a type argument, got {:?}", other),
+            }
+        })
+        .collect();
+    assert_eq!(
+        values,
+        [
+            "Core.Greeter",
+            "System.Collections.Generic.List`1[[Core.Greeter]], mscorlib",
+            "Shared.<Core>Util",
+            "Shared.Util",
+        ]
+    );
+}
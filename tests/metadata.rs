@@ -0,0 +1,139 @@
+use dotnetdll::prelude::*;
+
+fn round_trip(res: &Resolution, check: impl FnOnce(&Resolution)) {
+    let bytes = res.write(WriteOptions::default()).unwrap();
+    check(&Resolution::parse(&bytes, ReadOptions::default()).unwrap());
+}
+
+#[test]
+pub fn body_management() {
+    let mut res = Resolution::new(Module::new("Bodies.dll"));
+    res.assembly = Some(Assembly::new("Bodies"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Bodies"));
+
+    let managed = res.push_method(
+        class,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "Managed",
+            Some(body::Method::new(vec![Instruction::Return])),
+        ),
+    );
+    let mut method = Method::new(Accessibility::Public, msig! { static void () }, "Unmanaged", None);
+    method.body_format = BodyFormat::Native;
+    method.body_management = BodyManagement::Unmanaged;
+    let unmanaged = res.push_method(class, method);
+
+    round_trip(&res, |read| {
+        assert!(matches!(read[managed].body_management, BodyManagement::Managed));
+        assert!(matches!(read[unmanaged].body_management, BodyManagement::Unmanaged));
+    });
+}
+
+#[test]
+pub fn method_lists() {
+    let mut res = Resolution::new(Module::new("Methods.dll"));
+    res.assembly = Some(Assembly::new("Methods"));
+
+    // a type without methods between two types with methods must still start its (empty) list at the right row
+    let mut classes = vec![];
+    for (name, methods) in [("A", &["A1", "A2"][..]), ("B", &[]), ("C", &["C1"]), ("D", &[])] {
+        let class = res.push_type_definition(TypeDefinition::new(None, name));
+        for &m in methods {
+            res.push_method(
+                class,
+                Method::new(Accessibility::Public, msig! { static void () }, m, None),
+            );
+        }
+        classes.push(class);
+    }
+
+    round_trip(&res, |read| {
+        let names: Vec<Vec<_>> = classes
+            .iter()
+            .map(|&c| read[c].methods.iter().map(|m| m.name.as_ref()).collect())
+            .collect();
+        assert_eq!(names, [vec!["A1", "A2"], vec![], vec!["C1"], vec![]]);
+    });
+}
+
+#[test]
+pub fn nested_types() {
+    let mut res = Resolution::new(Module::new("Nested.dll"));
+    res.assembly = Some(Assembly::new("Nested"));
+
+    let outer = res.push_type_definition(TypeDefinition::new(Some("Example".into()), "Outer"));
+    let mut inner = TypeDefinition::new(None, "Inner");
+    inner.encloser = Some(outer);
+    let inner = res.push_type_definition(inner);
+
+    round_trip(&res, |read| {
+        assert_eq!(read[outer].encloser, None);
+        assert_eq!(read[inner].encloser, Some(outer));
+    });
+}
+
+#[test]
+pub fn generic_base_types() {
+    let mut res = Resolution::new(Module::new("Generic.dll"));
+    res.assembly = Some(Assembly::new("Generic"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let list = res.push_type_reference(ExternalTypeReference::new(
+        Some("System.Collections.Generic".into()),
+        "List`1",
+        ResolutionScope::Assembly(mscorlib),
+    ));
+    let equatable = res.push_type_reference(ExternalTypeReference::new(
+        Some("System".into()),
+        "IEquatable`1",
+        ResolutionScope::Assembly(mscorlib),
+    ));
+
+    let class = res.push_type_definition(TypeDefinition::new(None, "Derived"));
+    // base types and interfaces are written as TypeSpecs when they're instantiations
+    let base = TypeSource::Generic {
+        base: list.into(),
+        parameters: vec![ctype! { int }],
+    };
+    let interface = TypeSource::Generic {
+        base: equatable.into(),
+        parameters: vec![BaseType::class(class).into()],
+    };
+    res[class].set_extends(base.clone());
+    res[class].implements.push((vec![], interface.clone()));
+
+    round_trip(&res, |read| {
+        assert_eq!(read[class].extends, Some(base));
+        assert_eq!(read[class].implements[0].1, interface);
+    });
+}
+
+#[test]
+pub fn field_initial_values() {
+    let mut res = Resolution::new(Module::new("Data.dll"));
+    res.assembly = Some(Assembly::new("Data"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Data"));
+    let mut block = TypeDefinition::new(None, "Block");
+    block.flags.layout = Layout::Explicit(Some(ExplicitLayout { class_size: 3 }));
+    let block = res.push_type_definition(block);
+
+    let mut fields = vec![];
+    for (name, field_type, value) in [
+        ("Int", ctype! { int }, &[1, 2, 3, 4][..]),
+        ("Long", ctype! { long }, &[5; 8]),
+        ("Byte", ctype! { byte }, &[6]),
+        ("Block", BaseType::valuetype(block).into(), &[7, 8, 9]),
+    ] {
+        let mut field = Field::static_member(Accessibility::Private, name, field_type);
+        field.initial_value = Some(value.into());
+        fields.push((res.push_field(class, field), value));
+    }
+
+    // the stored data has no length of its own, so reading it must not run on into the data of other fields
+    round_trip(&res, |read| {
+        for (field, value) in fields {
+            assert_eq!(read[field].initial_value.as_deref(), Some(value));
+        }
+    });
+}
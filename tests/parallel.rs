@@ -0,0 +1,103 @@
+use dotnetdll::prelude::*;
+
+// many bodies that share strings, type specs and blobs, so that merging them has to deduplicate across bodies
+fn original() -> Resolution<'static> {
+    let mut res = Resolution::new(Module::new("Parallel.dll"));
+    res.assembly = Some(Assembly::new("Parallel"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let list = res.push_type_reference(type_ref! { System.Collections.Generic.List<1> in #mscorlib });
+    let activator: MethodType =
+        BaseType::class(res.push_type_reference(type_ref! { System.Activator in #mscorlib })).into();
+    let create_instance = res.push_method_reference(method_ref! { static M0 #activator::CreateInstance<1>() });
+
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    for i in 0..40 {
+        let element: MethodType = if i % 3 == 0 {
+            ctype! { string }
+        } else {
+            ctype! { int }
+        };
+        let list_t: MethodType = BaseType::class(TypeSource::generic(list, vec![element.clone()])).into();
+        let create = GenericMethodInstantiation::new(create_instance, vec![element]);
+
+        let (instructions, try_start, handler_start, end) = asm! {
+                load_string "shared";
+                Pop;
+                load_string format!("method {}", i);
+                Pop;
+                LoadNull;
+                cast_class list_t.clone();
+                StoreLocal 0;
+            +try_start
+                LoadConstantInt32 i;
+                NewArray ctype! { int[] };
+                Pop;
+                Leave end;
+            +handler_start
+                Pop;
+                Leave end;
+            +end
+                call create;
+                Pop;
+                Return;
+        };
+        let mut body = body::Method::with_locals(vec![LocalVariable::new(list_t.clone())], instructions);
+        if i % 2 == 0 {
+            body.data_sections
+                .push(body::DataSection::ExceptionHandlers(vec![body::Exception {
+                    kind: body::ExceptionKind::TypedException(list_t),
+                    try_offset: try_start,
+                    try_length: handler_start - try_start,
+                    handler_offset: handler_start,
+                    handler_length: end - handler_start,
+                }]));
+        }
+
+        res.push_method(
+            program,
+            Method::new(
+                Accessibility::Public,
+                msig! { static void () },
+                format!("Method{}", i),
+                Some(body),
+            ),
+        );
+    }
+
+    res
+}
+
+#[test]
+pub fn bodies_round_trip() {
+    let res = original();
+    let bytes = res.write(WriteOptions::default()).unwrap();
+
+    let read = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+    for (original, read) in res.type_definitions[1]
+        .methods
+        .iter()
+        .zip(&read.type_definitions[1].methods)
+    {
+        let (original, read) = (original.body.as_ref().unwrap(), read.body.as_ref().unwrap());
+        assert_eq!(
+            format!("{:?}", read.instructions),
+            format!("{:?}", original.instructions)
+        );
+        assert_eq!(read.header.local_variables, original.header.local_variables);
+        assert_eq!(read.data_sections, original.data_sections);
+    }
+
+    assert_eq!(read.write(WriteOptions::default()).unwrap(), bytes);
+}
+
+#[test]
+pub fn shared_rows() {
+    let bytes = original().write(WriteOptions::default()).unwrap();
+    let dll = DLL::parse(&bytes).unwrap();
+    let tables = dll.get_logical_metadata().unwrap().tables;
+
+    // only the exception clause types go through the type cache, so those are the only specs shared across bodies
+    assert_eq!(tables.type_spec.len(), 2 + 2 * 40);
+    assert_eq!(tables.method_spec.len(), 40);
+    assert_eq!(tables.stand_alone_sig.len(), 40);
+}
@@ -0,0 +1,307 @@
+use dotnetdll::prelude::*;
+
+#[test]
+pub fn round_trip() {
+    let mut res = Resolution::new(Module::new("Debuggable.dll"));
+    res.assembly = Some(Assembly::new("Debuggable"));
+
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    // an odd-length name checks that the streams are padded to 4 bytes
+    let source = res.push_document(debug::Document::new("/src/app/Program.cs", debug::LANGUAGE_CSHARP));
+    let generated = res.push_document(debug::Document {
+        hash_algorithm: Some(debug::HASH_SHA1),
+        hash: vec![0xab; 20].into(),
+        ..debug::Document::new(r"C:\obj\Generated.g.cs", debug::LANGUAGE_CSHARP)
+    });
+
+    let span = |line, start_column, end_column| debug::SourceSpan {
+        start_line: line,
+        start_column,
+        end_line: line,
+        end_column,
+    };
+    let sequence_points = vec![
+        debug::SequencePoint::new(0, source, span(3, 9, 20)),
+        debug::SequencePoint::hidden(1, source),
+        debug::SequencePoint::new(2, generated, span(120, 1, 5)),
+        debug::SequencePoint::new(3, source, span(4, 5, 6)),
+    ];
+
+    let main = res.push_method(
+        program,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "Main",
+            Some(body::Method {
+                debug: Some(debug::MethodDebugInformation {
+                    sequence_points: sequence_points.clone(),
+                    scopes: vec![],
+                }),
+                ..body::Method::new(asm! {
+                    NoOperation;
+                    LoadConstantInt32 1;
+                    Pop;
+                    Return;
+                })
+            }),
+        ),
+    );
+    res.set_entry_point(main);
+
+    let (dll, pdb) = res.write_with_pdb(WriteOptions::default()).unwrap();
+
+    // the PDB records the entry point itself, as a MethodDef token
+    assert_eq!(
+        PDB::parse(&pdb).unwrap().get_pdb_stream().unwrap().entry_point,
+        0x0600_0001
+    );
+
+    let read = Resolution::parse_with_pdb(&dll, &pdb, ReadOptions::default()).unwrap();
+    assert!(matches!(read.entry_point, Some(EntryPoint::Method(m)) if m == main));
+
+    assert_eq!(read.documents.len(), 2);
+    for (read, written) in read.documents.iter().zip(&res.documents) {
+        assert_eq!(read.name, written.name);
+        assert_eq!(read.language, written.language);
+        assert_eq!(read.hash_algorithm, written.hash_algorithm);
+        assert_eq!(read.hash, written.hash);
+    }
+
+    let body = read[main].body.as_ref().unwrap();
+    assert_eq!(body.debug.as_ref().unwrap().sequence_points, sequence_points);
+
+    // without the PDB, the same image has no debugging information
+    let read = Resolution::parse(&dll, ReadOptions::default()).unwrap();
+    assert!(read.documents.is_empty());
+    assert!(read[main].body.as_ref().unwrap().debug.is_none());
+}
+
+#[test]
+pub fn local_scopes() {
+    let mut res = Resolution::new(Module::new("Scopes.dll"));
+    res.assembly = Some(Assembly::new("Scopes"));
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+
+    let scopes = vec![
+        debug::LocalScope::new(0, 6, vec![debug::LocalVariableName::new(0, "count")]),
+        debug::LocalScope::new(
+            1,
+            3,
+            vec![
+                debug::LocalVariableName::new(1, "name"),
+                debug::LocalVariableName {
+                    debugger_hidden: true,
+                    ..debug::LocalVariableName::new(2, "<>temp")
+                },
+            ],
+        ),
+        // scopes without variables are kept too
+        debug::LocalScope::new(4, 1, vec![]),
+    ];
+
+    let mut body = body::Method::new(asm! {
+        LoadConstantInt32 0;
+        StoreLocal 0;
+        LoadNull;
+        StoreLocal 1;
+        NoOperation;
+        Return;
+    });
+    body.header.local_variables = vec![
+        LocalVariable::new(ctype! { int }),
+        LocalVariable::new(ctype! { string }),
+        LocalVariable::new(ctype! { object }),
+    ];
+    body.debug = Some(debug::MethodDebugInformation {
+        sequence_points: vec![],
+        scopes: scopes.clone(),
+    });
+    let method = res.push_method(
+        program,
+        Method::new(Accessibility::Public, msig! { static void () }, "M", Some(body)),
+    );
+
+    let (dll, pdb) = res.write_with_pdb(WriteOptions::default()).unwrap();
+    let read = Resolution::parse_with_pdb(&dll, &pdb, ReadOptions::default()).unwrap();
+    let debug = read[method].body.as_ref().unwrap().debug.as_ref().unwrap();
+    assert!(debug.sequence_points.is_empty());
+    // scopes without imports are written with an empty import scope, which is read back like any other
+    assert_eq!(read.import_scopes, vec![debug::ImportScope::default()]);
+    let scopes: Vec<_> = scopes
+        .into_iter()
+        .map(|s| debug::LocalScope {
+            import_scope: read.import_scope_index(0),
+            ..s
+        })
+        .collect();
+    assert_eq!(debug.scopes, scopes);
+}
+
+#[test]
+pub fn invalid_sequence_points() {
+    use dotnetdll::binary::{
+        pdb::SequencePoints,
+        signature::compressed::{Signed, Unsigned},
+    };
+    use scroll::{Pread, Pwrite};
+
+    let mut res = Resolution::new(Module::new("Invalid.dll"));
+    res.assembly = Some(Assembly::new("Invalid"));
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    let source = res.push_document(debug::Document::new("/src/Program.cs", debug::LANGUAGE_CSHARP));
+    let mut body = body::Method::new(vec![Instruction::Return]);
+    body.debug = Some(debug::MethodDebugInformation {
+        sequence_points: vec![debug::SequencePoint::new(
+            0,
+            source,
+            debug::SourceSpan {
+                start_line: 5,
+                start_column: 1,
+                end_line: 4,
+                end_column: 10,
+            },
+        )],
+        scopes: vec![],
+    });
+    res.push_method(
+        program,
+        Method::new(Accessibility::Public, msig! { static void () }, "M", Some(body)),
+    );
+    // a sequence point that ends before it starts can't be encoded
+    assert!(res.write_with_pdb(WriteOptions::default()).is_err());
+
+    // a record on line 1, followed by one whose start line is moved by d_line
+    let blob = |d_line: i32| {
+        let mut blob = vec![0_u8; 32];
+        let offset = &mut 0;
+        for value in [0, 0, 0, 1, 1, 1, 1, 0, 1] {
+            blob.gwrite(Unsigned(value), offset).unwrap();
+        }
+        blob.gwrite(Signed(d_line), offset).unwrap();
+        blob.gwrite(Signed(0), offset).unwrap();
+        blob.truncate(*offset);
+        blob
+    };
+    let read = blob(2).pread_with::<SequencePoints>(0, false).unwrap();
+    assert_eq!(read.records.len(), 2);
+    // moving it above the first line is an error rather than an overflow
+    assert!(blob(-5).pread_with::<SequencePoints>(0, false).is_err());
+}
+
+#[test]
+pub fn invalid_variable_list() {
+    let mut res = Resolution::new(Module::new("Invalid.dll"));
+    res.assembly = Some(Assembly::new("Invalid"));
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    let mut body = body::Method::new(vec![Instruction::NoOperation, Instruction::Return]);
+    body.header.local_variables = vec![LocalVariable::new(ctype! { int })];
+    body.debug = Some(debug::MethodDebugInformation {
+        sequence_points: vec![],
+        scopes: vec![debug::LocalScope::new(
+            0,
+            2,
+            vec![debug::LocalVariableName::new(0, "count")],
+        )],
+    });
+    res.push_method(
+        program,
+        Method::new(Accessibility::Public, msig! { static void () }, "M", Some(body)),
+    );
+
+    let (dll, mut pdb) = res.write_with_pdb(WriteOptions::default()).unwrap();
+
+    // the only LocalScope row: method 1, import scope 1, variable list 1, constant list 1, offset 0, length 2
+    let row = [1, 0, 1, 0, 1, 0, 1, 0, 0, 0, 0, 0, 2, 0, 0, 0];
+    let found: Vec<_> = pdb
+        .windows(row.len())
+        .enumerate()
+        .filter_map(|(i, w)| (w == row).then_some(i))
+        .collect();
+    assert_eq!(found.len(), 1);
+    // a null variable list has no first row to start from
+    pdb[found[0] + 4] = 0;
+
+    assert!(Resolution::parse_with_pdb(&dll, &pdb, ReadOptions::default()).is_err());
+}
+
+#[test]
+pub fn import_scopes() {
+    let mut res = Resolution::new(Module::new("Imports.dll"));
+    res.assembly = Some(Assembly::new("Imports"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let math = res.push_type_reference(type_ref! { System.Math in #mscorlib });
+    let list = res.push_type_reference(type_ref! { System.Collections.Generic.List<1> in #mscorlib });
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+
+    let root = res.push_import_scope(debug::ImportScope {
+        parent: None,
+        imports: vec![
+            debug::Import::Namespace {
+                alias: None,
+                assembly: None,
+                namespace: "System".to_string(),
+            },
+            debug::Import::XmlNamespace {
+                alias: "ns".to_string(),
+                namespace: "http://example.com".to_string(),
+            },
+            debug::Import::AssemblyAlias {
+                alias: "corlib".to_string(),
+                assembly: mscorlib,
+            },
+        ],
+    });
+    let file = res.push_import_scope(debug::ImportScope {
+        parent: Some(root),
+        imports: vec![
+            debug::Import::AssemblyAliasReference("corlib".to_string()),
+            debug::Import::Namespace {
+                alias: Some("IO".to_string()),
+                assembly: Some(mscorlib),
+                namespace: "System.IO".to_string(),
+            },
+            debug::Import::Type {
+                alias: None,
+                // whether a type token refers to a value type isn't recorded
+                target: BaseType::Type {
+                    value_kind: None,
+                    source: math.into(),
+                }
+                .into(),
+            },
+            // generic instantiations are written as type specs
+            debug::Import::Type {
+                alias: Some("Ints".to_string()),
+                target: BaseType::class(TypeSource::generic(list, vec![ctype! { int }])).into(),
+            },
+        ],
+    });
+
+    let mut body = body::Method::new(vec![Instruction::NoOperation, Instruction::Return]);
+    body.debug = Some(debug::MethodDebugInformation {
+        sequence_points: vec![],
+        scopes: vec![
+            debug::LocalScope {
+                import_scope: Some(file),
+                ..debug::LocalScope::new(0, 2, vec![])
+            },
+            debug::LocalScope::new(1, 1, vec![]),
+        ],
+    });
+    let method = res.push_method(
+        program,
+        Method::new(Accessibility::Public, msig! { static void () }, "M", Some(body)),
+    );
+
+    let (dll, pdb) = res.write_with_pdb(WriteOptions::default()).unwrap();
+    let read = Resolution::parse_with_pdb(&dll, &pdb, ReadOptions::default()).unwrap();
+
+    // scopes without imports share an empty root scope, which is added after the others
+    assert_eq!(read.import_scopes[..2], res.import_scopes[..]);
+    assert_eq!(read.import_scopes[2], debug::ImportScope::default());
+
+    let scopes = &read[method].body.as_ref().unwrap().debug.as_ref().unwrap().scopes;
+    assert_eq!(scopes[0].import_scope, Some(file));
+    assert_eq!(scopes[1].import_scope, read.import_scope_index(2));
+}
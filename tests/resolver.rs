@@ -0,0 +1,87 @@
+use dotnetdll::prelude::*;
+use dotnetdll::resolution::resolver::ResolveError;
+use std::path::Path;
+
+fn write_library(dir: &Path) {
+    let mut res = Resolution::new(Module::new("Shapes.dll"));
+    res.assembly = Some(Assembly::new("Shapes"));
+
+    res.push_type_definition(TypeDefinition::new(Some("Geometry".into()), "Color"));
+    let outer = res.push_type_definition(TypeDefinition::new(Some("Geometry".into()), "Outer"));
+    let inner = res.push_type_definition(TypeDefinition::new(None, "Inner"));
+    res[inner].encloser = Some(outer);
+
+    std::fs::write(dir.join("Shapes.dll"), res.write(WriteOptions::default()).unwrap()).unwrap();
+}
+
+fn write_facade(dir: &Path) {
+    let mut res = Resolution::new(Module::new("Facade.dll"));
+    res.assembly = Some(Assembly::new("Facade"));
+
+    let shapes = res.push_assembly_reference(ExternalAssemblyReference::new("Shapes"));
+    res.push_exported_type(ExportedType {
+        attributes: vec![],
+        flags: TypeFlags::default(),
+        name: "Color".into(),
+        namespace: Some("Geometry".into()),
+        implementation: TypeImplementation::TypeForwarder(shapes),
+    });
+
+    std::fs::write(dir.join("Facade.dll"), res.write(WriteOptions::default()).unwrap()).unwrap();
+}
+
+#[test]
+pub fn find_type() {
+    let dir = tempfile::tempdir().unwrap();
+    write_library(dir.path());
+    write_facade(dir.path());
+
+    let mut resolver = AssemblyResolver::new([dir.path()]);
+    resolver.default_assemblies = vec!["Shapes".to_string()];
+    let resolver = &resolver;
+
+    let (color, res) = resolver.find_type("Geometry.Color").unwrap();
+    assert_eq!(color.name, "Color");
+    assert_eq!(res.assembly.as_ref().unwrap().name, "Shapes");
+
+    let (inner, res) = resolver
+        .find_type("Geometry.Outer+Inner, Shapes, Version=1.0.0.0")
+        .unwrap();
+    assert_eq!(inner.name, "Inner");
+    assert_eq!(res[inner.encloser.unwrap()].name, "Outer");
+
+    let (forwarded, res) = resolver.find_type("Geometry.Color, Facade").unwrap();
+    assert_eq!(forwarded.name, "Color");
+    assert_eq!(res.assembly.as_ref().unwrap().name, "Shapes");
+
+    // nested types are named with a / like everywhere else, whatever syntax the name was given in
+    let (inner, _) = resolver.find_in("Shapes", "Geometry.Outer/Inner").unwrap();
+    assert_eq!(inner.name, "Inner");
+    assert!(matches!(
+        resolver.find_type("Geometry.Outer+Missing"),
+        Err(ResolveError::TypeNotFound(name)) if name == "Geometry.Outer/Missing"
+    ));
+    assert!(matches!(
+        resolver.find_type("Geometry.Color, Missing"),
+        Err(ResolveError::AssemblyNotFound(_))
+    ));
+}
+
+#[test]
+pub fn loaded_assemblies_outlive_later_loads() {
+    let dir = tempfile::tempdir().unwrap();
+    write_library(dir.path());
+    write_facade(dir.path());
+
+    let resolver = AssemblyResolver::new([dir.path()]);
+    let shapes = resolver.load("Shapes").unwrap();
+    let (color, _) = resolver.find_in("Shapes", "Geometry.Color").unwrap();
+
+    // loading more assemblies doesn't move the ones that were already loaded
+    resolver.load("Facade").unwrap();
+    assert!(resolver.load("Missing").is_err());
+
+    assert_eq!(shapes.assembly.as_ref().unwrap().name, "Shapes");
+    assert_eq!(color.name, "Color");
+    assert!(std::ptr::eq(shapes, resolver.load("Shapes").unwrap()));
+}
@@ -1,4 +1,5 @@
 use dotnetdll::prelude::*;
+use dotnetdll::resources::{self, Entry, Value};
 use std::borrow::Cow;
 use std::process::Command;
 
@@ -31,12 +32,11 @@ pub fn write() {
                 attributes: vec![],
                 name: "strings.resources".into(),
                 visibility: resource::Visibility::Public,
-                //////
-                // using (var res = new ResourceWriter("./strings.resources")) {
-                //   res.AddResource("string", "foo bar");
-                // }
-                //////
-                implementation: resource::Implementation::CurrentFile(include_bytes!("./strings.resources").as_slice().into()),
+                implementation: resource::Implementation::CurrentFile({
+                    let mut set = ResourceSet::new();
+                    set.insert("string", resources::Value::String("foo bar".into()));
+                    set.write().unwrap().into()
+                }),
             }]);
 
             let mscorlib = ctx.mscorlib;
@@ -80,3 +80,91 @@ pub fn write() {
     )
     .unwrap();
 }
+
+#[test]
+pub fn codec() {
+    //////
+    // using (var res = new ResourceWriter("./strings.resources")) {
+    //   res.AddResource("string", "foo bar");
+    // }
+    //////
+    let bytes = include_bytes!("./strings.resources");
+    let set = ResourceSet::parse(bytes).unwrap();
+    assert_eq!(set.reader_type, resources::DEFAULT_READER_TYPE);
+    assert_eq!(set.set_type, resources::DEFAULT_SET_TYPE);
+    assert_eq!(
+        set.entries,
+        [Entry {
+            name: "string".into(),
+            value: Value::String("foo bar".into()),
+        }]
+    );
+    assert_eq!(resources::hash_name("string"), 0x7fa3_e570);
+    assert_eq!(set.write().unwrap(), bytes);
+}
+
+#[test]
+pub fn value_types() {
+    let serialized = "Example.Point, Example, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null";
+    let mut set = ResourceSet::new();
+    for (name, value) in [
+        ("null", Value::Null),
+        ("bool", Value::Boolean(true)),
+        ("char", Value::Char(0xd83d)),
+        ("byte", Value::Byte(0xff)),
+        ("sbyte", Value::SByte(-1)),
+        ("short", Value::Int16(-2)),
+        ("ushort", Value::UInt16(2)),
+        ("int", Value::Int32(-3)),
+        ("uint", Value::UInt32(3)),
+        ("long", Value::Int64(-4)),
+        ("ulong", Value::UInt64(4)),
+        ("float", Value::Single(0.5)),
+        ("double", Value::Double(-0.25)),
+        ("decimal", Value::Decimal([125, 0, 0, 2 << 16])),
+        ("date", Value::DateTime(0x4000_0000_0000_0000)),
+        ("time", Value::TimeSpan(10_000_000)),
+        ("bytes", Value::ByteArray(vec![1, 2, 3].into())),
+        ("stream", Value::Stream(vec![4, 5].into())),
+        // the data of serialized objects isn't interpreted, so it doesn't need to be a valid BinaryFormatter stream
+        (
+            "point",
+            Value::Serialized {
+                type_name: serialized.into(),
+                data: vec![0, 1, 2, 3].into(),
+            },
+        ),
+        (
+            "ünïcödé",
+            Value::Serialized {
+                type_name: serialized.into(),
+                data: vec![4].into(),
+            },
+        ),
+        ("string", Value::String("a longer string that needs more than one byte for its length ".repeat(3).into())),
+    ] {
+        set.insert(name, value);
+    }
+    set.insert("int", Value::Int32(3));
+
+    let bytes = set.write().unwrap();
+    let read = ResourceSet::parse(&bytes).unwrap();
+    assert_eq!(read.entries.len(), set.entries.len());
+    for Entry { name, value } in &set.entries {
+        assert_eq!(read.get(name), Some(value), "{}", name);
+    }
+    // entries are written in ordinal order, and the type table only needs each serialized type once
+    assert_eq!(read.entries[0].name, "bool");
+    assert_eq!(
+        bytes.windows(serialized.len()).filter(|w| *w == serialized.as_bytes()).count(),
+        1
+    );
+    assert_eq!(read.write().unwrap(), bytes);
+
+    set.entries.push(Entry {
+        name: "int".into(),
+        value: Value::Null,
+    });
+    assert!(set.write().is_err());
+    assert!(ResourceSet::parse(&bytes[1..]).is_err());
+}
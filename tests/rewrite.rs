@@ -0,0 +1,154 @@
+use dotnetdll::prelude::*;
+
+fn assert_instructions(body: &body::Method, expected: Vec<Instruction>) {
+    assert_eq!(format!("{:?}", body.instructions), format!("{:?}", expected));
+}
+
+#[test]
+pub fn branches() {
+    let mut body = body::Method::new(asm! {
+        LoadConstantInt32 0;
+        StoreLocal 0;
+        Branch condition;
+    @loop_body
+        LoadLocal 0;
+        LoadConstantInt32 1;
+        Add;
+        StoreLocal 0;
+    @condition
+        LoadLocal 0;
+        LoadConstantInt32 10;
+        BranchLess NumberSign::Signed, loop_body;
+        Return;
+    });
+
+    // the branch into the condition runs the new instructions, but the loop body's fallthrough skips them
+    body.insert_before(7, [Instruction::LoadConstantInt32(2), Instruction::Pop]);
+    // the back edge still goes to the start of the loop body, which is now after the inserted instructions.
+    // branches in the new instructions already use indices after insertion
+    body.insert_after(2, [Instruction::NoOperation, Instruction::Branch(14)]);
+    assert_eq!(
+        format!("{:?}", body.replace(6, Instruction::LoadConstantInt32(3))),
+        format!("{:?}", Instruction::LoadConstantInt32(1))
+    );
+    assert_instructions(
+        &body,
+        asm! {
+            LoadConstantInt32 0;
+            StoreLocal 0;
+            Branch before_condition;
+            NoOperation;
+            Branch ret;
+        @loop_body
+            LoadLocal 0;
+            LoadConstantInt32 3;
+            Add;
+            StoreLocal 0;
+        @before_condition
+            LoadConstantInt32 2;
+            Pop;
+            LoadLocal 0;
+            LoadConstantInt32 10;
+            BranchLess NumberSign::Signed, loop_body;
+        @ret
+            Return;
+        },
+    );
+
+    // branches into removed instructions go to the instruction after them
+    let removed = body.remove(9..11);
+    assert_eq!(removed.len(), 2);
+    body.remove(3..3);
+    assert_instructions(
+        &body,
+        asm! {
+            LoadConstantInt32 0;
+            StoreLocal 0;
+            Branch condition;
+            NoOperation;
+            Branch ret;
+        @loop_body
+            LoadLocal 0;
+            LoadConstantInt32 3;
+            Add;
+            StoreLocal 0;
+        @condition
+            LoadLocal 0;
+            LoadConstantInt32 10;
+            BranchLess NumberSign::Signed, loop_body;
+        @ret
+            Return;
+        },
+    );
+
+    body.insert_before(0, [Instruction::Switch(vec![1, 3])]);
+    body.insert_after(4, [Instruction::NoOperation]);
+    assert!(matches!(&body.instructions[0], Instruction::Switch(t) if t == &[1, 3]));
+    assert!(matches!(body.instructions[3], Instruction::Branch(11)));
+    assert!(matches!(body.instructions[6], Instruction::Branch(14)));
+    assert!(matches!(body.instructions[13], Instruction::BranchLess(_, 7)));
+}
+
+#[test]
+pub fn exception_handlers() {
+    let (instructions, handler_start, end) = asm! {
+            LoadArgument 0;
+            LoadConstantInt32 1;
+            Switch vec![inner, end];
+        @inner
+            Leave end;
+        +handler_start
+            LoadArgument 0;
+            Pop;
+            EndFilter;
+        @handler
+            Pop;
+            Leave end;
+        +end
+            Return;
+    };
+    let filter = body::Exception {
+        kind: body::ExceptionKind::Filter { offset: handler_start },
+        try_offset: 1,
+        try_length: handler_start - 1,
+        handler_offset: handler_start + 3,
+        handler_length: end - handler_start - 3,
+    };
+    let mut body = body::Method::new(instructions);
+    body.data_sections
+        .push(body::DataSection::ExceptionHandlers(vec![filter]));
+
+    // the first new instruction is part of the try block, and the second is part of the handler
+    body.insert_before(1, [Instruction::NoOperation]);
+    body.insert_after(8, [Instruction::NoOperation]);
+    // inserting at the start of the filter makes it part of the filter, not the try block
+    body.insert_before(5, [Instruction::Duplicate]);
+    assert_eq!(
+        body.data_sections,
+        [body::DataSection::ExceptionHandlers(vec![body::Exception {
+            kind: body::ExceptionKind::Filter { offset: 5 },
+            try_offset: 1,
+            try_length: 4,
+            handler_offset: 9,
+            handler_length: 3,
+        }])]
+    );
+    assert!(matches!(body.instructions[11], Instruction::Leave(12)));
+
+    // removing the whole handler drops the clause
+    body.remove(5..12);
+    assert!(body.data_sections.is_empty());
+    assert_instructions(
+        &body,
+        asm! {
+            LoadArgument 0;
+            NoOperation;
+            LoadConstantInt32 1;
+            Switch vec![inner, end];
+        @inner
+            Leave end;
+        @end
+            Return;
+        },
+    );
+}
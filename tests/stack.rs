@@ -0,0 +1,218 @@
+use dotnetdll::prelude::*;
+use dotnetdll::resolution::stack::max_stack;
+
+fn max(body: body::Method) -> Result<usize, DLLError> {
+    max_stack(&Resolution::new(Module::new("Stack.dll")), &body)
+}
+
+fn with_handler(instructions: Vec<Instruction>, handler: body::Exception) -> body::Method {
+    body::Method {
+        data_sections: vec![body::DataSection::ExceptionHandlers(vec![handler])],
+        ..body::Method::new(instructions)
+    }
+}
+
+#[test]
+pub fn straight_line() {
+    let body = body::Method::new(asm! {
+        LoadConstantInt32 1;
+        LoadConstantInt32 2;
+        LoadConstantInt32 3;
+        Add;
+        Add;
+        Pop;
+        Return;
+    });
+    assert_eq!(max(body).unwrap(), 3);
+    assert_eq!(max(body::Method::new(vec![])).unwrap(), 0);
+}
+
+#[test]
+pub fn conditional() {
+    // the deeper side is the fallthrough
+    let body = body::Method::new(asm! {
+            LoadArgument 0;
+            BranchTruthy shallow;
+            LoadConstantInt32 1;
+            LoadConstantInt32 2;
+            LoadConstantInt32 3;
+            Add;
+            Add;
+            Branch join;
+        @shallow
+            LoadConstantInt32 1;
+        @join
+            Pop;
+            Return;
+    });
+    assert_eq!(max(body).unwrap(), 3);
+
+    // the deeper side is the branch target
+    let body = body::Method::new(asm! {
+            LoadArgument 0;
+            BranchTruthy deep;
+            LoadConstantInt32 1;
+            Branch join;
+        @deep
+            LoadConstantInt32 1;
+            LoadConstantInt32 2;
+            LoadConstantInt32 3;
+            LoadConstantInt32 4;
+            Add;
+            Add;
+            Add;
+        @join
+            Pop;
+            Return;
+    });
+    assert_eq!(max(body).unwrap(), 4);
+}
+
+#[test]
+pub fn switch() {
+    let body = body::Method::new(asm! {
+            LoadArgument 0;
+            Switch vec![first, second];
+            LoadConstantInt32 0;
+            Branch end;
+        @first
+            LoadConstantInt32 1;
+            LoadConstantInt32 2;
+            Add;
+            Branch end;
+        @second
+            LoadConstantInt32 1;
+            LoadConstantInt32 2;
+            LoadConstantInt32 3;
+            Add;
+            Add;
+        @end
+            Pop;
+            Return;
+    });
+    assert_eq!(max(body).unwrap(), 3);
+}
+
+#[test]
+pub fn leave() {
+    // leave empties the stack, so the values left in the try block don't carry over to its target
+    let (instructions, end) = asm! {
+            LoadConstantInt32 1;
+            LoadConstantInt32 2;
+            Leave end;
+            EndFinally;
+        +end
+            LoadConstantInt32 3;
+            Pop;
+            Return;
+    };
+    let body = with_handler(
+        instructions,
+        body::Exception {
+            kind: body::ExceptionKind::Finally,
+            try_offset: 0,
+            try_length: 3,
+            handler_offset: 3,
+            handler_length: end - 3,
+        },
+    );
+    assert_eq!(max(body).unwrap(), 2);
+}
+
+#[test]
+pub fn handlers() {
+    let (instructions, handler, end) = asm! {
+            NoOperation;
+            Leave end;
+        +handler
+            Duplicate;
+            Pop;
+            Pop;
+            Leave end;
+        +end
+            Return;
+    };
+    let catch = body::Exception {
+        kind: body::ExceptionKind::TypedException(ctype! { object }),
+        try_offset: 0,
+        try_length: handler,
+        handler_offset: handler,
+        handler_length: end - handler,
+    };
+
+    // catch handlers start with the exception on the stack
+    assert_eq!(max(with_handler(instructions.clone(), catch.clone())).unwrap(), 2);
+
+    // finally handlers start with an empty stack, so the same handler underflows
+    let finally = body::Exception {
+        kind: body::ExceptionKind::Finally,
+        ..catch
+    };
+    assert!(max(with_handler(instructions, finally)).is_err());
+}
+
+#[test]
+pub fn unreachable() {
+    let body = body::Method::new(asm! {
+        LoadConstantInt32 1;
+        Pop;
+        Return;
+        LoadConstantInt32 1;
+        LoadConstantInt32 2;
+        LoadConstantInt32 3;
+        Pop;
+    });
+    assert_eq!(max(body).unwrap(), 1);
+}
+
+#[test]
+pub fn errors() {
+    let underflow = body::Method::new(asm! {
+        LoadConstantInt32 1;
+        Add;
+        Return;
+    });
+    assert!(max(underflow).is_err());
+
+    let inconsistent = body::Method::new(asm! {
+            LoadArgument 0;
+            BranchTruthy join;
+            LoadConstantInt32 1;
+        @join
+            Return;
+    });
+    assert!(max(inconsistent).is_err());
+
+    let out_of_bounds = body::Method::new(vec![Instruction::Branch(5)]);
+    assert!(max(out_of_bounds).is_err());
+}
+
+#[test]
+pub fn computed_when_writing() {
+    let mut res = Resolution::new(Module::new("Stack.dll"));
+    res.assembly = Some(Assembly::new("Stack"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Stack"));
+    let mut body = body::Method::new(asm! {
+        LoadConstantInt32 1;
+        LoadConstantInt32 2;
+        Add;
+        Pop;
+        Return;
+    });
+    body.header.maximum_stack_size = 100;
+    // tiny headers always have a maximum stack size of 8, so a local forces a fat header
+    body.header.local_variables.push(LocalVariable::new(ctype! { int }));
+    let method = res.push_method(
+        class,
+        Method::new(Accessibility::Public, msig! { static void () }, "M", Some(body)),
+    );
+
+    let bytes = res
+        .write(WriteOptions {
+            compute_max_stack: true,
+            ..WriteOptions::default()
+        })
+        .unwrap();
+    let read = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+    assert_eq!(read[method].body.as_ref().unwrap().header.maximum_stack_size, 2);
+}
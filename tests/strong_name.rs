@@ -0,0 +1,92 @@
+use dotnetdll::prelude::*;
+
+const KEY: &[u8] = include_bytes!("test.snk");
+
+#[test]
+pub fn sign() {
+    let key = StrongNameKey::from_snk(KEY).unwrap();
+
+    let mut res = Resolution::new(Module::new("Signed.dll"));
+    res.assembly = Some(Assembly::new("Signed"));
+    res.push_type_definition(TypeDefinition::new(Some("Signed".into()), "Type"));
+
+    let bytes = res
+        .write(WriteOptions {
+            strong_name_key: Some(key.clone()),
+            ..WriteOptions::default()
+        })
+        .unwrap();
+
+    let dll = DLL::parse(&bytes).unwrap();
+    assert_eq!(dll.cli.flags & 0x8, 0x8);
+    assert_eq!(dll.cli.strong_name_signature.size as usize, key.signature_size());
+    let signature_offset =
+        dll.at_rva(&dll.cli.strong_name_signature).unwrap().as_ptr() as usize - bytes.as_ptr() as usize;
+    assert!(key.verify_image(&bytes, signature_offset).unwrap());
+
+    let reread = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+    let assembly = reread.assembly.unwrap();
+    assert_eq!(assembly.public_key.as_deref(), Some(key.public_key().as_slice()));
+
+    let mut reference = ExternalAssemblyReference::new("Signed");
+    reference.has_full_public_key = true;
+    reference.public_key_or_token = assembly.public_key;
+    assert_eq!(reference.public_key_token(), Some(key.public_key_token()));
+
+    let mut other = Resolution::new(Module::new("Other.dll"));
+    other.assembly = Some(Assembly::new("Other"));
+    other.assembly.as_mut().unwrap().public_key = Some(vec![0; 16].into());
+    assert!(other
+        .write(WriteOptions {
+            strong_name_key: Some(key),
+            ..WriteOptions::default()
+        })
+        .is_err());
+}
+
+#[test]
+pub fn reject_public_key() {
+    let mut public_only = KEY.to_vec();
+    public_only[0] = 0x06;
+    assert!(StrongNameKey::from_snk(&public_only).is_err());
+    assert!(StrongNameKey::from_snk(&KEY[..100]).is_err());
+}
+
+#[test]
+pub fn image_hash() {
+    let key = StrongNameKey::from_snk(KEY).unwrap();
+
+    let mut res = Resolution::new(Module::new("Signed.dll"));
+    res.assembly = Some(Assembly::new("Signed"));
+    let bytes = res
+        .write(WriteOptions {
+            strong_name_key: Some(key.clone()),
+            ..WriteOptions::default()
+        })
+        .unwrap();
+
+    let dll = DLL::parse(&bytes).unwrap();
+    let signature = dll.at_rva(&dll.cli.strong_name_signature).unwrap();
+    let signature_offset = signature.as_ptr() as usize - bytes.as_ptr() as usize;
+    let metadata_offset = dll.at_rva(&dll.cli.metadata).unwrap().as_ptr() as usize - bytes.as_ptr() as usize;
+
+    let read_u16 = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) as usize;
+    let pe_offset = bytes[0x3c] as usize | (bytes[0x3d] as usize) << 8;
+    let section_table = pe_offset + 24 + read_u16(pe_offset + 20);
+    let section_table_end = section_table + 40 * read_u16(pe_offset + 6);
+
+    let verify = |change: usize| {
+        let mut changed = bytes.clone();
+        changed[change] ^= 0xff;
+        key.verify_image(&changed, signature_offset).unwrap()
+    };
+
+    // the checksum and the padding after the section table are not part of the hash
+    assert!(verify(pe_offset + 24 + 64));
+    assert!(verify(section_table_end));
+    // everything else is
+    assert!(!verify(0x40));
+    assert!(!verify(signature_offset));
+    assert!(!verify(section_table_end - 1));
+    assert!(!verify(metadata_offset));
+}
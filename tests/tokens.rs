@@ -0,0 +1,212 @@
+use dotnetdll::binary::heap::{Reader, UserStringReader};
+use dotnetdll::prelude::*;
+
+const RECORD: ReadOptions = ReadOptions {
+    skip_method_bodies: false,
+    record_tokens: true,
+};
+
+fn original() -> Vec<u8> {
+    let mut res = Resolution::new(Module::new("Tokens.dll"));
+    res.assembly = Some(Assembly::new("Tokens"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let console = res.push_type_reference(type_ref! { System.Console in #mscorlib });
+    let console_t: MethodType = BaseType::class(console).into();
+    let write_line = res.push_method_reference(method_ref! { static void @console_t::WriteLine(string) });
+    let out = res.push_field_reference(field_ref! { object @console_t::Out });
+
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    res.push_method(
+        program,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "First",
+            Some(body::Method::new(vec![
+                Instruction::load_string("first"),
+                Instruction::call(write_line),
+                Instruction::load_static_field(out),
+                Instruction::Pop,
+                Instruction::Return,
+            ])),
+        ),
+    );
+    let value = res.push_property(program, Property::new(true, "Value", Parameter::value(ctype! { int })));
+    res.set_property_getter(
+        value,
+        Method::new(
+            Accessibility::Public,
+            msig! { static int () },
+            "get_Value",
+            Some(body::Method::new(vec![
+                Instruction::LoadConstantInt32(1),
+                Instruction::Return,
+            ])),
+        ),
+    );
+
+    res.write(WriteOptions::default()).unwrap()
+}
+
+// adds a method before the property getter, a method reference before the field reference
+// and a string before the existing one, which all move rows when written normally
+fn modify(res: &mut Resolution) -> (MethodIndex, MethodIndex, FieldRefIndex) {
+    let program = res.type_definition_index(1).unwrap();
+    let getter = res
+        .property_getter_index(res.property_index(program, 0).unwrap())
+        .unwrap();
+    let out = res.field_reference_index(0).unwrap();
+
+    let first = res.method_index(program, 0).unwrap();
+    res[first]
+        .body
+        .as_mut()
+        .unwrap()
+        .instructions
+        .splice(0..0, [Instruction::load_string("inserted"), Instruction::Pop]);
+
+    let console_t = res.method_references[0].parent.clone();
+    res.push_method_reference(ExternalMethodReference::new(
+        console_t,
+        "Beep",
+        msig! { static void () },
+    ));
+    let second = res.push_method(
+        program,
+        Method::new(Accessibility::Public, msig! { static void () }, "Second", None),
+    );
+
+    (getter, second, out)
+}
+
+fn first_user_string(bytes: &[u8]) -> String {
+    let dll = DLL::parse(bytes).unwrap();
+    let heap: UserStringReader = dll.get_heap().unwrap();
+    String::from_utf16(&heap.at_index(1).unwrap()).unwrap()
+}
+
+#[test]
+pub fn remapping() {
+    let bytes = original();
+    let mut res = Resolution::parse(&bytes, RECORD).unwrap();
+    modify(&mut res);
+
+    let (written, remapping) = res.write_with_remapping(WriteOptions::default()).unwrap();
+    assert_eq!(
+        remapping.iter().collect::<Vec<_>>(),
+        [(0x0600_0002, 0x0600_0003), (0x0a00_0002, 0x0a00_0003)]
+    );
+    assert_eq!(remapping.remap(0x0600_0001), 0x0600_0001);
+    assert_eq!(first_user_string(&written), "inserted");
+}
+
+#[test]
+pub fn preserve_tokens() {
+    let bytes = original();
+    let mut res = Resolution::parse(&bytes, RECORD).unwrap();
+    let (getter, second, out) = modify(&mut res);
+    let original_tokens = res.original_tokens.clone().unwrap();
+    assert_eq!(original_tokens.method(getter), Some(0x0600_0002));
+    assert_eq!(original_tokens.method(second), None);
+
+    let (written, remapping) = res
+        .write_with_remapping(WriteOptions {
+            preserve_tokens: true,
+            ..WriteOptions::default()
+        })
+        .unwrap();
+    assert!(remapping.is_empty());
+    assert_eq!(first_user_string(&written), "first");
+
+    let reread = Resolution::parse(&written, RECORD).unwrap();
+    let tokens = reread.original_tokens.as_ref().unwrap();
+    assert_eq!(tokens.method(getter), Some(0x0600_0002));
+    assert_eq!(tokens.method(second), Some(0x0600_0003));
+    assert_eq!(tokens.field_reference(out), original_tokens.field_reference(out));
+    assert_eq!(
+        tokens.method_reference(reread.method_reference_index(1).unwrap()),
+        Some(0x0a00_0003)
+    );
+}
+
+// two methods whose bodies use a type spec, a method spec and a local signature each
+// only the second one does at first, so adding them to the first one moves its rows
+fn signatures() -> Vec<u8> {
+    let mut res = Resolution::new(Module::new("Signatures.dll"));
+    res.assembly = Some(Assembly::new("Signatures"));
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    let activator: MethodType =
+        BaseType::class(res.push_type_reference(type_ref! { System.Activator in #mscorlib })).into();
+    let create_instance = res.push_method_reference(method_ref! { static M0 #activator::CreateInstance<1>() });
+
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    res.push_method(
+        program,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "First",
+            Some(body::Method::new(vec![Instruction::Return])),
+        ),
+    );
+    let array: MethodType = ctype! { string[] };
+    let create = GenericMethodInstantiation::new(create_instance, vec![ctype! { string }]);
+    res.push_method(
+        program,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            "Second",
+            Some(body::Method::with_locals(
+                vec![LocalVariable::new(ctype! { int })],
+                asm! {
+                    LoadNull;
+                    cast_class array;
+                    Pop;
+                    call create;
+                    Pop;
+                    Return;
+                },
+            )),
+        ),
+    );
+
+    res.write(WriteOptions::default()).unwrap()
+}
+
+#[test]
+pub fn signature_rows() {
+    let bytes = signatures();
+    let mut res = Resolution::parse(&bytes, RECORD).unwrap();
+
+    let create_instance = res.method_reference_index(0).unwrap();
+    let array: MethodType = ctype! { object[] };
+    let create = GenericMethodInstantiation::new(create_instance, vec![ctype! { object }]);
+    let first = res.method_index(res.type_definition_index(1).unwrap(), 0).unwrap();
+    res[first].body = Some(body::Method::with_locals(
+        vec![LocalVariable::new(ctype! { string })],
+        asm! {
+            LoadNull;
+            cast_class array;
+            Pop;
+            call create;
+            Pop;
+            Return;
+        },
+    ));
+
+    let (_, remapping) = res.write_with_remapping(WriteOptions::default()).unwrap();
+    assert_eq!(
+        remapping.iter().collect::<Vec<_>>(),
+        [
+            (0x1100_0001, 0x1100_0002),
+            (0x1b00_0001, 0x1b00_0002),
+            (0x2b00_0001, 0x2b00_0002)
+        ]
+    );
+
+    // writing it again without changes keeps every row
+    let res = Resolution::parse(&bytes, RECORD).unwrap();
+    let (_, remapping) = res.write_with_remapping(WriteOptions::default()).unwrap();
+    assert!(remapping.is_empty());
+}
@@ -0,0 +1,274 @@
+use dotnetdll::binary::{
+    cli::Metadata,
+    metadata::{header, table},
+    stream,
+};
+use dotnetdll::prelude::*;
+use scroll::{Pread, Pwrite};
+use scroll_buffer::DynamicBuffer;
+
+fn original() -> Vec<u8> {
+    let mut res = Resolution::new(Module::new("Uncompressed.dll"));
+    res.assembly = Some(Assembly::new("Uncompressed"));
+
+    let program = res.push_type_definition(TypeDefinition::new(None, "Program"));
+    for name in ["a", "b", "c"] {
+        res.push_field(
+            program,
+            Field::static_member(Accessibility::Public, name, ctype! { int }),
+        );
+    }
+    for (name, param) in [("First", Some("x")), ("Second", None), ("Third", Some("y"))] {
+        let mut method = Method::new(
+            Accessibility::Public,
+            msig! { static void (int) },
+            name,
+            Some(body::Method::new(vec![Instruction::Return])),
+        );
+        method.parameter_metadata = vec![param.map(ParameterMetadata::name)];
+        res.push_method(program, method);
+    }
+
+    // room for the larger table stream, since the resources come right after the metadata
+    res.manifest_resources.push(resource::ManifestResource {
+        attributes: vec![],
+        name: "padding".into(),
+        visibility: resource::Visibility::Private,
+        implementation: resource::Implementation::CurrentFile(vec![0; 1024].into()),
+    });
+
+    res.write(WriteOptions::default()).unwrap()
+}
+
+fn reverse_through_pointers<T>(rows: &mut [T]) -> Vec<usize> {
+    rows.reverse();
+    (1..=rows.len()).rev().collect()
+}
+
+// stores the fields, methods and parameters in reverse order behind pointer tables, in an uncompressed #- stream
+fn uncompressed(bytes: &[u8]) -> Vec<u8> {
+    with_tables(bytes, |tables| {
+        tables.field_ptr = reverse_through_pointers(&mut tables.field)
+            .into_iter()
+            .map(|r| table::FieldPtr { field: r.into() })
+            .collect();
+        tables.method_ptr = reverse_through_pointers(&mut tables.method_def)
+            .into_iter()
+            .map(|r| table::MethodPtr { method: r.into() })
+            .collect();
+
+        // parameter lists end where the list of the next physical method begins,
+        // so after reversing the methods, each list ends where the one of the method before it began
+        let param_rows = reverse_through_pointers(&mut tables.param);
+        let ends: Vec<_> = std::iter::once(param_rows.len() + 1)
+            .chain(tables.method_def.iter().map(|m| m.param_list.0))
+            .collect();
+        for (m, end) in tables.method_def.iter_mut().zip(ends) {
+            let start = m.param_list.0;
+            m.param_list = (tables.param_ptr.len() + 1).into();
+            tables.param_ptr.extend((start..end).map(|r| table::ParamPtr {
+                param: param_rows[r - 1].into(),
+            }));
+        }
+    })
+}
+
+// writes the tables of a module as changed by f into an uncompressed #- stream, in place of its #~ stream
+fn with_tables(bytes: &[u8], f: impl FnOnce(&mut table::Tables)) -> Vec<u8> {
+    let dll = DLL::parse(bytes).unwrap();
+    let meta = dll.get_cli_metadata().unwrap();
+    let mut header = dll.get_logical_metadata().unwrap();
+
+    header.tables.manifest_resource.clear();
+    f(&mut header.tables);
+    header.valid = header.tables.valid_mask();
+
+    let mut table_stream = DynamicBuffer::with_increment(64);
+    table_stream
+        .pwrite_with(
+            header,
+            0,
+            header::Layout {
+                uncompressed: true,
+                ..header::Layout::default()
+            },
+        )
+        .unwrap();
+
+    let root_start = bytes.windows(4).position(|w| w == b"BSJB").unwrap();
+    let root = &bytes[root_start..];
+    let streams: Vec<(&str, &[u8])> = meta
+        .stream_headers
+        .iter()
+        .map(|h| match h.name {
+            "#~" => ("#-", table_stream.get()),
+            n => (n, &root[h.offset as usize..(h.offset + h.size) as usize]),
+        })
+        .collect();
+
+    let write_root = |buf: &mut [u8], offsets: &[u32]| {
+        buf.pwrite(
+            Metadata {
+                signature: meta.signature,
+                major_version: meta.major_version,
+                minor_version: meta.minor_version,
+                reserved: meta.reserved,
+                version: meta.version,
+                flags: meta.flags,
+                stream_headers: streams
+                    .iter()
+                    .zip(offsets)
+                    .map(|(&(name, s), &offset)| stream::Header {
+                        offset,
+                        size: s.len() as u32,
+                        name,
+                    })
+                    .collect(),
+            },
+            0,
+        )
+        .unwrap()
+    };
+
+    let mut new_root = vec![0_u8; 4096];
+    let mut offset = write_root(&mut new_root, &vec![0; streams.len()]);
+    let mut offsets = vec![];
+    for (_, s) in &streams {
+        offsets.push(offset as u32);
+        new_root[offset..offset + s.len()].copy_from_slice(s);
+        offset += (s.len() + 3) & !3;
+    }
+    write_root(&mut new_root, &offsets);
+
+    let mut modified = bytes.to_vec();
+    modified[root_start..root_start + offset].copy_from_slice(&new_root[..offset]);
+    modified
+}
+
+#[test]
+pub fn pointer_tables() {
+    let bytes = uncompressed(&original());
+    let dll = DLL::parse(&bytes).unwrap();
+    let tables = dll.get_logical_metadata().unwrap().tables;
+    assert_eq!(tables.method_ptr.len(), 3);
+
+    let res = dll.resolve(ReadOptions::default()).unwrap();
+    let program = &res.type_definitions[1];
+    assert_eq!(
+        program.fields.iter().map(|f| f.name.as_ref()).collect::<Vec<_>>(),
+        ["a", "b", "c"]
+    );
+    assert_eq!(
+        program.methods.iter().map(|m| m.name.as_ref()).collect::<Vec<_>>(),
+        ["First", "Second", "Third"]
+    );
+    assert_eq!(
+        program
+            .methods
+            .iter()
+            .map(|m| m.parameter_metadata.first().and_then(|p| p.as_ref()?.name.as_deref()))
+            .collect::<Vec<_>>(),
+        [Some("x"), None, Some("y")]
+    );
+}
+
+// two types with accessors, whose methods are stored out of order behind a method pointer table
+fn accessors() -> Vec<u8> {
+    let mut res = Resolution::new(Module::new("Accessors.dll"));
+    res.assembly = Some(Assembly::new("Accessors"));
+
+    let method = |name: &'static str, signature| {
+        Method::new(
+            Accessibility::Public,
+            signature,
+            name,
+            Some(body::Method::new(vec![Instruction::Return])),
+        )
+    };
+
+    let a = res.push_type_definition(TypeDefinition::new(None, "A"));
+    res.push_method(a, method("A1", msig! { static void () }));
+    res.push_method(a, method("A2", msig! { static void () }));
+    let property = res.push_property(a, Property::new(true, "P", Parameter::value(ctype! { int })));
+    res.set_property_getter(property, method("get_P", msig! { static int () }));
+
+    let b = res.push_type_definition(TypeDefinition::new(None, "B"));
+    res.push_method(b, method("B1", msig! { static void () }));
+    res.push_method(b, method("B2", msig! { static void () }));
+    res.push_event(
+        b,
+        Event::new(
+            "E",
+            ctype! { object },
+            method("add_E", msig! { static void (object) }),
+            method("remove_E", msig! { static void (object) }),
+        ),
+    );
+
+    // room for the larger table stream, since the resources come right after the metadata
+    res.manifest_resources.push(resource::ManifestResource {
+        attributes: vec![],
+        name: "padding".into(),
+        visibility: resource::Visibility::Private,
+        implementation: resource::Implementation::CurrentFile(vec![0; 1024].into()),
+    });
+
+    let bytes = res.write(WriteOptions::default()).unwrap();
+
+    with_tables(&bytes, |tables| {
+        // the original rows in their new physical order, which splits up the methods of both types
+        let order = [6, 0, 4, 2, 5, 1, 3];
+        assert_eq!(tables.method_def.len(), order.len());
+
+        let new_row = |original: usize| order.iter().position(|&o| o == original - 1).unwrap() + 1;
+        tables.method_def = order.iter().map(|&o| tables.method_def[o]).collect();
+        tables.method_ptr = (1..=order.len())
+            .map(|r| table::MethodPtr {
+                method: new_row(r).into(),
+            })
+            .collect();
+        for s in &mut tables.method_semantics {
+            s.method = new_row(s.method.0).into();
+        }
+    })
+}
+
+#[test]
+pub fn accessors_through_pointers() {
+    let bytes = accessors();
+    let res = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+
+    let a = &res.type_definitions[1];
+    assert_eq!(
+        a.methods.iter().map(|m| m.name.as_ref()).collect::<Vec<_>>(),
+        ["A1", "A2"]
+    );
+    assert_eq!(a.properties[0].getter.as_ref().unwrap().name, "get_P");
+
+    let b = &res.type_definitions[2];
+    assert_eq!(
+        b.methods.iter().map(|m| m.name.as_ref()).collect::<Vec<_>>(),
+        ["B1", "B2"]
+    );
+    assert_eq!(b.events[0].add_listener.name, "add_E");
+    assert_eq!(b.events[0].remove_listener.name, "remove_E");
+}
+
+#[test]
+pub fn extra_data() {
+    let dll_bytes = original();
+    let dll = DLL::parse(&dll_bytes).unwrap();
+    let header = dll.get_logical_metadata().unwrap();
+
+    let mut buf = DynamicBuffer::with_increment(64);
+    buf.pwrite_with(header.clone(), 0, header::Layout::default()).unwrap();
+    let mut bytes = buf.get().to_vec();
+
+    bytes[6] |= 0x40;
+    let rows_end = 24 + 4 * header.valid.count_ones() as usize;
+    bytes.splice(rows_end..rows_end, [0xAA; 4]);
+
+    let read: header::Header = bytes.pread_with(0, header::Layout::default()).unwrap();
+    assert_eq!(read.heap_sizes & 0x40, 0x40);
+    assert_eq!(read.tables, header.tables);
+}
@@ -0,0 +1,323 @@
+use dotnetdll::prelude::*;
+use dotnetdll::resolution::verify::{verify, Error, ErrorKind, StackType};
+
+fn errors(
+    signature: ManagedMethod<MethodType>,
+    locals: Vec<LocalVariable>,
+    instructions: Vec<Instruction>,
+    handlers: Vec<body::Exception>,
+) -> Vec<Error> {
+    let mut res = Resolution::new(Module::new("Verify.dll"));
+    res.assembly = Some(Assembly::new("Verify"));
+    let class = res.push_type_definition(TypeDefinition::new(None, "Verify"));
+
+    let mut body = body::Method::new(instructions);
+    body.header.local_variables = locals;
+    if !handlers.is_empty() {
+        body.data_sections.push(body::DataSection::ExceptionHandlers(handlers));
+    }
+    let method = res.push_method(class, Method::new(Accessibility::Public, signature, "M", Some(body)));
+
+    verify(&res, method)
+}
+
+fn simple(instructions: Vec<Instruction>) -> Vec<Error> {
+    errors(msig! { static void () }, vec![], instructions, vec![])
+}
+
+fn error(instruction: usize, kind: ErrorKind) -> Vec<Error> {
+    vec![Error { instruction, kind }]
+}
+
+fn finally(try_offset: usize, try_length: usize, handler_offset: usize, handler_length: usize) -> body::Exception {
+    body::Exception {
+        kind: body::ExceptionKind::Finally,
+        try_offset,
+        try_length,
+        handler_offset,
+        handler_length,
+    }
+}
+
+#[test]
+pub fn valid() {
+    let (instructions, handler, end) = asm! {
+            LoadArgument 0;
+            LoadConstantInt32 1;
+            Add;
+            StoreLocal 0;
+            LoadArgument 1;
+            Pop;
+            Leave end;
+        +handler
+            Pop;
+            Leave end;
+        +end
+            LoadLocal 0;
+            Return;
+    };
+    let catch = body::Exception {
+        kind: body::ExceptionKind::TypedException(ctype! { object }),
+        try_offset: 4,
+        try_length: handler - 4,
+        handler_offset: handler,
+        handler_length: end - handler,
+    };
+
+    assert_eq!(
+        errors(
+            msig! { static int (int, string) },
+            vec![LocalVariable::new(ctype! { int })],
+            instructions,
+            vec![catch],
+        ),
+        []
+    );
+}
+
+#[test]
+pub fn stack_underflow() {
+    assert_eq!(
+        simple(asm! {
+            Pop;
+            Return;
+        }),
+        error(0, ErrorKind::StackUnderflow)
+    );
+}
+
+#[test]
+pub fn stack_mismatch() {
+    let join_depth = errors(
+        msig! { static void (int) },
+        vec![],
+        asm! {
+                LoadArgument 0;
+                BranchTruthy join;
+                LoadConstantInt32 1;
+            @join
+                Return;
+        },
+        vec![],
+    );
+    assert!(
+        matches!(join_depth.as_slice(), [Error { instruction: 1 | 2, kind: ErrorKind::StackMismatch { expected, found } }] if expected.len() != found.len()),
+        "{:?}",
+        join_depth
+    );
+
+    let join_type = errors(
+        msig! { static void (int) },
+        vec![],
+        asm! {
+                LoadArgument 0;
+                BranchTruthy float;
+                LoadConstantInt32 1;
+                Branch join;
+            @float
+                LoadConstantFloat64 1.0;
+            @join
+                Pop;
+                Return;
+        },
+        vec![],
+    );
+    assert!(
+        matches!(join_type.as_slice(), [Error { kind: ErrorKind::StackMismatch { expected, found }, .. }]
+            if [expected, found].contains(&&vec![StackType::Int32]) && [expected, found].contains(&&vec![StackType::Float])),
+        "{:?}",
+        join_type
+    );
+}
+
+#[test]
+pub fn non_empty_stack() {
+    assert_eq!(
+        simple(asm! {
+            LoadConstantInt32 1;
+            Return;
+        }),
+        error(1, ErrorKind::NonEmptyStack(1))
+    );
+}
+
+#[test]
+pub fn invalid_targets() {
+    assert_eq!(
+        simple(vec![Instruction::Branch(10)]),
+        error(0, ErrorKind::InvalidBranchTarget(10))
+    );
+    assert_eq!(simple(vec![Instruction::NoOperation]), error(0, ErrorKind::FallsOffEnd));
+}
+
+#[test]
+pub fn leave_and_endfinally() {
+    assert_eq!(
+        simple(asm! {
+                Leave end;
+            @end
+                Return;
+        }),
+        error(0, ErrorKind::LeaveOutsideProtectedRegion)
+    );
+
+    assert_eq!(
+        simple(vec![Instruction::EndFinally]),
+        error(0, ErrorKind::EndFinallyOutsideHandler)
+    );
+
+    // endfinally in a catch handler is just as invalid
+    let (instructions, handler, end) = asm! {
+            Leave end;
+        +handler
+            Pop;
+            EndFinally;
+        +end
+            Return;
+    };
+    let catch = body::Exception {
+        kind: body::ExceptionKind::TypedException(ctype! { object }),
+        try_offset: 0,
+        try_length: handler,
+        handler_offset: handler,
+        handler_length: end - handler,
+    };
+    assert_eq!(
+        errors(msig! { static void () }, vec![], instructions, vec![catch]),
+        error(2, ErrorKind::EndFinallyOutsideHandler)
+    );
+}
+
+#[test]
+pub fn region_boundaries() {
+    let branch_into = |target| {
+        let (instructions, start, handler, end) = asm! {
+                LoadArgument 0;
+                BranchTruthy target;
+            +start
+                NoOperation;
+                NoOperation;
+                Leave end;
+            +handler
+                EndFinally;
+            +end
+                Return;
+        };
+        errors(
+            msig! { static void (bool) },
+            vec![],
+            instructions,
+            vec![finally(start, handler - start, handler, end - handler)],
+        )
+    };
+    // try blocks can be entered at their first instruction, but nowhere else
+    assert_eq!(branch_into(2), []);
+    assert_eq!(branch_into(3), error(1, ErrorKind::CrossesRegionBoundary(3)));
+    // neither can handlers
+    assert_eq!(branch_into(5), error(1, ErrorKind::CrossesRegionBoundary(5)));
+
+    // branches can't exit try blocks, only leave can
+    let (instructions, handler, end) = asm! {
+            Branch end;
+        +handler
+            EndFinally;
+        +end
+            Return;
+    };
+    assert_eq!(
+        errors(
+            msig! { static void () },
+            vec![],
+            instructions,
+            vec![finally(0, handler, handler, end - handler)],
+        ),
+        error(0, ErrorKind::CrossesRegionBoundary(end))
+    );
+
+    // even in an enclosing try block, leave can't exit a finally handler
+    let (instructions, inner_handler, outer_handler, end) = asm! {
+            Leave end;
+        +inner_handler
+            Leave end;
+        +outer_handler
+            EndFinally;
+        +end
+            Return;
+    };
+    assert_eq!(
+        errors(
+            msig! { static void () },
+            vec![],
+            instructions,
+            vec![
+                finally(0, inner_handler, inner_handler, outer_handler - inner_handler),
+                finally(0, outer_handler, outer_handler, end - outer_handler),
+            ],
+        ),
+        error(1, ErrorKind::CrossesRegionBoundary(end))
+    );
+}
+
+#[test]
+pub fn arguments_and_locals() {
+    assert_eq!(
+        simple(asm! {
+            LoadArgument 0;
+            Pop;
+            Return;
+        }),
+        error(0, ErrorKind::InvalidArgument(0))
+    );
+    assert_eq!(
+        simple(asm! {
+            LoadConstantInt32 0;
+            StoreLocal 1;
+            Return;
+        }),
+        error(1, ErrorKind::InvalidLocal(1))
+    );
+}
+
+#[test]
+pub fn operand_types() {
+    assert_eq!(
+        errors(
+            msig! { static void (string) },
+            vec![LocalVariable::new(ctype! { int })],
+            asm! {
+                LoadArgument 0;
+                StoreLocal 0;
+                Return;
+            },
+            vec![],
+        ),
+        error(
+            1,
+            ErrorKind::TypeMismatch {
+                expected: StackType::Int32,
+                found: StackType::Object(Some(ctype! { string })),
+            }
+        )
+    );
+
+    assert_eq!(
+        simple(asm! {
+            LoadNull;
+            Negate;
+            Pop;
+            Return;
+        }),
+        error(1, ErrorKind::InvalidOperand(StackType::Null))
+    );
+
+    assert_eq!(
+        simple(asm! {
+            LoadConstantInt32 1;
+            LoadConstantInt64 2;
+            Add;
+            Pop;
+            Return;
+        }),
+        error(2, ErrorKind::InvalidOperands(StackType::Int32, StackType::Int64))
+    );
+}
@@ -0,0 +1,267 @@
+use dotnetdll::prelude::*;
+use dotnetdll::win32::*;
+
+fn assembly_attribute(res: &mut Resolution<'static>, mscorlib: AssemblyRefIndex, name: &str, value: &'static str) {
+    let name = format!("{}Attribute", name);
+    let attribute: MethodType = BaseType::class(res.push_type_reference(ExternalTypeReference::new(
+        Some("System.Reflection".into()),
+        name,
+        ResolutionScope::Assembly(mscorlib),
+    )))
+    .into();
+    let ctor = res.push_method_reference(method_ref! { void @attribute::.ctor(string) });
+
+    res.assembly.as_mut().unwrap().attributes.push(Attribute::new(
+        ctor.into(),
+        CustomAttributeData {
+            constructor_args: vec![FixedArg::String(Some(value))],
+            named_args: vec![],
+        },
+    ));
+}
+
+fn round_trip(res: &Resolution, opts: WriteOptions, check: impl FnOnce(&Resources)) {
+    let bytes = res.write(opts).unwrap();
+    let read = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+    assert_eq!(
+        read.win32_resources,
+        DLL::parse(&bytes).unwrap().win32_resources().unwrap()
+    );
+    check(&read.win32_resources);
+}
+
+#[test]
+pub fn version_info() {
+    let mut res = Resolution::new(Module::new("Versioned.dll"));
+    let mut assembly = Assembly::new("Versioned");
+    assembly.version = Version {
+        major: 1,
+        minor: 2,
+        build: 3,
+        revision: 4,
+    };
+    res.assembly = Some(assembly);
+    let mscorlib = res.push_assembly_reference(ExternalAssemblyReference::new("mscorlib"));
+    assembly_attribute(&mut res, mscorlib, "AssemblyTitle", "Versioned library");
+    assembly_attribute(&mut res, mscorlib, "AssemblyCompany", "Example Corp");
+    assembly_attribute(&mut res, mscorlib, "AssemblyFileVersion", "5.6.7");
+    assembly_attribute(&mut res, mscorlib, "AssemblyInformationalVersion", "5.6.7-beta+abcdef");
+
+    let info = VersionInfo::from_resolution(&res);
+    assert_eq!(
+        info.file_version,
+        Version {
+            major: 5,
+            minor: 6,
+            build: 7,
+            revision: 0,
+        }
+    );
+    assert_eq!(info.product_version, info.file_version);
+    assert_eq!(info.file_type, VFT_DLL);
+    let strings = &info.string_tables[0];
+    assert_eq!(strings.get("FileDescription"), Some("Versioned library"));
+    assert_eq!(strings.get("CompanyName"), Some("Example Corp"));
+    assert_eq!(strings.get("FileVersion"), Some("5.6.7"));
+    assert_eq!(strings.get("ProductVersion"), Some("5.6.7-beta+abcdef"));
+    assert_eq!(strings.get("OriginalFilename"), Some("Versioned.dll"));
+    assert_eq!(strings.get("Assembly Version"), Some("1.2.3.4"));
+    assert_eq!(strings.get("ProductName"), None);
+
+    round_trip(&res, WriteOptions::default(), |r| assert!(r.entries.is_empty()));
+
+    res.win32_resources.set_version_info(&info).unwrap();
+    round_trip(&res, WriteOptions::default(), |r| {
+        assert_eq!(r.version_info().unwrap().as_ref(), Some(&info));
+        assert_eq!(r, &res.win32_resources);
+    });
+
+    // strings are padded to 4 bytes, so an odd number of characters checks the alignment
+    let mut odd = info;
+    odd.string_tables[0]
+        .strings
+        .push(("Comments".to_string(), "odd".to_string()));
+    odd.translations.push((0x0409, 1200));
+    assert_eq!(VersionInfo::parse(&odd.write().unwrap()).unwrap(), odd);
+
+    // block lengths are 16-bit
+    let mut large = odd;
+    large.string_tables[0]
+        .strings
+        .push(("Comments".to_string(), "x".repeat(0x8000)));
+    assert!(large.write().is_err());
+    assert!(res.win32_resources.set_version_info(&large).is_err());
+}
+
+// a minimal .ico file with two images, whose data doesn't need to be valid bitmaps for the resources to be stored
+fn ico() -> Vec<u8> {
+    let mut ico = vec![0, 0, 1, 0, 2, 0];
+    ico.extend([16, 16, 0, 0, 1, 0, 32, 0, 3, 0, 0, 0, 38, 0, 0, 0]);
+    ico.extend([0, 0, 0, 0, 1, 0, 32, 0, 5, 0, 0, 0, 41, 0, 0, 0]);
+    ico.extend([1, 2, 3]);
+    ico.extend([4, 5, 6, 7, 8]);
+    ico
+}
+
+#[test]
+pub fn manifest_and_icons() {
+    let mut res = Resolution::new(Module::new("App.exe"));
+    res.assembly = Some(Assembly::new("App"));
+
+    let ico = ico();
+    let group = IconGroup::from_ico(&ico).unwrap();
+    assert_eq!(group.icons.len(), 2);
+    assert_eq!((group.icons[1].width, group.icons[1].bit_count), (0, 32));
+    assert_eq!(group.icons[1].data, [4, 5, 6, 7, 8].as_slice());
+    assert_eq!(group.to_ico().unwrap(), ico);
+
+    let manifest = br#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<assembly xmlns="urn:schemas-microsoft-com:asm.v1" manifestVersion="1.0"/>"#;
+    res.win32_resources
+        .set_manifest(CREATEPROCESS_MANIFEST_RESOURCE_ID, manifest.as_slice());
+    res.win32_resources
+        .add_icon_group(ResourceId::Name("MAINICON".into()), group.clone())
+        .unwrap();
+    res.win32_resources.add_icon_group(2.into(), group.clone()).unwrap();
+
+    let opts = WriteOptions {
+        is_executable: true,
+        ..WriteOptions::default()
+    };
+    round_trip(&res, opts, |read| {
+        assert_eq!(read.manifest(), Some(manifest.as_slice()));
+        assert_eq!(
+            read.entries
+                .iter()
+                .filter(|r| r.kind == ResourceId::Id(RT_ICON))
+                .map(|r| &r.name)
+                .collect::<Vec<_>>(),
+            [&1.into(), &2.into(), &3.into(), &4.into()]
+        );
+
+        // named resources come first in the directory
+        let groups = read.icon_groups().unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0], (&ResourceId::Name("MAINICON".into()), group.clone()));
+        assert_eq!(groups[1], (&2.into(), group.clone()));
+    });
+}
+
+#[test]
+pub fn custom_resources() {
+    let mut resources = Resources::default();
+    for (kind, name, language, data) in [
+        (
+            ResourceId::Name("CUSTOM".into()),
+            ResourceId::Id(1),
+            0x0409,
+            [1].as_slice(),
+        ),
+        (ResourceId::Name("CUSTOM".into()), ResourceId::Id(1), 0x0407, &[2, 3]),
+        (
+            ResourceId::Name("CUSTOM".into()),
+            ResourceId::Name("Ünïcödé".into()),
+            0,
+            &[],
+        ),
+        (ResourceId::Id(10), ResourceId::Name("DATA".into()), 0, &[4; 9]),
+    ] {
+        resources.insert(Resource {
+            kind,
+            name,
+            language,
+            code_page: 1252,
+            data: data.into(),
+        });
+    }
+
+    let rva = 0x4000;
+    let bytes = resources.write(rva).unwrap();
+    let read = Resources::parse(&bytes, rva).unwrap();
+    // entries are read in directory order
+    assert_eq!(
+        read.entries
+            .iter()
+            .map(|r| (&r.name, r.language, r.data.as_ref()))
+            .collect::<Vec<_>>(),
+        [
+            (&ResourceId::Name("Ünïcödé".into()), 0, [].as_slice()),
+            (&1.into(), 0x0407, &[2, 3]),
+            (&1.into(), 0x0409, &[1]),
+            (&ResourceId::Name("DATA".into()), 0, &[4; 9]),
+        ]
+    );
+    assert!(read.entries.iter().all(|r| r.code_page == 1252));
+    assert_eq!(
+        read.get(&ResourceId::Id(10), &ResourceId::Name("DATA".into()))
+            .map(|r| r.data.len()),
+        Some(9)
+    );
+
+    // data is addressed by RVA, so reading at the wrong address fails
+    assert!(Resources::parse(&bytes, rva + 0x1000).is_err());
+
+    resources.entries.push(resources.entries[0].clone());
+    assert!(resources.write(rva).is_err());
+}
+
+#[test]
+pub fn case_insensitive_names() {
+    let resource = |kind: &'static str, name: &'static str, data: &'static [u8]| Resource {
+        kind: ResourceId::Name(kind.into()),
+        name: ResourceId::Name(name.into()),
+        language: 0,
+        code_page: 0,
+        data: data.into(),
+    };
+
+    // like FindResource, names match regardless of case
+    let mut resources = Resources::default();
+    resources.insert(resource("Custom", "Data", &[1]));
+    resources.insert(resource("CUSTOM", "data", &[2]));
+    assert_eq!(resources.entries.len(), 1);
+    assert_eq!(
+        resources
+            .get(&ResourceId::Name("custom".into()), &ResourceId::Name("DATA".into()))
+            .map(|r| r.data.as_ref()),
+        Some([2].as_slice())
+    );
+
+    // and are sorted ignoring case, as the directory requires
+    resources.insert(resource("custom", "b", &[3]));
+    resources.insert(resource("Custom", "C", &[4]));
+    resources.insert(resource("custom", "_", &[5]));
+    let bytes = resources.write(0).unwrap();
+    let read = Resources::parse(&bytes, 0).unwrap();
+    assert_eq!(
+        read.entries.iter().map(|r| r.data.as_ref()).collect::<Vec<_>>(),
+        [[3], [4], [2], [5]]
+    );
+
+    resources.entries.push(resource("CUSTOM", "B", &[6]));
+    assert!(resources.write(0).is_err());
+}
+
+#[test]
+pub fn malformed_directory() {
+    let mut res = Resolution::new(Module::new("Malformed.dll"));
+    res.assembly = Some(Assembly::new("Malformed"));
+    res.win32_resources
+        .set_manifest(ISOLATIONAWARE_MANIFEST_RESOURCE_ID, b"<assembly/>".as_slice());
+    let mut bytes = res.write(WriteOptions::default()).unwrap();
+
+    // the root directory is at the start of the .rsrc section, so claim far more entries than the section holds
+    let header = bytes
+        .windows(8)
+        .position(|w| w == b".rsrc\0\0\0")
+        .expect("could not find .rsrc section header");
+    let raw_data = u32::from_le_bytes(bytes[header + 20..header + 24].try_into().unwrap()) as usize;
+    bytes[raw_data + 14..raw_data + 16].copy_from_slice(&[0xff, 0xff]);
+
+    // only reading the resources themselves fails
+    let dll = DLL::parse(&bytes).unwrap();
+    assert!(dll.win32_resources().is_err());
+    let read = Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+    assert!(read.win32_resources.entries.is_empty());
+    assert_eq!(read.assembly.unwrap().name, "Malformed");
+}
@@ -0,0 +1,151 @@
+use dotnetdll::prelude::*;
+use object::{
+    pe,
+    read::pe::{ImageNtHeaders, ImageOptionalHeader, PeFile32, PeFile64},
+};
+
+fn assembly() -> Resolution<'static> {
+    let mut res = Resolution::new(Module::new("Target.dll"));
+    res.assembly = Some(Assembly::new("Target"));
+    res
+}
+
+#[test]
+pub fn arm64_gui() {
+    let bytes = assembly()
+        .write(WriteOptions {
+            platform: Platform::Arm64,
+            subsystem: Subsystem::Windows,
+            metadata_version: "v4.0.30319".into(),
+            runtime_version: (2, 5),
+            image_base: 0x1_8000_0000,
+            dll_characteristics: DllCharacteristics {
+                high_entropy_va: true,
+                dynamic_base: true,
+                nx_compatible: true,
+                ..DllCharacteristics::default()
+            },
+            time_date_stamp: Some(0x1234_5678),
+            ..WriteOptions::default()
+        })
+        .unwrap();
+
+    let pe = PeFile64::parse(&*bytes).unwrap();
+    let header = pe.nt_headers();
+    assert_eq!(
+        header.file_header().machine.get(object::LittleEndian),
+        pe::IMAGE_FILE_MACHINE_ARM64
+    );
+    assert_eq!(
+        header.file_header().time_date_stamp.get(object::LittleEndian),
+        0x1234_5678
+    );
+    let optional = header.optional_header();
+    assert_eq!(optional.subsystem(), pe::IMAGE_SUBSYSTEM_WINDOWS_GUI);
+    assert_eq!(optional.image_base(), 0x1_8000_0000);
+    assert_eq!(
+        optional.dll_characteristics(),
+        pe::IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA
+            | pe::IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE
+            | pe::IMAGE_DLLCHARACTERISTICS_NX_COMPAT
+    );
+
+    let dll = DLL::parse(&bytes).unwrap();
+    assert_eq!((dll.cli.major_runtime_version, dll.cli.minor_runtime_version), (2, 5));
+    assert_eq!(dll.get_cli_metadata().unwrap().version, "v4.0.30319");
+    Resolution::parse(&bytes, ReadOptions::default()).unwrap();
+}
+
+#[test]
+pub fn any_cpu_32_bit_preferred() {
+    let bytes = assembly()
+        .write(WriteOptions {
+            platform: Platform::AnyCpu32BitPreferred,
+            is_executable: true,
+            ..WriteOptions::default()
+        })
+        .unwrap();
+
+    let pe = PeFile32::parse(&*bytes).unwrap();
+    assert_eq!(
+        pe.nt_headers().file_header().machine.get(object::LittleEndian),
+        pe::IMAGE_FILE_MACHINE_I386
+    );
+
+    let dll = DLL::parse(&bytes).unwrap();
+    assert_eq!(
+        dll.cli.flags,
+        pe::COMIMAGE_FLAGS_ILONLY | pe::COMIMAGE_FLAGS_32BITREQUIRED | pe::COMIMAGE_FLAGS_32BITPREFERRED
+    );
+    assert_eq!(dll.get_cli_metadata().unwrap().version, "Standard CLI 2005");
+}
+
+#[test]
+#[allow(deprecated)]
+pub fn is_32_bit() {
+    let bytes = assembly()
+        .write(WriteOptions {
+            is_32_bit: true,
+            ..WriteOptions::default()
+        })
+        .unwrap();
+
+    // the image that was written before platforms could be selected, i.e. Platform::AnyCpu
+    let pe = PeFile32::parse(&*bytes).unwrap();
+    assert_eq!(
+        pe.nt_headers().file_header().machine.get(object::LittleEndian),
+        pe::IMAGE_FILE_MACHINE_I386
+    );
+    assert_eq!(DLL::parse(&bytes).unwrap().cli.flags, pe::COMIMAGE_FLAGS_ILONLY);
+
+    // a 32-bit platform is kept
+    let bytes = assembly()
+        .write(WriteOptions {
+            platform: Platform::X86,
+            is_32_bit: true,
+            ..WriteOptions::default()
+        })
+        .unwrap();
+    assert_eq!(
+        DLL::parse(&bytes).unwrap().cli.flags,
+        pe::COMIMAGE_FLAGS_ILONLY | pe::COMIMAGE_FLAGS_32BITREQUIRED
+    );
+}
+
+fn program(name: &'static str, mvid: [u8; 16]) -> Resolution<'static> {
+    let mut res = assembly();
+    res.module.mvid = mvid;
+    let program = res.push_type_definition(TypeDefinition::new(Some("Target".into()), "Program"));
+    res.push_method(
+        program,
+        Method::new(
+            Accessibility::Public,
+            msig! { static void () },
+            name,
+            Some(body::Method::new(vec![Instruction::Return])),
+        ),
+    );
+    res
+}
+
+#[test]
+pub fn deterministic() {
+    let opts = || WriteOptions {
+        deterministic: true,
+        ..WriteOptions::default()
+    };
+
+    let (first, first_pdb) = program("Main", [1; 16]).write_with_pdb(opts()).unwrap();
+    let (second, second_pdb) = program("Main", [2; 16]).write_with_pdb(opts()).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(first_pdb, second_pdb);
+
+    let mvid = Resolution::parse_with_pdb(&first, &first_pdb, ReadOptions::default())
+        .unwrap()
+        .module
+        .mvid;
+    let changed = program("Run", [1; 16]).write(opts()).unwrap();
+    let changed_mvid = Resolution::parse(&changed, ReadOptions::default()).unwrap().module.mvid;
+    assert_ne!(mvid, changed_mvid);
+    assert_ne!(mvid, [1; 16]);
+}